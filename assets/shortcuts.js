@@ -0,0 +1,97 @@
+// Binds the keyboard shortcuts listed by the navbar in #keyboard-shortcuts. Routes come from
+// the data attributes so that they are only defined on the server.
+(() => {
+    const SEQUENCE_TIMEOUT_MS = 1000;
+
+    const isTyping = (target) =>
+        target instanceof HTMLElement &&
+        (target.isContentEditable ||
+            ["INPUT", "SELECT", "TEXTAREA"].includes(target.tagName));
+
+    const openPalette = () => {
+        const palette = document.getElementById("command-palette");
+        if (!palette || palette.open) return;
+        palette.showModal();
+        const search = document.getElementById("command-palette-search");
+        search.value = "";
+        search.focus();
+    };
+
+    const runShortcut = (shortcut) => {
+        const focus = shortcut.dataset.focus;
+        const target = focus && document.querySelector(focus);
+        if (target) {
+            target.focus();
+        } else {
+            window.location.href = shortcut.dataset.href;
+        }
+    };
+
+    let pressed = [];
+    let resetTimer;
+
+    document.addEventListener("keydown", (event) => {
+        const shortcuts = document.getElementById("keyboard-shortcuts");
+        if (
+            !shortcuts ||
+            event.ctrlKey ||
+            event.metaKey ||
+            event.altKey ||
+            isTyping(event.target)
+        ) {
+            return;
+        }
+
+        if (event.key === shortcuts.dataset.paletteKey) {
+            event.preventDefault();
+            pressed = [];
+            openPalette();
+            return;
+        }
+
+        clearTimeout(resetTimer);
+        pressed.push(event.key);
+        const keys = pressed.join(" ");
+        const candidates = [...shortcuts.querySelectorAll("[data-keys]")].filter(
+            (shortcut) => shortcut.dataset.keys.startsWith(keys),
+        );
+        const match = candidates.find((shortcut) => shortcut.dataset.keys === keys);
+
+        if (match) {
+            event.preventDefault();
+            pressed = [];
+            runShortcut(match);
+        } else if (candidates.length === 0) {
+            pressed = [];
+        } else {
+            resetTimer = setTimeout(() => (pressed = []), SEQUENCE_TIMEOUT_MS);
+        }
+    });
+
+    // Move between palette results with the arrow keys.
+    document.addEventListener("keydown", (event) => {
+        const palette = document.getElementById("command-palette");
+        if (!palette || !palette.open) return;
+        if (event.key !== "ArrowDown" && event.key !== "ArrowUp") return;
+
+        const results = [...palette.querySelectorAll("[data-palette-result]")];
+        if (results.length === 0) return;
+        event.preventDefault();
+        const index = results.indexOf(document.activeElement);
+        const next =
+            event.key === "ArrowDown"
+                ? Math.min(index + 1, results.length - 1)
+                : Math.max(index - 1, 0);
+        results[next].focus();
+    });
+
+    // Enter in the search box goes to the best result.
+    document.addEventListener("keydown", (event) => {
+        if (event.key !== "Enter" || event.target.id !== "command-palette-search") return;
+        const first = document.querySelector("#command-palette-results [data-palette-result]");
+        if (first) {
+            event.preventDefault();
+            window.location.href = first.getAttribute("href");
+        }
+    });
+})();
@@ -0,0 +1,246 @@
+//! Administrative tasks that are run from the command line instead of the web app, e.g. creating
+//! a user after registration has been closed.
+
+use std::{path::Path, str::FromStr};
+
+use email_address::EmailAddress;
+use rusqlite::{Connection, OpenFlags};
+use thiserror::Error;
+
+use crate::{
+    db::{backup_database, initialize, migrations::get_schema_version, DatabaseError},
+    models::{PasswordError, PasswordHash, User, ValidatedPassword},
+    stores::{UserError, UserStore},
+};
+
+/// Errors that can occur while running an administrative task.
+#[derive(Debug, Error)]
+pub enum AdminError {
+    /// The email address is not valid.
+    #[error("invalid email address: {0}")]
+    InvalidEmail(String),
+
+    /// The password is too weak or could not be hashed.
+    #[error(transparent)]
+    Password(#[from] PasswordError),
+
+    /// The user could not be created, e.g. because the email is already in use.
+    #[error(transparent)]
+    User(#[from] UserError),
+
+    /// The database could not be opened, migrated or copied.
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+
+    /// The database file given does not exist.
+    #[error("the database {0} does not exist")]
+    DatabaseNotFound(String),
+
+    /// A backup would overwrite an existing file.
+    #[error("{0} already exists")]
+    FileExists(String),
+}
+
+/// The schema versions of a database before and after [migrate_database].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SchemaChange {
+    /// The schema version before migrating.
+    pub from: i64,
+    /// The schema version after migrating.
+    pub to: i64,
+}
+
+/// Open the existing database file at `path`.
+///
+/// Unlike [Connection::open], this does not create an empty database if the file is missing, e.g.
+/// because of a typo in the path.
+///
+/// # Errors
+/// Returns an [AdminError::DatabaseNotFound] if there is no file at `path`, or an
+/// [AdminError::Database] if it could not be opened.
+pub fn open_existing_database(path: &Path) -> Result<Connection, AdminError> {
+    if !path.exists() {
+        return Err(AdminError::DatabaseNotFound(path.display().to_string()));
+    }
+
+    let flags = OpenFlags::SQLITE_OPEN_READ_WRITE
+        | OpenFlags::SQLITE_OPEN_URI
+        | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+
+    Connection::open_with_flags(path, flags)
+        .map_err(|error| AdminError::Database(DatabaseError::SqlError(error)))
+}
+
+/// Apply any pending migrations to the database.
+///
+/// # Errors
+/// Returns an [AdminError::Database] if the database is corrupt, from a newer version of the
+/// application, or a migration failed.
+pub fn migrate_database(connection: &Connection) -> Result<SchemaChange, AdminError> {
+    let from = get_schema_version(connection).map_err(DatabaseError::from)?;
+    initialize(connection)?;
+    let to = get_schema_version(connection).map_err(DatabaseError::from)?;
+
+    Ok(SchemaChange { from, to })
+}
+
+/// Create a user with `email` and `password`, hashing the password with `cost`.
+///
+/// This applies the same checks as registering through the web app, but ignores the registration
+/// mode so that an administrator can add users when registration is closed.
+///
+/// # Errors
+/// Returns an [AdminError::InvalidEmail] if the email is not valid, an [AdminError::Password] if
+/// the password is too weak, or an [AdminError::User] if the email is already in use.
+pub fn create_user(
+    store: &mut impl UserStore,
+    email: &str,
+    password: &str,
+    cost: u32,
+) -> Result<User, AdminError> {
+    let email = EmailAddress::from_str(email.trim())
+        .map_err(|error| AdminError::InvalidEmail(error.to_string()))?;
+    let password = ValidatedPassword::new(password)?;
+    let password_hash = PasswordHash::new(password, cost)?;
+
+    Ok(store.create(email, password_hash)?)
+}
+
+/// Copy the database to a new file at `destination`.
+///
+/// # Errors
+/// Returns an [AdminError::FileExists] if there is already a file at `destination`, or an
+/// [AdminError::Database] if the copy failed.
+pub fn backup_to_file(connection: &Connection, destination: &Path) -> Result<(), AdminError> {
+    if destination.exists() {
+        return Err(AdminError::FileExists(destination.display().to_string()));
+    }
+
+    Ok(backup_database(connection, destination)?)
+}
+
+#[cfg(test)]
+mod admin_tests {
+    use std::{
+        env,
+        path::PathBuf,
+        sync::{Arc, Mutex},
+    };
+
+    use rusqlite::Connection;
+
+    use crate::{
+        db::{initialize, migrations::SCHEMA_VERSION},
+        stores::{SQLiteUserStore, UserError, UserStore},
+    };
+
+    use super::{
+        backup_to_file, create_user, migrate_database, open_existing_database, AdminError,
+        SchemaChange,
+    };
+
+    const PASSWORD: &str = "averysafeandsecurepassword";
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!(
+            "budgeteur-admin-{name}-{}.sqlite",
+            rand::random::<u64>()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn get_user_store() -> SQLiteUserStore {
+        let connection = Connection::open_in_memory().unwrap();
+        initialize(&connection).unwrap();
+        SQLiteUserStore::new(Arc::new(Mutex::new(connection)))
+    }
+
+    #[test]
+    fn migrate_reports_schema_change() {
+        let connection = Connection::open_in_memory().unwrap();
+
+        assert_eq!(
+            migrate_database(&connection).unwrap(),
+            SchemaChange {
+                from: 0,
+                to: SCHEMA_VERSION
+            }
+        );
+        assert_eq!(
+            migrate_database(&connection).unwrap(),
+            SchemaChange {
+                from: SCHEMA_VERSION,
+                to: SCHEMA_VERSION
+            }
+        );
+    }
+
+    #[test]
+    fn create_user_succeeds() {
+        let mut store = get_user_store();
+
+        let user = create_user(&mut store, " foo@bar.baz ", PASSWORD, 4).unwrap();
+
+        assert_eq!(user.email().as_str(), "foo@bar.baz");
+        assert!(user.password_hash().verify(PASSWORD).unwrap());
+        assert_eq!(store.get(user.id()).unwrap(), user);
+    }
+
+    #[test]
+    fn create_user_rejects_invalid_details() {
+        let mut store = get_user_store();
+        create_user(&mut store, "foo@bar.baz", PASSWORD, 4).unwrap();
+
+        assert!(matches!(
+            create_user(&mut store, "not an email", PASSWORD, 4),
+            Err(AdminError::InvalidEmail(_))
+        ));
+        assert!(matches!(
+            create_user(&mut store, "bar@baz.qux", "password", 4),
+            Err(AdminError::Password(_))
+        ));
+        assert!(matches!(
+            create_user(&mut store, "foo@bar.baz", PASSWORD, 4),
+            Err(AdminError::User(UserError::DuplicateEmail))
+        ));
+    }
+
+    #[test]
+    fn open_existing_database_does_not_create_file() {
+        let path = temp_path("missing");
+
+        assert!(matches!(
+            open_existing_database(&path),
+            Err(AdminError::DatabaseNotFound(_))
+        ));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn backup_copies_database_and_does_not_overwrite() {
+        let source_path = temp_path("source");
+        let destination = temp_path("destination");
+        let connection = Connection::open(&source_path).unwrap();
+        initialize(&connection).unwrap();
+        let mut store = SQLiteUserStore::new(Arc::new(Mutex::new(connection)));
+        create_user(&mut store, "foo@bar.baz", PASSWORD, 4).unwrap();
+        let connection = open_existing_database(&source_path).unwrap();
+
+        backup_to_file(&connection, &destination).unwrap();
+
+        let email: String = Connection::open(&destination)
+            .unwrap()
+            .query_row("SELECT email FROM user", (), |row| row.get(0))
+            .unwrap();
+        assert_eq!(email, "foo@bar.baz");
+        assert!(matches!(
+            backup_to_file(&connection, &destination),
+            Err(AdminError::FileExists(_))
+        ));
+
+        for path in [source_path, destination] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
@@ -0,0 +1,506 @@
+//! Functions for summarising transactions that are shared between the dashboard and the other
+//! summary pages.
+//!
+//! Weeks and months follow the user's [WeekStart] and month start day, so every page that groups
+//! transactions by week or month should get its date ranges from here so that the pages agree.
+
+use std::{collections::HashMap, ops::RangeInclusive};
+
+use time::{Date, Duration, Month};
+
+use crate::models::{clamp_month_start_day, DatabaseID, Transaction, WeekStart};
+
+/// The money earned and spent over a set of transactions.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Totals {
+    /// The sum of the positive transaction amounts.
+    pub income: f64,
+    /// The sum of the negative transaction amounts.
+    pub expenses: f64,
+    /// The sum of all the transaction amounts.
+    pub net: f64,
+}
+
+/// Sum the income, expenses and net amount of `transactions`.
+pub fn totals(transactions: &[Transaction]) -> Totals {
+    transactions
+        .iter()
+        .fold(Totals::default(), |mut totals, transaction| {
+            let amount = transaction.amount();
+
+            if amount >= 0.0 {
+                totals.income += amount;
+            } else {
+                totals.expenses += amount;
+            }
+
+            totals.net += amount;
+            totals
+        })
+}
+
+/// The transactions that have not been assigned a category.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Uncategorised {
+    /// The number of transactions without a category.
+    pub count: usize,
+    /// The sum of the amounts of the transactions without a category.
+    pub total: f64,
+}
+
+/// Count and sum the transactions in `transactions` that do not have a category.
+pub fn uncategorised(transactions: &[Transaction]) -> Uncategorised {
+    transactions
+        .iter()
+        .filter(|transaction| transaction.category_id().is_none())
+        .fold(
+            Uncategorised::default(),
+            |mut uncategorised, transaction| {
+                uncategorised.count += 1;
+                uncategorised.total += transaction.amount();
+                uncategorised
+            },
+        )
+}
+
+/// Sum the amounts of `transactions` for each category, where `None` is the total of the
+/// transactions without a category.
+///
+/// The totals are sorted from the most negative (i.e., the most spent) to the most positive.
+pub fn totals_by_category(transactions: &[Transaction]) -> Vec<(Option<DatabaseID>, f64)> {
+    let mut totals: HashMap<Option<DatabaseID>, f64> = HashMap::new();
+
+    for transaction in transactions {
+        *totals.entry(transaction.category_id()).or_default() += transaction.amount();
+    }
+
+    let mut totals: Vec<_> = totals.into_iter().collect();
+    totals.sort_by(|(lhs_id, lhs), (rhs_id, rhs)| lhs.total_cmp(rhs).then(lhs_id.cmp(rhs_id)));
+
+    totals
+}
+
+/// The total for a category, named for display.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CategoryTotal {
+    /// The category name, or "Uncategorised" for transactions without a category.
+    pub name: String,
+    /// The sum of the amounts of the category's transactions.
+    pub total: f64,
+}
+
+/// Like [totals_by_category], but with each category named by `category_names`.
+///
+/// Transactions without a category, or with a category missing from `category_names`, are named
+/// "Uncategorised".
+pub fn named_totals_by_category(
+    transactions: &[Transaction],
+    category_names: &HashMap<DatabaseID, String>,
+) -> Vec<CategoryTotal> {
+    totals_by_category(transactions)
+        .into_iter()
+        .map(|(category_id, total)| CategoryTotal {
+            name: category_id
+                .and_then(|id| category_names.get(&id).cloned())
+                .unwrap_or_else(|| "Uncategorised".to_string()),
+            total,
+        })
+        .collect()
+}
+
+/// The transactions on a single day.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DayGroup {
+    /// The date of the transactions.
+    pub date: Date,
+    /// The transactions on `date`.
+    pub transactions: Vec<Transaction>,
+    /// The sum of the amounts of `transactions`.
+    pub subtotal: f64,
+}
+
+/// Group `transactions` by date, keeping the order they are given in.
+///
+/// `transactions` should be sorted by date, otherwise a date may appear in more than one group.
+pub fn group_by_day(transactions: Vec<Transaction>) -> Vec<DayGroup> {
+    let mut groups: Vec<DayGroup> = Vec::new();
+
+    for transaction in transactions {
+        match groups.last_mut() {
+            Some(group) if group.date == *transaction.date() => {
+                group.subtotal += transaction.amount();
+                group.transactions.push(transaction);
+            }
+            _ => groups.push(DayGroup {
+                date: *transaction.date(),
+                subtotal: transaction.amount(),
+                transactions: vec![transaction],
+            }),
+        }
+    }
+
+    groups
+}
+
+/// Get up to `count` of the transactions with the largest amounts, ignoring the sign, in
+/// descending order.
+pub fn largest(transactions: &[Transaction], count: usize) -> Vec<Transaction> {
+    let mut transactions = transactions.to_vec();
+    transactions.sort_by(|lhs, rhs| rhs.amount().abs().total_cmp(&lhs.amount().abs()));
+    transactions.truncate(count);
+
+    transactions
+}
+
+/// The date range covering the week before `today`, including `today`.
+pub fn last_week(today: Date) -> RangeInclusive<Date> {
+    let one_week_ago = match today.checked_sub(Duration::weeks(1)) {
+        Some(date) => date,
+        None => {
+            tracing::warn!(
+                "Could not get date for one week before {today}. Using today's date ({today}) instead."
+            );
+
+            today
+        }
+    };
+
+    one_week_ago..=today
+}
+
+/// The date range from the start of the month containing `today` up to and including `today`.
+///
+/// Months start on `month_start_day`, see [month_range].
+pub fn month_to_date(today: Date, month_start_day: u8) -> RangeInclusive<Date> {
+    let (year, month) = month_containing(today, month_start_day);
+    let start = month_range(year, month, month_start_day)
+        .map(|range| *range.start())
+        .unwrap_or(today);
+
+    start..=today
+}
+
+/// The year and month of the month containing `date`, where months start on `month_start_day`.
+///
+/// A month is named after the calendar month it starts in, e.g. with months starting on the
+/// 15th, 2024-01-10 is in December 2023.
+pub fn month_containing(date: Date, month_start_day: u8) -> (i32, Month) {
+    if date.day() >= clamp_month_start_day(month_start_day) {
+        return (date.year(), date.month());
+    }
+
+    match date.month() {
+        Month::January => (date.year() - 1, Month::December),
+        month => (date.year(), month.previous()),
+    }
+}
+
+/// The date range covering every day of `month` in `year`, or `None` if `year` is out of range.
+///
+/// The month runs from `month_start_day` up to the day before `month_start_day` in the next
+/// month, e.g. the 15th of March to the 14th of April. A `month_start_day` of 1 gives the
+/// calendar month. Days after the 28th are clamped to the 28th so that every month has the day.
+pub fn month_range(year: i32, month: Month, month_start_day: u8) -> Option<RangeInclusive<Date>> {
+    let month_start_day = clamp_month_start_day(month_start_day);
+    let next_year = if month == Month::December {
+        year.checked_add(1)?
+    } else {
+        year
+    };
+
+    let start = Date::from_calendar_date(year, month, month_start_day).ok()?;
+    let end = Date::from_calendar_date(next_year, month.next(), month_start_day)
+        .ok()?
+        .previous_day()?;
+
+    Some(start..=end)
+}
+
+/// The seven days of the week containing `date`, where weeks start on `week_start`.
+pub fn week_range(date: Date, week_start: WeekStart) -> RangeInclusive<Date> {
+    let days_since_start = match week_start {
+        WeekStart::Monday => date.weekday().number_days_from_monday(),
+        WeekStart::Sunday => date.weekday().number_days_from_sunday(),
+    };
+    let start = date
+        .checked_sub(Duration::days(days_since_start.into()))
+        .unwrap_or(date);
+    let end = start.checked_add(Duration::days(6)).unwrap_or(date);
+
+    start..=end
+}
+
+/// The date range covering the seven days up to and including `end`.
+pub fn trailing_seven_days(end: Date) -> RangeInclusive<Date> {
+    let start = end.checked_sub(Duration::days(6)).unwrap_or(end);
+
+    start..=end
+}
+
+#[cfg(test)]
+mod aggregation_tests {
+    use std::collections::HashMap;
+
+    use time::{macros::date, Month};
+
+    use crate::models::{Transaction, UserID, WeekStart};
+
+    use super::{
+        group_by_day, largest, last_week, month_containing, month_range, month_to_date,
+        named_totals_by_category, totals, totals_by_category, trailing_seven_days, uncategorised,
+        week_range, CategoryTotal, DayGroup, Totals, Uncategorised,
+    };
+
+    #[test]
+    fn totals_splits_income_and_expenses() {
+        let user_id = UserID::new(1);
+        let transactions = [
+            Transaction::build(100.0, user_id).finalise(1),
+            Transaction::build(-30.0, user_id).finalise(2),
+            Transaction::build(-20.0, user_id).finalise(3),
+        ];
+
+        let got = totals(&transactions);
+
+        assert_eq!(
+            got,
+            Totals {
+                income: 100.0,
+                expenses: -50.0,
+                net: 50.0
+            }
+        );
+    }
+
+    #[test]
+    fn totals_of_no_transactions_is_zero() {
+        assert_eq!(totals(&[]), Totals::default());
+    }
+
+    #[test]
+    fn uncategorised_skips_transactions_with_category() {
+        let user_id = UserID::new(1);
+        let transactions = [
+            Transaction::build(100.0, user_id).finalise(1),
+            Transaction::build(-30.0, user_id)
+                .category(Some(1))
+                .finalise(2),
+            Transaction::build(-20.0, user_id).finalise(3),
+        ];
+
+        let got = uncategorised(&transactions);
+
+        assert_eq!(
+            got,
+            Uncategorised {
+                count: 2,
+                total: 80.0
+            }
+        );
+    }
+
+    #[test]
+    fn totals_by_category_sorts_by_most_spent() {
+        let user_id = UserID::new(1);
+        let transactions = [
+            Transaction::build(-10.0, user_id)
+                .category(Some(1))
+                .finalise(1),
+            Transaction::build(-50.0, user_id)
+                .category(Some(2))
+                .finalise(2),
+            Transaction::build(-5.0, user_id).finalise(3),
+            Transaction::build(-15.0, user_id)
+                .category(Some(1))
+                .finalise(4),
+        ];
+
+        let got = totals_by_category(&transactions);
+
+        assert_eq!(got, vec![(Some(2), -50.0), (Some(1), -25.0), (None, -5.0)]);
+    }
+
+    #[test]
+    fn named_totals_by_category_names_categories() {
+        let user_id = UserID::new(1);
+        let transactions = [
+            Transaction::build(-10.0, user_id)
+                .category(Some(1))
+                .finalise(1),
+            Transaction::build(-5.0, user_id).finalise(2),
+        ];
+        let category_names = HashMap::from([(1, "Food".to_string())]);
+
+        let got = named_totals_by_category(&transactions, &category_names);
+
+        assert_eq!(
+            got,
+            vec![
+                CategoryTotal {
+                    name: "Food".to_string(),
+                    total: -10.0
+                },
+                CategoryTotal {
+                    name: "Uncategorised".to_string(),
+                    total: -5.0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn largest_ignores_sign() {
+        let user_id = UserID::new(1);
+        let transactions = [
+            Transaction::build(10.0, user_id).finalise(1),
+            Transaction::build(-50.0, user_id).finalise(2),
+            Transaction::build(20.0, user_id).finalise(3),
+        ];
+
+        let got = largest(&transactions, 2);
+
+        assert_eq!(got, vec![transactions[1].clone(), transactions[2].clone()]);
+    }
+
+    #[test]
+    fn group_by_day_sums_each_day() {
+        let user_id = UserID::new(1);
+        let transactions = vec![
+            Transaction::build(10.0, user_id)
+                .date(date!(2024 - 03 - 01))
+                .unwrap()
+                .finalise(1),
+            Transaction::build(-2.5, user_id)
+                .date(date!(2024 - 03 - 01))
+                .unwrap()
+                .finalise(2),
+            Transaction::build(-4.0, user_id)
+                .date(date!(2024 - 03 - 03))
+                .unwrap()
+                .finalise(3),
+        ];
+
+        let got = group_by_day(transactions.clone());
+
+        assert_eq!(
+            got,
+            vec![
+                DayGroup {
+                    date: date!(2024 - 03 - 01),
+                    transactions: transactions[..2].to_vec(),
+                    subtotal: 7.5,
+                },
+                DayGroup {
+                    date: date!(2024 - 03 - 03),
+                    transactions: transactions[2..].to_vec(),
+                    subtotal: -4.0,
+                },
+            ]
+        );
+        assert!(group_by_day(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn month_range_covers_whole_month() {
+        assert_eq!(
+            month_range(2024, Month::February, 1),
+            Some(date!(2024 - 02 - 01)..=date!(2024 - 02 - 29))
+        );
+        assert_eq!(
+            month_range(2023, Month::December, 1),
+            Some(date!(2023 - 12 - 01)..=date!(2023 - 12 - 31))
+        );
+        assert_eq!(month_range(100_000, Month::January, 1), None);
+    }
+
+    #[test]
+    fn month_range_starting_mid_month_spans_year_boundary() {
+        assert_eq!(
+            month_range(2023, Month::December, 15),
+            Some(date!(2023 - 12 - 15)..=date!(2024 - 01 - 14))
+        );
+        assert_eq!(
+            month_range(2024, Month::January, 15),
+            Some(date!(2024 - 01 - 15)..=date!(2024 - 02 - 14))
+        );
+        assert_eq!(
+            month_containing(date!(2024 - 01 - 10), 15),
+            (2023, Month::December)
+        );
+        assert_eq!(
+            month_containing(date!(2024 - 01 - 15), 15),
+            (2024, Month::January)
+        );
+        assert_eq!(
+            month_to_date(date!(2024 - 01 - 10), 15),
+            date!(2023 - 12 - 15)..=date!(2024 - 01 - 10)
+        );
+    }
+
+    #[test]
+    fn month_start_day_after_the_28th_is_clamped() {
+        // February has no 31st, so months that would start on the 31st start on the 28th instead.
+        assert_eq!(
+            month_range(2024, Month::January, 31),
+            Some(date!(2024 - 01 - 28)..=date!(2024 - 02 - 27))
+        );
+        assert_eq!(
+            month_range(2024, Month::February, 31),
+            Some(date!(2024 - 02 - 28)..=date!(2024 - 03 - 27))
+        );
+        assert_eq!(
+            month_containing(date!(2024 - 03 - 31), 31),
+            (2024, Month::March)
+        );
+        assert_eq!(
+            month_containing(date!(2024 - 03 - 27), 31),
+            (2024, Month::February)
+        );
+    }
+
+    #[test]
+    fn week_range_starts_on_week_start() {
+        // 2024-03-06 was a Wednesday.
+        assert_eq!(
+            week_range(date!(2024 - 03 - 06), WeekStart::Monday),
+            date!(2024 - 03 - 04)..=date!(2024 - 03 - 10)
+        );
+        assert_eq!(
+            week_range(date!(2024 - 03 - 06), WeekStart::Sunday),
+            date!(2024 - 03 - 03)..=date!(2024 - 03 - 09)
+        );
+        // A week start day is the first day of its own week.
+        assert_eq!(
+            week_range(date!(2024 - 03 - 10), WeekStart::Sunday),
+            date!(2024 - 03 - 10)..=date!(2024 - 03 - 16)
+        );
+        assert_eq!(
+            week_range(date!(2024 - 03 - 10), WeekStart::Monday),
+            date!(2024 - 03 - 04)..=date!(2024 - 03 - 10)
+        );
+    }
+
+    #[test]
+    fn trailing_seven_days_includes_end() {
+        assert_eq!(
+            trailing_seven_days(date!(2024 - 03 - 05)),
+            date!(2024 - 02 - 28)..=date!(2024 - 03 - 05)
+        );
+    }
+
+    #[test]
+    fn last_week_spans_eight_days() {
+        assert_eq!(
+            last_week(date!(2024 - 03 - 05)),
+            date!(2024 - 02 - 27)..=date!(2024 - 03 - 05)
+        );
+    }
+
+    #[test]
+    fn month_to_date_starts_on_first_of_month() {
+        assert_eq!(
+            month_to_date(date!(2024 - 03 - 05), 1),
+            date!(2024 - 03 - 01)..=date!(2024 - 03 - 05)
+        );
+    }
+}
@@ -16,14 +16,20 @@ use super::AuthError;
 
 pub(crate) const COOKIE_USER_ID: &str = "user_id";
 pub(crate) const COOKIE_EXPIRY: &str = "expiry";
+pub(crate) const COOKIE_SESSION_GENERATION: &str = "session_generation";
 /// The default duration for which auth cookies are valid.
 pub(crate) const COOKIE_DURATION: Duration = Duration::minutes(5);
+/// The default duration for which auth cookies are valid if the user selects "remember me" at log-in.
+pub(crate) const REMEMBER_ME_COOKIE_DURATION: Duration = Duration::days(7);
 
 /// Add an auth cookie to the cookie jar, indicating that a user is logged in and authenticated.
 ///
 /// Sets the initial expiry of the cookie to `duration` from the current time.
 /// You can use [COOKIE_DURATION] for the default duration.
 ///
+/// `session_generation` should be the user's current session generation. The cookie is rejected
+/// by the auth guard once the user's session generation changes.
+///
 /// Returns the cookie jar with the cookie added.
 ///
 /// # Errors
@@ -32,6 +38,7 @@ pub(crate) const COOKIE_DURATION: Duration = Duration::minutes(5);
 pub(crate) fn set_auth_cookie(
     jar: PrivateCookieJar,
     user_id: UserID,
+    session_generation: i64,
     duration: Duration,
 ) -> Result<PrivateCookieJar, time::error::Format> {
     let expiry = OffsetDateTime::now_utc() + duration;
@@ -53,6 +60,13 @@ pub(crate) fn set_auth_cookie(
                 .http_only(true)
                 .same_site(SameSite::Strict)
                 .secure(true),
+        )
+        .add(
+            Cookie::build((COOKIE_SESSION_GENERATION, session_generation.to_string()))
+                .expires(expiry)
+                .http_only(true)
+                .same_site(SameSite::Strict)
+                .secure(true),
         ))
 }
 
@@ -74,6 +88,14 @@ pub(crate) fn invalidate_auth_cookie(jar: PrivateCookieJar) -> PrivateCookieJar
             .same_site(SameSite::Strict)
             .secure(true),
     )
+    .add(
+        Cookie::build((COOKIE_SESSION_GENERATION, "deleted"))
+            .expires(OffsetDateTime::UNIX_EPOCH)
+            .max_age(Duration::ZERO)
+            .http_only(true)
+            .same_site(SameSite::Strict)
+            .secure(true),
+    )
 }
 
 /// Set the expiry of the auth cookie in `jar` to the latest of UTC now
@@ -103,7 +125,7 @@ pub(crate) fn extend_auth_cookie_duration_if_needed(
     set_auth_cookie_expiry(jar, expiry)
 }
 
-/// Sets the expires field of the auth cookie and the expires field and
+/// Sets the expires field of the auth and session generation cookies and the expires field and
 /// value of the expiry cookie in `jar` to `expiry`.
 ///
 /// # Errors
@@ -111,7 +133,7 @@ pub(crate) fn extend_auth_cookie_duration_if_needed(
 /// If an error is returned, the cookie jar is not modified.
 ///
 /// Returns a:
-/// - [AuthError::CookieMissing] if the auth cookie or expiry cookie are not in the cookie jar.
+/// - [AuthError::CookieMissing] if the auth, expiry or session generation cookies are not in the cookie jar.
 /// - [AuthError::DateError] if the new expiry date time cannot be formatted.
 pub(crate) fn set_auth_cookie_expiry(
     jar: PrivateCookieJar,
@@ -123,12 +145,19 @@ pub(crate) fn set_auth_cookie_expiry(
 
     let mut auth_cookie = jar.get(COOKIE_USER_ID).ok_or(AuthError::CookieMissing)?;
     let mut expiry_cookie = jar.get(COOKIE_EXPIRY).ok_or(AuthError::CookieMissing)?;
+    let mut session_generation_cookie = jar
+        .get(COOKIE_SESSION_GENERATION)
+        .ok_or(AuthError::CookieMissing)?;
 
     auth_cookie.set_expires(expiry);
     expiry_cookie.set_expires(expiry);
     expiry_cookie.set_value(expiry_string);
+    session_generation_cookie.set_expires(expiry);
 
-    Ok(jar.add(auth_cookie).add(expiry_cookie))
+    Ok(jar
+        .add(auth_cookie)
+        .add(expiry_cookie)
+        .add(session_generation_cookie))
 }
 
 pub(crate) fn get_user_id_from_auth_cookie(jar: &PrivateCookieJar) -> Result<UserID, AuthError> {
@@ -140,6 +169,48 @@ pub(crate) fn get_user_id_from_auth_cookie(jar: &PrivateCookieJar) -> Result<Use
     }
 }
 
+/// Whether `jar` holds an auth cookie that has not expired.
+///
+/// This does not check the cookie against the user's session generation, so it is only suitable
+/// for deciding what to show, e.g. the navbar on error pages, and not for access control.
+pub(crate) fn has_unexpired_auth_cookie(jar: &PrivateCookieJar) -> bool {
+    get_user_id_from_auth_cookie(jar).is_ok()
+        && get_auth_cookie_expiry(jar).is_ok_and(|expiry| expiry > OffsetDateTime::now_utc())
+}
+
+/// Get the session generation that the auth cookie in `jar` was created with.
+///
+/// # Errors
+///
+/// Returns:
+/// - [AuthError::CookieMissing] if the session generation cookie is not in the cookie jar.
+/// - [AuthError::InvalidCredentials] if the session generation cookie does not hold an integer.
+pub(crate) fn get_session_generation_from_auth_cookie(
+    jar: &PrivateCookieJar,
+) -> Result<i64, AuthError> {
+    jar.get(COOKIE_SESSION_GENERATION)
+        .ok_or(AuthError::CookieMissing)?
+        .value_trimmed()
+        .parse()
+        .map_err(|_| AuthError::InvalidCredentials)
+}
+
+/// Get the date time at which the auth cookie in `jar` expires.
+///
+/// The value of the expiry cookie is used since the client does not send the expires field of
+/// a cookie back to the server.
+///
+/// # Errors
+///
+/// Returns:
+/// - [AuthError::CookieMissing] if the expiry cookie is not in the cookie jar.
+/// - [AuthError::DateError] if the expiry cookie does not hold a valid date time.
+pub(crate) fn get_auth_cookie_expiry(jar: &PrivateCookieJar) -> Result<OffsetDateTime, AuthError> {
+    let expiry_cookie = jar.get(COOKIE_EXPIRY).ok_or(AuthError::CookieMissing)?;
+
+    extract_date_time(&expiry_cookie).map_err(|_| AuthError::DateError)
+}
+
 /// Date time format for the cookie expiry, e.g. "2021-01-01 00:00:00.000000 +00:00:00".
 const DATE_TIME_FORMAT: &[BorrowedFormatItem] = format_description!(
     "[year]-[month]-[day] [hour]:[minute]:[second].[subsecond] [offset_hour \
@@ -169,8 +240,9 @@ mod cookie_tests {
     use crate::{
         auth::{
             cookie::{
-                extract_date_time, extract_user_id, get_user_id_from_auth_cookie, COOKIE_DURATION,
-                COOKIE_EXPIRY, COOKIE_USER_ID, DATE_TIME_FORMAT,
+                extract_date_time, extract_user_id, get_session_generation_from_auth_cookie,
+                get_user_id_from_auth_cookie, COOKIE_DURATION, COOKIE_EXPIRY, COOKIE_USER_ID,
+                DATE_TIME_FORMAT,
             },
             AuthError,
         },
@@ -243,7 +315,7 @@ mod cookie_tests {
         let jar = get_jar();
         let user_id = UserID::new(1);
 
-        let jar = set_auth_cookie(jar, user_id, COOKIE_DURATION).unwrap();
+        let jar = set_auth_cookie(jar, user_id, 0, COOKIE_DURATION).unwrap();
         let user_id_cookie = jar.get(COOKIE_USER_ID).unwrap();
         let expiry_cookie = jar.get(COOKIE_EXPIRY).unwrap();
 
@@ -254,10 +326,29 @@ mod cookie_tests {
         assert_date_time_close!(got_expiry, OffsetDateTime::now_utc() + Duration::minutes(5));
     }
 
+    #[test]
+    fn get_session_generation_from_cookie_succeeds() {
+        let jar = set_auth_cookie(get_jar(), UserID::new(1), 3, COOKIE_DURATION).unwrap();
+
+        assert_eq!(get_session_generation_from_auth_cookie(&jar), Ok(3));
+    }
+
+    #[test]
+    fn get_session_generation_fails_after_invalidation() {
+        let jar = set_auth_cookie(get_jar(), UserID::new(1), 3, COOKIE_DURATION).unwrap();
+
+        let jar = invalidate_auth_cookie(jar);
+
+        assert_eq!(
+            get_session_generation_from_auth_cookie(&jar),
+            Err(AuthError::InvalidCredentials)
+        );
+    }
+
     #[test]
     fn get_user_id_from_cookie_succeeds() {
         let user_id = UserID::new(1);
-        let jar = set_auth_cookie(get_jar(), user_id, COOKIE_DURATION).unwrap();
+        let jar = set_auth_cookie(get_jar(), user_id, 0, COOKIE_DURATION).unwrap();
 
         let retrieved_user_id = get_user_id_from_auth_cookie(&jar).unwrap();
 
@@ -267,7 +358,7 @@ mod cookie_tests {
     #[test]
     fn can_set_cookie_expires() {
         let jar = get_jar();
-        let jar = set_auth_cookie(jar, UserID::new(1), COOKIE_DURATION).unwrap();
+        let jar = set_auth_cookie(jar, UserID::new(1), 0, COOKIE_DURATION).unwrap();
 
         let want = OffsetDateTime::now_utc() + Duration::days(10);
         let updated_jar = set_auth_cookie_expiry(jar, want).unwrap();
@@ -283,7 +374,7 @@ mod cookie_tests {
     #[test]
     fn can_extend_cookie_duration() {
         let jar = get_jar();
-        let jar = set_auth_cookie(jar, UserID::new(1), COOKIE_DURATION).unwrap();
+        let jar = set_auth_cookie(jar, UserID::new(1), 0, COOKIE_DURATION).unwrap();
 
         let initial_cookie = jar.get(COOKIE_EXPIRY).unwrap();
         let want = extract_date_time(&initial_cookie)
@@ -304,7 +395,7 @@ mod cookie_tests {
     #[test]
     fn cookie_duration_does_not_change() {
         let user_id = UserID::new(1);
-        let jar = set_auth_cookie(get_jar(), user_id, COOKIE_DURATION).unwrap();
+        let jar = set_auth_cookie(get_jar(), user_id, 0, COOKIE_DURATION).unwrap();
         let stale_cookie = jar.get(COOKIE_USER_ID).unwrap();
         let want = Some(stale_cookie.expires_datetime().unwrap());
 
@@ -318,7 +409,7 @@ mod cookie_tests {
     #[test]
     fn invalidate_auth_cookie_succeeds() {
         let user_id = UserID::new(1);
-        let jar = set_auth_cookie(get_jar(), user_id, COOKIE_DURATION).unwrap();
+        let jar = set_auth_cookie(get_jar(), user_id, 0, COOKIE_DURATION).unwrap();
 
         let jar = invalidate_auth_cookie(jar);
         let cookie = jar.get(COOKIE_USER_ID).unwrap();
@@ -1,10 +1,15 @@
 //! Data and functions for logging in a user.
 
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+
 use email_address::EmailAddress;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    models::User,
+    models::{PasswordHash, User, ValidatedPassword},
     stores::{UserError, UserStore},
 };
 
@@ -31,6 +36,9 @@ pub struct LogInData {
 
 /// Verify the user `credentials` against the data in the database `connection`.
 ///
+/// `password_hash_cost` should be the bcrypt cost used for new passwords, so that checking the
+/// password of an unknown email takes about as long as checking the password of a registered user.
+///
 /// # Errors
 ///
 /// This function will return an error in a few situations.
@@ -40,6 +48,7 @@ pub struct LogInData {
 pub fn verify_credentials(
     credentials: LogInData,
     store: &impl UserStore,
+    password_hash_cost: u32,
 ) -> Result<User, AuthError> {
     let email: EmailAddress = credentials
         .email
@@ -47,7 +56,12 @@ pub fn verify_credentials(
         .map_err(|_| AuthError::InvalidCredentials)?;
 
     let user = store.get_by_email(&email).map_err(|e| match e {
-        UserError::NotFound => AuthError::InvalidCredentials,
+        UserError::NotFound => {
+            // Check the password anyway so that the response time does not reveal whether the
+            // email belongs to a registered user.
+            let _ = get_dummy_password_hash(password_hash_cost).verify(&credentials.password);
+            AuthError::InvalidCredentials
+        }
         _ => {
             tracing::error!("Error matching user: {e}");
             AuthError::InternalError
@@ -68,13 +82,37 @@ pub fn verify_credentials(
     }
 }
 
+/// Get a password hash with the bcrypt `cost` to verify against when a user does not exist.
+///
+/// Hashing is slow, so the hash for each cost is only created once.
+fn get_dummy_password_hash(cost: u32) -> PasswordHash {
+    static DUMMY_PASSWORD_HASHES: OnceLock<Mutex<HashMap<u32, PasswordHash>>> = OnceLock::new();
+
+    let mut hashes = DUMMY_PASSWORD_HASHES
+        .get_or_init(Default::default)
+        .lock()
+        // The map is only ever added to, so it is still valid if another thread panicked.
+        .unwrap_or_else(|error| error.into_inner());
+
+    hashes
+        .entry(cost)
+        .or_insert_with(|| {
+            PasswordHash::new(
+                ValidatedPassword::new_unchecked("not a real password"),
+                cost,
+            )
+            .expect("Could not hash dummy password")
+        })
+        .clone()
+}
+
 #[cfg(test)]
 mod log_in_tests {
     use email_address::EmailAddress;
 
     use crate::{
         auth::{
-            log_in::{verify_credentials, LogInData},
+            log_in::{get_dummy_password_hash, verify_credentials, LogInData},
             AuthError,
         },
         models::{PasswordHash, User, UserID},
@@ -95,6 +133,15 @@ mod log_in_tests {
             todo!()
         }
 
+        fn create_with_invite(
+            &mut self,
+            _email: email_address::EmailAddress,
+            _password_hash: PasswordHash,
+            _invite_code: &str,
+        ) -> Result<User, UserError> {
+            todo!()
+        }
+
         fn get(&self, _id: UserID) -> Result<User, UserError> {
             todo!()
         }
@@ -106,6 +153,74 @@ mod log_in_tests {
                 .ok_or(UserError::NotFound)
                 .map(|user| user.to_owned())
         }
+
+        fn set_password(
+            &mut self,
+            _id: UserID,
+            _password_hash: PasswordHash,
+        ) -> Result<User, UserError> {
+            todo!()
+        }
+
+        fn increment_session_generation(&mut self, _id: UserID) -> Result<User, UserError> {
+            todo!()
+        }
+
+        fn update_password_hash(
+            &mut self,
+            _id: UserID,
+            _password_hash: PasswordHash,
+        ) -> Result<(), UserError> {
+            todo!()
+        }
+
+        fn set_email(&mut self, _id: UserID, _email: EmailAddress) -> Result<User, UserError> {
+            todo!()
+        }
+
+        fn delete(&mut self, _id: UserID) -> Result<(), UserError> {
+            todo!()
+        }
+
+        fn get_preferences(
+            &self,
+            _user_id: UserID,
+        ) -> Result<crate::models::UserPreferences, UserError> {
+            todo!()
+        }
+
+        fn set_timezone(
+            &mut self,
+            _user_id: UserID,
+            _timezone: &str,
+        ) -> Result<crate::models::UserPreferences, UserError> {
+            todo!()
+        }
+
+        fn set_theme(
+            &mut self,
+            _user_id: UserID,
+            _theme: crate::models::Theme,
+        ) -> Result<crate::models::UserPreferences, UserError> {
+            todo!()
+        }
+
+        fn set_landing_page(
+            &mut self,
+            _user_id: UserID,
+            _landing_page: crate::models::LandingPage,
+        ) -> Result<crate::models::UserPreferences, crate::stores::UserError> {
+            todo!()
+        }
+
+        fn set_date_periods(
+            &mut self,
+            _user_id: UserID,
+            _week_start: crate::models::WeekStart,
+            _month_start_day: u8,
+        ) -> Result<crate::models::UserPreferences, crate::stores::UserError> {
+            todo!()
+        }
     }
 
     #[tokio::test]
@@ -125,7 +240,7 @@ mod log_in_tests {
             remember_me: None,
         };
 
-        assert!(verify_credentials(user_data, &store).is_ok());
+        assert!(verify_credentials(user_data, &store, 4).is_ok());
     }
 
     #[tokio::test]
@@ -137,8 +252,15 @@ mod log_in_tests {
             remember_me: None,
         };
 
-        let result = verify_credentials(user_data, &store);
+        let result = verify_credentials(user_data, &store, 4);
 
         assert!(matches!(result, Err(AuthError::InvalidCredentials)));
     }
+
+    #[test]
+    fn dummy_password_hash_uses_given_cost() {
+        assert_eq!(get_dummy_password_hash(4).cost(), Some(4));
+        assert_eq!(get_dummy_password_hash(5).cost(), Some(5));
+        assert_eq!(get_dummy_password_hash(4), get_dummy_password_hash(4));
+    }
 }
@@ -2,34 +2,61 @@
 
 use axum::{
     extract::{FromRequestParts, Request, State},
-    http::{header::SET_COOKIE, StatusCode, Uri},
+    http::{
+        header::{ACCEPT, AUTHORIZATION, SET_COOKIE, WWW_AUTHENTICATE},
+        HeaderMap, HeaderValue, StatusCode, Uri,
+    },
     middleware::Next,
     response::{IntoResponse, Redirect, Response},
 };
 use axum_extra::extract::PrivateCookieJar;
-use axum_htmx::HxRedirect;
-use time::Duration;
+use axum_htmx::{HxRedirect, HX_REQUEST};
+use time::OffsetDateTime;
 
 use crate::{
-    routes::endpoints,
-    stores::{CategoryStore, TransactionStore, UserStore},
+    db::is_unavailable_error,
+    json_error_response,
+    models::ApiToken,
+    routes::{endpoints, get_database_lock_error_response, get_database_unavailable_response},
+    stores::{
+        sql_store::SQLAppState, ApiTokenStore, CategoryStore, TransactionStore, UserError,
+        UserStore,
+    },
     AppState,
 };
 
-use super::cookie::{extend_auth_cookie_duration_if_needed, get_user_id_from_auth_cookie};
+use super::cookie::{
+    extend_auth_cookie_duration_if_needed, get_auth_cookie_expiry,
+    get_session_generation_from_auth_cookie, get_user_id_from_auth_cookie, invalidate_auth_cookie,
+    COOKIE_EXPIRY, COOKIE_USER_ID,
+};
 
 /// Middleware function that checks for a valid authorization cookie.
-/// The user ID is placed into request and then the request executed normally if the cookie is valid, otherwise a redirect to the log-in page is returned using `get_redirect`.
+/// The user ID is placed into request and then the request executed normally if the cookie is valid, otherwise a redirect to the log-in page is returned.
+///
+/// The redirect depends on who sent the request. HTMX requests, i.e. requests with the `HX-Request`
+/// header, get an HTMX redirect so that HTMX loads the log-in page instead of swapping it into the
+/// target element. API clients, i.e. requests that accept JSON but not HTML, get a 401 response
+/// with a JSON error instead of a redirect. All other requests get a 303 redirect.
+///
+/// If the session has expired, the client is sent to the log-in page with a message saying so.
+/// A session is considered expired if the client sends an expiry cookie with a time in the past,
+/// or if an HTMX request, which can only come from a page that was already loaded, has no valid
+/// auth cookie.
+///
+/// Auth cookies created before the user's session generation last changed, e.g. by logging out
+/// everywhere or changing their password, are treated as expired.
+///
+/// Valid auth cookies are extended so that they last for at least the app state's
+/// `cookie_duration` from the time of the request.
 ///
 /// **Note**: Route handlers can use the function argument `Extension(user_id): Extension<UserID>` to receive the user ID.
 ///
 /// **Note**: The app state must contain an `axum_extra::extract::cookie::Key` for decrypting and verifying the cookie contents.
-#[inline]
-async fn auth_guard_internal<C, T, U>(
-    state: AppState<C, T, U>,
+pub async fn auth_guard<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
     request: Request,
     next: Next,
-    get_redirect: fn() -> Response,
 ) -> Response
 where
     C: CategoryStore + Send + Sync,
@@ -37,24 +64,58 @@ where
     U: UserStore + Send + Sync,
 {
     let (mut parts, body) = request.into_parts();
+    let client = Client::from_headers(&parts.headers);
     let jar = match PrivateCookieJar::from_request_parts(&mut parts, &state).await {
         Ok(jar) => jar,
         Err(err) => {
             tracing::error!("Error getting cookie jar: {err:?}. Redirecting to log in page.");
-            return get_redirect();
+            return get_log_in_redirect(client);
         }
     };
     let user_id = match get_user_id_from_auth_cookie(&jar) {
         Ok(user_id) => user_id,
-        Err(_) => return get_redirect(),
+        Err(_) if client == Client::Htmx || jar.get(COOKIE_EXPIRY).is_some() => {
+            return get_session_expired_redirect(jar, client)
+        }
+        Err(_) => return get_log_in_redirect(client),
+    };
+    match get_auth_cookie_expiry(&jar) {
+        Ok(expiry) if expiry > OffsetDateTime::now_utc() => {}
+        _ => return get_session_expired_redirect(jar, client),
+    }
+    let user = match state.user_store().get(user_id) {
+        Ok(user) => user,
+        Err(UserError::NotFound) => return get_session_expired_redirect(jar, client),
+        Err(UserError::DatabaseLockError) => return get_database_lock_error_response(),
+        Err(UserError::SqlError(error)) if is_unavailable_error(&error) => {
+            return get_database_unavailable_response(&error)
+        }
+        Err(error) => {
+            tracing::error!("Could not get user {user_id}: {error}. Redirecting to log in page.");
+            return get_log_in_redirect(client);
+        }
     };
+    if get_session_generation_from_auth_cookie(&jar) != Ok(user.session_generation()) {
+        return get_session_expired_redirect(jar, client);
+    }
 
     parts.extensions.insert(user_id);
     let request = Request::from_parts(parts, body);
     let response = next.run(request).await;
 
     let (mut parts, body) = response.into_parts();
-    let jar = match extend_auth_cookie_duration_if_needed(jar.clone(), Duration::minutes(5)) {
+    // Handlers that replace or invalidate the auth cookie, e.g. when changing the password, must
+    // not have their cookies overwritten by the stale ones from the request.
+    let handler_set_auth_cookie = parts.headers.get_all(SET_COOKIE).iter().any(|value| {
+        value
+            .to_str()
+            .is_ok_and(|value| value.starts_with(&format!("{COOKIE_USER_ID}=")))
+    });
+    if handler_set_auth_cookie {
+        return Response::from_parts(parts, body);
+    }
+
+    let jar = match extend_auth_cookie_duration_if_needed(jar.clone(), state.cookie_duration) {
         Ok(updated_jar) => updated_jar,
         Err(err) => {
             tracing::error!("Error extending cookie duration: {err:?}. Rolling back cookie jar.");
@@ -72,52 +133,132 @@ where
     Response::from_parts(parts, body)
 }
 
-/// Middleware function that checks for a valid authorization cookie.
-/// The user ID is placed into request and then the request executed normally if the cookie is valid, otherwise a redirect to the log-in page is returned.
+/// Middleware function for the JSON API that accepts either an API token or an auth cookie.
 ///
-/// **Note**: Route handlers can use the function argument `Extension(user_id): Extension<UserID>` to receive the user ID.
+/// Requests with the header `Authorization: Bearer <token>` are authenticated with the API token,
+/// which must exist and not have expired. Such requests get a 401 response with a JSON error if
+/// the token is invalid, even if they also have a valid auth cookie. All other requests are
+/// passed on to [auth_guard].
 ///
-/// **Note**: The app state must contain an `axum_extra::extract::cookie::Key` for decrypting and verifying the cookie contents.
-pub async fn auth_guard<C, T, U>(
-    State(state): State<AppState<C, T, U>>,
-    request: Request,
+/// **Note**: Route handlers can use the function argument `Extension(user_id): Extension<UserID>` to receive the user ID.
+pub async fn api_auth_guard(
+    State(mut state): State<SQLAppState>,
+    mut request: Request,
     next: Next,
-) -> Response
-where
-    C: CategoryStore + Send + Sync,
-    T: TransactionStore + Send + Sync,
-    U: UserStore + Send + Sync,
-{
-    auth_guard_internal(state, request, next, || {
-        Redirect::to(endpoints::LOG_IN).into_response()
-    })
-    .await
+) -> Response {
+    let Some(token) = get_bearer_token(request.headers()) else {
+        return auth_guard(State(state), request, next).await;
+    };
+
+    let now = OffsetDateTime::now_utc();
+    let mut api_token_store = state.api_token_store();
+    let api_token = match api_token_store.get_by_hash(&ApiToken::hash_token(token)) {
+        Ok(api_token) if !api_token.is_expired(now) => api_token,
+        Ok(_) | Err(UserError::NotFound) => return get_invalid_token_response(),
+        Err(error) => {
+            tracing::error!("Could not get API token: {error}");
+            return json_error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "Internal server error",
+            );
+        }
+    };
+
+    if let Err(error) = api_token_store.set_last_used(api_token.id(), now) {
+        tracing::warn!(
+            "Could not update when API token {} was last used: {error}",
+            api_token.id()
+        );
+    }
+
+    request.extensions_mut().insert(api_token.user_id());
+    next.run(request).await
 }
 
-/// Middleware function that checks for a valid authorization cookie.
-/// The user ID is placed into request and then the request executed normally if the cookie is valid, otherwise a HTMX redirect to the log-in page is returned.
-///
-/// **Note**: Route handlers can use the function argument `Extension(user_id): Extension<UserID>` to receive the user ID.
-///
-/// **Note**: The app state must contain an `axum_extra::extract::cookie::Key` for decrypting and verifying the cookie contents.
-pub async fn auth_guard_hx<C, T, U>(
-    State(state): State<AppState<C, T, U>>,
-    request: Request,
-    next: Next,
-) -> Response
-where
-    C: CategoryStore + Send + Sync,
-    T: TransactionStore + Send + Sync,
-    U: UserStore + Send + Sync,
-{
-    auth_guard_internal(state, request, next, || {
-        (
-            HxRedirect(Uri::from_static(endpoints::LOG_IN)),
-            StatusCode::OK,
-        )
-            .into_response()
-    })
-    .await
+/// Get the token from an `Authorization: Bearer <token>` header, if there is one.
+fn get_bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+/// Get a 401 response telling an API client that their token is invalid, expired or revoked.
+fn get_invalid_token_response() -> Response {
+    let mut response = json_error_response(
+        StatusCode::UNAUTHORIZED,
+        "invalid_token",
+        "The API token is invalid, expired or has been revoked.",
+    );
+    response.headers_mut().insert(
+        WWW_AUTHENTICATE,
+        HeaderValue::from_static("Bearer error=\"invalid_token\""),
+    );
+
+    response
+}
+
+/// Who sent a request, which decides how they are sent to the log-in page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Client {
+    /// A plain browser request, e.g. following a link.
+    Browser,
+    /// A request sent by HTMX from a page that is already loaded.
+    Htmx,
+    /// A script or other program that wants JSON rather than HTML.
+    Api,
+}
+
+impl Client {
+    /// Work out who sent a request from its `HX-Request` and `Accept` headers.
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Self {
+        if headers.contains_key(HX_REQUEST) {
+            return Self::Htmx;
+        }
+
+        let accept = headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        if accept.contains("application/json") && !accept.contains("text/html") {
+            Self::Api
+        } else {
+            Self::Browser
+        }
+    }
+}
+
+/// Get a response that sends the client to the log-in page.
+fn get_log_in_redirect(client: Client) -> Response {
+    redirect_to(endpoints::LOG_IN, client)
+}
+
+/// Get a response that invalidates the auth cookies in `jar` and redirects the client to the
+/// log-in page with a message saying that their session has expired.
+fn get_session_expired_redirect(jar: PrivateCookieJar, client: Client) -> Response {
+    (
+        invalidate_auth_cookie(jar),
+        redirect_to(endpoints::LOG_IN_SESSION_EXPIRED, client),
+    )
+        .into_response()
+}
+
+fn redirect_to(uri: &'static str, client: Client) -> Response {
+    match client {
+        Client::Browser => Redirect::to(uri).into_response(),
+        Client::Htmx => (HxRedirect(Uri::from_static(uri)), StatusCode::OK).into_response(),
+        Client::Api => json_error_response(
+            StatusCode::UNAUTHORIZED,
+            "unauthorized",
+            "You must be logged in to access this resource.",
+        ),
+    }
 }
 
 #[cfg(test)]
@@ -126,6 +267,7 @@ mod auth_guard_tests {
 
     use axum::{
         extract::State,
+        http::{HeaderName, HeaderValue},
         middleware,
         routing::{get, post},
         Form, Router,
@@ -140,7 +282,7 @@ mod auth_guard_tests {
 
     use crate::{
         auth::{
-            cookie::{set_auth_cookie, COOKIE_EXPIRY, COOKIE_USER_ID},
+            cookie::{set_auth_cookie, COOKIE_EXPIRY, COOKIE_SESSION_GENERATION, COOKIE_USER_ID},
             log_in::{verify_credentials, LogInData},
             middleware::auth_guard,
             AuthError,
@@ -151,7 +293,8 @@ mod auth_guard_tests {
         },
         routes::endpoints,
         stores::{
-            transaction::TransactionQuery, CategoryStore, TransactionStore, UserError, UserStore,
+            transaction::{BatchOutcome, NewTransaction, TransactionQuery},
+            CategoryStore, TransactionStore, UserError, UserStore,
         },
         AppState,
     };
@@ -171,6 +314,27 @@ mod auth_guard_tests {
         fn get_by_user(&self, _user_id: UserID) -> Result<Vec<Category>, CategoryError> {
             todo!()
         }
+
+        fn search(
+            &self,
+            _user_id: UserID,
+            _prefix: &str,
+            _limit: u64,
+        ) -> Result<Vec<Category>, CategoryError> {
+            todo!()
+        }
+
+        fn suggest(
+            &self,
+
+            _user_id: UserID,
+
+            _description: &str,
+
+            _limit: u64,
+        ) -> Result<Vec<Category>, CategoryError> {
+            todo!()
+        }
     }
 
     #[derive(Clone)]
@@ -192,6 +356,13 @@ mod auth_guard_tests {
             todo!()
         }
 
+        fn create_batch(
+            &mut self,
+            _transactions: Vec<NewTransaction>,
+        ) -> Result<Vec<BatchOutcome>, TransactionError> {
+            todo!()
+        }
+
         fn get(&self, _id: DatabaseID) -> Result<Transaction, TransactionError> {
             todo!()
         }
@@ -206,6 +377,53 @@ mod auth_guard_tests {
         ) -> Result<Vec<Transaction>, TransactionError> {
             todo!()
         }
+
+        fn count_query(&self, _filter: TransactionQuery) -> Result<u64, TransactionError> {
+            todo!()
+        }
+
+        fn sum_query(&self, _filter: TransactionQuery) -> Result<f64, TransactionError> {
+            todo!()
+        }
+
+        fn set_ignored(
+            &mut self,
+            _id: DatabaseID,
+            _ignored: bool,
+        ) -> Result<Transaction, TransactionError> {
+            todo!()
+        }
+
+        fn set_reconciled(&mut self, _ids: &[DatabaseID]) -> Result<(), TransactionError> {
+            todo!()
+        }
+
+        fn update(
+            &mut self,
+            _id: DatabaseID,
+            _builder: TransactionBuilder,
+        ) -> Result<Transaction, TransactionError> {
+            todo!()
+        }
+
+        fn delete(&mut self, _id: DatabaseID) -> Result<(), TransactionError> {
+            todo!()
+        }
+
+        fn get_duplicates(
+            &self,
+            _user_id: UserID,
+        ) -> Result<Vec<Vec<Transaction>>, TransactionError> {
+            todo!()
+        }
+
+        fn merge_duplicates(
+            &mut self,
+            _keep_id: DatabaseID,
+            _duplicate_ids: &[DatabaseID],
+        ) -> Result<Transaction, TransactionError> {
+            todo!()
+        }
     }
 
     #[derive(Clone)]
@@ -222,10 +440,23 @@ mod auth_guard_tests {
             todo!()
         }
 
-        fn get(&self, _id: UserID) -> Result<User, UserError> {
+        fn create_with_invite(
+            &mut self,
+            _email: email_address::EmailAddress,
+            _password_hash: PasswordHash,
+            _invite_code: &str,
+        ) -> Result<User, UserError> {
             todo!()
         }
 
+        fn get(&self, id: UserID) -> Result<User, UserError> {
+            self.users
+                .iter()
+                .find(|user| user.id() == id)
+                .ok_or(UserError::NotFound)
+                .map(|user| user.to_owned())
+        }
+
         fn get_by_email(&self, email: &email_address::EmailAddress) -> Result<User, UserError> {
             self.users
                 .iter()
@@ -233,6 +464,74 @@ mod auth_guard_tests {
                 .ok_or(UserError::NotFound)
                 .map(|user| user.to_owned())
         }
+
+        fn set_password(
+            &mut self,
+            _id: UserID,
+            _password_hash: PasswordHash,
+        ) -> Result<User, UserError> {
+            todo!()
+        }
+
+        fn increment_session_generation(&mut self, _id: UserID) -> Result<User, UserError> {
+            todo!()
+        }
+
+        fn update_password_hash(
+            &mut self,
+            _id: UserID,
+            _password_hash: PasswordHash,
+        ) -> Result<(), UserError> {
+            todo!()
+        }
+
+        fn set_email(&mut self, _id: UserID, _email: EmailAddress) -> Result<User, UserError> {
+            todo!()
+        }
+
+        fn delete(&mut self, _id: UserID) -> Result<(), UserError> {
+            todo!()
+        }
+
+        fn get_preferences(
+            &self,
+            _user_id: UserID,
+        ) -> Result<crate::models::UserPreferences, UserError> {
+            todo!()
+        }
+
+        fn set_timezone(
+            &mut self,
+            _user_id: UserID,
+            _timezone: &str,
+        ) -> Result<crate::models::UserPreferences, UserError> {
+            todo!()
+        }
+
+        fn set_theme(
+            &mut self,
+            _user_id: UserID,
+            _theme: crate::models::Theme,
+        ) -> Result<crate::models::UserPreferences, UserError> {
+            todo!()
+        }
+
+        fn set_landing_page(
+            &mut self,
+            _user_id: UserID,
+            _landing_page: crate::models::LandingPage,
+        ) -> Result<crate::models::UserPreferences, crate::stores::UserError> {
+            todo!()
+        }
+
+        fn set_date_periods(
+            &mut self,
+            _user_id: UserID,
+            _week_start: crate::models::WeekStart,
+            _month_start_day: u8,
+        ) -> Result<crate::models::UserPreferences, crate::stores::UserError> {
+            todo!()
+        }
     }
 
     /// The email address for the test user.
@@ -268,9 +567,16 @@ mod auth_guard_tests {
         jar: PrivateCookieJar,
         Form(user_data): Form<LogInData>,
     ) -> Result<PrivateCookieJar, AuthError> {
-        let user = verify_credentials(user_data, state.user_store())?;
-
-        set_auth_cookie(jar, user.id(), state.cookie_duration).map_err(|_| AuthError::DateError)
+        let password_hash_cost = state.password_hash_cost;
+        let user = verify_credentials(user_data, state.user_store(), password_hash_cost)?;
+
+        set_auth_cookie(
+            jar,
+            user.id(),
+            user.session_generation(),
+            state.cookie_duration,
+        )
+        .map_err(|_| AuthError::DateError)
     }
 
     #[tokio::test]
@@ -297,11 +603,13 @@ mod auth_guard_tests {
         response.assert_status_ok();
         let auth_cookie = response.cookie(COOKIE_USER_ID);
         let expiry_cookie = response.cookie(COOKIE_EXPIRY);
+        let session_generation_cookie = response.cookie(COOKIE_SESSION_GENERATION);
 
         server
             .get("/protected")
             .add_cookie(auth_cookie)
             .add_cookie(expiry_cookie)
+            .add_cookie(session_generation_cookie)
             .await
             .assert_status_ok();
     }
@@ -359,14 +667,16 @@ mod auth_guard_tests {
 
     #[tokio::test]
     async fn auth_guard_extends_valid_cookie_duration() {
+        let mut log_in_state = get_test_app_state();
+        log_in_state.cookie_duration = Duration::seconds(5);
         let mut state = get_test_app_state();
-        state.cookie_duration = Duration::seconds(5);
+        state.cookie_duration = Duration::minutes(10);
 
         let app = Router::new()
             .route("/protected", get(test_handler))
-            .route_layer(middleware::from_fn_with_state(state.clone(), auth_guard))
+            .route_layer(middleware::from_fn_with_state(state, auth_guard))
             .route(endpoints::LOG_IN, post(test_log_in_route))
-            .with_state(state.clone());
+            .with_state(log_in_state);
 
         let server = TestServer::new(app).expect("Could not create test server.");
 
@@ -392,7 +702,96 @@ mod auth_guard_tests {
         let auth_cookie = response.cookie(COOKIE_USER_ID);
         assert_date_time_close!(
             auth_cookie.expires_datetime().unwrap(),
-            response_time + Duration::minutes(5),
+            response_time + Duration::minutes(10),
+        );
+    }
+
+    #[tokio::test]
+    async fn auth_guard_does_not_shorten_cookie_duration() {
+        let mut log_in_state = get_test_app_state();
+        log_in_state.cookie_duration = Duration::days(7);
+        let mut state = get_test_app_state();
+        state.cookie_duration = Duration::minutes(5);
+
+        let app = Router::new()
+            .route("/protected", get(test_handler))
+            .route_layer(middleware::from_fn_with_state(state, auth_guard))
+            .route(endpoints::LOG_IN, post(test_log_in_route))
+            .with_state(log_in_state);
+
+        let server = TestServer::new(app).expect("Could not create test server.");
+
+        let response = server
+            .post(endpoints::LOG_IN)
+            .form(&LogInData {
+                email: EMAIL.to_string(),
+                password: PASSWORD.to_string(),
+                remember_me: Some("on".to_string()),
+            })
+            .await;
+
+        response.assert_status_ok();
+        let response_time = OffsetDateTime::now_utc();
+        let jar = response.cookies();
+
+        let response = server.get("/protected").add_cookies(jar).await;
+
+        response.assert_status_ok();
+        let auth_cookie = response.cookie(COOKIE_USER_ID);
+        assert_date_time_close!(
+            auth_cookie.expires_datetime().unwrap(),
+            response_time + Duration::days(7),
+        );
+    }
+
+    #[tokio::test]
+    async fn get_protected_route_with_expired_session_redirects_with_message() {
+        let mut state = get_test_app_state();
+        state.cookie_duration = Duration::seconds(-5);
+
+        let app = Router::new()
+            .route("/protected", get(test_handler))
+            .route_layer(middleware::from_fn_with_state(state.clone(), auth_guard))
+            .route(endpoints::LOG_IN, post(test_log_in_route))
+            .with_state(state);
+
+        let server = TestServer::new(app).expect("Could not create test server.");
+
+        let response = server
+            .post(endpoints::LOG_IN)
+            .form(&LogInData {
+                email: EMAIL.to_string(),
+                password: PASSWORD.to_string(),
+                remember_me: None,
+            })
+            .await;
+
+        response.assert_status_ok();
+        // Simulate a client that keeps sending cookies after they expire so that the server-side
+        // expiry check is exercised.
+        let mut auth_cookie = response.cookie(COOKIE_USER_ID);
+        auth_cookie.unset_expires();
+        let mut expiry_cookie = response.cookie(COOKIE_EXPIRY);
+        expiry_cookie.unset_expires();
+        let mut session_generation_cookie = response.cookie(COOKIE_SESSION_GENERATION);
+        session_generation_cookie.unset_expires();
+
+        let response = server
+            .get("/protected")
+            .add_cookie(auth_cookie)
+            .add_cookie(expiry_cookie)
+            .add_cookie(session_generation_cookie)
+            .await;
+
+        response.assert_status_see_other();
+        assert_eq!(
+            response.header("location"),
+            endpoints::LOG_IN_SESSION_EXPIRED
+        );
+        assert_eq!(
+            response.cookie(COOKIE_USER_ID).max_age(),
+            Some(Duration::ZERO),
+            "expected the auth cookie to be invalidated"
         );
     }
 
@@ -462,4 +861,101 @@ mod auth_guard_tests {
             .await
             .assert_status_see_other();
     }
+
+    #[tokio::test]
+    async fn get_protected_route_with_stale_session_generation_redirects_with_message() {
+        let log_in_state = get_test_app_state();
+        let mut state = get_test_app_state();
+        state.user_store().users = state
+            .user_store()
+            .users
+            .iter()
+            .map(|user| user.clone().with_session_generation(1))
+            .collect();
+
+        let app = Router::new()
+            .route("/protected", get(test_handler))
+            .route_layer(middleware::from_fn_with_state(state, auth_guard))
+            .route(endpoints::LOG_IN, post(test_log_in_route))
+            .with_state(log_in_state);
+
+        let server = TestServer::new(app).expect("Could not create test server.");
+
+        let response = server
+            .post(endpoints::LOG_IN)
+            .form(&LogInData {
+                email: EMAIL.to_string(),
+                password: PASSWORD.to_string(),
+                remember_me: None,
+            })
+            .await;
+
+        response.assert_status_ok();
+        let jar = response.cookies();
+
+        let response = server.get("/protected").add_cookies(jar).await;
+
+        response.assert_status_see_other();
+        assert_eq!(
+            response.header("location"),
+            endpoints::LOG_IN_SESSION_EXPIRED
+        );
+    }
+
+    #[tokio::test]
+    async fn get_protected_route_without_session_generation_redirects_to_log_in() {
+        let state = get_test_app_state();
+
+        let app = Router::new()
+            .route("/protected", get(test_handler))
+            .route_layer(middleware::from_fn_with_state(state.clone(), auth_guard))
+            .route(endpoints::LOG_IN, post(test_log_in_route))
+            .with_state(state);
+
+        let server = TestServer::new(app).expect("Could not create test server.");
+
+        let response = server
+            .post(endpoints::LOG_IN)
+            .form(&LogInData {
+                email: EMAIL.to_string(),
+                password: PASSWORD.to_string(),
+                remember_me: None,
+            })
+            .await;
+
+        response.assert_status_ok();
+
+        server
+            .get("/protected")
+            .add_cookie(response.cookie(COOKIE_USER_ID))
+            .add_cookie(response.cookie(COOKIE_EXPIRY))
+            .await
+            .assert_status_see_other();
+    }
+
+    #[tokio::test]
+    async fn htmx_request_with_no_auth_cookie_gets_htmx_redirect() {
+        let state = get_test_app_state();
+        let app = Router::new()
+            .route("/protected", post(test_handler))
+            .route_layer(middleware::from_fn_with_state(state.clone(), auth_guard))
+            .with_state(state);
+
+        let server = TestServer::new(app).expect("Could not create test server.");
+
+        let response = server
+            .post("/protected")
+            .add_header(
+                HeaderName::from_static("hx-request"),
+                HeaderValue::from_static("true"),
+            )
+            .await;
+
+        response.assert_status_ok();
+        assert_eq!(
+            response.header("hx-redirect"),
+            endpoints::LOG_IN_SESSION_EXPIRED
+        );
+        assert!(response.maybe_header("location").is_none());
+    }
 }
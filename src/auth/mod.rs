@@ -4,13 +4,14 @@ use axum::{
     body::Body,
     http::StatusCode,
     response::{IntoResponse, Response},
-    Json,
 };
-use serde_json::json;
+
+use crate::json_error_response;
 
 pub mod cookie;
 pub mod log_in;
 pub mod middleware;
+pub mod rate_limit;
 
 /// Errors that can occur when authenticating a user.
 #[derive(Debug, PartialEq)]
@@ -27,19 +28,29 @@ pub enum AuthError {
 
 impl IntoResponse for AuthError {
     fn into_response(self) -> Response<Body> {
-        let (status, error_message) = match self {
-            AuthError::InvalidCredentials => (StatusCode::UNAUTHORIZED, "Invalid credentials"),
-            AuthError::CookieMissing => (StatusCode::UNAUTHORIZED, "Invalid cookie state"),
-            AuthError::DateError => (StatusCode::INTERNAL_SERVER_ERROR, "Invalid date format"),
-            AuthError::InternalError => {
-                (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
-            }
+        let (status, code, error_message) = match self {
+            AuthError::InvalidCredentials => (
+                StatusCode::UNAUTHORIZED,
+                "invalid_credentials",
+                "Invalid credentials",
+            ),
+            AuthError::CookieMissing => (
+                StatusCode::UNAUTHORIZED,
+                "unauthorized",
+                "Invalid cookie state",
+            ),
+            AuthError::DateError => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "Invalid date format",
+            ),
+            AuthError::InternalError => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "Internal server error",
+            ),
         };
 
-        let body = Json(json!({
-            "error": error_message,
-        }));
-
-        (status, body).into_response()
+        json_error_response(status, code, error_message)
     }
 }
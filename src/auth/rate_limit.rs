@@ -0,0 +1,331 @@
+//! Tracks failed log-in attempts so that repeated failures can be blocked for a while.
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+};
+
+use time::{Duration, OffsetDateTime};
+
+/// The default number of failed log-in attempts allowed within [DEFAULT_LOG_IN_WINDOW].
+pub const DEFAULT_MAX_LOG_IN_ATTEMPTS: usize = 5;
+/// The default period of time over which failed log-in attempts are counted.
+pub const DEFAULT_LOG_IN_WINDOW: Duration = Duration::minutes(15);
+
+/// Identifies who or what a failed log-in attempt is counted against.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AttemptKey {
+    /// The email address entered in the log-in form, normalised to lowercase.
+    Email(String),
+    /// The IP address of the client that sent the log-in request.
+    Ip(IpAddr),
+}
+
+impl AttemptKey {
+    /// Get the keys that a log-in attempt for `email` from `ip` should be counted against.
+    pub fn for_log_in(email: &str, ip: Option<IpAddr>) -> Vec<AttemptKey> {
+        let mut keys = vec![AttemptKey::Email(email.trim().to_lowercase())];
+
+        if let Some(ip) = ip {
+            keys.push(AttemptKey::Ip(ip));
+        }
+
+        keys
+    }
+}
+
+/// Counts failed log-in attempts per [AttemptKey] and blocks further attempts once
+/// `max_attempts` failures have been recorded within `window`.
+///
+/// Clones share the same counters, so the limiter can be stored in the app state.
+#[derive(Debug, Clone)]
+pub struct LogInRateLimiter {
+    max_attempts: usize,
+    window: Duration,
+    failures: Arc<Mutex<HashMap<AttemptKey, Vec<OffsetDateTime>>>>,
+}
+
+impl Default for LogInRateLimiter {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_LOG_IN_ATTEMPTS, DEFAULT_LOG_IN_WINDOW)
+    }
+}
+
+impl LogInRateLimiter {
+    /// Create a rate limiter that allows `max_attempts` failed log-in attempts within `window`.
+    pub fn new(max_attempts: usize, window: Duration) -> Self {
+        Self {
+            max_attempts,
+            window,
+            failures: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Reserve a log-in attempt at time `now` against each of `keys` if it is allowed.
+    ///
+    /// The attempt is counted as a failure straight away, so that requests that arrive at the
+    /// same time cannot all get past the limit before their failures are recorded. Call [reset]
+    /// if the log-in succeeds, or [release] if the attempt should not count, e.g. because of an
+    /// internal error.
+    ///
+    /// Failures that have fallen outside of the window are removed for every key, not only for
+    /// `keys`, so the counters do not grow without bound.
+    ///
+    /// [reset]: Self::reset
+    /// [release]: Self::release
+    ///
+    /// # Errors
+    ///
+    /// Returns the time until the next attempt will be allowed if any of the keys have reached the
+    /// maximum number of failed attempts. Nothing is recorded in this case.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lock for the failure counters is already held by the same thread.
+    pub fn reserve_attempt(
+        &self,
+        keys: &[AttemptKey],
+        now: OffsetDateTime,
+    ) -> Result<(), Duration> {
+        let mut failures = self.failures.lock().unwrap();
+
+        failures.retain(|_, timestamps| {
+            timestamps.retain(|timestamp| *timestamp + self.window > now);
+            !timestamps.is_empty()
+        });
+
+        let retry_after = keys
+            .iter()
+            .filter_map(|key| {
+                let timestamps = failures.get(key)?;
+
+                if timestamps.len() < self.max_attempts {
+                    return None;
+                }
+
+                // The attempt is allowed again once enough of the oldest failures fall outside of
+                // the window.
+                let unblocked_at = timestamps[timestamps.len() - self.max_attempts] + self.window;
+                Some(unblocked_at - now)
+            })
+            .max();
+
+        if let Some(retry_after) = retry_after {
+            return Err(retry_after);
+        }
+
+        for key in keys {
+            failures.entry(key.clone()).or_default().push(now);
+        }
+
+        Ok(())
+    }
+
+    /// Remove an attempt reserved at time `reserved_at` with [reserve_attempt](Self::reserve_attempt)
+    /// from each of `keys`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lock for the failure counters is already held by the same thread.
+    pub fn release(&self, keys: &[AttemptKey], reserved_at: OffsetDateTime) {
+        let mut failures = self.failures.lock().unwrap();
+
+        for key in keys {
+            let Some(timestamps) = failures.get_mut(key) else {
+                continue;
+            };
+
+            if let Some(index) = timestamps
+                .iter()
+                .rposition(|timestamp| *timestamp == reserved_at)
+            {
+                timestamps.remove(index);
+            }
+
+            if timestamps.is_empty() {
+                failures.remove(key);
+            }
+        }
+    }
+
+    /// Clear the failed log-in attempts for each of `keys`, e.g. after a successful log-in.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lock for the failure counters is already held by the same thread.
+    pub fn reset(&self, keys: &[AttemptKey]) {
+        let mut failures = self.failures.lock().unwrap();
+
+        for key in keys {
+            failures.remove(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod rate_limit_tests {
+    use std::{
+        net::{IpAddr, Ipv4Addr},
+        sync::{Arc, Barrier},
+        thread,
+    };
+
+    use time::{Duration, OffsetDateTime};
+
+    use super::{AttemptKey, LogInRateLimiter};
+
+    fn get_keys() -> Vec<AttemptKey> {
+        AttemptKey::for_log_in("foo@bar.baz", Some(IpAddr::V4(Ipv4Addr::LOCALHOST)))
+    }
+
+    #[test]
+    fn allows_attempts_below_threshold() {
+        let limiter = LogInRateLimiter::new(3, Duration::minutes(15));
+        let keys = get_keys();
+        let now = OffsetDateTime::now_utc();
+
+        for _ in 0..2 {
+            limiter.reserve_attempt(&keys, now).unwrap();
+        }
+
+        assert_eq!(limiter.reserve_attempt(&keys, now), Ok(()));
+    }
+
+    #[test]
+    fn blocks_burst_of_failures() {
+        let limiter = LogInRateLimiter::new(3, Duration::minutes(15));
+        let keys = get_keys();
+        let now = OffsetDateTime::now_utc();
+
+        for _ in 0..3 {
+            limiter.reserve_attempt(&keys, now).unwrap();
+        }
+
+        assert_eq!(
+            limiter.reserve_attempt(&keys, now),
+            Err(Duration::minutes(15))
+        );
+        assert_eq!(
+            limiter.reserve_attempt(&keys, now + Duration::minutes(10)),
+            Err(Duration::minutes(5))
+        );
+    }
+
+    #[test]
+    fn blocks_parallel_attempts_beyond_threshold() {
+        let limiter = LogInRateLimiter::new(3, Duration::minutes(15));
+        let now = OffsetDateTime::now_utc();
+        let barrier = Arc::new(Barrier::new(10));
+
+        let handles: Vec<_> = (0..10)
+            .map(|_| {
+                let limiter = limiter.clone();
+                let barrier = barrier.clone();
+                thread::spawn(move || {
+                    barrier.wait();
+                    limiter.reserve_attempt(&get_keys(), now).is_ok()
+                })
+            })
+            .collect();
+        let allowed = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .filter(|is_allowed| *is_allowed)
+            .count();
+
+        assert_eq!(allowed, 3);
+    }
+
+    #[test]
+    fn allows_attempts_once_window_passes() {
+        let limiter = LogInRateLimiter::new(3, Duration::minutes(15));
+        let keys = get_keys();
+        let now = OffsetDateTime::now_utc();
+
+        for _ in 0..3 {
+            limiter.reserve_attempt(&keys, now).unwrap();
+        }
+
+        assert_eq!(
+            limiter.reserve_attempt(&keys, now + Duration::minutes(15)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn removes_expired_failures_for_other_keys() {
+        let limiter = LogInRateLimiter::new(3, Duration::minutes(15));
+        let now = OffsetDateTime::now_utc();
+        limiter.reserve_attempt(&get_keys(), now).unwrap();
+
+        let later = now + Duration::minutes(15);
+        limiter
+            .reserve_attempt(&AttemptKey::for_log_in("qux@bar.baz", None), later)
+            .unwrap();
+
+        let failures = limiter.failures.lock().unwrap();
+        assert_eq!(failures.len(), 1);
+        assert!(failures.contains_key(&AttemptKey::Email("qux@bar.baz".to_string())));
+    }
+
+    #[test]
+    fn blocks_email_across_ip_addresses() {
+        let limiter = LogInRateLimiter::new(3, Duration::minutes(15));
+        let now = OffsetDateTime::now_utc();
+
+        for i in 0..3 {
+            let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, i));
+            limiter
+                .reserve_attempt(&AttemptKey::for_log_in("foo@bar.baz", Some(ip)), now)
+                .unwrap();
+        }
+
+        let keys = AttemptKey::for_log_in("FOO@bar.baz", Some(IpAddr::V4(Ipv4Addr::LOCALHOST)));
+        assert!(limiter.reserve_attempt(&keys, now).is_err());
+    }
+
+    #[test]
+    fn blocks_ip_address_across_emails() {
+        let limiter = LogInRateLimiter::new(3, Duration::minutes(15));
+        let ip = Some(IpAddr::V4(Ipv4Addr::LOCALHOST));
+        let now = OffsetDateTime::now_utc();
+
+        for i in 0..3 {
+            limiter
+                .reserve_attempt(&AttemptKey::for_log_in(&format!("{i}@bar.baz"), ip), now)
+                .unwrap();
+        }
+
+        assert!(limiter
+            .reserve_attempt(&AttemptKey::for_log_in("foo@bar.baz", ip), now)
+            .is_err());
+    }
+
+    #[test]
+    fn reset_clears_failures() {
+        let limiter = LogInRateLimiter::new(3, Duration::minutes(15));
+        let keys = get_keys();
+        let now = OffsetDateTime::now_utc();
+
+        for _ in 0..3 {
+            limiter.reserve_attempt(&keys, now).unwrap();
+        }
+        limiter.reset(&keys);
+
+        assert_eq!(limiter.reserve_attempt(&keys, now), Ok(()));
+    }
+
+    #[test]
+    fn release_removes_reserved_attempt() {
+        let limiter = LogInRateLimiter::new(1, Duration::minutes(15));
+        let keys = get_keys();
+        let now = OffsetDateTime::now_utc();
+        limiter.reserve_attempt(&keys, now).unwrap();
+
+        limiter.release(&keys, now);
+
+        assert_eq!(limiter.reserve_attempt(&keys, now), Ok(()));
+        assert!(limiter.reserve_attempt(&keys, now).is_err());
+    }
+}
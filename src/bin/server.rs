@@ -1,129 +1,306 @@
 use std::{
-    env::{self},
-    fs::OpenOptions,
+    fmt::Display,
+    io::{self, BufRead, IsTerminal, Write},
     net::SocketAddr,
-    path::PathBuf,
+    path::Path,
+    process,
     sync::{Arc, Mutex},
 };
 
-use axum::{
-    extract::{MatchedPath, Request},
-    Router,
-};
+use axum::middleware;
 use axum_server::{tls_rustls::RustlsConfig, Handle};
 use clap::Parser;
 use rusqlite::Connection;
-use tower_http::trace::TraceLayer;
+use time::{Duration, OffsetDateTime};
 
 #[cfg(debug_assertions)]
 use tower_livereload::LiveReloadLayer;
 
-use tracing_subscriber::{filter, layer::SubscriberExt, util::SubscriberInitExt, Layer};
-
 use budgeteur_rs::{
-    build_router, graceful_shutdown,
-    stores::{SQLiteCategoryStore, SQLiteTransactionStore, SQLiteUserStore},
-    AppState,
+    admin::{backup_to_file, create_user, migrate_database, open_existing_database},
+    auth::rate_limit::LogInRateLimiter,
+    build_router,
+    config::{Cli, Command, Config, Listener},
+    db::{
+        backup_database, check_database_path, close_database, get_backup_file_name,
+        initialize_with_options, maintenance::IntegrityCheck, InitializeOptions,
+    },
+    demo::{seed_demo_data, DEMO_EMAIL, DEMO_PASSWORD},
+    filters::set_money_format,
+    graceful_shutdown,
+    https::{build_https_redirect_router, hsts_middleware},
+    logging::{init_logging, logging_middleware},
+    scheduled_backups::{run_scheduled_backups, BackupStatus},
+    stores::{sql_store::build_app_state, SQLiteUserStore},
 };
 
-/// The REST API server for budgeteur_rs.
-#[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
-struct Args {
-    /// File path to the application SQLite database.
-    #[arg(long)]
-    db_path: String,
-
-    /// File path to an SSL certificate `cert.pem` and key `key.pem`.
-    #[arg(long)]
-    cert_path: String,
-
-    /// The port to serve the API from.
-    #[arg(short, long, default_value_t = 3000)]
-    port: u16,
-}
-
 #[tokio::main]
 async fn main() {
-    setup_logging();
+    match Cli::parse().command {
+        Command::Serve(config) => serve(config).await,
+        Command::Migrate { db_path } => migrate(&db_path),
+        Command::CreateUser {
+            db_path,
+            email,
+            password_hash_cost,
+        } => create_user_from_stdin(&db_path, &email, password_hash_cost),
+        Command::Backup { db_path, path } => backup(&db_path, &path),
+    }
+}
+
+/// Print `message` and exit with a non-zero status, instead of panicking with a backtrace.
+fn exit_with_error(message: impl Display) -> ! {
+    eprintln!("error: {message}");
+    process::exit(1);
+}
+
+fn migrate(db_path: &Path) {
+    let conn = open_existing_database(db_path).unwrap_or_else(|error| exit_with_error(error));
+    let change = migrate_database(&conn).unwrap_or_else(|error| exit_with_error(error));
 
-    let args = Args::parse();
+    if change.from == change.to {
+        println!("The database is up to date at schema version {}", change.to);
+    } else {
+        println!(
+            "Migrated the database from schema version {} to {}",
+            change.from, change.to
+        );
+    }
+}
+
+fn create_user_from_stdin(db_path: &Path, email: &str, password_hash_cost: u32) {
+    let password = read_password().unwrap_or_else(|error| exit_with_error(error));
+
+    let conn = Connection::open(db_path).unwrap_or_else(|error| exit_with_error(error));
+    migrate_database(&conn).unwrap_or_else(|error| exit_with_error(error));
+    let mut user_store = SQLiteUserStore::new(Arc::new(Mutex::new(conn)));
+
+    let user = create_user(&mut user_store, email, &password, password_hash_cost)
+        .unwrap_or_else(|error| exit_with_error(error));
+    println!(
+        "Created the user {} with the ID {}",
+        user.email(),
+        user.id()
+    );
+}
 
-    let addr = SocketAddr::from(([127, 0, 0, 1], args.port));
+/// Read a password from standard input, asking for it twice if a person is typing it in.
+fn read_password() -> Result<String, String> {
+    let stdin = io::stdin();
+    let is_terminal = stdin.is_terminal();
+    let mut lines = stdin.lock().lines();
+    let mut read_line = |prompt: &str| -> Result<String, String> {
+        if is_terminal {
+            eprint!("{prompt}");
+            io::stderr().flush().map_err(|error| error.to_string())?;
+        }
 
-    let tls_config = RustlsConfig::from_pem_file(
-        PathBuf::from(&args.cert_path).join("cert.pem"),
-        PathBuf::from(&args.cert_path).join("key.pem"),
-    )
-    .await
-    .expect("Could not open TLS certificates.");
+        match lines.next() {
+            Some(line) => line.map_err(|error| error.to_string()),
+            None => Err("expected a password on standard input".to_owned()),
+        }
+    };
 
-    let secret = env::var("SECRET").expect("The environment variable 'SECRET' must be set");
+    let password = read_line("Password (shown as you type): ")?;
 
-    let conn = Connection::open(&args.db_path).unwrap();
+    if is_terminal && read_line("Confirm password: ")? != password {
+        return Err("the passwords do not match".to_owned());
+    }
+
+    Ok(password)
+}
+
+fn backup(db_path: &Path, destination: &Path) {
+    let conn = open_existing_database(db_path).unwrap_or_else(|error| exit_with_error(error));
+    backup_to_file(&conn, destination).unwrap_or_else(|error| exit_with_error(error));
+
+    println!("Backed up the database to {}", destination.display());
+}
+
+async fn serve(args: Config) {
+    init_logging(args.log_format);
+
+    let addr = args.address();
+
+    let tls_config = match args.listener() {
+        Listener::Tls { cert_path } => Some(
+            RustlsConfig::from_pem_file(cert_path.join("cert.pem"), cert_path.join("key.pem"))
+                .await
+                .unwrap_or_else(|error| {
+                    exit_with_error(format!("could not open the TLS certificates: {error}"))
+                }),
+        ),
+        Listener::PlainHttp => None,
+    };
+
+    let secret = match args.cookie_secret() {
+        Ok(Some(secret)) => secret,
+        // Sessions do not outlive a demo, so a random secret is fine.
+        Ok(None) if args.demo => rand::random::<u64>().to_string(),
+        Ok(None) => exit_with_error(
+            "a secret must be set with --secret, --secret-file or the SECRET environment variable",
+        ),
+        Err(error) => exit_with_error(format!("could not read the secret file: {error}")),
+    };
+
+    if let Some(db_path) = &args.db_path {
+        check_database_path(Path::new(db_path)).unwrap_or_else(|error| {
+            exit_with_error(format!("cannot use the database at {db_path}: {error}"))
+        });
+    }
+    let conn = match &args.db_path {
+        Some(db_path) => Connection::open(db_path),
+        None => Connection::open_in_memory(),
+    }
+    .unwrap_or_else(|error| exit_with_error(format!("could not open the database: {error}")));
+    if let Some(backup_dir) = &args.backup_dir {
+        let backup_path = backup_dir.join(get_backup_file_name(OffsetDateTime::now_utc()));
+        backup_database(&conn, &backup_path).unwrap_or_else(|error| {
+            exit_with_error(format!("could not back up the database: {error}"))
+        });
+        tracing::info!("Backed up the database to {}", backup_path.display());
+    }
+    let options = InitializeOptions {
+        integrity_check: if args.full_integrity_check {
+            IntegrityCheck::Full
+        } else {
+            IntegrityCheck::Quick
+        },
+        ignore_integrity_errors: args.ignore_integrity_errors,
+    };
+    initialize_with_options(&conn, options).unwrap_or_else(|error| {
+        exit_with_error(format!("could not initialize the database: {error}"))
+    });
+    if args.demo {
+        seed_demo_data(&conn, OffsetDateTime::now_utc().date()).unwrap_or_else(|error| {
+            exit_with_error(format!("could not create the demo data: {error}"))
+        });
+        tracing::info!(
+            "Running in demo mode, log in as {DEMO_EMAIL} with the password {DEMO_PASSWORD}"
+        );
+    }
     let conn = Arc::new(Mutex::new(conn));
-    let app_config = AppState::new(
-        &secret,
-        SQLiteCategoryStore::new(conn.clone()),
-        SQLiteTransactionStore::new(conn.clone()),
-        SQLiteUserStore::new(conn.clone()),
+    let mut app_config = build_app_state(conn.clone(), &secret);
+    app_config.log_in_rate_limiter = LogInRateLimiter::new(
+        args.max_log_in_attempts,
+        Duration::minutes(args.log_in_window_minutes),
     );
+    app_config.registration_mode = args.registration;
+    app_config.password_hash_cost = args.password_hash_cost;
+    app_config.request_timeout = std::time::Duration::from_secs(args.request_timeout_seconds);
+    app_config.max_request_body_size = args.max_request_body_size;
+    app_config.suspicious_transaction_age = args.suspicious_transaction_age();
+    app_config.proxy_settings = args.proxy_settings();
+    app_config.demo_mode = args.demo;
+    set_money_format(args.money_format());
 
+    let (stop_backups, backups_stopped) = tokio::sync::oneshot::channel::<()>();
+    let backup_task = args.backup_schedule().map(|schedule| {
+        tracing::info!(
+            "Backing up the database to {} every {} minutes",
+            schedule.directory.display(),
+            args.scheduled_backup_interval_minutes
+        );
+        app_config.backup_status = BackupStatus::new(schedule.interval);
+
+        tokio::spawn(run_scheduled_backups(
+            SQLiteUserStore::new(conn.clone()),
+            schedule,
+            app_config.backup_status.clone(),
+            async {
+                let _ = backups_stopped.await;
+            },
+        ))
+    });
+
+    let operations = app_config.operations.clone();
+    let grace_period = std::time::Duration::from_secs(args.shutdown_grace_period_seconds);
     let handle = Handle::new();
-    tokio::spawn(graceful_shutdown(handle.clone()));
+    tokio::spawn(graceful_shutdown(handle.clone(), grace_period));
+
+    let mut router = build_router(app_config);
+    if let Some(max_age) = args.hsts_max_age {
+        router = router.layer(middleware::from_fn_with_state(max_age, hsts_middleware));
+    }
+    let router = router.layer(middleware::from_fn_with_state(
+        args.request_log_settings(),
+        logging_middleware,
+    ));
+
+    if let Some(http_port) = args.http_redirect_port {
+        let http_addr = SocketAddr::new(args.bind_address, http_port);
+        let redirect_router = build_https_redirect_router(args.port);
+        let handle = handle.clone();
 
-    let router = add_tracing_layer(build_router(app_config));
+        tracing::info!("HTTP redirect server listening on {}", http_addr);
+        tokio::spawn(async move {
+            axum_server::bind(http_addr)
+                .handle(handle)
+                .serve(redirect_router.into_make_service())
+                .await
+                .expect("Could not start the HTTP redirect server");
+        });
+    }
 
     #[cfg(debug_assertions)]
     let router = router.layer(LiveReloadLayer::new());
 
-    tracing::info!("HTTPS server listening on {}", addr);
-    axum_server::bind_rustls(addr, tls_config)
-        .handle(handle)
-        .serve(router.into_make_service())
-        .await
-        .unwrap();
-}
-
-fn setup_logging() {
-    let stdout_log = tracing_subscriber::fmt::layer().pretty();
-
-    let log_file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open("debug.log")
-        .expect("Could not create log file");
-
-    let debug_log = tracing_subscriber::fmt::layer()
-        .pretty()
-        .with_writer(Arc::new(log_file));
-
-    tracing_subscriber::registry()
-        .with(
-            stdout_log
-                .with_filter(filter::LevelFilter::INFO)
-                .and_then(debug_log)
-                .with_filter(filter::LevelFilter::DEBUG),
-        )
-        .init();
-}
+    let service = router.into_make_service_with_connect_info::<SocketAddr>();
+    match tls_config {
+        Some(tls_config) => {
+            tracing::info!("HTTPS server listening on {}", addr);
+            axum_server::bind_rustls(addr, tls_config)
+                .handle(handle)
+                .serve(service)
+                .await
+                .unwrap_or_else(|error| {
+                    exit_with_error(format!("could not start the server: {error}"))
+                });
+        }
+        None => {
+            tracing::info!("HTTP server listening on {}", addr);
+            axum_server::bind(addr)
+                .handle(handle)
+                .serve(service)
+                .await
+                .unwrap_or_else(|error| {
+                    exit_with_error(format!("could not start the server: {error}"))
+                });
+        }
+    }
 
-fn add_tracing_layer(router: Router) -> Router {
-    let tracing_layer = TraceLayer::new_for_http()
-        .make_span_with(|req: &Request| {
-            let method = req.method();
-            let uri = req.uri();
+    // Dropping the sender stops the backup task, which finishes any backup in progress first.
+    drop(stop_backups);
+    if let Some(backup_task) = backup_task {
+        if let Err(error) = backup_task.await {
+            tracing::error!("The scheduled backup task failed: {error}");
+        }
+    }
 
-            let matched_path = req
-                .extensions()
-                .get::<MatchedPath>()
-                .map(|matched_path| matched_path.as_str());
+    let running = operations.running();
+    if !running.is_empty() {
+        tracing::info!(
+            "Waiting up to {grace_period:?} for {} to finish",
+            running.join(", ")
+        );
 
-            tracing::debug_span!("request", %method, %uri, matched_path)
-        })
-        // By default, `TraceLayer` will log 5xx responses but we're doing our specific
-        // logging of errors so disable that
-        .on_failure(());
+        if !operations.wait(grace_period).await {
+            tracing::warn!(
+                "Gave up waiting for {} to finish",
+                operations.running().join(", ")
+            );
+        }
+    }
 
-    router.layer(tracing_layer)
+    // Operations that are still running hold their own reference to the connection.
+    match Arc::try_unwrap(conn) {
+        Ok(conn) => {
+            let conn = conn.into_inner().unwrap_or_else(|error| error.into_inner());
+            match close_database(conn) {
+                Ok(()) => tracing::info!("Closed the database"),
+                Err(error) => tracing::error!("Could not close the database cleanly: {error}"),
+            }
+        }
+        Err(_) => tracing::warn!("The database is still in use, so it was not closed cleanly"),
+    }
 }
@@ -0,0 +1,531 @@
+//! The command line options and environment variables for the server.
+
+use std::{
+    fs, io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::{
+    auth::rate_limit::{DEFAULT_LOG_IN_WINDOW, DEFAULT_MAX_LOG_IN_ATTEMPTS},
+    filters::{MoneyFormat, NegativeStyle},
+    logging::{LogFormat, RequestLogSettings, DEFAULT_SLOW_REQUEST_THRESHOLD},
+    models::PasswordHash,
+    proxy::ProxySettings,
+    routes::{
+        DEFAULT_MAX_REQUEST_BODY_SIZE, DEFAULT_REQUEST_TIMEOUT, DEFAULT_SUSPICIOUS_TRANSACTION_AGE,
+    },
+    scheduled_backups::{BackupSchedule, DEFAULT_BACKUP_INTERVAL, DEFAULT_BACKUP_RETENTION},
+    RegistrationMode,
+};
+
+/// The REST API server for budgeteur_rs.
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None, arg_required_else_help = true)]
+pub struct Cli {
+    /// What to do.
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// The tasks the server binary can run.
+#[derive(Subcommand, Debug)]
+// The command is only parsed once at startup, so the size of the serve options does not matter.
+#[allow(clippy::large_enum_variant)]
+pub enum Command {
+    /// Start the web server.
+    Serve(Config),
+    /// Apply any pending database migrations and exit.
+    Migrate {
+        /// File path to the application SQLite database.
+        #[arg(long, env = "DB_PATH")]
+        db_path: PathBuf,
+    },
+    /// Create a user without going through the web app, e.g. when registration is closed.
+    ///
+    /// The password is read from standard input.
+    CreateUser {
+        /// File path to the application SQLite database.
+        #[arg(long, env = "DB_PATH")]
+        db_path: PathBuf,
+        /// The email address of the new user.
+        #[arg(long)]
+        email: String,
+        /// The bcrypt cost for hashing the password.
+        #[arg(long, default_value_t = PasswordHash::DEFAULT_COST, value_parser = clap::value_parser!(u32).range(4..=31))]
+        password_hash_cost: u32,
+    },
+    /// Copy the database to a new file.
+    Backup {
+        /// File path to the application SQLite database.
+        #[arg(long, env = "DB_PATH")]
+        db_path: PathBuf,
+        /// Where to write the copy. This must not already exist.
+        path: PathBuf,
+    },
+}
+
+/// The options for running the web server.
+#[derive(Args, Debug)]
+pub struct Config {
+    /// File path to the application SQLite database.
+    #[arg(long, env = "DB_PATH", required_unless_present = "demo")]
+    pub db_path: Option<String>,
+
+    /// The secret used to encrypt auth cookies.
+    #[arg(long, env = "SECRET", hide_env_values = true)]
+    pub secret: Option<String>,
+
+    /// A file containing the secret used to encrypt auth cookies, e.g. a Docker secret.
+    #[arg(long, env = "SECRET_FILE", conflicts_with = "secret")]
+    pub secret_file: Option<PathBuf>,
+
+    /// Run on an in-memory database filled with sample data instead of `--db-path`. Log in as
+    /// demo@example.com with the password "demo". Changes are lost when the server stops, and the
+    /// demo account's password cannot be changed nor the account deleted.
+    #[arg(long, conflicts_with = "db_path")]
+    pub demo: bool,
+
+    /// File path to an SSL certificate `cert.pem` and key `key.pem`. Required unless
+    /// `--plain-http` is set.
+    #[arg(long, env = "CERT_PATH", required_unless_present = "plain_http")]
+    pub cert_path: Option<PathBuf>,
+
+    /// Serve plain HTTP instead of HTTPS, e.g. behind a reverse proxy that terminates TLS.
+    #[arg(long, env = "PLAIN_HTTP", conflicts_with_all = ["cert_path", "http_redirect_port"])]
+    pub plain_http: bool,
+
+    /// The address to listen on, e.g. 0.0.0.0 to accept connections from other machines.
+    #[arg(long, env = "BIND_ADDRESS", default_value_t = IpAddr::V4(Ipv4Addr::LOCALHOST))]
+    pub bind_address: IpAddr,
+
+    /// The port to serve the API from.
+    #[arg(short, long, env = "PORT", default_value_t = 3000)]
+    pub port: u16,
+
+    /// Trust the X-Forwarded-For and X-Forwarded-Proto headers for the client's IP address and
+    /// scheme. Only set this if the server can only be reached through a reverse proxy that sets
+    /// these headers, otherwise clients can spoof them.
+    #[arg(long, env = "TRUST_PROXY_HEADERS")]
+    pub trust_proxy_headers: bool,
+
+    /// A port to listen for plain HTTP requests on and redirect them to HTTPS, e.g. 80.
+    #[arg(long)]
+    pub http_redirect_port: Option<u16>,
+
+    /// Send a Strict-Transport-Security header telling browsers to only use HTTPS for this many
+    /// seconds, e.g. 31536000 for a year.
+    #[arg(long)]
+    pub hsts_max_age: Option<u64>,
+
+    /// The number of failed log-in attempts allowed for an email or IP address before further
+    /// attempts are blocked.
+    #[arg(long, default_value_t = DEFAULT_MAX_LOG_IN_ATTEMPTS)]
+    pub max_log_in_attempts: usize,
+
+    /// The number of minutes over which failed log-in attempts are counted.
+    #[arg(long, default_value_t = DEFAULT_LOG_IN_WINDOW.whole_minutes())]
+    pub log_in_window_minutes: i64,
+
+    /// The bcrypt cost for hashing passwords. Each increment doubles the time taken to hash and
+    /// verify a password. Existing passwords are rehashed when their users next log in.
+    #[arg(long, default_value_t = PasswordHash::DEFAULT_COST, value_parser = clap::value_parser!(u32).range(4..=31))]
+    pub password_hash_cost: u32,
+
+    /// Who can create an account: 'open', 'closed' or 'invite-only'.
+    #[arg(long, env = "REGISTRATION", default_value_t = RegistrationMode::Open)]
+    pub registration: RegistrationMode,
+
+    /// A directory to write a dated backup of the database to before starting, e.g. to keep a
+    /// copy from before any schema migrations are applied.
+    #[arg(long)]
+    pub backup_dir: Option<PathBuf>,
+
+    /// A directory to back up the database to while the server is running. Scheduled backups are
+    /// off unless this is set.
+    #[arg(long, env = "SCHEDULED_BACKUP_DIR")]
+    pub scheduled_backup_dir: Option<PathBuf>,
+
+    /// The number of minutes between scheduled backups.
+    #[arg(long, default_value_t = DEFAULT_BACKUP_INTERVAL.as_secs() / 60, value_parser = clap::value_parser!(u64).range(1..))]
+    pub scheduled_backup_interval_minutes: u64,
+
+    /// The number of scheduled backups to keep. Older backups are deleted.
+    #[arg(long, default_value_t = DEFAULT_BACKUP_RETENTION as u64, value_parser = clap::value_parser!(u64).range(1..))]
+    pub scheduled_backup_retention: u64,
+
+    /// Run a full integrity check of the database at startup instead of the faster quick check.
+    #[arg(long)]
+    pub full_integrity_check: bool,
+
+    /// Start the server even if the database fails its integrity check, e.g. to download a backup
+    /// of a damaged database.
+    #[arg(long, env = "IGNORE_INTEGRITY_ERRORS")]
+    pub ignore_integrity_errors: bool,
+
+    /// The number of seconds to spend on a request before responding with a timeout error.
+    #[arg(long, default_value_t = DEFAULT_REQUEST_TIMEOUT.as_secs())]
+    pub request_timeout_seconds: u64,
+
+    /// The maximum size of a request body in bytes.
+    #[arg(long, default_value_t = DEFAULT_MAX_REQUEST_BODY_SIZE)]
+    pub max_request_body_size: usize,
+
+    /// Ask for confirmation before creating a transaction from the transactions page that is
+    /// dated more than this many days ago, in case the date is a typo. Zero turns this off.
+    #[arg(long, default_value_t = DEFAULT_SUSPICIOUS_TRANSACTION_AGE.whole_days() as u64)]
+    pub suspicious_transaction_age_days: u64,
+
+    /// The number of seconds to wait for open requests and long-running operations, such as
+    /// VACUUM, to finish when shutting down.
+    #[arg(long, default_value_t = 30)]
+    pub shutdown_grace_period_seconds: u64,
+
+    /// Log requests that take longer than this many milliseconds as warnings.
+    #[arg(long, default_value_t = DEFAULT_SLOW_REQUEST_THRESHOLD.as_millis() as u64)]
+    pub slow_request_threshold_ms: u64,
+
+    /// How to format logs: 'pretty' or 'json'.
+    #[arg(long, env = "LOG_FORMAT", default_value_t = LogFormat::Pretty)]
+    pub log_format: LogFormat,
+
+    /// The symbol written before amounts of money, e.g. '€'.
+    #[arg(long, env = "CURRENCY_SYMBOL", default_value = "$")]
+    pub currency_symbol: String,
+
+    /// How to write negative amounts of money: 'minus' for -$12.34 or 'parentheses' for ($12.34).
+    #[arg(long, default_value_t = NegativeStyle::Minus)]
+    pub negative_amounts: NegativeStyle,
+}
+
+/// How the server accepts connections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Listener {
+    /// Serve HTTPS with the certificate `cert.pem` and key `key.pem` in `cert_path`.
+    Tls {
+        /// The directory holding the certificate and key.
+        cert_path: PathBuf,
+    },
+    /// Serve plain HTTP.
+    PlainHttp,
+}
+
+impl Config {
+    /// How the server should accept connections.
+    pub fn listener(&self) -> Listener {
+        match &self.cert_path {
+            Some(cert_path) if !self.plain_http => Listener::Tls {
+                cert_path: cert_path.clone(),
+            },
+            _ => Listener::PlainHttp,
+        }
+    }
+
+    /// The address and port to listen on.
+    pub fn address(&self) -> SocketAddr {
+        SocketAddr::new(self.bind_address, self.port)
+    }
+
+    /// The secret used to encrypt auth cookies from `--secret` or `--secret-file`, if either is
+    /// set. Surrounding whitespace is removed from the file's contents.
+    ///
+    /// # Errors
+    /// Returns an error if the secret file could not be read.
+    pub fn cookie_secret(&self) -> io::Result<Option<String>> {
+        match (&self.secret, &self.secret_file) {
+            (Some(secret), _) => Ok(Some(secret.clone())),
+            (None, Some(path)) => Ok(Some(fs::read_to_string(path)?.trim().to_owned())),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// How amounts of money are written in pages.
+    pub fn money_format(&self) -> MoneyFormat {
+        MoneyFormat {
+            currency_symbol: self.currency_symbol.clone(),
+            negative_style: self.negative_amounts,
+        }
+    }
+
+    /// When and where to back up the database, or `None` if scheduled backups are off.
+    pub fn backup_schedule(&self) -> Option<BackupSchedule> {
+        self.scheduled_backup_dir
+            .as_ref()
+            .map(|directory| BackupSchedule {
+                directory: directory.clone(),
+                interval: Duration::from_secs(self.scheduled_backup_interval_minutes * 60),
+                retention: self.scheduled_backup_retention as usize,
+            })
+    }
+
+    /// How old a new transaction's date can be before it must be confirmed, or `None` if the
+    /// confirmation is off.
+    pub fn suspicious_transaction_age(&self) -> Option<time::Duration> {
+        (self.suspicious_transaction_age_days > 0)
+            .then(|| time::Duration::days(self.suspicious_transaction_age_days as i64))
+    }
+
+    /// How clients reach the server.
+    pub fn proxy_settings(&self) -> ProxySettings {
+        ProxySettings {
+            tls: self.listener() != Listener::PlainHttp,
+            trust_proxy_headers: self.trust_proxy_headers,
+        }
+    }
+
+    /// How requests are logged.
+    pub fn request_log_settings(&self) -> RequestLogSettings {
+        RequestLogSettings {
+            proxy_settings: self.proxy_settings(),
+            slow_request_threshold: Duration::from_millis(self.slow_request_threshold_ms),
+        }
+    }
+}
+
+#[cfg(test)]
+mod config_tests {
+    use std::{
+        env, fs,
+        net::SocketAddr,
+        path::{Path, PathBuf},
+        time::Duration,
+    };
+
+    use clap::{error::ErrorKind, CommandFactory, Parser};
+
+    use crate::{
+        filters::{MoneyFormat, NegativeStyle},
+        logging::RequestLogSettings,
+        proxy::ProxySettings,
+        routes::DEFAULT_SUSPICIOUS_TRANSACTION_AGE,
+        scheduled_backups::BackupSchedule,
+    };
+
+    use super::{Cli, Command, Config, Listener};
+
+    fn parse_serve<'a>(args: impl IntoIterator<Item = &'a str>) -> Result<Config, clap::Error> {
+        let cli = Cli::try_parse_from(["server", "serve"].into_iter().chain(args))?;
+
+        match cli.command {
+            Command::Serve(config) => Ok(config),
+            command => panic!("expected the serve command, got {command:?}"),
+        }
+    }
+
+    #[test]
+    fn command_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn parses_tls_mode() {
+        let config = parse_serve(["--db-path", "foo.db", "--cert-path", "certs"]).unwrap();
+
+        assert_eq!(
+            config.listener(),
+            Listener::Tls {
+                cert_path: PathBuf::from("certs")
+            }
+        );
+        assert_eq!(
+            config.address(),
+            "127.0.0.1:3000".parse::<SocketAddr>().unwrap()
+        );
+        assert_eq!(config.proxy_settings(), ProxySettings::default());
+        assert_eq!(config.money_format(), MoneyFormat::default());
+        assert_eq!(config.request_log_settings(), RequestLogSettings::default());
+    }
+
+    #[test]
+    fn parses_plain_http_mode_behind_proxy() {
+        let config = parse_serve([
+            "--db-path",
+            "foo.db",
+            "--plain-http",
+            "--bind-address",
+            "0.0.0.0",
+            "--port",
+            "8080",
+            "--trust-proxy-headers",
+        ])
+        .unwrap();
+
+        assert_eq!(config.listener(), Listener::PlainHttp);
+        assert_eq!(
+            config.address(),
+            "0.0.0.0:8080".parse::<SocketAddr>().unwrap()
+        );
+        assert_eq!(
+            config.proxy_settings(),
+            ProxySettings {
+                tls: false,
+                trust_proxy_headers: true
+            }
+        );
+    }
+
+    #[test]
+    fn requires_cert_path_or_plain_http() {
+        let error = parse_serve(["--db-path", "foo.db"]).unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn plain_http_conflicts_with_tls_options() {
+        for extra_args in [["--cert-path", "certs"], ["--http-redirect-port", "80"]] {
+            let error = parse_serve(
+                ["--db-path", "foo.db", "--plain-http"]
+                    .into_iter()
+                    .chain(extra_args),
+            )
+            .unwrap_err();
+
+            assert_eq!(error.kind(), ErrorKind::ArgumentConflict);
+        }
+    }
+
+    #[test]
+    fn scheduled_backups_are_off_by_default() {
+        let config = parse_serve(["--demo", "--plain-http"]).unwrap();
+
+        assert_eq!(config.backup_schedule(), None);
+    }
+
+    #[test]
+    fn parses_suspicious_transaction_age() {
+        let config = parse_serve(["--demo", "--plain-http"]).unwrap();
+        assert_eq!(
+            config.suspicious_transaction_age(),
+            Some(DEFAULT_SUSPICIOUS_TRANSACTION_AGE)
+        );
+
+        let config = parse_serve([
+            "--demo",
+            "--plain-http",
+            "--suspicious-transaction-age-days",
+            "30",
+        ])
+        .unwrap();
+        assert_eq!(
+            config.suspicious_transaction_age(),
+            Some(time::Duration::days(30))
+        );
+
+        let config = parse_serve([
+            "--demo",
+            "--plain-http",
+            "--suspicious-transaction-age-days",
+            "0",
+        ])
+        .unwrap();
+        assert_eq!(config.suspicious_transaction_age(), None);
+    }
+
+    #[test]
+    fn parses_backup_schedule() {
+        let config = parse_serve([
+            "--demo",
+            "--plain-http",
+            "--scheduled-backup-dir",
+            "backups",
+            "--scheduled-backup-interval-minutes",
+            "90",
+            "--scheduled-backup-retention",
+            "3",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            config.backup_schedule(),
+            Some(BackupSchedule {
+                directory: PathBuf::from("backups"),
+                interval: Duration::from_secs(90 * 60),
+                retention: 3,
+            })
+        );
+        assert!(parse_serve([
+            "--demo",
+            "--plain-http",
+            "--scheduled-backup-retention",
+            "0"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn parses_money_format() {
+        let config = parse_serve([
+            "--demo",
+            "--plain-http",
+            "--currency-symbol",
+            "€",
+            "--negative-amounts",
+            "parentheses",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            config.money_format(),
+            MoneyFormat {
+                currency_symbol: "€".to_owned(),
+                negative_style: NegativeStyle::Parentheses
+            }
+        );
+    }
+
+    #[test]
+    fn reads_secret_from_file() {
+        let path = env::temp_dir().join(format!("budgeteur-secret-{}", rand::random::<u64>()));
+        fs::write(&path, "hunter2\n").unwrap();
+
+        let config = parse_serve([
+            "--demo",
+            "--plain-http",
+            "--secret-file",
+            path.to_str().unwrap(),
+        ])
+        .unwrap();
+
+        assert_eq!(config.cookie_secret().unwrap().as_deref(), Some("hunter2"));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn parses_admin_commands() {
+        let cli =
+            Cli::try_parse_from(["server", "backup", "--db-path", "foo.db", "backup.db"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Backup { db_path, path }
+                if db_path == Path::new("foo.db") && path == Path::new("backup.db")
+        ));
+
+        let cli = Cli::try_parse_from([
+            "server",
+            "create-user",
+            "--db-path",
+            "foo.db",
+            "--email",
+            "foo@bar.baz",
+        ])
+        .unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::CreateUser { email, .. } if email == "foo@bar.baz"
+        ));
+    }
+
+    #[test]
+    fn missing_command_prints_usage() {
+        let error = Cli::try_parse_from(["server"]).unwrap_err();
+
+        assert_eq!(
+            error.kind(),
+            ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand
+        );
+    }
+}
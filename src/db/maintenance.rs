@@ -0,0 +1,205 @@
+//! Checking the integrity of the database and keeping it compact and fast.
+
+use std::{
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+use rusqlite::Connection;
+
+use super::DatabaseError;
+
+/// How thoroughly to check the database for corruption.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum IntegrityCheck {
+    /// Run `PRAGMA quick_check`, which skips checking that indexes match their tables and is much
+    /// faster on large databases.
+    #[default]
+    Quick,
+    /// Run `PRAGMA integrity_check`.
+    Full,
+}
+
+/// Check the database for corruption.
+///
+/// # Errors
+/// Returns a [DatabaseError::IntegrityCheckFailed] with the problems that SQLite found, or a
+/// [DatabaseError::SqlError] if the check could not be run.
+pub fn check_integrity(
+    connection: &Connection,
+    check: IntegrityCheck,
+) -> Result<(), DatabaseError> {
+    let pragma = match check {
+        IntegrityCheck::Quick => "quick_check",
+        IntegrityCheck::Full => "integrity_check",
+    };
+
+    let mut problems = Vec::new();
+    connection.pragma_query(None, pragma, |row| {
+        problems.push(row.get::<_, String>(0)?);
+        Ok(())
+    })?;
+
+    // SQLite reports a single row of "ok" if no problems were found.
+    if problems.len() == 1 && problems[0] == "ok" {
+        Ok(())
+    } else {
+        Err(DatabaseError::IntegrityCheckFailed(problems))
+    }
+}
+
+/// A maintenance task that the owner of the site can run on demand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaintenanceTask {
+    /// Rebuild the database file to reclaim free space and defragment it.
+    Vacuum,
+    /// Gather statistics that the query planner uses to pick indexes.
+    Analyze,
+}
+
+impl Display for MaintenanceTask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MaintenanceTask::Vacuum => f.write_str("VACUUM"),
+            MaintenanceTask::Analyze => f.write_str("ANALYZE"),
+        }
+    }
+}
+
+/// The outcome of running a [MaintenanceTask].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaintenanceReport {
+    /// The task that was run.
+    pub task: MaintenanceTask,
+    /// How long the task took.
+    pub duration: Duration,
+    /// The size of the database in bytes before the task was run.
+    pub size_before: u64,
+    /// The size of the database in bytes after the task was run.
+    pub size_after: u64,
+}
+
+/// Run `task` on the database.
+///
+/// This may take several seconds on large databases, during which the database is locked, so it
+/// should be run on a blocking thread.
+///
+/// # Errors
+/// Returns a [DatabaseError::SqlError] if the task failed.
+pub fn run_maintenance(
+    connection: &Connection,
+    task: MaintenanceTask,
+) -> Result<MaintenanceReport, DatabaseError> {
+    let size_before = get_database_size(connection)?;
+    let start = Instant::now();
+
+    match task {
+        MaintenanceTask::Vacuum => connection.execute_batch("VACUUM")?,
+        MaintenanceTask::Analyze => connection.execute_batch("ANALYZE")?,
+    }
+
+    Ok(MaintenanceReport {
+        task,
+        duration: start.elapsed(),
+        size_before,
+        size_after: get_database_size(connection)?,
+    })
+}
+
+/// Get the size of the database in bytes, excluding the write-ahead log.
+///
+/// # Errors
+/// Returns an error if there is an SQL error.
+pub fn get_database_size(connection: &Connection) -> Result<u64, rusqlite::Error> {
+    let page_count: u64 = connection.pragma_query_value(None, "page_count", |row| row.get(0))?;
+    let page_size: u64 = connection.pragma_query_value(None, "page_size", |row| row.get(0))?;
+
+    Ok(page_count * page_size)
+}
+
+#[cfg(test)]
+mod maintenance_tests {
+    use rusqlite::Connection;
+
+    use crate::db::{initialize, DatabaseError};
+
+    use super::{
+        check_integrity, get_database_size, run_maintenance, IntegrityCheck, MaintenanceTask,
+    };
+
+    fn get_connection() -> Connection {
+        let connection = Connection::open_in_memory().unwrap();
+        initialize(&connection).unwrap();
+
+        connection
+    }
+
+    #[test]
+    fn integrity_check_passes_for_new_database() {
+        let connection = get_connection();
+
+        for check in [IntegrityCheck::Quick, IntegrityCheck::Full] {
+            assert!(check_integrity(&connection, check).is_ok());
+        }
+    }
+
+    #[test]
+    fn integrity_check_reports_problems() {
+        let connection = get_connection();
+        connection
+            .execute_batch("CREATE TABLE foo (bar TEXT); INSERT INTO foo (bar) VALUES (NULL);")
+            .unwrap();
+        // Edit the schema behind SQLite's back so that the existing row breaks a constraint.
+        let schema_version: i64 = connection
+            .pragma_query_value(None, "schema_version", |row| row.get(0))
+            .unwrap();
+        connection
+            .execute_batch(&format!(
+                "PRAGMA writable_schema = ON;
+                UPDATE sqlite_master SET sql = 'CREATE TABLE foo (bar TEXT NOT NULL)'
+                    WHERE name = 'foo';
+                PRAGMA writable_schema = OFF;
+                PRAGMA schema_version = {};",
+                schema_version + 1
+            ))
+            .unwrap();
+
+        for check in [IntegrityCheck::Quick, IntegrityCheck::Full] {
+            let result = check_integrity(&connection, check);
+
+            assert!(
+                matches!(result, Err(DatabaseError::IntegrityCheckFailed(ref problems)) if !problems.is_empty()),
+                "got {result:?} for {check:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn vacuum_reclaims_free_space() {
+        let connection = get_connection();
+        connection
+            .execute_batch(
+                "CREATE TABLE foo (bar TEXT);
+                WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 1000)
+                INSERT INTO foo (bar) SELECT printf('%.500c', 'x') FROM n;
+                DELETE FROM foo;",
+            )
+            .unwrap();
+        let size_before = get_database_size(&connection).unwrap();
+
+        let report = run_maintenance(&connection, MaintenanceTask::Vacuum).unwrap();
+
+        assert_eq!(report.task, MaintenanceTask::Vacuum);
+        assert_eq!(report.size_before, size_before);
+        assert!(report.size_after < report.size_before);
+    }
+
+    #[test]
+    fn analyze_runs() {
+        let connection = get_connection();
+
+        let report = run_maintenance(&connection, MaintenanceTask::Analyze).unwrap();
+
+        assert_eq!(report.task, MaintenanceTask::Analyze);
+    }
+}
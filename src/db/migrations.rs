@@ -0,0 +1,515 @@
+//! Versioned changes to the database schema.
+//!
+//! The schema version of a database is stored in the `schema_version` table. Each entry in
+//! [MIGRATIONS] moves the schema from one version to the next, so a database at version `n` has
+//! had the first `n` migrations applied to it. To change the schema, append a new migration to
+//! [MIGRATIONS] rather than editing an existing one, since existing databases will not re-run
+//! migrations that they have already applied.
+
+use rusqlite::{Connection, OptionalExtension, Transaction as SqlTransaction};
+
+use super::DatabaseError;
+
+/// A function that moves the schema from one version to the next.
+type Migration = fn(&SqlTransaction) -> Result<(), rusqlite::Error>;
+
+/// The migrations in the order that they must be applied.
+const MIGRATIONS: [Migration; 17] = [
+    create_initial_tables,
+    add_transaction_ignored,
+    add_transaction_reconciled,
+    add_user_session_generation,
+    create_invite_table,
+    create_api_token_table,
+    create_webhook_table,
+    add_transaction_created_at,
+    create_audit_log_table,
+    create_user_preferences_table,
+    add_user_theme,
+    add_transaction_date_index,
+    add_transaction_raw_description,
+    add_user_landing_page,
+    add_transaction_external_id,
+    add_user_date_periods,
+    add_user_is_owner,
+];
+
+/// The schema version that this build of the application expects.
+pub const SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64;
+
+/// Create the tables as they were in the first release of the application.
+///
+/// This must not change, since databases from before schema versioning was introduced are
+/// assumed to have exactly these tables. Later changes to the schema are in the following
+/// migrations.
+fn create_initial_tables(transaction: &SqlTransaction) -> Result<(), rusqlite::Error> {
+    transaction.execute_batch(
+        "CREATE TABLE user (
+            id INTEGER PRIMARY KEY,
+            email TEXT UNIQUE NOT NULL,
+            password TEXT NOT NULL
+        );
+        CREATE TABLE category (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            user_id INTEGER NOT NULL,
+            FOREIGN KEY(user_id) REFERENCES user(id) ON UPDATE CASCADE ON DELETE CASCADE,
+            UNIQUE(user_id, name) ON CONFLICT ROLLBACK
+        );
+        CREATE TABLE \"transaction\" (
+            id INTEGER PRIMARY KEY,
+            amount REAL NOT NULL,
+            date TEXT NOT NULL,
+            description TEXT NOT NULL,
+            category_id INTEGER,
+            user_id INTEGER NOT NULL,
+            FOREIGN KEY(category_id) REFERENCES category(id) ON UPDATE CASCADE ON DELETE CASCADE,
+            FOREIGN KEY(user_id) REFERENCES user(id) ON UPDATE CASCADE ON DELETE CASCADE
+        );",
+    )
+}
+
+/// Record whether each transaction should be left out of totals.
+fn add_transaction_ignored(transaction: &SqlTransaction) -> Result<(), rusqlite::Error> {
+    transaction.execute(
+        "ALTER TABLE \"transaction\" ADD COLUMN ignored INTEGER NOT NULL DEFAULT 0",
+        (),
+    )?;
+
+    Ok(())
+}
+
+/// Record whether each transaction has been checked off against a bank statement.
+fn add_transaction_reconciled(transaction: &SqlTransaction) -> Result<(), rusqlite::Error> {
+    transaction.execute(
+        "ALTER TABLE \"transaction\" ADD COLUMN reconciled INTEGER NOT NULL DEFAULT 0",
+        (),
+    )?;
+
+    Ok(())
+}
+
+/// Count how many times each user has logged out everywhere, so that older sessions can be
+/// rejected.
+fn add_user_session_generation(transaction: &SqlTransaction) -> Result<(), rusqlite::Error> {
+    transaction.execute(
+        "ALTER TABLE user ADD COLUMN session_generation INTEGER NOT NULL DEFAULT 0",
+        (),
+    )?;
+
+    Ok(())
+}
+
+/// Store the invite codes for registering when registration is invite only.
+fn create_invite_table(transaction: &SqlTransaction) -> Result<(), rusqlite::Error> {
+    transaction.execute(
+        "CREATE TABLE invite (
+            code TEXT PRIMARY KEY,
+            created_at TEXT NOT NULL
+        )",
+        (),
+    )?;
+
+    Ok(())
+}
+
+/// Store the hashes of the personal access tokens for the JSON API.
+fn create_api_token_table(transaction: &SqlTransaction) -> Result<(), rusqlite::Error> {
+    transaction.execute(
+        "CREATE TABLE api_token (
+            id INTEGER PRIMARY KEY,
+            user_id INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            token_hash TEXT UNIQUE NOT NULL,
+            created_at TEXT NOT NULL,
+            last_used_at TEXT,
+            expires_at TEXT,
+            FOREIGN KEY(user_id) REFERENCES user(id) ON UPDATE CASCADE ON DELETE CASCADE
+        )",
+        (),
+    )?;
+
+    Ok(())
+}
+
+/// Store the URLs that are sent each user's transaction events.
+fn create_webhook_table(transaction: &SqlTransaction) -> Result<(), rusqlite::Error> {
+    transaction.execute(
+        "CREATE TABLE webhook (
+            id INTEGER PRIMARY KEY,
+            user_id INTEGER NOT NULL,
+            url TEXT NOT NULL,
+            secret TEXT NOT NULL,
+            events TEXT NOT NULL,
+            enabled INTEGER NOT NULL DEFAULT 1,
+            FOREIGN KEY(user_id) REFERENCES user(id) ON UPDATE CASCADE ON DELETE CASCADE
+        )",
+        (),
+    )?;
+
+    Ok(())
+}
+
+/// Record when each transaction was added to the database.
+///
+/// Transactions created before this migration have no creation time.
+fn add_transaction_created_at(transaction: &SqlTransaction) -> Result<(), rusqlite::Error> {
+    transaction.execute("ALTER TABLE \"transaction\" ADD COLUMN created_at TEXT", ())?;
+
+    Ok(())
+}
+
+/// Record who changed what in the audit log.
+fn create_audit_log_table(transaction: &SqlTransaction) -> Result<(), rusqlite::Error> {
+    transaction.execute_batch(
+        "CREATE TABLE audit_log (
+            id INTEGER PRIMARY KEY,
+            timestamp TEXT NOT NULL,
+            user_id INTEGER NOT NULL,
+            action TEXT NOT NULL,
+            entity_type TEXT NOT NULL,
+            entity_id INTEGER NOT NULL,
+            summary TEXT NOT NULL,
+            FOREIGN KEY(user_id) REFERENCES user(id) ON UPDATE CASCADE ON DELETE CASCADE
+        );
+        CREATE INDEX audit_log_user_id ON audit_log (user_id, entity_type);",
+    )
+}
+
+/// Store each user's preferences, such as their timezone.
+///
+/// Users without a row use the defaults in [UserPreferences](crate::models::UserPreferences).
+fn create_user_preferences_table(transaction: &SqlTransaction) -> Result<(), rusqlite::Error> {
+    transaction.execute(
+        "CREATE TABLE user_preferences (
+            user_id INTEGER PRIMARY KEY,
+            timezone TEXT NOT NULL,
+            FOREIGN KEY(user_id) REFERENCES user(id) ON UPDATE CASCADE ON DELETE CASCADE
+        )",
+        (),
+    )?;
+
+    Ok(())
+}
+
+/// Store whether each user wants light or dark pages.
+fn add_user_theme(transaction: &SqlTransaction) -> Result<(), rusqlite::Error> {
+    transaction.execute(
+        "ALTER TABLE user_preferences ADD COLUMN theme TEXT NOT NULL DEFAULT 'system'",
+        (),
+    )?;
+
+    Ok(())
+}
+
+/// Index each user's transactions by date and ID, so that a page of transactions after a cursor
+/// can be found without scanning the preceding transactions.
+fn add_transaction_date_index(transaction: &SqlTransaction) -> Result<(), rusqlite::Error> {
+    transaction.execute(
+        "CREATE INDEX transaction_user_date ON \"transaction\" (user_id, date, id)",
+        (),
+    )?;
+
+    Ok(())
+}
+
+/// Keep each transaction's description as it was entered or imported, since the description
+/// column holds the cleaned up version. Transactions from before this migration have no raw
+/// description.
+fn add_transaction_raw_description(transaction: &SqlTransaction) -> Result<(), rusqlite::Error> {
+    transaction.execute(
+        "ALTER TABLE \"transaction\" ADD COLUMN raw_description TEXT",
+        (),
+    )?;
+
+    Ok(())
+}
+
+/// Store which page each user is sent to after logging in.
+fn add_user_landing_page(transaction: &SqlTransaction) -> Result<(), rusqlite::Error> {
+    transaction.execute(
+        "ALTER TABLE user_preferences ADD COLUMN landing_page TEXT NOT NULL DEFAULT 'dashboard'",
+        (),
+    )?;
+
+    Ok(())
+}
+
+/// Keep the ID that a transaction has in the system it was synced from, so that sending it again
+/// does not create a duplicate. Each user's external IDs are unique, but transactions may have no
+/// external ID.
+fn add_transaction_external_id(transaction: &SqlTransaction) -> Result<(), rusqlite::Error> {
+    transaction.execute_batch(
+        "ALTER TABLE \"transaction\" ADD COLUMN external_id TEXT;
+        CREATE UNIQUE INDEX transaction_user_external_id ON \"transaction\" (user_id, external_id);",
+    )
+}
+
+/// Store the day that each user's weeks and months start on.
+fn add_user_date_periods(transaction: &SqlTransaction) -> Result<(), rusqlite::Error> {
+    transaction.execute_batch(
+        "ALTER TABLE user_preferences ADD COLUMN week_start TEXT NOT NULL DEFAULT 'monday';
+        ALTER TABLE user_preferences ADD COLUMN month_start_day INTEGER NOT NULL DEFAULT 1;",
+    )
+}
+
+/// Mark which user owns the site, which is the first user to register. Existing databases did not
+/// store the owner, so the user with the lowest ID becomes the owner.
+fn add_user_is_owner(transaction: &SqlTransaction) -> Result<(), rusqlite::Error> {
+    transaction.execute_batch(
+        "ALTER TABLE user ADD COLUMN is_owner INTEGER NOT NULL DEFAULT FALSE;
+        UPDATE user SET is_owner = TRUE WHERE id = (SELECT MIN(id) FROM user);",
+    )
+}
+
+/// Get the schema version of the database, or zero for an empty database.
+///
+/// Databases created before schema versioning was introduced have the tables from the first
+/// migration but no `schema_version` table, so they are treated as being at version one.
+///
+/// # Errors
+/// Returns an error if there is an SQL error.
+pub fn get_schema_version(connection: &Connection) -> Result<i64, rusqlite::Error> {
+    let table_exists = |name: &str| -> Result<bool, rusqlite::Error> {
+        connection.query_row(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+            [name],
+            |row| row.get(0),
+        )
+    };
+
+    if table_exists("schema_version")? {
+        let version = connection
+            .query_row("SELECT version FROM schema_version", (), |row| row.get(0))
+            .optional()?;
+
+        return Ok(version.unwrap_or(0));
+    }
+
+    if table_exists("user")? {
+        Ok(1)
+    } else {
+        Ok(0)
+    }
+}
+
+/// Apply any migrations that the database has not had applied yet.
+///
+/// The pending migrations are applied in a single transaction, so if one fails the database is
+/// left unchanged.
+///
+/// # Errors
+/// Returns a [DatabaseError::SchemaTooNew] if the database was created by a newer version of the
+/// application, or a [DatabaseError::SqlError] if a migration failed.
+pub fn run_migrations(connection: &Connection) -> Result<(), DatabaseError> {
+    let transaction =
+        SqlTransaction::new_unchecked(connection, rusqlite::TransactionBehavior::Exclusive)?;
+
+    let current_version = get_schema_version(&transaction)?;
+
+    if current_version > SCHEMA_VERSION {
+        return Err(DatabaseError::SchemaTooNew {
+            database_version: current_version,
+            supported_version: SCHEMA_VERSION,
+        });
+    }
+
+    if current_version == SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(current_version as usize) {
+        tracing::info!("Migrating the database to schema version {}", index + 1);
+        migration(&transaction)?;
+    }
+
+    transaction.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version (version INTEGER NOT NULL)",
+        (),
+    )?;
+    transaction.execute("DELETE FROM schema_version", ())?;
+    transaction.execute(
+        "INSERT INTO schema_version (version) VALUES (?1)",
+        [SCHEMA_VERSION],
+    )?;
+
+    transaction.commit()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod migrations_tests {
+    use std::sync::{Arc, Mutex};
+
+    use email_address::EmailAddress;
+    use rusqlite::Connection;
+
+    use crate::{
+        db::{configure_connection, DatabaseError},
+        models::UserID,
+        stores::{SQLiteTransactionStore, SQLiteUserStore, TransactionStore, UserStore},
+    };
+
+    use super::{get_schema_version, run_migrations, SCHEMA_VERSION};
+
+    fn get_connection() -> Connection {
+        let connection = Connection::open_in_memory().unwrap();
+        configure_connection(&connection).unwrap();
+
+        connection
+    }
+
+    fn has_column(connection: &Connection, table: &str, column: &str) -> bool {
+        connection
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2)",
+                [table, column],
+                |row| row.get(0),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn migrates_empty_database_to_latest_version() {
+        let connection = get_connection();
+
+        run_migrations(&connection).unwrap();
+
+        assert_eq!(get_schema_version(&connection), Ok(SCHEMA_VERSION));
+        assert!(has_column(&connection, "transaction", "created_at"));
+        assert!(has_column(&connection, "transaction", "raw_description"));
+        assert!(has_column(&connection, "user_preferences", "landing_page"));
+        assert!(has_column(&connection, "user_preferences", "week_start"));
+        assert!(has_column(
+            &connection,
+            "user_preferences",
+            "month_start_day"
+        ));
+        assert!(has_column(&connection, "transaction", "external_id"));
+    }
+
+    #[test]
+    fn running_migrations_twice_does_nothing() {
+        let connection = get_connection();
+        run_migrations(&connection).unwrap();
+
+        run_migrations(&connection).unwrap();
+
+        assert_eq!(get_schema_version(&connection), Ok(SCHEMA_VERSION));
+    }
+
+    /// The schema of the first release, copied verbatim so that changes to the migrations
+    /// cannot change what an old database is assumed to look like.
+    const BASELINE_SCHEMA: &str = "
+        CREATE TABLE user (
+                id INTEGER PRIMARY KEY,
+                email TEXT UNIQUE NOT NULL,
+                password TEXT NOT NULL
+                );
+        CREATE TABLE category (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                user_id INTEGER NOT NULL,
+                FOREIGN KEY(user_id) REFERENCES user(id) ON UPDATE CASCADE ON DELETE CASCADE,
+                UNIQUE(user_id, name) ON CONFLICT ROLLBACK
+                );
+        CREATE TABLE \"transaction\" (
+                id INTEGER PRIMARY KEY,
+                amount REAL NOT NULL,
+                date TEXT NOT NULL,
+                description TEXT NOT NULL,
+                category_id INTEGER,
+                user_id INTEGER NOT NULL,
+                FOREIGN KEY(category_id) REFERENCES category(id) ON UPDATE CASCADE ON DELETE CASCADE,
+                FOREIGN KEY(user_id) REFERENCES user(id) ON UPDATE CASCADE ON DELETE CASCADE
+                );";
+
+    #[test]
+    fn upgrades_unversioned_database_and_keeps_data() {
+        let connection = get_connection();
+        connection.execute_batch(BASELINE_SCHEMA).unwrap();
+        connection
+            .execute(
+                "INSERT INTO user (id, email, password) VALUES (1, 'foo@bar.baz', 'hunter2')",
+                (),
+            )
+            .unwrap();
+        connection
+            .execute(
+                "INSERT INTO category (id, name, user_id) VALUES (1, 'Food', 1)",
+                (),
+            )
+            .unwrap();
+        connection
+            .execute(
+                "INSERT INTO \"transaction\" (amount, date, description, category_id, user_id) \
+                VALUES (12.3, '2024-01-01', 'foo', 1, 1)",
+                (),
+            )
+            .unwrap();
+        assert_eq!(get_schema_version(&connection), Ok(1));
+
+        run_migrations(&connection).unwrap();
+
+        assert_eq!(get_schema_version(&connection), Ok(SCHEMA_VERSION));
+        for (table, column) in [
+            ("transaction", "ignored"),
+            ("transaction", "reconciled"),
+            ("transaction", "created_at"),
+            ("transaction", "raw_description"),
+            ("transaction", "external_id"),
+            ("user", "session_generation"),
+            ("user", "is_owner"),
+            ("invite", "code"),
+            ("api_token", "token_hash"),
+            ("webhook", "secret"),
+            ("audit_log", "id"),
+            ("user_preferences", "landing_page"),
+        ] {
+            assert!(
+                has_column(&connection, table, column),
+                "expected column {table}.{column} after upgrading"
+            );
+        }
+
+        let connection = Arc::new(Mutex::new(connection));
+        let user_store = SQLiteUserStore::new(connection.clone());
+        let user = user_store
+            .get_by_email(&EmailAddress::new_unchecked("foo@bar.baz"))
+            .unwrap();
+        assert_eq!(user.id(), UserID::new(1));
+        assert_eq!(user.session_generation(), 0);
+        assert_eq!(user_store.get_owner_id(), Ok(Some(user.id())));
+        let transactions = SQLiteTransactionStore::new(connection)
+            .get_by_user_id(UserID::new(1))
+            .unwrap();
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].description(), "foo");
+        assert_eq!(transactions[0].category_id(), Some(1));
+        assert!(!transactions[0].is_ignored());
+    }
+
+    #[test]
+    fn refuses_database_from_newer_version() {
+        let connection = get_connection();
+        run_migrations(&connection).unwrap();
+        connection
+            .execute(
+                "UPDATE schema_version SET version = ?1",
+                [SCHEMA_VERSION + 1],
+            )
+            .unwrap();
+
+        let result = run_migrations(&connection);
+
+        assert!(matches!(
+            result,
+            Err(DatabaseError::SchemaTooNew {
+                database_version,
+                supported_version: SCHEMA_VERSION,
+            }) if database_version == SCHEMA_VERSION + 1
+        ));
+        assert_eq!(get_schema_version(&connection), Ok(SCHEMA_VERSION + 1));
+    }
+}
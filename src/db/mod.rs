@@ -0,0 +1,727 @@
+/*! This module defines and implements traits for interacting with the application's database. */
+
+use std::{
+    fs::{self, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    process,
+    time::Duration,
+};
+
+use rusqlite::{backup::Backup, Connection, Error, ErrorCode, Row};
+use thiserror::Error;
+use time::{format_description::BorrowedFormatItem, macros::format_description, OffsetDateTime};
+
+pub mod maintenance;
+pub mod migrations;
+
+use maintenance::{check_integrity, IntegrityCheck};
+use migrations::run_migrations;
+
+/// How long a connection waits for a lock held by another connection before giving up with
+/// `SQLITE_BUSY`.
+pub const BUSY_TIMEOUT_MS: i64 = 5000;
+
+/// The number of pages copied in each step of a backup.
+const BACKUP_PAGES_PER_STEP: i32 = 256;
+
+/// The format of the date and time in backup file names.
+const BACKUP_DATE_TIME_FORMAT: &[BorrowedFormatItem] =
+    format_description!("[year]-[month]-[day]T[hour][minute][second]Z");
+
+/// Errors that can occur while setting up the application's database.
+#[derive(Debug, Error)]
+pub enum DatabaseError {
+    /// An unhandled/unexpected SQL error.
+    #[error("an SQL error occurred: {0}")]
+    SqlError(#[from] Error),
+
+    /// A pragma did not take the expected value after it was set, e.g. because the SQLite build
+    /// does not support it.
+    #[error("expected the pragma {pragma} to be {expected}, but it is {actual}")]
+    PragmaNotApplied {
+        /// The name of the pragma.
+        pragma: &'static str,
+        /// The value the pragma was set to.
+        expected: String,
+        /// The value the pragma has after setting it.
+        actual: String,
+    },
+
+    /// The database has a newer schema than this version of the application knows about, e.g.
+    /// because it was opened by a newer version of the application.
+    #[error(
+        "the database schema version {database_version} is newer than the latest supported version {supported_version}"
+    )]
+    SchemaTooNew {
+        /// The schema version of the database.
+        database_version: i64,
+        /// The latest schema version that the application supports.
+        supported_version: i64,
+    },
+
+    /// SQLite found problems when checking the database for corruption.
+    #[error("the database failed its integrity check: {}", .0.join("; "))]
+    IntegrityCheckFailed(Vec<String>),
+
+    /// The database lock was poisoned by a thread that panicked while holding it.
+    #[error("the database lock is poisoned")]
+    DatabaseLockError,
+
+    /// The directory that the database file should be in does not exist.
+    #[error(
+        "the directory {} does not exist, create it or choose another database path",
+        .0.display()
+    )]
+    MissingDirectory(PathBuf),
+
+    /// The database path is a directory instead of a file.
+    #[error("{} is a directory, the database path must be a file", .0.display())]
+    PathIsDirectory(PathBuf),
+
+    /// The database file or the directory it is in cannot be written to.
+    #[error(
+        "cannot write to {}: {source}, check its permissions and that the file system is not read-only",
+        path.display()
+    )]
+    NotWritable {
+        /// The file or directory that could not be written to.
+        path: PathBuf,
+        /// Why the file or directory could not be written to.
+        source: io::Error,
+    },
+}
+
+/// Options for [initialize_with_options].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct InitializeOptions {
+    /// How thoroughly to check the database for corruption.
+    pub integrity_check: IntegrityCheck,
+    /// Log integrity check failures instead of returning an error, e.g. to get the data out of a
+    /// damaged database.
+    pub ignore_integrity_errors: bool,
+}
+
+/// A trait for mapping from a `rusqlite::Row` from a SQLite database to a concrete rust type.
+///
+/// # Examples
+/// ```no_run
+/// use rusqlite::{Connection, Error, Row};
+///
+/// use budgeteur_rs::db::MapRow;
+///
+/// struct Foo {
+///     id: i64,
+///     desc: String
+/// }
+///
+/// impl MapRow for Foo {
+///     type ReturnType = Self;
+///
+///     fn map_row_with_offset(row: &Row, offset: usize) -> Result<Self, Error> {
+///         Ok(Self {
+///             id: row.get(offset)?,
+///             desc: row.get(offset + 1)?,
+///         })
+///     }
+/// }
+///
+/// struct Bar {
+///     id: i64,
+///     desc: String
+/// }
+///
+/// impl MapRow for Bar {
+///     type ReturnType = Self;
+///
+///     fn map_row_with_offset(row: &Row, offset: usize) -> Result<Self, Error> {
+///         Ok(Self {
+///             id: row.get(offset)?,
+///             desc: row.get(offset + 1)?,
+///         })
+///     }
+/// }
+///
+/// fn example(conn: &Connection) -> Result<(Foo, Bar), Error> {
+///     conn.
+///         prepare("SELECT l.id, l.desc, r.id, r.desc FROM foo l INNER JOIN bar r ON l.id = r.foo_id WHERE l.id = :id")?
+///         .query_row(&[(":id", &1)], |row| {
+///             let foo = Foo::map_row(row)?;
+///             let bar = Bar::map_row_with_offset(row, 2)?;
+///
+///             Ok((foo, bar))
+///         })
+/// }
+/// ```
+pub trait MapRow {
+    /// The type of the mapping output.
+    type ReturnType;
+
+    /// Convert a row into a concrete type.
+    ///
+    /// **Note:** This function expects that the row object contains all the table columns in the order they were defined.
+    ///
+    /// # Errors
+    /// Returns an error if a row item cannot be converted into the corresponding rust type, or if an invalid column index was used.
+    fn map_row(row: &Row) -> Result<Self::ReturnType, Error> {
+        Self::map_row_with_offset(row, 0)
+    }
+
+    /// Convert a row into a concrete type.
+    ///
+    /// The `offset` indicates which column the row should be read from.
+    /// This is useful in cases where tables have been joined and you want to construct two different types from the one query.
+    ///
+    /// **Note:** This function expects that the row object contains all the table columns in the order they were defined.
+    ///
+    /// # Errors
+    /// Returns an error if a row item cannot be converted into the corresponding rust type, or if an invalid column index was used.
+    fn map_row_with_offset(row: &Row, offset: usize) -> Result<Self::ReturnType, Error>;
+}
+
+/// Escape the wildcards `%` and `_` in `text` so that they match themselves in a `LIKE` pattern.
+///
+/// The pattern must be used with `ESCAPE '\'`.
+pub fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Whether `error` means that the database cannot be used right now, e.g. because another
+/// program is holding a lock on it or the file has become read-only, rather than that there is a
+/// problem with the query or the data.
+pub fn is_unavailable_error(error: &Error) -> bool {
+    matches!(
+        error.sqlite_error_code(),
+        Some(
+            ErrorCode::DatabaseBusy
+                | ErrorCode::DatabaseLocked
+                | ErrorCode::ReadOnly
+                | ErrorCode::CannotOpen
+                | ErrorCode::PermissionDenied
+                | ErrorCode::DiskFull
+                | ErrorCode::SystemIoFailure
+        )
+    )
+}
+
+/// Check that the database at `path` can be opened and written to, so that a bad path is
+/// reported when the server starts instead of on the first request that writes to the database.
+///
+/// The directory must be writable as well as the file, since SQLite keeps the write-ahead log
+/// next to the database file. The file does not need to exist yet.
+///
+/// # Errors
+/// Returns a [DatabaseError::MissingDirectory] if the directory for the database does not exist,
+/// a [DatabaseError::PathIsDirectory] if `path` is a directory, or a [DatabaseError::NotWritable]
+/// if the file or its directory cannot be written to.
+pub fn check_database_path(path: &Path) -> Result<(), DatabaseError> {
+    let directory = match path.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => directory,
+        _ => Path::new("."),
+    };
+
+    if !directory.is_dir() {
+        return Err(DatabaseError::MissingDirectory(directory.to_owned()));
+    }
+
+    if path.is_dir() {
+        return Err(DatabaseError::PathIsDirectory(path.to_owned()));
+    }
+
+    if path.exists() {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(|source| DatabaseError::NotWritable {
+                path: path.to_owned(),
+                source,
+            })?;
+    }
+
+    let probe_path = directory.join(format!(".budgeteur-write-check-{}", process::id()));
+    let not_writable = |source: io::Error| DatabaseError::NotWritable {
+        path: directory.to_owned(),
+        source,
+    };
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&probe_path)
+        .map_err(not_writable)?;
+    fs::remove_file(&probe_path).map_err(not_writable)?;
+
+    Ok(())
+}
+
+/// Configure a connection for use by the application.
+///
+/// This enables write-ahead logging so that readers do not block writers, enforces foreign key
+/// constraints, waits up to [BUSY_TIMEOUT_MS] for locks instead of failing immediately, and
+/// relaxes syncing to what is safe with write-ahead logging.
+///
+/// This must be called on every connection since, apart from the journal mode, these settings do
+/// not persist in the database file.
+///
+/// # Errors
+/// Returns a [DatabaseError::PragmaNotApplied] if a setting could not be applied, or a
+/// [DatabaseError::SqlError] if something else went wrong.
+pub fn configure_connection(connection: &Connection) -> Result<(), DatabaseError> {
+    let journal_mode: String =
+        connection.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+    // In-memory databases do not support write-ahead logging and always use the "memory" mode.
+    if !journal_mode.eq_ignore_ascii_case("wal") && !journal_mode.eq_ignore_ascii_case("memory") {
+        return Err(DatabaseError::PragmaNotApplied {
+            pragma: "journal_mode",
+            expected: "wal".to_string(),
+            actual: journal_mode,
+        });
+    }
+
+    connection.pragma_update(None, "foreign_keys", "ON")?;
+    connection.pragma_update(None, "busy_timeout", BUSY_TIMEOUT_MS)?;
+    connection.pragma_update(None, "synchronous", "NORMAL")?;
+
+    // The values are as reported by SQLite, e.g. synchronous=NORMAL is reported as 1.
+    for (pragma, expected) in [
+        ("foreign_keys", 1),
+        ("busy_timeout", BUSY_TIMEOUT_MS),
+        ("synchronous", 1),
+    ] {
+        let actual: i64 = connection.pragma_query_value(None, pragma, |row| row.get(0))?;
+
+        if actual != expected {
+            return Err(DatabaseError::PragmaNotApplied {
+                pragma,
+                expected: expected.to_string(),
+                actual: actual.to_string(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Initialize the database with the default [InitializeOptions].
+///
+/// See [initialize_with_options].
+///
+/// # Errors
+/// This function may return a [DatabaseError] if the connection could not be configured, the
+/// database is corrupt or from a newer version of the application, or a migration failed.
+pub fn initialize(connection: &Connection) -> Result<(), DatabaseError> {
+    initialize_with_options(connection, InitializeOptions::default())
+}
+
+/// Configure the connection with [configure_connection], check the database for corruption with
+/// [check_integrity] and bring the database schema up to date with [run_migrations].
+///
+/// This is safe to call on an existing database, which will have any pending migrations applied.
+///
+/// # Errors
+/// This function may return a [DatabaseError] if the connection could not be configured, the
+/// database is corrupt and `options` does not ignore integrity errors, the database is from a
+/// newer version of the application, or a migration failed.
+pub fn initialize_with_options(
+    connection: &Connection,
+    options: InitializeOptions,
+) -> Result<(), DatabaseError> {
+    configure_connection(connection)?;
+
+    match check_integrity(connection, options.integrity_check) {
+        Err(DatabaseError::IntegrityCheckFailed(problems)) if options.ignore_integrity_errors => {
+            tracing::error!(
+                "The database failed its integrity check, continuing anyway: {}",
+                problems.join("; ")
+            );
+        }
+        result => result?,
+    }
+
+    run_migrations(connection)
+}
+
+/// Copy the database of `source` to a new database file at `destination`.
+///
+/// This uses SQLite's online backup API, so the copy is consistent even if the database is being
+/// written to by another connection.
+///
+/// # Errors
+/// Returns a [DatabaseError::SqlError] if `destination` could not be opened or the copy failed.
+pub fn backup_database(source: &Connection, destination: &Path) -> Result<(), DatabaseError> {
+    let mut destination = Connection::open(destination)?;
+    let backup = Backup::new(source, &mut destination)?;
+    backup.run_to_completion(BACKUP_PAGES_PER_STEP, Duration::ZERO, None)?;
+
+    Ok(())
+}
+
+/// Write the write-ahead log back into the database file and close `connection`.
+///
+/// Call this when the server shuts down so that the database file is up to date on its own, e.g.
+/// for copying it elsewhere.
+///
+/// # Errors
+/// Returns a [DatabaseError::SqlError] if the checkpoint failed or the connection could not be
+/// closed.
+pub fn close_database(connection: Connection) -> Result<(), DatabaseError> {
+    connection.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    connection.close().map_err(|(_, error)| error)?;
+
+    Ok(())
+}
+
+/// A file name for a backup taken at `date_time`, e.g. "budgeteur-2024-01-31T093000Z.sqlite".
+pub fn get_backup_file_name(date_time: OffsetDateTime) -> String {
+    let date_time = date_time
+        .to_offset(time::UtcOffset::UTC)
+        .format(BACKUP_DATE_TIME_FORMAT)
+        .expect("the backup date time format should be valid");
+
+    format!("budgeteur-{date_time}.sqlite")
+}
+
+#[cfg(test)]
+mod db_tests {
+    use std::{env, fs};
+
+    use rusqlite::{Connection, OpenFlags};
+
+    use time::macros::datetime;
+
+    use super::{
+        backup_database, check_database_path, close_database, configure_connection,
+        get_backup_file_name, initialize, initialize_with_options, is_unavailable_error,
+        DatabaseError, InitializeOptions, BUSY_TIMEOUT_MS,
+    };
+
+    #[test]
+    fn configure_connection_applies_pragmas_to_file_database() {
+        let path = env::temp_dir().join(format!(
+            "budgeteur_configure_test_{}_{}.db",
+            std::process::id(),
+            rand::random::<u64>()
+        ));
+        let connection = Connection::open(&path).unwrap();
+
+        configure_connection(&connection).unwrap();
+
+        let journal_mode: String = connection
+            .pragma_query_value(None, "journal_mode", |row| row.get(0))
+            .unwrap();
+        let foreign_keys: i64 = connection
+            .pragma_query_value(None, "foreign_keys", |row| row.get(0))
+            .unwrap();
+        let busy_timeout: i64 = connection
+            .pragma_query_value(None, "busy_timeout", |row| row.get(0))
+            .unwrap();
+        let synchronous: i64 = connection
+            .pragma_query_value(None, "synchronous", |row| row.get(0))
+            .unwrap();
+        drop(connection);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = fs::remove_file(format!("{}{suffix}", path.display()));
+        }
+
+        assert_eq!(journal_mode, "wal");
+        assert_eq!(foreign_keys, 1);
+        assert_eq!(busy_timeout, BUSY_TIMEOUT_MS);
+        assert_eq!(synchronous, 1);
+    }
+
+    #[test]
+    fn configure_connection_succeeds_for_in_memory_database() {
+        let connection = Connection::open_in_memory().unwrap();
+
+        configure_connection(&connection).unwrap();
+    }
+
+    #[test]
+    fn foreign_keys_are_enforced() {
+        let connection = Connection::open_in_memory().unwrap();
+        initialize(&connection).unwrap();
+
+        let result = connection.execute(
+            "INSERT INTO category (name, user_id) VALUES ('Food', 42)",
+            (),
+        );
+
+        assert!(
+            result.is_err(),
+            "inserting a category for a missing user should fail"
+        );
+    }
+
+    #[test]
+    fn deleting_category_follows_on_delete_action() {
+        let connection = Connection::open_in_memory().unwrap();
+        initialize(&connection).unwrap();
+        connection
+            .execute(
+                "INSERT INTO user (id, email, password) VALUES (1, 'foo@bar.baz', 'hunter2')",
+                (),
+            )
+            .unwrap();
+        connection
+            .execute(
+                "INSERT INTO category (id, name, user_id) VALUES (1, 'Food', 1)",
+                (),
+            )
+            .unwrap();
+        connection
+            .execute(
+                "INSERT INTO \"transaction\" (amount, date, description, category_id, user_id) \
+                VALUES (1.0, '2024-01-01', '', 1, 1)",
+                (),
+            )
+            .unwrap();
+
+        connection
+            .execute("DELETE FROM category WHERE id = 1", ())
+            .unwrap();
+
+        // The transaction table declares ON DELETE CASCADE for categories.
+        let transaction_count: i64 = connection
+            .query_row("SELECT COUNT(*) FROM \"transaction\"", (), |row| row.get(0))
+            .unwrap();
+        assert_eq!(transaction_count, 0);
+    }
+
+    #[test]
+    fn backup_database_copies_rows() {
+        let connection = Connection::open_in_memory().unwrap();
+        initialize(&connection).unwrap();
+        connection
+            .execute(
+                "INSERT INTO user (email, password) VALUES ('foo@bar.baz', 'hunter2'), ('qux@bar.baz', 'hunter3')",
+                (),
+            )
+            .unwrap();
+        let path = env::temp_dir().join(format!(
+            "budgeteur_backup_test_{}.db",
+            rand::random::<u64>()
+        ));
+
+        backup_database(&connection, &path).unwrap();
+
+        let backup = Connection::open(&path).unwrap();
+        let user_count: i64 = backup
+            .query_row("SELECT COUNT(*) FROM user", (), |row| row.get(0))
+            .unwrap();
+        drop(backup);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(user_count, 2);
+    }
+
+    #[test]
+    fn close_database_checkpoints_write_ahead_log() {
+        let path = env::temp_dir().join(format!(
+            "budgeteur_close_test_{}_{}.db",
+            std::process::id(),
+            rand::random::<u64>()
+        ));
+        let connection = Connection::open(&path).unwrap();
+        initialize(&connection).unwrap();
+        // Keep another connection open so that closing the first does not remove the log.
+        let other_connection = Connection::open(&path).unwrap();
+        other_connection
+            .query_row("SELECT COUNT(*) FROM user", (), |row| row.get::<_, i64>(0))
+            .unwrap();
+        connection
+            .execute(
+                "INSERT INTO user (email, password) VALUES ('foo@bar.baz', 'hunter2')",
+                (),
+            )
+            .unwrap();
+        let wal_path = format!("{}-wal", path.display());
+        assert!(fs::metadata(&wal_path).unwrap().len() > 0);
+
+        close_database(connection).unwrap();
+
+        let wal_size = fs::metadata(&wal_path).unwrap().len();
+        drop(other_connection);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = fs::remove_file(format!("{}{suffix}", path.display()));
+        }
+        assert_eq!(wal_size, 0);
+    }
+
+    #[test]
+    fn backup_file_name_includes_utc_date_time() {
+        assert_eq!(
+            get_backup_file_name(datetime!(2024-01-31 21:30:05 +12)),
+            "budgeteur-2024-01-31T093005Z.sqlite"
+        );
+    }
+
+    #[test]
+    fn initialize_can_ignore_integrity_errors() {
+        let connection = Connection::open_in_memory().unwrap();
+        initialize(&connection).unwrap();
+        connection
+            .execute_batch("INSERT INTO user (email, password) VALUES ('foo@bar.baz', 'hunter2')")
+            .unwrap();
+        // Make the existing row break a NOT NULL constraint by editing the schema.
+        let schema_version: i64 = connection
+            .pragma_query_value(None, "schema_version", |row| row.get(0))
+            .unwrap();
+        connection
+            .execute_batch(&format!(
+                "PRAGMA writable_schema = ON;
+                UPDATE sqlite_master SET sql = replace(sql, 'session_generation INTEGER NOT NULL DEFAULT 0', 'session_generation INTEGER NOT NULL DEFAULT 0, extra TEXT NOT NULL')
+                    WHERE name = 'user';
+                PRAGMA writable_schema = OFF;
+                PRAGMA schema_version = {};",
+                schema_version + 1
+            ))
+            .unwrap();
+
+        assert!(matches!(
+            initialize(&connection),
+            Err(DatabaseError::IntegrityCheckFailed(_))
+        ));
+        assert!(initialize_with_options(
+            &connection,
+            InitializeOptions {
+                ignore_integrity_errors: true,
+                ..Default::default()
+            }
+        )
+        .is_ok());
+    }
+
+    fn get_temp_directory(name: &str) -> std::path::PathBuf {
+        let directory = env::temp_dir().join(format!(
+            "budgeteur_{name}_{}_{}",
+            std::process::id(),
+            rand::random::<u64>()
+        ));
+        fs::create_dir(&directory).unwrap();
+
+        directory
+    }
+
+    #[test]
+    fn check_database_path_accepts_new_file_in_writable_directory() {
+        let directory = get_temp_directory("check_path_test");
+
+        let result = check_database_path(&directory.join("budgeteur.db"));
+
+        let leftover_files = fs::read_dir(&directory).unwrap().count();
+        fs::remove_dir_all(&directory).unwrap();
+        assert!(result.is_ok(), "got {result:?}");
+        assert_eq!(leftover_files, 0);
+    }
+
+    #[test]
+    fn check_database_path_rejects_missing_directory() {
+        let directory = env::temp_dir().join(format!(
+            "budgeteur_missing_directory_{}",
+            rand::random::<u64>()
+        ));
+
+        let result = check_database_path(&directory.join("budgeteur.db"));
+
+        assert!(
+            matches!(result, Err(DatabaseError::MissingDirectory(ref path)) if path == &directory),
+            "got {result:?}"
+        );
+    }
+
+    #[test]
+    fn check_database_path_rejects_directory() {
+        let directory = get_temp_directory("check_path_directory_test");
+
+        let result = check_database_path(&directory);
+
+        fs::remove_dir_all(&directory).unwrap();
+        assert!(
+            matches!(result, Err(DatabaseError::PathIsDirectory(_))),
+            "got {result:?}"
+        );
+    }
+
+    /// Whether file permissions stop this process from writing to `directory`, which is not the
+    /// case when the tests run as root.
+    #[cfg(unix)]
+    fn permissions_are_enforced(directory: &std::path::Path) -> bool {
+        let probe_path = directory.join("probe");
+        let is_writable = fs::write(&probe_path, "").is_ok();
+        let _ = fs::remove_file(&probe_path);
+
+        !is_writable
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_database_path_rejects_read_only_directory() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory = get_temp_directory("check_path_read_only_directory_test");
+        fs::set_permissions(&directory, fs::Permissions::from_mode(0o555)).unwrap();
+
+        let is_enforced = permissions_are_enforced(&directory);
+        let result = check_database_path(&directory.join("budgeteur.db"));
+
+        fs::set_permissions(&directory, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        if is_enforced {
+            assert!(
+                matches!(result, Err(DatabaseError::NotWritable { ref path, .. }) if path == &directory),
+                "got {result:?}"
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_database_path_rejects_read_only_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory = get_temp_directory("check_path_read_only_file_test");
+        let path = directory.join("budgeteur.db");
+        fs::write(&path, "").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o444)).unwrap();
+        fs::set_permissions(&directory, fs::Permissions::from_mode(0o555)).unwrap();
+
+        let is_enforced = permissions_are_enforced(&directory);
+        let result = check_database_path(&path);
+
+        fs::set_permissions(&directory, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        if is_enforced {
+            assert!(
+                matches!(result, Err(DatabaseError::NotWritable { path: ref error_path, .. }) if error_path == &path),
+                "got {result:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn is_unavailable_error_matches_writes_to_read_only_database() {
+        let directory = get_temp_directory("read_only_database_test");
+        let path = directory.join("budgeteur.db");
+        initialize(&Connection::open(&path).unwrap()).unwrap();
+        let connection =
+            Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY).unwrap();
+
+        let write_error = connection
+            .execute(
+                "INSERT INTO user (email, password) VALUES ('foo@bar.baz', 'hunter2')",
+                (),
+            )
+            .unwrap_err();
+        let query_error = connection
+            .execute("INSERT INTO does_not_exist VALUES (1)", ())
+            .unwrap_err();
+
+        drop(connection);
+        fs::remove_dir_all(&directory).unwrap();
+        assert!(is_unavailable_error(&write_error), "got {write_error:?}");
+        assert!(!is_unavailable_error(&query_error), "got {query_error:?}");
+        assert!(!is_unavailable_error(&rusqlite::Error::QueryReturnedNoRows));
+    }
+}
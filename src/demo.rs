@@ -0,0 +1,243 @@
+//! Demo mode, which runs the app on a throwaway database filled with sample data.
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rusqlite::{Connection, Transaction as SqlTransaction};
+use time::{Date, Duration};
+
+use crate::models::{PasswordHash, ValidatedPassword};
+
+/// The email address of the demo user.
+pub const DEMO_EMAIL: &str = "demo@example.com";
+/// The password of the demo user.
+pub const DEMO_PASSWORD: &str = "demo";
+
+/// The seed for the random number generator, so that the demo data is the same every time.
+const DEMO_SEED: u64 = 42;
+/// How many days of transactions to create, ending today.
+const DEMO_DAYS: i64 = 365;
+
+/// A kind of transaction that repeats throughout the demo data.
+struct Pattern {
+    category: &'static str,
+    descriptions: &'static [&'static str],
+    /// The range of amounts, where negative amounts are expenses.
+    amount: (f64, f64),
+    schedule: Schedule,
+}
+
+/// How often a [Pattern] occurs.
+enum Schedule {
+    /// On this day of every month.
+    Monthly(u8),
+    /// On this many days of every week, on average.
+    PerWeek(f64),
+}
+
+/// The categories and transactions that make up the demo data.
+const PATTERNS: [Pattern; 9] = [
+    Pattern {
+        category: "Income",
+        descriptions: &["Salary"],
+        amount: (4200.0, 4200.0),
+        schedule: Schedule::Monthly(15),
+    },
+    Pattern {
+        category: "Rent",
+        descriptions: &["Rent"],
+        amount: (-1850.0, -1850.0),
+        schedule: Schedule::Monthly(1),
+    },
+    Pattern {
+        category: "Utilities",
+        descriptions: &["Power bill", "Internet", "Water rates"],
+        amount: (-180.0, -60.0),
+        schedule: Schedule::Monthly(20),
+    },
+    Pattern {
+        category: "Groceries",
+        descriptions: &["Supermarket", "Farmers market", "Corner dairy"],
+        amount: (-160.0, -15.0),
+        schedule: Schedule::PerWeek(2.0),
+    },
+    Pattern {
+        category: "Transport",
+        descriptions: &["Petrol", "Bus fare", "Parking"],
+        amount: (-90.0, -4.0),
+        schedule: Schedule::PerWeek(1.5),
+    },
+    Pattern {
+        category: "Eating Out",
+        descriptions: &["Cafe", "Takeaways", "Restaurant"],
+        amount: (-85.0, -6.0),
+        schedule: Schedule::PerWeek(1.5),
+    },
+    Pattern {
+        category: "Entertainment",
+        descriptions: &["Cinema", "Streaming subscription", "Concert tickets"],
+        amount: (-120.0, -12.0),
+        schedule: Schedule::PerWeek(0.5),
+    },
+    Pattern {
+        category: "Health",
+        descriptions: &["Pharmacy", "Doctor", "Gym membership"],
+        amount: (-95.0, -15.0),
+        schedule: Schedule::PerWeek(0.3),
+    },
+    Pattern {
+        category: "Shopping",
+        descriptions: &["Clothing", "Hardware store", "Online order"],
+        amount: (-250.0, -20.0),
+        schedule: Schedule::PerWeek(0.5),
+    },
+];
+
+/// Fill an empty, initialized database with a demo user, categories and a year of transactions
+/// ending on `today`.
+///
+/// The data is generated from a fixed seed, so calling this with the same `today` always creates
+/// the same data. Log in with [DEMO_EMAIL] and [DEMO_PASSWORD].
+///
+/// # Errors
+/// Returns an error if the data could not be inserted, e.g. because the database already has a
+/// user with the demo email address.
+pub fn seed_demo_data(connection: &Connection, today: Date) -> Result<(), rusqlite::Error> {
+    let mut rng = StdRng::seed_from_u64(DEMO_SEED);
+    // Use the lowest cost that bcrypt allows to keep start up fast. The password is rehashed with
+    // the server's cost when the demo user logs in.
+    let password_hash = PasswordHash::new(ValidatedPassword::new_unchecked(DEMO_PASSWORD), 4)
+        .expect("hashing the demo password should not fail");
+
+    let transaction =
+        SqlTransaction::new_unchecked(connection, rusqlite::TransactionBehavior::Exclusive)?;
+
+    transaction.execute(
+        "INSERT INTO user (email, password) VALUES (?1, ?2)",
+        (DEMO_EMAIL, password_hash.to_string()),
+    )?;
+    let user_id = transaction.last_insert_rowid();
+
+    let mut category_ids = Vec::with_capacity(PATTERNS.len());
+    for pattern in &PATTERNS {
+        transaction.execute(
+            "INSERT INTO category (name, user_id) VALUES (?1, ?2)",
+            (pattern.category, user_id),
+        )?;
+        category_ids.push(transaction.last_insert_rowid());
+    }
+
+    let mut insert = transaction.prepare(
+        "INSERT INTO \"transaction\" (amount, date, description, category_id, user_id, reconciled) \
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?;
+    let start = today - Duration::days(DEMO_DAYS - 1);
+
+    for date in (0..DEMO_DAYS).map(|offset| start + Duration::days(offset)) {
+        for (pattern, category_id) in PATTERNS.iter().zip(&category_ids) {
+            let occurs = match pattern.schedule {
+                Schedule::Monthly(day) => date.day() == day,
+                Schedule::PerWeek(times) => rng.gen_bool(times / 7.0),
+            };
+
+            if !occurs {
+                continue;
+            }
+
+            let (low, high) = pattern.amount;
+            let amount = if low == high {
+                low
+            } else {
+                (rng.gen_range(low..high) * 100.0).round() / 100.0
+            };
+            let description = pattern.descriptions[rng.gen_range(0..pattern.descriptions.len())];
+            // Leave the last month unreconciled so that the reconcile page has something to show.
+            let reconciled = today - date > Duration::days(30);
+
+            insert.execute((amount, date, description, category_id, user_id, reconciled))?;
+        }
+    }
+
+    drop(insert);
+    transaction.commit()
+}
+
+#[cfg(test)]
+mod demo_tests {
+    use rusqlite::Connection;
+    use time::macros::date;
+
+    use crate::{db::initialize, models::PasswordHash};
+
+    use super::{seed_demo_data, DEMO_EMAIL, DEMO_PASSWORD, PATTERNS};
+
+    fn seed(today: time::Date) -> Connection {
+        let connection = Connection::open_in_memory().unwrap();
+        initialize(&connection).unwrap();
+        seed_demo_data(&connection, today).unwrap();
+
+        connection
+    }
+
+    fn get_transactions(connection: &Connection) -> Vec<(f64, String, String, i64)> {
+        connection
+            .prepare(
+                "SELECT amount, date, description, category_id FROM \"transaction\" ORDER BY id",
+            )
+            .unwrap()
+            .query_map((), |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn seeds_user_categories_and_a_year_of_transactions() {
+        let today = date!(2024 - 06 - 30);
+        let connection = seed(today);
+
+        let password: String = connection
+            .query_row(
+                "SELECT password FROM user WHERE email = ?1",
+                [DEMO_EMAIL],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(PasswordHash::new_unchecked(&password)
+            .verify(DEMO_PASSWORD)
+            .unwrap());
+        let category_count: i64 = connection
+            .query_row("SELECT COUNT(*) FROM category", (), |row| row.get(0))
+            .unwrap();
+        assert_eq!(category_count, PATTERNS.len() as i64);
+
+        let (first_date, last_date, salary_count): (String, String, i64) = connection
+            .query_row(
+                "SELECT MIN(date), MAX(date), SUM(description = 'Salary') FROM \"transaction\"",
+                (),
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert!(first_date.as_str() >= "2023-07-01");
+        assert!(last_date.as_str() <= "2024-06-30");
+        assert_eq!(salary_count, 12);
+        assert!(get_transactions(&connection).len() > 300);
+    }
+
+    #[test]
+    fn seeded_data_is_deterministic() {
+        let today = date!(2024 - 06 - 30);
+
+        assert_eq!(
+            get_transactions(&seed(today)),
+            get_transactions(&seed(today))
+        );
+    }
+
+    #[test]
+    fn seeding_twice_fails() {
+        let connection = seed(date!(2024 - 06 - 30));
+
+        assert!(seed_demo_data(&connection, date!(2024 - 06 - 30)).is_err());
+    }
+}
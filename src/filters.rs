@@ -0,0 +1,302 @@
+//! Custom template filters for formatting values, e.g. amounts of money.
+//!
+//! To use these filters in a template, import this module where the template struct is defined
+//! with `use crate::filters;`.
+
+use std::{fmt::Display, str::FromStr, sync::OnceLock};
+
+use askama::{Html, MarkupDisplay};
+
+/// The text rendered in place of an amount that is not a finite number.
+pub const INVALID_AMOUNT: &str = "—";
+
+/// How negative amounts of money are written.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NegativeStyle {
+    /// With a leading minus sign, e.g. -$12.34.
+    #[default]
+    Minus,
+    /// In parentheses, e.g. ($12.34).
+    Parentheses,
+}
+
+impl FromStr for NegativeStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "minus" => Ok(Self::Minus),
+            "parentheses" => Ok(Self::Parentheses),
+            _ => Err(format!(
+                "invalid negative style '{s}', expected one of 'minus' or 'parentheses'"
+            )),
+        }
+    }
+}
+
+impl Display for NegativeStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let style = match self {
+            Self::Minus => "minus",
+            Self::Parentheses => "parentheses",
+        };
+
+        write!(f, "{style}")
+    }
+}
+
+/// How amounts of money are written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoneyFormat {
+    /// The symbol written before the amount, e.g. "$".
+    pub currency_symbol: String,
+    /// How negative amounts are written.
+    pub negative_style: NegativeStyle,
+}
+
+impl Default for MoneyFormat {
+    fn default() -> Self {
+        Self {
+            currency_symbol: "$".to_owned(),
+            negative_style: NegativeStyle::default(),
+        }
+    }
+}
+
+/// A value that can be formatted as an amount of money.
+///
+/// Askama passes values to filters by reference, or by reference to a reference for variables
+/// bound in the template, so this is implemented for references as well as [f64].
+pub trait Amount {
+    /// The amount as a number.
+    fn amount(&self) -> f64;
+}
+
+impl Amount for f64 {
+    fn amount(&self) -> f64 {
+        *self
+    }
+}
+
+impl<T: Amount + ?Sized> Amount for &T {
+    fn amount(&self) -> f64 {
+        (**self).amount()
+    }
+}
+
+static MONEY_FORMAT: OnceLock<MoneyFormat> = OnceLock::new();
+
+/// Set how the [money] and [signed_money] filters write amounts.
+///
+/// This can only be set once, later calls are ignored.
+pub fn set_money_format(format: MoneyFormat) {
+    if MONEY_FORMAT.set(format).is_err() {
+        tracing::warn!("The money format has already been set, ignoring the new format.");
+    }
+}
+
+fn get_money_format() -> &'static MoneyFormat {
+    MONEY_FORMAT.get_or_init(MoneyFormat::default)
+}
+
+/// Write `amount` rounded to cents with thousands separators, e.g. -$1,234.50.
+///
+/// Amounts that are NaN or infinite are written as [INVALID_AMOUNT].
+pub fn format_money(amount: f64, format: &MoneyFormat) -> String {
+    if !amount.is_finite() {
+        return INVALID_AMOUNT.to_owned();
+    }
+
+    let digits = format!("{:.2}", amount.abs());
+    let (whole, cents) = digits.split_once('.').unwrap_or((&digits, "00"));
+    let mut grouped = String::with_capacity(whole.len() + whole.len() / 3);
+
+    for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+
+        grouped.push(digit);
+    }
+
+    let symbol = &format.currency_symbol;
+    // Amounts that round to zero, e.g. -0.001, should not be written as negative.
+    let is_negative = amount < 0.0 && digits.bytes().any(|b| b.is_ascii_digit() && b != b'0');
+
+    match (is_negative, format.negative_style) {
+        (false, _) => format!("{symbol}{grouped}.{cents}"),
+        (true, NegativeStyle::Minus) => format!("-{symbol}{grouped}.{cents}"),
+        (true, NegativeStyle::Parentheses) => format!("({symbol}{grouped}.{cents})"),
+    }
+}
+
+/// Format an amount of money, e.g. `{{ transaction.amount()|money }}` renders -$1,234.50.
+pub fn money(amount: impl Amount) -> askama::Result<String> {
+    Ok(format_money(amount.amount(), get_money_format()))
+}
+
+/// Format an amount of money in a span coloured green for income and red for expenses.
+///
+/// The output is HTML, so it must be followed by the `safe` filter, e.g.
+/// `{{ transaction.amount()|signed_money|safe }}`.
+pub fn signed_money(amount: impl Amount) -> askama::Result<String> {
+    let amount = amount.amount();
+    let class = if !amount.is_finite() || amount == 0.0 {
+        ""
+    } else if amount > 0.0 {
+        "text-green-600 dark:text-green-400"
+    } else {
+        "text-red-600 dark:text-red-400"
+    };
+
+    Ok(format!(
+        r#"<span class="{class}">{}</span>"#,
+        MarkupDisplay::new_unsafe(money(amount)?, Html)
+    ))
+}
+
+/// The byte range of the first match of `query` in `text` at or after `from`, ignoring case.
+fn find_ignoring_case(text: &str, query: &str, from: usize) -> Option<(usize, usize)> {
+    text[from..].char_indices().find_map(|(offset, _)| {
+        let start = from + offset;
+        let mut text_chars = text[start..].chars();
+        let mut end = start;
+
+        for query_char in query.chars() {
+            let text_char = text_chars.next()?;
+
+            if !text_char.to_lowercase().eq(query_char.to_lowercase()) {
+                return None;
+            }
+
+            end += text_char.len_utf8();
+        }
+
+        Some((start, end))
+    })
+}
+
+/// Escape `text` and wrap each match of `query` in a `mark` element, ignoring case.
+///
+/// The output is HTML, so it must be followed by the `safe` filter, e.g.
+/// `{{ transaction.description()|highlight(query)|safe }}`.
+pub fn highlight(text: impl AsRef<str>, query: impl AsRef<str>) -> askama::Result<String> {
+    let (text, query) = (text.as_ref(), query.as_ref());
+    let mut html = String::new();
+    let mut position = 0;
+
+    if !query.is_empty() {
+        while let Some((start, end)) = find_ignoring_case(text, query, position) {
+            html.push_str(&format!(
+                "{}<mark>{}</mark>",
+                MarkupDisplay::new_safe(&text[position..start], Html),
+                MarkupDisplay::new_safe(&text[start..end], Html)
+            ));
+            position = end;
+        }
+    }
+
+    html.push_str(&MarkupDisplay::new_safe(&text[position..], Html).to_string());
+
+    Ok(html)
+}
+
+#[cfg(test)]
+mod filters_tests {
+    use super::{
+        format_money, highlight, signed_money, MoneyFormat, NegativeStyle, INVALID_AMOUNT,
+    };
+
+    fn parentheses() -> MoneyFormat {
+        MoneyFormat {
+            currency_symbol: "€".to_owned(),
+            negative_style: NegativeStyle::Parentheses,
+        }
+    }
+
+    #[test]
+    fn formats_positive_amounts() {
+        let format = MoneyFormat::default();
+
+        assert_eq!(format_money(1234.5, &format), "$1,234.50");
+        assert_eq!(format_money(999.999, &format), "$1,000.00");
+        assert_eq!(format_money(12.3, &format), "$12.30");
+        assert_eq!(format_money(123456.0, &format), "$123,456.00");
+    }
+
+    #[test]
+    fn formats_negative_amounts() {
+        assert_eq!(format_money(-1234.5, &MoneyFormat::default()), "-$1,234.50");
+        assert_eq!(format_money(-1234.5, &parentheses()), "(€1,234.50)");
+    }
+
+    #[test]
+    fn formats_zero_without_sign() {
+        for amount in [0.0, -0.0, -0.001] {
+            assert_eq!(format_money(amount, &MoneyFormat::default()), "$0.00");
+            assert_eq!(format_money(amount, &parentheses()), "€0.00");
+        }
+    }
+
+    #[test]
+    fn formats_very_large_amounts() {
+        assert_eq!(
+            format_money(-1_234_567_890_123.45, &MoneyFormat::default()),
+            "-$1,234,567,890,123.45"
+        );
+        assert!(format_money(f64::MAX, &MoneyFormat::default()).starts_with("$179,769,313,"));
+    }
+
+    #[test]
+    fn guards_against_nan_and_infinity() {
+        for amount in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert_eq!(
+                format_money(amount, &MoneyFormat::default()),
+                INVALID_AMOUNT
+            );
+        }
+    }
+
+    #[test]
+    fn signed_money_colours_income_and_expenses() {
+        assert_eq!(
+            signed_money(12.5).unwrap(),
+            r#"<span class="text-green-600 dark:text-green-400">$12.50</span>"#
+        );
+        assert_eq!(
+            signed_money(-12.5).unwrap(),
+            r#"<span class="text-red-600 dark:text-red-400">-$12.50</span>"#
+        );
+        assert_eq!(signed_money(0.0).unwrap(), r#"<span class="">$0.00</span>"#);
+        assert_eq!(
+            signed_money(f64::NAN).unwrap(),
+            format!(r#"<span class="">{INVALID_AMOUNT}</span>"#)
+        );
+    }
+
+    #[test]
+    fn highlight_marks_every_match_ignoring_case() {
+        assert_eq!(
+            highlight("Buckstars coffee at BUCKSTARS", "buckstars").unwrap(),
+            "<mark>Buckstars</mark> coffee at <mark>BUCKSTARS</mark>"
+        );
+        assert_eq!(highlight("Rent", "food").unwrap(), "Rent");
+        assert_eq!(highlight("Rent", "").unwrap(), "Rent");
+        assert_eq!(
+            highlight("Café Ümlaut", "ü").unwrap(),
+            "Café <mark>Ü</mark>mlaut"
+        );
+    }
+
+    #[test]
+    fn highlight_escapes_text_and_matches() {
+        assert_eq!(
+            highlight("<script>alert(1)</script>", "<script>").unwrap(),
+            "<mark>&lt;script&gt;</mark>alert(1)&lt;/script&gt;"
+        );
+        assert_eq!(
+            highlight("50% & more", "%").unwrap(),
+            "50<mark>%</mark> &amp; more"
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! Sending clients to the HTTPS server: redirecting plain HTTP requests and setting HSTS headers.
+
+use axum::{
+    extract::{Host, Request, State},
+    http::{
+        header::{LOCATION, STRICT_TRANSPORT_SECURITY},
+        HeaderValue, StatusCode, Uri,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Router,
+};
+
+/// The default port for HTTPS, which is left out of redirect URLs.
+const DEFAULT_HTTPS_PORT: u16 = 443;
+
+/// A router that permanently redirects every request to the same host, path and query on the
+/// HTTPS server listening on `https_port`.
+///
+/// Serve this on the plain HTTP port so that people who type the bare hostname end up on the app
+/// instead of getting a connection error.
+pub fn build_https_redirect_router(https_port: u16) -> Router {
+    Router::new()
+        .fallback(redirect_to_https)
+        .with_state(https_port)
+}
+
+async fn redirect_to_https(State(https_port): State<u16>, Host(host): Host, uri: Uri) -> Response {
+    let path_and_query = uri
+        .path_and_query()
+        .map(|path_and_query| path_and_query.as_str())
+        .unwrap_or("/");
+
+    let location = get_https_url(&host, https_port, path_and_query);
+
+    match HeaderValue::from_str(&location) {
+        Ok(location) => (StatusCode::MOVED_PERMANENTLY, [(LOCATION, location)]).into_response(),
+        Err(_) => (StatusCode::BAD_REQUEST, "Invalid host").into_response(),
+    }
+}
+
+/// The HTTPS URL for `path_and_query` on `host`, replacing any port in `host` with `https_port`.
+fn get_https_url(host: &str, https_port: u16, path_and_query: &str) -> String {
+    let host = strip_port(host);
+
+    if https_port == DEFAULT_HTTPS_PORT {
+        format!("https://{host}{path_and_query}")
+    } else {
+        format!("https://{host}:{https_port}{path_and_query}")
+    }
+}
+
+/// Remove the port from `host`, e.g. "example.com:80" becomes "example.com".
+fn strip_port(host: &str) -> &str {
+    // IPv6 addresses contain colons, so they are wrapped in brackets when a port is given.
+    if let Some(end) = host.find(']') {
+        return &host[..=end];
+    }
+
+    host.split_once(':').map_or(host, |(host, _)| host)
+}
+
+/// Tell browsers to only use HTTPS for this site for the next `max_age` seconds.
+///
+/// Only use this when the site is served over HTTPS, browsers ignore the header otherwise.
+pub async fn hsts_middleware(State(max_age): State<u64>, request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+
+    if let Ok(value) = HeaderValue::from_str(&format!("max-age={max_age}")) {
+        response
+            .headers_mut()
+            .insert(STRICT_TRANSPORT_SECURITY, value);
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod https_tests {
+    use axum::{
+        http::{
+            header::{HOST, LOCATION, STRICT_TRANSPORT_SECURITY},
+            HeaderValue, StatusCode,
+        },
+        middleware,
+        routing::get,
+        Router,
+    };
+    use axum_test::TestServer;
+
+    use super::{build_https_redirect_router, get_https_url, hsts_middleware};
+
+    #[tokio::test]
+    async fn redirect_preserves_path_and_query() {
+        let server = TestServer::new(build_https_redirect_router(3000)).unwrap();
+
+        let response = server
+            .get("/transactions")
+            .add_query_param("page", 2)
+            .add_header(HOST, HeaderValue::from_static("example.com:8080"))
+            .await;
+
+        response.assert_status(StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(
+            response.header(LOCATION),
+            "https://example.com:3000/transactions?page=2"
+        );
+    }
+
+    #[tokio::test]
+    async fn redirect_leaves_out_default_port() {
+        let server = TestServer::new(build_https_redirect_router(443)).unwrap();
+
+        let response = server
+            .post("/log_in")
+            .add_header(HOST, HeaderValue::from_static("example.com"))
+            .await;
+
+        response.assert_status(StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(response.header(LOCATION), "https://example.com/log_in");
+    }
+
+    #[test]
+    fn https_url_handles_ipv6_hosts() {
+        assert_eq!(
+            get_https_url("[::1]:8080", 3000, "/"),
+            "https://[::1]:3000/"
+        );
+        assert_eq!(get_https_url("[::1]", 443, "/"), "https://[::1]/");
+    }
+
+    #[tokio::test]
+    async fn hsts_header_is_set() {
+        let app = Router::new()
+            .route("/", get(|| async { "hello" }))
+            .layer(middleware::from_fn_with_state(31536000, hsts_middleware));
+        let server = TestServer::new(app).unwrap();
+
+        let response = server.get("/").await;
+
+        assert_eq!(
+            response.header(STRICT_TRANSPORT_SECURITY),
+            "max-age=31536000"
+        );
+    }
+}
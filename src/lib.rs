@@ -19,26 +19,44 @@ use axum::{
     Json,
 };
 use axum_server::Handle;
+use db::is_unavailable_error;
 use models::{CategoryError, TransactionError};
+use routes::{
+    get_database_lock_error_response, get_database_unavailable_response, get_error_response,
+};
 use serde_json::json;
 use thiserror::Error;
 use tokio::signal;
 
 pub use routes::build_router;
-pub use state::AppState;
+pub use state::{AppState, RegistrationMode};
 
+pub mod admin;
+pub mod aggregation;
 pub mod auth;
+pub mod config;
 pub mod db;
+pub mod demo;
+pub mod filters;
+pub mod https;
+pub mod logging;
 pub mod models;
+pub mod operations;
+pub mod pagination;
+pub mod proxy;
 pub mod routes;
+pub mod scheduled_backups;
 pub mod state;
 pub mod stores;
+pub mod timezone;
+pub mod webhooks;
 
 /// An async task that waits for either the ctrl+c or terminate signal, whichever comes first, and
 /// then signals the server to shut down gracefully.
 ///
-/// `handle` is a handle to an Axum `Server`.
-pub async fn graceful_shutdown(handle: Handle) {
+/// `handle` is a handle to an Axum `Server`. Open connections are given `grace_period` to finish
+/// before they are closed.
+pub async fn graceful_shutdown(handle: Handle, grace_period: Duration) {
     let ctrl_c = async {
         signal::ctrl_c()
             .await
@@ -59,11 +77,11 @@ pub async fn graceful_shutdown(handle: Handle) {
     tokio::select! {
         _ = ctrl_c => {
             tracing::debug!("Received ctrl+c signal.");
-            handle.graceful_shutdown(Some(Duration::from_secs(1)));
+            handle.graceful_shutdown(Some(grace_period));
         },
         _ = terminate => {
             tracing::debug!("Received terminate signal.");
-            handle.graceful_shutdown(Some(Duration::from_secs(1)));
+            handle.graceful_shutdown(Some(grace_period));
         },
     }
 }
@@ -86,6 +104,11 @@ enum AppError {
     /// The user is not authenticated/authorized to access the given resource.
     #[error("auth error")]
     AuthError(AuthError),
+
+    /// The database cannot be used right now, e.g. because another program is holding a lock on
+    /// it or the file has become read-only.
+    #[error("the database is unavailable: {0}")]
+    DatabaseUnavailable(rusqlite::Error),
 }
 
 impl From<AuthError> for AppError {
@@ -98,36 +121,97 @@ impl From<CategoryError> for AppError {
     fn from(e: CategoryError) -> Self {
         tracing::error!("{e:?}");
 
-        AppError::CategoryError(e)
+        match e {
+            CategoryError::SqlError(error) if is_unavailable_error(&error) => {
+                AppError::DatabaseUnavailable(error)
+            }
+            e => AppError::CategoryError(e),
+        }
     }
 }
 
 impl From<TransactionError> for AppError {
     fn from(value: TransactionError) -> Self {
-        AppError::TransactionError(value)
+        match value {
+            TransactionError::SqlError(error) if is_unavailable_error(&error) => {
+                AppError::DatabaseUnavailable(error)
+            }
+            value => AppError::TransactionError(value),
+        }
     }
 }
 
 impl IntoResponse for AppError {
+    /// Get a response for the error that [get_error_response] renders as an error page,
+    /// an HTMX alert or JSON depending on the request.
     fn into_response(self) -> Response {
-        let (status, error_message) = match self {
-            AppError::CategoryError(e) => return e.into_response(),
-            AppError::TransactionError(e) => (
+        match self {
+            AppError::DatabaseUnavailable(error) => get_database_unavailable_response(&error),
+            // Handlers that wrap store errors with `map_err` skip the `From` impls above.
+            AppError::CategoryError(CategoryError::SqlError(error))
+            | AppError::TransactionError(TransactionError::SqlError(error))
+                if is_unavailable_error(&error) =>
+            {
+                get_database_unavailable_response(&error)
+            }
+            AppError::CategoryError(CategoryError::DatabaseLockError)
+            | AppError::TransactionError(TransactionError::DatabaseLockError) => {
+                get_database_lock_error_response()
+            }
+            AppError::CategoryError(CategoryError::NotFound)
+            | AppError::TransactionError(TransactionError::NotFound)
+            | AppError::NotFound => get_error_response(
+                StatusCode::NOT_FOUND,
+                "not_found",
+                "The requested resource could not be found.",
+            ),
+            AppError::CategoryError(
+                e @ (CategoryError::InvalidName | CategoryError::NameTooLong),
+            ) => get_error_response(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "invalid_category",
+                &format!("Invalid category: {e}"),
+            ),
+            AppError::CategoryError(e @ CategoryError::DuplicateName) => get_error_response(
+                StatusCode::CONFLICT,
+                "duplicate_category",
+                &format!("Invalid category: {e}"),
+            ),
+            AppError::CategoryError(e) => get_error_response(
                 StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Internal server error: {e:?}"),
+                "internal_error",
+                &format!("Internal server error: {e:?}"),
             ),
-
-            AppError::AuthError(e) => (StatusCode::UNAUTHORIZED, format!("Auth error: {e:?}")),
-            AppError::NotFound => (
-                StatusCode::NOT_FOUND,
-                "The requested resource could not be found.".to_string(),
+            AppError::TransactionError(
+                e @ (TransactionError::FutureDate | TransactionError::InvalidCategory),
+            ) => get_error_response(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "invalid_transaction",
+                &format!("Invalid transaction: {e}"),
             ),
-        };
-
-        let body = Json(json!({
-            "error": error_message,
-        }));
-
-        (status, body).into_response()
+            AppError::TransactionError(e) => get_error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                &format!("Internal server error: {e:?}"),
+            ),
+            AppError::AuthError(e) => get_error_response(
+                StatusCode::UNAUTHORIZED,
+                "unauthorized",
+                &format!("Auth error: {e:?}"),
+            ),
+        }
     }
 }
+
+/// Create a JSON response with the body `{ "error": message, "code": code }`.
+///
+/// `code` is a short, stable identifier for the kind of error that clients can match on, while
+/// `message` is a human-readable description that may change.
+pub(crate) fn json_error_response(status: StatusCode, code: &str, message: &str) -> Response {
+    let body = Json(json!({
+        "error": message,
+        "code": code,
+    }));
+
+    (status, body).into_response()
+}
@@ -0,0 +1,570 @@
+//! Sets up logging for the server and logs each request.
+//!
+//! Logs can be written for people to read or as JSON lines for log aggregators such as Loki.
+
+use std::{
+    fmt::Display,
+    fs::OpenOptions,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+use tracing::{Instrument, Subscriber};
+use tracing_subscriber::{
+    filter, fmt::MakeWriter, layer::SubscriberExt, registry::LookupSpan, util::SubscriberInitExt,
+    Layer,
+};
+
+use crate::proxy::{get_peer_ip, ProxySettings};
+
+/// The header that holds the ID of a request in responses.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The file that debug logs are written to.
+const DEBUG_LOG_PATH: &str = "debug.log";
+
+/// How long a request can take before it is logged as a warning.
+pub const DEFAULT_SLOW_REQUEST_THRESHOLD: Duration = Duration::from_secs(1);
+
+/// How [logging_middleware] logs requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestLogSettings {
+    /// Used to find the client IP address of each request.
+    pub proxy_settings: ProxySettings,
+    /// Requests that take longer than this are logged as warnings.
+    pub slow_request_threshold: Duration,
+}
+
+impl Default for RequestLogSettings {
+    fn default() -> Self {
+        Self {
+            proxy_settings: ProxySettings::default(),
+            slow_request_threshold: DEFAULT_SLOW_REQUEST_THRESHOLD,
+        }
+    }
+}
+
+/// How log lines are formatted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Multi-line, human readable logs.
+    #[default]
+    Pretty,
+    /// One JSON object per line.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pretty" => Ok(Self::Pretty),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "invalid log format '{s}', expected one of 'pretty' or 'json'"
+            )),
+        }
+    }
+}
+
+impl Display for LogFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let format = match self {
+            Self::Pretty => "pretty",
+            Self::Json => "json",
+        };
+
+        write!(f, "{format}")
+    }
+}
+
+/// Log info and above to stdout and debug and above to the file `debug.log` in `format`.
+///
+/// # Panics
+/// Panics if the log file cannot be opened or if logging has already been set up.
+pub fn init_logging(format: LogFormat) {
+    let log_file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(DEBUG_LOG_PATH)
+        .expect("Could not create log file");
+    let log_file = Arc::new(log_file);
+
+    match format {
+        LogFormat::Pretty => {
+            let stdout_log = tracing_subscriber::fmt::layer().pretty();
+            let debug_log = tracing_subscriber::fmt::layer()
+                .pretty()
+                .with_writer(log_file);
+
+            tracing_subscriber::registry()
+                .with(
+                    stdout_log
+                        .with_filter(filter::LevelFilter::INFO)
+                        .and_then(debug_log)
+                        .with_filter(filter::LevelFilter::DEBUG),
+                )
+                .init();
+        }
+        LogFormat::Json => {
+            let stdout_log = json_layer(std::io::stdout);
+            let debug_log = json_layer(log_file);
+
+            tracing_subscriber::registry()
+                .with(
+                    stdout_log
+                        .with_filter(filter::LevelFilter::INFO)
+                        .and_then(debug_log)
+                        .with_filter(filter::LevelFilter::DEBUG),
+                )
+                .init();
+        }
+    }
+}
+
+/// Log each request with its method, path, route, client IP address, response status and
+/// latency.
+///
+/// Requests are logged at INFO, or at WARN if they took longer than the slow request threshold
+/// or failed with a server error, so that slow routes can be found by filtering for warnings.
+/// The route is the pattern that matched the request, e.g. `/transactions/:transaction_id`, as
+/// recorded by [record_matched_path], so that requests for different IDs can be grouped.
+///
+/// The client IP address comes from `X-Forwarded-For` if the proxy settings trust proxy headers.
+///
+/// Each request is given a random ID which is added to the request's span, so that every log
+/// line written while handling the request includes it, and is returned in the `X-Request-Id`
+/// header.
+pub async fn logging_middleware(
+    State(settings): State<RequestLogSettings>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let request_id = format!("{:032x}", rand::random::<u128>());
+    let client_ip = settings
+        .proxy_settings
+        .get_client_ip(request.headers(), get_peer_ip(&request))
+        .map_or_else(|| "unknown".to_owned(), |address| address.to_string());
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %request.method(),
+        path = %request.uri().path(),
+        client_ip = %client_ip,
+    );
+
+    let start = Instant::now();
+    let mut response = next.run(request).instrument(span.clone()).await;
+    let latency = start.elapsed();
+    let latency_ms = latency.as_secs_f64() * 1000.0;
+    let status = response.status().as_u16();
+    let route = response
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", MatchedPath::as_str);
+
+    span.in_scope(|| {
+        if latency > settings.slow_request_threshold || response.status().is_server_error() {
+            tracing::warn!(status, latency_ms, route, "finished processing request");
+        } else {
+            tracing::info!(status, latency_ms, route, "finished processing request");
+        }
+    });
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+
+    response
+}
+
+/// Copy the route pattern that matched the request to the response so that
+/// [logging_middleware], which runs before routing, can log it.
+///
+/// Add this with [Router::route_layer](axum::Router::route_layer) so that it runs after routing.
+pub async fn record_matched_path(
+    matched_path: Option<MatchedPath>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+
+    if let Some(matched_path) = matched_path {
+        response.extensions_mut().insert(matched_path);
+    }
+
+    response
+}
+
+/// A [Layer] that writes each event as a single line of JSON to the writers created by
+/// `make_writer`.
+///
+/// Each line has the event's timestamp, level, target and fields. The fields of the span that the
+/// event happened in, e.g. the request ID, are under `span`, and every span the event is in is
+/// listed under `spans`.
+fn json_layer<S, W>(make_writer: W) -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'a> MakeWriter<'a> + 'static,
+{
+    tracing_subscriber::fmt::layer()
+        .json()
+        .flatten_event(true)
+        .with_writer(make_writer)
+}
+
+#[cfg(test)]
+mod logging_tests {
+    use std::{
+        io::Write,
+        str::FromStr,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use axum::{
+        http::{HeaderName, HeaderValue, StatusCode},
+        middleware,
+        routing::get,
+        Router,
+    };
+    use axum_test::TestServer;
+    use rusqlite::Connection;
+    use serde_json::Value;
+    use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+    use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt};
+
+    use crate::{
+        auth::log_in::LogInData,
+        build_router,
+        proxy::{ProxySettings, X_FORWARDED_FOR},
+        routes::endpoints,
+        stores::sql_store::create_app_state,
+    };
+
+    use super::{
+        json_layer, logging_middleware, record_matched_path, LogFormat, RequestLogSettings,
+        REQUEST_ID_HEADER,
+    };
+
+    /// Collects log output in memory.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Buffer {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+
+        fn lines(&self) -> Vec<Value> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).expect("log line should be JSON"))
+                .collect()
+        }
+    }
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Buffer {
+        type Writer = Buffer;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn log_format_round_trips_through_string() {
+        for format in [LogFormat::Pretty, LogFormat::Json] {
+            assert_eq!(LogFormat::from_str(&format.to_string()), Ok(format));
+        }
+
+        assert!(LogFormat::from_str("xml").is_err());
+    }
+
+    #[test]
+    fn writes_events_as_json_lines() {
+        let buffer = Buffer::default();
+        let subscriber = tracing_subscriber::registry().with(json_layer(buffer.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("outer", foo = "bar", count = 1);
+            let _guard = span.enter();
+            tracing::warn!(count = 2, "something happened");
+        });
+
+        let lines = buffer.lines();
+        assert_eq!(lines.len(), 1);
+        let line = &lines[0];
+        assert_eq!(line["level"], "WARN");
+        assert_eq!(line["target"], module_path!());
+        assert_eq!(line["message"], "something happened");
+        assert_eq!(line["count"], 2);
+        assert_eq!(line["span"]["name"], "outer");
+        assert_eq!(line["span"]["foo"], "bar");
+        assert_eq!(line["span"]["count"], 1);
+        assert!(OffsetDateTime::parse(line["timestamp"].as_str().unwrap(), &Rfc3339).is_ok());
+    }
+
+    #[tokio::test]
+    async fn request_logs_are_json_and_share_the_request_id() {
+        let buffer = Buffer::default();
+        let subscriber = tracing_subscriber::registry().with(json_layer(buffer.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let app = Router::new()
+            .route(
+                "/foo",
+                get(|| async {
+                    tracing::info!("handling foo");
+                    "foo"
+                }),
+            )
+            .layer(middleware::from_fn_with_state(
+                RequestLogSettings::default(),
+                logging_middleware,
+            ));
+        let server = TestServer::new(app).unwrap();
+
+        let response = server.get("/foo").await;
+
+        response.assert_status_ok();
+        let request_id = response.header(REQUEST_ID_HEADER);
+        let request_id = request_id.to_str().unwrap();
+        assert_eq!(request_id.len(), 32);
+
+        let lines = buffer.lines();
+        let handler_line = lines
+            .iter()
+            .find(|line| line["message"] == "handling foo")
+            .expect("could not find the handler's log line");
+        assert_eq!(handler_line["span"]["request_id"], request_id);
+
+        let request_line = lines
+            .iter()
+            .find(|line| line["message"] == "finished processing request")
+            .expect("could not find the request log line");
+        assert_eq!(request_line["level"], "INFO");
+        assert_eq!(request_line["span"]["request_id"], request_id);
+        assert_eq!(request_line["span"]["method"], "GET");
+        assert_eq!(request_line["span"]["path"], "/foo");
+        assert_eq!(request_line["status"], 200);
+        assert!(request_line["latency_ms"].is_f64());
+    }
+
+    /// Get a server with the routes `/fast/:id`, which responds with 200 OK straight away, and
+    /// `/slow/:id`, which responds with 500 Internal Server Error after 50 ms.
+    fn get_server_with_fast_and_slow_routes(slow_request_threshold: Duration) -> TestServer {
+        let settings = RequestLogSettings {
+            slow_request_threshold,
+            ..Default::default()
+        };
+        let app = Router::new()
+            .route("/fast/:id", get(|| async { "fast" }))
+            .route(
+                "/slow/:id",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    StatusCode::INTERNAL_SERVER_ERROR
+                }),
+            )
+            .route_layer(middleware::from_fn(record_matched_path))
+            .layer(middleware::from_fn_with_state(settings, logging_middleware));
+
+        TestServer::new(app).unwrap()
+    }
+
+    /// Find the log line for the request to `path` in `buffer`.
+    fn find_request_line(buffer: &Buffer, path: &str) -> Value {
+        buffer
+            .lines()
+            .into_iter()
+            .find(|line| {
+                line["message"] == "finished processing request" && line["span"]["path"] == path
+            })
+            .expect("could not find the request log line")
+    }
+
+    #[tokio::test]
+    async fn fast_successful_requests_are_logged_as_info_with_route() {
+        let buffer = Buffer::default();
+        let subscriber = tracing_subscriber::registry().with(json_layer(buffer.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let server = get_server_with_fast_and_slow_routes(Duration::from_secs(10));
+
+        server.get("/fast/1").await.assert_status_ok();
+
+        let request_line = find_request_line(&buffer, "/fast/1");
+        assert_eq!(request_line["level"], "INFO");
+        assert_eq!(request_line["route"], "/fast/:id");
+        assert_eq!(request_line["status"], 200);
+    }
+
+    #[tokio::test]
+    async fn slow_failed_requests_are_logged_as_warnings() {
+        let buffer = Buffer::default();
+        let subscriber = tracing_subscriber::registry().with(json_layer(buffer.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let server = get_server_with_fast_and_slow_routes(Duration::from_millis(10));
+
+        server
+            .get("/slow/1")
+            .await
+            .assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+
+        let request_line = find_request_line(&buffer, "/slow/1");
+        assert_eq!(request_line["level"], "WARN");
+        assert_eq!(request_line["route"], "/slow/:id");
+        assert_eq!(request_line["status"], 500);
+        assert!(request_line["latency_ms"].as_f64().unwrap() >= 50.0);
+    }
+
+    #[tokio::test]
+    async fn requests_slower_than_threshold_are_logged_as_warnings() {
+        let buffer = Buffer::default();
+        let subscriber = tracing_subscriber::registry().with(json_layer(buffer.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let server = get_server_with_fast_and_slow_routes(Duration::ZERO);
+
+        server.get("/fast/1").await.assert_status_ok();
+
+        assert_eq!(find_request_line(&buffer, "/fast/1")["level"], "WARN");
+    }
+
+    #[tokio::test]
+    async fn unmatched_requests_are_logged_without_route() {
+        let buffer = Buffer::default();
+        let subscriber = tracing_subscriber::registry().with(json_layer(buffer.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let server = get_server_with_fast_and_slow_routes(Duration::from_secs(10));
+
+        server.get("/nope").await.assert_status_not_found();
+
+        let request_line = find_request_line(&buffer, "/nope");
+        assert_eq!(request_line["level"], "INFO");
+        assert_eq!(request_line["route"], "unmatched");
+    }
+
+    #[tokio::test]
+    async fn client_ip_comes_from_trusted_proxy_header() {
+        let buffer = Buffer::default();
+        let subscriber = tracing_subscriber::registry().with(json_layer(buffer.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let settings = RequestLogSettings {
+            proxy_settings: ProxySettings {
+                tls: false,
+                trust_proxy_headers: true,
+            },
+            ..Default::default()
+        };
+        let app = Router::new()
+            .route("/foo", get(|| async { "foo" }))
+            .layer(middleware::from_fn_with_state(settings, logging_middleware));
+        let server = TestServer::new(app).unwrap();
+
+        server
+            .get("/foo")
+            .add_header(
+                HeaderName::from_static(X_FORWARDED_FOR),
+                HeaderValue::from_static("203.0.113.7"),
+            )
+            .await
+            .assert_status_ok();
+
+        let lines = buffer.lines();
+        let request_line = lines
+            .iter()
+            .find(|line| line["message"] == "finished processing request")
+            .expect("could not find the request log line");
+        assert_eq!(request_line["span"]["client_ip"], "203.0.113.7");
+    }
+
+    #[tokio::test]
+    async fn request_ids_are_unique() {
+        let app = Router::new().route("/foo", get(|| async { "foo" })).layer(
+            middleware::from_fn_with_state(RequestLogSettings::default(), logging_middleware),
+        );
+        let server = TestServer::new(app).unwrap();
+
+        let first = server.get("/foo").await.header(REQUEST_ID_HEADER);
+        let second = server.get("/foo").await.header(REQUEST_ID_HEADER);
+
+        assert_ne!(first, second);
+    }
+
+    #[tokio::test]
+    async fn passwords_are_not_logged() {
+        const PASSWORD: &str = "averysafeandsecurepassword";
+        const NEW_PASSWORD: &str = "anevensaferandmoresecurepassword";
+        const WRONG_PASSWORD: &str = "notthepasswordatall";
+
+        let buffer = Buffer::default();
+        let subscriber = tracing_subscriber::registry().with(json_layer(buffer.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        state.password_hash_cost = 4;
+        let app = build_router(state).layer(middleware::from_fn_with_state(
+            RequestLogSettings::default(),
+            logging_middleware,
+        ));
+        let mut server = TestServer::new(app).unwrap();
+        server.do_save_cookies();
+
+        server
+            .post(endpoints::USERS)
+            .form(&[
+                ("email", "foo@bar.baz"),
+                ("password", PASSWORD),
+                ("confirm_password", PASSWORD),
+            ])
+            .await;
+        for password in [WRONG_PASSWORD, PASSWORD] {
+            server
+                .post(endpoints::LOG_IN)
+                .form(&LogInData {
+                    email: "foo@bar.baz".to_string(),
+                    password: password.to_string(),
+                    remember_me: None,
+                })
+                .await;
+        }
+        server
+            .post(endpoints::CHANGE_PASSWORD)
+            .form(&[
+                ("current_password", PASSWORD),
+                ("new_password", NEW_PASSWORD),
+                ("confirm_password", NEW_PASSWORD),
+            ])
+            .await;
+
+        let logs = buffer.text();
+        assert_eq!(
+            buffer
+                .lines()
+                .iter()
+                .filter(|line| line["message"] == "finished processing request")
+                .count(),
+            4
+        );
+        for password in [PASSWORD, NEW_PASSWORD, WRONG_PASSWORD] {
+            assert!(!logs.contains(password), "found {password} in the logs");
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! This file defines a personal access token for authenticating scripts with the JSON API.
+
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+
+use super::{DatabaseID, UserID};
+
+/// The prefix of generated tokens, which makes them easy to recognise, e.g. in leaked secrets.
+const TOKEN_PREFIX: &str = "bgt_";
+
+/// The number of random characters in a generated token, not including the prefix.
+const TOKEN_LENGTH: usize = 40;
+
+/// A long-lived token that a user can create for scripts to authenticate with the JSON API.
+///
+/// Only the SHA-256 hash of the token is stored, so the plaintext token can only be shown to the
+/// user once when it is created.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiToken {
+    id: DatabaseID,
+    user_id: UserID,
+    name: String,
+    created_at: OffsetDateTime,
+    last_used_at: Option<OffsetDateTime>,
+    expires_at: Option<OffsetDateTime>,
+}
+
+impl ApiToken {
+    /// Create an API token from its parts.
+    ///
+    /// The caller should ensure that `id` is unique and that `user_id` refers to a valid user.
+    pub fn new(
+        id: DatabaseID,
+        user_id: UserID,
+        name: String,
+        created_at: OffsetDateTime,
+        last_used_at: Option<OffsetDateTime>,
+        expires_at: Option<OffsetDateTime>,
+    ) -> Self {
+        Self {
+            id,
+            user_id,
+            name,
+            created_at,
+            last_used_at,
+            expires_at,
+        }
+    }
+
+    /// Generate a new random plaintext token.
+    pub fn generate_token() -> String {
+        let random_part: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(TOKEN_LENGTH)
+            .map(char::from)
+            .collect();
+
+        format!("{TOKEN_PREFIX}{random_part}")
+    }
+
+    /// Hash a plaintext token for storing in, or looking up from, the database.
+    ///
+    /// A fast hash is fine here since generated tokens have far too much entropy to brute force.
+    pub fn hash_token(token: &str) -> String {
+        format!("{:x}", Sha256::digest(token.as_bytes()))
+    }
+
+    /// The token's ID in the database.
+    pub fn id(&self) -> DatabaseID {
+        self.id
+    }
+
+    /// The ID of the user that the token authenticates as.
+    pub fn user_id(&self) -> UserID {
+        self.user_id
+    }
+
+    /// The name the user gave the token, e.g. what script uses it.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// When the token was created.
+    pub fn created_at(&self) -> OffsetDateTime {
+        self.created_at
+    }
+
+    /// When the token was last used to authenticate a request, if ever.
+    pub fn last_used_at(&self) -> Option<OffsetDateTime> {
+        self.last_used_at
+    }
+
+    /// When the token stops being accepted, or `None` if it never expires.
+    pub fn expires_at(&self) -> Option<OffsetDateTime> {
+        self.expires_at
+    }
+
+    /// Whether the token has expired as of `now`.
+    pub fn is_expired(&self, now: OffsetDateTime) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+#[cfg(test)]
+mod api_token_tests {
+    use time::{Duration, OffsetDateTime};
+
+    use crate::models::UserID;
+
+    use super::{ApiToken, TOKEN_LENGTH, TOKEN_PREFIX};
+
+    #[test]
+    fn generate_token_creates_distinct_prefixed_tokens() {
+        let token = ApiToken::generate_token();
+
+        assert!(token.starts_with(TOKEN_PREFIX));
+        assert_eq!(token.len(), TOKEN_PREFIX.len() + TOKEN_LENGTH);
+        assert_ne!(token, ApiToken::generate_token());
+    }
+
+    #[test]
+    fn hash_token_is_deterministic_hex() {
+        let hash = ApiToken::hash_token("foobar");
+
+        assert_eq!(hash, ApiToken::hash_token("foobar"));
+        assert_ne!(hash, ApiToken::hash_token("foobaz"));
+        assert_eq!(hash.len(), 64);
+        assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[test]
+    fn is_expired() {
+        let now = OffsetDateTime::now_utc();
+        let token_expiring_at =
+            |expires_at| ApiToken::new(1, UserID::new(1), "foo".to_string(), now, None, expires_at);
+
+        assert!(!token_expiring_at(None).is_expired(now));
+        assert!(!token_expiring_at(Some(now + Duration::days(1))).is_expired(now));
+        assert!(token_expiring_at(Some(now)).is_expired(now));
+        assert!(token_expiring_at(Some(now - Duration::days(1))).is_expired(now));
+    }
+}
@@ -0,0 +1,190 @@
+//! This file defines an entry in the audit log, which records changes to users' data.
+
+use std::{fmt::Display, str::FromStr};
+
+use time::OffsetDateTime;
+
+use super::{DatabaseID, UserID};
+
+/// What was done to an entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    /// The entity was created.
+    Create,
+    /// The entity was changed.
+    Update,
+    /// The entity was deleted.
+    Delete,
+}
+
+impl AuditAction {
+    /// The name of the action, as it is stored in the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Create => "create",
+            AuditAction::Update => "update",
+            AuditAction::Delete => "delete",
+        }
+    }
+}
+
+impl Display for AuditAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AuditAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "create" => Ok(AuditAction::Create),
+            "update" => Ok(AuditAction::Update),
+            "delete" => Ok(AuditAction::Delete),
+            other => Err(format!("unknown audit action \"{other}\"")),
+        }
+    }
+}
+
+/// The kind of entity that an audit log entry is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditEntityType {
+    /// A [Transaction](super::Transaction).
+    Transaction,
+    /// A [Category](super::Category).
+    Category,
+}
+
+impl AuditEntityType {
+    /// All of the entity types, e.g. for filtering the audit log.
+    pub const ALL: [AuditEntityType; 2] = [AuditEntityType::Transaction, AuditEntityType::Category];
+
+    /// The name of the entity type, as it is stored in the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEntityType::Transaction => "transaction",
+            AuditEntityType::Category => "category",
+        }
+    }
+}
+
+impl Display for AuditEntityType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AuditEntityType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "transaction" => Ok(AuditEntityType::Transaction),
+            "category" => Ok(AuditEntityType::Category),
+            other => Err(format!("unknown audit entity type \"{other}\"")),
+        }
+    }
+}
+
+/// A record of a change that a user made to their data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditLogEntry {
+    id: DatabaseID,
+    timestamp: OffsetDateTime,
+    user_id: UserID,
+    action: AuditAction,
+    entity_type: AuditEntityType,
+    entity_id: DatabaseID,
+    summary: String,
+}
+
+impl AuditLogEntry {
+    /// Create an audit log entry from its parts.
+    pub fn new(
+        id: DatabaseID,
+        timestamp: OffsetDateTime,
+        user_id: UserID,
+        action: AuditAction,
+        entity_type: AuditEntityType,
+        entity_id: DatabaseID,
+        summary: String,
+    ) -> Self {
+        Self {
+            id,
+            timestamp,
+            user_id,
+            action,
+            entity_type,
+            entity_id,
+            summary,
+        }
+    }
+
+    /// The entry's ID in the database.
+    pub fn id(&self) -> DatabaseID {
+        self.id
+    }
+
+    /// When the change was made.
+    pub fn timestamp(&self) -> OffsetDateTime {
+        self.timestamp
+    }
+
+    /// The ID of the user that made the change.
+    pub fn user_id(&self) -> UserID {
+        self.user_id
+    }
+
+    /// What was done.
+    pub fn action(&self) -> AuditAction {
+        self.action
+    }
+
+    /// The kind of entity that was changed.
+    pub fn entity_type(&self) -> AuditEntityType {
+        self.entity_type
+    }
+
+    /// The ID of the entity that was changed.
+    pub fn entity_id(&self) -> DatabaseID {
+        self.entity_id
+    }
+
+    /// A short description of the change.
+    pub fn summary(&self) -> &str {
+        &self.summary
+    }
+}
+
+#[cfg(test)]
+mod audit_tests {
+    use std::str::FromStr;
+
+    use super::{AuditAction, AuditEntityType};
+
+    #[test]
+    fn action_round_trips_through_string() {
+        for action in [
+            AuditAction::Create,
+            AuditAction::Update,
+            AuditAction::Delete,
+        ] {
+            assert_eq!(AuditAction::from_str(action.as_str()), Ok(action));
+        }
+
+        assert!(AuditAction::from_str("foo").is_err());
+    }
+
+    #[test]
+    fn entity_type_round_trips_through_string() {
+        for entity_type in AuditEntityType::ALL {
+            assert_eq!(
+                AuditEntityType::from_str(entity_type.as_str()),
+                Ok(entity_type)
+            );
+        }
+
+        assert!(AuditEntityType::from_str("foo").is_err());
+    }
+}
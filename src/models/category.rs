@@ -16,10 +16,19 @@ pub enum CategoryError {
     #[error("a category with the given details could not found in the database")]
     NotFound,
 
-    /// An empty string was used to create a category name.
-    #[error("an empty string is not a valid category name")]
+    /// An empty string, or one with control characters such as newlines, was used to create a
+    /// category name.
+    #[error("category names must not be empty or contain control characters")]
     InvalidName,
 
+    /// The category name is longer than [MAX_CATEGORY_NAME_LENGTH] characters.
+    #[error("category names must be at most {MAX_CATEGORY_NAME_LENGTH} characters long")]
+    NameTooLong,
+
+    /// The user already has a category with the same name, ignoring case.
+    #[error("a category with that name already exists")]
+    DuplicateName,
+
     /// The user ID used to create a category does not refer to a valid user.
     #[error("the user ID does not refer to a valid user.")]
     InvalidUser,
@@ -27,15 +36,20 @@ pub enum CategoryError {
     /// An unexpected and unhandled SQL error occurred.
     #[error("an unexpected error occurred: {0}")]
     SqlError(rusqlite::Error),
+
+    /// The database lock was poisoned by a thread that panicked while holding it.
+    #[error("the database lock is poisoned")]
+    DatabaseLockError,
 }
 
 impl IntoResponse for CategoryError {
     fn into_response(self) -> askama_axum::Response {
         match self {
-            CategoryError::InvalidName => (
+            err @ (CategoryError::InvalidName | CategoryError::NameTooLong) => (
                 StatusCode::UNPROCESSABLE_ENTITY,
-                "category name cannot be emtpy.".to_string(),
+                format!("Invalid category: {err}"),
             ),
+            err @ CategoryError::DuplicateName => (StatusCode::CONFLICT, err.to_string()),
             err => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 format!("Internal server error: {err:?}"),
@@ -45,6 +59,9 @@ impl IntoResponse for CategoryError {
     }
 }
 
+/// The maximum number of characters in a category name.
+pub const MAX_CATEGORY_NAME_LENGTH: usize = 50;
+
 /// The name of a category.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
 pub struct CategoryName(String);
@@ -52,14 +69,28 @@ pub struct CategoryName(String);
 impl CategoryName {
     /// Create a category name.
     ///
+    /// Leading and trailing whitespace is removed and runs of whitespace are collapsed into a
+    /// single space.
+    ///
     /// # Errors
     ///
-    /// This function will return an error if `name` is an empty string.
+    /// This function will return a:
+    /// - [CategoryError::InvalidName] if `name` is empty or contains control characters,
+    /// - or [CategoryError::NameTooLong] if `name` is longer than [MAX_CATEGORY_NAME_LENGTH]
+    ///   characters.
     pub fn new(name: &str) -> Result<Self, CategoryError> {
+        if name.chars().any(char::is_control) {
+            return Err(CategoryError::InvalidName);
+        }
+
+        let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+
         if name.is_empty() {
             Err(CategoryError::InvalidName)
+        } else if name.chars().count() > MAX_CATEGORY_NAME_LENGTH {
+            Err(CategoryError::NameTooLong)
         } else {
-            Ok(Self(name.to_string()))
+            Ok(Self(name))
         }
     }
 
@@ -130,7 +161,7 @@ impl Category {
 
 #[cfg(test)]
 mod category_name_tests {
-    use crate::models::category::{CategoryError, CategoryName};
+    use crate::models::category::{CategoryError, CategoryName, MAX_CATEGORY_NAME_LENGTH};
 
     #[test]
     fn new_fails_on_empty_string() {
@@ -139,6 +170,46 @@ mod category_name_tests {
         assert_eq!(category_name, Err(CategoryError::InvalidName));
     }
 
+    #[test]
+    fn new_fails_on_whitespace_only() {
+        assert_eq!(
+            CategoryName::new("  \u{a0} "),
+            Err(CategoryError::InvalidName)
+        );
+    }
+
+    #[test]
+    fn new_fails_on_control_characters() {
+        for name in ["Eating\nOut", "Eating\tOut", "Rent\r", "Bell\u{7}"] {
+            assert_eq!(
+                CategoryName::new(name),
+                Err(CategoryError::InvalidName),
+                "{name:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn new_fails_on_long_name() {
+        let name = "a".repeat(MAX_CATEGORY_NAME_LENGTH + 1);
+
+        assert_eq!(CategoryName::new(&name), Err(CategoryError::NameTooLong));
+    }
+
+    #[test]
+    fn new_accepts_name_at_max_length() {
+        let name = "é".repeat(MAX_CATEGORY_NAME_LENGTH);
+
+        assert!(CategoryName::new(&name).is_ok());
+    }
+
+    #[test]
+    fn new_collapses_whitespace() {
+        let category_name = CategoryName::new("  Eating   Out ").unwrap();
+
+        assert_eq!(category_name.as_ref(), "Eating Out");
+    }
+
     #[test]
     fn new_succeeds_on_non_empty_string() {
         let category_name = CategoryName::new("🔥");
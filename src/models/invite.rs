@@ -0,0 +1,57 @@
+//! This file defines an invite code that allows someone to register while registration is invite-only.
+
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+/// The number of characters in a generated invite code.
+const INVITE_CODE_LENGTH: usize = 16;
+
+/// A single-use code that allows someone to create an account while registration is invite-only.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Invite {
+    code: String,
+    created_at: OffsetDateTime,
+}
+
+impl Invite {
+    /// Create an invite from its parts.
+    ///
+    /// The caller should ensure that `code` is unique.
+    pub fn new(code: String, created_at: OffsetDateTime) -> Self {
+        Self { code, created_at }
+    }
+
+    /// Generate a new random invite code.
+    pub fn generate_code() -> String {
+        rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(INVITE_CODE_LENGTH)
+            .map(char::from)
+            .collect()
+    }
+
+    /// The code to enter on the registration page.
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    /// When the invite was created.
+    pub fn created_at(&self) -> OffsetDateTime {
+        self.created_at
+    }
+}
+
+#[cfg(test)]
+mod invite_tests {
+    use super::{Invite, INVITE_CODE_LENGTH};
+
+    #[test]
+    fn generate_code_creates_distinct_alphanumeric_codes() {
+        let code = Invite::generate_code();
+
+        assert_eq!(code.len(), INVITE_CODE_LENGTH);
+        assert!(code.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(code, Invite::generate_code());
+    }
+}
@@ -1,14 +1,27 @@
 //! This module defines the domain data types.
 
-pub use category::{Category, CategoryError, CategoryName};
+pub use api_token::ApiToken;
+pub use audit::{AuditAction, AuditEntityType, AuditLogEntry};
+pub use category::{Category, CategoryError, CategoryName, MAX_CATEGORY_NAME_LENGTH};
+pub use invite::Invite;
 pub use password::{PasswordError, PasswordHash, ValidatedPassword};
-pub use transaction::{Transaction, TransactionBuilder, TransactionError};
+pub use preferences::{
+    clamp_month_start_day, LandingPage, Theme, UserPreferences, WeekStart, DEFAULT_TIMEZONE,
+    MAX_MONTH_START_DAY,
+};
+pub use transaction::{normalize_description, Transaction, TransactionBuilder, TransactionError};
 pub use user::{User, UserID};
+pub use webhook::{Webhook, WebhookEvent};
 
+mod api_token;
+mod audit;
 mod category;
+mod invite;
 mod password;
+mod preferences;
 mod transaction;
 mod user;
+mod webhook;
 
 /// Alias for the integer type used for mapping to database IDs.
 pub type DatabaseID = i64;
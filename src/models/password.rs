@@ -2,9 +2,9 @@
 //! `ValidatedPassword` wraps a string and ensure it is a strong password.
 //! `PasswordHash` converts a `ValidatedPassword` into a salted and hashed password.
 
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
 
-use bcrypt::{hash, verify, BcryptError};
+use bcrypt::{hash, verify, BcryptError, HashParts};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use zxcvbn::{feedback::Feedback, zxcvbn, Score};
@@ -114,6 +114,18 @@ impl PasswordHash {
     pub fn verify(&self, raw_password: &str) -> Result<bool, BcryptError> {
         verify(raw_password, &self.0)
     }
+
+    /// The cost that the password was hashed with, or `None` if the hash is malformed.
+    pub fn cost(&self) -> Option<u32> {
+        HashParts::from_str(&self.0)
+            .ok()
+            .map(|hash_parts| hash_parts.get_cost())
+    }
+
+    /// Whether the password was hashed with a lower cost than `cost` and should be hashed again.
+    pub fn needs_rehash(&self, cost: u32) -> bool {
+        self.cost().is_some_and(|hash_cost| hash_cost < cost)
+    }
 }
 
 impl Display for PasswordHash {
@@ -204,4 +216,26 @@ mod password_hash_tests {
 
         assert!(hash.is_ok());
     }
+
+    #[test]
+    fn cost_matches_hashing_cost() {
+        let hash = PasswordHash::from_raw_password("thisisaverysecurepassword!!!!", 5).unwrap();
+
+        assert_eq!(hash.cost(), Some(5));
+    }
+
+    #[test]
+    fn cost_is_none_for_malformed_hash() {
+        assert_eq!(PasswordHash::new_unchecked("hunter2").cost(), None);
+    }
+
+    #[test]
+    fn needs_rehash_only_for_lower_cost() {
+        let hash = PasswordHash::from_raw_password("thisisaverysecurepassword!!!!", 5).unwrap();
+
+        assert!(hash.needs_rehash(6));
+        assert!(!hash.needs_rehash(5));
+        assert!(!hash.needs_rehash(4));
+        assert!(!PasswordHash::new_unchecked("hunter2").needs_rehash(12));
+    }
 }
@@ -0,0 +1,242 @@
+//! The settings that each user can change for themselves.
+
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// The timezone used for users who have not chosen one.
+pub const DEFAULT_TIMEZONE: &str = "Etc/UTC";
+
+/// The latest day of the month that a user's months can start on.
+///
+/// Every month has a 28th, so later days are clamped to this.
+pub const MAX_MONTH_START_DAY: u8 = 28;
+
+/// Whether pages use light or dark colours.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    /// Always use light colours.
+    Light,
+    /// Always use dark colours.
+    Dark,
+    /// Follow the colour scheme of the user's device.
+    #[default]
+    System,
+}
+
+impl Theme {
+    /// Every theme, in the order they are offered to the user.
+    pub const ALL: [Theme; 3] = [Theme::Light, Theme::Dark, Theme::System];
+
+    /// The name of the theme, as it appears in forms and the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+            Theme::System => "system",
+        }
+    }
+
+    /// The name of the theme to show the user.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Theme::Light => "Light",
+            Theme::Dark => "Dark",
+            Theme::System => "System",
+        }
+    }
+}
+
+impl Display for Theme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "light" => Ok(Theme::Light),
+            "dark" => Ok(Theme::Dark),
+            "system" => Ok(Theme::System),
+            other => Err(format!("unknown theme \"{other}\"")),
+        }
+    }
+}
+
+/// The page that a user is sent to after logging in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LandingPage {
+    /// The dashboard, which summarises recent spending.
+    #[default]
+    Dashboard,
+    /// The list of transactions.
+    Transactions,
+}
+
+impl LandingPage {
+    /// Every landing page, in the order they are offered to the user.
+    pub const ALL: [LandingPage; 2] = [LandingPage::Dashboard, LandingPage::Transactions];
+
+    /// The name of the landing page, as it appears in forms and the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LandingPage::Dashboard => "dashboard",
+            LandingPage::Transactions => "transactions",
+        }
+    }
+
+    /// The name of the landing page to show the user.
+    pub fn label(&self) -> &'static str {
+        match self {
+            LandingPage::Dashboard => "Dashboard",
+            LandingPage::Transactions => "Transactions",
+        }
+    }
+}
+
+impl Display for LandingPage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for LandingPage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dashboard" => Ok(LandingPage::Dashboard),
+            "transactions" => Ok(LandingPage::Transactions),
+            other => Err(format!("unknown landing page \"{other}\"")),
+        }
+    }
+}
+
+/// The day that weeks start on in weekly summaries.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekStart {
+    /// Weeks run from Monday to Sunday.
+    #[default]
+    Monday,
+    /// Weeks run from Sunday to Saturday.
+    Sunday,
+}
+
+impl WeekStart {
+    /// Every week start, in the order they are offered to the user.
+    pub const ALL: [WeekStart; 2] = [WeekStart::Monday, WeekStart::Sunday];
+
+    /// The name of the week start, as it appears in forms and the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WeekStart::Monday => "monday",
+            WeekStart::Sunday => "sunday",
+        }
+    }
+
+    /// The name of the week start to show the user.
+    pub fn label(&self) -> &'static str {
+        match self {
+            WeekStart::Monday => "Monday",
+            WeekStart::Sunday => "Sunday",
+        }
+    }
+}
+
+impl Display for WeekStart {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for WeekStart {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "monday" => Ok(WeekStart::Monday),
+            "sunday" => Ok(WeekStart::Sunday),
+            other => Err(format!("unknown week start \"{other}\"")),
+        }
+    }
+}
+
+/// Clamp `day` to a day that every month has, from 1 to [MAX_MONTH_START_DAY].
+pub fn clamp_month_start_day(day: u8) -> u8 {
+    day.clamp(1, MAX_MONTH_START_DAY)
+}
+
+/// A user's preferences.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UserPreferences {
+    /// The IANA name of the user's timezone, e.g. "Pacific/Auckland", which decides what today's
+    /// date is for the user.
+    pub timezone: String,
+    /// Whether pages use light or dark colours.
+    pub theme: Theme,
+    /// The page the user is sent to after logging in.
+    pub landing_page: LandingPage,
+    /// The day that weeks start on.
+    pub week_start: WeekStart,
+    /// The day of the month that months start on, e.g. 15 for a pay cycle starting on the 15th.
+    ///
+    /// A month is named after the calendar month that it starts in.
+    pub month_start_day: u8,
+}
+
+impl Default for UserPreferences {
+    fn default() -> Self {
+        Self {
+            timezone: DEFAULT_TIMEZONE.to_owned(),
+            theme: Theme::default(),
+            landing_page: LandingPage::default(),
+            week_start: WeekStart::default(),
+            month_start_day: 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod theme_tests {
+    use super::{clamp_month_start_day, LandingPage, Theme, WeekStart};
+
+    #[test]
+    fn theme_round_trips_through_string() {
+        for theme in Theme::ALL {
+            assert_eq!(theme.as_str().parse(), Ok(theme));
+        }
+
+        assert!("purple".parse::<Theme>().is_err());
+    }
+
+    #[test]
+    fn landing_page_round_trips_through_string() {
+        for landing_page in LandingPage::ALL {
+            assert_eq!(landing_page.as_str().parse(), Ok(landing_page));
+        }
+
+        assert!("rules".parse::<LandingPage>().is_err());
+    }
+
+    #[test]
+    fn week_start_round_trips_through_string() {
+        for week_start in WeekStart::ALL {
+            assert_eq!(week_start.as_str().parse(), Ok(week_start));
+        }
+
+        assert!("wednesday".parse::<WeekStart>().is_err());
+    }
+
+    #[test]
+    fn month_start_day_is_clamped_to_days_in_every_month() {
+        assert_eq!(clamp_month_start_day(0), 1);
+        assert_eq!(clamp_month_start_day(15), 15);
+        assert_eq!(clamp_month_start_day(31), 28);
+    }
+}
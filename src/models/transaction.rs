@@ -32,6 +32,10 @@ pub enum TransactionError {
     #[error("an unexpected error occurred: {0}")]
     SqlError(rusqlite::Error),
 
+    /// The database lock was poisoned by a thread that panicked while holding it.
+    #[error("the database lock is poisoned")]
+    DatabaseLockError,
+
     /// There was an unexpected and unhandled error.
     #[error("an unexpected error occurred: {0}")]
     Unspecified(String),
@@ -49,6 +53,19 @@ impl From<rusqlite::Error> for TransactionError {
     }
 }
 
+/// Clean up a transaction description so that the same payee is always written the same way.
+///
+/// Leading and trailing whitespace is removed, runs of whitespace are collapsed into a single
+/// space, and trailing semicolons, e.g. the " ;" that some banks append, are removed.
+pub fn normalize_description(description: &str) -> String {
+    description
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_end_matches(|c: char| c == ';' || c.is_whitespace())
+        .to_owned()
+}
+
 /// An expense or income, i.e. an event where money was either spent or earned.
 ///
 /// To create a new `Transaction`, use [Transaction::build].
@@ -60,6 +77,8 @@ pub struct Transaction {
     description: String,
     category_id: Option<DatabaseID>,
     user_id: UserID,
+    ignored: bool,
+    reconciled: bool,
 }
 
 impl Transaction {
@@ -71,6 +90,8 @@ impl Transaction {
     ///
     /// This function has `_unchecked` in the name but is not `unsafe`, because if an invalid date
     /// is provided it may cause incorrect behaviour but will not affect memory safety.
+    // The arguments map one-to-one to the table columns, so grouping them would not help.
+    #[allow(clippy::too_many_arguments)]
     pub fn new_unchecked(
         id: DatabaseID,
         amount: f64,
@@ -78,6 +99,8 @@ impl Transaction {
         description: String,
         category_id: Option<DatabaseID>,
         user_id: UserID,
+        ignored: bool,
+        reconciled: bool,
     ) -> Self {
         Self {
             id,
@@ -86,6 +109,8 @@ impl Transaction {
             description,
             category_id,
             user_id,
+            ignored,
+            reconciled,
         }
     }
 
@@ -125,6 +150,18 @@ impl Transaction {
     pub fn user_id(&self) -> UserID {
         self.user_id
     }
+
+    /// Whether the transaction should be left out of totals, e.g., a reversed card authorisation.
+    ///
+    /// Unlike deleting a transaction, an ignored transaction is kept for completeness.
+    pub fn is_ignored(&self) -> bool {
+        self.ignored
+    }
+
+    /// Whether the transaction has been checked off against a bank statement.
+    pub fn is_reconciled(&self) -> bool {
+        self.reconciled
+    }
 }
 
 /// Builder for creating a new [Transaction].
@@ -137,6 +174,10 @@ pub struct TransactionBuilder {
     description: String,
     category_id: Option<DatabaseID>,
     user_id: UserID,
+    ignored: bool,
+    reconciled: bool,
+    /// The latest date that the transaction can have.
+    today: Date,
 }
 
 impl TransactionBuilder {
@@ -144,33 +185,53 @@ impl TransactionBuilder {
     ///
     /// Finalize the builder with [TransactionBuilder::finalise].
     pub fn new(amount: f64, user_id: UserID) -> Self {
+        let today = OffsetDateTime::now_utc().date();
+
         Self {
             amount,
-            date: OffsetDateTime::now_utc().date(),
+            date: today,
             description: String::new(),
             category_id: None,
             user_id,
+            ignored: false,
+            reconciled: false,
+            today,
         }
     }
 
+    /// Set today's date in the user's timezone, which defaults to the date in UTC.
+    ///
+    /// This is used as the transaction's date if none is set, and is the latest date accepted by
+    /// [TransactionBuilder::date], so call this before setting the date.
+    pub fn today(mut self, today: Date) -> Self {
+        self.today = today;
+        self.date = today;
+        self
+    }
+
     /// Build the final [Transaction] instance.
+    ///
+    /// The description is cleaned up with [normalize_description].
     pub fn finalise(self, id: DatabaseID) -> Transaction {
         Transaction {
             id,
             amount: self.amount,
             date: self.date,
-            description: self.description,
+            description: normalize_description(&self.description),
             category_id: self.category_id,
             user_id: self.user_id,
+            ignored: self.ignored,
+            reconciled: self.reconciled,
         }
     }
 
     /// Set the date for the transaction.
     ///
     /// # Errors
-    /// This function will return an error if `date` is a date in the future.
+    /// This function will return an error if `date` is after today, see
+    /// [TransactionBuilder::today].
     pub fn date(mut self, date: Date) -> Result<Self, TransactionError> {
-        if date > OffsetDateTime::now_utc().date() {
+        if date > self.today {
             return Err(TransactionError::FutureDate);
         }
 
@@ -184,11 +245,34 @@ impl TransactionBuilder {
         self
     }
 
+    /// The description exactly as it was given, before [TransactionBuilder::finalise] cleans it
+    /// up.
+    pub fn raw_description(&self) -> &str {
+        &self.description
+    }
+
+    /// The user that the transaction will belong to.
+    pub fn user_id(&self) -> UserID {
+        self.user_id
+    }
+
     /// Set the category for the transaction.
     pub fn category(mut self, category_id: Option<DatabaseID>) -> Self {
         self.category_id = category_id;
         self
     }
+
+    /// Set whether the transaction should be left out of totals.
+    pub fn ignored(mut self, ignored: bool) -> Self {
+        self.ignored = ignored;
+        self
+    }
+
+    /// Set whether the transaction has been checked off against a bank statement.
+    pub fn reconciled(mut self, reconciled: bool) -> Self {
+        self.reconciled = reconciled;
+        self
+    }
 }
 
 #[cfg(test)]
@@ -199,7 +283,7 @@ mod transaction_builder_tests {
 
     use crate::models::{TransactionBuilder, UserID};
 
-    use super::{Transaction, TransactionError};
+    use super::{normalize_description, Transaction, TransactionError};
 
     #[test]
     fn new_fails_on_future_date() {
@@ -214,6 +298,25 @@ mod transaction_builder_tests {
         assert_eq!(result, Err(TransactionError::FutureDate));
     }
 
+    #[test]
+    fn date_is_checked_against_local_today() {
+        let user_id = UserID::new(42);
+        let utc_today = OffsetDateTime::now_utc().date();
+        let local_today = utc_today.next_day().unwrap();
+
+        // Ahead of UTC, e.g. in New Zealand, today's local date is tomorrow in UTC.
+        let builder = TransactionBuilder::new(1.0, user_id)
+            .today(local_today)
+            .date(local_today);
+        assert_eq!(builder.unwrap().finalise(1).date(), &local_today);
+
+        // Behind UTC, today's date in UTC is still in the future.
+        let result = TransactionBuilder::new(1.0, user_id)
+            .today(utc_today.previous_day().unwrap())
+            .date(utc_today);
+        assert_eq!(result, Err(TransactionError::FutureDate));
+    }
+
     #[test]
     fn new_succeeds_on_today() {
         let user_id = UserID::new(42);
@@ -266,5 +369,32 @@ mod transaction_builder_tests {
         assert_eq!(transaction.description(), description);
         assert_eq!(transaction.category_id(), category_id);
         assert_eq!(transaction.user_id(), user_id);
+        assert!(!transaction.is_ignored());
+    }
+
+    #[test]
+    fn normalize_description_cleans_up_bank_descriptions() {
+        let cases = [
+            ("PAK N SAVE PETONE ;", "PAK N SAVE PETONE"),
+            ("  COUNTDOWN   WELLINGTON   ;  ", "COUNTDOWN WELLINGTON"),
+            ("Z ENERGY\tLOWER HUTT;;", "Z ENERGY LOWER HUTT"),
+            ("DEBIT INTEREST", "DEBIT INTEREST"),
+            ("Transfer; to savings", "Transfer; to savings"),
+            (" ; ", ""),
+            ("", ""),
+        ];
+
+        for (description, want) in cases {
+            assert_eq!(normalize_description(description), want, "{description:?}");
+        }
+    }
+
+    #[test]
+    fn finalise_normalizes_description() {
+        let builder =
+            Transaction::build(1.0, UserID::new(1)).description("  NEW  WORLD  ;".to_string());
+
+        assert_eq!(builder.raw_description(), "  NEW  WORLD  ;");
+        assert_eq!(builder.finalise(1).description(), "NEW WORLD");
     }
 }
@@ -38,10 +38,11 @@ pub struct User {
     id: UserID,
     email: EmailAddress,
     password_hash: PasswordHash,
+    session_generation: i64,
 }
 
 impl User {
-    /// Create a new user.
+    /// Create a new user with a session generation of zero.
     ///
     /// The caller should ensure that `id` is unique.
     pub fn new(id: UserID, email: EmailAddress, password_hash: PasswordHash) -> Self {
@@ -49,9 +50,16 @@ impl User {
             id,
             email,
             password_hash,
+            session_generation: 0,
         }
     }
 
+    /// Set the user's session generation.
+    pub fn with_session_generation(mut self, session_generation: i64) -> Self {
+        self.session_generation = session_generation;
+        self
+    }
+
     /// The user's ID in the database.
     pub fn id(&self) -> UserID {
         self.id
@@ -66,4 +74,12 @@ impl User {
     pub fn password_hash(&self) -> &PasswordHash {
         &self.password_hash
     }
+
+    /// The user's session generation.
+    ///
+    /// Auth cookies record the session generation at log-in, and are only valid while it matches
+    /// the user's current session generation. Incrementing it logs the user out everywhere.
+    pub fn session_generation(&self) -> i64 {
+        self.session_generation
+    }
 }
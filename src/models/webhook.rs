@@ -0,0 +1,186 @@
+//! This file defines a webhook that is notified when something happens to a user's data.
+
+use std::{fmt::Display, str::FromStr};
+
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+
+use super::{DatabaseID, UserID};
+
+/// The number of characters in a generated webhook secret.
+const SECRET_LENGTH: usize = 32;
+
+/// Something that happened which webhooks can be notified about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WebhookEvent {
+    /// A transaction was created.
+    #[serde(rename = "transaction.created")]
+    TransactionCreated,
+    /// A test event sent by the user to check that their webhook works.
+    #[serde(rename = "test")]
+    Test,
+}
+
+impl WebhookEvent {
+    /// The events that a user can choose to subscribe a webhook to.
+    pub const SUBSCRIBABLE: [WebhookEvent; 1] = [WebhookEvent::TransactionCreated];
+
+    /// The name of the event, as it appears in payloads and the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::TransactionCreated => "transaction.created",
+            WebhookEvent::Test => "test",
+        }
+    }
+}
+
+impl Display for WebhookEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for WebhookEvent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "transaction.created" => Ok(WebhookEvent::TransactionCreated),
+            "test" => Ok(WebhookEvent::Test),
+            other => Err(format!("unknown webhook event \"{other}\"")),
+        }
+    }
+}
+
+/// A URL that is sent a signed JSON payload when one of its events happens.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Webhook {
+    id: DatabaseID,
+    user_id: UserID,
+    url: String,
+    secret: String,
+    events: Vec<WebhookEvent>,
+    enabled: bool,
+}
+
+impl Webhook {
+    /// Create a webhook from its parts.
+    ///
+    /// The caller should ensure that `id` is unique and that `user_id` refers to a valid user.
+    pub fn new(
+        id: DatabaseID,
+        user_id: UserID,
+        url: String,
+        secret: String,
+        events: Vec<WebhookEvent>,
+        enabled: bool,
+    ) -> Self {
+        Self {
+            id,
+            user_id,
+            url,
+            secret,
+            events,
+            enabled,
+        }
+    }
+
+    /// Generate a new random secret for signing payloads.
+    pub fn generate_secret() -> String {
+        rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(SECRET_LENGTH)
+            .map(char::from)
+            .collect()
+    }
+
+    /// The webhook's ID in the database.
+    pub fn id(&self) -> DatabaseID {
+        self.id
+    }
+
+    /// The ID of the user that owns the webhook.
+    pub fn user_id(&self) -> UserID {
+        self.user_id
+    }
+
+    /// The URL that payloads are sent to.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The secret used to sign payloads so that the receiver can verify where they came from.
+    pub fn secret(&self) -> &str {
+        &self.secret
+    }
+
+    /// The events that the webhook is subscribed to.
+    pub fn events(&self) -> &[WebhookEvent] {
+        &self.events
+    }
+
+    /// Whether payloads are sent to the webhook.
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Whether the webhook should be sent `event`.
+    ///
+    /// Enabled webhooks are always sent test events.
+    pub fn wants(&self, event: WebhookEvent) -> bool {
+        self.enabled && (event == WebhookEvent::Test || self.events.contains(&event))
+    }
+}
+
+#[cfg(test)]
+mod webhook_tests {
+    use std::str::FromStr;
+
+    use crate::models::UserID;
+
+    use super::{Webhook, WebhookEvent, SECRET_LENGTH};
+
+    #[test]
+    fn event_round_trips_through_string() {
+        for event in [WebhookEvent::TransactionCreated, WebhookEvent::Test] {
+            assert_eq!(WebhookEvent::from_str(event.as_str()), Ok(event));
+        }
+
+        assert!(WebhookEvent::from_str("foo").is_err());
+    }
+
+    #[test]
+    fn generate_secret_creates_distinct_alphanumeric_secrets() {
+        let secret = Webhook::generate_secret();
+
+        assert_eq!(secret.len(), SECRET_LENGTH);
+        assert!(secret.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(secret, Webhook::generate_secret());
+    }
+
+    #[test]
+    fn wants_subscribed_events_when_enabled() {
+        let webhook = |events, enabled| {
+            Webhook::new(
+                1,
+                UserID::new(1),
+                "http://localhost".to_string(),
+                "secret".to_string(),
+                events,
+                enabled,
+            )
+        };
+
+        let subscribed = webhook(vec![WebhookEvent::TransactionCreated], true);
+        assert!(subscribed.wants(WebhookEvent::TransactionCreated));
+        assert!(subscribed.wants(WebhookEvent::Test));
+
+        let unsubscribed = webhook(vec![], true);
+        assert!(!unsubscribed.wants(WebhookEvent::TransactionCreated));
+        assert!(unsubscribed.wants(WebhookEvent::Test));
+
+        let disabled = webhook(vec![WebhookEvent::TransactionCreated], false);
+        assert!(!disabled.wants(WebhookEvent::TransactionCreated));
+        assert!(!disabled.wants(WebhookEvent::Test));
+    }
+}
@@ -0,0 +1,151 @@
+//! Tracks long-running operations, such as VACUUM, so that shutdown can wait for them to finish.
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use tokio::sync::Notify;
+
+/// Keeps track of the long-running operations that are in progress.
+///
+/// Clones share the same set of operations.
+#[derive(Debug, Clone, Default)]
+pub struct OperationTracker {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    next_id: AtomicU64,
+    /// The names of the running operations by ID, in the order they were started.
+    running: Mutex<BTreeMap<u64, String>>,
+    /// Notified whenever an operation finishes.
+    finished: Notify,
+}
+
+/// Marks an operation as running until it is dropped.
+///
+/// Move the guard into the task doing the work, e.g. the closure given to
+/// [spawn_blocking](tokio::task::spawn_blocking), so that the operation is only marked as finished
+/// when the work is done and not when the request that started it is cancelled.
+#[derive(Debug)]
+#[must_use = "the operation is marked as finished as soon as the guard is dropped"]
+pub struct OperationGuard {
+    id: u64,
+    inner: Arc<Inner>,
+}
+
+impl Drop for OperationGuard {
+    fn drop(&mut self) {
+        self.inner.running.lock().unwrap().remove(&self.id);
+        self.inner.finished.notify_waiters();
+    }
+}
+
+impl OperationTracker {
+    /// Mark the operation `name` as running until the returned guard is dropped.
+    pub fn start(&self, name: impl Into<String>) -> OperationGuard {
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        self.inner.running.lock().unwrap().insert(id, name.into());
+
+        OperationGuard {
+            id,
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// The names of the operations that are running, oldest first.
+    pub fn running(&self) -> Vec<String> {
+        self.inner
+            .running
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Wait up to `timeout` for every running operation to finish.
+    ///
+    /// Returns `true` if there are no running operations, or `false` if some were still running
+    /// when the timeout expired.
+    pub async fn wait(&self, timeout: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        loop {
+            // Create the future before checking so that an operation finishing in between is not
+            // missed.
+            let finished = self.inner.finished.notified();
+
+            if self.inner.running.lock().unwrap().is_empty() {
+                return true;
+            }
+
+            if tokio::time::timeout_at(deadline, finished).await.is_err() {
+                return false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod operation_tracker_tests {
+    use std::time::{Duration, Instant};
+
+    use super::OperationTracker;
+
+    #[tokio::test]
+    async fn wait_returns_immediately_without_operations() {
+        let tracker = OperationTracker::default();
+
+        assert!(tracker.wait(Duration::ZERO).await);
+    }
+
+    #[tokio::test]
+    async fn wait_waits_for_operations_to_finish() {
+        let tracker = OperationTracker::default();
+        let first = tracker.start("VACUUM");
+        let second = tracker.start("backup");
+        assert_eq!(tracker.running(), vec!["VACUUM", "backup"]);
+
+        let start = Instant::now();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(first);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(second);
+        });
+
+        assert!(tracker.wait(Duration::from_secs(5)).await);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert!(tracker.running().is_empty());
+    }
+
+    #[tokio::test]
+    async fn wait_gives_up_after_timeout() {
+        let tracker = OperationTracker::default();
+        let _operation = tracker.start("VACUUM");
+
+        assert!(!tracker.wait(Duration::from_millis(50)).await);
+        assert_eq!(tracker.running(), vec!["VACUUM"]);
+    }
+
+    #[tokio::test]
+    async fn guard_in_blocking_task_keeps_operation_running() {
+        let tracker = OperationTracker::default();
+        let operation = tracker.start("VACUUM");
+
+        let task = tokio::task::spawn_blocking(move || {
+            let _operation = operation;
+            std::thread::sleep(Duration::from_millis(50));
+        });
+
+        assert!(tracker.wait(Duration::from_secs(5)).await);
+        task.await.unwrap();
+    }
+}
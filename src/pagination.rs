@@ -0,0 +1,90 @@
+//! Splitting long lists, e.g. of transactions, into pages.
+
+/// The page sizes that users can choose from.
+pub const PAGE_SIZE_OPTIONS: [u64; 4] = [10, 25, 50, 100];
+
+/// The limits on how many items a page can show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaginationConfig {
+    /// The page size used when none, or zero, is requested.
+    pub default_per_page: u64,
+    /// The smallest page size that can be requested.
+    pub min_per_page: u64,
+    /// The largest page size that can be requested.
+    pub max_per_page: u64,
+}
+
+impl Default for PaginationConfig {
+    fn default() -> Self {
+        Self {
+            default_per_page: 25,
+            min_per_page: 10,
+            max_per_page: 100,
+        }
+    }
+}
+
+impl PaginationConfig {
+    /// The page size to use for the requested page size `per_page`.
+    ///
+    /// No page size, or zero, gives the default page size. Other page sizes are clamped to
+    /// the minimum and maximum page size so that a request cannot build a huge page.
+    pub fn per_page(&self, per_page: Option<u64>) -> u64 {
+        match per_page {
+            None | Some(0) => self.default_per_page,
+            Some(per_page) => per_page.clamp(self.min_per_page, self.max_per_page),
+        }
+    }
+}
+
+/// The number of pages needed to show `count` items, `per_page` at a time.
+///
+/// There is always at least one page, even if it is empty.
+pub fn get_page_count(count: u64, per_page: u64) -> u64 {
+    count.div_ceil(per_page.max(1)).max(1)
+}
+
+/// The page to show for the requested `page`, starting from one.
+///
+/// No page gives the first page, and pages past the end give the last page.
+pub fn get_page(page: Option<u64>, page_count: u64) -> u64 {
+    page.unwrap_or(1).clamp(1, page_count.max(1))
+}
+
+#[cfg(test)]
+mod pagination_tests {
+    use super::{get_page, get_page_count, PaginationConfig};
+
+    #[test]
+    fn per_page_uses_default_for_missing_or_zero() {
+        let config = PaginationConfig::default();
+
+        assert_eq!(config.per_page(None), config.default_per_page);
+        assert_eq!(config.per_page(Some(0)), config.default_per_page);
+    }
+
+    #[test]
+    fn per_page_is_clamped() {
+        let config = PaginationConfig::default();
+
+        assert_eq!(config.per_page(Some(1)), config.min_per_page);
+        assert_eq!(config.per_page(Some(50)), 50);
+        assert_eq!(config.per_page(Some(1_000_000)), config.max_per_page);
+    }
+
+    #[test]
+    fn page_count_is_at_least_one() {
+        assert_eq!(get_page_count(0, 25), 1);
+        assert_eq!(get_page_count(25, 25), 1);
+        assert_eq!(get_page_count(26, 25), 2);
+        assert_eq!(get_page_count(10, 0), 10);
+    }
+
+    #[test]
+    fn page_is_clamped_to_existing_pages() {
+        assert_eq!(get_page(None, 3), 1);
+        assert_eq!(get_page(Some(0), 3), 1);
+        assert_eq!(get_page(Some(2), 3), 2);
+        assert_eq!(get_page(Some(99999), 3), 3);
+    }
+}
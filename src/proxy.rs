@@ -0,0 +1,249 @@
+//! Working out who a request came from and how, for when the server runs behind a reverse proxy.
+//!
+//! A reverse proxy that terminates TLS, such as Caddy, forwards requests over plain HTTP and
+//! passes the client's address and scheme in the `X-Forwarded-For` and `X-Forwarded-Proto`
+//! headers. Anyone can set these headers, so they are only used when the server is told to trust
+//! them.
+
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header::SET_COOKIE, HeaderMap, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use axum_extra::extract::cookie::Cookie;
+
+/// The header a reverse proxy uses to pass on the addresses a request was forwarded for.
+pub const X_FORWARDED_FOR: &str = "x-forwarded-for";
+/// The header a reverse proxy uses to pass on the scheme the client used, e.g. "https".
+pub const X_FORWARDED_PROTO: &str = "x-forwarded-proto";
+
+/// How clients reach the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProxySettings {
+    /// Whether the server terminates TLS itself, in which case every request uses HTTPS.
+    pub tls: bool,
+    /// Whether to trust the `X-Forwarded-For` and `X-Forwarded-Proto` headers, i.e. the server
+    /// can only be reached through a reverse proxy that sets them.
+    pub trust_proxy_headers: bool,
+}
+
+impl Default for ProxySettings {
+    fn default() -> Self {
+        Self {
+            tls: true,
+            trust_proxy_headers: false,
+        }
+    }
+}
+
+impl ProxySettings {
+    /// The IP address of the client that sent a request, given the address of the `peer` that
+    /// connected to the server.
+    ///
+    /// If proxy headers are trusted, this is the last address in `X-Forwarded-For`, which is the
+    /// one added by the proxy in front of the server. Earlier addresses are set by the client and
+    /// cannot be trusted.
+    pub fn get_client_ip(&self, headers: &HeaderMap, peer: Option<IpAddr>) -> Option<IpAddr> {
+        if !self.trust_proxy_headers {
+            return peer;
+        }
+
+        headers
+            .get_all(X_FORWARDED_FOR)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .last()
+            .and_then(|address| address.trim().parse().ok())
+            .or(peer)
+    }
+
+    /// Whether the client sent a request over HTTPS.
+    pub fn is_https(&self, headers: &HeaderMap) -> bool {
+        if self.tls {
+            return true;
+        }
+
+        self.trust_proxy_headers
+            && headers
+                .get(X_FORWARDED_PROTO)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|proto| proto.trim().eq_ignore_ascii_case("https"))
+    }
+}
+
+/// Get the address of the peer that connected to the server, if the server was started with
+/// connect info.
+pub fn get_peer_ip(request: &Request) -> Option<IpAddr> {
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(address)| address.ip())
+}
+
+/// Remove the `Secure` attribute from cookies set in responses to plain HTTP requests.
+///
+/// Browsers drop secure cookies sent over plain HTTP, which would make it impossible to log in
+/// when running without TLS, e.g. on a private network. Cookies keep the attribute when the
+/// request used HTTPS, including when a trusted proxy says so with `X-Forwarded-Proto`.
+pub async fn secure_cookies_middleware(
+    State(settings): State<ProxySettings>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let is_https = settings.is_https(request.headers());
+    let mut response = next.run(request).await;
+
+    if is_https {
+        return response;
+    }
+
+    let cookies: Vec<HeaderValue> = response
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .cloned()
+        .map(|value| {
+            let Some(mut cookie) = value
+                .to_str()
+                .ok()
+                .and_then(|value| Cookie::parse(value.to_owned()).ok())
+            else {
+                return value;
+            };
+
+            cookie.set_secure(false);
+            HeaderValue::from_str(&cookie.to_string()).unwrap_or(value)
+        })
+        .collect();
+
+    response.headers_mut().remove(SET_COOKIE);
+    for cookie in cookies {
+        response.headers_mut().append(SET_COOKIE, cookie);
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod proxy_tests {
+    use std::net::IpAddr;
+
+    use axum::{
+        http::{header::SET_COOKIE, HeaderMap, HeaderName, HeaderValue},
+        middleware,
+        routing::get,
+        Router,
+    };
+    use axum_test::TestServer;
+
+    use super::{secure_cookies_middleware, ProxySettings, X_FORWARDED_FOR, X_FORWARDED_PROTO};
+
+    const PROXY: ProxySettings = ProxySettings {
+        tls: false,
+        trust_proxy_headers: true,
+    };
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| {
+                (
+                    HeaderName::from_static(name),
+                    HeaderValue::from_static(value),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn client_ip_uses_last_forwarded_address_when_trusted() {
+        let peer: IpAddr = "10.0.0.1".parse().unwrap();
+        let headers = headers(&[(X_FORWARDED_FOR, "1.2.3.4, 203.0.113.7")]);
+
+        assert_eq!(
+            PROXY.get_client_ip(&headers, Some(peer)),
+            Some("203.0.113.7".parse().unwrap())
+        );
+        assert_eq!(
+            ProxySettings::default().get_client_ip(&headers, Some(peer)),
+            Some(peer)
+        );
+    }
+
+    #[test]
+    fn client_ip_falls_back_to_peer() {
+        let peer: IpAddr = "10.0.0.1".parse().unwrap();
+
+        assert_eq!(
+            PROXY.get_client_ip(&HeaderMap::new(), Some(peer)),
+            Some(peer)
+        );
+        assert_eq!(
+            PROXY.get_client_ip(&headers(&[(X_FORWARDED_FOR, "garbage")]), Some(peer)),
+            Some(peer)
+        );
+    }
+
+    #[test]
+    fn https_is_detected_from_tls_or_trusted_header() {
+        let forwarded_https = headers(&[(X_FORWARDED_PROTO, "https")]);
+
+        assert!(ProxySettings::default().is_https(&HeaderMap::new()));
+        assert!(PROXY.is_https(&forwarded_https));
+        assert!(!PROXY.is_https(&headers(&[(X_FORWARDED_PROTO, "http")])));
+        assert!(!ProxySettings {
+            trust_proxy_headers: false,
+            ..PROXY
+        }
+        .is_https(&forwarded_https));
+    }
+
+    async fn get_cookie(settings: ProxySettings, proto: Option<&'static str>) -> String {
+        let app = Router::new()
+            .route(
+                "/",
+                get(|| async { [(SET_COOKIE, "foo=bar; HttpOnly; Secure")] }),
+            )
+            .layer(middleware::from_fn_with_state(
+                settings,
+                secure_cookies_middleware,
+            ));
+        let server = TestServer::new(app).unwrap();
+
+        let mut request = server.get("/");
+        if let Some(proto) = proto {
+            request = request.add_header(
+                HeaderName::from_static(X_FORWARDED_PROTO),
+                HeaderValue::from_static(proto),
+            );
+        }
+
+        request
+            .await
+            .header(SET_COOKIE)
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn cookies_stay_secure_over_https() {
+        assert!(get_cookie(ProxySettings::default(), None)
+            .await
+            .contains("Secure"));
+        assert!(get_cookie(PROXY, Some("https")).await.contains("Secure"));
+    }
+
+    #[tokio::test]
+    async fn cookies_are_not_secure_over_plain_http() {
+        let cookie = get_cookie(PROXY, Some("http")).await;
+
+        assert!(!cookie.contains("Secure"));
+        assert!(cookie.contains("foo=bar"));
+        assert!(cookie.contains("HttpOnly"));
+    }
+}
@@ -0,0 +1,939 @@
+//! A versioned JSON API for scripting against the logged in user's transactions.
+//!
+//! Scripts should authenticate with an API token, created on the settings page, in the header
+//! `Authorization: Bearer <token>`. The API also accepts the same auth cookie as the HTML pages.
+//! Clients using the cookie should send the header `Accept: application/json` so that missing or
+//! expired sessions get a 401 response with a JSON error instead of a redirect to the log-in page.
+//! Errors have the shape `{ "error": "<message>", "code": "<code>" }`.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use axum_extra::extract::Query;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::Date;
+
+use crate::{
+    models::{DatabaseID, Transaction, TransactionBuilder, TransactionError, UserID, WebhookEvent},
+    pagination::{get_page, get_page_count},
+    stores::{
+        transaction::{BatchOutcome, NewTransaction, TransactionQuery},
+        CategoryStore, TransactionStore, UserStore,
+    },
+    timezone::get_user_today,
+    AppError, AppState,
+};
+
+use super::{errors::get_error_response, transactions::TransactionsPageQuery};
+
+/// The most transactions that can be created with one request to
+/// [create_api_transactions_batch].
+pub const MAX_BATCH_SIZE: usize = 500;
+
+/// The header with the number of transactions that match the filters on every page.
+pub const TOTAL_COUNT_HEADER: &str = "X-Total-Count";
+
+/// The header with the number of pages of transactions that match the filters.
+pub const PAGE_COUNT_HEADER: &str = "X-Page-Count";
+
+/// The request body for creating or updating a transaction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionData {
+    /// The value of the transaction in dollars.
+    pub amount: f64,
+    /// The date when the transaction ocurred, e.g. "2024-10-31".
+    pub date: Date,
+    /// Text detailing the transaction.
+    #[serde(default)]
+    pub description: String,
+    /// The ID of the category to assign the transaction to, if any.
+    #[serde(default)]
+    pub category_id: Option<DatabaseID>,
+}
+
+impl TransactionData {
+    fn into_builder(
+        self,
+        user_id: UserID,
+        today: Date,
+    ) -> Result<TransactionBuilder, TransactionError> {
+        Ok(TransactionBuilder::new(self.amount, user_id)
+            .today(today)
+            .date(self.date)?
+            .description(self.description)
+            .category(self.category_id))
+    }
+}
+
+/// A transaction in the request body for [create_api_transactions_batch].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchTransactionData {
+    /// The details of the transaction.
+    #[serde(flatten)]
+    pub transaction: TransactionData,
+    /// The ID of the transaction in the system it came from, e.g. a bank's API. A transaction
+    /// is only created once for each external ID, so a batch can safely be sent again.
+    #[serde(default)]
+    pub external_id: Option<String>,
+}
+
+/// The result of creating one transaction in [create_api_transactions_batch].
+///
+/// Serialized with a `status` field, e.g. `{ "status": "duplicate", "transaction_id": 1 }`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum BatchItemResult {
+    /// The transaction was created.
+    Created { transaction: Transaction },
+    /// The user already has the transaction `transaction_id` with the same external ID.
+    Duplicate { transaction_id: DatabaseID },
+    /// The transaction was not created, with the same message and code as the single
+    /// transaction routes would give.
+    Error { error: String, code: String },
+}
+
+impl BatchItemResult {
+    fn invalid_transaction(error: TransactionError) -> Self {
+        Self::Error {
+            error: format!("Invalid transaction: {error}"),
+            code: "invalid_transaction".to_owned(),
+        }
+    }
+}
+
+/// List a page of the logged in user's transactions, newest first.
+///
+/// Takes the same filters, `page` and `per_page` as the transactions page, with the same default
+/// page size and limits in [AppState::pagination]. Pages past the last page give the last page.
+/// The headers [TOTAL_COUNT_HEADER] and [PAGE_COUNT_HEADER] tell clients when to stop paging.
+pub async fn get_api_transactions<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Query(query): Query<TransactionsPageQuery>,
+) -> impl IntoResponse
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let count = state
+        .transaction_store()
+        .count_query(query.transaction_query(user_id))
+        .map_err(AppError::TransactionError)?;
+    let per_page = state.pagination.per_page(query.per_page);
+    let page_count = get_page_count(count, per_page);
+    let page = get_page(query.page, page_count);
+
+    let transactions = state
+        .transaction_store()
+        .get_query(TransactionQuery {
+            limit: Some(per_page),
+            offset: Some((page - 1) * per_page),
+            ..query.transaction_query(user_id)
+        })
+        .map_err(AppError::TransactionError)?;
+
+    Ok::<_, AppError>((
+        [
+            (TOTAL_COUNT_HEADER, count.to_string()),
+            (PAGE_COUNT_HEADER, page_count.to_string()),
+        ],
+        Json(transactions),
+    ))
+}
+
+/// Create a transaction for the logged in user and respond with 201 Created and the transaction.
+pub async fn create_api_transaction<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Json(data): Json<TransactionData>,
+) -> impl IntoResponse
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let today = get_user_today(state.user_store(), user_id);
+    let builder = data.into_builder(user_id, today)?;
+    let transaction = state
+        .transaction_store()
+        .create_from_builder(builder)
+        .map_err(AppError::TransactionError)?;
+
+    state.dispatch_webhooks(user_id, WebhookEvent::TransactionCreated, &transaction);
+
+    Ok::<_, AppError>((StatusCode::CREATED, Json(transaction)))
+}
+
+/// Create many transactions for the logged in user at once, e.g. from a nightly sync script.
+///
+/// The body is a JSON array of up to [MAX_BATCH_SIZE] transactions, each like the body of
+/// [create_api_transaction] with an optional `external_id`. Larger batches get a 422 error,
+/// since a 413 error would be replaced by the body size limit's error page. The transactions are created in a
+/// single database transaction, and the response is an array with a [BatchItemResult] for each
+/// transaction in the same order as the request. A transaction that is invalid, e.g. dated in
+/// the future, is reported in its result and does not stop the others from being created.
+pub async fn create_api_transactions_batch<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Json(items): Json<Vec<Value>>,
+) -> Result<Json<Vec<BatchItemResult>>, Response>
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    if items.len() > MAX_BATCH_SIZE {
+        return Err(get_error_response(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "batch_too_large",
+            &format!(
+                "A batch can have at most {MAX_BATCH_SIZE} transactions, but got {}.",
+                items.len()
+            ),
+        ));
+    }
+
+    let today = get_user_today(state.user_store(), user_id);
+    let mut results = Vec::with_capacity(items.len());
+    let mut new_transactions = Vec::new();
+    let mut new_transaction_indices = Vec::new();
+
+    for (index, item) in items.into_iter().enumerate() {
+        let data = match serde_json::from_value::<BatchTransactionData>(item) {
+            Ok(data) => data,
+            Err(error) => {
+                results.push(Some(BatchItemResult::Error {
+                    error: format!("Invalid transaction: {error}"),
+                    code: "invalid_json".to_owned(),
+                }));
+                continue;
+            }
+        };
+        let external_id = data.external_id.filter(|id| !id.trim().is_empty());
+
+        match data.transaction.into_builder(user_id, today) {
+            Ok(builder) => {
+                new_transactions.push(NewTransaction {
+                    builder,
+                    external_id,
+                });
+                new_transaction_indices.push(index);
+                results.push(None);
+            }
+            Err(error) => results.push(Some(BatchItemResult::invalid_transaction(error))),
+        }
+    }
+
+    let outcomes = state
+        .transaction_store()
+        .create_batch(new_transactions)
+        .map_err(|error| AppError::TransactionError(error).into_response())?;
+
+    for (index, outcome) in new_transaction_indices.into_iter().zip(outcomes) {
+        results[index] = Some(match outcome {
+            BatchOutcome::Created(transaction) => {
+                state.dispatch_webhooks(user_id, WebhookEvent::TransactionCreated, &transaction);
+                BatchItemResult::Created { transaction }
+            }
+            BatchOutcome::Duplicate(transaction_id) => {
+                BatchItemResult::Duplicate { transaction_id }
+            }
+            BatchOutcome::Failed(error) => BatchItemResult::invalid_transaction(error),
+        });
+    }
+
+    Ok(Json(results.into_iter().flatten().collect()))
+}
+
+/// Get one of the logged in user's transactions.
+///
+/// Responds with 404 if the transaction does not exist or belongs to another user.
+pub async fn get_api_transaction<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Path(transaction_id): Path<DatabaseID>,
+) -> impl IntoResponse
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    get_users_transaction(state.transaction_store(), transaction_id, user_id).map(Json)
+}
+
+/// Replace the amount, date, description and category of one of the logged in user's
+/// transactions.
+///
+/// Whether the transaction is ignored or reconciled is left unchanged. Responds with 404 if the
+/// transaction does not exist or belongs to another user.
+pub async fn update_api_transaction<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Path(transaction_id): Path<DatabaseID>,
+    Json(data): Json<TransactionData>,
+) -> impl IntoResponse
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let transaction = get_users_transaction(state.transaction_store(), transaction_id, user_id)?;
+    let today = get_user_today(state.user_store(), user_id);
+    let builder = data
+        .into_builder(user_id, today)?
+        .ignored(transaction.is_ignored())
+        .reconciled(transaction.is_reconciled());
+
+    state
+        .transaction_store()
+        .update(transaction_id, builder)
+        .map(Json)
+        .map_err(AppError::TransactionError)
+}
+
+/// Delete one of the logged in user's transactions and respond with 204 No Content.
+///
+/// Responds with 404 if the transaction does not exist or belongs to another user.
+pub async fn delete_api_transaction<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Path(transaction_id): Path<DatabaseID>,
+) -> impl IntoResponse
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    get_users_transaction(state.transaction_store(), transaction_id, user_id)?;
+
+    state
+        .transaction_store()
+        .delete(transaction_id)
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(AppError::TransactionError)
+}
+
+/// Get the transaction `transaction_id` if it belongs to the user `user_id`.
+fn get_users_transaction(
+    store: &impl TransactionStore,
+    transaction_id: DatabaseID,
+    user_id: UserID,
+) -> Result<Transaction, AppError> {
+    match store.get(transaction_id) {
+        Ok(transaction) if transaction.user_id() == user_id => Ok(transaction),
+        // Respond with 404 not found so that unauthorized users cannot know whether another user's resource exists.
+        Ok(_) | Err(TransactionError::NotFound) => Err(AppError::NotFound),
+        Err(error) => Err(AppError::TransactionError(error)),
+    }
+}
+
+#[cfg(test)]
+mod api_tests {
+    use axum::http::{
+        header::{ACCEPT, AUTHORIZATION, WWW_AUTHENTICATE},
+        HeaderValue, StatusCode,
+    };
+    use axum_extra::extract::cookie::Cookie;
+    use axum_test::{TestRequest, TestServer};
+    use email_address::EmailAddress;
+    use rusqlite::Connection;
+    use serde_json::{json, Value};
+    use time::{Duration, OffsetDateTime};
+
+    use crate::{
+        auth::log_in::LogInData,
+        build_router,
+        models::{ApiToken, CategoryName, PasswordHash, Transaction, UserID, ValidatedPassword},
+        routes::endpoints::{self, format_endpoint},
+        stores::{
+            sql_store::{create_app_state, SQLAppState},
+            ApiTokenStore, CategoryStore, UserStore,
+        },
+    };
+
+    use super::{
+        BatchItemResult, BatchTransactionData, TransactionData, MAX_BATCH_SIZE, PAGE_COUNT_HEADER,
+        TOTAL_COUNT_HEADER,
+    };
+
+    const PASSWORD: &str = "averysafeandsecurepassword";
+
+    trait AddCookiesFrom {
+        fn add_cookies_from(self, cookies: &[Cookie<'static>]) -> Self;
+    }
+
+    impl AddCookiesFrom for TestRequest {
+        fn add_cookies_from(self, cookies: &[Cookie<'static>]) -> Self {
+            cookies
+                .iter()
+                .cloned()
+                .fold(self, |request, cookie| request.add_cookie(cookie))
+        }
+    }
+
+    /// Create a server with two users and return the server and their auth cookies.
+    async fn get_test_server_and_cookies() -> (
+        TestServer,
+        Vec<Cookie<'static>>,
+        Vec<Cookie<'static>>,
+        UserID,
+    ) {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        state.password_hash_cost = 4;
+
+        let mut user_ids = vec![];
+        for email in ["foo@bar.baz", "qux@bar.baz"] {
+            let user = state
+                .user_store()
+                .create(
+                    EmailAddress::new_unchecked(email),
+                    PasswordHash::new(ValidatedPassword::new_unchecked(PASSWORD), 4).unwrap(),
+                )
+                .unwrap();
+            user_ids.push(user.id());
+        }
+        state
+            .category_store()
+            .create(CategoryName::new_unchecked("Food"), user_ids[0])
+            .unwrap();
+
+        let server = TestServer::new(build_router(state)).expect("Could not create test server.");
+
+        let mut cookies = vec![];
+        for email in ["foo@bar.baz", "qux@bar.baz"] {
+            let response = server
+                .post(endpoints::LOG_IN)
+                .form(&LogInData {
+                    email: email.to_string(),
+                    password: PASSWORD.to_string(),
+                    remember_me: None,
+                })
+                .await;
+            cookies.push(response.cookies().iter().cloned().collect::<Vec<_>>());
+        }
+        let other_user_cookies = cookies.pop().unwrap();
+        let user_cookies = cookies.pop().unwrap();
+
+        (server, user_cookies, other_user_cookies, user_ids[0])
+    }
+
+    fn get_transaction_data() -> TransactionData {
+        TransactionData {
+            amount: -12.34,
+            date: OffsetDateTime::now_utc().date() - Duration::days(1),
+            description: "Groceries".to_string(),
+            category_id: Some(1),
+        }
+    }
+
+    async fn create_transaction(server: &TestServer, cookies: &[Cookie<'static>]) -> Transaction {
+        let response = server
+            .post(endpoints::API_TRANSACTIONS)
+            .add_cookies_from(cookies)
+            .json(&get_transaction_data())
+            .await;
+
+        response.assert_status(StatusCode::CREATED);
+        response.json()
+    }
+
+    #[tokio::test]
+    async fn create_and_get_transaction() {
+        let (server, cookies, _, user_id) = get_test_server_and_cookies().await;
+
+        let transaction = create_transaction(&server, &cookies).await;
+
+        let want = get_transaction_data();
+        assert_eq!(transaction.amount(), want.amount);
+        assert_eq!(transaction.date(), &want.date);
+        assert_eq!(transaction.description(), want.description);
+        assert_eq!(transaction.category_id(), want.category_id);
+        assert_eq!(transaction.user_id(), user_id);
+
+        let response = server
+            .get(&format_endpoint(
+                endpoints::API_TRANSACTION,
+                transaction.id(),
+            ))
+            .add_cookies_from(&cookies)
+            .await;
+        response.assert_status_ok();
+        assert_eq!(response.json::<Transaction>(), transaction);
+    }
+
+    #[tokio::test]
+    async fn transaction_dates_are_serialized_as_iso_dates() {
+        let (server, cookies, _, _) = get_test_server_and_cookies().await;
+
+        create_transaction(&server, &cookies).await;
+
+        let response = server
+            .get(endpoints::API_TRANSACTIONS)
+            .add_cookies_from(&cookies)
+            .await;
+        response.assert_status_ok();
+        let transactions = response.json::<Value>();
+        assert_eq!(
+            transactions[0]["date"],
+            json!(get_transaction_data().date.to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn list_transactions_applies_filters() {
+        let (server, cookies, _, _) = get_test_server_and_cookies().await;
+        let transaction = create_transaction(&server, &cookies).await;
+        server
+            .post(endpoints::API_TRANSACTIONS)
+            .add_cookies_from(&cookies)
+            .json(&TransactionData {
+                category_id: None,
+                ..get_transaction_data()
+            })
+            .await
+            .assert_status(StatusCode::CREATED);
+
+        let response = server
+            .get(endpoints::API_TRANSACTIONS)
+            .add_query_param("category_id", 1)
+            .add_cookies_from(&cookies)
+            .await;
+
+        response.assert_status_ok();
+        assert_eq!(response.json::<Vec<Transaction>>(), vec![transaction]);
+    }
+
+    #[tokio::test]
+    async fn list_transactions_is_paginated() {
+        let (server, cookies, _, _) = get_test_server_and_cookies().await;
+        let transactions = (0..12)
+            .map(|i| get_batch_transaction_data(&i.to_string()))
+            .collect::<Vec<_>>();
+        server
+            .post(endpoints::API_TRANSACTIONS_BATCH)
+            .add_cookies_from(&cookies)
+            .json(&transactions)
+            .await
+            .assert_status_ok();
+
+        let response = server
+            .get(endpoints::API_TRANSACTIONS)
+            .add_query_param("page", 2)
+            .add_query_param("per_page", 10)
+            .add_cookies_from(&cookies)
+            .await;
+
+        response.assert_status_ok();
+        assert_eq!(response.json::<Vec<Transaction>>().len(), 2);
+        assert_eq!(response.header(TOTAL_COUNT_HEADER), "12");
+        assert_eq!(response.header(PAGE_COUNT_HEADER), "2");
+    }
+
+    #[tokio::test]
+    async fn list_transactions_caps_page_size() {
+        let (server, cookies, _, _) = get_test_server_and_cookies().await;
+        let transactions = (0..101)
+            .map(|i| get_batch_transaction_data(&i.to_string()))
+            .collect::<Vec<_>>();
+        server
+            .post(endpoints::API_TRANSACTIONS_BATCH)
+            .add_cookies_from(&cookies)
+            .json(&transactions)
+            .await
+            .assert_status_ok();
+
+        let response = server
+            .get(endpoints::API_TRANSACTIONS)
+            .add_query_param("per_page", 1000)
+            .add_cookies_from(&cookies)
+            .await;
+
+        response.assert_status_ok();
+        assert_eq!(response.json::<Vec<Transaction>>().len(), 100);
+        assert_eq!(response.header(PAGE_COUNT_HEADER), "2");
+    }
+
+    #[tokio::test]
+    async fn update_transaction() {
+        let (server, cookies, _, _) = get_test_server_and_cookies().await;
+        let transaction = create_transaction(&server, &cookies).await;
+
+        let response = server
+            .put(&format_endpoint(
+                endpoints::API_TRANSACTION,
+                transaction.id(),
+            ))
+            .add_cookies_from(&cookies)
+            .json(&TransactionData {
+                amount: 56.78,
+                description: "Refund".to_string(),
+                category_id: None,
+                ..get_transaction_data()
+            })
+            .await;
+
+        response.assert_status_ok();
+        let updated_transaction = response.json::<Transaction>();
+        assert_eq!(updated_transaction.id(), transaction.id());
+        assert_eq!(updated_transaction.amount(), 56.78);
+        assert_eq!(updated_transaction.description(), "Refund");
+        assert_eq!(updated_transaction.category_id(), None);
+    }
+
+    #[tokio::test]
+    async fn delete_transaction() {
+        let (server, cookies, _, _) = get_test_server_and_cookies().await;
+        let transaction = create_transaction(&server, &cookies).await;
+        let route = format_endpoint(endpoints::API_TRANSACTION, transaction.id());
+
+        server
+            .delete(&route)
+            .add_cookies_from(&cookies)
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+
+        server
+            .get(&route)
+            .add_cookies_from(&cookies)
+            .await
+            .assert_status_not_found();
+    }
+
+    #[tokio::test]
+    async fn other_users_cannot_access_transaction() {
+        let (server, cookies, other_user_cookies, _) = get_test_server_and_cookies().await;
+        let transaction = create_transaction(&server, &cookies).await;
+        let route = format_endpoint(endpoints::API_TRANSACTION, transaction.id());
+
+        let response = server
+            .get(&route)
+            .add_cookies_from(&other_user_cookies)
+            .await;
+        response.assert_status_not_found();
+        response.assert_json(&json!({
+            "error": "The requested resource could not be found.",
+            "code": "not_found",
+        }));
+
+        server
+            .put(&route)
+            .add_cookies_from(&other_user_cookies)
+            .json(&get_transaction_data())
+            .await
+            .assert_status_not_found();
+        server
+            .delete(&route)
+            .add_cookies_from(&other_user_cookies)
+            .await
+            .assert_status_not_found();
+
+        let response = server
+            .get(endpoints::API_TRANSACTIONS)
+            .add_cookies_from(&other_user_cookies)
+            .await;
+        response.assert_status_ok();
+        assert_eq!(response.json::<Vec<Transaction>>(), vec![]);
+    }
+
+    #[tokio::test]
+    async fn create_transaction_fails_with_other_users_category() {
+        let (server, _, other_user_cookies, _) = get_test_server_and_cookies().await;
+
+        let response = server
+            .post(endpoints::API_TRANSACTIONS)
+            .add_cookies_from(&other_user_cookies)
+            .json(&get_transaction_data())
+            .await;
+
+        response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(response.json::<Value>()["code"], "invalid_transaction");
+    }
+
+    fn get_batch_transaction_data(external_id: &str) -> BatchTransactionData {
+        BatchTransactionData {
+            transaction: get_transaction_data(),
+            external_id: Some(external_id.to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn batch_create_reports_mixed_outcomes() {
+        let (server, cookies, _, user_id) = get_test_server_and_cookies().await;
+        server
+            .post(endpoints::API_TRANSACTIONS_BATCH)
+            .add_cookies_from(&cookies)
+            .json(&vec![get_batch_transaction_data("abc")])
+            .await
+            .assert_status_ok();
+        let future_date = OffsetDateTime::now_utc().date() + Duration::days(2);
+
+        let response = server
+            .post(endpoints::API_TRANSACTIONS_BATCH)
+            .add_cookies_from(&cookies)
+            .json(&json!([
+                get_batch_transaction_data("def"),
+                get_batch_transaction_data("abc"),
+                { "amount": 1.0, "date": future_date.to_string() },
+                { "amount": "lots", "date": future_date.to_string() },
+                { "amount": 1.0, "date": get_transaction_data().date.to_string(), "category_id": 999 },
+            ]))
+            .await;
+
+        response.assert_status_ok();
+        let results = response.json::<Vec<BatchItemResult>>();
+        assert_eq!(results.len(), 5);
+        let created = match &results[0] {
+            BatchItemResult::Created { transaction } => transaction,
+            result => panic!("expected a created transaction, got {result:?}"),
+        };
+        assert_eq!(created.user_id(), user_id);
+        assert_eq!(created.description(), "Groceries");
+        assert!(matches!(results[1], BatchItemResult::Duplicate { .. }));
+        assert!(
+            matches!(&results[2], BatchItemResult::Error { code, .. } if code == "invalid_transaction")
+        );
+        assert!(
+            matches!(&results[3], BatchItemResult::Error { code, .. } if code == "invalid_json")
+        );
+        assert!(
+            matches!(&results[4], BatchItemResult::Error { code, .. } if code == "invalid_transaction")
+        );
+
+        let response = server
+            .get(endpoints::API_TRANSACTIONS)
+            .add_cookies_from(&cookies)
+            .await;
+        assert_eq!(response.json::<Vec<Transaction>>().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn batch_create_results_have_status_field() {
+        let (server, cookies, _, _) = get_test_server_and_cookies().await;
+
+        let response = server
+            .post(endpoints::API_TRANSACTIONS_BATCH)
+            .add_cookies_from(&cookies)
+            .json(&vec![
+                get_batch_transaction_data("abc"),
+                get_batch_transaction_data("abc"),
+            ])
+            .await;
+
+        response.assert_status_ok();
+        let results = response.json::<Value>();
+        assert_eq!(results[0]["status"], "created");
+        assert_eq!(results[1]["status"], "duplicate");
+        assert_eq!(
+            results[1]["transaction_id"],
+            results[0]["transaction"]["id"]
+        );
+    }
+
+    #[tokio::test]
+    async fn batch_create_rejects_too_many_transactions() {
+        let (server, cookies, _, _) = get_test_server_and_cookies().await;
+        let transactions = (0..=MAX_BATCH_SIZE)
+            .map(|i| get_batch_transaction_data(&i.to_string()))
+            .collect::<Vec<_>>();
+
+        let response = server
+            .post(endpoints::API_TRANSACTIONS_BATCH)
+            .add_cookies_from(&cookies)
+            .json(&transactions)
+            .await;
+
+        response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(response.json::<Value>()["code"], "batch_too_large");
+        let response = server
+            .get(endpoints::API_TRANSACTIONS)
+            .add_cookies_from(&cookies)
+            .await;
+        assert_eq!(response.json::<Vec<Transaction>>(), vec![]);
+    }
+
+    #[tokio::test]
+    async fn json_clients_get_unauthorized_error_without_session() {
+        let (server, _, _, _) = get_test_server_and_cookies().await;
+
+        let response = server
+            .get(endpoints::API_TRANSACTIONS)
+            .add_header(ACCEPT, HeaderValue::from_static("application/json"))
+            .await;
+
+        response.assert_status(StatusCode::UNAUTHORIZED);
+        assert_eq!(response.json::<Value>()["code"], "unauthorized");
+        assert!(response.maybe_header("location").is_none());
+    }
+
+    #[tokio::test]
+    async fn browsers_are_redirected_without_session() {
+        let (server, _, _, _) = get_test_server_and_cookies().await;
+
+        let response = server
+            .get(endpoints::API_TRANSACTIONS)
+            .add_header(
+                ACCEPT,
+                HeaderValue::from_static("text/html,application/json;q=0.9"),
+            )
+            .await;
+
+        response.assert_status_see_other();
+        assert_eq!(response.header("location"), endpoints::LOG_IN);
+    }
+
+    /// Create a server with a user that has an API token expiring at `expires_at`, and return the
+    /// app state, the server, the plaintext token and the stored token.
+    fn get_test_state_server_and_token(
+        expires_at: Option<OffsetDateTime>,
+    ) -> (SQLAppState, TestServer, String, ApiToken) {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        let user = state
+            .user_store()
+            .create(
+                EmailAddress::new_unchecked("foo@bar.baz"),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+        let token = ApiToken::generate_token();
+        let api_token = state
+            .api_token_store()
+            .create(
+                user.id(),
+                "foo".to_string(),
+                ApiToken::hash_token(&token),
+                expires_at,
+            )
+            .unwrap();
+        let server =
+            TestServer::new(build_router(state.clone())).expect("Could not create test server.");
+
+        (state, server, token, api_token)
+    }
+
+    fn bearer(token: &str) -> HeaderValue {
+        HeaderValue::from_str(&format!("Bearer {token}")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn api_token_authenticates_requests() {
+        let (mut state, server, token, api_token) = get_test_state_server_and_token(None);
+
+        let response = server
+            .post(endpoints::API_TRANSACTIONS)
+            .add_header(AUTHORIZATION, bearer(&token))
+            .json(&TransactionData {
+                category_id: None,
+                ..get_transaction_data()
+            })
+            .await;
+
+        response.assert_status(StatusCode::CREATED);
+        assert_eq!(
+            response.json::<Transaction>().user_id(),
+            api_token.user_id()
+        );
+        let response = server
+            .get(endpoints::API_TRANSACTIONS)
+            .add_header(AUTHORIZATION, bearer(&token))
+            .await;
+        response.assert_status_ok();
+        assert_eq!(response.json::<Vec<Transaction>>().len(), 1);
+        assert!(state
+            .api_token_store()
+            .get_by_hash(&ApiToken::hash_token(&token))
+            .unwrap()
+            .last_used_at()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn api_token_authenticates_summary_text() {
+        let (_, server, token, _) = get_test_state_server_and_token(None);
+
+        let response = server
+            .get(endpoints::SUMMARY_TEXT)
+            .add_header(AUTHORIZATION, bearer(&token))
+            .await;
+
+        response.assert_status_ok();
+        response.assert_text_contains("week_net: 0.00");
+    }
+
+    #[tokio::test]
+    async fn api_token_that_expires_later_is_accepted() {
+        let expires_at = OffsetDateTime::now_utc() + Duration::days(1);
+        let (_, server, token, _) = get_test_state_server_and_token(Some(expires_at));
+
+        server
+            .get(endpoints::API_TRANSACTIONS)
+            .add_header(AUTHORIZATION, bearer(&token))
+            .await
+            .assert_status_ok();
+    }
+
+    #[tokio::test]
+    async fn expired_api_token_is_rejected() {
+        let expires_at = OffsetDateTime::now_utc() - Duration::days(1);
+        let (_, server, token, _) = get_test_state_server_and_token(Some(expires_at));
+
+        let response = server
+            .get(endpoints::API_TRANSACTIONS)
+            .add_header(AUTHORIZATION, bearer(&token))
+            .await;
+
+        response.assert_status(StatusCode::UNAUTHORIZED);
+        assert_eq!(response.json::<Value>()["code"], "invalid_token");
+    }
+
+    #[tokio::test]
+    async fn revoked_api_token_is_rejected() {
+        let (mut state, server, token, api_token) = get_test_state_server_and_token(None);
+        state
+            .api_token_store()
+            .delete(api_token.user_id(), api_token.id())
+            .unwrap();
+
+        let response = server
+            .get(endpoints::API_TRANSACTIONS)
+            .add_header(AUTHORIZATION, bearer(&token))
+            .await;
+
+        response.assert_status(StatusCode::UNAUTHORIZED);
+        assert_eq!(response.json::<Value>()["code"], "invalid_token");
+    }
+
+    #[tokio::test]
+    async fn unknown_api_token_is_rejected() {
+        let (_, server, _, _) = get_test_state_server_and_token(None);
+
+        let response = server
+            .get(endpoints::API_TRANSACTIONS)
+            .add_header(AUTHORIZATION, bearer(&ApiToken::generate_token()))
+            .await;
+
+        response.assert_status(StatusCode::UNAUTHORIZED);
+        assert_eq!(response.json::<Value>()["code"], "invalid_token");
+        assert_eq!(
+            response.header(WWW_AUTHENTICATE),
+            r#"Bearer error="invalid_token""#
+        );
+    }
+
+    #[tokio::test]
+    async fn html_routes_do_not_accept_api_tokens() {
+        let (_, server, token, _) = get_test_state_server_and_token(None);
+
+        let response = server
+            .get(endpoints::DASHBOARD)
+            .add_header(AUTHORIZATION, bearer(&token))
+            .await;
+
+        response.assert_status_see_other();
+        assert_eq!(response.header("location"), endpoints::LOG_IN);
+    }
+}
@@ -0,0 +1,362 @@
+//! The section of the settings page for managing the API tokens that scripts use to access the
+//! JSON API.
+
+use askama_axum::Template;
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    Extension, Form,
+};
+use serde::{Deserialize, Serialize};
+use time::{Duration, OffsetDateTime};
+
+use crate::{
+    models::{ApiToken, DatabaseID, UserID},
+    routes::get_internal_server_error_redirect,
+    stores::{sql_store::SQLAppState, ApiTokenStore, UserError},
+};
+
+use super::endpoints;
+
+/// The maximum number of days that a user can choose for a token to last.
+const MAX_EXPIRY_DAYS: i64 = 3650;
+
+/// Renders the user's API tokens with a form for creating new tokens.
+///
+/// Empty strings mean that there is no token or message to display.
+#[derive(Template)]
+#[template(path = "partials/settings/api_tokens.html")]
+pub(super) struct ApiTokensTemplate<'a> {
+    api_tokens_route: &'a str,
+    tokens: Vec<ApiToken>,
+    /// The plaintext of a token that was just created, which is only ever shown once.
+    new_token: &'a str,
+    name_error: &'a str,
+    expiry_error: &'a str,
+}
+
+impl ApiTokensTemplate<'_> {
+    pub(super) fn new(tokens: Vec<ApiToken>) -> Self {
+        Self {
+            api_tokens_route: endpoints::API_TOKENS,
+            tokens,
+            new_token: "",
+            name_error: "",
+            expiry_error: "",
+        }
+    }
+
+    /// The route for revoking the token.
+    fn delete_route(&self, token: &ApiToken) -> String {
+        endpoints::format_endpoint(endpoints::API_TOKEN, token.id())
+    }
+
+    /// A summary of when the token was created, last used and expires.
+    fn describe(&self, token: &ApiToken) -> String {
+        let last_used = match token.last_used_at() {
+            Some(last_used_at) => last_used_at.date().to_string(),
+            None => "never".to_string(),
+        };
+        let expiry = match token.expires_at() {
+            Some(expires_at) if token.is_expired(OffsetDateTime::now_utc()) => {
+                format!("expired {}", expires_at.date())
+            }
+            Some(expires_at) => format!("expires {}", expires_at.date()),
+            None => "never expires".to_string(),
+        };
+
+        format!(
+            "Created {}, last used {last_used}, {expiry}",
+            token.created_at().date()
+        )
+    }
+}
+
+/// The form data for creating an API token.
+#[derive(Serialize, Deserialize)]
+pub struct ApiTokenForm {
+    /// A name to help the user remember what the token is for.
+    pub name: String,
+    /// The number of days until the token expires, or an empty string if it never expires.
+    pub expires_in_days: String,
+}
+
+/// Create an API token for the logged in user and respond with the updated list of tokens and the
+/// new token's plaintext.
+///
+/// Responds with inline error messages if the form is invalid.
+pub async fn create_api_token(
+    State(mut state): State<SQLAppState>,
+    Extension(user_id): Extension<UserID>,
+    Form(form): Form<ApiTokenForm>,
+) -> Response {
+    let mut api_token_store = state.api_token_store();
+    let name = form.name.trim();
+    let expires_in_days = match form.expires_in_days.trim() {
+        "" => Ok(None),
+        days => match days.parse::<i64>() {
+            Ok(days) if (1..=MAX_EXPIRY_DAYS).contains(&days) => Ok(Some(days)),
+            _ => Err(format!(
+                "The expiry must be between 1 and {MAX_EXPIRY_DAYS} days"
+            )),
+        },
+    };
+
+    let (name_error, expiry_error) = match (name.is_empty(), &expires_in_days) {
+        (true, _) => ("Enter a name for the token", ""),
+        (false, Err(error)) => ("", error.as_str()),
+        (false, Ok(_)) => ("", ""),
+    };
+    if !name_error.is_empty() || !expiry_error.is_empty() {
+        return get_api_tokens_response(&api_token_store, user_id, |template| ApiTokensTemplate {
+            name_error,
+            expiry_error,
+            ..template
+        });
+    }
+
+    let token = ApiToken::generate_token();
+    let expires_at = expires_in_days
+        .unwrap_or_default()
+        .map(|days| OffsetDateTime::now_utc() + Duration::days(days));
+
+    if let Err(error) = api_token_store.create(
+        user_id,
+        name.to_string(),
+        ApiToken::hash_token(&token),
+        expires_at,
+    ) {
+        tracing::error!("Could not create API token for user {user_id}: {error}");
+        return get_internal_server_error_redirect();
+    }
+
+    get_api_tokens_response(&api_token_store, user_id, |template| ApiTokensTemplate {
+        new_token: &token,
+        ..template
+    })
+}
+
+/// Revoke the logged in user's API token with the ID in the path and respond with the updated
+/// list of tokens.
+pub async fn delete_api_token(
+    State(mut state): State<SQLAppState>,
+    Extension(user_id): Extension<UserID>,
+    Path(token_id): Path<DatabaseID>,
+) -> Response {
+    let mut api_token_store = state.api_token_store();
+
+    match api_token_store.delete(user_id, token_id) {
+        // The token may have already been revoked, e.g. in another tab, which is fine.
+        Ok(()) | Err(UserError::NotFound) => {}
+        Err(error) => {
+            tracing::error!("Could not delete API token {token_id}: {error}");
+            return get_internal_server_error_redirect();
+        }
+    }
+
+    get_api_tokens_response(&api_token_store, user_id, |template| template)
+}
+
+/// Respond with the user's API tokens rendered by the template that `customise` returns, or a
+/// redirect to the internal server error page.
+fn get_api_tokens_response<'a, A>(
+    api_token_store: &A,
+    user_id: UserID,
+    customise: impl FnOnce(ApiTokensTemplate<'a>) -> ApiTokensTemplate<'a>,
+) -> Response
+where
+    A: ApiTokenStore,
+{
+    match api_token_store.get_by_user_id(user_id) {
+        Ok(tokens) => customise(ApiTokensTemplate::new(tokens)).into_response(),
+        Err(error) => {
+            tracing::error!("Could not get API tokens for user {user_id}: {error}");
+            get_internal_server_error_redirect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod api_tokens_tests {
+    use axum::http::StatusCode;
+    use axum_test::TestServer;
+    use email_address::EmailAddress;
+    use rusqlite::Connection;
+
+    use crate::{
+        auth::log_in::LogInData,
+        build_router,
+        models::{ApiToken, PasswordHash, User, ValidatedPassword},
+        routes::endpoints::{self, format_endpoint},
+        stores::{
+            sql_store::{create_app_state, SQLAppState},
+            ApiTokenStore, UserStore,
+        },
+    };
+
+    use super::ApiTokenForm;
+
+    const PASSWORD: &str = "averysafeandsecurepassword";
+
+    async fn get_test_state_server_and_user() -> (SQLAppState, TestServer, User) {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        state.password_hash_cost = 4;
+        let user = state
+            .user_store()
+            .create(
+                EmailAddress::new_unchecked("foo@bar.baz"),
+                PasswordHash::new(ValidatedPassword::new_unchecked(PASSWORD), 4).unwrap(),
+            )
+            .unwrap();
+
+        let mut server =
+            TestServer::new(build_router(state.clone())).expect("Could not create test server.");
+        server.do_save_cookies();
+        server
+            .post(endpoints::LOG_IN)
+            .form(&LogInData {
+                email: user.email().to_string(),
+                password: PASSWORD.to_string(),
+                remember_me: None,
+            })
+            .await
+            .assert_status_see_other();
+
+        (state, server, user)
+    }
+
+    /// Get the plaintext of a newly created token from the API tokens HTML.
+    fn get_new_token(html: &str) -> Option<String> {
+        let (_, rest) = html.split_once(r#"id="new-api-token""#)?;
+        let (_, rest) = rest.split_once('>')?;
+        let (token, _) = rest.split_once('<')?;
+
+        Some(token.trim().to_string())
+    }
+
+    #[tokio::test]
+    async fn create_token_shows_plaintext_once() {
+        let (mut state, server, user) = get_test_state_server_and_user().await;
+
+        let response = server
+            .post(endpoints::API_TOKENS)
+            .form(&ApiTokenForm {
+                name: "Import script".to_string(),
+                expires_in_days: "30".to_string(),
+            })
+            .await;
+
+        response.assert_status_ok();
+        let token = get_new_token(&response.text()).expect("Could not find the new token");
+        let tokens = state.api_token_store().get_by_user_id(user.id()).unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].name(), "Import script");
+        assert!(tokens[0].expires_at().is_some());
+        assert_eq!(
+            state
+                .api_token_store()
+                .get_by_hash(&ApiToken::hash_token(&token)),
+            Ok(tokens[0].clone())
+        );
+
+        let response = server.get(endpoints::SETTINGS).await;
+        response.assert_status_ok();
+        response.assert_text_contains("Import script");
+        assert!(!response.text().contains(&token));
+    }
+
+    #[tokio::test]
+    async fn create_token_without_expiry() {
+        let (mut state, server, user) = get_test_state_server_and_user().await;
+
+        server
+            .post(endpoints::API_TOKENS)
+            .form(&ApiTokenForm {
+                name: "Import script".to_string(),
+                expires_in_days: "".to_string(),
+            })
+            .await
+            .assert_status_ok();
+
+        let tokens = state.api_token_store().get_by_user_id(user.id()).unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].expires_at(), None);
+    }
+
+    #[tokio::test]
+    async fn create_token_fails_with_invalid_form() {
+        let (mut state, server, user) = get_test_state_server_and_user().await;
+
+        for (name, expires_in_days, want_error) in [
+            ("  ", "30", "Enter a name for the token"),
+            ("foo", "0", "The expiry must be between 1 and 3650 days"),
+            ("foo", "bar", "The expiry must be between 1 and 3650 days"),
+        ] {
+            let response = server
+                .post(endpoints::API_TOKENS)
+                .form(&ApiTokenForm {
+                    name: name.to_string(),
+                    expires_in_days: expires_in_days.to_string(),
+                })
+                .await;
+
+            response.assert_status_ok();
+            response.assert_text_contains(want_error);
+            assert_eq!(get_new_token(&response.text()), None);
+        }
+
+        assert_eq!(
+            state.api_token_store().get_by_user_id(user.id()).unwrap(),
+            vec![]
+        );
+    }
+
+    #[tokio::test]
+    async fn delete_token() {
+        let (mut state, server, user) = get_test_state_server_and_user().await;
+        let token = state
+            .api_token_store()
+            .create(user.id(), "foo".to_string(), "hash".to_string(), None)
+            .unwrap();
+
+        let response = server
+            .delete(&format_endpoint(endpoints::API_TOKEN, token.id()))
+            .await;
+
+        response.assert_status_ok();
+        response.assert_text_contains("You have no API tokens.");
+        assert_eq!(
+            state.api_token_store().get_by_user_id(user.id()).unwrap(),
+            vec![]
+        );
+    }
+
+    #[tokio::test]
+    async fn delete_token_ignores_other_users_tokens() {
+        let (mut state, server, _) = get_test_state_server_and_user().await;
+        let other_user = state
+            .user_store()
+            .create(
+                EmailAddress::new_unchecked("qux@bar.baz"),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+        let token = state
+            .api_token_store()
+            .create(other_user.id(), "foo".to_string(), "hash".to_string(), None)
+            .unwrap();
+
+        let response = server
+            .delete(&format_endpoint(endpoints::API_TOKEN, token.id()))
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(
+            state
+                .api_token_store()
+                .get_by_user_id(other_user.id())
+                .unwrap(),
+            vec![token]
+        );
+    }
+}
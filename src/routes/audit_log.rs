@@ -0,0 +1,261 @@
+//! The page listing the changes that the current user has made to their data.
+
+use askama_axum::Template;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use serde::Deserialize;
+use time::macros::format_description;
+
+use crate::{
+    models::{AuditEntityType, AuditLogEntry, UserID},
+    routes::get_internal_server_error_redirect,
+    stores::{audit::AuditLogQuery, sql_store::SQLAppState},
+};
+
+use super::{
+    endpoints,
+    navigation::{get_nav_bar, NavbarTemplate},
+};
+
+/// How many audit log entries to show on each page.
+const PAGE_SIZE: u64 = 20;
+
+/// Renders a page of the audit log.
+#[derive(Template)]
+#[template(path = "views/audit_log.html")]
+struct AuditLogTemplate<'a> {
+    navbar: NavbarTemplate<'a>,
+    audit_log_route: &'a str,
+    entries: Vec<AuditLogEntry>,
+    /// The entity type that the entries are filtered by, if any.
+    entity_type: Option<AuditEntityType>,
+    entity_types: [AuditEntityType; 2],
+    /// The current page, starting from one.
+    page: u64,
+    page_count: u64,
+    previous_page_route: Option<String>,
+    next_page_route: Option<String>,
+}
+
+impl AuditLogTemplate<'_> {
+    fn is_selected(&self, entity_type: &AuditEntityType) -> bool {
+        self.entity_type == Some(*entity_type)
+    }
+
+    fn format_timestamp(&self, entry: &AuditLogEntry) -> String {
+        entry
+            .timestamp()
+            .format(format_description!(
+                "[year]-[month]-[day] [hour]:[minute]:[second]"
+            ))
+            .unwrap_or_default()
+    }
+}
+
+/// The query parameters for the audit log page.
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditLogPageQuery {
+    /// The page to show, starting from one. Defaults to the first page.
+    pub page: Option<u64>,
+    /// Only show changes to this kind of entity, e.g. "transaction". An empty string or no value
+    /// shows changes to every kind of entity.
+    pub entity_type: Option<String>,
+}
+
+/// The route for `page` of the audit log filtered by `entity_type`.
+fn get_page_route(page: u64, entity_type: Option<AuditEntityType>) -> String {
+    match entity_type {
+        Some(entity_type) => format!(
+            "{}?page={page}&entity_type={entity_type}",
+            endpoints::AUDIT_LOG
+        ),
+        None => format!("{}?page={page}", endpoints::AUDIT_LOG),
+    }
+}
+
+/// Display a page of the changes that the logged in user has made, newest first.
+pub async fn get_audit_log_page(
+    State(mut state): State<SQLAppState>,
+    Extension(user_id): Extension<UserID>,
+    Query(query): Query<AuditLogPageQuery>,
+) -> Response {
+    let entity_type = match query.entity_type.as_deref() {
+        None | Some("") => None,
+        Some(entity_type) => match entity_type.parse() {
+            Ok(entity_type) => Some(entity_type),
+            Err(error) => return (StatusCode::BAD_REQUEST, error).into_response(),
+        },
+    };
+    let page = query.page.unwrap_or(1).max(1);
+
+    let (entries, count) = match state.user_store().get_audit_log(AuditLogQuery {
+        user_id,
+        entity_type,
+        limit: PAGE_SIZE,
+        offset: (page - 1) * PAGE_SIZE,
+    }) {
+        Ok(result) => result,
+        Err(error) => {
+            tracing::error!("Could not get the audit log: {error}");
+            return get_internal_server_error_redirect();
+        }
+    };
+
+    let page_count = count.div_ceil(PAGE_SIZE).max(1);
+
+    AuditLogTemplate {
+        navbar: get_nav_bar(endpoints::AUDIT_LOG, state.demo_mode),
+        audit_log_route: endpoints::AUDIT_LOG,
+        entries,
+        entity_type,
+        entity_types: AuditEntityType::ALL,
+        page,
+        page_count,
+        previous_page_route: (page > 1).then(|| get_page_route(page - 1, entity_type)),
+        next_page_route: (page < page_count).then(|| get_page_route(page + 1, entity_type)),
+    }
+    .into_response()
+}
+
+#[cfg(test)]
+mod audit_log_route_tests {
+    use axum::http::StatusCode;
+    use axum_test::TestServer;
+    use email_address::EmailAddress;
+    use rusqlite::Connection;
+
+    use crate::{
+        auth::log_in::LogInData,
+        build_router,
+        models::{CategoryName, PasswordHash, Transaction, User, ValidatedPassword},
+        routes::endpoints,
+        stores::{
+            sql_store::{create_app_state, SQLAppState},
+            CategoryStore, TransactionStore, UserStore,
+        },
+    };
+
+    const PASSWORD: &str = "averysafeandsecurepassword";
+
+    fn create_user(state: &mut SQLAppState, email: &str) -> User {
+        state
+            .user_store()
+            .create(
+                EmailAddress::new_unchecked(email),
+                PasswordHash::new(ValidatedPassword::new_unchecked(PASSWORD), 4).unwrap(),
+            )
+            .unwrap()
+    }
+
+    async fn get_server(state: SQLAppState, user: &User) -> TestServer {
+        let mut server = TestServer::new(build_router(state)).unwrap();
+        server.do_save_cookies();
+        server
+            .post(endpoints::LOG_IN)
+            .form(&LogInData {
+                email: user.email().to_string(),
+                password: PASSWORD.to_string(),
+                remember_me: None,
+            })
+            .await
+            .assert_status_see_other();
+
+        server
+    }
+
+    #[tokio::test]
+    async fn lists_own_changes_and_filters_by_entity_type() {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        state.password_hash_cost = 4;
+        let user = create_user(&mut state, "foo@bar.baz");
+        let other_user = create_user(&mut state, "qux@bar.baz");
+        state
+            .category_store()
+            .create(CategoryName::new_unchecked("Groceries"), user.id())
+            .unwrap();
+        let transaction = state
+            .transaction_store()
+            .create_from_builder(
+                Transaction::build(12.3, user.id()).description("Apples".to_string()),
+            )
+            .unwrap();
+        state.transaction_store().delete(transaction.id()).unwrap();
+        state
+            .transaction_store()
+            .create_from_builder(
+                Transaction::build(45.6, other_user.id()).description("Secret".to_string()),
+            )
+            .unwrap();
+        let server = get_server(state, &user).await;
+
+        let response = server.get(endpoints::AUDIT_LOG).await;
+
+        response.assert_status_ok();
+        let text = response.text();
+        assert!(text.contains("Groceries"));
+        assert!(text.contains("12.30 on"));
+        assert!(text.contains("(Apples)"));
+        assert!(text.contains("delete"));
+        assert!(!text.contains("Secret"));
+
+        let response = server
+            .get(endpoints::AUDIT_LOG)
+            .add_query_param("entity_type", "category")
+            .await;
+
+        response.assert_status_ok();
+        let text = response.text();
+        assert!(text.contains("Groceries"));
+        assert!(!text.contains("Apples"));
+    }
+
+    #[tokio::test]
+    async fn pages_through_entries() {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        state.password_hash_cost = 4;
+        let user = create_user(&mut state, "foo@bar.baz");
+        for i in 0..25 {
+            state
+                .category_store()
+                .create(
+                    CategoryName::new_unchecked(&format!("Category {i:02}")),
+                    user.id(),
+                )
+                .unwrap();
+        }
+        let server = get_server(state, &user).await;
+
+        let first_page = server.get(endpoints::AUDIT_LOG).await.text();
+        let second_page = server
+            .get(endpoints::AUDIT_LOG)
+            .add_query_param("page", 2)
+            .await
+            .text();
+
+        assert!(first_page.contains("Category 24"));
+        assert!(!first_page.contains("Category 04"));
+        assert!(first_page.contains("Page 1 of 2"));
+        assert!(first_page.contains("/audit_log?page=2"));
+        assert!(second_page.contains("Category 04"));
+        assert!(!second_page.contains("Category 05"));
+        assert!(second_page.contains("/audit_log?page=1"));
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_entity_type() {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        state.password_hash_cost = 4;
+        let user = create_user(&mut state, "foo@bar.baz");
+        let server = get_server(state, &user).await;
+
+        server
+            .get(endpoints::AUDIT_LOG)
+            .add_query_param("entity_type", "foo")
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,201 @@
+//! Downloading a backup of the whole database.
+
+use std::{env, fs};
+
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Extension,
+};
+use time::OffsetDateTime;
+
+use crate::{db::get_backup_file_name, models::UserID, stores::sql_store::SQLAppState};
+
+use super::endpoints;
+
+/// The media type for SQLite database files.
+const SQLITE_CONTENT_TYPE: &str = "application/vnd.sqlite3";
+
+/// Respond with a consistent copy of the database as a file download.
+///
+/// The backup contains every user's data, so only the owner of the site, i.e. the first user to
+/// register, may download it. Other users get 403 Forbidden.
+pub async fn get_backup(
+    State(mut state): State<SQLAppState>,
+    Extension(user_id): Extension<UserID>,
+) -> Response {
+    match state.user_store().get_owner_id() {
+        Ok(Some(owner_id)) if owner_id == user_id => {}
+        Ok(_) => {
+            return (
+                StatusCode::FORBIDDEN,
+                "Only the owner of this site can download backups.",
+            )
+                .into_response()
+        }
+        Err(error) => {
+            tracing::error!("Could not get the owner of the site: {error}");
+            return Redirect::to(endpoints::INTERNAL_ERROR).into_response();
+        }
+    }
+
+    let file_name = get_backup_file_name(OffsetDateTime::now_utc());
+    let path = env::temp_dir().join(format!("{}-{file_name}", rand::random::<u64>()));
+
+    // The backup blocks while it copies the database, so keep it off the async runtime.
+    let backup_path = path.clone();
+    let operation = state.operations.start("backup");
+    let result = tokio::task::spawn_blocking(move || {
+        let _operation = operation;
+        state.user_store().backup(&backup_path)?;
+        Ok::<_, Box<dyn std::error::Error + Send + Sync>>(fs::read(&backup_path)?)
+    })
+    .await;
+
+    if let Err(error) = fs::remove_file(&path) {
+        tracing::warn!("Could not remove the temporary backup file {path:?}: {error}");
+    }
+
+    match result {
+        Ok(Ok(backup)) => (
+            [
+                (header::CONTENT_TYPE, SQLITE_CONTENT_TYPE.to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{file_name}\""),
+                ),
+            ],
+            backup,
+        )
+            .into_response(),
+        Ok(Err(error)) => {
+            tracing::error!("Could not back up the database: {error}");
+            Redirect::to(endpoints::INTERNAL_ERROR).into_response()
+        }
+        Err(error) => {
+            tracing::error!("The backup task failed: {error}");
+            Redirect::to(endpoints::INTERNAL_ERROR).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod backup_tests {
+    use std::{env, fs};
+
+    use axum::http::{header, StatusCode};
+    use axum_test::TestServer;
+    use email_address::EmailAddress;
+    use rusqlite::Connection;
+
+    use crate::{
+        auth::log_in::LogInData,
+        build_router,
+        models::{PasswordHash, User, ValidatedPassword},
+        routes::endpoints,
+        stores::{
+            sql_store::{create_app_state, SQLAppState},
+            TransactionStore, UserStore,
+        },
+    };
+
+    const PASSWORD: &str = "averysafeandsecurepassword";
+
+    fn create_user(state: &mut SQLAppState, email: &str) -> User {
+        state
+            .user_store()
+            .create(
+                EmailAddress::new_unchecked(email),
+                PasswordHash::new(ValidatedPassword::new_unchecked(PASSWORD), 4).unwrap(),
+            )
+            .unwrap()
+    }
+
+    async fn log_in(server: &TestServer, user: &User) {
+        server
+            .post(endpoints::LOG_IN)
+            .form(&LogInData {
+                email: user.email().to_string(),
+                password: PASSWORD.to_string(),
+                remember_me: None,
+            })
+            .await
+            .assert_status_see_other();
+    }
+
+    fn count_rows(connection: &Connection, table: &str) -> i64 {
+        connection
+            .query_row(&format!("SELECT COUNT(*) FROM \"{table}\""), (), |row| {
+                row.get(0)
+            })
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn backup_contains_all_rows() {
+        let path = env::temp_dir().join(format!(
+            "budgeteur_backup_route_test_{}.db",
+            rand::random::<u64>()
+        ));
+        let mut state = create_app_state(Connection::open(&path).unwrap(), "42").unwrap();
+        state.password_hash_cost = 4;
+        let owner = create_user(&mut state, "foo@bar.baz");
+        create_user(&mut state, "qux@bar.baz");
+        for amount in [1.0, 2.0, 3.0] {
+            state
+                .transaction_store()
+                .create(amount, owner.id())
+                .unwrap();
+        }
+        let mut server = TestServer::new(build_router(state)).unwrap();
+        server.do_save_cookies();
+        log_in(&server, &owner).await;
+
+        let response = server.get(endpoints::BACKUP).await;
+
+        response.assert_status_ok();
+        assert_eq!(
+            response.header(header::CONTENT_TYPE),
+            "application/vnd.sqlite3"
+        );
+        let content_disposition = response.header(header::CONTENT_DISPOSITION);
+        let content_disposition = content_disposition.to_str().unwrap();
+        assert!(content_disposition.starts_with("attachment; filename=\"budgeteur-"));
+        assert!(content_disposition.ends_with(".sqlite\""));
+
+        let backup_path = env::temp_dir().join(format!(
+            "budgeteur_backup_route_test_copy_{}.db",
+            rand::random::<u64>()
+        ));
+        fs::write(&backup_path, response.as_bytes()).unwrap();
+        let backup = Connection::open(&backup_path).unwrap();
+        let original = Connection::open(&path).unwrap();
+        for table in ["user", "transaction"] {
+            assert_eq!(count_rows(&backup, table), count_rows(&original, table));
+        }
+        assert_eq!(count_rows(&backup, "transaction"), 3);
+
+        drop(backup);
+        drop(original);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = fs::remove_file(format!("{}{suffix}", path.display()));
+            let _ = fs::remove_file(format!("{}{suffix}", backup_path.display()));
+        }
+    }
+
+    #[tokio::test]
+    async fn backup_is_forbidden_for_other_users() {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        state.password_hash_cost = 4;
+        create_user(&mut state, "foo@bar.baz");
+        let other_user = create_user(&mut state, "qux@bar.baz");
+        let mut server = TestServer::new(build_router(state)).unwrap();
+        server.do_save_cookies();
+        log_in(&server, &other_user).await;
+
+        let response = server.get(endpoints::BACKUP).await;
+
+        response.assert_status(StatusCode::FORBIDDEN);
+    }
+}
@@ -1,10 +1,11 @@
 //! This files defines the API routes for the category type.
 
+use askama_axum::Template;
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
-    Form, Json,
+    Extension, Form, Json,
 };
 use axum_extra::extract::PrivateCookieJar;
 
@@ -12,25 +13,65 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     auth::cookie::get_user_id_from_auth_cookie,
-    models::{CategoryName, DatabaseID, UserID},
+    models::{Category, CategoryName, DatabaseID, UserID},
     stores::{CategoryStore, TransactionStore, UserStore},
     AppError, AppState,
 };
 
+/// The maximum number of categories shown while searching.
+const CATEGORY_SEARCH_LIMIT: u64 = 10;
+
+/// The maximum number of categories suggested for a new transaction.
+const CATEGORY_SUGGESTION_LIMIT: u64 = 3;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CategoryData {
     pub name: String,
 }
 
+/// The query parameters for searching the user's categories.
+#[derive(Debug, Default, Deserialize)]
+pub struct CategorySearchQuery {
+    /// The start of the category name, ignoring case.
+    #[serde(default)]
+    pub q: String,
+}
+
+/// Renders the categories matching a search as options for the category combobox.
+#[derive(Template)]
+#[template(path = "partials/categories/options.html")]
+struct CategoryOptionsTemplate {
+    categories: Vec<Category>,
+    /// The name of a new category to offer to create, if no category has exactly this name.
+    create_name: Option<String>,
+}
+
+/// The query parameters for suggesting categories for a new transaction.
+#[derive(Debug, Default, Deserialize)]
+pub struct CategorySuggestionQuery {
+    /// The description of the new transaction.
+    #[serde(default)]
+    pub description: String,
+}
+
+/// Renders the categories suggested for a new transaction as one-click buttons.
+#[derive(Template)]
+#[template(path = "partials/categories/suggestions.html")]
+struct CategorySuggestionsTemplate {
+    categories: Vec<Category>,
+}
+
 /// A route handler for creating a new category.
 ///
+/// This function will return the status code 404 if `user_id` is not the logged in user.
+///
 /// # Panics
 ///
 /// Panics if the lock for the database connection is already held by the same thread.
 pub async fn create_category<C, T, U>(
     State(state): State<AppState<C, T, U>>,
     Path(user_id): Path<UserID>,
-    _: PrivateCookieJar,
+    Extension(logged_in_user_id): Extension<UserID>,
     Form(new_category): Form<CategoryData>,
 ) -> impl IntoResponse
 where
@@ -38,6 +79,11 @@ where
     T: TransactionStore + Send + Sync,
     U: UserStore + Send + Sync,
 {
+    if user_id != logged_in_user_id {
+        // Respond with 404 not found so that unauthorized users cannot know whether another user exists.
+        return Err(AppError::NotFound);
+    }
+
     let name = CategoryName::new(&new_category.name)?;
 
     state
@@ -47,6 +93,62 @@ where
         .map_err(AppError::CategoryError)
 }
 
+/// A route handler for searching the logged in user's categories by the start of their names.
+///
+/// Responds with up to [CATEGORY_SEARCH_LIMIT] categories as options for the category combobox,
+/// followed by an option to create a category named `q` if the user does not have one.
+pub async fn search_categories<C, T, U>(
+    State(state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Query(query): Query<CategorySearchQuery>,
+) -> impl IntoResponse
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let q = query.q.trim();
+    let categories = state
+        .category_store()
+        .search(user_id, q, CATEGORY_SEARCH_LIMIT)?;
+
+    let create_name = CategoryName::new(q)
+        .ok()
+        .filter(|name| {
+            !categories
+                .iter()
+                .any(|category| category.name().as_ref().eq_ignore_ascii_case(name.as_ref()))
+        })
+        .map(|name| name.as_ref().to_string());
+
+    Ok::<_, AppError>(CategoryOptionsTemplate {
+        categories,
+        create_name,
+    })
+}
+
+/// A route handler for suggesting categories for a new transaction based on the categories of
+/// past transactions with similar descriptions.
+///
+/// Responds with up to [CATEGORY_SUGGESTION_LIMIT] categories, most likely first.
+pub async fn suggest_categories<C, T, U>(
+    State(state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Query(query): Query<CategorySuggestionQuery>,
+) -> impl IntoResponse
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let categories =
+        state
+            .category_store()
+            .suggest(user_id, &query.description, CATEGORY_SUGGESTION_LIMIT)?;
+
+    Ok::<_, AppError>(CategorySuggestionsTemplate { categories })
+}
+
 /// A route handler for getting a category by its database ID.
 ///
 /// This function will return the status code 404 if the requested resource does not exist (e.g., not created yet).
@@ -89,7 +191,7 @@ mod category_tests {
     use axum::{
         extract::{Path, State},
         http::StatusCode,
-        Form,
+        Extension, Form,
     };
     use axum_extra::extract::{cookie::Key, PrivateCookieJar};
 
@@ -97,10 +199,13 @@ mod category_tests {
         auth::cookie::{set_auth_cookie, COOKIE_DURATION},
         models::{
             Category, CategoryError, CategoryName, DatabaseID, PasswordHash, Transaction,
-            TransactionBuilder, TransactionError, User, UserID,
+            TransactionBuilder, TransactionError, User, UserID, MAX_CATEGORY_NAME_LENGTH,
         },
         routes::category::{create_category, get_category},
-        stores::{transaction::TransactionQuery, CategoryStore, TransactionStore, UserStore},
+        stores::{
+            transaction::{BatchOutcome, NewTransaction, TransactionQuery},
+            CategoryStore, TransactionStore, UserStore,
+        },
         AppState,
     };
 
@@ -157,6 +262,27 @@ mod category_tests {
         fn get_by_user(&self, _user_id: UserID) -> Result<Vec<Category>, CategoryError> {
             todo!()
         }
+
+        fn search(
+            &self,
+            _user_id: UserID,
+            _prefix: &str,
+            _limit: u64,
+        ) -> Result<Vec<Category>, CategoryError> {
+            todo!()
+        }
+
+        fn suggest(
+            &self,
+
+            _user_id: UserID,
+
+            _description: &str,
+
+            _limit: u64,
+        ) -> Result<Vec<Category>, CategoryError> {
+            todo!()
+        }
     }
 
     #[derive(Clone)]
@@ -171,6 +297,15 @@ mod category_tests {
             todo!()
         }
 
+        fn create_with_invite(
+            &mut self,
+            _email: email_address::EmailAddress,
+            _password_hash: PasswordHash,
+            _invite_code: &str,
+        ) -> Result<User, crate::stores::UserError> {
+            todo!()
+        }
+
         fn get(&self, _id: UserID) -> Result<User, crate::stores::UserError> {
             todo!()
         }
@@ -181,6 +316,81 @@ mod category_tests {
         ) -> Result<User, crate::stores::UserError> {
             todo!()
         }
+
+        fn set_password(
+            &mut self,
+            _id: UserID,
+            _password_hash: PasswordHash,
+        ) -> Result<User, crate::stores::UserError> {
+            todo!()
+        }
+
+        fn increment_session_generation(
+            &mut self,
+            _id: UserID,
+        ) -> Result<User, crate::stores::UserError> {
+            todo!()
+        }
+
+        fn update_password_hash(
+            &mut self,
+            _id: UserID,
+            _password_hash: PasswordHash,
+        ) -> Result<(), crate::stores::UserError> {
+            todo!()
+        }
+
+        fn set_email(
+            &mut self,
+            _id: UserID,
+            _email: email_address::EmailAddress,
+        ) -> Result<crate::models::User, crate::stores::UserError> {
+            todo!()
+        }
+
+        fn delete(&mut self, _id: UserID) -> Result<(), crate::stores::UserError> {
+            todo!()
+        }
+
+        fn get_preferences(
+            &self,
+            _user_id: UserID,
+        ) -> Result<crate::models::UserPreferences, crate::stores::UserError> {
+            todo!()
+        }
+
+        fn set_timezone(
+            &mut self,
+            _user_id: UserID,
+            _timezone: &str,
+        ) -> Result<crate::models::UserPreferences, crate::stores::UserError> {
+            todo!()
+        }
+
+        fn set_theme(
+            &mut self,
+            _user_id: UserID,
+            _theme: crate::models::Theme,
+        ) -> Result<crate::models::UserPreferences, crate::stores::UserError> {
+            todo!()
+        }
+
+        fn set_landing_page(
+            &mut self,
+            _user_id: UserID,
+            _landing_page: crate::models::LandingPage,
+        ) -> Result<crate::models::UserPreferences, crate::stores::UserError> {
+            todo!()
+        }
+
+        fn set_date_periods(
+            &mut self,
+            _user_id: UserID,
+            _week_start: crate::models::WeekStart,
+            _month_start_day: u8,
+        ) -> Result<crate::models::UserPreferences, crate::stores::UserError> {
+            todo!()
+        }
     }
 
     #[derive(Clone)]
@@ -202,6 +412,13 @@ mod category_tests {
             todo!()
         }
 
+        fn create_batch(
+            &mut self,
+            _transactions: Vec<NewTransaction>,
+        ) -> Result<Vec<BatchOutcome>, TransactionError> {
+            todo!()
+        }
+
         fn get(&self, _id: DatabaseID) -> Result<Transaction, TransactionError> {
             todo!()
         }
@@ -216,6 +433,53 @@ mod category_tests {
         ) -> Result<Vec<Transaction>, TransactionError> {
             todo!()
         }
+
+        fn count_query(&self, _filter: TransactionQuery) -> Result<u64, TransactionError> {
+            todo!()
+        }
+
+        fn sum_query(&self, _filter: TransactionQuery) -> Result<f64, TransactionError> {
+            todo!()
+        }
+
+        fn set_ignored(
+            &mut self,
+            _id: DatabaseID,
+            _ignored: bool,
+        ) -> Result<Transaction, TransactionError> {
+            todo!()
+        }
+
+        fn set_reconciled(&mut self, _ids: &[DatabaseID]) -> Result<(), TransactionError> {
+            todo!()
+        }
+
+        fn update(
+            &mut self,
+            _id: DatabaseID,
+            _builder: TransactionBuilder,
+        ) -> Result<Transaction, TransactionError> {
+            todo!()
+        }
+
+        fn delete(&mut self, _id: DatabaseID) -> Result<(), TransactionError> {
+            todo!()
+        }
+
+        fn get_duplicates(
+            &self,
+            _user_id: UserID,
+        ) -> Result<Vec<Vec<Transaction>>, TransactionError> {
+            todo!()
+        }
+
+        fn merge_duplicates(
+            &mut self,
+            _keep_id: DatabaseID,
+            _duplicate_ids: &[DatabaseID],
+        ) -> Result<Transaction, TransactionError> {
+            todo!()
+        }
     }
 
     fn get_test_app_config() -> (
@@ -250,11 +514,14 @@ mod category_tests {
         let form = CategoryData {
             name: want.name.to_string(),
         };
-        let jar = get_cookie_jar(want.user_id, state.cookie_key().to_owned());
-
-        let response = create_category(State(state), Path(want.user_id), jar, Form(form))
-            .await
-            .into_response();
+        let response = create_category(
+            State(state),
+            Path(want.user_id),
+            Extension(want.user_id),
+            Form(form),
+        )
+        .await
+        .into_response();
 
         assert_eq!(response.status(), StatusCode::OK);
         assert_create_calls(&store, &want);
@@ -269,13 +536,51 @@ mod category_tests {
         let form = CategoryData {
             name: "".to_string(),
         };
-        let jar = get_cookie_jar(user_id, state.cookie_key().to_owned());
+        let response = create_category(State(state), Path(user_id), Extension(user_id), Form(form))
+            .await
+            .into_response();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
 
-        let response = create_category(State(state), Path(user_id), jar, Form(form))
+    #[tokio::test]
+    async fn create_category_fails_on_long_name() {
+        let (state, store) = get_test_app_config();
+        let user_id = UserID::new(123);
+
+        let form = CategoryData {
+            name: "a".repeat(MAX_CATEGORY_NAME_LENGTH + 1),
+        };
+        let response = create_category(State(state), Path(user_id), Extension(user_id), Form(form))
             .await
             .into_response();
 
         assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(store.create_calls.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn create_category_fails_on_wrong_user() {
+        let (state, store) = get_test_app_config();
+
+        let user_id = UserID::new(123);
+        let unauthorized_user_id = UserID::new(user_id.as_i64() + 999);
+
+        let form = CategoryData {
+            name: "Foo".to_string(),
+        };
+
+        let response = create_category(
+            State(state),
+            Path(user_id),
+            Extension(unauthorized_user_id),
+            Form(form),
+        )
+        .await
+        .into_response();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(store.create_calls.lock().unwrap().is_empty());
     }
 
     #[tokio::test]
@@ -324,7 +629,7 @@ mod category_tests {
 
     fn get_cookie_jar(user_id: UserID, key: Key) -> PrivateCookieJar {
         let jar = PrivateCookieJar::new(key);
-        set_auth_cookie(jar, user_id, COOKIE_DURATION).unwrap()
+        set_auth_cookie(jar, user_id, 0, COOKIE_DURATION).unwrap()
     }
 
     fn assert_create_calls(store: &SpyCategoryStore, want: &CreateCategoryCall) {
@@ -359,3 +664,176 @@ mod category_tests {
         );
     }
 }
+
+#[cfg(test)]
+mod category_search_tests {
+    use axum::{
+        body::to_bytes,
+        extract::{Query, State},
+        http::StatusCode,
+        response::{IntoResponse, Response},
+        Extension,
+    };
+    use rusqlite::Connection;
+
+    use crate::{
+        models::{CategoryName, PasswordHash, Transaction, User, ValidatedPassword},
+        stores::{
+            sql_store::{create_app_state, SQLAppState},
+            CategoryStore, TransactionStore, UserStore,
+        },
+    };
+
+    use super::{
+        search_categories, suggest_categories, CategorySearchQuery, CategorySuggestionQuery,
+        CATEGORY_SEARCH_LIMIT,
+    };
+
+    fn get_test_state_and_user() -> (SQLAppState, User) {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        let user = state
+            .user_store()
+            .create(
+                "test@test.com".parse().unwrap(),
+                PasswordHash::new(ValidatedPassword::new_unchecked("test"), 4).unwrap(),
+            )
+            .unwrap();
+
+        (state, user)
+    }
+
+    fn create_categories(state: &mut SQLAppState, user: &User, names: &[&str]) {
+        for name in names {
+            state
+                .category_store()
+                .create(CategoryName::new_unchecked(name), user.id())
+                .unwrap();
+        }
+    }
+
+    async fn search(state: SQLAppState, user: &User, q: &str) -> Response {
+        search_categories(
+            State(state),
+            Extension(user.id()),
+            Query(CategorySearchQuery { q: q.to_string() }),
+        )
+        .await
+        .into_response()
+    }
+
+    async fn get_body_text(response: Response) -> String {
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        String::from_utf8_lossy(&body).to_string()
+    }
+
+    #[tokio::test]
+    async fn search_lists_matching_categories() {
+        let (mut state, user) = get_test_state_and_user();
+        create_categories(&mut state, &user, &["Groceries", "Gifts", "Rent"]);
+
+        let response = search(state, &user, "g").await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let text = get_body_text(response).await;
+        assert!(text.contains("Gifts"));
+        assert!(text.contains("Groceries"));
+        assert!(!text.contains("Rent"));
+        assert_eq!(text.matches("role=\"option\"").count(), 3);
+        assert!(text.contains("Create 'g'"));
+    }
+
+    #[tokio::test]
+    async fn search_offers_create_only_without_exact_match() {
+        let (mut state, user) = get_test_state_and_user();
+        create_categories(&mut state, &user, &["Groceries"]);
+
+        let text = get_body_text(search(state, &user, " groceries ").await).await;
+
+        assert!(text.contains("Groceries"));
+        assert!(!text.contains("data-create-category"));
+    }
+
+    #[tokio::test]
+    async fn search_without_query_does_not_offer_create() {
+        let (state, user) = get_test_state_and_user();
+
+        let text = get_body_text(search(state, &user, "").await).await;
+
+        assert!(!text.contains("data-create-category"));
+        assert!(text.contains("Type to search or create a category"));
+    }
+
+    #[tokio::test]
+    async fn search_returns_at_most_limit() {
+        let (mut state, user) = get_test_state_and_user();
+        let names: Vec<_> = (0..CATEGORY_SEARCH_LIMIT + 5)
+            .map(|i| format!("Category {i:02}"))
+            .collect();
+        let names: Vec<_> = names.iter().map(String::as_str).collect();
+        create_categories(&mut state, &user, &names);
+
+        let text = get_body_text(search(state, &user, "Category").await).await;
+
+        assert_eq!(
+            text.matches("data-category-id").count(),
+            CATEGORY_SEARCH_LIMIT as usize
+        );
+    }
+
+    #[tokio::test]
+    async fn search_escapes_category_names() {
+        let (mut state, user) = get_test_state_and_user();
+        create_categories(&mut state, &user, &["<b>Bold</b>"]);
+
+        let text = get_body_text(search(state, &user, "<b>").await).await;
+
+        assert!(!text.contains("<b>"));
+        assert!(text.contains("&lt;b&gt;Bold&lt;/b&gt;"));
+    }
+
+    #[tokio::test]
+    async fn search_treats_wildcards_literally() {
+        let (mut state, user) = get_test_state_and_user();
+        create_categories(&mut state, &user, &["100% Fun", "Food"]);
+
+        let text = get_body_text(search(state, &user, "%").await).await;
+
+        assert!(!text.contains("data-category-id"));
+    }
+
+    #[tokio::test]
+    async fn suggestions_come_from_similar_past_transactions() {
+        let (mut state, user) = get_test_state_and_user();
+        let coffee = state
+            .category_store()
+            .create(CategoryName::new_unchecked("<i>Coffee</i>"), user.id())
+            .unwrap();
+        create_categories(&mut state, &user, &["Rent"]);
+        state
+            .transaction_store()
+            .create_from_builder(
+                Transaction::build(-5.0, user.id())
+                    .description("BUCKSTARS Wellington".to_string())
+                    .category(Some(coffee.id())),
+            )
+            .unwrap();
+
+        let response = suggest_categories(
+            State(state),
+            Extension(user.id()),
+            Query(CategorySuggestionQuery {
+                description: "buckstars".to_string(),
+            }),
+        )
+        .await
+        .into_response();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let text = get_body_text(response).await;
+        assert!(text.contains(&format!("data-category-id=\"{}\"", coffee.id())));
+        assert!(text.contains("&lt;i&gt;Coffee&lt;/i&gt;"), "got {text}");
+        assert!(!text.contains("<i>"));
+        assert!(!text.contains("Rent"));
+    }
+}
@@ -0,0 +1,439 @@
+//! The page for logged in users to change their password.
+
+use askama_axum::Template;
+use axum::{
+    extract::State,
+    response::{IntoResponse, Response},
+    Extension, Form,
+};
+use axum_extra::extract::PrivateCookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auth::cookie::set_auth_cookie,
+    models::{PasswordHash, UserID, ValidatedPassword},
+    routes::get_internal_server_error_redirect,
+    stores::{CategoryStore, TransactionStore, UserStore},
+    AppState,
+};
+
+use super::{
+    endpoints,
+    navigation::{get_nav_bar, NavbarTemplate},
+    register::PASSWORD_INPUT_MIN_LENGTH,
+};
+
+#[derive(Template)]
+#[template(path = "views/change_password.html")]
+struct ChangePasswordPageTemplate<'a> {
+    navbar: NavbarTemplate<'a>,
+    form: ChangePasswordFormTemplate<'a>,
+}
+
+/// The change password form with inline error messages.
+///
+/// Empty strings mean that there is no message to display.
+#[derive(Template)]
+#[template(path = "partials/change_password/form.html")]
+struct ChangePasswordFormTemplate<'a> {
+    change_password_route: &'a str,
+    min_length: usize,
+    current_password_error: &'a str,
+    new_password_error: &'a str,
+    confirm_password_error: &'a str,
+    success_message: &'a str,
+}
+
+impl Default for ChangePasswordFormTemplate<'_> {
+    fn default() -> Self {
+        Self {
+            change_password_route: endpoints::CHANGE_PASSWORD,
+            min_length: PASSWORD_INPUT_MIN_LENGTH,
+            current_password_error: "",
+            new_password_error: "",
+            confirm_password_error: "",
+            success_message: "",
+        }
+    }
+}
+
+/// Display the change password page.
+pub async fn get_change_password_page<C, T, U>(State(state): State<AppState<C, T, U>>) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    ChangePasswordPageTemplate {
+        navbar: get_nav_bar(endpoints::SETTINGS, state.demo_mode),
+        form: ChangePasswordFormTemplate::default(),
+    }
+    .into_response()
+}
+
+/// The form data for changing a user's password.
+#[derive(Serialize, Deserialize)]
+pub struct ChangePasswordForm {
+    /// The user's password before the change.
+    pub current_password: String,
+    /// The password to change to.
+    pub new_password: String,
+    /// The new password entered again to catch typos.
+    pub confirm_password: String,
+}
+
+/// Change the logged in user's password.
+///
+/// Responds with the change password form, with inline error messages if the password could not
+/// be changed. On success, the user's other sessions are logged out and the auth cookie for the
+/// current session is replaced with a new one. The password cannot be changed in demo mode, so
+/// that visitors cannot lock everyone else out of the demo account.
+pub async fn change_password<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    jar: PrivateCookieJar,
+    Form(form): Form<ChangePasswordForm>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    if state.demo_mode {
+        return ChangePasswordFormTemplate {
+            current_password_error: "Passwords cannot be changed in the demo",
+            ..Default::default()
+        }
+        .into_response();
+    }
+
+    let user = match state.user_store().get(user_id) {
+        Ok(user) => user,
+        Err(error) => {
+            tracing::error!("Could not get user {user_id}: {error}");
+            return get_internal_server_error_redirect();
+        }
+    };
+
+    match user.password_hash().verify(&form.current_password) {
+        Ok(true) => {}
+        Ok(false) => {
+            return ChangePasswordFormTemplate {
+                current_password_error: "Incorrect password",
+                ..Default::default()
+            }
+            .into_response()
+        }
+        Err(error) => {
+            tracing::error!("Error verifying password: {error}");
+            return get_internal_server_error_redirect();
+        }
+    }
+
+    let validated_password = match ValidatedPassword::new(&form.new_password) {
+        Ok(password) => password,
+        Err(error) => {
+            return ChangePasswordFormTemplate {
+                new_password_error: &error.to_string(),
+                ..Default::default()
+            }
+            .into_response()
+        }
+    };
+
+    if form.new_password != form.confirm_password {
+        return ChangePasswordFormTemplate {
+            confirm_password_error: "Passwords do not match",
+            ..Default::default()
+        }
+        .into_response();
+    }
+
+    let password_hash = match PasswordHash::new(validated_password, state.password_hash_cost) {
+        Ok(hash) => hash,
+        Err(error) => {
+            tracing::error!("An error occurred while hashing a password: {error}");
+            return get_internal_server_error_redirect();
+        }
+    };
+
+    // Changing the password logs out all other sessions, so the current session needs a new
+    // cookie with the updated session generation.
+    let user = match state.user_store().set_password(user_id, password_hash) {
+        Ok(user) => user,
+        Err(error) => {
+            tracing::error!("Could not set the password for user {user_id}: {error}");
+            return get_internal_server_error_redirect();
+        }
+    };
+
+    let jar = match set_auth_cookie(
+        jar,
+        user_id,
+        user.session_generation(),
+        state.cookie_duration,
+    ) {
+        Ok(jar) => jar,
+        Err(error) => {
+            tracing::error!("An error occurred while setting the auth cookie: {error}");
+            return get_internal_server_error_redirect();
+        }
+    };
+
+    (
+        jar,
+        ChangePasswordFormTemplate {
+            success_message: "Your password has been changed.",
+            ..Default::default()
+        },
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod change_password_tests {
+    use axum::{
+        middleware,
+        routing::{get, post},
+        Router,
+    };
+    use axum_test::TestServer;
+    use rusqlite::Connection;
+
+    use crate::{
+        auth::{cookie::COOKIE_USER_ID, log_in::LogInData, middleware::auth_guard},
+        models::{PasswordHash, User, ValidatedPassword},
+        routes::{endpoints, log_in::post_log_in},
+        stores::{
+            sql_store::{create_app_state, SQLAppState},
+            UserStore,
+        },
+    };
+
+    use super::{change_password, get_change_password_page, ChangePasswordForm};
+
+    const PASSWORD: &str = "averysafeandsecurepassword";
+    const NEW_PASSWORD: &str = "anotherverysafeandsecurepassword";
+
+    fn get_test_state_and_user() -> (SQLAppState, User) {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        state.password_hash_cost = 4;
+
+        let user = state
+            .user_store()
+            .create(
+                "test@test.com".parse().unwrap(),
+                PasswordHash::new(ValidatedPassword::new_unchecked(PASSWORD), 4).unwrap(),
+            )
+            .unwrap();
+
+        (state, user)
+    }
+
+    fn get_test_server(state: SQLAppState) -> TestServer {
+        let app = Router::new()
+            .route(endpoints::CHANGE_PASSWORD, get(get_change_password_page))
+            .route(endpoints::CHANGE_PASSWORD, post(change_password))
+            .layer(middleware::from_fn_with_state(state.clone(), auth_guard))
+            .route(endpoints::LOG_IN, post(post_log_in))
+            .with_state(state);
+
+        TestServer::new(app).expect("Could not create test server.")
+    }
+
+    fn get_test_state_server_and_user() -> (SQLAppState, TestServer, User) {
+        let (state, user) = get_test_state_and_user();
+        let server = get_test_server(state.clone());
+
+        (state, server, user)
+    }
+
+    async fn log_in(server: &TestServer) -> axum_test::TestResponse {
+        server
+            .post(endpoints::LOG_IN)
+            .form(&LogInData {
+                email: "test@test.com".to_string(),
+                password: PASSWORD.to_string(),
+                remember_me: None,
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn change_password_page_displays_form() {
+        let (_, server, _) = get_test_state_server_and_user();
+        let jar = log_in(&server).await.cookies();
+
+        let response = server
+            .get(endpoints::CHANGE_PASSWORD)
+            .add_cookies(jar)
+            .await;
+
+        response.assert_status_ok();
+        assert!(response.text().contains("current_password"));
+    }
+
+    #[tokio::test]
+    async fn change_password_succeeds() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let jar = log_in(&server).await.cookies();
+
+        let response = server
+            .post(endpoints::CHANGE_PASSWORD)
+            .add_cookies(jar)
+            .form(&ChangePasswordForm {
+                current_password: PASSWORD.to_string(),
+                new_password: NEW_PASSWORD.to_string(),
+                confirm_password: NEW_PASSWORD.to_string(),
+            })
+            .await;
+
+        response.assert_status_ok();
+        assert!(response.text().contains("Your password has been changed."));
+        assert!(
+            response.cookies().get(COOKIE_USER_ID).is_some(),
+            "expected the auth cookie to be replaced"
+        );
+
+        let password_hash = state
+            .user_store()
+            .get(user.id())
+            .unwrap()
+            .password_hash()
+            .to_owned();
+        assert!(password_hash.verify(NEW_PASSWORD).unwrap());
+        assert!(!password_hash.verify(PASSWORD).unwrap());
+    }
+
+    #[tokio::test]
+    async fn change_password_logs_out_other_sessions() {
+        let (_, server, _) = get_test_state_server_and_user();
+        let this_session = log_in(&server).await.cookies();
+        let other_session = log_in(&server).await.cookies();
+
+        let response = server
+            .post(endpoints::CHANGE_PASSWORD)
+            .add_cookies(this_session)
+            .form(&ChangePasswordForm {
+                current_password: PASSWORD.to_string(),
+                new_password: NEW_PASSWORD.to_string(),
+                confirm_password: NEW_PASSWORD.to_string(),
+            })
+            .await;
+        response.assert_status_ok();
+        let this_session = response.cookies();
+
+        server
+            .get(endpoints::CHANGE_PASSWORD)
+            .add_cookies(this_session)
+            .await
+            .assert_status_ok();
+        server
+            .get(endpoints::CHANGE_PASSWORD)
+            .add_cookies(other_session)
+            .await
+            .assert_status_see_other();
+    }
+
+    #[tokio::test]
+    async fn change_password_fails_with_wrong_current_password() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let jar = log_in(&server).await.cookies();
+
+        let response = server
+            .post(endpoints::CHANGE_PASSWORD)
+            .add_cookies(jar)
+            .form(&ChangePasswordForm {
+                current_password: "wrongpassword".to_string(),
+                new_password: NEW_PASSWORD.to_string(),
+                confirm_password: NEW_PASSWORD.to_string(),
+            })
+            .await;
+
+        response.assert_status_ok();
+        assert!(response.text().contains("Incorrect password"));
+        assert!(state
+            .user_store()
+            .get(user.id())
+            .unwrap()
+            .password_hash()
+            .verify(PASSWORD)
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn change_password_fails_with_mismatched_confirmation() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let jar = log_in(&server).await.cookies();
+
+        let response = server
+            .post(endpoints::CHANGE_PASSWORD)
+            .add_cookies(jar)
+            .form(&ChangePasswordForm {
+                current_password: PASSWORD.to_string(),
+                new_password: NEW_PASSWORD.to_string(),
+                confirm_password: "somethingelseentirely".to_string(),
+            })
+            .await;
+
+        response.assert_status_ok();
+        assert!(response.text().contains("Passwords do not match"));
+        assert!(state
+            .user_store()
+            .get(user.id())
+            .unwrap()
+            .password_hash()
+            .verify(PASSWORD)
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn change_password_fails_with_weak_password() {
+        let (_, server, _) = get_test_state_server_and_user();
+        let jar = log_in(&server).await.cookies();
+
+        let response = server
+            .post(endpoints::CHANGE_PASSWORD)
+            .add_cookies(jar)
+            .form(&ChangePasswordForm {
+                current_password: PASSWORD.to_string(),
+                new_password: "password".to_string(),
+                confirm_password: "password".to_string(),
+            })
+            .await;
+
+        response.assert_status_ok();
+        assert!(!response.text().contains("Your password has been changed."));
+        assert!(response.text().contains("text-red-500"));
+    }
+
+    #[tokio::test]
+    async fn change_password_fails_in_demo_mode() {
+        let (mut state, user) = get_test_state_and_user();
+        state.demo_mode = true;
+        let server = get_test_server(state.clone());
+        let jar = log_in(&server).await.cookies();
+
+        let response = server
+            .post(endpoints::CHANGE_PASSWORD)
+            .add_cookies(jar)
+            .form(&ChangePasswordForm {
+                current_password: PASSWORD.to_string(),
+                new_password: NEW_PASSWORD.to_string(),
+                confirm_password: NEW_PASSWORD.to_string(),
+            })
+            .await;
+
+        response.assert_status_ok();
+        assert!(response
+            .text()
+            .contains("Passwords cannot be changed in the demo"));
+        assert!(state
+            .user_store()
+            .get(user.id())
+            .unwrap()
+            .password_hash()
+            .verify(PASSWORD)
+            .unwrap());
+    }
+}
@@ -0,0 +1,501 @@
+//! Downloading a user's configuration, i.e. their categories and preferences, as a JSON file and
+//! uploading it to another instance.
+//!
+//! The configuration does not include transactions, so it can be used to set up a new instance
+//! without copying the whole database.
+
+use askama_axum::Template;
+use axum::{
+    extract::{Multipart, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    models::{CategoryError, CategoryName, LandingPage, Theme, UserID, WeekStart},
+    routes::get_internal_server_error_redirect,
+    stores::{CategoryStore, TransactionStore, UserStore},
+    timezone::get_timezone,
+    AppError, AppState,
+};
+
+/// The version of the configuration file format. Files with a newer version are rejected.
+pub const CONFIGURATION_VERSION: u32 = 1;
+
+/// The name of the downloaded configuration file.
+const CONFIGURATION_FILE_NAME: &str = "budgeteur-configuration.json";
+
+/// The name of the form field holding the uploaded configuration file.
+const CONFIGURATION_FIELD: &str = "configuration";
+
+/// A user's categories and preferences.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Configuration {
+    /// The version of the file format, see [CONFIGURATION_VERSION].
+    pub version: u32,
+    /// The user's categories.
+    #[serde(default)]
+    pub categories: Vec<CategoryConfiguration>,
+    /// The user's preferences, if they should be copied.
+    pub preferences: Option<PreferencesConfiguration>,
+}
+
+/// A category in a [Configuration].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CategoryConfiguration {
+    /// The name of the category. Categories are matched by name, ignoring case.
+    pub name: String,
+}
+
+/// The preferences in a [Configuration].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PreferencesConfiguration {
+    /// The name of the user's timezone, e.g. "Pacific/Auckland".
+    pub timezone: String,
+    /// Whether the user sees light or dark pages.
+    pub theme: Theme,
+    /// The page the user is sent to after logging in. Files from before this was added use the
+    /// dashboard.
+    #[serde(default)]
+    pub landing_page: LandingPage,
+    /// The day that the user's weeks start on. Files from before this was added use Monday.
+    #[serde(default)]
+    pub week_start: WeekStart,
+    /// The day of the month that the user's months start on. Files from before this was added
+    /// use the first.
+    #[serde(default = "default_month_start_day")]
+    pub month_start_day: u8,
+}
+
+/// The month start day for files from before it was added, i.e. calendar months.
+fn default_month_start_day() -> u8 {
+    1
+}
+
+/// The number of items of one kind that were created and skipped by an upload.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct ImportCounts {
+    created: usize,
+    skipped: usize,
+}
+
+/// The message shown after uploading a configuration file.
+///
+/// An empty string means that there is no message to display.
+#[derive(Template)]
+#[template(path = "partials/settings/configuration_result.html")]
+struct ConfigurationResultTemplate<'a> {
+    message: &'a str,
+    error_message: &'a str,
+}
+
+impl<'a> ConfigurationResultTemplate<'a> {
+    fn error(error_message: &'a str) -> Response {
+        Self {
+            message: "",
+            error_message,
+        }
+        .into_response()
+    }
+}
+
+/// Respond with the logged in user's categories and preferences as a JSON file download.
+pub async fn get_configuration<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let categories = match state.category_store().get_by_user(user_id) {
+        Ok(categories) => categories,
+        Err(error) => return AppError::CategoryError(error).into_response(),
+    };
+    let preferences = match state.user_store().get_preferences(user_id) {
+        Ok(preferences) => preferences,
+        Err(error) => {
+            tracing::error!("Could not get the preferences for user {user_id}: {error}");
+            return get_internal_server_error_redirect();
+        }
+    };
+
+    let configuration = Configuration {
+        version: CONFIGURATION_VERSION,
+        categories: categories
+            .iter()
+            .map(|category| CategoryConfiguration {
+                name: category.name().as_ref().to_string(),
+            })
+            .collect(),
+        preferences: Some(PreferencesConfiguration {
+            timezone: preferences.timezone,
+            theme: preferences.theme,
+            landing_page: preferences.landing_page,
+            week_start: preferences.week_start,
+            month_start_day: preferences.month_start_day,
+        }),
+    };
+
+    (
+        [(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{CONFIGURATION_FILE_NAME}\""),
+        )],
+        Json(configuration),
+    )
+        .into_response()
+}
+
+/// Merge an uploaded configuration file into the logged in user's configuration.
+///
+/// Categories are created unless the user already has one with the same name, ignoring case.
+/// The preferences are replaced unless the timezone is unknown. Responds with the number of
+/// categories and preferences that were created and skipped.
+pub async fn upload_configuration<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    mut multipart: Multipart,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let mut file = None;
+
+    loop {
+        match multipart.next_field().await {
+            Ok(Some(field)) if field.name() == Some(CONFIGURATION_FIELD) => {
+                match field.bytes().await {
+                    Ok(bytes) => file = Some(bytes),
+                    Err(error) => return ConfigurationResultTemplate::error(&error.body_text()),
+                }
+            }
+            Ok(Some(_)) => {}
+            Ok(None) => break,
+            Err(error) => return ConfigurationResultTemplate::error(&error.body_text()),
+        }
+    }
+
+    let Some(file) = file else {
+        return ConfigurationResultTemplate::error("Choose a configuration file to upload.");
+    };
+
+    let configuration: Configuration = match serde_json::from_slice(&file) {
+        Ok(configuration) => configuration,
+        Err(error) => {
+            return ConfigurationResultTemplate::error(&format!(
+                "The file is not a valid configuration file: {error}"
+            ))
+        }
+    };
+
+    if configuration.version > CONFIGURATION_VERSION {
+        return ConfigurationResultTemplate::error(&format!(
+            "The file is from a newer version of Budgeteur (version {}), so it cannot be uploaded.",
+            configuration.version
+        ));
+    }
+
+    let categories = match import_categories(&state, user_id, &configuration.categories) {
+        Ok(counts) => counts,
+        Err(error) => return AppError::CategoryError(error).into_response(),
+    };
+
+    let mut preferences = ImportCounts::default();
+
+    if let Some(preferences_configuration) = &configuration.preferences {
+        if get_timezone(&preferences_configuration.timezone).is_none() {
+            preferences.skipped += 1;
+        } else {
+            let result = state
+                .user_store()
+                .set_timezone(user_id, &preferences_configuration.timezone)
+                .and_then(|_| {
+                    state
+                        .user_store()
+                        .set_theme(user_id, preferences_configuration.theme)
+                })
+                .and_then(|_| {
+                    state
+                        .user_store()
+                        .set_landing_page(user_id, preferences_configuration.landing_page)
+                })
+                .and_then(|_| {
+                    state.user_store().set_date_periods(
+                        user_id,
+                        preferences_configuration.week_start,
+                        preferences_configuration.month_start_day,
+                    )
+                });
+
+            if let Err(error) = result {
+                tracing::error!("Could not set the preferences for user {user_id}: {error}");
+                return get_internal_server_error_redirect();
+            }
+
+            preferences.created += 1;
+        }
+    }
+
+    ConfigurationResultTemplate {
+        message: &format!(
+            "Categories: {} created, {} skipped. Preferences: {} updated, {} skipped.",
+            categories.created, categories.skipped, preferences.created, preferences.skipped
+        ),
+        error_message: "",
+    }
+    .into_response()
+}
+
+/// Create the categories in `categories` that the user does not already have.
+///
+/// Categories with invalid names are skipped along with existing ones.
+fn import_categories<C, T, U>(
+    state: &AppState<C, T, U>,
+    user_id: UserID,
+    categories: &[CategoryConfiguration],
+) -> Result<ImportCounts, CategoryError>
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let mut counts = ImportCounts::default();
+
+    for category in categories {
+        let Ok(name) = CategoryName::new(&category.name) else {
+            counts.skipped += 1;
+            continue;
+        };
+
+        match state.category_store().create(name, user_id) {
+            Ok(_) => counts.created += 1,
+            Err(CategoryError::DuplicateName) => counts.skipped += 1,
+            Err(error) => return Err(error),
+        }
+    }
+
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod configuration_tests {
+    use axum::{
+        http::header,
+        middleware,
+        routing::{get, post},
+        Router,
+    };
+    use axum_test::{
+        multipart::{MultipartForm, Part},
+        TestServer,
+    };
+    use email_address::EmailAddress;
+    use rusqlite::Connection;
+
+    use crate::{
+        auth::{log_in::LogInData, middleware::auth_guard},
+        models::{
+            CategoryName, LandingPage, PasswordHash, Theme, User, ValidatedPassword, WeekStart,
+        },
+        routes::{endpoints, log_in::post_log_in},
+        stores::{
+            sql_store::{create_app_state, SQLAppState},
+            CategoryStore, UserStore,
+        },
+    };
+
+    use super::{
+        get_configuration, upload_configuration, CategoryConfiguration, Configuration,
+        PreferencesConfiguration, CONFIGURATION_VERSION,
+    };
+
+    const PASSWORD: &str = "averysafeandsecurepassword";
+
+    fn get_test_state_and_user() -> (SQLAppState, User) {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        let user = state
+            .user_store()
+            .create(
+                EmailAddress::new_unchecked("foo@bar.baz"),
+                PasswordHash::new(ValidatedPassword::new_unchecked(PASSWORD), 4).unwrap(),
+            )
+            .unwrap();
+
+        (state, user)
+    }
+
+    async fn get_server(state: SQLAppState, user: &User) -> TestServer {
+        let app = Router::new()
+            .route(
+                endpoints::CONFIGURATION,
+                get(get_configuration).post(upload_configuration),
+            )
+            .layer(middleware::from_fn_with_state(state.clone(), auth_guard))
+            .route(endpoints::LOG_IN, post(post_log_in))
+            .with_state(state);
+        let mut server = TestServer::new(app).unwrap();
+        server.do_save_cookies();
+        server
+            .post(endpoints::LOG_IN)
+            .form(&LogInData {
+                email: user.email().to_string(),
+                password: PASSWORD.to_string(),
+                remember_me: None,
+            })
+            .await
+            .assert_status_see_other();
+
+        server
+    }
+
+    fn get_upload_form(configuration: &str) -> MultipartForm {
+        MultipartForm::new().add_part(
+            "configuration",
+            Part::text(configuration.to_string()).mime_type("application/json"),
+        )
+    }
+
+    #[tokio::test]
+    async fn downloads_categories_and_preferences() {
+        let (mut state, user) = get_test_state_and_user();
+        state
+            .category_store()
+            .create(CategoryName::new_unchecked("Food"), user.id())
+            .unwrap();
+        state
+            .user_store()
+            .set_timezone(user.id(), "Pacific/Auckland")
+            .unwrap();
+        let server = get_server(state, &user).await;
+
+        let response = server.get(endpoints::CONFIGURATION).await;
+
+        response.assert_status_ok();
+        assert!(response
+            .header(header::CONTENT_DISPOSITION)
+            .to_str()
+            .unwrap()
+            .starts_with("attachment"));
+        assert_eq!(
+            response.json::<Configuration>(),
+            Configuration {
+                version: CONFIGURATION_VERSION,
+                categories: vec![CategoryConfiguration {
+                    name: "Food".to_string()
+                }],
+                preferences: Some(PreferencesConfiguration {
+                    timezone: "Pacific/Auckland".to_string(),
+                    theme: Theme::System,
+                    landing_page: LandingPage::Dashboard,
+                    week_start: WeekStart::Monday,
+                    month_start_day: 1,
+                }),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn upload_merges_categories_and_preferences() {
+        let (mut state, user) = get_test_state_and_user();
+        state
+            .category_store()
+            .create(CategoryName::new_unchecked("Food"), user.id())
+            .unwrap();
+        let server = get_server(state.clone(), &user).await;
+        let configuration = serde_json::to_string(&Configuration {
+            version: CONFIGURATION_VERSION,
+            categories: ["food", "Rent", "Fun", " "]
+                .into_iter()
+                .map(|name| CategoryConfiguration {
+                    name: name.to_string(),
+                })
+                .collect(),
+            preferences: Some(PreferencesConfiguration {
+                timezone: "Pacific/Auckland".to_string(),
+                theme: Theme::Dark,
+                landing_page: LandingPage::Transactions,
+                week_start: WeekStart::Sunday,
+                month_start_day: 15,
+            }),
+        })
+        .unwrap();
+
+        let response = server
+            .post(endpoints::CONFIGURATION)
+            .multipart(get_upload_form(&configuration))
+            .await;
+
+        response.assert_status_ok();
+        response.assert_text_contains(
+            "Categories: 2 created, 2 skipped. Preferences: 1 updated, 0 skipped.",
+        );
+        let mut names: Vec<_> = state
+            .category_store()
+            .get_by_user(user.id())
+            .unwrap()
+            .iter()
+            .map(|category| category.name().as_ref().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["Food", "Fun", "Rent"]);
+        let preferences = state.user_store().get_preferences(user.id()).unwrap();
+        assert_eq!(preferences.timezone, "Pacific/Auckland");
+        assert_eq!(preferences.theme, Theme::Dark);
+        assert_eq!(preferences.landing_page, LandingPage::Transactions);
+        assert_eq!(preferences.week_start, WeekStart::Sunday);
+        assert_eq!(preferences.month_start_day, 15);
+    }
+
+    #[tokio::test]
+    async fn upload_skips_unknown_timezone() {
+        let (mut state, user) = get_test_state_and_user();
+        let server = get_server(state.clone(), &user).await;
+        let configuration = r#"{
+            "version": 1,
+            "preferences": { "timezone": "Mars/Olympus_Mons", "theme": "dark" }
+        }"#;
+
+        let response = server
+            .post(endpoints::CONFIGURATION)
+            .multipart(get_upload_form(configuration))
+            .await;
+
+        response.assert_status_ok();
+        response.assert_text_contains("Preferences: 0 updated, 1 skipped.");
+        let preferences = state.user_store().get_preferences(user.id()).unwrap();
+        assert_eq!(preferences.theme, Theme::System);
+    }
+
+    #[tokio::test]
+    async fn upload_rejects_invalid_files() {
+        let (state, user) = get_test_state_and_user();
+        let server = get_server(state, &user).await;
+        let cases = [
+            (
+                get_upload_form("not json"),
+                "not a valid configuration file",
+            ),
+            (
+                get_upload_form(r#"{ "version": 2 }"#),
+                "newer version of Budgeteur",
+            ),
+            (
+                MultipartForm::new().add_text("notes", "no file"),
+                "Choose a configuration file",
+            ),
+        ];
+
+        for (form, want_error) in cases {
+            let response = server.post(endpoints::CONFIGURATION).multipart(form).await;
+
+            response.assert_status_ok();
+            response.assert_text_contains(want_error);
+        }
+    }
+}
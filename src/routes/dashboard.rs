@@ -3,18 +3,23 @@
 use super::{
     endpoints::{self},
     navigation::{get_nav_bar, NavbarTemplate},
+    templates::render,
+    transactions::TransactionsPageQuery,
 };
 use askama_axum::Template;
 use axum::{
     extract::State,
+    http::StatusCode,
     response::{IntoResponse, Response},
     Extension,
 };
-use time::{Duration, OffsetDateTime};
 
 use crate::{
+    aggregation::{last_week, totals, uncategorised, Uncategorised},
+    filters,
     models::UserID,
     stores::{transaction::TransactionQuery, CategoryStore, TransactionStore, UserStore},
+    timezone::get_user_today,
     AppError, AppState,
 };
 
@@ -26,6 +31,20 @@ struct DashboardTemplate<'a> {
     user_id: UserID,
     /// How much over or under budget the user is for this week.
     balance: f64,
+    /// The link to the transactions that make up `balance`.
+    balance_transactions_href: String,
+    /// The transactions from this week that do not have a category.
+    uncategorised: Uncategorised,
+    /// The link to this week's transactions that do not have a category.
+    uncategorised_transactions_href: String,
+    /// The link to the printable weekly summary.
+    weekly_summary_route: &'a str,
+    /// The link to the printable report for the current month.
+    monthly_report_route: &'a str,
+    /// The number of groups of possible duplicate transactions.
+    duplicate_group_count: usize,
+    /// The link to the page for merging duplicate transactions.
+    duplicates_route: &'a str,
 }
 
 /// Display a page with an overview of the user's data.
@@ -38,40 +57,53 @@ where
     T: TransactionStore + Send + Sync,
     U: UserStore + Send + Sync,
 {
-    let navbar = get_nav_bar(endpoints::DASHBOARD);
+    let navbar = get_nav_bar(endpoints::DASHBOARD, state.demo_mode);
 
-    let today = OffsetDateTime::now_utc().date();
-    let one_week_ago = match today.checked_sub(Duration::weeks(1)) {
-        Some(date) => date,
-        None => {
-            tracing::warn!(
-                "Could not get date for one week before {today}. Using today's date ({today}) instead."
-            );
-
-            today
-        }
+    let today = get_user_today(state.user_store(), user_id);
+    let date_range = last_week(today);
+    let balance_transactions_query = TransactionsPageQuery {
+        from: Some(*date_range.start()),
+        to: Some(*date_range.end()),
+        ..Default::default()
     };
+    let uncategorised_transactions_href = TransactionsPageQuery {
+        uncategorised_only: true,
+        ..balance_transactions_query.clone()
+    }
+    .href();
+    let balance_transactions_href = balance_transactions_query.href();
 
     let transactions = state.transaction_store().get_query(TransactionQuery {
         user_id: Some(user_id),
-        date_range: Some(one_week_ago..=today),
+        date_range: Some(date_range),
         ..Default::default()
     });
 
-    let balance = match transactions {
-        Ok(transactions) => transactions
-            .iter()
-            .map(|transaction| transaction.amount())
-            .sum(),
+    let transactions = match transactions {
+        Ok(transactions) => transactions,
         Err(error) => return AppError::TransactionError(error).into_response(),
     };
 
-    DashboardTemplate {
-        navbar,
-        user_id,
-        balance,
-    }
-    .into_response()
+    let duplicate_group_count = match state.transaction_store().get_duplicates(user_id) {
+        Ok(groups) => groups.len(),
+        Err(error) => return AppError::TransactionError(error).into_response(),
+    };
+
+    render(
+        StatusCode::OK,
+        &DashboardTemplate {
+            navbar,
+            user_id,
+            balance: totals(&transactions).net,
+            balance_transactions_href,
+            uncategorised: uncategorised(&transactions),
+            uncategorised_transactions_href,
+            weekly_summary_route: endpoints::WEEKLY_SUMMARY,
+            monthly_report_route: endpoints::MONTHLY_REPORT,
+            duplicate_group_count,
+            duplicates_route: endpoints::DUPLICATES,
+        },
+    )
 }
 
 #[cfg(test)]
@@ -90,11 +122,14 @@ mod dashboard_route_tests {
             TransactionBuilder, TransactionError, User, UserID,
         },
         stores::{
-            transaction::TransactionQuery, CategoryStore, TransactionStore, UserError, UserStore,
+            transaction::{BatchOutcome, IgnoredFilter, NewTransaction, TransactionQuery},
+            CategoryStore, TransactionStore, UserError, UserStore,
         },
         AppState,
     };
 
+    use crate::{aggregation::last_week, routes::transactions::TransactionsPageQuery};
+
     use super::get_dashboard_page;
 
     #[derive(Clone)]
@@ -109,6 +144,15 @@ mod dashboard_route_tests {
             todo!()
         }
 
+        fn create_with_invite(
+            &mut self,
+            _email: email_address::EmailAddress,
+            _password_hash: PasswordHash,
+            _invite_code: &str,
+        ) -> Result<User, UserError> {
+            todo!()
+        }
+
         fn get(&self, _id: UserID) -> Result<User, UserError> {
             todo!()
         }
@@ -116,6 +160,78 @@ mod dashboard_route_tests {
         fn get_by_email(&self, _email: &email_address::EmailAddress) -> Result<User, UserError> {
             todo!()
         }
+
+        fn set_password(
+            &mut self,
+            _id: UserID,
+            _password_hash: PasswordHash,
+        ) -> Result<User, UserError> {
+            todo!()
+        }
+
+        fn increment_session_generation(&mut self, _id: UserID) -> Result<User, UserError> {
+            todo!()
+        }
+
+        fn update_password_hash(
+            &mut self,
+            _id: UserID,
+            _password_hash: PasswordHash,
+        ) -> Result<(), UserError> {
+            todo!()
+        }
+
+        fn set_email(
+            &mut self,
+            _id: UserID,
+            _email: email_address::EmailAddress,
+        ) -> Result<User, UserError> {
+            todo!()
+        }
+
+        fn delete(&mut self, _id: UserID) -> Result<(), UserError> {
+            todo!()
+        }
+
+        fn get_preferences(
+            &self,
+            _user_id: UserID,
+        ) -> Result<crate::models::UserPreferences, UserError> {
+            Ok(crate::models::UserPreferences::default())
+        }
+
+        fn set_timezone(
+            &mut self,
+            _user_id: UserID,
+            _timezone: &str,
+        ) -> Result<crate::models::UserPreferences, UserError> {
+            todo!()
+        }
+
+        fn set_theme(
+            &mut self,
+            _user_id: UserID,
+            _theme: crate::models::Theme,
+        ) -> Result<crate::models::UserPreferences, UserError> {
+            todo!()
+        }
+
+        fn set_landing_page(
+            &mut self,
+            _user_id: UserID,
+            _landing_page: crate::models::LandingPage,
+        ) -> Result<crate::models::UserPreferences, crate::stores::UserError> {
+            todo!()
+        }
+
+        fn set_date_periods(
+            &mut self,
+            _user_id: UserID,
+            _week_start: crate::models::WeekStart,
+            _month_start_day: u8,
+        ) -> Result<crate::models::UserPreferences, crate::stores::UserError> {
+            todo!()
+        }
     }
 
     #[derive(Clone)]
@@ -133,6 +249,27 @@ mod dashboard_route_tests {
         fn get_by_user(&self, _user_id: UserID) -> Result<Vec<Category>, CategoryError> {
             todo!()
         }
+
+        fn search(
+            &self,
+            _user_id: UserID,
+            _prefix: &str,
+            _limit: u64,
+        ) -> Result<Vec<Category>, CategoryError> {
+            todo!()
+        }
+
+        fn suggest(
+            &self,
+
+            _user_id: UserID,
+
+            _description: &str,
+
+            _limit: u64,
+        ) -> Result<Vec<Category>, CategoryError> {
+            todo!()
+        }
     }
 
     #[derive(Clone)]
@@ -165,6 +302,13 @@ mod dashboard_route_tests {
             Ok(transaction)
         }
 
+        fn create_batch(
+            &mut self,
+            _transactions: Vec<NewTransaction>,
+        ) -> Result<Vec<BatchOutcome>, TransactionError> {
+            todo!()
+        }
+
         fn get(&self, _id: DatabaseID) -> Result<Transaction, TransactionError> {
             todo!()
         }
@@ -191,11 +335,76 @@ mod dashboard_route_tests {
                             && transaction.date() <= date_range.end();
                     }
 
+                    if let Some(category_id) = filter.category_id {
+                        should_keep &= transaction.category_id() == Some(category_id);
+                    }
+
+                    if filter.uncategorised_only {
+                        should_keep &= transaction.category_id().is_none();
+                    }
+
+                    if let Some(reconciled) = filter.reconciled {
+                        should_keep &= transaction.is_reconciled() == reconciled;
+                    }
+
+                    should_keep &= match filter.ignored {
+                        IgnoredFilter::Exclude => !transaction.is_ignored(),
+                        IgnoredFilter::Include => true,
+                        IgnoredFilter::Only => transaction.is_ignored(),
+                    };
+
                     should_keep
                 })
                 .map(|transaction| Ok(transaction.to_owned()))
                 .collect()
         }
+
+        fn count_query(&self, _filter: TransactionQuery) -> Result<u64, TransactionError> {
+            todo!()
+        }
+
+        fn sum_query(&self, _filter: TransactionQuery) -> Result<f64, TransactionError> {
+            todo!()
+        }
+
+        fn set_ignored(
+            &mut self,
+            _id: DatabaseID,
+            _ignored: bool,
+        ) -> Result<Transaction, TransactionError> {
+            todo!()
+        }
+
+        fn set_reconciled(&mut self, _ids: &[DatabaseID]) -> Result<(), TransactionError> {
+            todo!()
+        }
+
+        fn update(
+            &mut self,
+            _id: DatabaseID,
+            _builder: TransactionBuilder,
+        ) -> Result<Transaction, TransactionError> {
+            todo!()
+        }
+
+        fn delete(&mut self, _id: DatabaseID) -> Result<(), TransactionError> {
+            todo!()
+        }
+
+        fn get_duplicates(
+            &self,
+            _user_id: UserID,
+        ) -> Result<Vec<Vec<Transaction>>, TransactionError> {
+            Ok(Vec::new())
+        }
+
+        fn merge_duplicates(
+            &mut self,
+            _keep_id: DatabaseID,
+            _duplicate_ids: &[DatabaseID],
+        ) -> Result<Transaction, TransactionError> {
+            todo!()
+        }
     }
 
     #[tokio::test]
@@ -232,6 +441,103 @@ mod dashboard_route_tests {
         assert_body_contains_amount(response, "$123").await;
     }
 
+    #[tokio::test]
+    async fn dashboard_balance_excludes_ignored_transactions() {
+        let user_id = UserID::new(321);
+        let transactions = vec![
+            Transaction::build(123.0, user_id).finalise(1),
+            Transaction::build(1000.0, user_id)
+                .ignored(true)
+                .finalise(2),
+        ];
+        let state = AppState::new(
+            "123",
+            DummyCategoryStore {},
+            FakeTransactionStore { transactions },
+            DummyUserStore {},
+        );
+
+        let response = get_dashboard_page(State(state), Extension(user_id)).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_body_contains_amount(response, "$123").await;
+    }
+
+    #[tokio::test]
+    async fn dashboard_links_to_balance_transactions() {
+        let user_id = UserID::new(321);
+        let state = AppState::new(
+            "123",
+            DummyCategoryStore {},
+            FakeTransactionStore {
+                transactions: vec![],
+            },
+            DummyUserStore {},
+        );
+        let date_range = last_week(OffsetDateTime::now_utc().date());
+        let want = TransactionsPageQuery {
+            from: Some(*date_range.start()),
+            to: Some(*date_range.end()),
+            ..Default::default()
+        }
+        .href()
+        // The link is HTML escaped in the page.
+        .replace('&', "&amp;");
+
+        let response = get_dashboard_page(State(state), Extension(user_id)).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_body_contains_amount(response, &want).await;
+    }
+
+    #[tokio::test]
+    async fn dashboard_displays_uncategorised_transactions() {
+        let user_id = UserID::new(321);
+        let transactions = vec![
+            Transaction::build(-12.5, user_id).finalise(1),
+            Transaction::build(-30.0, user_id).finalise(2),
+            Transaction::build(-99.0, user_id)
+                .category(Some(1))
+                .finalise(3),
+        ];
+        let state = AppState::new(
+            "123",
+            DummyCategoryStore {},
+            FakeTransactionStore { transactions },
+            DummyUserStore {},
+        );
+
+        let response = get_dashboard_page(State(state), Extension(user_id)).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let text = get_body_text(response).await;
+        assert!(text.contains("2 uncategorised"), "got {text}");
+        assert!(text.contains("-$42.50"), "got {text}");
+        assert!(text.contains("uncategorised_only=true"), "got {text}");
+        assert!(!text.contains("All categorised"), "got {text}");
+    }
+
+    #[tokio::test]
+    async fn dashboard_displays_all_categorised() {
+        let user_id = UserID::new(321);
+        let transactions = vec![Transaction::build(-99.0, user_id)
+            .category(Some(1))
+            .finalise(1)];
+        let state = AppState::new(
+            "123",
+            DummyCategoryStore {},
+            FakeTransactionStore { transactions },
+            DummyUserStore {},
+        );
+
+        let response = get_dashboard_page(State(state), Extension(user_id)).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let text = get_body_text(response).await;
+        assert!(text.contains("All categorised 🎉"), "got {text}");
+        assert!(!text.contains("uncategorised_only=true"), "got {text}");
+    }
+
     #[tokio::test]
     async fn dashboard_displays_negative_balance_without_sign() {
         let user_id = UserID::new(321);
@@ -249,11 +555,15 @@ mod dashboard_route_tests {
         assert_body_contains_amount(response, "$123").await;
     }
 
-    async fn assert_body_contains_amount(response: Response<Body>, want: &str) {
+    async fn get_body_text(response: Response<Body>) -> String {
         let body = response.into_body();
         let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
 
-        let text = String::from_utf8_lossy(&body).to_string();
+        String::from_utf8_lossy(&body).to_string()
+    }
+
+    async fn assert_body_contains_amount(response: Response<Body>, want: &str) {
+        let text = get_body_text(response).await;
 
         assert!(
             text.contains(want),
@@ -0,0 +1,252 @@
+//! This file defines the page for finding and merging duplicate transactions, e.g. a transaction
+//! that was entered by hand and then imported again.
+
+use askama_axum::Template;
+use axum::{
+    extract::State,
+    http::{StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use axum_extra::extract::Form;
+use axum_htmx::HxRedirect;
+use serde::Deserialize;
+
+use crate::{
+    filters,
+    models::{DatabaseID, Transaction, UserID},
+    stores::{CategoryStore, TransactionStore, UserStore},
+    AppError, AppState,
+};
+
+use super::{
+    endpoints,
+    navigation::{get_nav_bar, NavbarTemplate},
+};
+
+/// Renders the groups of possible duplicate transactions.
+#[derive(Template)]
+#[template(path = "views/duplicates.html")]
+struct DuplicatesTemplate<'a> {
+    navbar: NavbarTemplate<'a>,
+    /// Groups of transactions with the same date, amount and description, oldest first.
+    groups: Vec<Vec<Transaction>>,
+    merge_route: &'a str,
+}
+
+/// The form data for merging a group of duplicate transactions.
+#[derive(Debug, Deserialize)]
+pub struct MergeDuplicatesForm {
+    /// The transaction to keep.
+    pub keep: DatabaseID,
+    /// The transactions to delete.
+    #[serde(default)]
+    pub remove: Vec<DatabaseID>,
+}
+
+/// Display the user's groups of possible duplicate transactions.
+pub async fn get_duplicates_page<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let groups = match state.transaction_store().get_duplicates(user_id) {
+        Ok(groups) => groups,
+        Err(error) => return AppError::TransactionError(error).into_response(),
+    };
+
+    DuplicatesTemplate {
+        navbar: get_nav_bar(endpoints::DUPLICATES, state.demo_mode),
+        groups,
+        merge_route: endpoints::DUPLICATES_MERGE,
+    }
+    .into_response()
+}
+
+/// Delete the checked transactions in a group of duplicates, keeping the chosen transaction.
+///
+/// Only transactions in the same group as the kept transaction are deleted, which also stops
+/// users from deleting other users' transactions. Redirects back to the duplicates page.
+pub async fn merge_duplicates<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Form(form): Form<MergeDuplicatesForm>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let groups = match state.transaction_store().get_duplicates(user_id) {
+        Ok(groups) => groups,
+        Err(error) => return AppError::TransactionError(error).into_response(),
+    };
+
+    let Some(group) = groups.into_iter().find(|group| {
+        group
+            .iter()
+            .any(|transaction| transaction.id() == form.keep)
+    }) else {
+        return AppError::NotFound.into_response();
+    };
+
+    let duplicate_ids: Vec<DatabaseID> = group
+        .iter()
+        .map(|transaction| transaction.id())
+        .filter(|&id| id != form.keep && form.remove.contains(&id))
+        .collect();
+
+    if let Err(error) = state
+        .transaction_store()
+        .merge_duplicates(form.keep, &duplicate_ids)
+    {
+        return AppError::TransactionError(error).into_response();
+    }
+
+    (
+        HxRedirect(Uri::from_static(endpoints::DUPLICATES)),
+        StatusCode::OK,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod duplicates_route_tests {
+    use axum_htmx::HX_REDIRECT;
+    use axum_test::TestServer;
+    use email_address::EmailAddress;
+    use rusqlite::Connection;
+    use time::{Duration, OffsetDateTime};
+
+    use crate::{
+        auth::log_in::LogInData,
+        build_router,
+        models::{PasswordHash, Transaction, TransactionError, User, ValidatedPassword},
+        routes::endpoints,
+        stores::{
+            sql_store::{create_app_state, SQLAppState},
+            TransactionStore, UserStore,
+        },
+    };
+
+    const PASSWORD: &str = "averysafeandsecurepassword";
+
+    fn create_user(state: &mut SQLAppState, email: &str) -> User {
+        state
+            .user_store()
+            .create(
+                EmailAddress::new_unchecked(email),
+                PasswordHash::new(ValidatedPassword::new_unchecked(PASSWORD), 4).unwrap(),
+            )
+            .unwrap()
+    }
+
+    /// Create two identical transactions for `user` and return them, oldest first.
+    fn create_duplicates(state: &mut SQLAppState, user: &User) -> (Transaction, Transaction) {
+        let yesterday = OffsetDateTime::now_utc().date() - Duration::days(1);
+        let mut create = || {
+            state
+                .transaction_store()
+                .create_from_builder(
+                    Transaction::build(-4.5, user.id())
+                        .date(yesterday)
+                        .unwrap()
+                        .description("FLAT WHITE".to_string()),
+                )
+                .unwrap()
+        };
+
+        (create(), create())
+    }
+
+    async fn get_server(state: SQLAppState, user: &User) -> TestServer {
+        let mut server = TestServer::new(build_router(state)).unwrap();
+        server.do_save_cookies();
+        server
+            .post(endpoints::LOG_IN)
+            .form(&LogInData {
+                email: user.email().to_string(),
+                password: PASSWORD.to_string(),
+                remember_me: None,
+            })
+            .await
+            .assert_status_see_other();
+
+        server
+    }
+
+    #[tokio::test]
+    async fn duplicates_are_listed_and_flagged_on_dashboard() {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        let user = create_user(&mut state, "foo@bar.baz");
+        let (first, second) = create_duplicates(&mut state, &user);
+        let server = get_server(state, &user).await;
+
+        let page = server.get(endpoints::DUPLICATES).await;
+
+        page.assert_status_ok();
+        let page = page.text();
+        assert!(page.contains(&format!("#{}<", first.id())));
+        assert!(page.contains(&format!("#{}<", second.id())));
+        let dashboard = server.get(endpoints::DASHBOARD).await.text();
+        assert!(dashboard.contains("1\n        group"));
+        assert!(dashboard.contains(endpoints::DUPLICATES));
+    }
+
+    #[tokio::test]
+    async fn dashboard_has_no_duplicates_card_without_duplicates() {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        let user = create_user(&mut state, "foo@bar.baz");
+        let server = get_server(state, &user).await;
+
+        let dashboard = server.get(endpoints::DASHBOARD).await.text();
+
+        assert!(!dashboard.contains(endpoints::DUPLICATES));
+    }
+
+    #[tokio::test]
+    async fn merge_deletes_checked_duplicates() {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        let user = create_user(&mut state, "foo@bar.baz");
+        let (first, second) = create_duplicates(&mut state, &user);
+        let server = get_server(state.clone(), &user).await;
+
+        let response = server
+            .post(endpoints::DUPLICATES_MERGE)
+            .form(&[
+                ("keep", second.id()),
+                ("remove", first.id()),
+                ("remove", second.id()),
+            ])
+            .await;
+
+        response.assert_status_ok();
+        assert_eq!(response.header(HX_REDIRECT), endpoints::DUPLICATES);
+        assert_eq!(
+            state.transaction_store().get(first.id()),
+            Err(TransactionError::NotFound)
+        );
+        assert_eq!(state.transaction_store().get(second.id()), Ok(second));
+    }
+
+    #[tokio::test]
+    async fn cannot_merge_other_users_duplicates() {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        let user = create_user(&mut state, "foo@bar.baz");
+        let other_user = create_user(&mut state, "qux@bar.baz");
+        let (first, second) = create_duplicates(&mut state, &other_user);
+        let server = get_server(state.clone(), &user).await;
+
+        server
+            .post(endpoints::DUPLICATES_MERGE)
+            .form(&[("keep", first.id()), ("remove", second.id())])
+            .await
+            .assert_status_not_found();
+
+        assert_eq!(state.transaction_store().get(second.id()), Ok(second));
+    }
+}
@@ -12,6 +12,50 @@ pub const DASHBOARD: &str = "/dashboard";
 pub const ROOT: &str = "/";
 /// The route for getting log in page and logging in a user.
 pub const LOG_IN: &str = "/log_in";
+/// The log-in page with a message telling the user that their session has expired.
+pub const LOG_IN_SESSION_EXPIRED: &str = "/log_in?expired=true";
+/// The route for getting the change password page and changing the current user's password.
+pub const CHANGE_PASSWORD: &str = "/change_password";
+/// The account settings page.
+pub const SETTINGS: &str = "/settings";
+/// The route to log the current user out of all of their sessions.
+pub const LOG_OUT_EVERYWHERE: &str = "/settings/log_out_everywhere";
+/// The route to change the current user's email address.
+pub const CHANGE_EMAIL: &str = "/settings/email";
+/// The route to change the current user's timezone.
+pub const TIMEZONE: &str = "/settings/timezone";
+/// The route to change whether the current user sees light or dark pages.
+pub const THEME: &str = "/settings/theme";
+/// The route to change the page the current user is sent to after logging in.
+pub const LANDING_PAGE: &str = "/settings/landing_page";
+/// The route to change the days that the current user's weeks and months start on.
+pub const DATE_PERIODS: &str = "/settings/date_periods";
+/// The route to delete the current user's account and all of their data.
+pub const DELETE_ACCOUNT: &str = "/settings/delete_account";
+/// The route for creating API tokens for the current user.
+pub const API_TOKENS: &str = "/settings/api_tokens";
+/// The route to download a backup of the database.
+pub const BACKUP: &str = "/settings/backup.sqlite";
+/// The route to download and upload the current user's categories and preferences.
+pub const CONFIGURATION: &str = "/settings/configuration";
+/// The route to run VACUUM on the database.
+pub const VACUUM: &str = "/settings/vacuum";
+/// The route to run ANALYZE on the database.
+pub const ANALYZE: &str = "/settings/analyze";
+/// The route to revoke a single API token.
+pub const API_TOKEN: &str = "/settings/api_tokens/:token_id";
+/// The route for viewing and adding webhooks.
+pub const WEBHOOKS: &str = "/webhooks";
+/// The route to delete a single webhook.
+pub const WEBHOOK: &str = "/webhooks/:webhook_id";
+/// The route to enable or disable a single webhook.
+pub const WEBHOOK_ENABLED: &str = "/webhooks/:webhook_id/enabled";
+/// The route to send a test event to a single webhook.
+pub const WEBHOOK_TEST: &str = "/webhooks/:webhook_id/test";
+/// The route for viewing and creating invites for registering while registration is invite-only.
+pub const INVITES: &str = "/invites";
+/// The route to access a single invite.
+pub const INVITE: &str = "/invites/:code";
 /// The route for the client to log out the current user.
 pub const LOG_OUT: &str = "/log_out";
 /// The route for getting the registration page and registering new users.
@@ -24,14 +68,56 @@ pub const USER_CATEGORIES: &str = "/users/:user_id/categories";
 pub const USER_TRANSACTIONS: &str = "/users/:user_id/transactions";
 /// The route to access categories.
 pub const CATEGORIES: &str = "/categories";
+/// The route for searching the current user's categories, e.g. for the category combobox.
+pub const CATEGORY_SEARCH: &str = "/categories/search";
+/// The route for suggesting categories for a new transaction from its description.
+pub const CATEGORY_SUGGESTIONS: &str = "/categories/suggestions";
 /// The route to access a single category.
 pub const CATEGORY: &str = "/categories/:category_id";
 /// The route to access transactions.
 pub const TRANSACTIONS: &str = "/transactions";
+/// The route for loading the transactions after a cursor, e.g. for a "Load more" button.
+pub const TRANSACTIONS_MORE: &str = "/transactions/more";
+/// The route for rendering only the transactions table, e.g. to refresh it after a change.
+pub const TRANSACTIONS_TABLE: &str = "/transactions/table";
+/// The route for finding and merging duplicate transactions.
+pub const DUPLICATES: &str = "/transactions/duplicates";
+/// The route for merging a group of duplicate transactions.
+pub const DUPLICATES_MERGE: &str = "/transactions/duplicates/merge";
 /// The route to access a single transaction.
 pub const TRANSACTION: &str = "/transactions/:transaction_id";
+/// The route to set whether a single transaction is ignored.
+pub const TRANSACTION_IGNORED: &str = "/transactions/:transaction_id/ignored";
+/// The JSON API route for listing and creating transactions.
+pub const API_TRANSACTIONS: &str = "/api/v1/transactions";
+/// The JSON API route for creating many transactions at once.
+pub const API_TRANSACTIONS_BATCH: &str = "/api/v1/transactions/batch";
+/// The JSON API route for getting, updating and deleting a single transaction.
+pub const API_TRANSACTION: &str = "/api/v1/transactions/:transaction_id";
+/// The route for a plain-text summary of the user's finances.
+pub const SUMMARY_TEXT: &str = "/api/summary.txt";
+/// The printable summary of a week.
+pub const WEEKLY_SUMMARY: &str = "/summary/weekly";
+/// The printable report of a month's totals and transactions.
+pub const MONTHLY_REPORT: &str = "/summary/monthly";
+/// The statement of a month's transactions grouped by day.
+pub const STATEMENT: &str = "/statement";
+/// The route for reconciling transactions against a bank statement.
+pub const RECONCILE: &str = "/reconcile";
+/// The page listing the changes that the current user has made to their data.
+pub const AUDIT_LOG: &str = "/audit_log";
+/// The route for searching pages and categories from the command palette.
+pub const PALETTE: &str = "/palette";
+/// The page for searching the current user's transactions and categories.
+pub const SEARCH: &str = "/search";
+/// The route for the search results without the rest of the page, e.g. for the navbar dropdown.
+pub const SEARCH_RESULTS: &str = "/search/results";
 /// The page to display when an internal server error occurs.
 pub const INTERNAL_ERROR: &str = "/error";
+/// The route for checking that the server is running.
+pub const HEALTHZ: &str = "/healthz";
+/// The route for checking that the server can serve requests, i.e. the database is available.
+pub const READYZ: &str = "/readyz";
 
 /// The regex pattern for path parameters.
 const PARAMETER_PATTERN: &str = r":[a-z_]+";
@@ -76,18 +162,61 @@ mod endpoints_tests {
 
     #[test]
     fn endpoints_are_valid_uris() {
+        assert_endpoint_is_valid_uri(endpoints::API_TOKEN);
+        assert_endpoint_is_valid_uri(endpoints::API_TOKENS);
+        assert_endpoint_is_valid_uri(endpoints::API_TRANSACTION);
+        assert_endpoint_is_valid_uri(endpoints::API_TRANSACTIONS);
+        assert_endpoint_is_valid_uri(endpoints::API_TRANSACTIONS_BATCH);
+        assert_endpoint_is_valid_uri(endpoints::TRANSACTIONS_MORE);
+        assert_endpoint_is_valid_uri(endpoints::TRANSACTIONS_TABLE);
+        assert_endpoint_is_valid_uri(endpoints::DUPLICATES);
+        assert_endpoint_is_valid_uri(endpoints::DUPLICATES_MERGE);
+        assert_endpoint_is_valid_uri(endpoints::AUDIT_LOG);
+        assert_endpoint_is_valid_uri(endpoints::PALETTE);
+        assert_endpoint_is_valid_uri(endpoints::SEARCH);
+        assert_endpoint_is_valid_uri(endpoints::SEARCH_RESULTS);
         assert_endpoint_is_valid_uri(endpoints::CATEGORIES);
         assert_endpoint_is_valid_uri(endpoints::CATEGORY);
+        assert_endpoint_is_valid_uri(endpoints::CHANGE_PASSWORD);
+        assert_endpoint_is_valid_uri(endpoints::CHANGE_EMAIL);
+        assert_endpoint_is_valid_uri(endpoints::TIMEZONE);
+        assert_endpoint_is_valid_uri(endpoints::THEME);
+        assert_endpoint_is_valid_uri(endpoints::LANDING_PAGE);
+        assert_endpoint_is_valid_uri(endpoints::DATE_PERIODS);
         assert_endpoint_is_valid_uri(endpoints::COFFEE);
         assert_endpoint_is_valid_uri(endpoints::DASHBOARD);
+        assert_endpoint_is_valid_uri(endpoints::DELETE_ACCOUNT);
+        assert_endpoint_is_valid_uri(endpoints::HEALTHZ);
+        assert_endpoint_is_valid_uri(endpoints::INVITE);
+        assert_endpoint_is_valid_uri(endpoints::INVITES);
         assert_endpoint_is_valid_uri(endpoints::LOG_IN);
+        assert_endpoint_is_valid_uri(endpoints::LOG_IN_SESSION_EXPIRED);
         assert_endpoint_is_valid_uri(endpoints::LOG_OUT);
+        assert_endpoint_is_valid_uri(endpoints::LOG_OUT_EVERYWHERE);
         assert_endpoint_is_valid_uri(endpoints::REGISTER);
+        assert_endpoint_is_valid_uri(endpoints::READYZ);
         assert_endpoint_is_valid_uri(endpoints::ROOT);
+        assert_endpoint_is_valid_uri(endpoints::SETTINGS);
         assert_endpoint_is_valid_uri(endpoints::USERS);
         assert_endpoint_is_valid_uri(endpoints::USER_CATEGORIES);
         assert_endpoint_is_valid_uri(endpoints::USER_TRANSACTIONS);
+        assert_endpoint_is_valid_uri(endpoints::TRANSACTION_IGNORED);
+        assert_endpoint_is_valid_uri(endpoints::SUMMARY_TEXT);
+        assert_endpoint_is_valid_uri(endpoints::WEEKLY_SUMMARY);
+        assert_endpoint_is_valid_uri(endpoints::MONTHLY_REPORT);
+        assert_endpoint_is_valid_uri(endpoints::CONFIGURATION);
+        assert_endpoint_is_valid_uri(endpoints::CATEGORY_SEARCH);
+        assert_endpoint_is_valid_uri(endpoints::CATEGORY_SUGGESTIONS);
+        assert_endpoint_is_valid_uri(endpoints::STATEMENT);
+        assert_endpoint_is_valid_uri(endpoints::RECONCILE);
         assert_endpoint_is_valid_uri(endpoints::INTERNAL_ERROR);
+        assert_endpoint_is_valid_uri(endpoints::BACKUP);
+        assert_endpoint_is_valid_uri(endpoints::VACUUM);
+        assert_endpoint_is_valid_uri(endpoints::ANALYZE);
+        assert_endpoint_is_valid_uri(endpoints::WEBHOOKS);
+        assert_endpoint_is_valid_uri(endpoints::WEBHOOK);
+        assert_endpoint_is_valid_uri(endpoints::WEBHOOK_ENABLED);
+        assert_endpoint_is_valid_uri(endpoints::WEBHOOK_TEST);
     }
 
     #[test]
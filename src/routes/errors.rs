@@ -0,0 +1,415 @@
+//! Error responses that are shown as a full page, an alert or JSON depending on who sent the
+//! request.
+
+use askama_axum::Template;
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::{cookie::Key, PrivateCookieJar};
+use axum_htmx::{HxReswap, HxRetarget, SwapOption};
+
+use crate::{auth::middleware::Client, json_error_response};
+
+use super::{get_not_found_page, limits::AlertTemplate};
+
+/// Describes an error response so that [render_errors] can replace it with a representation
+/// that suits the client.
+#[derive(Debug, Clone)]
+struct ErrorDetails {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+}
+
+/// The error page for when the database cannot be used, with the likely causes.
+#[derive(Template)]
+#[template(path = "views/database_unavailable_500.html")]
+struct DatabaseUnavailableTemplate {
+    status: u16,
+}
+
+/// A generic error page for errors that do not have their own page.
+#[derive(Template)]
+#[template(path = "views/error.html")]
+struct ErrorPageTemplate<'a> {
+    status: u16,
+    message: &'a str,
+}
+
+/// Get an error response with the body `{ "error": message, "code": code }`.
+///
+/// [render_errors] replaces the body with an error page for browsers or an alert for HTMX
+/// requests, so handlers can return the same response no matter who sent the request.
+pub(crate) fn get_error_response(
+    status: StatusCode,
+    code: &'static str,
+    message: &str,
+) -> Response {
+    let mut response = json_error_response(status, code, message);
+    response.extensions_mut().insert(ErrorDetails {
+        status,
+        code,
+        message: message.to_owned(),
+    });
+
+    response
+}
+
+/// Get a 500 response for when a store returned a `DatabaseLockError`.
+pub(crate) fn get_database_lock_error_response() -> Response {
+    tracing::error!("The database lock is poisoned, a thread panicked while holding it.");
+
+    get_error_response(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "database_unavailable",
+        "The database is unavailable right now. Please try again later.",
+    )
+}
+
+/// Get a 503 response for when SQLite could not use the database, e.g. because another program is
+/// holding a lock on it or the file has become read-only.
+///
+/// See [crate::db::is_unavailable_error].
+pub(crate) fn get_database_unavailable_response(error: &rusqlite::Error) -> Response {
+    tracing::error!("The database is unavailable: {error}");
+
+    get_error_response(
+        StatusCode::SERVICE_UNAVAILABLE,
+        "database_unavailable",
+        "The database is unavailable right now. Please try again later.",
+    )
+}
+
+/// Replace responses from [get_error_response] with a full error page for browsers, or an alert
+/// at the top of the page for HTMX requests. API clients that only accept JSON keep the JSON
+/// error.
+///
+/// The state is the key for reading the auth cookie and whether the server is in demo mode, which
+/// decide how the navbar on the 404 page is shown.
+pub async fn render_errors(
+    State((key, demo_mode)): State<(Key, bool)>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let client = Client::from_headers(request.headers());
+    let jar = PrivateCookieJar::from_headers(request.headers(), key);
+    let response = next.run(request).await;
+
+    let Some(error) = response.extensions().get::<ErrorDetails>().cloned() else {
+        return response;
+    };
+
+    match client {
+        Client::Htmx => (
+            error.status,
+            HxRetarget("body".to_owned()),
+            HxReswap(SwapOption::AfterBegin),
+            AlertTemplate {
+                message: &error.message,
+            },
+        )
+            .into_response(),
+        Client::Browser => match error.code {
+            "not_found" => get_not_found_page(&jar, demo_mode),
+            "database_unavailable" => (
+                error.status,
+                DatabaseUnavailableTemplate {
+                    status: error.status.as_u16(),
+                },
+            )
+                .into_response(),
+            _ => (
+                error.status,
+                ErrorPageTemplate {
+                    status: error.status.as_u16(),
+                    message: &error.message,
+                },
+            )
+                .into_response(),
+        },
+        Client::Api => response,
+    }
+}
+
+/// Stop [render_errors] from replacing the JSON errors in `response`, e.g. for the JSON API whose
+/// clients may not ask for JSON explicitly.
+pub async fn keep_json_errors(mut response: Response) -> Response {
+    response.extensions_mut().remove::<ErrorDetails>();
+
+    response
+}
+
+#[cfg(test)]
+mod errors_tests {
+    use std::{
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    use axum::http::{header::ACCEPT, HeaderValue, StatusCode};
+    use axum_htmx::{HX_REQUEST, HX_RESWAP, HX_RETARGET};
+    use axum_test::TestServer;
+    use email_address::EmailAddress;
+    use rusqlite::Connection;
+    use serde_json::json;
+
+    use crate::{
+        auth::log_in::LogInData,
+        build_router,
+        db::initialize,
+        models::{PasswordHash, ValidatedPassword},
+        routes::endpoints::{self, format_endpoint},
+        stores::{
+            sql_store::create_app_state, SQLiteCategoryStore, SQLiteTransactionStore,
+            SQLiteUserStore, UserStore,
+        },
+        AppState,
+    };
+
+    const PASSWORD: &str = "averysafeandsecurepassword";
+
+    /// Get a server with a logged in user and the database connection it uses.
+    async fn get_server_and_connection() -> (TestServer, Arc<Mutex<Connection>>) {
+        let connection = Connection::open_in_memory().unwrap();
+        initialize(&connection).unwrap();
+        let connection = Arc::new(Mutex::new(connection));
+        let mut state = AppState::new(
+            "42",
+            SQLiteCategoryStore::new(connection.clone()),
+            SQLiteTransactionStore::new(connection.clone()),
+            SQLiteUserStore::new(connection.clone()),
+        );
+        state.password_hash_cost = 4;
+        let user = state
+            .user_store()
+            .create(
+                EmailAddress::new_unchecked("foo@bar.baz"),
+                PasswordHash::new(ValidatedPassword::new_unchecked(PASSWORD), 4).unwrap(),
+            )
+            .unwrap();
+
+        let mut server = TestServer::new(build_router(state)).unwrap();
+        server.do_save_cookies();
+        server
+            .post(endpoints::LOG_IN)
+            .form(&LogInData {
+                email: user.email().to_string(),
+                password: PASSWORD.to_string(),
+                remember_me: None,
+            })
+            .await
+            .assert_status_see_other();
+
+        (server, connection)
+    }
+
+    /// Get a server with a logged in user and the database lock poisoned.
+    async fn get_server_with_poisoned_lock() -> TestServer {
+        let (server, connection) = get_server_and_connection().await;
+
+        let _ = thread::spawn(move || {
+            let _guard = connection.lock().unwrap();
+            panic!("poison the database lock");
+        })
+        .join();
+
+        server
+    }
+
+    /// The link to log out, which is only shown in the navbar.
+    fn get_log_out_link() -> String {
+        format!(r#"href="{}""#, endpoints::LOG_OUT)
+    }
+
+    fn get_missing_transaction_route() -> String {
+        format_endpoint(endpoints::TRANSACTION, 999)
+    }
+
+    #[tokio::test]
+    async fn browser_gets_not_found_page() {
+        let (server, _) = get_server_and_connection().await;
+
+        let response = server.get(&get_missing_transaction_route()).await;
+
+        response.assert_status_not_found();
+        let body = response.text();
+        assert!(body.contains("<html"));
+        assert!(body.contains("we can't find that page"));
+        assert!(body.contains(&get_log_out_link()));
+    }
+
+    #[tokio::test]
+    async fn unknown_route_shows_navbar_when_logged_in() {
+        let (server, _) = get_server_and_connection().await;
+
+        let response = server.get("/does/not/exist").await;
+
+        response.assert_status_not_found();
+        assert!(response.text().contains(&get_log_out_link()));
+    }
+
+    #[tokio::test]
+    async fn unknown_route_has_no_navbar_when_logged_out() {
+        let state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        let server = TestServer::new(build_router(state)).unwrap();
+
+        let response = server.get("/does/not/exist").await;
+
+        response.assert_status_not_found();
+        let body = response.text();
+        assert!(body.contains("we can't find that page"));
+        assert!(!body.contains(&get_log_out_link()));
+    }
+
+    #[tokio::test]
+    async fn htmx_request_gets_not_found_alert() {
+        let (server, _) = get_server_and_connection().await;
+
+        let response = server
+            .get(&get_missing_transaction_route())
+            .add_header(HX_REQUEST, HeaderValue::from_static("true"))
+            .await;
+
+        response.assert_status_not_found();
+        assert_eq!(response.header(HX_RETARGET), "body");
+        assert_eq!(response.header(HX_RESWAP), "afterbegin");
+        let body = response.text();
+        assert!(!body.contains("<html"));
+        assert!(body.contains(r#"role="alert""#));
+        assert!(body.contains("The requested resource could not be found."));
+    }
+
+    #[tokio::test]
+    async fn json_request_gets_not_found_json() {
+        let (server, _) = get_server_and_connection().await;
+
+        let response = server
+            .get(&get_missing_transaction_route())
+            .add_header(ACCEPT, HeaderValue::from_static("application/json"))
+            .await;
+
+        response.assert_status_not_found();
+        response.assert_json(&json!({
+            "error": "The requested resource could not be found.",
+            "code": "not_found",
+        }));
+    }
+
+    #[tokio::test]
+    async fn api_routes_always_get_json() {
+        let (server, _) = get_server_and_connection().await;
+
+        let response = server
+            .get(&format_endpoint(endpoints::API_TRANSACTION, 999))
+            .add_header(ACCEPT, HeaderValue::from_static("*/*"))
+            .await;
+
+        response.assert_status_not_found();
+        response.assert_json(&json!({
+            "error": "The requested resource could not be found.",
+            "code": "not_found",
+        }));
+    }
+
+    #[tokio::test]
+    async fn browser_gets_database_unavailable_page() {
+        let server = get_server_with_poisoned_lock().await;
+
+        let response = server.get(endpoints::DASHBOARD).await;
+
+        response.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+        let body = response.text();
+        assert!(body.contains("<html"));
+        assert!(body.contains("the database is unavailable"));
+    }
+
+    #[tokio::test]
+    async fn htmx_request_gets_database_unavailable_alert() {
+        let server = get_server_with_poisoned_lock().await;
+
+        let response = server
+            .get(endpoints::TRANSACTIONS_TABLE)
+            .add_header(HX_REQUEST, HeaderValue::from_static("true"))
+            .await;
+
+        response.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(response.header(HX_RETARGET), "body");
+        let body = response.text();
+        assert!(!body.contains("<html"));
+        assert!(body.contains(r#"role="alert""#));
+        assert!(body.contains("The database is unavailable"));
+    }
+
+    #[tokio::test]
+    async fn json_request_gets_database_unavailable_json() {
+        let server = get_server_with_poisoned_lock().await;
+
+        let response = server
+            .get(endpoints::API_TRANSACTIONS)
+            .add_header(ACCEPT, HeaderValue::from_static("application/json"))
+            .await;
+
+        response.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+        response.assert_json(&json!({
+            "error": "The database is unavailable right now. Please try again later.",
+            "code": "database_unavailable",
+        }));
+    }
+
+    #[tokio::test]
+    async fn browser_gets_database_unavailable_page_when_database_is_read_only() {
+        let (server, connection) = get_server_and_connection().await;
+        let user_id: i64 = connection
+            .lock()
+            .unwrap()
+            .query_row("SELECT id FROM user", (), |row| row.get(0))
+            .unwrap();
+        // Writes now fail with the same error as when the database file is read-only.
+        connection
+            .lock()
+            .unwrap()
+            .pragma_update(None, "query_only", true)
+            .unwrap();
+
+        let response = server
+            .post(&format_endpoint(endpoints::USER_CATEGORIES, user_id))
+            .form(&[("name", "Groceries")])
+            .await;
+
+        response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+        let body = response.text();
+        assert!(body.contains("<html"));
+        assert!(body.contains("the database is"));
+        assert!(body.contains("mounted read-only"));
+    }
+
+    #[tokio::test]
+    async fn json_request_gets_database_unavailable_json_when_database_is_read_only() {
+        let (server, connection) = get_server_and_connection().await;
+        let user_id: i64 = connection
+            .lock()
+            .unwrap()
+            .query_row("SELECT id FROM user", (), |row| row.get(0))
+            .unwrap();
+        connection
+            .lock()
+            .unwrap()
+            .pragma_update(None, "query_only", true)
+            .unwrap();
+
+        let response = server
+            .post(&format_endpoint(endpoints::USER_CATEGORIES, user_id))
+            .add_header(ACCEPT, HeaderValue::from_static("application/json"))
+            .form(&[("name", "Groceries")])
+            .await;
+
+        response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+        response.assert_json(&json!({
+            "error": "The database is unavailable right now. Please try again later.",
+            "code": "database_unavailable",
+        }));
+    }
+}
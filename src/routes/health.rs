@@ -0,0 +1,136 @@
+//! Endpoints for uptime monitoring and reverse proxies to check the server's health.
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+use crate::stores::sql_store::SQLAppState;
+
+/// Respond with 200 OK to show that the server is running.
+pub async fn get_healthz() -> Response {
+    Json(json!({ "status": "ok" })).into_response()
+}
+
+/// Respond with 200 OK if the database can be queried, otherwise 503 Service Unavailable.
+pub async fn get_readyz(State(mut state): State<SQLAppState>) -> Response {
+    match state.user_store().check_connection() {
+        Ok(()) => Json(json!({ "status": "ready" })).into_response(),
+        Err(error) => {
+            tracing::error!("Database is unavailable: {error}");
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(json!({ "status": "unavailable" })),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod health_tests {
+    use std::{
+        env, fs,
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    use axum::http::StatusCode;
+    use axum_test::TestServer;
+    use rusqlite::{Connection, OpenFlags};
+    use serde_json::json;
+
+    use crate::{
+        build_router,
+        db::initialize,
+        routes::endpoints,
+        stores::{SQLiteCategoryStore, SQLiteTransactionStore, SQLiteUserStore},
+        AppState,
+    };
+
+    fn get_test_server_and_connection() -> (TestServer, Arc<Mutex<Connection>>) {
+        let connection = Connection::open_in_memory().unwrap();
+        initialize(&connection).unwrap();
+
+        get_test_server_for_connection(connection)
+    }
+
+    fn get_test_server_for_connection(
+        connection: Connection,
+    ) -> (TestServer, Arc<Mutex<Connection>>) {
+        let connection = Arc::new(Mutex::new(connection));
+        let state = AppState::new(
+            "42",
+            SQLiteCategoryStore::new(connection.clone()),
+            SQLiteTransactionStore::new(connection.clone()),
+            SQLiteUserStore::new(connection.clone()),
+        );
+
+        (
+            TestServer::new(build_router(state)).expect("Could not create test server."),
+            connection,
+        )
+    }
+
+    #[tokio::test]
+    async fn healthz_responds_ok_without_auth() {
+        let (server, _) = get_test_server_and_connection();
+
+        let response = server.get(endpoints::HEALTHZ).await;
+
+        response.assert_status_ok();
+        response.assert_json(&json!({ "status": "ok" }));
+    }
+
+    #[tokio::test]
+    async fn readyz_responds_ok_when_database_is_available() {
+        let (server, _) = get_test_server_and_connection();
+
+        let response = server.get(endpoints::READYZ).await;
+
+        response.assert_status_ok();
+        response.assert_json(&json!({ "status": "ready" }));
+    }
+
+    #[tokio::test]
+    async fn readyz_responds_unavailable_when_database_lock_is_poisoned() {
+        let (server, connection) = get_test_server_and_connection();
+        let _ = thread::spawn(move || {
+            let _guard = connection.lock().unwrap();
+            panic!("poison the database lock");
+        })
+        .join();
+
+        let response = server.get(endpoints::READYZ).await;
+
+        response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+        response.assert_json(&json!({ "status": "unavailable" }));
+    }
+
+    #[tokio::test]
+    async fn readyz_responds_unavailable_when_database_is_read_only() {
+        let path = env::temp_dir().join(format!(
+            "budgeteur_readyz_test_{}_{}.db",
+            std::process::id(),
+            rand::random::<u64>()
+        ));
+        let writable_connection = Connection::open(&path).unwrap();
+        initialize(&writable_connection).unwrap();
+        let connection =
+            Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY).unwrap();
+        let (server, _) = get_test_server_for_connection(connection);
+
+        let response = server.get(endpoints::READYZ).await;
+
+        drop(server);
+        drop(writable_connection);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = fs::remove_file(format!("{}{suffix}", path.display()));
+        }
+        response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+        response.assert_json(&json!({ "status": "unavailable" }));
+    }
+}
@@ -0,0 +1,285 @@
+//! The page for managing the invites that allow people to register while registration is invite-only.
+//!
+//! Invites let people create an account on the site, so only the owner of the site, i.e. the first
+//! user to register, may manage them. Other users get 403 Forbidden.
+
+use askama_axum::Template;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    Extension,
+};
+
+use crate::{
+    models::{Invite, UserID},
+    routes::get_internal_server_error_redirect,
+    stores::{sql_store::SQLAppState, InviteStore, UserError},
+    RegistrationMode,
+};
+
+use super::{
+    endpoints,
+    navigation::{get_nav_bar, NavbarTemplate},
+};
+
+#[derive(Template)]
+#[template(path = "views/invites.html")]
+struct InvitesPageTemplate<'a> {
+    navbar: NavbarTemplate<'a>,
+    registration_mode: RegistrationMode,
+    invites_route: &'a str,
+    invite_list: InviteListTemplate,
+}
+
+/// Renders the unused invites with links for registering and buttons for revoking them.
+#[derive(Template)]
+#[template(path = "partials/invites/list.html")]
+struct InviteListTemplate {
+    invites: Vec<Invite>,
+}
+
+impl InviteListTemplate {
+    /// The link to the registration page with the invite code filled in.
+    fn register_route(&self, invite: &Invite) -> String {
+        format!("{}?invite={}", endpoints::REGISTER, invite.code())
+    }
+
+    /// The route for revoking the invite.
+    fn delete_route(&self, invite: &Invite) -> String {
+        endpoints::INVITE.replace(":code", invite.code())
+    }
+}
+
+/// Respond with the list of unused invites, or a redirect to the internal server error page.
+fn get_invite_list_response<I>(invite_store: &I) -> Response
+where
+    I: InviteStore,
+{
+    match invite_store.get_all() {
+        Ok(invites) => InviteListTemplate { invites }.into_response(),
+        Err(error) => {
+            tracing::error!("Could not get invites: {error}");
+            get_internal_server_error_redirect()
+        }
+    }
+}
+
+/// Respond with 403 Forbidden unless `user_id` is the owner of the site.
+fn check_is_owner(state: &mut SQLAppState, user_id: UserID) -> Result<(), Response> {
+    match state.user_store().get_owner_id() {
+        Ok(Some(owner_id)) if owner_id == user_id => Ok(()),
+        Ok(_) => Err((
+            StatusCode::FORBIDDEN,
+            "Only the owner of this site can manage invites.",
+        )
+            .into_response()),
+        Err(error) => {
+            tracing::error!("Could not get the owner of the site: {error}");
+            Err(get_internal_server_error_redirect())
+        }
+    }
+}
+
+/// Display the invites page.
+pub async fn get_invites_page(
+    State(mut state): State<SQLAppState>,
+    Extension(user_id): Extension<UserID>,
+) -> Response {
+    if let Err(response) = check_is_owner(&mut state, user_id) {
+        return response;
+    }
+
+    let invites = match state.invite_store().get_all() {
+        Ok(invites) => invites,
+        Err(error) => {
+            tracing::error!("Could not get invites: {error}");
+            return Redirect::to(endpoints::INTERNAL_ERROR).into_response();
+        }
+    };
+
+    InvitesPageTemplate {
+        navbar: get_nav_bar(endpoints::SETTINGS, state.demo_mode),
+        registration_mode: state.registration_mode,
+        invites_route: endpoints::INVITES,
+        invite_list: InviteListTemplate { invites },
+    }
+    .into_response()
+}
+
+/// Create a new invite and respond with the updated list of invites.
+pub async fn create_invite(
+    State(mut state): State<SQLAppState>,
+    Extension(user_id): Extension<UserID>,
+) -> Response {
+    if let Err(response) = check_is_owner(&mut state, user_id) {
+        return response;
+    }
+
+    let mut invite_store = state.invite_store();
+
+    if let Err(error) = invite_store.create(Invite::generate_code()) {
+        tracing::error!("Could not create invite: {error}");
+        return get_internal_server_error_redirect();
+    }
+
+    get_invite_list_response(&invite_store)
+}
+
+/// Revoke the invite with the code in the path and respond with the updated list of invites.
+pub async fn delete_invite(
+    State(mut state): State<SQLAppState>,
+    Extension(user_id): Extension<UserID>,
+    Path(code): Path<String>,
+) -> Response {
+    if let Err(response) = check_is_owner(&mut state, user_id) {
+        return response;
+    }
+
+    let mut invite_store = state.invite_store();
+
+    match invite_store.delete(&code) {
+        // The invite may have been used or revoked in the meantime, which is fine.
+        Ok(()) | Err(UserError::NotFound) => {}
+        Err(error) => {
+            tracing::error!("Could not delete invite: {error}");
+            return get_internal_server_error_redirect();
+        }
+    }
+
+    get_invite_list_response(&invite_store)
+}
+
+#[cfg(test)]
+mod invites_tests {
+    use axum::{
+        http::StatusCode,
+        middleware,
+        routing::{delete, get, post},
+        Router,
+    };
+    use axum_test::TestServer;
+    use email_address::EmailAddress;
+    use rusqlite::Connection;
+
+    use crate::{
+        auth::{log_in::LogInData, middleware::auth_guard},
+        models::{PasswordHash, ValidatedPassword},
+        routes::{endpoints, log_in::post_log_in},
+        stores::{
+            sql_store::{create_app_state, SQLAppState},
+            InviteStore, UserStore,
+        },
+        RegistrationMode,
+    };
+
+    use super::{create_invite, delete_invite, get_invites_page};
+
+    const EMAIL: &str = "foo@bar.baz";
+    const OTHER_EMAIL: &str = "qux@bar.baz";
+    const PASSWORD: &str = "averysafeandsecurepassword";
+
+    async fn get_test_state_and_logged_in_server() -> (SQLAppState, TestServer) {
+        get_test_state_and_server_logged_in_as(EMAIL).await
+    }
+
+    /// Create a server with the owner of the site, whose email is [EMAIL], and another user whose
+    /// email is [OTHER_EMAIL], and log in as the user with `email`.
+    async fn get_test_state_and_server_logged_in_as(email: &str) -> (SQLAppState, TestServer) {
+        let connection = Connection::open_in_memory().unwrap();
+        let mut state = create_app_state(connection, "42").unwrap();
+        state.password_hash_cost = 4;
+        state.registration_mode = RegistrationMode::InviteOnly;
+        for email in [EMAIL, OTHER_EMAIL] {
+            state
+                .user_store()
+                .create(
+                    EmailAddress::new_unchecked(email),
+                    PasswordHash::new(ValidatedPassword::new_unchecked(PASSWORD), 4).unwrap(),
+                )
+                .unwrap();
+        }
+
+        let app = Router::new()
+            .route(endpoints::INVITES, get(get_invites_page))
+            .route(endpoints::INVITES, post(create_invite))
+            .route(endpoints::INVITE, delete(delete_invite))
+            .layer(middleware::from_fn_with_state(state.clone(), auth_guard))
+            .route(endpoints::LOG_IN, post(post_log_in))
+            .with_state(state.clone());
+
+        let mut server = TestServer::new(app).expect("Could not create test server.");
+        server.do_save_cookies();
+        server
+            .post(endpoints::LOG_IN)
+            .form(&LogInData {
+                email: email.to_string(),
+                password: PASSWORD.to_string(),
+                remember_me: None,
+            })
+            .await;
+
+        (state, server)
+    }
+
+    #[tokio::test]
+    async fn invites_page_displays_registration_mode() {
+        let (_, server) = get_test_state_and_logged_in_server().await;
+
+        let response = server.get(endpoints::INVITES).await;
+
+        response.assert_status_ok();
+        response.assert_text_contains("invite-only");
+        response.assert_text_contains("There are no unused invites.");
+    }
+
+    #[tokio::test]
+    async fn can_create_invite() {
+        let (mut state, server) = get_test_state_and_logged_in_server().await;
+
+        let response = server.post(endpoints::INVITES).await;
+
+        response.assert_status_ok();
+        let invites = state.invite_store().get_all().unwrap();
+        assert_eq!(invites.len(), 1);
+        response.assert_text_contains(format!(
+            "{}?invite={}",
+            endpoints::REGISTER,
+            invites[0].code()
+        ));
+    }
+
+    #[tokio::test]
+    async fn can_delete_invite() {
+        let (mut state, server) = get_test_state_and_logged_in_server().await;
+        let invite = state.invite_store().create("foobar".to_string()).unwrap();
+
+        let response = server
+            .delete(&endpoints::INVITE.replace(":code", invite.code()))
+            .await;
+
+        response.assert_status_ok();
+        response.assert_text_contains("There are no unused invites.");
+        assert_eq!(state.invite_store().get_all().unwrap(), vec![]);
+    }
+
+    #[tokio::test]
+    async fn only_owner_can_manage_invites() {
+        let (mut state, server) = get_test_state_and_server_logged_in_as(OTHER_EMAIL).await;
+        state.invite_store().create("foobar".to_string()).unwrap();
+
+        let responses = [
+            server.get(endpoints::INVITES).await,
+            server.post(endpoints::INVITES).await,
+            server
+                .delete(&endpoints::INVITE.replace(":code", "foobar"))
+                .await,
+        ];
+
+        for response in responses {
+            response.assert_status(StatusCode::FORBIDDEN);
+            response.assert_text_contains("Only the owner of this site can manage invites.");
+        }
+        assert_eq!(state.invite_store().get_all().unwrap().len(), 1);
+    }
+}
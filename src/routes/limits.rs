@@ -0,0 +1,230 @@
+//! Middleware that stops slow requests and large request bodies from tying up the server.
+
+use std::time::Duration;
+
+use askama_axum::Template;
+use axum::{
+    extract::{Request, State},
+    http::{header::CONTENT_LENGTH, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_htmx::HX_REQUEST;
+
+use super::maintenance::format_bytes;
+
+/// The default time the server spends on a request before giving up.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The default maximum size of a request body in bytes.
+pub const DEFAULT_MAX_REQUEST_BODY_SIZE: usize = 2 * 1024 * 1024;
+
+/// A short error message that HTMX can show on the current page.
+#[derive(Template)]
+#[template(path = "partials/alert.html")]
+pub(super) struct AlertTemplate<'a> {
+    pub(super) message: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "views/request_timeout_503.html")]
+struct RequestTimeoutTemplate;
+
+#[derive(Template)]
+#[template(path = "views/payload_too_large_413.html")]
+struct PayloadTooLargeTemplate {
+    /// The maximum body size, e.g. "2.0 MiB".
+    limit: String,
+}
+
+/// Respond with a 503 error page if the rest of the app takes longer than `timeout` to respond.
+///
+/// The handler is dropped when the timeout expires, so any work it has not finished is cancelled.
+pub async fn timeout_requests(
+    State(timeout): State<Duration>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path().to_owned();
+
+    match tokio::time::timeout(timeout, next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => {
+            tracing::warn!("Request to {path} timed out after {timeout:?}");
+            (StatusCode::SERVICE_UNAVAILABLE, RequestTimeoutTemplate).into_response()
+        }
+    }
+}
+
+/// Respond with a 413 error if the request body is larger than `max_size` bytes.
+///
+/// Requests that declare their size with `Content-Length` are rejected before their body is read.
+/// Streamed bodies are cut off by the extractors once they pass the limit set with
+/// [DefaultBodyLimit](axum::extract::DefaultBodyLimit), and the resulting 413 response is
+/// replaced with the same error as above.
+pub async fn limit_body_size(
+    State(max_size): State<usize>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let is_htmx = request.headers().contains_key(HX_REQUEST);
+
+    if get_content_length(request.headers()).is_some_and(|length| length > max_size) {
+        return get_payload_too_large_response(max_size, is_htmx);
+    }
+
+    let response = next.run(request).await;
+
+    if response.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        get_payload_too_large_response(max_size, is_htmx)
+    } else {
+        response
+    }
+}
+
+fn get_content_length(headers: &HeaderMap) -> Option<usize> {
+    headers.get(CONTENT_LENGTH)?.to_str().ok()?.parse().ok()
+}
+
+fn get_payload_too_large_response(max_size: usize, is_htmx: bool) -> Response {
+    let limit = format_bytes(max_size as u64);
+
+    if is_htmx {
+        (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            AlertTemplate {
+                message: &format!("That request was too large, the limit is {limit}."),
+            },
+        )
+            .into_response()
+    } else {
+        (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            PayloadTooLargeTemplate { limit },
+        )
+            .into_response()
+    }
+}
+
+#[cfg(test)]
+mod limits_tests {
+    use std::time::Duration;
+
+    use axum::{
+        body::Bytes,
+        extract::DefaultBodyLimit,
+        http::{header::CONTENT_LENGTH, HeaderName, HeaderValue, StatusCode},
+        middleware,
+        routing::{get, post},
+        Router,
+    };
+    use axum_test::TestServer;
+    use rusqlite::Connection;
+
+    use crate::{build_router, routes::endpoints, stores::sql_store::create_app_state};
+
+    use super::{limit_body_size, timeout_requests};
+
+    #[tokio::test]
+    async fn slow_requests_time_out() {
+        let app = Router::new()
+            .route(
+                "/slow",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    "done"
+                }),
+            )
+            .route("/fast", get(|| async { "done" }))
+            .layer(middleware::from_fn_with_state(
+                Duration::from_millis(50),
+                timeout_requests,
+            ));
+        let server = TestServer::new(app).unwrap();
+
+        let response = server.get("/slow").await;
+
+        response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+        response.assert_text_contains("503");
+        server.get("/fast").await.assert_status_ok();
+    }
+
+    #[tokio::test]
+    async fn over_limit_body_is_rejected_with_error_page() {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        state.max_request_body_size = 1024;
+        let server = TestServer::new(build_router(state)).unwrap();
+
+        let response = server
+            .post(endpoints::LOG_IN)
+            .form(&[("email", "foo@bar.baz"), ("password", &"a".repeat(2048))])
+            .await;
+
+        response.assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+        response.assert_text_contains("1.0 KiB");
+        assert!(response.text().contains("<html"));
+    }
+
+    #[tokio::test]
+    async fn over_limit_body_from_htmx_gets_alert() {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        state.max_request_body_size = 1024;
+        let server = TestServer::new(build_router(state)).unwrap();
+
+        let response = server
+            .post(endpoints::LOG_IN)
+            .add_header(
+                HeaderName::from_static("hx-request"),
+                HeaderValue::from_static("true"),
+            )
+            .form(&[("email", "foo@bar.baz"), ("password", &"a".repeat(2048))])
+            .await;
+
+        response.assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+        response.assert_text_contains("role=\"alert\"");
+        assert!(!response.text().contains("<html"));
+    }
+
+    #[tokio::test]
+    async fn declared_content_length_is_rejected_before_reading_body() {
+        let app = Router::new()
+            .route("/upload", post(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(16, limit_body_size));
+        let server = TestServer::new(app).unwrap();
+
+        // The handler never reads the body, so only the middleware can reject it.
+        let response = server
+            .post("/upload")
+            .add_header(CONTENT_LENGTH, HeaderValue::from_static("32"))
+            .bytes(Bytes::from_static(&[0; 32]))
+            .await;
+
+        response.assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+        response.assert_text_contains("16 B");
+    }
+
+    #[tokio::test]
+    async fn streamed_body_is_cut_off_by_extractor() {
+        let app = Router::new()
+            .route(
+                "/upload",
+                post(|body: Bytes| async move { body.len().to_string() }),
+            )
+            .layer(DefaultBodyLimit::max(16))
+            .layer(middleware::from_fn_with_state(16, limit_body_size));
+        let server = TestServer::new(app).unwrap();
+
+        let response = server
+            .post("/upload")
+            .bytes(Bytes::from_static(&[0; 32]))
+            .await;
+
+        response.assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+        response.assert_text_contains("16 B");
+        server
+            .post("/upload")
+            .bytes(Bytes::from_static(&[0; 8]))
+            .await
+            .assert_text("8");
+    }
+}
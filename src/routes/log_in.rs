@@ -2,28 +2,34 @@
 //! The auth module handles the lower level authentication and cookie auth logic.
 
 use askama::Template;
+use std::net::SocketAddr;
+
 use axum::{
-    extract::State,
-    http::{StatusCode, Uri},
+    extract::{ConnectInfo, Query, State},
+    http::{header::RETRY_AFTER, HeaderMap, StatusCode, Uri},
     response::{IntoResponse, Response},
     Form,
 };
 use axum_extra::extract::PrivateCookieJar;
 use axum_htmx::HxRedirect;
-use time::Duration;
+use serde::Deserialize;
+use time::{Duration, OffsetDateTime};
 
 use crate::{
     auth::{
         cookie::{invalidate_auth_cookie, set_auth_cookie},
         log_in::{verify_credentials, LogInData},
+        rate_limit::AttemptKey,
         AuthError,
     },
+    demo::{DEMO_EMAIL, DEMO_PASSWORD},
+    models::{PasswordHash, User, ValidatedPassword},
     stores::{CategoryStore, TransactionStore, UserStore},
-    AppState,
+    AppState, RegistrationMode,
 };
 
 use super::{
-    endpoints,
+    endpoints, get_landing_page_route,
     templates::{EmailInputTemplate, PasswordInputTemplate},
 };
 
@@ -35,6 +41,9 @@ struct LogInFormTemplate<'a> {
     password_input: PasswordInputTemplate<'a>,
     log_in_route: &'a str,
     register_route: &'a str,
+    /// Whether to display the link to the registration page.
+    registration_open: bool,
+    session_expired: bool,
 }
 
 impl Default for LogInFormTemplate<'_> {
@@ -42,6 +51,8 @@ impl Default for LogInFormTemplate<'_> {
         Self {
             email_input: Default::default(),
             password_input: Default::default(),
+            registration_open: true,
+            session_expired: false,
             log_in_route: endpoints::LOG_IN,
             register_route: endpoints::REGISTER,
         }
@@ -55,17 +66,53 @@ struct LogInTemplate<'a> {
     log_in_form: LogInFormTemplate<'a>,
 }
 
-/// Display the log-in page.
-pub async fn get_log_in_page() -> Response {
-    LogInTemplate::default().into_response()
+/// The query parameters for the log-in page.
+#[derive(Debug, Default, Deserialize)]
+pub struct LogInPageQuery {
+    /// Whether the user was sent to the log-in page because their session expired.
+    #[serde(default)]
+    pub expired: bool,
 }
 
-/// How long the auth cookie should last if the user selects "remember me" at log-in.
-pub const REMEMBER_ME_COOKIE_DURATION: Duration = Duration::days(7);
+/// Display the log-in page.
+pub async fn get_log_in_page<C, T, U>(
+    State(state): State<AppState<C, T, U>>,
+    Query(query): Query<LogInPageQuery>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    // Fill in the demo user's credentials so that visitors to the demo can log in with one click.
+    let (email, password) = if state.demo_mode {
+        (DEMO_EMAIL, DEMO_PASSWORD)
+    } else {
+        ("", "")
+    };
+
+    LogInTemplate {
+        log_in_form: LogInFormTemplate {
+            email_input: EmailInputTemplate {
+                value: email,
+                error_message: "",
+            },
+            password_input: PasswordInputTemplate {
+                value: password,
+                ..Default::default()
+            },
+            registration_open: state.registration_mode != RegistrationMode::Closed,
+            session_expired: query.expired,
+            ..Default::default()
+        },
+    }
+    .into_response()
+}
 
 /// Handler for log-in requests via the POST method.
 ///
-/// On a successful log-in request, the auth cookie set and the client is redirected to the dashboard page.
+/// On a successful log-in request, the auth cookie set and the client is redirected to the user's
+/// landing page, which is the dashboard unless they have chosen another page.
 /// Otherwise, the form is return with an error message explaining the problem.
 ///
 /// # Errors
@@ -75,11 +122,19 @@ pub const REMEMBER_ME_COOKIE_DURATION: Duration = Duration::days(7);
 /// - The password is not correct.
 /// - An internal error occurred when verifying the password.
 ///
+/// If there have been too many failed log-in attempts for the email or the client's IP address,
+/// the credentials are not checked and the form is returned with the status code 429 and a
+/// message saying when to try again.
+///
 /// # Panics
 ///
-/// Panics if the lock for the database connection is already held by the same thread.
+/// Panics if the lock for the database connection or the rate limiter is already held by the same thread.
+// The error variant is a response that is returned as-is, so boxing it would not gain anything.
+#[allow(clippy::result_large_err)]
 pub async fn post_log_in<C, T, U>(
     State(mut state): State<AppState<C, T, U>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
     jar: PrivateCookieJar,
     Form(user_data): Form<LogInData>,
 ) -> Response
@@ -88,30 +143,70 @@ where
     T: TransactionStore + Send + Sync,
     U: UserStore + Send + Sync,
 {
-    verify_credentials(user_data.clone(), state.user_store())
+    let attempt_keys = AttemptKey::for_log_in(
+        &user_data.email,
+        state.proxy_settings.get_client_ip(
+            &headers,
+            connect_info.map(|ConnectInfo(address)| address.ip()),
+        ),
+    );
+    let rate_limiter = state.log_in_rate_limiter.clone();
+
+    let registration_open = state.registration_mode != RegistrationMode::Closed;
+
+    let attempted_at = OffsetDateTime::now_utc();
+    if let Err(retry_after) = rate_limiter.reserve_attempt(&attempt_keys, attempted_at) {
+        return get_too_many_attempts_response(&user_data.email, retry_after, registration_open);
+    }
+
+    let password_hash_cost = state.password_hash_cost;
+    let result = verify_credentials(user_data.clone(), state.user_store(), password_hash_cost);
+
+    match result {
+        Ok(ref user) => {
+            rate_limiter.reset(&attempt_keys);
+            upgrade_password_hash(
+                state.user_store(),
+                user,
+                &user_data.password,
+                password_hash_cost,
+            );
+        }
+        // The reserved attempt already counts as a failure.
+        Err(AuthError::InvalidCredentials) => {}
+        Err(_) => rate_limiter.release(&attempt_keys, attempted_at),
+    }
+
+    result
         .map(|user| {
             let cookie_duration = if user_data.remember_me.is_some() {
-                REMEMBER_ME_COOKIE_DURATION
+                state.remember_me_cookie_duration
             } else {
                 state.cookie_duration
             };
+            let landing_page_route = get_landing_page_route(state.user_store(), user.id());
 
-            set_auth_cookie(jar.clone(), user.id(), cookie_duration)
-                .map(|updated_jar| {
-                    (
-                        StatusCode::SEE_OTHER,
-                        HxRedirect(Uri::from_static(endpoints::DASHBOARD)),
-                        updated_jar,
-                    )
-                })
-                .map_err(|err| {
-                    tracing::error!("Error setting auth cookie: {err}");
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        HxRedirect(Uri::from_static(endpoints::INTERNAL_ERROR)),
-                        invalidate_auth_cookie(jar),
-                    )
-                })
+            set_auth_cookie(
+                jar.clone(),
+                user.id(),
+                user.session_generation(),
+                cookie_duration,
+            )
+            .map(|updated_jar| {
+                (
+                    StatusCode::SEE_OTHER,
+                    HxRedirect(Uri::from_static(landing_page_route)),
+                    updated_jar,
+                )
+            })
+            .map_err(|err| {
+                tracing::error!("Error setting auth cookie: {err}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    HxRedirect(Uri::from_static(endpoints::INTERNAL_ERROR)),
+                    invalidate_auth_cookie(jar),
+                )
+            })
         })
         .map_err(|e| LogInFormTemplate {
             email_input: EmailInputTemplate {
@@ -128,11 +223,77 @@ where
                     }
                 },
             },
+            registration_open,
             ..Default::default()
         })
         .into_response()
 }
 
+/// Rehash the user's password if it was hashed with a lower cost than `cost`.
+///
+/// Failing to rehash is logged but does not stop the user from logging in.
+fn upgrade_password_hash(
+    user_store: &mut impl UserStore,
+    user: &User,
+    raw_password: &str,
+    cost: u32,
+) {
+    if !user.password_hash().needs_rehash(cost) {
+        return;
+    }
+
+    // The password has already been verified, so it does not need to meet the current password
+    // rules to be rehashed.
+    let result = PasswordHash::new(ValidatedPassword::new_unchecked(raw_password), cost)
+        .map_err(|error| error.to_string())
+        .and_then(|password_hash| {
+            user_store
+                .update_password_hash(user.id(), password_hash)
+                .map_err(|error| error.to_string())
+        });
+
+    if let Err(error) = result {
+        tracing::warn!(
+            "Could not upgrade the password hash for user {}: {error}",
+            user.id()
+        );
+    }
+}
+
+fn get_too_many_attempts_response(
+    email: &str,
+    retry_after: Duration,
+    registration_open: bool,
+) -> Response {
+    // Round up so that the client is never told to retry before the block is lifted.
+    let seconds = ((retry_after.whole_milliseconds() + 999) / 1000).max(1);
+    let minutes = (seconds + 59) / 60;
+    let error_message = format!(
+        "Too many failed log-in attempts. Try again in {} minute{}.",
+        minutes,
+        if minutes > 1 { "s" } else { "" }
+    );
+
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(RETRY_AFTER, seconds.to_string())],
+        LogInFormTemplate {
+            email_input: EmailInputTemplate {
+                value: email,
+                error_message: "",
+            },
+            password_input: PasswordInputTemplate {
+                value: "",
+                min_length: 0,
+                error_message: &error_message,
+            },
+            registration_open,
+            ..Default::default()
+        },
+    )
+        .into_response()
+}
+
 const INVALID_CREDENTIALS_ERROR_MSG: &str = "Incorrect email or password.";
 
 #[cfg(test)]
@@ -141,8 +302,8 @@ mod log_in_tests {
 
     use axum::{
         body::Body,
-        extract::State,
-        http::{header::SET_COOKIE, Response, StatusCode},
+        extract::{Query, State},
+        http::{header::SET_COOKIE, HeaderMap, Response, StatusCode},
         routing::post,
         Form, Router,
     };
@@ -150,25 +311,29 @@ mod log_in_tests {
     use axum_htmx::HX_REDIRECT;
     use axum_test::TestServer;
     use email_address::EmailAddress;
+    use rusqlite::Connection;
     use time::{Duration, OffsetDateTime};
 
     use crate::{
         auth::{
-            cookie::{COOKIE_EXPIRY, COOKIE_USER_ID},
+            cookie::{COOKIE_EXPIRY, COOKIE_SESSION_GENERATION, COOKIE_USER_ID},
             log_in::LogInData,
+            rate_limit::LogInRateLimiter,
         },
         models::{
-            Category, CategoryError, CategoryName, DatabaseID, PasswordHash, Transaction,
-            TransactionBuilder, TransactionError, User, UserID, ValidatedPassword,
+            Category, CategoryError, CategoryName, DatabaseID, LandingPage, PasswordHash,
+            Transaction, TransactionBuilder, TransactionError, User, UserID, ValidatedPassword,
         },
         routes::{
             endpoints,
-            log_in::{post_log_in, INVALID_CREDENTIALS_ERROR_MSG, REMEMBER_ME_COOKIE_DURATION},
+            log_in::{get_log_in_page, post_log_in, LogInPageQuery, INVALID_CREDENTIALS_ERROR_MSG},
         },
         stores::{
-            transaction::TransactionQuery, CategoryStore, TransactionStore, UserError, UserStore,
+            sql_store::create_app_state,
+            transaction::{BatchOutcome, NewTransaction, TransactionQuery},
+            CategoryStore, TransactionStore, UserError, UserStore,
         },
-        AppState,
+        AppState, RegistrationMode,
     };
 
     #[derive(Clone)]
@@ -193,6 +358,15 @@ mod log_in_tests {
             Ok(user)
         }
 
+        fn create_with_invite(
+            &mut self,
+            _email: email_address::EmailAddress,
+            _password_hash: PasswordHash,
+            _invite_code: &str,
+        ) -> Result<User, UserError> {
+            todo!()
+        }
+
         fn get(&self, id: UserID) -> Result<User, UserError> {
             self.users
                 .iter()
@@ -208,6 +382,74 @@ mod log_in_tests {
                 .ok_or(UserError::NotFound)
                 .map(|user| user.to_owned())
         }
+
+        fn set_password(
+            &mut self,
+            _id: UserID,
+            _password_hash: PasswordHash,
+        ) -> Result<User, UserError> {
+            todo!()
+        }
+
+        fn increment_session_generation(&mut self, _id: UserID) -> Result<User, UserError> {
+            todo!()
+        }
+
+        fn update_password_hash(
+            &mut self,
+            _id: UserID,
+            _password_hash: PasswordHash,
+        ) -> Result<(), UserError> {
+            todo!()
+        }
+
+        fn set_email(&mut self, _id: UserID, _email: EmailAddress) -> Result<User, UserError> {
+            todo!()
+        }
+
+        fn delete(&mut self, _id: UserID) -> Result<(), UserError> {
+            todo!()
+        }
+
+        fn get_preferences(
+            &self,
+            _user_id: UserID,
+        ) -> Result<crate::models::UserPreferences, UserError> {
+            Ok(crate::models::UserPreferences::default())
+        }
+
+        fn set_timezone(
+            &mut self,
+            _user_id: UserID,
+            _timezone: &str,
+        ) -> Result<crate::models::UserPreferences, UserError> {
+            todo!()
+        }
+
+        fn set_theme(
+            &mut self,
+            _user_id: UserID,
+            _theme: crate::models::Theme,
+        ) -> Result<crate::models::UserPreferences, UserError> {
+            todo!()
+        }
+
+        fn set_landing_page(
+            &mut self,
+            _user_id: UserID,
+            _landing_page: crate::models::LandingPage,
+        ) -> Result<crate::models::UserPreferences, crate::stores::UserError> {
+            todo!()
+        }
+
+        fn set_date_periods(
+            &mut self,
+            _user_id: UserID,
+            _week_start: crate::models::WeekStart,
+            _month_start_day: u8,
+        ) -> Result<crate::models::UserPreferences, crate::stores::UserError> {
+            todo!()
+        }
     }
 
     #[derive(Clone)]
@@ -225,6 +467,27 @@ mod log_in_tests {
         fn get_by_user(&self, _user_id: UserID) -> Result<Vec<Category>, CategoryError> {
             todo!()
         }
+
+        fn search(
+            &self,
+            _user_id: UserID,
+            _prefix: &str,
+            _limit: u64,
+        ) -> Result<Vec<Category>, CategoryError> {
+            todo!()
+        }
+
+        fn suggest(
+            &self,
+
+            _user_id: UserID,
+
+            _description: &str,
+
+            _limit: u64,
+        ) -> Result<Vec<Category>, CategoryError> {
+            todo!()
+        }
     }
 
     #[derive(Clone)]
@@ -246,6 +509,13 @@ mod log_in_tests {
             todo!()
         }
 
+        fn create_batch(
+            &mut self,
+            _transactions: Vec<NewTransaction>,
+        ) -> Result<Vec<BatchOutcome>, TransactionError> {
+            todo!()
+        }
+
         fn get(&self, _id: DatabaseID) -> Result<Transaction, TransactionError> {
             todo!()
         }
@@ -260,6 +530,53 @@ mod log_in_tests {
         ) -> Result<Vec<Transaction>, TransactionError> {
             todo!()
         }
+
+        fn count_query(&self, _filter: TransactionQuery) -> Result<u64, TransactionError> {
+            todo!()
+        }
+
+        fn sum_query(&self, _filter: TransactionQuery) -> Result<f64, TransactionError> {
+            todo!()
+        }
+
+        fn set_ignored(
+            &mut self,
+            _id: DatabaseID,
+            _ignored: bool,
+        ) -> Result<Transaction, TransactionError> {
+            todo!()
+        }
+
+        fn set_reconciled(&mut self, _ids: &[DatabaseID]) -> Result<(), TransactionError> {
+            todo!()
+        }
+
+        fn update(
+            &mut self,
+            _id: DatabaseID,
+            _builder: TransactionBuilder,
+        ) -> Result<Transaction, TransactionError> {
+            todo!()
+        }
+
+        fn delete(&mut self, _id: DatabaseID) -> Result<(), TransactionError> {
+            todo!()
+        }
+
+        fn get_duplicates(
+            &self,
+            _user_id: UserID,
+        ) -> Result<Vec<Vec<Transaction>>, TransactionError> {
+            todo!()
+        }
+
+        fn merge_duplicates(
+            &mut self,
+            _keep_id: DatabaseID,
+            _duplicate_ids: &[DatabaseID],
+        ) -> Result<Transaction, TransactionError> {
+            todo!()
+        }
     }
 
     type TestAppState = AppState<DummyCategoryStore, DummyTransactionStore, StubUserStore>;
@@ -326,9 +643,11 @@ mod log_in_tests {
 
     #[tokio::test]
     async fn remember_me_extends_auth_cookie_through_form() {
+        let mut state = get_test_app_config();
+        state.remember_me_cookie_duration = Duration::days(3);
         let app = Router::new()
             .route(endpoints::LOG_IN, post(post_log_in))
-            .with_state(get_test_app_config());
+            .with_state(state);
         let server = TestServer::new(app).expect("Could not create test server.");
         let form = [
             ("email", "test@test.com"),
@@ -343,7 +662,7 @@ mod log_in_tests {
         let auth_cookie = response.cookie(COOKIE_USER_ID);
         assert_date_time_close!(
             auth_cookie.expires_datetime().unwrap(),
-            OffsetDateTime::now_utc() + REMEMBER_ME_COOKIE_DURATION
+            OffsetDateTime::now_utc() + Duration::days(3)
         );
     }
 
@@ -386,6 +705,137 @@ mod log_in_tests {
         assert_body_contains_message(response, INVALID_CREDENTIALS_ERROR_MSG).await;
     }
 
+    #[tokio::test]
+    async fn log_in_is_blocked_after_burst_of_failures() {
+        let mut state = get_test_app_config();
+        state.log_in_rate_limiter = LogInRateLimiter::new(3, Duration::minutes(15));
+        let app = Router::new()
+            .route(endpoints::LOG_IN, post(post_log_in))
+            .with_state(state);
+        let server = TestServer::new(app).expect("Could not create test server.");
+
+        for _ in 0..3 {
+            let response = server
+                .post(endpoints::LOG_IN)
+                .form(&[("email", "test@test.com"), ("password", "wrongpassword")])
+                .await;
+            response.assert_status_ok();
+            response.assert_text_contains(INVALID_CREDENTIALS_ERROR_MSG);
+        }
+
+        // Even the correct password is rejected while the email is blocked.
+        let response = server
+            .post(endpoints::LOG_IN)
+            .form(&[("email", "test@test.com"), ("password", "test")])
+            .await;
+
+        response.assert_status(StatusCode::TOO_MANY_REQUESTS);
+        response.assert_text_contains("Try again in 15 minutes.");
+        assert_eq!(response.header("retry-after"), "900");
+        assert!(response.maybe_cookie(COOKIE_USER_ID).is_none());
+    }
+
+    #[tokio::test]
+    async fn log_in_blocks_unknown_email_same_as_known_email() {
+        let mut state = get_test_app_config();
+        state.log_in_rate_limiter = LogInRateLimiter::new(2, Duration::minutes(15));
+        let app = Router::new()
+            .route(endpoints::LOG_IN, post(post_log_in))
+            .with_state(state);
+        let server = TestServer::new(app).expect("Could not create test server.");
+
+        for email in ["test@test.com", "unknown@test.com"] {
+            for _ in 0..2 {
+                server
+                    .post(endpoints::LOG_IN)
+                    .form(&[("email", email), ("password", "wrongpassword")])
+                    .await
+                    .assert_status_ok();
+            }
+
+            server
+                .post(endpoints::LOG_IN)
+                .form(&[("email", email), ("password", "wrongpassword")])
+                .await
+                .assert_status(StatusCode::TOO_MANY_REQUESTS);
+        }
+    }
+
+    #[tokio::test]
+    async fn successful_log_in_resets_failed_attempts() {
+        let mut state = get_test_app_config();
+        state.log_in_rate_limiter = LogInRateLimiter::new(2, Duration::minutes(15));
+        let app = Router::new()
+            .route(endpoints::LOG_IN, post(post_log_in))
+            .with_state(state);
+        let server = TestServer::new(app).expect("Could not create test server.");
+        let bad_form = [("email", "test@test.com"), ("password", "wrongpassword")];
+
+        server.post(endpoints::LOG_IN).form(&bad_form).await;
+        server
+            .post(endpoints::LOG_IN)
+            .form(&[("email", "test@test.com"), ("password", "test")])
+            .await
+            .assert_status(StatusCode::SEE_OTHER);
+        server.post(endpoints::LOG_IN).form(&bad_form).await;
+
+        server
+            .post(endpoints::LOG_IN)
+            .form(&bad_form)
+            .await
+            .assert_status_ok();
+    }
+
+    #[tokio::test]
+    async fn log_in_page_displays_session_expired_message() {
+        let response = get_log_in_page(
+            State(get_test_app_config()),
+            Query(LogInPageQuery { expired: true }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_body_contains_message(response, "Your session has expired").await;
+    }
+
+    #[tokio::test]
+    async fn log_in_page_omits_session_expired_message_by_default() {
+        let response = get_log_in_page(
+            State(get_test_app_config()),
+            Query(LogInPageQuery::default()),
+        )
+        .await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        assert!(!String::from_utf8_lossy(&body).contains("Your session has expired"));
+    }
+
+    #[tokio::test]
+    async fn log_in_page_links_to_registration_when_open() {
+        let response = get_log_in_page(
+            State(get_test_app_config()),
+            Query(LogInPageQuery::default()),
+        )
+        .await;
+
+        assert_body_contains_message(response, endpoints::REGISTER).await;
+    }
+
+    #[tokio::test]
+    async fn log_in_page_hides_registration_link_when_closed() {
+        let mut state = get_test_app_config();
+        state.registration_mode = RegistrationMode::Closed;
+
+        let response = get_log_in_page(State(state), Query(LogInPageQuery::default())).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        assert!(!String::from_utf8_lossy(&body).contains(endpoints::REGISTER));
+    }
+
     fn get_test_app_config() -> TestAppState {
         let mut state = AppState::new(
             "42",
@@ -393,6 +843,8 @@ mod log_in_tests {
             DummyTransactionStore {},
             StubUserStore { users: vec![] },
         );
+        // Match the cost of the test user's password hash so that it is not rehashed.
+        state.password_hash_cost = 4;
 
         state
             .user_store()
@@ -409,7 +861,7 @@ mod log_in_tests {
         let state = get_test_app_config();
         let jar = PrivateCookieJar::new(state.cookie_key().to_owned());
 
-        post_log_in(State(state), jar, Form(log_in_form)).await
+        post_log_in(State(state), None, HeaderMap::new(), jar, Form(log_in_form)).await
     }
 
     fn assert_hx_redirect(response: &Response<Body>, want_location: &str) {
@@ -427,7 +879,7 @@ mod log_in_tests {
             let cookie = Cookie::parse(cookie_string).unwrap();
 
             match cookie.name() {
-                COOKIE_USER_ID | COOKIE_EXPIRY => {
+                COOKIE_USER_ID | COOKIE_EXPIRY | COOKIE_SESSION_GENERATION => {
                     assert!(cookie.expires_datetime() > Some(OffsetDateTime::now_utc()));
                     found_cookies.insert(cookie.name().to_string());
                 }
@@ -448,6 +900,13 @@ mod log_in_tests {
             COOKIE_EXPIRY,
             found_cookies
         );
+
+        assert!(
+            found_cookies.contains(COOKIE_SESSION_GENERATION),
+            "could not find cookie '{}' in {:?}",
+            COOKIE_SESSION_GENERATION,
+            found_cookies
+        );
     }
 
     async fn assert_body_contains_message(response: Response<Body>, message: &str) {
@@ -463,4 +922,74 @@ mod log_in_tests {
             text
         );
     }
+
+    #[tokio::test]
+    async fn log_in_upgrades_legacy_password_hash() {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        state.password_hash_cost = 5;
+        let legacy_password_hash =
+            PasswordHash::new(ValidatedPassword::new_unchecked("test"), 4).unwrap();
+        let user = state
+            .user_store()
+            .create(
+                EmailAddress::new_unchecked("test@test.com"),
+                legacy_password_hash,
+            )
+            .unwrap();
+        let jar = PrivateCookieJar::new(state.cookie_key().to_owned());
+
+        let response = post_log_in(
+            State(state.clone()),
+            None,
+            HeaderMap::new(),
+            jar,
+            Form(LogInData {
+                email: "test@test.com".to_string(),
+                password: "test".to_string(),
+                remember_me: None,
+            }),
+        )
+        .await;
+
+        assert_hx_redirect(&response, endpoints::DASHBOARD);
+        let upgraded_user = state.user_store().get(user.id()).unwrap();
+        assert_eq!(upgraded_user.password_hash().cost(), Some(5));
+        assert!(upgraded_user.password_hash().verify("test").unwrap());
+        assert_eq!(
+            upgraded_user.session_generation(),
+            user.session_generation()
+        );
+    }
+
+    #[tokio::test]
+    async fn log_in_redirects_to_landing_page() {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        let user = state
+            .user_store()
+            .create(
+                EmailAddress::new_unchecked("test@test.com"),
+                PasswordHash::new(ValidatedPassword::new_unchecked("test"), 4).unwrap(),
+            )
+            .unwrap();
+        state
+            .user_store()
+            .set_landing_page(user.id(), LandingPage::Transactions)
+            .unwrap();
+        let jar = PrivateCookieJar::new(state.cookie_key().to_owned());
+
+        let response = post_log_in(
+            State(state),
+            None,
+            HeaderMap::new(),
+            jar,
+            Form(LogInData {
+                email: "test@test.com".to_string(),
+                password: "test".to_string(),
+                remember_me: None,
+            }),
+        )
+        .await;
+
+        assert_hx_redirect(&response, endpoints::TRANSACTIONS);
+    }
 }
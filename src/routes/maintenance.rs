@@ -0,0 +1,236 @@
+//! Running database maintenance tasks from the settings page.
+
+use askama_axum::Template;
+use axum::{
+    extract::State,
+    response::{IntoResponse, Response},
+    Extension,
+};
+
+use crate::{
+    db::maintenance::{MaintenanceReport, MaintenanceTask},
+    models::UserID,
+    routes::get_internal_server_error_redirect,
+    stores::sql_store::SQLAppState,
+};
+
+/// The outcome of a maintenance task as a short message.
+///
+/// An empty string means that there is no message to display.
+#[derive(Template)]
+#[template(path = "partials/settings/maintenance_result.html")]
+struct MaintenanceResultTemplate<'a> {
+    message: &'a str,
+    error_message: &'a str,
+}
+
+/// Run VACUUM on the database and respond with how long it took and how much space it saved.
+pub async fn post_vacuum(
+    State(state): State<SQLAppState>,
+    Extension(user_id): Extension<UserID>,
+) -> Response {
+    run_maintenance_task(state, user_id, MaintenanceTask::Vacuum).await
+}
+
+/// Run ANALYZE on the database and respond with how long it took.
+pub async fn post_analyze(
+    State(state): State<SQLAppState>,
+    Extension(user_id): Extension<UserID>,
+) -> Response {
+    run_maintenance_task(state, user_id, MaintenanceTask::Analyze).await
+}
+
+/// Run `task` if `user_id` is the owner of the site and respond with a summary of the outcome.
+async fn run_maintenance_task(
+    mut state: SQLAppState,
+    user_id: UserID,
+    task: MaintenanceTask,
+) -> Response {
+    match state.user_store().get_owner_id() {
+        Ok(Some(owner_id)) if owner_id == user_id => {}
+        Ok(_) => {
+            return MaintenanceResultTemplate {
+                message: "",
+                error_message: "Only the owner of this site can run maintenance tasks.",
+            }
+            .into_response()
+        }
+        Err(error) => {
+            tracing::error!("Could not get the owner of the site: {error}");
+            return get_internal_server_error_redirect();
+        }
+    }
+
+    // VACUUM can take several seconds on a large database. Running the task on its own thread
+    // means that it is not cancelled part way through if the server shuts down before it
+    // finishes, since the runtime waits for blocking tasks before exiting.
+    let operation = state.operations.start(task.to_string());
+    let result = tokio::task::spawn_blocking(move || {
+        let _operation = operation;
+        state.user_store().run_maintenance(task)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(report)) => MaintenanceResultTemplate {
+            message: &describe_report(&report),
+            error_message: "",
+        }
+        .into_response(),
+        Ok(Err(error)) => {
+            tracing::error!("Could not run {task}: {error}");
+            MaintenanceResultTemplate {
+                message: "",
+                error_message: &format!("{task} failed, check the server logs for details."),
+            }
+            .into_response()
+        }
+        Err(error) => {
+            tracing::error!("The {task} task failed: {error}");
+            get_internal_server_error_redirect()
+        }
+    }
+}
+
+/// Summarise the duration and change in size from running a maintenance task.
+fn describe_report(report: &MaintenanceReport) -> String {
+    format!(
+        "{} finished in {:.2} seconds. The database went from {} to {}.",
+        report.task,
+        report.duration.as_secs_f64(),
+        format_bytes(report.size_before),
+        format_bytes(report.size_after)
+    )
+}
+
+/// Format a number of bytes with a binary unit, e.g. "1.5 MiB".
+pub(super) fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = UNITS[0];
+
+    for next_unit in &UNITS[1..] {
+        if size < 1024.0 {
+            break;
+        }
+
+        size /= 1024.0;
+        unit = next_unit;
+    }
+
+    format!("{size:.1} {unit}")
+}
+
+#[cfg(test)]
+mod maintenance_route_tests {
+    use std::time::Duration;
+
+    use axum_test::TestServer;
+    use email_address::EmailAddress;
+    use rusqlite::Connection;
+
+    use crate::{
+        auth::log_in::LogInData,
+        build_router,
+        db::maintenance::{MaintenanceReport, MaintenanceTask},
+        models::{PasswordHash, User, ValidatedPassword},
+        routes::endpoints,
+        stores::{
+            sql_store::{create_app_state, SQLAppState},
+            UserStore,
+        },
+    };
+
+    use super::{describe_report, format_bytes};
+
+    const PASSWORD: &str = "averysafeandsecurepassword";
+
+    fn create_user(state: &mut SQLAppState, email: &str) -> User {
+        state
+            .user_store()
+            .create(
+                EmailAddress::new_unchecked(email),
+                PasswordHash::new(ValidatedPassword::new_unchecked(PASSWORD), 4).unwrap(),
+            )
+            .unwrap()
+    }
+
+    async fn get_server_logged_in_as(state: SQLAppState, user: &User) -> TestServer {
+        let mut server = TestServer::new(build_router(state)).unwrap();
+        server.do_save_cookies();
+        server
+            .post(endpoints::LOG_IN)
+            .form(&LogInData {
+                email: user.email().to_string(),
+                password: PASSWORD.to_string(),
+                remember_me: None,
+            })
+            .await
+            .assert_status_see_other();
+
+        server
+    }
+
+    #[tokio::test]
+    async fn owner_can_run_maintenance() {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        state.password_hash_cost = 4;
+        let owner = create_user(&mut state, "foo@bar.baz");
+        let server = get_server_logged_in_as(state, &owner).await;
+
+        for (endpoint, task) in [
+            (endpoints::VACUUM, "VACUUM"),
+            (endpoints::ANALYZE, "ANALYZE"),
+        ] {
+            let response = server.post(endpoint).await;
+
+            response.assert_status_ok();
+            response.assert_text_contains(format!("{task} finished in"));
+        }
+    }
+
+    #[tokio::test]
+    async fn other_users_cannot_run_maintenance() {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        state.password_hash_cost = 4;
+        create_user(&mut state, "foo@bar.baz");
+        let other_user = create_user(&mut state, "qux@bar.baz");
+        let server = get_server_logged_in_as(state, &other_user).await;
+
+        for endpoint in [endpoints::VACUUM, endpoints::ANALYZE] {
+            let response = server.post(endpoint).await;
+
+            response.assert_status_ok();
+            response.assert_text_contains("Only the owner of this site can run maintenance tasks.");
+            assert!(!response.text().contains("finished in"));
+        }
+    }
+
+    #[test]
+    fn describes_report() {
+        let report = MaintenanceReport {
+            task: MaintenanceTask::Vacuum,
+            duration: Duration::from_millis(2500),
+            size_before: 3 * 1024 * 1024,
+            size_after: 1024 * 1024 + 512 * 1024,
+        };
+
+        assert_eq!(
+            describe_report(&report),
+            "VACUUM finished in 2.50 seconds. The database went from 3.0 MiB to 1.5 MiB."
+        );
+    }
+
+    #[test]
+    fn formats_bytes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1024), "1.0 KiB");
+        assert_eq!(format_bytes(5 * 1024 * 1024 * 1024), "5.0 GiB");
+    }
+}
@@ -2,43 +2,114 @@
 
 use askama_axum::Template;
 use axum::{
+    extract::{DefaultBodyLimit, State},
     http::{StatusCode, Uri},
     middleware,
     response::{Html, IntoResponse, Redirect, Response},
-    routing::{get, post},
-    Router,
+    routing::{delete, get, post, put},
+    Extension, Router,
 };
+use axum_extra::extract::PrivateCookieJar;
 use axum_htmx::HxRedirect;
 
-use category::{create_category, get_category};
+use api::{
+    create_api_transaction, create_api_transactions_batch, delete_api_transaction,
+    get_api_transaction, get_api_transactions, update_api_transaction,
+};
+use api_tokens::{create_api_token, delete_api_token};
+use audit_log::get_audit_log_page;
+use backup::get_backup;
+use category::{create_category, get_category, search_categories, suggest_categories};
+use change_password::{change_password, get_change_password_page};
+use configuration::{get_configuration, upload_configuration};
 use dashboard::get_dashboard_page;
+use duplicates::{get_duplicates_page, merge_duplicates};
+pub(crate) use errors::{
+    get_database_lock_error_response, get_database_unavailable_response, get_error_response,
+};
+use errors::{keep_json_errors, render_errors};
+use health::{get_healthz, get_readyz};
+use invites::{create_invite, delete_invite, get_invites_page};
+use limits::{limit_body_size, timeout_requests};
+pub use limits::{DEFAULT_MAX_REQUEST_BODY_SIZE, DEFAULT_REQUEST_TIMEOUT};
 use log_in::{get_log_in_page, post_log_in};
 use log_out::get_log_out;
+use maintenance::{post_analyze, post_vacuum};
+use monthly_report::get_monthly_report_page;
+use navigation::{get_nav_bar, NavbarTemplate};
+use palette::get_palette_results;
+use reconcile::{get_reconcile_page, reconcile_transactions};
 use register::{create_user, get_register_page};
+use search::{get_search_page, get_search_results};
+use settings::{
+    change_email, delete_account, get_settings_page, log_out_everywhere, set_date_periods,
+    set_landing_page, set_timezone,
+};
+use statement::get_statement_page;
+use summary::get_summary_text;
+use theme::{set_theme, theme_middleware};
 use tower_http::services::ServeDir;
-use transaction::{create_transaction, get_transaction};
-use transactions::get_transactions_page;
+pub use transaction::DEFAULT_SUSPICIOUS_TRANSACTION_AGE;
+use transaction::{
+    create_transaction, delete_transaction, get_transaction, set_transaction_ignored,
+};
+use transactions::{get_more_transactions, get_transactions_page, get_transactions_table_fragment};
+use webhooks::{
+    create_webhook, delete_webhook, get_webhooks_page, send_test_webhook, set_webhook_enabled,
+};
+use weekly_summary::get_weekly_summary_page;
 
 use crate::{
-    auth::middleware::{auth_guard, auth_guard_hx},
-    stores::sql_store::SQLAppState,
+    auth::{
+        cookie::has_unexpired_auth_cookie,
+        middleware::{api_auth_guard, auth_guard},
+    },
+    logging::record_matched_path,
+    models::{LandingPage, UserID},
+    proxy::secure_cookies_middleware,
+    stores::{sql_store::SQLAppState, CategoryStore, TransactionStore, UserStore},
+    AppState,
 };
 
+mod api;
+mod api_tokens;
+mod audit_log;
+mod backup;
 mod category;
+mod change_password;
+mod configuration;
 mod dashboard;
+mod duplicates;
 pub mod endpoints;
+mod errors;
+mod health;
+mod invites;
+mod limits;
 mod log_in;
 mod log_out;
+mod maintenance;
+mod monthly_report;
 mod navigation;
+mod palette;
+mod reconcile;
 mod register;
+mod search;
+mod settings;
+mod statement;
+mod summary;
 mod templates;
+pub(crate) mod theme;
 mod transaction;
 mod transactions;
+mod webhooks;
+mod weekly_summary;
 
 /// Return a router with all the app's routes.
 pub fn build_router(state: SQLAppState) -> Router {
     let unprotected_routes = Router::new()
         .route(endpoints::COFFEE, get(get_coffee))
+        .route(endpoints::HEALTHZ, get(get_healthz))
+        .route(endpoints::READYZ, get(get_readyz))
         .route(endpoints::LOG_IN, get(get_log_in_page))
         .route(endpoints::LOG_IN, post(post_log_in))
         .route(endpoints::LOG_OUT, get(get_log_out))
@@ -52,25 +123,117 @@ pub fn build_router(state: SQLAppState) -> Router {
     let protected_routes = Router::new()
         .route(endpoints::ROOT, get(get_index_page))
         .route(endpoints::DASHBOARD, get(get_dashboard_page))
+        .route(endpoints::CATEGORY_SEARCH, get(search_categories))
+        .route(endpoints::CATEGORY_SUGGESTIONS, get(suggest_categories))
         .route(endpoints::CATEGORY, get(get_category))
-        .route(endpoints::TRANSACTION, get(get_transaction))
+        .route(
+            endpoints::TRANSACTION,
+            get(get_transaction).delete(delete_transaction),
+        )
         .route(endpoints::TRANSACTIONS, get(get_transactions_page))
+        .route(endpoints::TRANSACTIONS_MORE, get(get_more_transactions))
+        .route(
+            endpoints::TRANSACTIONS_TABLE,
+            get(get_transactions_table_fragment),
+        )
+        .route(endpoints::DUPLICATES, get(get_duplicates_page))
+        .route(endpoints::DUPLICATES_MERGE, post(merge_duplicates))
+        .route(endpoints::WEEKLY_SUMMARY, get(get_weekly_summary_page))
+        .route(endpoints::MONTHLY_REPORT, get(get_monthly_report_page))
+        .route(endpoints::STATEMENT, get(get_statement_page))
+        .route(endpoints::RECONCILE, get(get_reconcile_page))
+        .route(endpoints::CHANGE_PASSWORD, get(get_change_password_page))
+        .route(endpoints::SETTINGS, get(get_settings_page))
+        .route(endpoints::BACKUP, get(get_backup))
+        .route(
+            endpoints::CONFIGURATION,
+            get(get_configuration).post(upload_configuration),
+        )
+        .route(endpoints::AUDIT_LOG, get(get_audit_log_page))
+        .route(endpoints::PALETTE, get(get_palette_results))
+        .route(endpoints::SEARCH, get(get_search_page))
+        .route(endpoints::SEARCH_RESULTS, get(get_search_results))
+        .route(endpoints::INVITES, get(get_invites_page))
+        .route(endpoints::WEBHOOKS, get(get_webhooks_page))
+        .route(endpoints::USER_CATEGORIES, post(create_category))
+        .route(endpoints::USER_TRANSACTIONS, post(create_transaction))
+        .route(
+            endpoints::TRANSACTION_IGNORED,
+            post(set_transaction_ignored),
+        )
+        .route(endpoints::RECONCILE, post(reconcile_transactions))
+        .route(endpoints::CHANGE_PASSWORD, post(change_password))
+        .route(endpoints::LOG_OUT_EVERYWHERE, post(log_out_everywhere))
+        .route(endpoints::CHANGE_EMAIL, post(change_email))
+        .route(endpoints::TIMEZONE, post(set_timezone))
+        .route(endpoints::THEME, post(set_theme))
+        .route(endpoints::LANDING_PAGE, post(set_landing_page))
+        .route(endpoints::DATE_PERIODS, post(set_date_periods))
+        .route(endpoints::DELETE_ACCOUNT, post(delete_account))
+        .route(endpoints::VACUUM, post(post_vacuum))
+        .route(endpoints::ANALYZE, post(post_analyze))
+        .route(endpoints::INVITES, post(create_invite))
+        .route(endpoints::INVITE, delete(delete_invite))
+        .route(endpoints::API_TOKENS, post(create_api_token))
+        .route(endpoints::API_TOKEN, delete(delete_api_token))
+        .route(endpoints::WEBHOOKS, post(create_webhook))
+        .route(endpoints::WEBHOOK, delete(delete_webhook))
+        .route(endpoints::WEBHOOK_ENABLED, post(set_webhook_enabled))
+        .route(endpoints::WEBHOOK_TEST, post(send_test_webhook))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            theme_middleware,
+        ))
         .layer(middleware::from_fn_with_state(state.clone(), auth_guard));
 
-    // These POST routes need to use the HX-REDIRECT header for auth redirects to work properly for
-    // HTMX requests.
-    let protected_routes = protected_routes.merge(
-        Router::new()
-            .route(endpoints::USER_CATEGORIES, post(create_category))
-            .route(endpoints::USER_TRANSACTIONS, post(create_transaction))
-            .layer(middleware::from_fn_with_state(state.clone(), auth_guard_hx)),
-    );
+    // The JSON API also accepts API tokens so that scripts do not need to log in.
+    let api_routes = Router::new()
+        .route(endpoints::API_TRANSACTIONS, get(get_api_transactions))
+        .route(endpoints::API_TRANSACTIONS, post(create_api_transaction))
+        .route(
+            endpoints::API_TRANSACTIONS_BATCH,
+            post(create_api_transactions_batch),
+        )
+        .route(endpoints::API_TRANSACTION, get(get_api_transaction))
+        .route(endpoints::API_TRANSACTION, put(update_api_transaction))
+        .route(endpoints::API_TRANSACTION, delete(delete_api_transaction))
+        .route(endpoints::SUMMARY_TEXT, get(get_summary_text))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            api_auth_guard,
+        ))
+        .layer(middleware::map_response(keep_json_errors));
+
+    let request_timeout = state.request_timeout;
+    let max_request_body_size = state.max_request_body_size;
+    let proxy_settings = state.proxy_settings;
+    let cookie_key = state.cookie_key().clone();
+    let demo_mode = state.demo_mode;
 
     protected_routes
+        .merge(api_routes)
         .merge(unprotected_routes)
+        .route_layer(middleware::from_fn(record_matched_path))
         .nest_service("/assets", ServeDir::new("assets/"))
         .fallback(get_404_not_found)
         .with_state(state)
+        .layer(middleware::from_fn_with_state(
+            (cookie_key, demo_mode),
+            render_errors,
+        ))
+        .layer(middleware::from_fn_with_state(
+            max_request_body_size,
+            limit_body_size,
+        ))
+        .layer(DefaultBodyLimit::max(max_request_body_size))
+        .layer(middleware::from_fn_with_state(
+            request_timeout,
+            timeout_requests,
+        ))
+        .layer(middleware::from_fn_with_state(
+            proxy_settings,
+            secure_cookies_middleware,
+        ))
 }
 
 /// Attempt to get a cup of coffee from the server.
@@ -78,9 +241,42 @@ async fn get_coffee() -> Response {
     (StatusCode::IM_A_TEAPOT, Html("I'm a teapot")).into_response()
 }
 
-/// The root path '/' redirects to the dashboard page.
-async fn get_index_page() -> Redirect {
-    Redirect::to(endpoints::DASHBOARD)
+/// The root path '/' redirects to the logged in user's landing page, which is the dashboard unless
+/// they have chosen another page.
+async fn get_index_page<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+) -> Redirect
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    Redirect::to(get_landing_page_route(state.user_store(), user_id))
+}
+
+/// The route of the page `landing_page`.
+pub(crate) fn landing_page_route(landing_page: LandingPage) -> &'static str {
+    match landing_page {
+        LandingPage::Dashboard => endpoints::DASHBOARD,
+        LandingPage::Transactions => endpoints::TRANSACTIONS,
+    }
+}
+
+/// The route of the page that the user `user_id` is sent to after logging in.
+///
+/// Falls back to the dashboard if the user's preferences could not be loaded.
+pub(crate) fn get_landing_page_route<U>(user_store: &U, user_id: UserID) -> &'static str
+where
+    U: UserStore,
+{
+    match user_store.get_preferences(user_id) {
+        Ok(preferences) => landing_page_route(preferences.landing_page),
+        Err(error) => {
+            tracing::error!("Could not get the preferences for user {user_id}: {error}");
+            endpoints::DASHBOARD
+        }
+    }
 }
 
 /// Get a response that will redirect the client to the internal server error 500 page.
@@ -105,25 +301,416 @@ async fn get_internal_server_error_page() -> Response {
 
 #[derive(Template)]
 #[template(path = "views/not_found_404.html")]
-struct NotFoundTemplate;
+struct NotFoundTemplate<'a> {
+    /// The navbar for logged in users, so that they can get back to their pages.
+    navbar: Option<NavbarTemplate<'a>>,
+}
+
+/// Get the 404 page, which shows the navbar if `jar` holds an auth cookie.
+pub(super) fn get_not_found_page(jar: &PrivateCookieJar, demo_mode: bool) -> Response {
+    let navbar = has_unexpired_auth_cookie(jar).then(|| get_nav_bar("", demo_mode));
+
+    (StatusCode::NOT_FOUND, NotFoundTemplate { navbar }).into_response()
+}
 
-async fn get_404_not_found() -> Response {
-    (StatusCode::NOT_FOUND, NotFoundTemplate).into_response()
+async fn get_404_not_found(State(state): State<SQLAppState>, jar: PrivateCookieJar) -> Response {
+    get_not_found_page(&jar, state.demo_mode)
 }
 
 #[cfg(test)]
 mod root_route_tests {
     use askama_axum::IntoResponse;
-    use axum::http::StatusCode;
+    use axum::{extract::State, http::StatusCode, Extension};
+    use rusqlite::Connection;
+
+    use crate::{
+        models::{LandingPage, PasswordHash, User},
+        routes::{endpoints, get_index_page},
+        stores::{
+            sql_store::{create_app_state, SQLAppState},
+            UserStore,
+        },
+    };
 
-    use crate::routes::{endpoints, get_index_page};
+    fn get_test_state_and_user() -> (SQLAppState, User) {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        let user = state
+            .user_store()
+            .create(
+                "test@test.com".parse().unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+
+        (state, user)
+    }
 
     #[tokio::test]
     async fn root_redirects_to_dashboard() {
-        let response = get_index_page().await.into_response();
+        let (state, user) = get_test_state_and_user();
+
+        let response = get_index_page(State(state), Extension(user.id()))
+            .await
+            .into_response();
         assert_eq!(response.status(), StatusCode::SEE_OTHER);
 
         let location = response.headers().get("location").unwrap();
         assert_eq!(location, endpoints::DASHBOARD);
     }
+
+    #[tokio::test]
+    async fn root_redirects_to_landing_page() {
+        let cases = [
+            (LandingPage::Dashboard, endpoints::DASHBOARD),
+            (LandingPage::Transactions, endpoints::TRANSACTIONS),
+        ];
+
+        for (landing_page, want) in cases {
+            let (mut state, user) = get_test_state_and_user();
+            state
+                .user_store()
+                .set_landing_page(user.id(), landing_page)
+                .unwrap();
+
+            let response = get_index_page(State(state), Extension(user.id()))
+                .await
+                .into_response();
+
+            let location = response.headers().get("location").unwrap();
+            assert_eq!(location, want, "got {location:?} for {landing_page:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod auth_redirect_tests {
+    use std::collections::BTreeSet;
+
+    use axum::http::{HeaderName, HeaderValue, Method, StatusCode};
+    use axum_extra::extract::cookie::Cookie;
+    use axum_test::{TestRequest, TestServer};
+    use regex::Regex;
+    use rusqlite::Connection;
+    use serde_json::Value;
+    use time::Duration;
+
+    use crate::{
+        auth::log_in::LogInData,
+        build_router,
+        models::{PasswordHash, User, ValidatedPassword},
+        stores::{
+            sql_store::{create_app_state, SQLAppState},
+            UserStore,
+        },
+    };
+
+    use super::endpoints;
+
+    const EMAIL: &str = "test@test.com";
+    const PASSWORD: &str = "averysafeandsecurepassword";
+
+    /// Every route that requires the user to be logged in.
+    ///
+    /// [protected_routes_match_router] checks that this list matches the router.
+    fn get_protected_routes() -> Vec<(Method, &'static str)> {
+        vec![
+            (Method::GET, endpoints::ROOT),
+            (Method::GET, endpoints::DASHBOARD),
+            (Method::GET, endpoints::CATEGORY),
+            (Method::GET, endpoints::TRANSACTION),
+            (Method::DELETE, endpoints::TRANSACTION),
+            (Method::GET, endpoints::TRANSACTIONS),
+            (Method::GET, endpoints::TRANSACTIONS_MORE),
+            (Method::GET, endpoints::TRANSACTIONS_TABLE),
+            (Method::GET, endpoints::DUPLICATES),
+            (Method::POST, endpoints::DUPLICATES_MERGE),
+            (Method::GET, endpoints::SUMMARY_TEXT),
+            (Method::GET, endpoints::WEEKLY_SUMMARY),
+            (Method::GET, endpoints::MONTHLY_REPORT),
+            (Method::GET, endpoints::STATEMENT),
+            (Method::GET, endpoints::CONFIGURATION),
+            (Method::GET, endpoints::CATEGORY_SEARCH),
+            (Method::GET, endpoints::CATEGORY_SUGGESTIONS),
+            (Method::POST, endpoints::CONFIGURATION),
+            (Method::GET, endpoints::RECONCILE),
+            (Method::GET, endpoints::CHANGE_PASSWORD),
+            (Method::GET, endpoints::SETTINGS),
+            (Method::GET, endpoints::BACKUP),
+            (Method::GET, endpoints::AUDIT_LOG),
+            (Method::GET, endpoints::PALETTE),
+            (Method::GET, endpoints::SEARCH),
+            (Method::GET, endpoints::SEARCH_RESULTS),
+            (Method::GET, endpoints::INVITES),
+            (Method::GET, endpoints::WEBHOOKS),
+            (Method::POST, endpoints::USER_CATEGORIES),
+            (Method::POST, endpoints::USER_TRANSACTIONS),
+            (Method::POST, endpoints::TRANSACTION_IGNORED),
+            (Method::POST, endpoints::RECONCILE),
+            (Method::POST, endpoints::VACUUM),
+            (Method::POST, endpoints::ANALYZE),
+            (Method::POST, endpoints::CHANGE_PASSWORD),
+            (Method::POST, endpoints::LOG_OUT_EVERYWHERE),
+            (Method::POST, endpoints::CHANGE_EMAIL),
+            (Method::POST, endpoints::TIMEZONE),
+            (Method::POST, endpoints::THEME),
+            (Method::POST, endpoints::LANDING_PAGE),
+            (Method::POST, endpoints::DATE_PERIODS),
+            (Method::POST, endpoints::DELETE_ACCOUNT),
+            (Method::POST, endpoints::INVITES),
+            (Method::DELETE, endpoints::INVITE),
+            (Method::POST, endpoints::API_TOKENS),
+            (Method::DELETE, endpoints::API_TOKEN),
+            (Method::POST, endpoints::WEBHOOKS),
+            (Method::DELETE, endpoints::WEBHOOK),
+            (Method::POST, endpoints::WEBHOOK_ENABLED),
+            (Method::POST, endpoints::WEBHOOK_TEST),
+            (Method::GET, endpoints::API_TRANSACTIONS),
+            (Method::POST, endpoints::API_TRANSACTIONS),
+            (Method::POST, endpoints::API_TRANSACTIONS_BATCH),
+            (Method::GET, endpoints::API_TRANSACTION),
+            (Method::PUT, endpoints::API_TRANSACTION),
+            (Method::DELETE, endpoints::API_TRANSACTION),
+        ]
+    }
+
+    /// Every path in [endpoints], e.g. `/transactions/:transaction_id`.
+    fn get_all_endpoints() -> Vec<&'static str> {
+        Regex::new(r#"pub const [A-Z_]+: &str = "(/[^"]*)";"#)
+            .unwrap()
+            .captures_iter(include_str!("endpoints.rs"))
+            .map(|captures| captures.get(1).unwrap().as_str())
+            .collect()
+    }
+
+    /// Fill in the parameters in `endpoint`, e.g. `/transactions/:transaction_id`, with `1`.
+    fn fill_endpoint(endpoint: &str) -> String {
+        endpoint
+            .split('/')
+            .map(|segment| {
+                if segment.starts_with(':') {
+                    "1"
+                } else {
+                    segment
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    fn get_test_state_and_user() -> (SQLAppState, User) {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        state.password_hash_cost = 4;
+        let user = state
+            .user_store()
+            .create(
+                EMAIL.parse().unwrap(),
+                PasswordHash::new(ValidatedPassword::new_unchecked(PASSWORD), 4).unwrap(),
+            )
+            .unwrap();
+
+        (state, user)
+    }
+
+    fn get_test_server() -> TestServer {
+        let (state, _) = get_test_state_and_user();
+
+        TestServer::new(build_router(state)).expect("Could not create test server.")
+    }
+
+    /// Log in and get the auth cookies, including ones that have already expired.
+    async fn log_in(server: &TestServer) -> Vec<Cookie<'static>> {
+        let response = server
+            .post(endpoints::LOG_IN)
+            .form(&LogInData {
+                email: EMAIL.to_string(),
+                password: PASSWORD.to_string(),
+                remember_me: None,
+            })
+            .await;
+
+        response.assert_status_see_other();
+        response
+            .cookies()
+            .iter()
+            .cloned()
+            .map(|mut cookie| {
+                // Simulate a client that keeps sending cookies after they expire so that the
+                // server-side expiry check is exercised.
+                cookie.unset_expires();
+                cookie
+            })
+            .collect()
+    }
+
+    fn with_htmx_header(request: TestRequest) -> TestRequest {
+        request.add_header(
+            HeaderName::from_static("hx-request"),
+            HeaderValue::from_static("true"),
+        )
+    }
+
+    fn with_json_accept_header(request: TestRequest) -> TestRequest {
+        request.add_header(
+            HeaderName::from_static("accept"),
+            HeaderValue::from_static("application/json"),
+        )
+    }
+
+    fn with_cookies(mut request: TestRequest, cookies: &[Cookie<'static>]) -> TestRequest {
+        for cookie in cookies {
+            request = request.add_cookie(cookie.clone());
+        }
+
+        request
+    }
+
+    /// Check that browsers, HTMX and API clients sending `cookies` are all told that their session
+    /// has expired by every protected route.
+    async fn assert_session_expired_for_all_clients(
+        server: &TestServer,
+        cookies: &[Cookie<'static>],
+    ) {
+        for (method, endpoint) in get_protected_routes() {
+            let path = fill_endpoint(endpoint);
+
+            let response = with_cookies(server.method(method.clone(), &path), cookies).await;
+            assert_eq!(
+                response.status_code(),
+                StatusCode::SEE_OTHER,
+                "browser {method} {path}"
+            );
+            assert_eq!(
+                response.header("location"),
+                endpoints::LOG_IN_SESSION_EXPIRED,
+                "browser {method} {path}"
+            );
+
+            let request = with_htmx_header(server.method(method.clone(), &path));
+            let response = with_cookies(request, cookies).await;
+            assert_eq!(
+                response.status_code(),
+                StatusCode::OK,
+                "HTMX {method} {path}"
+            );
+            assert_eq!(
+                response.header("hx-redirect"),
+                endpoints::LOG_IN_SESSION_EXPIRED,
+                "HTMX {method} {path}"
+            );
+
+            let request = with_json_accept_header(server.method(method.clone(), &path));
+            let response = with_cookies(request, cookies).await;
+            assert_eq!(
+                response.status_code(),
+                StatusCode::UNAUTHORIZED,
+                "API {method} {path}"
+            );
+            assert_eq!(
+                response.json::<Value>()["code"],
+                "unauthorized",
+                "API {method} {path}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn protected_routes_match_router() {
+        let server = get_test_server();
+        let mut router_endpoints = BTreeSet::new();
+
+        // Without a session, only protected routes send HTMX requests to the log-in page with the
+        // session expired message.
+        for endpoint in get_all_endpoints() {
+            let path = fill_endpoint(endpoint);
+
+            for method in [Method::GET, Method::POST, Method::PUT, Method::DELETE] {
+                let response = with_htmx_header(server.method(method, &path)).await;
+
+                if response
+                    .maybe_header("hx-redirect")
+                    .is_some_and(|location| location == endpoints::LOG_IN_SESSION_EXPIRED)
+                {
+                    router_endpoints.insert(endpoint);
+                }
+            }
+        }
+
+        let listed_endpoints = get_protected_routes()
+            .into_iter()
+            .map(|(_, endpoint)| endpoint)
+            .collect::<BTreeSet<_>>();
+        assert_eq!(listed_endpoints, router_endpoints);
+    }
+
+    #[tokio::test]
+    async fn plain_requests_without_session_get_see_other_redirect() {
+        let server = get_test_server();
+
+        for (method, endpoint) in get_protected_routes() {
+            let path = fill_endpoint(endpoint);
+            let response = server.method(method.clone(), &path).await;
+
+            assert_eq!(
+                response.status_code(),
+                StatusCode::SEE_OTHER,
+                "{method} {path}"
+            );
+            assert_eq!(
+                response.header("location"),
+                endpoints::LOG_IN,
+                "{method} {path}"
+            );
+            assert!(
+                response.maybe_header("hx-redirect").is_none(),
+                "{method} {path}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn htmx_requests_without_session_get_htmx_redirect() {
+        let server = get_test_server();
+
+        for (method, endpoint) in get_protected_routes() {
+            let path = fill_endpoint(endpoint);
+            let response = with_htmx_header(server.method(method.clone(), &path)).await;
+
+            assert_eq!(response.status_code(), StatusCode::OK, "{method} {path}");
+            assert_eq!(
+                response.header("hx-redirect"),
+                endpoints::LOG_IN_SESSION_EXPIRED,
+                "{method} {path}"
+            );
+            assert!(
+                response.maybe_header("location").is_none(),
+                "{method} {path}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_with_expired_session_are_told_it_expired() {
+        let (mut state, _) = get_test_state_and_user();
+        state.cookie_duration = Duration::seconds(-5);
+        let server = TestServer::new(build_router(state)).expect("Could not create test server.");
+
+        let cookies = log_in(&server).await;
+
+        assert_session_expired_for_all_clients(&server, &cookies).await;
+    }
+
+    #[tokio::test]
+    async fn requests_with_old_session_generation_are_told_session_expired() {
+        let (mut state, user) = get_test_state_and_user();
+        let server =
+            TestServer::new(build_router(state.clone())).expect("Could not create test server.");
+        let cookies = log_in(&server).await;
+
+        state
+            .user_store()
+            .increment_session_generation(user.id())
+            .unwrap();
+
+        assert_session_expired_for_all_clients(&server, &cookies).await;
+    }
 }
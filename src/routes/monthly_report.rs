@@ -0,0 +1,314 @@
+//! This file defines the printable monthly report, which summarises a month for record keeping,
+//! e.g. at tax time.
+
+use std::collections::HashMap;
+
+use askama_axum::Template;
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use serde::Deserialize;
+
+use crate::{
+    aggregation::{named_totals_by_category, totals, CategoryTotal, Totals},
+    filters,
+    models::{DatabaseID, Transaction, UserID},
+    stores::{
+        transaction::{SortOrder, TransactionQuery},
+        CategoryStore, TransactionStore, UserStore,
+    },
+    timezone::{get_preferences_today, get_user_preferences},
+    AppError, AppState,
+};
+
+use super::statement::{get_month, month_title};
+
+/// A transaction in the monthly report with its category name.
+struct ReportTransaction {
+    transaction: Transaction,
+    /// The category name, or an empty string for transactions without a category.
+    category: String,
+}
+
+/// Renders the monthly report without the navbar so that it prints cleanly, with each section
+/// starting on a new page.
+#[derive(Template)]
+#[template(path = "views/monthly_report.html")]
+struct MonthlyReportTemplate {
+    /// The month shown, e.g. "March 2024".
+    title: String,
+    /// The money in and out over the month.
+    totals: Totals,
+    /// The totals for each category, starting with the category with the most spent.
+    category_totals: Vec<CategoryTotal>,
+    /// Every transaction in the month, earliest first.
+    transactions: Vec<ReportTransaction>,
+}
+
+/// The query parameters for the monthly report.
+#[derive(Debug, Default, Deserialize)]
+pub struct MonthlyReportQuery {
+    /// The year of the month to report on. Defaults to the current year.
+    pub year: Option<i32>,
+    /// The month to report on, from 1 to 12. Defaults to the current month.
+    pub month: Option<u8>,
+}
+
+/// Display a printable report of a month's totals, category totals and transactions.
+///
+/// Defaults to the current month in the user's timezone, where months start on the user's chosen
+/// month start day. Ignored transactions are left out, the same as on the dashboard.
+pub async fn get_monthly_report_page<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Query(query): Query<MonthlyReportQuery>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let preferences = get_user_preferences(state.user_store(), user_id);
+    let today = get_preferences_today(&preferences);
+    let (year, month, date_range) =
+        match get_month(today, query.year, query.month, preferences.month_start_day) {
+            Ok(month) => month,
+            Err(response) => return response,
+        };
+
+    let category_names: HashMap<DatabaseID, String> =
+        match state.category_store().get_by_user(user_id) {
+            Ok(categories) => categories
+                .into_iter()
+                .map(|category| (category.id(), category.name().as_ref().to_string()))
+                .collect(),
+            Err(error) => return AppError::CategoryError(error).into_response(),
+        };
+
+    let transactions = match state.transaction_store().get_query(TransactionQuery {
+        user_id: Some(user_id),
+        date_range: Some(date_range.clone()),
+        sort_date: Some(SortOrder::Ascending),
+        ..Default::default()
+    }) {
+        Ok(transactions) => transactions,
+        Err(error) => return AppError::TransactionError(error).into_response(),
+    };
+
+    MonthlyReportTemplate {
+        title: month_title(year, month, &date_range),
+        totals: totals(&transactions),
+        category_totals: named_totals_by_category(&transactions, &category_names),
+        transactions: transactions
+            .into_iter()
+            .map(|transaction| ReportTransaction {
+                category: transaction
+                    .category_id()
+                    .and_then(|id| category_names.get(&id).cloned())
+                    .unwrap_or_default(),
+                transaction,
+            })
+            .collect(),
+    }
+    .into_response()
+}
+
+#[cfg(test)]
+mod monthly_report_route_tests {
+    use axum::{
+        body::to_bytes,
+        extract::{Query, State},
+        http::StatusCode,
+        response::Response,
+        Extension,
+    };
+    use rusqlite::Connection;
+    use time::{macros::date, Date, OffsetDateTime};
+
+    use crate::{
+        models::{CategoryName, PasswordHash, Transaction, User, ValidatedPassword, WeekStart},
+        routes::dashboard::get_dashboard_page,
+        stores::{
+            sql_store::{create_app_state, SQLAppState},
+            CategoryStore, TransactionStore, UserStore,
+        },
+    };
+
+    use super::{get_monthly_report_page, MonthlyReportQuery};
+
+    fn get_test_state_and_user() -> (SQLAppState, User) {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        let user = state
+            .user_store()
+            .create(
+                "test@test.com".parse().unwrap(),
+                PasswordHash::new(ValidatedPassword::new_unchecked("test"), 4).unwrap(),
+            )
+            .unwrap();
+
+        (state, user)
+    }
+
+    /// Create a salary, two categorised expenses, an uncategorised expense and an ignored
+    /// transfer on `date`, which net to $830.
+    fn create_fixture_transactions(state: &mut SQLAppState, user: &User, date: Date) {
+        let food = state
+            .category_store()
+            .create(CategoryName::new_unchecked("Food"), user.id())
+            .unwrap();
+
+        let builders = [
+            Transaction::build(1000.0, user.id()).description("Salary".to_string()),
+            Transaction::build(-50.0, user.id())
+                .description("Groceries".to_string())
+                .category(Some(food.id())),
+            Transaction::build(-20.0, user.id())
+                .description("Lunch".to_string())
+                .category(Some(food.id())),
+            Transaction::build(-100.0, user.id()).description("Power bill".to_string()),
+            Transaction::build(-999.0, user.id())
+                .description("Transfer".to_string())
+                .ignored(true),
+        ];
+
+        for builder in builders {
+            state
+                .transaction_store()
+                .create_from_builder(builder.date(date).unwrap())
+                .unwrap();
+        }
+    }
+
+    async fn get_body_text(response: Response) -> String {
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        String::from_utf8_lossy(&body).to_string()
+    }
+
+    async fn get_report(state: SQLAppState, user: &User, query: MonthlyReportQuery) -> Response {
+        get_monthly_report_page(State(state), Extension(user.id()), Query(query)).await
+    }
+
+    #[tokio::test]
+    async fn report_displays_totals_and_transactions() {
+        let (mut state, user) = get_test_state_and_user();
+        create_fixture_transactions(&mut state, &user, date!(2024 - 03 - 05));
+        // Transactions from other months should not be included.
+        state
+            .transaction_store()
+            .create_from_builder(
+                Transaction::build(-500.0, user.id())
+                    .description("Last month".to_string())
+                    .date(date!(2024 - 02 - 29))
+                    .unwrap(),
+            )
+            .unwrap();
+
+        let response = get_report(
+            state,
+            &user,
+            MonthlyReportQuery {
+                year: Some(2024),
+                month: Some(3),
+            },
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let text = get_body_text(response).await;
+        assert!(text.contains("Report for March 2024"));
+        assert!(text.contains("$1,000.00"));
+        assert!(text.contains("-$170.00"));
+        assert!(text.contains("$830.00"));
+        assert!(text.contains("-$70.00"));
+        assert!(text.contains("Power bill"));
+        assert!(!text.contains("Last month"));
+        assert!(!text.contains("Transfer"));
+        assert!(
+            !text.contains("<nav"),
+            "the report should not have a navbar"
+        );
+        assert!(text.contains("break-before-page"));
+    }
+
+    #[tokio::test]
+    async fn report_totals_match_dashboard() {
+        let (mut state, user) = get_test_state_and_user();
+        let today = OffsetDateTime::now_utc().date();
+        create_fixture_transactions(&mut state, &user, today);
+
+        let dashboard =
+            get_body_text(get_dashboard_page(State(state.clone()), Extension(user.id())).await)
+                .await;
+        let report =
+            get_body_text(get_report(state, &user, MonthlyReportQuery::default()).await).await;
+
+        assert!(
+            dashboard.contains("$830.00 under budget"),
+            "got {dashboard}"
+        );
+        assert!(report.contains("$830.00"), "got {report}");
+    }
+
+    #[tokio::test]
+    async fn report_follows_month_start_day() {
+        let (mut state, user) = get_test_state_and_user();
+        state
+            .user_store()
+            .set_date_periods(user.id(), WeekStart::Monday, 15)
+            .unwrap();
+        for (description, date) in [
+            ("Too early", date!(2023 - 12 - 14)),
+            ("First day", date!(2023 - 12 - 15)),
+            ("Last day", date!(2024 - 01 - 14)),
+            ("Too late", date!(2024 - 01 - 15)),
+        ] {
+            state
+                .transaction_store()
+                .create_from_builder(
+                    Transaction::build(-1.0, user.id())
+                        .description(description.to_string())
+                        .date(date)
+                        .unwrap(),
+                )
+                .unwrap();
+        }
+
+        let response = get_report(
+            state,
+            &user,
+            MonthlyReportQuery {
+                year: Some(2023),
+                month: Some(12),
+            },
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let text = get_body_text(response).await;
+        assert!(text.contains("Report for December 2023 (2023-12-15 to 2024-01-14)"));
+        assert!(text.contains("First day"));
+        assert!(text.contains("Last day"));
+        assert!(!text.contains("Too early"));
+        assert!(!text.contains("Too late"));
+    }
+
+    #[tokio::test]
+    async fn report_rejects_invalid_month() {
+        let (state, user) = get_test_state_and_user();
+
+        let response = get_report(
+            state,
+            &user,
+            MonthlyReportQuery {
+                year: Some(2024),
+                month: Some(13),
+            },
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
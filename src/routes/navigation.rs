@@ -1,7 +1,15 @@
 //! This file defines the templates and a convenience function for creating the navigation bar.
 use askama::Template;
 
-use crate::routes::endpoints;
+use crate::{
+    demo::{DEMO_EMAIL, DEMO_PASSWORD},
+    models::Theme,
+    routes::{
+        endpoints, landing_page_route,
+        palette::{Shortcut, PALETTE_KEY, SHORTCUTS},
+        theme::{get_current_landing_page, get_current_theme},
+    },
+};
 
 /// Template for a link in the navigation bar.
 ///
@@ -13,6 +21,8 @@ struct Link<'a> {
     url: &'a str,
     title: &'a str,
     is_current: bool,
+    /// Whether this is the page the user is sent to after logging in.
+    is_landing_page: bool,
 }
 
 /// Template for the navigation bar which includes links to pages and a log out button.
@@ -20,32 +30,79 @@ struct Link<'a> {
 #[template(path = "partials/navbar.html")]
 pub struct NavbarTemplate<'a> {
     links: Vec<Link<'a>>,
+    /// Whether to show a banner saying that the site is a demo.
+    demo_mode: bool,
+    demo_email: &'a str,
+    demo_password: &'a str,
+    /// The logged in user's theme, which is selected in the theme toggle.
+    theme: Theme,
+    themes: &'a [Theme],
+    theme_route: &'a str,
+    /// The page for searching transactions and categories.
+    search_route: &'a str,
+    /// The route that fills the search box's dropdown.
+    search_results_route: &'a str,
+    /// The route that the command palette searches.
+    palette_route: &'a str,
+    palette_key: &'a str,
+    /// The keyboard shortcuts that `assets/shortcuts.js` binds.
+    shortcuts: &'a [Shortcut],
+}
+
+impl NavbarTemplate<'_> {
+    fn is_selected(&self, theme: &Theme) -> bool {
+        self.theme == *theme
+    }
 }
 
 /// Get the navigation bar.
 ///
 /// If a link matches `active_endpoint`, then that link will be
 /// marked as active and displayed differently in the HTML.
-pub fn get_nav_bar(active_endpoint: &str) -> NavbarTemplate {
+/// The demo banner is shown if `demo_mode` is `true`.
+pub fn get_nav_bar(active_endpoint: &str, demo_mode: bool) -> NavbarTemplate<'_> {
+    let landing_page = landing_page_route(get_current_landing_page());
     let links = vec![
         Link {
             url: endpoints::DASHBOARD,
             title: "Dashboard",
             is_current: active_endpoint == endpoints::DASHBOARD,
+            is_landing_page: landing_page == endpoints::DASHBOARD,
         },
         Link {
             url: endpoints::TRANSACTIONS,
             title: "Transactions",
             is_current: active_endpoint == endpoints::TRANSACTIONS,
+            is_landing_page: landing_page == endpoints::TRANSACTIONS,
+        },
+        Link {
+            url: endpoints::SETTINGS,
+            title: "Settings",
+            is_current: active_endpoint == endpoints::SETTINGS,
+            is_landing_page: landing_page == endpoints::SETTINGS,
         },
         Link {
             url: endpoints::LOG_OUT,
             title: "Log out",
             is_current: false,
+            is_landing_page: false,
         },
     ];
 
-    NavbarTemplate { links }
+    NavbarTemplate {
+        links,
+        demo_mode,
+        demo_email: DEMO_EMAIL,
+        demo_password: DEMO_PASSWORD,
+        theme: get_current_theme(),
+        themes: &Theme::ALL,
+        theme_route: endpoints::THEME,
+        search_route: endpoints::SEARCH,
+        search_results_route: endpoints::SEARCH_RESULTS,
+        palette_route: endpoints::PALETTE,
+        palette_key: PALETTE_KEY,
+        shortcuts: &SHORTCUTS,
+    }
 }
 
 #[cfg(test)]
@@ -61,8 +118,30 @@ mod nav_bar_tests {
         let mut cases = HashMap::new();
         cases.insert(endpoints::DASHBOARD, true);
         cases.insert(endpoints::TRANSACTIONS, true);
+        cases.insert(endpoints::SETTINGS, true);
 
         cases.insert(endpoints::LOG_OUT, false);
+        cases.insert(endpoints::LOG_OUT_EVERYWHERE, false);
+        cases.insert(endpoints::CHANGE_EMAIL, false);
+        cases.insert(endpoints::TIMEZONE, false);
+        cases.insert(endpoints::THEME, false);
+        cases.insert(endpoints::LANDING_PAGE, false);
+        cases.insert(endpoints::DATE_PERIODS, false);
+        cases.insert(endpoints::DELETE_ACCOUNT, false);
+        cases.insert(endpoints::CHANGE_PASSWORD, false);
+        cases.insert(endpoints::AUDIT_LOG, false);
+        cases.insert(endpoints::PALETTE, false);
+        cases.insert(endpoints::SEARCH, false);
+        cases.insert(endpoints::SEARCH_RESULTS, false);
+        cases.insert(endpoints::BACKUP, false);
+        cases.insert(endpoints::VACUUM, false);
+        cases.insert(endpoints::ANALYZE, false);
+        cases.insert(endpoints::WEBHOOKS, false);
+        cases.insert(endpoints::WEBHOOK, false);
+        cases.insert(endpoints::WEBHOOK_ENABLED, false);
+        cases.insert(endpoints::WEBHOOK_TEST, false);
+        cases.insert(endpoints::INVITES, false);
+        cases.insert(endpoints::INVITE, false);
         cases.insert(endpoints::ROOT, false);
         cases.insert(endpoints::USERS, false);
         cases.insert(endpoints::COFFEE, false);
@@ -71,7 +150,27 @@ mod nav_bar_tests {
         cases.insert(endpoints::REGISTER, false);
         cases.insert(endpoints::CATEGORIES, false);
         cases.insert(endpoints::TRANSACTION, false);
+        cases.insert(endpoints::TRANSACTION_IGNORED, false);
+        cases.insert(endpoints::TRANSACTIONS_MORE, false);
+        cases.insert(endpoints::TRANSACTIONS_TABLE, false);
+        cases.insert(endpoints::DUPLICATES, false);
+        cases.insert(endpoints::DUPLICATES_MERGE, false);
+        cases.insert(endpoints::SUMMARY_TEXT, false);
+        cases.insert(endpoints::WEEKLY_SUMMARY, false);
+        cases.insert(endpoints::MONTHLY_REPORT, false);
+        cases.insert(endpoints::STATEMENT, false);
+        cases.insert(endpoints::CONFIGURATION, false);
+        cases.insert(endpoints::CATEGORY_SEARCH, false);
+        cases.insert(endpoints::CATEGORY_SUGGESTIONS, false);
+        cases.insert(endpoints::RECONCILE, false);
         cases.insert(endpoints::INTERNAL_ERROR, false);
+        cases.insert(endpoints::HEALTHZ, false);
+        cases.insert(endpoints::API_TOKENS, false);
+        cases.insert(endpoints::API_TOKEN, false);
+        cases.insert(endpoints::API_TRANSACTIONS, false);
+        cases.insert(endpoints::API_TRANSACTIONS_BATCH, false);
+        cases.insert(endpoints::API_TRANSACTION, false);
+        cases.insert(endpoints::READYZ, false);
         cases.insert(endpoints::USER_CATEGORIES, false);
         cases.insert(endpoints::USER_TRANSACTIONS, false);
 
@@ -84,7 +183,7 @@ mod nav_bar_tests {
         };
 
         for (endpoint, should_be_active) in cases {
-            let navbar = get_nav_bar(endpoint);
+            let navbar = get_nav_bar(endpoint, false);
 
             for link in navbar.links {
                 if link.url == endpoint {
@@ -107,3 +206,50 @@ mod nav_bar_tests {
         }
     }
 }
+
+#[cfg(test)]
+mod demo_banner_tests {
+    use askama::Template;
+
+    use super::get_nav_bar;
+
+    #[test]
+    fn shows_banner_in_demo_mode() {
+        let html = get_nav_bar("", true).render().unwrap();
+
+        assert!(html.contains("demo-banner"));
+        assert!(html.contains("demo@example.com"));
+    }
+
+    #[test]
+    fn hides_banner_outside_demo_mode() {
+        let html = get_nav_bar("", false).render().unwrap();
+
+        assert!(!html.contains("demo-banner"));
+    }
+}
+
+#[cfg(test)]
+mod shortcut_tests {
+    use askama::Template;
+
+    use crate::routes::endpoints;
+
+    use super::get_nav_bar;
+
+    #[test]
+    fn lists_shortcuts_and_palette_route() {
+        let html = get_nav_bar("", false).render().unwrap();
+
+        assert!(html.contains(&format!("data-palette-url=\"{}\"", endpoints::PALETTE)));
+        assert!(html.contains(&format!(
+            "data-keys=\"g t\" data-href=\"{}\"",
+            endpoints::TRANSACTIONS
+        )));
+        assert!(html.contains(&format!(
+            "data-keys=\"g d\" data-href=\"{}\"",
+            endpoints::DASHBOARD
+        )));
+        assert!(html.contains("data-focus=\"#amount\""));
+    }
+}
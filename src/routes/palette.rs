@@ -0,0 +1,420 @@
+//! The command palette, which searches the app's pages and the logged in user's categories, and
+//! the keyboard shortcuts that are bound in the browser by `assets/shortcuts.js`.
+
+use std::collections::HashSet;
+
+use askama_axum::Template;
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    Extension,
+};
+use serde::Deserialize;
+
+use crate::{
+    models::{Category, UserID},
+    stores::{
+        transaction::{SortOrder, TransactionQuery},
+        CategoryStore, TransactionStore, UserStore,
+    },
+    AppError, AppState,
+};
+
+use super::{endpoints, transactions::TransactionsPageQuery};
+
+/// The most results the command palette shows at once.
+const PALETTE_RESULT_LIMIT: usize = 10;
+
+/// The number of recently used categories shown before the user types anything.
+const RECENT_CATEGORY_LIMIT: usize = 5;
+
+/// The number of recent transactions to look through for recently used categories.
+const RECENT_TRANSACTION_LIMIT: u64 = 100;
+
+/// The pages that can be found with the command palette, in the order they are listed when
+/// nothing has been typed.
+const PAGES: [(&str, &str); 13] = [
+    ("Dashboard", endpoints::DASHBOARD),
+    ("Transactions", endpoints::TRANSACTIONS),
+    ("Monthly statement", endpoints::STATEMENT),
+    ("Weekly summary", endpoints::WEEKLY_SUMMARY),
+    ("Monthly report", endpoints::MONTHLY_REPORT),
+    ("Reconcile", endpoints::RECONCILE),
+    ("Find duplicates", endpoints::DUPLICATES),
+    ("Settings", endpoints::SETTINGS),
+    ("Change password", endpoints::CHANGE_PASSWORD),
+    ("Audit log", endpoints::AUDIT_LOG),
+    ("Invites", endpoints::INVITES),
+    ("Webhooks", endpoints::WEBHOOKS),
+    ("Log out", endpoints::LOG_OUT),
+];
+
+/// A keyboard shortcut, listed in the navbar so that the browser can bind it.
+pub struct Shortcut {
+    /// The keys to press in order, separated by spaces, e.g. "g t".
+    pub keys: &'static str,
+    /// What the shortcut does, e.g. "Go to transactions".
+    pub description: &'static str,
+    /// The page the shortcut goes to.
+    pub href: &'static str,
+    /// The element to focus instead of going to `href`, if it is on the current page.
+    pub focus: Option<&'static str>,
+}
+
+/// The key that opens the command palette.
+pub const PALETTE_KEY: &str = "/";
+
+/// The keyboard shortcuts available on every page with a navbar.
+pub const SHORTCUTS: [Shortcut; 3] = [
+    Shortcut {
+        keys: "g d",
+        description: "Go to the dashboard",
+        href: endpoints::DASHBOARD,
+        focus: None,
+    },
+    Shortcut {
+        keys: "g t",
+        description: "Go to transactions",
+        href: endpoints::TRANSACTIONS,
+        focus: None,
+    },
+    Shortcut {
+        keys: "n",
+        description: "New transaction",
+        href: endpoints::TRANSACTIONS,
+        focus: Some("#amount"),
+    },
+];
+
+/// Whether a command palette result is a page or a category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum PaletteResultKind {
+    Page,
+    Category,
+}
+
+impl PaletteResultKind {
+    fn label(&self) -> &'static str {
+        match self {
+            PaletteResultKind::Page => "Page",
+            PaletteResultKind::Category => "Category",
+        }
+    }
+}
+
+/// A page or category that matches the command palette search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PaletteResult {
+    title: String,
+    href: String,
+    kind: PaletteResultKind,
+}
+
+/// Renders the command palette results.
+#[derive(Template)]
+#[template(path = "partials/palette/results.html")]
+struct PaletteResultsTemplate {
+    results: Vec<PaletteResult>,
+}
+
+/// The query parameters for searching with the command palette.
+#[derive(Debug, Default, Deserialize)]
+pub struct PaletteQuery {
+    /// The text to search for. Empty text lists the pages and recently used categories.
+    #[serde(default)]
+    pub q: String,
+}
+
+/// How well `name` matches `query`, ignoring case, or `None` if it does not match.
+///
+/// Lower ranks are better: an exact match, then a match at the start of the name, then a match at
+/// the start of a later word, then a match anywhere else.
+fn rank(name: &str, query: &str) -> Option<u8> {
+    let name = name.to_lowercase();
+    let query = query.to_lowercase();
+
+    if name == query {
+        Some(0)
+    } else if name.starts_with(&query) {
+        Some(1)
+    } else if name.split_whitespace().any(|word| word.starts_with(&query)) {
+        Some(2)
+    } else if name.contains(&query) {
+        Some(3)
+    } else {
+        None
+    }
+}
+
+/// Keep the results that match `query`, best first.
+///
+/// Results with the same rank are ordered with pages first, then by title.
+fn rank_results(results: Vec<PaletteResult>, query: &str) -> Vec<PaletteResult> {
+    let mut ranked: Vec<_> = results
+        .into_iter()
+        .filter_map(|result| rank(&result.title, query).map(|rank| (rank, result)))
+        .collect();
+    ranked.sort_by(|(lhs_rank, lhs), (rhs_rank, rhs)| {
+        lhs_rank
+            .cmp(rhs_rank)
+            .then(lhs.kind.cmp(&rhs.kind))
+            .then_with(|| lhs.title.to_lowercase().cmp(&rhs.title.to_lowercase()))
+    });
+
+    ranked
+        .into_iter()
+        .map(|(_, result)| result)
+        .take(PALETTE_RESULT_LIMIT)
+        .collect()
+}
+
+/// Search the app's pages and the logged in user's categories for the command palette.
+///
+/// Categories link to the transactions page filtered by that category. Without a search, the
+/// pages are listed with the categories used by the user's most recent transactions.
+pub async fn get_palette_results<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Query(query): Query<PaletteQuery>,
+) -> impl IntoResponse
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let q = query.q.trim();
+    let categories = state.category_store().get_by_user(user_id)?;
+
+    let pages = PAGES.iter().map(|(title, href)| PaletteResult {
+        title: title.to_string(),
+        href: href.to_string(),
+        kind: PaletteResultKind::Page,
+    });
+
+    let category_result = |category: &Category| PaletteResult {
+        title: category.name().as_ref().to_string(),
+        href: TransactionsPageQuery {
+            category_id: Some(category.id()),
+            ..Default::default()
+        }
+        .href(),
+        kind: PaletteResultKind::Category,
+    };
+
+    let results = if q.is_empty() {
+        let recent_transactions = state
+            .transaction_store()
+            .get_query(TransactionQuery {
+                user_id: Some(user_id),
+                sort_date: Some(SortOrder::Descending),
+                limit: Some(RECENT_TRANSACTION_LIMIT),
+                ..Default::default()
+            })
+            .map_err(AppError::TransactionError)?;
+
+        let mut seen = HashSet::new();
+        let recent_categories = recent_transactions
+            .iter()
+            .filter_map(|transaction| transaction.category_id())
+            .filter(|category_id| seen.insert(*category_id))
+            .filter_map(|category_id| {
+                categories
+                    .iter()
+                    .find(|category| category.id() == category_id)
+            })
+            .take(RECENT_CATEGORY_LIMIT)
+            .map(category_result);
+
+        pages.chain(recent_categories).collect()
+    } else {
+        rank_results(
+            pages
+                .chain(categories.iter().map(category_result))
+                .collect(),
+            q,
+        )
+    };
+
+    Ok::<_, AppError>(PaletteResultsTemplate { results })
+}
+
+#[cfg(test)]
+mod palette_tests {
+    use axum::{
+        body::to_bytes,
+        extract::{Query, State},
+        response::{IntoResponse, Response},
+        Extension,
+    };
+    use rusqlite::Connection;
+
+    use crate::{
+        models::{CategoryName, PasswordHash, Transaction, User},
+        routes::endpoints,
+        stores::{
+            sql_store::{create_app_state, SQLAppState},
+            CategoryStore, TransactionStore, UserStore,
+        },
+    };
+
+    use super::{
+        get_palette_results, rank, rank_results, PaletteQuery, PaletteResult, PaletteResultKind,
+        PALETTE_RESULT_LIMIT,
+    };
+
+    fn get_test_state_and_user() -> (SQLAppState, User) {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        let user = state
+            .user_store()
+            .create(
+                "test@test.com".parse().unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+
+        (state, user)
+    }
+
+    async fn search(state: SQLAppState, user: &User, q: &str) -> String {
+        let response: Response = get_palette_results(
+            State(state),
+            Extension(user.id()),
+            Query(PaletteQuery { q: q.to_string() }),
+        )
+        .await
+        .into_response();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        String::from_utf8_lossy(&body).to_string()
+    }
+
+    fn result(title: &str, kind: PaletteResultKind) -> PaletteResult {
+        PaletteResult {
+            title: title.to_string(),
+            href: String::new(),
+            kind,
+        }
+    }
+
+    #[test]
+    fn rank_prefers_earlier_matches() {
+        assert_eq!(rank("Transactions", "transactions"), Some(0));
+        assert_eq!(rank("Transactions", "TRANS"), Some(1));
+        assert_eq!(rank("Find duplicates", "dup"), Some(2));
+        assert_eq!(rank("Dashboard", "board"), Some(3));
+        assert_eq!(rank("Dashboard", "settings"), None);
+    }
+
+    #[test]
+    fn rank_results_orders_by_rank_then_pages_then_title() {
+        let results = vec![
+            result("Travel", PaletteResultKind::Category),
+            result("Mortgage repayments", PaletteResultKind::Category),
+            result("Transactions", PaletteResultKind::Page),
+            result("Rent", PaletteResultKind::Category),
+            result("tr", PaletteResultKind::Category),
+        ];
+
+        let got: Vec<_> = rank_results(results, "tr")
+            .into_iter()
+            .map(|result| result.title)
+            .collect();
+
+        assert_eq!(got, ["tr", "Transactions", "Travel"]);
+    }
+
+    #[test]
+    fn rank_results_returns_at_most_limit() {
+        let results = (0..PALETTE_RESULT_LIMIT + 5)
+            .map(|i| result(&format!("Category {i:02}"), PaletteResultKind::Category))
+            .collect();
+
+        assert_eq!(
+            rank_results(results, "category").len(),
+            PALETTE_RESULT_LIMIT
+        );
+    }
+
+    #[tokio::test]
+    async fn palette_finds_pages_and_categories() {
+        let (state, user) = get_test_state_and_user();
+        let category = state
+            .category_store()
+            .create(CategoryName::new_unchecked("Travel"), user.id())
+            .unwrap();
+
+        let text = search(state, &user, "tr").await;
+
+        let transactions = text.find(&format!("href=\"{}\"", endpoints::TRANSACTIONS));
+        let travel = text.find(&format!(
+            "href=\"{}?category_id={}\"",
+            endpoints::TRANSACTIONS,
+            category.id()
+        ));
+        assert!(transactions.is_some(), "got {text}");
+        assert!(travel.is_some(), "got {text}");
+        assert!(transactions < travel);
+        assert!(!text.contains(endpoints::DASHBOARD));
+    }
+
+    #[tokio::test]
+    async fn palette_escapes_category_names() {
+        let (state, user) = get_test_state_and_user();
+        state
+            .category_store()
+            .create(
+                CategoryName::new_unchecked("<script>alert(1)</script>"),
+                user.id(),
+            )
+            .unwrap();
+
+        let text = search(state, &user, "script").await;
+
+        assert!(!text.contains("<script>"));
+        assert!(text.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+    }
+
+    #[tokio::test]
+    async fn palette_excludes_other_users_categories() {
+        let (mut state, user) = get_test_state_and_user();
+        let other_user = state
+            .user_store()
+            .create(
+                "other@test.com".parse().unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+        state
+            .category_store()
+            .create(CategoryName::new_unchecked("Secret"), other_user.id())
+            .unwrap();
+
+        let text = search(state, &user, "secret").await;
+
+        assert!(!text.contains("Secret"));
+    }
+
+    #[tokio::test]
+    async fn empty_search_lists_pages_and_recent_categories() {
+        let (mut state, user) = get_test_state_and_user();
+        let used = state
+            .category_store()
+            .create(CategoryName::new_unchecked("Groceries"), user.id())
+            .unwrap();
+        state
+            .category_store()
+            .create(CategoryName::new_unchecked("Unused"), user.id())
+            .unwrap();
+        state
+            .transaction_store()
+            .create_from_builder(Transaction::build(-10.0, user.id()).category(Some(used.id())))
+            .unwrap();
+
+        let text = search(state, &user, " ").await;
+
+        assert!(text.contains(endpoints::DASHBOARD));
+        assert!(text.contains(endpoints::SETTINGS));
+        assert!(text.contains("Groceries"));
+        assert!(!text.contains("Unused"));
+    }
+}
@@ -0,0 +1,517 @@
+//! This file defines the page for reconciling transactions against a bank statement.
+//!
+//! The reconciled total is the sum of every reconciled transaction up to the end of the statement
+//! period, plus the transactions that have been checked off. Once it matches the statement
+//! closing balance, the checked transactions can be marked as reconciled.
+
+use std::ops::RangeInclusive;
+
+use askama_axum::Template;
+use axum::{
+    extract::{Query, State},
+    http::{StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use axum_extra::extract::Form;
+use axum_htmx::HxRedirect;
+use serde::Deserialize;
+use time::Date;
+
+use crate::{
+    aggregation::{month_to_date, totals},
+    filters,
+    models::{DatabaseID, Transaction, TransactionError, UserID},
+    routes::get_internal_server_error_redirect,
+    stores::{
+        transaction::{IgnoredFilter, SortOrder, TransactionQuery},
+        CategoryStore, TransactionStore, UserStore,
+    },
+    timezone::{get_preferences_today, get_user_preferences},
+    AppError, AppState,
+};
+
+use super::{
+    endpoints,
+    navigation::{get_nav_bar, NavbarTemplate},
+    transactions::TransactionsPageQuery,
+};
+
+/// Amounts closer than this are treated as equal to account for floating point error.
+const BALANCE_TOLERANCE: f64 = 0.005;
+
+/// Renders the running reconciled total and how it compares to the statement closing balance.
+#[derive(Template)]
+#[template(path = "partials/reconcile/status.html")]
+struct ReconcileStatusTemplate {
+    /// The sum of the reconciled and checked transactions.
+    reconciled_total: f64,
+    /// The closing balance from the bank statement, if the user has entered it.
+    closing_balance: Option<f64>,
+    /// Whether to tell the user that the closing balance is needed to finish reconciling.
+    closing_balance_required: bool,
+}
+
+impl ReconcileStatusTemplate {
+    /// The closing balance minus the reconciled total, if the closing balance has been entered.
+    fn difference(&self) -> Option<f64> {
+        self.closing_balance
+            .map(|closing_balance| closing_balance - self.reconciled_total)
+    }
+
+    /// Whether the reconciled total matches the closing balance.
+    fn is_balanced(&self) -> bool {
+        self.difference()
+            .is_some_and(|difference| difference.abs() < BALANCE_TOLERANCE)
+    }
+}
+
+/// Renders the reconciliation page.
+#[derive(Template)]
+#[template(path = "views/reconcile.html")]
+struct ReconcileTemplate<'a> {
+    navbar: NavbarTemplate<'a>,
+    /// The first day of the statement period.
+    from: Date,
+    /// The last day of the statement period.
+    to: Date,
+    /// The unreconciled transactions in the statement period.
+    transactions: Vec<Transaction>,
+    status: ReconcileStatusTemplate,
+    reconcile_route: &'a str,
+}
+
+/// The query parameters for choosing the statement period on the reconciliation page.
+#[derive(Debug, Default, Deserialize)]
+pub struct ReconcilePageQuery {
+    /// The first day of the statement period. Defaults to the first day of the current month, where
+    /// months start on the user's chosen month start day.
+    pub from: Option<Date>,
+    /// The last day of the statement period. Defaults to today.
+    pub to: Option<Date>,
+}
+
+/// The form data for reconciling transactions.
+#[derive(Debug, Deserialize)]
+pub struct ReconcileForm {
+    /// The first day of the statement period.
+    pub from: Date,
+    /// The last day of the statement period.
+    pub to: Date,
+    /// The closing balance from the bank statement.
+    pub closing_balance: Option<f64>,
+    /// The IDs of the transactions that have been checked off against the statement.
+    #[serde(default)]
+    pub transaction_ids: Vec<DatabaseID>,
+    /// Whether to mark the checked transactions as reconciled, otherwise only the running total is
+    /// calculated.
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+/// Display the user's unreconciled transactions for a statement period.
+pub async fn get_reconcile_page<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Query(query): Query<ReconcilePageQuery>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let preferences = get_user_preferences(state.user_store(), user_id);
+    let this_month = month_to_date(
+        get_preferences_today(&preferences),
+        preferences.month_start_day,
+    );
+    let from = query.from.unwrap_or(*this_month.start());
+    let to = query.to.unwrap_or(*this_month.end());
+
+    let reconciled_total = match get_reconciled_total(state.transaction_store(), user_id, to) {
+        Ok(total) => total,
+        Err(error) => return AppError::TransactionError(error).into_response(),
+    };
+
+    let transactions =
+        match get_unreconciled_transactions(state.transaction_store(), user_id, from..=to) {
+            Ok(transactions) => transactions,
+            Err(error) => return AppError::TransactionError(error).into_response(),
+        };
+
+    ReconcileTemplate {
+        navbar: get_nav_bar(endpoints::RECONCILE, state.demo_mode),
+        from,
+        to,
+        transactions,
+        status: ReconcileStatusTemplate {
+            reconciled_total,
+            closing_balance: None,
+            closing_balance_required: false,
+        },
+        reconcile_route: endpoints::RECONCILE,
+    }
+    .into_response()
+}
+
+/// Calculate the running reconciled total and, if `confirm` is set and the total matches the
+/// closing balance, mark the checked transactions as reconciled.
+///
+/// Responds with the updated [ReconcileStatusTemplate], or redirects to the reconciled
+/// transactions on success. Transactions are only marked as reconciled if the reconciled total
+/// matches the closing balance.
+pub async fn reconcile_transactions<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Form(form): Form<ReconcileForm>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let previously_reconciled_total =
+        match get_reconciled_total(state.transaction_store(), user_id, form.to) {
+            Ok(total) => total,
+            Err(error) => {
+                tracing::error!("Could not get reconciled total: {error}");
+                return get_internal_server_error_redirect();
+            }
+        };
+
+    let candidates = match get_unreconciled_transactions(
+        state.transaction_store(),
+        user_id,
+        form.from..=form.to,
+    ) {
+        Ok(transactions) => transactions,
+        Err(error) => {
+            tracing::error!("Could not get unreconciled transactions: {error}");
+            return get_internal_server_error_redirect();
+        }
+    };
+
+    // Only transactions shown on the page can be checked off, this also stops users from
+    // reconciling other users' transactions.
+    let checked: Vec<Transaction> = candidates
+        .into_iter()
+        .filter(|transaction| form.transaction_ids.contains(&transaction.id()))
+        .collect();
+
+    let status = ReconcileStatusTemplate {
+        reconciled_total: previously_reconciled_total + totals(&checked).net,
+        closing_balance: form.closing_balance,
+        closing_balance_required: form.confirm && form.closing_balance.is_none(),
+    };
+
+    if !form.confirm || !status.is_balanced() {
+        return (StatusCode::OK, status).into_response();
+    }
+
+    let ids: Vec<DatabaseID> = checked.iter().map(|transaction| transaction.id()).collect();
+
+    if let Err(error) = state.transaction_store().set_reconciled(&ids) {
+        tracing::error!("Could not mark transactions as reconciled: {error}");
+        return get_internal_server_error_redirect();
+    }
+
+    let href = TransactionsPageQuery {
+        from: Some(form.from),
+        to: Some(form.to),
+        ..Default::default()
+    }
+    .href();
+
+    match href.parse::<Uri>() {
+        Ok(uri) => (HxRedirect(uri), StatusCode::OK).into_response(),
+        Err(error) => {
+            tracing::error!("Could not parse the URI {href}: {error}");
+            get_internal_server_error_redirect()
+        }
+    }
+}
+
+/// Sum the amounts of all of the user's reconciled transactions up to and including `to`.
+fn get_reconciled_total<T: TransactionStore>(
+    store: &T,
+    user_id: UserID,
+    to: Date,
+) -> Result<f64, TransactionError> {
+    let transactions = store.get_query(TransactionQuery {
+        user_id: Some(user_id),
+        date_range: Some(Date::MIN..=to),
+        // Ignored transactions still appear on bank statements.
+        ignored: IgnoredFilter::Include,
+        reconciled: Some(true),
+        ..Default::default()
+    })?;
+
+    Ok(totals(&transactions).net)
+}
+
+/// Get the user's unreconciled transactions in `date_range`, oldest first.
+fn get_unreconciled_transactions<T: TransactionStore>(
+    store: &T,
+    user_id: UserID,
+    date_range: RangeInclusive<Date>,
+) -> Result<Vec<Transaction>, TransactionError> {
+    store.get_query(TransactionQuery {
+        user_id: Some(user_id),
+        date_range: Some(date_range),
+        sort_date: Some(SortOrder::Ascending),
+        // Ignored transactions still appear on bank statements.
+        ignored: IgnoredFilter::Include,
+        reconciled: Some(false),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod reconcile_route_tests {
+    use axum::{
+        body::to_bytes,
+        extract::{Query, State},
+        http::StatusCode,
+        response::Response,
+        Extension,
+    };
+    use axum_extra::extract::Form;
+    use rusqlite::Connection;
+    use time::macros::date;
+
+    use crate::{
+        models::{PasswordHash, Transaction, User, ValidatedPassword},
+        stores::{
+            sql_store::{create_app_state, SQLAppState},
+            TransactionStore, UserStore,
+        },
+    };
+
+    use super::{get_reconcile_page, reconcile_transactions, ReconcileForm, ReconcilePageQuery};
+
+    fn get_test_state_and_user() -> (SQLAppState, User) {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        let user = state
+            .user_store()
+            .create(
+                "test@test.com".parse().unwrap(),
+                PasswordHash::new(ValidatedPassword::new_unchecked("test"), 4).unwrap(),
+            )
+            .unwrap();
+
+        (state, user)
+    }
+
+    /// Create a reconciled opening balance of $100 on 2024-02-29, and two unreconciled
+    /// transactions in March 2024.
+    fn create_transactions(state: &mut SQLAppState, user: &User) -> Vec<Transaction> {
+        [
+            Transaction::build(100.0, user.id())
+                .description("Opening balance".to_string())
+                .date(date!(2024 - 02 - 29))
+                .unwrap()
+                .reconciled(true),
+            Transaction::build(-20.0, user.id())
+                .description("Groceries".to_string())
+                .date(date!(2024 - 03 - 02))
+                .unwrap(),
+            Transaction::build(-5.5, user.id())
+                .description("Coffee".to_string())
+                .date(date!(2024 - 03 - 03))
+                .unwrap(),
+        ]
+        .into_iter()
+        .map(|builder| {
+            state
+                .transaction_store()
+                .create_from_builder(builder)
+                .unwrap()
+        })
+        .collect()
+    }
+
+    async fn get_body_text(response: Response) -> String {
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        String::from_utf8_lossy(&body).to_string()
+    }
+
+    #[tokio::test]
+    async fn reconcile_page_lists_unreconciled_transactions() {
+        let (mut state, user) = get_test_state_and_user();
+        create_transactions(&mut state, &user);
+
+        let response = get_reconcile_page(
+            State(state),
+            Extension(user.id()),
+            Query(ReconcilePageQuery {
+                from: Some(date!(2024 - 03 - 01)),
+                to: Some(date!(2024 - 03 - 31)),
+            }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let text = get_body_text(response).await;
+        assert!(!text.contains("Opening balance"));
+        assert!(text.contains("Groceries"));
+        assert!(text.contains("Coffee"));
+        assert!(text.contains("Reconciled total: $100.00"));
+    }
+
+    #[tokio::test]
+    async fn preview_warns_on_mismatch_without_saving() {
+        let (mut state, user) = get_test_state_and_user();
+        let transactions = create_transactions(&mut state, &user);
+
+        let response = reconcile_transactions(
+            State(state.clone()),
+            Extension(user.id()),
+            Form(ReconcileForm {
+                from: date!(2024 - 03 - 01),
+                to: date!(2024 - 03 - 31),
+                closing_balance: Some(74.5),
+                transaction_ids: vec![transactions[1].id()],
+                confirm: false,
+            }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let text = get_body_text(response).await;
+        assert!(text.contains("Reconciled total: $80.00"), "got {text}");
+        assert!(text.contains("difference is -$5.50"), "got {text}");
+        assert!(!state
+            .transaction_store()
+            .get(transactions[1].id())
+            .unwrap()
+            .is_reconciled());
+    }
+
+    #[tokio::test]
+    async fn confirm_with_mismatch_does_not_reconcile() {
+        let (mut state, user) = get_test_state_and_user();
+        let transactions = create_transactions(&mut state, &user);
+
+        let response = reconcile_transactions(
+            State(state.clone()),
+            Extension(user.id()),
+            Form(ReconcileForm {
+                from: date!(2024 - 03 - 01),
+                to: date!(2024 - 03 - 31),
+                closing_balance: Some(50.0),
+                transaction_ids: vec![transactions[1].id(), transactions[2].id()],
+                confirm: true,
+            }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(get_body_text(response).await.contains("Warning"));
+
+        for transaction in &transactions[1..] {
+            assert!(!state
+                .transaction_store()
+                .get(transaction.id())
+                .unwrap()
+                .is_reconciled());
+        }
+    }
+
+    #[tokio::test]
+    async fn confirm_without_closing_balance_does_not_reconcile() {
+        let (mut state, user) = get_test_state_and_user();
+        let transactions = create_transactions(&mut state, &user);
+
+        let response = reconcile_transactions(
+            State(state.clone()),
+            Extension(user.id()),
+            Form(ReconcileForm {
+                from: date!(2024 - 03 - 01),
+                to: date!(2024 - 03 - 31),
+                closing_balance: None,
+                transaction_ids: vec![transactions[1].id()],
+                confirm: true,
+            }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(get_body_text(response)
+            .await
+            .contains("Enter the statement closing balance"));
+        assert!(!state
+            .transaction_store()
+            .get(transactions[1].id())
+            .unwrap()
+            .is_reconciled());
+    }
+
+    #[tokio::test]
+    async fn confirm_with_matching_balance_reconciles_checked_transactions() {
+        let (mut state, user) = get_test_state_and_user();
+        let transactions = create_transactions(&mut state, &user);
+
+        let response = reconcile_transactions(
+            State(state.clone()),
+            Extension(user.id()),
+            Form(ReconcileForm {
+                from: date!(2024 - 03 - 01),
+                to: date!(2024 - 03 - 31),
+                closing_balance: Some(74.5),
+                transaction_ids: vec![transactions[1].id(), transactions[2].id()],
+                confirm: true,
+            }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("hx-redirect").unwrap(),
+            "/transactions?from=2024-03-01&to=2024-03-31"
+        );
+
+        for transaction in &transactions[1..] {
+            assert!(state
+                .transaction_store()
+                .get(transaction.id())
+                .unwrap()
+                .is_reconciled());
+        }
+    }
+
+    #[tokio::test]
+    async fn cannot_reconcile_other_users_transactions() {
+        let (mut state, user) = get_test_state_and_user();
+        let transactions = create_transactions(&mut state, &user);
+        let other_user = state
+            .user_store()
+            .create(
+                "other@test.com".parse().unwrap(),
+                PasswordHash::new(ValidatedPassword::new_unchecked("test"), 4).unwrap(),
+            )
+            .unwrap();
+
+        let response = reconcile_transactions(
+            State(state.clone()),
+            Extension(other_user.id()),
+            Form(ReconcileForm {
+                from: date!(2024 - 03 - 01),
+                to: date!(2024 - 03 - 31),
+                closing_balance: Some(0.0),
+                transaction_ids: vec![transactions[1].id()],
+                confirm: true,
+            }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!state
+            .transaction_store()
+            .get(transactions[1].id())
+            .unwrap()
+            .is_reconciled());
+    }
+}
@@ -3,7 +3,7 @@ use std::str::FromStr;
 
 use askama::Template;
 use axum::{
-    extract::State,
+    extract::{Query, State},
     http::{StatusCode, Uri},
     response::{IntoResponse, Response},
     Form,
@@ -18,7 +18,7 @@ use crate::{
     models::{PasswordHash, ValidatedPassword},
     routes::get_internal_server_error_redirect,
     stores::{CategoryStore, TransactionStore, UserError, UserStore},
-    AppState,
+    AppState, RegistrationMode,
 };
 
 use super::{
@@ -27,7 +27,7 @@ use super::{
 };
 
 /// The minimum number of characters the password should have to be considered valid on the client side (server-side validation is done on top of this validation).
-const PASSWORD_INPUT_MIN_LENGTH: usize = 8;
+pub(super) const PASSWORD_INPUT_MIN_LENGTH: usize = 8;
 
 #[derive(Template)]
 #[template(path = "views/register.html")]
@@ -43,6 +43,8 @@ struct RegisterFormTemplate<'a> {
     email_input: EmailInputTemplate<'a>,
     password_input: PasswordInputTemplate<'a>,
     confirm_password_input: ConfirmPasswordInputTemplate<'a>,
+    /// The invite code input, which is only displayed if registration is invite-only.
+    invite_code_input: Option<InviteCodeInputTemplate<'a>>,
 }
 
 impl Default for RegisterFormTemplate<'_> {
@@ -53,6 +55,7 @@ impl Default for RegisterFormTemplate<'_> {
             email_input: EmailInputTemplate::default(),
             password_input: PasswordInputTemplate::default(),
             confirm_password_input: ConfirmPasswordInputTemplate::default(),
+            invite_code_input: None,
         }
     }
 }
@@ -63,14 +66,57 @@ struct ConfirmPasswordInputTemplate<'a> {
     error_message: &'a str,
 }
 
+#[derive(Template, Default, Clone, Copy)]
+#[template(path = "partials/register/inputs/invite_code.html")]
+struct InviteCodeInputTemplate<'a> {
+    value: &'a str,
+    error_message: &'a str,
+}
+
+/// Displayed instead of the registration page when registration is closed.
+#[derive(Template)]
+#[template(path = "views/registration_closed.html")]
+struct RegistrationClosedTemplate;
+
+fn get_registration_closed_response() -> Response {
+    (StatusCode::FORBIDDEN, RegistrationClosedTemplate).into_response()
+}
+
+/// The query parameters for the registration page.
+#[derive(Debug, Default, Deserialize)]
+pub struct RegisterPageQuery {
+    /// An invite code to fill in, e.g. from a link shared by an existing user.
+    pub invite: Option<String>,
+}
+
 /// Display the registration page.
-pub async fn get_register_page() -> Response {
+///
+/// Responds with the status code 403 if registration is closed.
+pub async fn get_register_page<C, T, U>(
+    State(state): State<AppState<C, T, U>>,
+    Query(query): Query<RegisterPageQuery>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let invite_code_input = match state.registration_mode {
+        RegistrationMode::Open => None,
+        RegistrationMode::Closed => return get_registration_closed_response(),
+        RegistrationMode::InviteOnly => Some(InviteCodeInputTemplate {
+            value: query.invite.as_deref().unwrap_or_default(),
+            error_message: "",
+        }),
+    };
+
     RegisterPageTemplate {
         register_form: RegisterFormTemplate {
             password_input: PasswordInputTemplate {
                 min_length: PASSWORD_INPUT_MIN_LENGTH,
                 ..Default::default()
             },
+            invite_code_input,
             ..Default::default()
         },
     }
@@ -82,8 +128,16 @@ pub struct RegisterForm {
     pub email: String,
     pub password: String,
     pub confirm_password: String,
+    /// The invite code, which is only required if registration is invite-only.
+    #[serde(default)]
+    pub invite_code: Option<String>,
 }
 
+/// Create a new user and log them in.
+///
+/// Responds with the status code 403 if registration is closed. If registration is invite-only,
+/// the invite code must match an unused invite, which is deleted in the same database transaction
+/// that creates the user.
 pub async fn create_user<C, T, U>(
     State(mut state): State<AppState<C, T, U>>,
     jar: PrivateCookieJar,
@@ -94,7 +148,18 @@ where
     T: TransactionStore + Send + Sync,
     U: UserStore + Send + Sync,
 {
+    let invite_code = match state.registration_mode {
+        RegistrationMode::Open => None,
+        RegistrationMode::Closed => return get_registration_closed_response(),
+        RegistrationMode::InviteOnly => Some(user_data.invite_code.as_deref().unwrap_or_default()),
+    };
+
     // Make templates ahead of time that preserve the user's input since they are used multiple times in this function.
+    let invite_code_input = invite_code.map(|code| InviteCodeInputTemplate {
+        value: code,
+        error_message: "",
+    });
+
     let email_input = EmailInputTemplate {
         value: &user_data.email,
         ..Default::default()
@@ -116,6 +181,7 @@ where
                     error_message: &format!("Invalid email address: {}", e),
                 },
                 password_input,
+                invite_code_input,
                 ..Default::default()
             }
             .into_response();
@@ -129,6 +195,7 @@ where
                 error_message: "The email address is already in use",
             },
             password_input,
+            invite_code_input,
             ..Default::default()
         }
         .into_response();
@@ -144,6 +211,7 @@ where
                     min_length: PASSWORD_INPUT_MIN_LENGTH,
                     error_message: e.to_string().as_ref(),
                 },
+                invite_code_input,
                 ..Default::default()
             }
             .into_response();
@@ -157,12 +225,13 @@ where
             confirm_password_input: ConfirmPasswordInputTemplate {
                 error_message: "Passwords do not match",
             },
+            invite_code_input,
             ..Default::default()
         }
         .into_response();
     }
 
-    let password_hash = match PasswordHash::new(validated_password, PasswordHash::DEFAULT_COST) {
+    let password_hash = match PasswordHash::new(validated_password, state.password_hash_cost) {
         Ok(hash) => hash,
         Err(e) => {
             tracing::error!("an error occurred while hashing a password: {e}");
@@ -171,11 +240,21 @@ where
         }
     };
 
-    state
-        .user_store()
-        .create(email, password_hash)
+    let result = match invite_code {
+        Some(code) => state
+            .user_store()
+            .create_with_invite(email, password_hash, code),
+        None => state.user_store().create(email, password_hash),
+    };
+
+    result
         .map(|user| {
-            let jar = set_auth_cookie(jar, user.id(), state.cookie_duration);
+            let jar = set_auth_cookie(
+                jar,
+                user.id(),
+                user.session_generation(),
+                state.cookie_duration,
+            );
 
             match jar {
                 Ok(jar) => (
@@ -198,6 +277,17 @@ where
                     error_message: "The email address is already in use",
                 },
                 password_input,
+                invite_code_input,
+                ..Default::default()
+            }
+            .into_response(),
+            UserError::InvalidInvite => RegisterFormTemplate {
+                email_input,
+                password_input,
+                invite_code_input: Some(InviteCodeInputTemplate {
+                    value: invite_code.unwrap_or_default(),
+                    error_message: "The invite code is invalid or has already been used",
+                }),
                 ..Default::default()
             }
             .into_response(),
@@ -212,9 +302,12 @@ where
 
 #[cfg(test)]
 mod tests {
-    use axum::{routing::post, Router};
+    use axum::{
+        http::StatusCode,
+        routing::{get, post},
+        Router,
+    };
     use axum_test::TestServer;
-    use serde::{Deserialize, Serialize};
 
     use crate::{
         models::{
@@ -223,17 +316,21 @@ mod tests {
         },
         routes::{
             endpoints,
-            register::{create_user, RegisterForm},
+            register::{create_user, get_register_page, RegisterForm},
         },
         stores::{
-            transaction::TransactionQuery, CategoryStore, TransactionStore, UserError, UserStore,
+            transaction::{BatchOutcome, NewTransaction, TransactionQuery},
+            CategoryStore, TransactionStore, UserError, UserStore,
         },
-        AppState,
+        AppState, RegistrationMode,
     };
 
+    use std::sync::{Arc, Mutex};
+
     #[derive(Clone)]
     struct StubUserStore {
         users: Vec<User>,
+        invite_codes: Arc<Mutex<Vec<String>>>,
     }
 
     impl UserStore for StubUserStore {
@@ -253,6 +350,23 @@ mod tests {
             Ok(user)
         }
 
+        fn create_with_invite(
+            &mut self,
+            email: email_address::EmailAddress,
+            password_hash: PasswordHash,
+            invite_code: &str,
+        ) -> Result<User, UserError> {
+            let mut invite_codes = self.invite_codes.lock().unwrap();
+            let index = invite_codes
+                .iter()
+                .position(|code| code == invite_code)
+                .ok_or(UserError::InvalidInvite)?;
+            invite_codes.remove(index);
+            drop(invite_codes);
+
+            self.create(email, password_hash)
+        }
+
         fn get(&self, id: UserID) -> Result<User, UserError> {
             self.users
                 .iter()
@@ -268,6 +382,78 @@ mod tests {
                 .ok_or(UserError::NotFound)
                 .map(|user| user.to_owned())
         }
+
+        fn set_password(
+            &mut self,
+            _id: UserID,
+            _password_hash: PasswordHash,
+        ) -> Result<User, UserError> {
+            todo!()
+        }
+
+        fn increment_session_generation(&mut self, _id: UserID) -> Result<User, UserError> {
+            todo!()
+        }
+
+        fn update_password_hash(
+            &mut self,
+            _id: UserID,
+            _password_hash: PasswordHash,
+        ) -> Result<(), UserError> {
+            todo!()
+        }
+
+        fn set_email(
+            &mut self,
+            _id: UserID,
+            _email: email_address::EmailAddress,
+        ) -> Result<User, UserError> {
+            todo!()
+        }
+
+        fn delete(&mut self, _id: UserID) -> Result<(), UserError> {
+            todo!()
+        }
+
+        fn get_preferences(
+            &self,
+            _user_id: UserID,
+        ) -> Result<crate::models::UserPreferences, UserError> {
+            todo!()
+        }
+
+        fn set_timezone(
+            &mut self,
+            _user_id: UserID,
+            _timezone: &str,
+        ) -> Result<crate::models::UserPreferences, UserError> {
+            todo!()
+        }
+
+        fn set_theme(
+            &mut self,
+            _user_id: UserID,
+            _theme: crate::models::Theme,
+        ) -> Result<crate::models::UserPreferences, UserError> {
+            todo!()
+        }
+
+        fn set_landing_page(
+            &mut self,
+            _user_id: UserID,
+            _landing_page: crate::models::LandingPage,
+        ) -> Result<crate::models::UserPreferences, crate::stores::UserError> {
+            todo!()
+        }
+
+        fn set_date_periods(
+            &mut self,
+            _user_id: UserID,
+            _week_start: crate::models::WeekStart,
+            _month_start_day: u8,
+        ) -> Result<crate::models::UserPreferences, crate::stores::UserError> {
+            todo!()
+        }
     }
 
     #[derive(Clone)]
@@ -285,6 +471,27 @@ mod tests {
         fn get_by_user(&self, _user_id: UserID) -> Result<Vec<Category>, CategoryError> {
             todo!()
         }
+
+        fn search(
+            &self,
+            _user_id: UserID,
+            _prefix: &str,
+            _limit: u64,
+        ) -> Result<Vec<Category>, CategoryError> {
+            todo!()
+        }
+
+        fn suggest(
+            &self,
+
+            _user_id: UserID,
+
+            _description: &str,
+
+            _limit: u64,
+        ) -> Result<Vec<Category>, CategoryError> {
+            todo!()
+        }
     }
 
     #[derive(Clone)]
@@ -306,6 +513,13 @@ mod tests {
             todo!()
         }
 
+        fn create_batch(
+            &mut self,
+            _transactions: Vec<NewTransaction>,
+        ) -> Result<Vec<BatchOutcome>, TransactionError> {
+            todo!()
+        }
+
         fn get(&self, _id: DatabaseID) -> Result<Transaction, TransactionError> {
             todo!()
         }
@@ -320,19 +534,84 @@ mod tests {
         ) -> Result<Vec<Transaction>, TransactionError> {
             todo!()
         }
+
+        fn count_query(&self, _filter: TransactionQuery) -> Result<u64, TransactionError> {
+            todo!()
+        }
+
+        fn sum_query(&self, _filter: TransactionQuery) -> Result<f64, TransactionError> {
+            todo!()
+        }
+
+        fn set_ignored(
+            &mut self,
+            _id: DatabaseID,
+            _ignored: bool,
+        ) -> Result<Transaction, TransactionError> {
+            todo!()
+        }
+
+        fn set_reconciled(&mut self, _ids: &[DatabaseID]) -> Result<(), TransactionError> {
+            todo!()
+        }
+
+        fn update(
+            &mut self,
+            _id: DatabaseID,
+            _builder: TransactionBuilder,
+        ) -> Result<Transaction, TransactionError> {
+            todo!()
+        }
+
+        fn delete(&mut self, _id: DatabaseID) -> Result<(), TransactionError> {
+            todo!()
+        }
+
+        fn get_duplicates(
+            &self,
+            _user_id: UserID,
+        ) -> Result<Vec<Vec<Transaction>>, TransactionError> {
+            todo!()
+        }
+
+        fn merge_duplicates(
+            &mut self,
+            _keep_id: DatabaseID,
+            _duplicate_ids: &[DatabaseID],
+        ) -> Result<Transaction, TransactionError> {
+            todo!()
+        }
     }
 
-    fn get_test_app_config() -> AppState<DummyCategoryStore, DummyTransactionStore, StubUserStore> {
+    type TestAppState = AppState<DummyCategoryStore, DummyTransactionStore, StubUserStore>;
+
+    fn get_test_app_config() -> TestAppState {
         let category_store = DummyCategoryStore {};
         let transaction_store = DummyTransactionStore {};
-        let user_store = StubUserStore { users: vec![] };
+        let user_store = StubUserStore {
+            users: vec![],
+            invite_codes: Arc::new(Mutex::new(vec![])),
+        };
 
         AppState::new("42", category_store, transaction_store, user_store)
     }
 
-    #[derive(Serialize, Deserialize)]
-    struct Foo {
-        bar: String,
+    fn get_test_server(state: TestAppState) -> TestServer {
+        let app = Router::new()
+            .route(endpoints::REGISTER, get(get_register_page))
+            .route(endpoints::USERS, post(create_user))
+            .with_state(state);
+
+        TestServer::new(app).expect("Could not create test server.")
+    }
+
+    fn get_register_form(invite_code: Option<&str>) -> RegisterForm {
+        RegisterForm {
+            email: "foo@bar.baz".to_string(),
+            password: "iamtestingwhethericancreateanewuser".to_string(),
+            confirm_password: "iamtestingwhethericancreateanewuser".to_string(),
+            invite_code: invite_code.map(str::to_string),
+        }
     }
 
     #[tokio::test]
@@ -349,6 +628,7 @@ mod tests {
                 email: "foo@bar.baz".to_string(),
                 password: "iamtestingwhethericancreateanewuser".to_string(),
                 confirm_password: "iamtestingwhethericancreateanewuser".to_string(),
+                invite_code: None,
             })
             .await
             .assert_status_see_other();
@@ -368,10 +648,95 @@ mod tests {
                 email: "foo@bar.baz".to_string(),
                 password: "iamtestingwhethericancreateanewuser".to_string(),
                 confirm_password: "thisisadifferentpassword".to_string(),
+                invite_code: None,
             })
             .await
             .text();
 
         assert!(response.to_lowercase().contains("passwords do not match"))
     }
+
+    #[tokio::test]
+    async fn register_page_has_no_invite_code_when_open() {
+        let server = get_test_server(get_test_app_config());
+
+        let response = server.get(endpoints::REGISTER).await;
+
+        response.assert_status_ok();
+        assert!(!response.text().contains("invite_code"));
+    }
+
+    #[tokio::test]
+    async fn registration_is_forbidden_when_closed() {
+        let mut state = get_test_app_config();
+        state.registration_mode = RegistrationMode::Closed;
+        let server = get_test_server(state.clone());
+
+        let response = server.get(endpoints::REGISTER).await;
+        response.assert_status(StatusCode::FORBIDDEN);
+        response.assert_text_contains("registration is closed");
+
+        server
+            .post(endpoints::USERS)
+            .form(&get_register_form(None))
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+        assert!(state.user_store().users.is_empty());
+    }
+
+    #[tokio::test]
+    async fn register_page_prefills_invite_code_when_invite_only() {
+        let mut state = get_test_app_config();
+        state.registration_mode = RegistrationMode::InviteOnly;
+        let server = get_test_server(state);
+
+        let response = server
+            .get(endpoints::REGISTER)
+            .add_query_param("invite", "foobar")
+            .await;
+
+        response.assert_status_ok();
+        response.assert_text_contains("name=\"invite_code\"");
+        response.assert_text_contains("value=\"foobar\"");
+    }
+
+    #[tokio::test]
+    async fn create_user_fails_with_invalid_invite_code() {
+        let mut state = get_test_app_config();
+        state.registration_mode = RegistrationMode::InviteOnly;
+        state
+            .user_store()
+            .invite_codes
+            .lock()
+            .unwrap()
+            .push("foobar".to_string());
+        let server = get_test_server(state);
+
+        for invite_code in [None, Some("wrongcode")] {
+            let response = server
+                .post(endpoints::USERS)
+                .form(&get_register_form(invite_code))
+                .await;
+
+            response.assert_status_ok();
+            response.assert_text_contains("The invite code is invalid or has already been used");
+        }
+    }
+
+    #[tokio::test]
+    async fn create_user_consumes_invite_code() {
+        let mut state = get_test_app_config();
+        state.registration_mode = RegistrationMode::InviteOnly;
+        let invite_codes = state.user_store().invite_codes.clone();
+        invite_codes.lock().unwrap().push("foobar".to_string());
+        let server = get_test_server(state);
+
+        server
+            .post(endpoints::USERS)
+            .form(&get_register_form(Some("foobar")))
+            .await
+            .assert_status_see_other();
+
+        assert!(invite_codes.lock().unwrap().is_empty());
+    }
 }
@@ -0,0 +1,383 @@
+//! This file defines the search page, which finds the logged in user's transactions and categories
+//! from one search box, and the dropdown of results under the search box in the navbar.
+
+use askama_axum::Template;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use serde::Deserialize;
+
+use crate::{
+    filters,
+    models::{Transaction, UserID},
+    stores::{
+        transaction::{IgnoredFilter, SortOrder, TransactionQuery},
+        CategoryStore, TransactionStore, UserStore,
+    },
+    AppError, AppState,
+};
+
+use super::{
+    endpoints::{self, format_endpoint},
+    navigation::{get_nav_bar, NavbarTemplate},
+    templates::render,
+    transactions::TransactionsPageQuery,
+};
+
+/// The fewest characters to search for, since shorter searches match almost everything.
+const MIN_SEARCH_LENGTH: usize = 2;
+
+/// The most transactions listed in the search results.
+const TRANSACTION_RESULT_LIMIT: u64 = 20;
+
+/// The most categories listed in the search results.
+const CATEGORY_RESULT_LIMIT: usize = 10;
+
+/// A transaction whose description matches the search.
+struct TransactionResult {
+    transaction: Transaction,
+    href: String,
+}
+
+/// A category whose name matches the search.
+struct CategoryResult {
+    name: String,
+    /// The link to the transactions page filtered by the category.
+    href: String,
+}
+
+/// Renders the search results grouped by type, with the matching text highlighted.
+#[derive(Template)]
+#[template(path = "partials/search/results.html")]
+struct SearchResultsTemplate {
+    /// The text that was searched for, with surrounding whitespace removed.
+    query: String,
+    /// Whether the search is too short to run, in which case the user is asked to type more.
+    is_too_short: bool,
+    min_search_length: usize,
+    /// The matching transactions, most recent first.
+    transactions: Vec<TransactionResult>,
+    /// The matching categories, sorted by name.
+    categories: Vec<CategoryResult>,
+}
+
+/// Renders the search page.
+#[derive(Template)]
+#[template(path = "views/search.html")]
+struct SearchTemplate<'a> {
+    navbar: NavbarTemplate<'a>,
+    search_route: &'a str,
+    results: SearchResultsTemplate,
+}
+
+/// The query parameters for searching.
+#[derive(Debug, Default, Deserialize)]
+pub struct SearchQuery {
+    /// The text to search for.
+    #[serde(default)]
+    pub q: String,
+}
+
+/// Search the transaction descriptions and category names of the user `user_id` for `query`,
+/// ignoring case.
+///
+/// Searches shorter than [MIN_SEARCH_LENGTH] are not run.
+fn search<C, T, U>(
+    state: &mut AppState<C, T, U>,
+    user_id: UserID,
+    query: &str,
+) -> Result<SearchResultsTemplate, AppError>
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let query = query.trim();
+    let mut results = SearchResultsTemplate {
+        query: query.to_string(),
+        is_too_short: query.chars().count() < MIN_SEARCH_LENGTH,
+        min_search_length: MIN_SEARCH_LENGTH,
+        transactions: Vec::new(),
+        categories: Vec::new(),
+    };
+
+    if results.is_too_short {
+        return Ok(results);
+    }
+
+    results.transactions = state
+        .transaction_store()
+        .get_query(TransactionQuery {
+            user_id: Some(user_id),
+            description_contains: Some(query.to_string()),
+            ignored: IgnoredFilter::Include,
+            sort_date: Some(SortOrder::Descending),
+            limit: Some(TRANSACTION_RESULT_LIMIT),
+            ..Default::default()
+        })
+        .map_err(AppError::TransactionError)?
+        .into_iter()
+        .map(|transaction| TransactionResult {
+            href: format_endpoint(endpoints::TRANSACTION, transaction.id()),
+            transaction,
+        })
+        .collect();
+
+    let lowercase_query = query.to_lowercase();
+    let mut categories: Vec<_> = state
+        .category_store()
+        .get_by_user(user_id)?
+        .into_iter()
+        .filter(|category| {
+            category
+                .name()
+                .as_ref()
+                .to_lowercase()
+                .contains(&lowercase_query)
+        })
+        .collect();
+    categories.sort_by_key(|category| category.name().as_ref().to_lowercase());
+    results.categories = categories
+        .into_iter()
+        .take(CATEGORY_RESULT_LIMIT)
+        .map(|category| CategoryResult {
+            name: category.name().as_ref().to_string(),
+            href: TransactionsPageQuery {
+                category_id: Some(category.id()),
+                ..Default::default()
+            }
+            .href(),
+        })
+        .collect();
+
+    Ok(results)
+}
+
+/// Display the search page with the results for the query parameter `q`.
+pub async fn get_search_page<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Query(query): Query<SearchQuery>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let results = match search(&mut state, user_id, &query.q) {
+        Ok(results) => results,
+        Err(error) => return error.into_response(),
+    };
+
+    render(
+        StatusCode::OK,
+        &SearchTemplate {
+            navbar: get_nav_bar(endpoints::SEARCH, state.demo_mode),
+            search_route: endpoints::SEARCH,
+            results,
+        },
+    )
+}
+
+/// Get the results for the query parameter `q` without the rest of the search page, e.g. for the
+/// dropdown under the search box in the navbar.
+pub async fn get_search_results<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Query(query): Query<SearchQuery>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    match search(&mut state, user_id, &query.q) {
+        Ok(results) => render(StatusCode::OK, &results),
+        Err(error) => error.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod search_tests {
+    use axum::{
+        body::to_bytes,
+        extract::{Query, State},
+        http::StatusCode,
+        response::Response,
+        Extension,
+    };
+    use rusqlite::Connection;
+
+    use crate::{
+        models::{CategoryName, PasswordHash, Transaction, User},
+        routes::endpoints::{self, format_endpoint},
+        stores::{
+            sql_store::{create_app_state, SQLAppState},
+            CategoryStore, TransactionStore, UserStore,
+        },
+    };
+
+    use super::{get_search_page, get_search_results, SearchQuery};
+
+    fn get_test_state_and_user() -> (SQLAppState, User) {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        let user = state
+            .user_store()
+            .create(
+                "test@test.com".parse().unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+
+        (state, user)
+    }
+
+    async fn get_body_text(response: Response) -> String {
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        String::from_utf8_lossy(&body).to_string()
+    }
+
+    async fn search(state: SQLAppState, user: &User, q: &str) -> String {
+        let response = get_search_results(
+            State(state),
+            Extension(user.id()),
+            Query(SearchQuery { q: q.to_string() }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        get_body_text(response).await
+    }
+
+    #[tokio::test]
+    async fn search_finds_transactions_and_categories() {
+        let (mut state, user) = get_test_state_and_user();
+        let category = state
+            .category_store()
+            .create(CategoryName::new_unchecked("Buckstars"), user.id())
+            .unwrap();
+        let transaction = state
+            .transaction_store()
+            .create_from_builder(
+                Transaction::build(-5.0, user.id()).description("BUCKSTARS COFFEE".to_string()),
+            )
+            .unwrap();
+        state
+            .transaction_store()
+            .create_from_builder(
+                Transaction::build(-80.0, user.id()).description("Groceries".to_string()),
+            )
+            .unwrap();
+
+        let text = search(state, &user, " buckstars ").await;
+
+        assert!(text.contains(&format!(
+            "href=\"{}\"",
+            format_endpoint(endpoints::TRANSACTION, transaction.id())
+        )));
+        assert!(text.contains("<mark>BUCKSTARS</mark> COFFEE"), "got {text}");
+        assert!(text.contains(&format!(
+            "href=\"{}?category_id={}\"",
+            endpoints::TRANSACTIONS,
+            category.id()
+        )));
+        assert!(text.contains("<mark>Buckstars</mark>"));
+        assert!(!text.contains("Groceries"));
+    }
+
+    #[tokio::test]
+    async fn short_search_asks_for_more() {
+        let (mut state, user) = get_test_state_and_user();
+        state
+            .transaction_store()
+            .create_from_builder(
+                Transaction::build(-5.0, user.id()).description("Bus fare".to_string()),
+            )
+            .unwrap();
+
+        let text = search(state, &user, "b").await;
+
+        assert!(text.contains("Type at least 2 characters to search."));
+        assert!(!text.contains("Bus fare"));
+    }
+
+    #[tokio::test]
+    async fn search_treats_percent_literally() {
+        let (mut state, user) = get_test_state_and_user();
+        for description in ["50% off sale", "500 bolts"] {
+            state
+                .transaction_store()
+                .create_from_builder(
+                    Transaction::build(-5.0, user.id()).description(description.to_string()),
+                )
+                .unwrap();
+        }
+
+        let text = search(state, &user, "50%").await;
+
+        assert!(text.contains("<mark>50%</mark> off sale"), "got {text}");
+        assert!(!text.contains("bolts"));
+    }
+
+    #[tokio::test]
+    async fn search_escapes_query_and_results() {
+        let (mut state, user) = get_test_state_and_user();
+        state
+            .transaction_store()
+            .create_from_builder(
+                Transaction::build(-5.0, user.id())
+                    .description("<script>alert(1)</script>".to_string()),
+            )
+            .unwrap();
+
+        let response = get_search_page(
+            State(state),
+            Extension(user.id()),
+            Query(SearchQuery {
+                q: "<script>".to_string(),
+            }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let text = get_body_text(response).await;
+
+        assert!(!text.contains("<script>alert"), "got {text}");
+        assert!(text.contains("<mark>&lt;script&gt;</mark>alert(1)&lt;/script&gt;"));
+        assert!(text.contains("value=\"&lt;script&gt;\""));
+    }
+
+    #[tokio::test]
+    async fn search_excludes_other_users_data() {
+        let (mut state, user) = get_test_state_and_user();
+        let other_user = state
+            .user_store()
+            .create(
+                "other@test.com".parse().unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+        state
+            .category_store()
+            .create(
+                CategoryName::new_unchecked("Secret category"),
+                other_user.id(),
+            )
+            .unwrap();
+        state
+            .transaction_store()
+            .create_from_builder(
+                Transaction::build(-5.0, other_user.id())
+                    .description("Secret transaction".to_string()),
+            )
+            .unwrap();
+
+        let text = search(state, &user, "secret").await;
+
+        assert!(!text.contains("ecret"), "got {text}");
+        assert!(text.contains("No matches"));
+    }
+}
@@ -0,0 +1,1037 @@
+//! The account settings page for logged in users.
+
+use std::str::FromStr;
+
+use askama_axum::Template;
+use axum::{
+    extract::State,
+    http::{StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    Extension, Form,
+};
+use axum_extra::extract::PrivateCookieJar;
+use axum_htmx::HxRedirect;
+use email_address::EmailAddress;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::{
+    auth::cookie::invalidate_auth_cookie,
+    models::{LandingPage, UserID, WeekStart, MAX_MONTH_START_DAY},
+    routes::get_internal_server_error_redirect,
+    scheduled_backups::format_time_ago,
+    stores::{
+        sql_store::SQLAppState, ApiTokenStore, CategoryStore, TransactionStore, UserError,
+        UserStore,
+    },
+    timezone::{get_timezone, get_timezone_names},
+    AppState,
+};
+
+use super::{
+    api_tokens::ApiTokensTemplate,
+    endpoints,
+    navigation::{get_nav_bar, NavbarTemplate},
+};
+
+#[derive(Template)]
+#[template(path = "views/settings.html")]
+struct SettingsTemplate<'a> {
+    navbar: NavbarTemplate<'a>,
+    change_email_form: ChangeEmailFormTemplate<'a>,
+    timezone_form: TimezoneFormTemplate<'a>,
+    landing_page_form: LandingPageFormTemplate<'a>,
+    date_periods_form: DatePeriodsFormTemplate<'a>,
+    change_password_route: &'a str,
+    invites_route: &'a str,
+    webhooks_route: &'a str,
+    audit_log_route: &'a str,
+    backup_route: &'a str,
+    configuration_route: &'a str,
+    /// When the last scheduled backup was taken, or an empty string if scheduled backups are off.
+    last_backup: String,
+    last_backup_is_stale: bool,
+    vacuum_route: &'a str,
+    analyze_route: &'a str,
+    api_tokens: ApiTokensTemplate<'a>,
+    log_out_everywhere_route: &'a str,
+    delete_account_form: DeleteAccountFormTemplate<'a>,
+}
+
+/// The change email form with inline error messages.
+///
+/// Empty strings mean that there is no message to display.
+#[derive(Template)]
+#[template(path = "partials/settings/change_email_form.html")]
+struct ChangeEmailFormTemplate<'a> {
+    change_email_route: &'a str,
+    email: &'a str,
+    email_error: &'a str,
+    password_error: &'a str,
+    success_message: &'a str,
+}
+
+impl<'a> ChangeEmailFormTemplate<'a> {
+    fn new(email: &'a str) -> Self {
+        Self {
+            change_email_route: endpoints::CHANGE_EMAIL,
+            email,
+            email_error: "",
+            password_error: "",
+            success_message: "",
+        }
+    }
+}
+
+/// The timezone form with inline messages.
+///
+/// Empty strings mean that there is no message to display.
+#[derive(Template)]
+#[template(path = "partials/settings/timezone_form.html")]
+struct TimezoneFormTemplate<'a> {
+    timezone_route: &'a str,
+    timezones: Vec<&'static str>,
+    timezone: &'a str,
+    error_message: &'a str,
+    success_message: &'a str,
+}
+
+impl<'a> TimezoneFormTemplate<'a> {
+    fn new(timezone: &'a str) -> Self {
+        Self {
+            timezone_route: endpoints::TIMEZONE,
+            timezones: get_timezone_names(),
+            timezone,
+            error_message: "",
+            success_message: "",
+        }
+    }
+
+    fn is_selected(&self, name: &str) -> bool {
+        name == self.timezone
+    }
+}
+
+/// The landing page form with an inline success message.
+///
+/// An empty string means that there is no message to display.
+#[derive(Template)]
+#[template(path = "partials/settings/landing_page_form.html")]
+struct LandingPageFormTemplate<'a> {
+    landing_page_route: &'a str,
+    landing_pages: &'a [LandingPage],
+    landing_page: LandingPage,
+    success_message: &'a str,
+}
+
+impl LandingPageFormTemplate<'_> {
+    fn new(landing_page: LandingPage) -> Self {
+        Self {
+            landing_page_route: endpoints::LANDING_PAGE,
+            landing_pages: &LandingPage::ALL,
+            landing_page,
+            success_message: "",
+        }
+    }
+
+    fn is_selected(&self, landing_page: &LandingPage) -> bool {
+        self.landing_page == *landing_page
+    }
+}
+
+/// The form for choosing the days that weeks and months start on, with an inline success
+/// message.
+///
+/// An empty string means that there is no message to display.
+#[derive(Template)]
+#[template(path = "partials/settings/date_periods_form.html")]
+struct DatePeriodsFormTemplate<'a> {
+    date_periods_route: &'a str,
+    week_starts: &'a [WeekStart],
+    week_start: WeekStart,
+    month_start_day: u8,
+    max_month_start_day: u8,
+    success_message: &'a str,
+}
+
+impl DatePeriodsFormTemplate<'_> {
+    fn new(week_start: WeekStart, month_start_day: u8) -> Self {
+        Self {
+            date_periods_route: endpoints::DATE_PERIODS,
+            week_starts: &WeekStart::ALL,
+            week_start,
+            month_start_day,
+            max_month_start_day: MAX_MONTH_START_DAY,
+            success_message: "",
+        }
+    }
+
+    fn is_selected(&self, week_start: &WeekStart) -> bool {
+        self.week_start == *week_start
+    }
+}
+
+/// The delete account form with an inline error message.
+///
+/// An empty string means that there is no message to display.
+#[derive(Template)]
+#[template(path = "partials/settings/delete_account_form.html")]
+struct DeleteAccountFormTemplate<'a> {
+    delete_account_route: &'a str,
+    confirm_email_error: &'a str,
+}
+
+impl Default for DeleteAccountFormTemplate<'_> {
+    fn default() -> Self {
+        Self {
+            delete_account_route: endpoints::DELETE_ACCOUNT,
+            confirm_email_error: "",
+        }
+    }
+}
+
+/// Display the account settings page.
+pub async fn get_settings_page(
+    State(mut state): State<SQLAppState>,
+    Extension(user_id): Extension<UserID>,
+) -> Response {
+    let user = match state.user_store().get(user_id) {
+        Ok(user) => user,
+        Err(error) => {
+            tracing::error!("Could not get user {user_id}: {error}");
+            return Redirect::to(endpoints::INTERNAL_ERROR).into_response();
+        }
+    };
+    let api_tokens = match state.api_token_store().get_by_user_id(user_id) {
+        Ok(api_tokens) => api_tokens,
+        Err(error) => {
+            tracing::error!("Could not get API tokens for user {user_id}: {error}");
+            return Redirect::to(endpoints::INTERNAL_ERROR).into_response();
+        }
+    };
+    let preferences = match state.user_store().get_preferences(user_id) {
+        Ok(preferences) => preferences,
+        Err(error) => {
+            tracing::error!("Could not get the preferences for user {user_id}: {error}");
+            return Redirect::to(endpoints::INTERNAL_ERROR).into_response();
+        }
+    };
+
+    let now = OffsetDateTime::now_utc();
+    let last_backup = match (
+        state.backup_status.is_enabled(),
+        state.backup_status.last_success(),
+    ) {
+        (false, _) => String::new(),
+        (true, Some(last_success)) => {
+            format!("Last backup: {}", format_time_ago(now - last_success))
+        }
+        (true, None) => "Last backup: never".to_owned(),
+    };
+
+    SettingsTemplate {
+        navbar: get_nav_bar(endpoints::SETTINGS, state.demo_mode),
+        change_email_form: ChangeEmailFormTemplate::new(user.email().as_str()),
+        timezone_form: TimezoneFormTemplate::new(&preferences.timezone),
+        landing_page_form: LandingPageFormTemplate::new(preferences.landing_page),
+        date_periods_form: DatePeriodsFormTemplate::new(
+            preferences.week_start,
+            preferences.month_start_day,
+        ),
+        change_password_route: endpoints::CHANGE_PASSWORD,
+        invites_route: endpoints::INVITES,
+        webhooks_route: endpoints::WEBHOOKS,
+        audit_log_route: endpoints::AUDIT_LOG,
+        backup_route: endpoints::BACKUP,
+        configuration_route: endpoints::CONFIGURATION,
+        last_backup,
+        last_backup_is_stale: state.backup_status.is_stale(now),
+        vacuum_route: endpoints::VACUUM,
+        analyze_route: endpoints::ANALYZE,
+        api_tokens: ApiTokensTemplate::new(api_tokens),
+        log_out_everywhere_route: endpoints::LOG_OUT_EVERYWHERE,
+        delete_account_form: DeleteAccountFormTemplate::default(),
+    }
+    .into_response()
+}
+
+/// The form data for changing a user's email address.
+#[derive(Serialize, Deserialize)]
+pub struct ChangeEmailForm {
+    /// The email address to change to.
+    pub email: String,
+    /// The user's password, to confirm that the change was made by the account owner.
+    pub password: String,
+}
+
+/// Change the logged in user's email address.
+///
+/// Responds with the change email form, with inline error messages if the email could not be
+/// changed.
+pub async fn change_email<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Form(form): Form<ChangeEmailForm>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let user = match state.user_store().get(user_id) {
+        Ok(user) => user,
+        Err(error) => {
+            tracing::error!("Could not get user {user_id}: {error}");
+            return get_internal_server_error_redirect();
+        }
+    };
+
+    match user.password_hash().verify(&form.password) {
+        Ok(true) => {}
+        Ok(false) => {
+            return ChangeEmailFormTemplate {
+                password_error: "Incorrect password",
+                ..ChangeEmailFormTemplate::new(&form.email)
+            }
+            .into_response()
+        }
+        Err(error) => {
+            tracing::error!("Error verifying password: {error}");
+            return get_internal_server_error_redirect();
+        }
+    }
+
+    let email = match EmailAddress::from_str(&form.email) {
+        Ok(email) => email,
+        Err(error) => {
+            return ChangeEmailFormTemplate {
+                email_error: &format!("Invalid email address: {error}"),
+                ..ChangeEmailFormTemplate::new(&form.email)
+            }
+            .into_response()
+        }
+    };
+
+    match state.user_store().set_email(user_id, email) {
+        Ok(user) => ChangeEmailFormTemplate {
+            success_message: "Your email address has been changed.",
+            ..ChangeEmailFormTemplate::new(user.email().as_str())
+        }
+        .into_response(),
+        Err(UserError::DuplicateEmail) => ChangeEmailFormTemplate {
+            email_error: "The email address is already in use",
+            ..ChangeEmailFormTemplate::new(&form.email)
+        }
+        .into_response(),
+        Err(error) => {
+            tracing::error!("Could not set the email for user {user_id}: {error}");
+            get_internal_server_error_redirect()
+        }
+    }
+}
+
+/// The form data for changing a user's timezone.
+#[derive(Serialize, Deserialize)]
+pub struct TimezoneForm {
+    /// The IANA name of the timezone, e.g. "Pacific/Auckland".
+    pub timezone: String,
+}
+
+/// Change the logged in user's timezone.
+///
+/// Responds with the timezone form, with an inline error message if the timezone is not known.
+pub async fn set_timezone<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Form(form): Form<TimezoneForm>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    if get_timezone(&form.timezone).is_none() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            TimezoneFormTemplate {
+                error_message: "Unknown timezone",
+                ..TimezoneFormTemplate::new(&form.timezone)
+            },
+        )
+            .into_response();
+    }
+
+    match state.user_store().set_timezone(user_id, &form.timezone) {
+        Ok(preferences) => TimezoneFormTemplate {
+            success_message: "Your timezone has been changed.",
+            ..TimezoneFormTemplate::new(&preferences.timezone)
+        }
+        .into_response(),
+        Err(error) => {
+            tracing::error!("Could not set the timezone for user {user_id}: {error}");
+            get_internal_server_error_redirect()
+        }
+    }
+}
+
+/// The form data for changing the page a user is sent to after logging in.
+#[derive(Serialize, Deserialize)]
+pub struct LandingPageForm {
+    /// The page to send the user to after logging in.
+    pub landing_page: LandingPage,
+}
+
+/// Change the page the logged in user is sent to after logging in.
+///
+/// Responds with the landing page form and a message saying that the change was saved.
+pub async fn set_landing_page<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Form(form): Form<LandingPageForm>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    match state
+        .user_store()
+        .set_landing_page(user_id, form.landing_page)
+    {
+        Ok(preferences) => LandingPageFormTemplate {
+            success_message: "Your default page has been changed.",
+            ..LandingPageFormTemplate::new(preferences.landing_page)
+        }
+        .into_response(),
+        Err(error) => {
+            tracing::error!("Could not set the landing page for user {user_id}: {error}");
+            get_internal_server_error_redirect()
+        }
+    }
+}
+
+/// The form data for changing the days that a user's weeks and months start on.
+#[derive(Serialize, Deserialize)]
+pub struct DatePeriodsForm {
+    /// The day that weeks start on.
+    pub week_start: WeekStart,
+    /// The day of the month that months start on, clamped to a day that every month has.
+    pub month_start_day: u8,
+}
+
+/// Change the days that the logged in user's weeks and months start on.
+///
+/// Responds with the form and a message saying that the change was saved.
+pub async fn set_date_periods<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Form(form): Form<DatePeriodsForm>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    match state
+        .user_store()
+        .set_date_periods(user_id, form.week_start, form.month_start_day)
+    {
+        Ok(preferences) => DatePeriodsFormTemplate {
+            success_message: "Your weeks and months have been changed.",
+            ..DatePeriodsFormTemplate::new(preferences.week_start, preferences.month_start_day)
+        }
+        .into_response(),
+        Err(error) => {
+            tracing::error!("Could not set the date periods for user {user_id}: {error}");
+            get_internal_server_error_redirect()
+        }
+    }
+}
+
+/// The form data for deleting a user's account.
+#[derive(Serialize, Deserialize)]
+pub struct DeleteAccountForm {
+    /// The user's email address, typed out to confirm the deletion.
+    pub confirm_email: String,
+}
+
+/// Delete the logged in user's account along with all of their data, then log them out.
+///
+/// Responds with the delete account form and an inline error message if the confirmation email
+/// does not match the user's email address. Accounts cannot be deleted in demo mode, so that the
+/// demo account stays around for other visitors.
+pub async fn delete_account<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    jar: PrivateCookieJar,
+    Form(form): Form<DeleteAccountForm>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    if state.demo_mode {
+        return DeleteAccountFormTemplate {
+            confirm_email_error: "Accounts cannot be deleted in the demo",
+            ..Default::default()
+        }
+        .into_response();
+    }
+
+    let user = match state.user_store().get(user_id) {
+        Ok(user) => user,
+        Err(error) => {
+            tracing::error!("Could not get user {user_id}: {error}");
+            return get_internal_server_error_redirect();
+        }
+    };
+
+    if !form
+        .confirm_email
+        .trim()
+        .eq_ignore_ascii_case(user.email().as_str())
+    {
+        return DeleteAccountFormTemplate {
+            confirm_email_error: "The email address does not match your account",
+            ..Default::default()
+        }
+        .into_response();
+    }
+
+    match state.user_store().delete(user_id) {
+        Ok(()) => {}
+        Err(UserError::OwnerCannotBeDeleted) => {
+            return DeleteAccountFormTemplate {
+                confirm_email_error: "You own this site, so your account cannot be deleted",
+                ..Default::default()
+            }
+            .into_response();
+        }
+        Err(error) => {
+            tracing::error!("Could not delete user {user_id}: {error}");
+            return get_internal_server_error_redirect();
+        }
+    }
+
+    (
+        StatusCode::OK,
+        HxRedirect(Uri::from_static(endpoints::LOG_IN)),
+        invalidate_auth_cookie(jar),
+    )
+        .into_response()
+}
+
+/// Log the current user out of every session, including the current one.
+///
+/// Increments the user's session generation so that the auth guard rejects all existing auth
+/// cookies, then redirects the client to the log-in page.
+pub async fn log_out_everywhere<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    jar: PrivateCookieJar,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    if let Err(error) = state.user_store().increment_session_generation(user_id) {
+        tracing::error!("Could not log out user {user_id} everywhere: {error}");
+        return get_internal_server_error_redirect();
+    }
+
+    (
+        StatusCode::OK,
+        HxRedirect(Uri::from_static(endpoints::LOG_IN)),
+        invalidate_auth_cookie(jar),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod settings_tests {
+    use axum::{
+        http::StatusCode,
+        middleware,
+        routing::{get, post},
+        Router,
+    };
+    use std::time::Duration;
+
+    use axum_test::TestServer;
+    use email_address::EmailAddress;
+    use rusqlite::Connection;
+    use time::OffsetDateTime;
+
+    use crate::{
+        auth::{log_in::LogInData, middleware::auth_guard},
+        models::{LandingPage, PasswordHash, User, ValidatedPassword, WeekStart},
+        routes::{endpoints, log_in::post_log_in, theme::theme_middleware},
+        scheduled_backups::BackupStatus,
+        stores::{
+            sql_store::{create_app_state, SQLAppState},
+            UserError, UserStore,
+        },
+    };
+
+    use super::{
+        change_email, delete_account, get_settings_page, log_out_everywhere, set_date_periods,
+        set_landing_page, set_timezone, ChangeEmailForm, DatePeriodsForm, DeleteAccountForm,
+        LandingPageForm, TimezoneForm,
+    };
+
+    const PASSWORD: &str = "averysafeandsecurepassword";
+
+    fn get_test_server_and_user() -> (TestServer, User) {
+        let (_, server, user) = get_test_state_server_and_user();
+
+        (server, user)
+    }
+
+    fn get_test_state_server_and_user() -> (SQLAppState, TestServer, User) {
+        let (state, user) = get_test_state_and_user();
+        let server = get_test_server(state.clone());
+
+        (state, server, user)
+    }
+
+    fn get_test_state_and_user() -> (SQLAppState, User) {
+        let connection = Connection::open_in_memory().unwrap();
+        let mut state = create_app_state(connection, "42").unwrap();
+        state.password_hash_cost = 4;
+        let user = state
+            .user_store()
+            .create(
+                EmailAddress::new_unchecked("foo@bar.baz"),
+                PasswordHash::new(ValidatedPassword::new_unchecked(PASSWORD), 4).unwrap(),
+            )
+            .unwrap();
+
+        (state, user)
+    }
+
+    fn get_test_server(state: SQLAppState) -> TestServer {
+        let app = Router::new()
+            .route(endpoints::SETTINGS, get(get_settings_page))
+            .route(endpoints::LOG_OUT_EVERYWHERE, post(log_out_everywhere))
+            .route(endpoints::CHANGE_EMAIL, post(change_email))
+            .route(endpoints::DELETE_ACCOUNT, post(delete_account))
+            .route(endpoints::TIMEZONE, post(set_timezone))
+            .route(endpoints::LANDING_PAGE, post(set_landing_page))
+            .route(endpoints::DATE_PERIODS, post(set_date_periods))
+            .layer(middleware::from_fn_with_state(
+                state.clone(),
+                theme_middleware,
+            ))
+            .layer(middleware::from_fn_with_state(state.clone(), auth_guard))
+            .route(endpoints::LOG_IN, post(post_log_in))
+            .with_state(state);
+
+        TestServer::new(app).expect("Could not create test server.")
+    }
+
+    async fn log_in(server: &TestServer, user: &User) -> axum_test::TestResponse {
+        server
+            .post(endpoints::LOG_IN)
+            .form(&LogInData {
+                email: user.email().to_string(),
+                password: PASSWORD.to_string(),
+                remember_me: None,
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn settings_page_has_log_out_everywhere_button() {
+        let (server, user) = get_test_server_and_user();
+        let cookies = log_in(&server, &user).await.cookies();
+
+        let response = server.get(endpoints::SETTINGS).add_cookies(cookies).await;
+
+        response.assert_status_ok();
+        response.assert_text_contains(endpoints::LOG_OUT_EVERYWHERE);
+        response.assert_text_contains(endpoints::CHANGE_PASSWORD);
+    }
+
+    #[tokio::test]
+    async fn settings_page_shows_email_and_account_forms() {
+        let (server, user) = get_test_server_and_user();
+        let cookies = log_in(&server, &user).await.cookies();
+
+        let response = server.get(endpoints::SETTINGS).add_cookies(cookies).await;
+
+        response.assert_status_ok();
+        response.assert_text_contains(user.email().as_str());
+        response.assert_text_contains(endpoints::CHANGE_EMAIL);
+        response.assert_text_contains(endpoints::DELETE_ACCOUNT);
+    }
+
+    #[tokio::test]
+    async fn settings_page_hides_last_backup_when_scheduled_backups_are_off() {
+        let (server, user) = get_test_server_and_user();
+        let cookies = log_in(&server, &user).await.cookies();
+
+        let response = server.get(endpoints::SETTINGS).add_cookies(cookies).await;
+
+        response.assert_status_ok();
+        assert!(!response.text().contains("Last backup"));
+    }
+
+    #[tokio::test]
+    async fn settings_page_shows_last_backup() {
+        let (mut state, user) = get_test_state_and_user();
+        state.backup_status = BackupStatus::new(Duration::from_secs(60 * 60));
+        state
+            .backup_status
+            .record_success(OffsetDateTime::now_utc() - time::Duration::hours(1));
+        let server = get_test_server(state);
+        let cookies = log_in(&server, &user).await.cookies();
+
+        let response = server.get(endpoints::SETTINGS).add_cookies(cookies).await;
+
+        response.assert_status_ok();
+        response.assert_text_contains("Last backup: 1 hour ago");
+        assert!(!response.text().contains("have not succeeded recently"));
+    }
+
+    #[tokio::test]
+    async fn settings_page_warns_when_last_backup_is_stale() {
+        let (mut state, user) = get_test_state_and_user();
+        state.backup_status = BackupStatus::new(Duration::from_secs(60 * 60));
+        state
+            .backup_status
+            .record_success(OffsetDateTime::now_utc() - time::Duration::hours(3));
+        let server = get_test_server(state);
+        let cookies = log_in(&server, &user).await.cookies();
+
+        let response = server.get(endpoints::SETTINGS).add_cookies(cookies).await;
+
+        response.assert_status_ok();
+        response.assert_text_contains("Last backup: 3 hours ago");
+        response.assert_text_contains("have not succeeded recently");
+    }
+
+    #[tokio::test]
+    async fn set_timezone_succeeds() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let cookies = log_in(&server, &user).await.cookies();
+
+        let response = server
+            .post(endpoints::TIMEZONE)
+            .add_cookies(cookies.clone())
+            .form(&TimezoneForm {
+                timezone: "Pacific/Auckland".to_string(),
+            })
+            .await;
+
+        response.assert_status_ok();
+        response.assert_text_contains("Your timezone has been changed.");
+        assert_eq!(
+            state
+                .user_store()
+                .get_preferences(user.id())
+                .unwrap()
+                .timezone,
+            "Pacific/Auckland"
+        );
+        server
+            .get(endpoints::SETTINGS)
+            .add_cookies(cookies)
+            .await
+            .assert_text_contains(r#"<option value="Pacific/Auckland" selected>"#);
+    }
+
+    #[tokio::test]
+    async fn set_landing_page_succeeds() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let cookies = log_in(&server, &user).await.cookies();
+
+        let settings_page = server
+            .get(endpoints::SETTINGS)
+            .add_cookies(cookies.clone())
+            .await;
+        settings_page.assert_text_contains(r#"<option value="dashboard" selected>"#);
+        settings_page.assert_text_contains("Dashboard<span");
+
+        let response = server
+            .post(endpoints::LANDING_PAGE)
+            .add_cookies(cookies.clone())
+            .form(&LandingPageForm {
+                landing_page: LandingPage::Transactions,
+            })
+            .await;
+
+        response.assert_status_ok();
+        response.assert_text_contains("Your default page has been changed.");
+        assert_eq!(
+            state
+                .user_store()
+                .get_preferences(user.id())
+                .unwrap()
+                .landing_page,
+            LandingPage::Transactions
+        );
+        let settings_page = server.get(endpoints::SETTINGS).add_cookies(cookies).await;
+        settings_page.assert_text_contains(r#"<option value="transactions" selected>"#);
+        settings_page.assert_text_contains("Transactions<span");
+        assert!(!settings_page.text().contains("Dashboard<span"));
+    }
+
+    #[tokio::test]
+    async fn set_date_periods_succeeds() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let cookies = log_in(&server, &user).await.cookies();
+
+        let response = server
+            .post(endpoints::DATE_PERIODS)
+            .add_cookies(cookies.clone())
+            .form(&DatePeriodsForm {
+                week_start: WeekStart::Sunday,
+                month_start_day: 31,
+            })
+            .await;
+
+        response.assert_status_ok();
+        response.assert_text_contains("Your weeks and months have been changed.");
+        let preferences = state.user_store().get_preferences(user.id()).unwrap();
+        assert_eq!(preferences.week_start, WeekStart::Sunday);
+        assert_eq!(preferences.month_start_day, 28);
+
+        let settings_page = server.get(endpoints::SETTINGS).add_cookies(cookies).await;
+        settings_page.assert_text_contains(r#"<option value="sunday" selected>"#);
+        settings_page.assert_text_contains(r#"value="28""#);
+    }
+
+    #[tokio::test]
+    async fn set_timezone_fails_with_unknown_timezone() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let cookies = log_in(&server, &user).await.cookies();
+
+        let response = server
+            .post(endpoints::TIMEZONE)
+            .add_cookies(cookies)
+            .form(&TimezoneForm {
+                timezone: "Mars/Olympus_Mons".to_string(),
+            })
+            .await;
+
+        response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        response.assert_text_contains("Unknown timezone");
+        assert_eq!(
+            state.user_store().get_preferences(user.id()).unwrap(),
+            Default::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn change_email_succeeds() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let cookies = log_in(&server, &user).await.cookies();
+
+        let response = server
+            .post(endpoints::CHANGE_EMAIL)
+            .add_cookies(cookies)
+            .form(&ChangeEmailForm {
+                email: "qux@bar.baz".to_string(),
+                password: PASSWORD.to_string(),
+            })
+            .await;
+
+        response.assert_status_ok();
+        response.assert_text_contains("Your email address has been changed.");
+        assert_eq!(
+            state.user_store().get(user.id()).unwrap().email().as_str(),
+            "qux@bar.baz"
+        );
+    }
+
+    #[tokio::test]
+    async fn change_email_fails_with_wrong_password() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let cookies = log_in(&server, &user).await.cookies();
+
+        let response = server
+            .post(endpoints::CHANGE_EMAIL)
+            .add_cookies(cookies)
+            .form(&ChangeEmailForm {
+                email: "qux@bar.baz".to_string(),
+                password: "wrongpassword".to_string(),
+            })
+            .await;
+
+        response.assert_status_ok();
+        response.assert_text_contains("Incorrect password");
+        assert_eq!(
+            state.user_store().get(user.id()).unwrap().email(),
+            user.email()
+        );
+    }
+
+    #[tokio::test]
+    async fn change_email_fails_with_duplicate_email() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        state
+            .user_store()
+            .create(
+                EmailAddress::new_unchecked("qux@bar.baz"),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+        let cookies = log_in(&server, &user).await.cookies();
+
+        let response = server
+            .post(endpoints::CHANGE_EMAIL)
+            .add_cookies(cookies)
+            .form(&ChangeEmailForm {
+                email: "qux@bar.baz".to_string(),
+                password: PASSWORD.to_string(),
+            })
+            .await;
+
+        response.assert_status_ok();
+        response.assert_text_contains("The email address is already in use");
+        assert_eq!(
+            state.user_store().get(user.id()).unwrap().email(),
+            user.email()
+        );
+    }
+
+    #[tokio::test]
+    async fn delete_account_fails_with_wrong_email() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let cookies = log_in(&server, &user).await.cookies();
+
+        let response = server
+            .post(endpoints::DELETE_ACCOUNT)
+            .add_cookies(cookies)
+            .form(&DeleteAccountForm {
+                confirm_email: "qux@bar.baz".to_string(),
+            })
+            .await;
+
+        response.assert_status_ok();
+        response.assert_text_contains("The email address does not match your account");
+        assert!(state.user_store().get(user.id()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn delete_account_fails_for_owner() {
+        let (mut state, server, owner) = get_test_state_server_and_user();
+        let cookies = log_in(&server, &owner).await.cookies();
+
+        let response = server
+            .post(endpoints::DELETE_ACCOUNT)
+            .add_cookies(cookies)
+            .form(&DeleteAccountForm {
+                confirm_email: owner.email().to_string(),
+            })
+            .await;
+
+        response.assert_status_ok();
+        response.assert_text_contains("You own this site, so your account cannot be deleted");
+        assert!(state.user_store().get(owner.id()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn delete_account_fails_in_demo_mode() {
+        let (mut state, _) = get_test_state_and_user();
+        let user = state
+            .user_store()
+            .create(
+                EmailAddress::new_unchecked("qux@bar.baz"),
+                PasswordHash::new(ValidatedPassword::new_unchecked(PASSWORD), 4).unwrap(),
+            )
+            .unwrap();
+        state.demo_mode = true;
+        let server = get_test_server(state.clone());
+        let cookies = log_in(&server, &user).await.cookies();
+
+        let response = server
+            .post(endpoints::DELETE_ACCOUNT)
+            .add_cookies(cookies)
+            .form(&DeleteAccountForm {
+                confirm_email: user.email().to_string(),
+            })
+            .await;
+
+        response.assert_status_ok();
+        response.assert_text_contains("Accounts cannot be deleted in the demo");
+        assert!(state.user_store().get(user.id()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn delete_account_deletes_user_and_logs_out() {
+        let (mut state, _) = get_test_state_and_user();
+        let user = state
+            .user_store()
+            .create(
+                EmailAddress::new_unchecked("qux@bar.baz"),
+                PasswordHash::new(ValidatedPassword::new_unchecked(PASSWORD), 4).unwrap(),
+            )
+            .unwrap();
+        let server = get_test_server(state.clone());
+        let cookies = log_in(&server, &user).await.cookies();
+
+        let response = server
+            .post(endpoints::DELETE_ACCOUNT)
+            .add_cookies(cookies.clone())
+            .form(&DeleteAccountForm {
+                confirm_email: user.email().to_string(),
+            })
+            .await;
+
+        response.assert_status_ok();
+        assert_eq!(response.header("hx-redirect"), endpoints::LOG_IN);
+        assert_eq!(state.user_store().get(user.id()), Err(UserError::NotFound));
+        server
+            .get(endpoints::SETTINGS)
+            .add_cookies(cookies)
+            .await
+            .assert_status_see_other();
+    }
+
+    #[tokio::test]
+    async fn log_out_everywhere_rejects_other_sessions() {
+        let (server, user) = get_test_server_and_user();
+        let this_session = log_in(&server, &user).await.cookies();
+        let other_session = log_in(&server, &user).await.cookies();
+
+        let response = server
+            .post(endpoints::LOG_OUT_EVERYWHERE)
+            .add_cookies(this_session)
+            .await;
+
+        response.assert_status_ok();
+        assert_eq!(response.header("hx-redirect"), endpoints::LOG_IN);
+
+        let response = server
+            .get(endpoints::SETTINGS)
+            .add_cookies(other_session)
+            .await;
+        assert_eq!(response.status_code(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            response.header("location"),
+            endpoints::LOG_IN_SESSION_EXPIRED
+        );
+    }
+
+    #[tokio::test]
+    async fn can_log_in_again_after_logging_out_everywhere() {
+        let (server, user) = get_test_server_and_user();
+        let cookies = log_in(&server, &user).await.cookies();
+        server
+            .post(endpoints::LOG_OUT_EVERYWHERE)
+            .add_cookies(cookies)
+            .await
+            .assert_status_ok();
+
+        let cookies = log_in(&server, &user).await.cookies();
+
+        server
+            .get(endpoints::SETTINGS)
+            .add_cookies(cookies)
+            .await
+            .assert_status_ok();
+    }
+}
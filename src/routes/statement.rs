@@ -0,0 +1,370 @@
+//! This file defines the monthly statement page, which lists a month's transactions grouped by day
+//! like a bank statement.
+
+use std::ops::RangeInclusive;
+
+use askama_axum::Template;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use serde::Deserialize;
+use time::{Date, Month};
+
+use crate::{
+    aggregation::{group_by_day, month_containing, month_range, DayGroup},
+    filters,
+    models::{DatabaseID, UserID},
+    stores::{
+        transaction::{SortOrder, TransactionQuery},
+        CategoryStore, TransactionStore, UserStore,
+    },
+    timezone::{get_preferences_today, get_user_preferences},
+    AppError, AppState,
+};
+
+use super::{
+    endpoints, get_error_response,
+    navigation::{get_nav_bar, NavbarTemplate},
+};
+
+/// Renders a month's transactions grouped by day with a subtotal for each day.
+#[derive(Template)]
+#[template(path = "views/statement.html")]
+struct StatementTemplate<'a> {
+    navbar: NavbarTemplate<'a>,
+    /// The month shown, e.g. "March 2024".
+    title: String,
+    /// The days with transactions, earliest first.
+    days: Vec<DayGroup>,
+    /// The sum of the daily subtotals.
+    total: f64,
+    /// The link to the previous month, if it is in range.
+    previous_href: Option<String>,
+    /// The link to the next month, if it is in range.
+    next_href: Option<String>,
+    /// The link to the same month without the category filter, if one is set.
+    clear_filter_href: Option<String>,
+}
+
+/// The query parameters for the monthly statement page.
+#[derive(Debug, Default, Deserialize)]
+pub struct StatementQuery {
+    /// The year of the month to show. Defaults to the current year.
+    pub year: Option<i32>,
+    /// The month to show, from 1 to 12. Defaults to the current month.
+    pub month: Option<u8>,
+    /// Show only transactions assigned to the category with this ID.
+    pub category_id: Option<DatabaseID>,
+}
+
+/// Build the link to the statement for `month` of `year`, keeping the category filter.
+fn statement_href(year: i32, month: Month, category_id: Option<DatabaseID>) -> String {
+    let href = format!("{}?year={year}&month={}", endpoints::STATEMENT, month as u8);
+
+    match category_id {
+        Some(category_id) => format!("{href}&category_id={category_id}"),
+        None => href,
+    }
+}
+
+/// The title for `month` of `year`, e.g. "March 2024".
+///
+/// Months that do not start on the first also show their dates, e.g.
+/// "March 2024 (2024-03-15 to 2024-04-14)".
+pub(crate) fn month_title(year: i32, month: Month, date_range: &RangeInclusive<Date>) -> String {
+    if date_range.start().day() == 1 {
+        format!("{month} {year}")
+    } else {
+        format!(
+            "{month} {year} ({} to {})",
+            date_range.start(),
+            date_range.end()
+        )
+    }
+}
+
+/// Get the month selected by `year` and `month` and the date range covering it, defaulting to
+/// the month containing `today`.
+///
+/// Months start on `month_start_day`, see [month_range].
+///
+/// # Errors
+/// Returns a 400 Bad Request response if `month` is not from 1 to 12, or `year` is out of range.
+#[allow(clippy::result_large_err)]
+pub(crate) fn get_month(
+    today: Date,
+    year: Option<i32>,
+    month: Option<u8>,
+    month_start_day: u8,
+) -> Result<(i32, Month, RangeInclusive<Date>), Response> {
+    let (current_year, current_month) = month_containing(today, month_start_day);
+    let year = year.unwrap_or(current_year);
+    let month = match month.map(Month::try_from) {
+        Some(Ok(month)) => month,
+        Some(Err(_)) => {
+            return Err(get_error_response(
+                StatusCode::BAD_REQUEST,
+                "invalid_month",
+                "The month must be a number from 1 to 12.",
+            ))
+        }
+        None => current_month,
+    };
+    let Some(date_range) = month_range(year, month, month_start_day) else {
+        return Err(get_error_response(
+            StatusCode::BAD_REQUEST,
+            "invalid_year",
+            "The year is out of range.",
+        ));
+    };
+
+    Ok((year, month, date_range))
+}
+
+/// Display the transactions in a month grouped by day, with daily subtotals and a month total.
+///
+/// Defaults to the current month in the user's timezone, where months start on the user's chosen
+/// month start day.
+pub async fn get_statement_page<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Query(query): Query<StatementQuery>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let preferences = get_user_preferences(state.user_store(), user_id);
+    let today = get_preferences_today(&preferences);
+    let month_start_day = preferences.month_start_day;
+    let (year, month, date_range) = match get_month(today, query.year, query.month, month_start_day)
+    {
+        Ok(month) => month,
+        Err(response) => return response,
+    };
+
+    let transactions = match state.transaction_store().get_query(TransactionQuery {
+        user_id: Some(user_id),
+        date_range: Some(date_range.clone()),
+        category_id: query.category_id,
+        sort_date: Some(SortOrder::Ascending),
+        ..Default::default()
+    }) {
+        Ok(transactions) => transactions,
+        Err(error) => return AppError::TransactionError(error).into_response(),
+    };
+
+    let days = group_by_day(transactions);
+    let total = days.iter().map(|day| day.subtotal).sum();
+
+    let previous_year = if month == Month::January {
+        year - 1
+    } else {
+        year
+    };
+    let next_year = if month == Month::December {
+        year + 1
+    } else {
+        year
+    };
+
+    StatementTemplate {
+        navbar: get_nav_bar(endpoints::STATEMENT, state.demo_mode),
+        title: month_title(year, month, &date_range),
+        days,
+        total,
+        previous_href: month_range(previous_year, month.previous(), month_start_day)
+            .map(|_| statement_href(previous_year, month.previous(), query.category_id)),
+        next_href: month_range(next_year, month.next(), month_start_day)
+            .map(|_| statement_href(next_year, month.next(), query.category_id)),
+        clear_filter_href: query.category_id.map(|_| statement_href(year, month, None)),
+    }
+    .into_response()
+}
+
+#[cfg(test)]
+mod statement_route_tests {
+    use axum::{
+        body::to_bytes,
+        extract::{Query, State},
+        http::StatusCode,
+        response::Response,
+        Extension,
+    };
+    use rusqlite::Connection;
+    use time::macros::date;
+
+    use crate::{
+        models::{CategoryName, PasswordHash, Transaction, User, ValidatedPassword},
+        routes::endpoints,
+        stores::{
+            sql_store::{create_app_state, SQLAppState},
+            CategoryStore, TransactionStore, UserStore,
+        },
+    };
+
+    use super::{get_statement_page, StatementQuery};
+
+    fn get_test_state_and_user() -> (SQLAppState, User) {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        let user = state
+            .user_store()
+            .create(
+                "test@test.com".parse().unwrap(),
+                PasswordHash::new(ValidatedPassword::new_unchecked("test"), 4).unwrap(),
+            )
+            .unwrap();
+
+        (state, user)
+    }
+
+    async fn get_body_text(response: Response) -> String {
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        String::from_utf8_lossy(&body).to_string()
+    }
+
+    async fn get_statement(state: SQLAppState, user: &User, query: StatementQuery) -> Response {
+        get_statement_page(State(state), Extension(user.id()), Query(query)).await
+    }
+
+    #[tokio::test]
+    async fn statement_groups_transactions_by_day() {
+        let (mut state, user) = get_test_state_and_user();
+        let builders = [
+            Transaction::build(-12.5, user.id())
+                .description("Lunch".to_string())
+                .date(date!(2024 - 03 - 05))
+                .unwrap(),
+            Transaction::build(1000.0, user.id())
+                .description("Salary".to_string())
+                .date(date!(2024 - 03 - 01))
+                .unwrap(),
+            Transaction::build(-40.0, user.id())
+                .description("Groceries".to_string())
+                .date(date!(2024 - 03 - 01))
+                .unwrap(),
+            // Transactions outside of the month should not be included.
+            Transaction::build(-500.0, user.id())
+                .description("Last month".to_string())
+                .date(date!(2024 - 02 - 29))
+                .unwrap(),
+        ];
+        for builder in builders {
+            state
+                .transaction_store()
+                .create_from_builder(builder)
+                .unwrap();
+        }
+
+        let response = get_statement(
+            state,
+            &user,
+            StatementQuery {
+                year: Some(2024),
+                month: Some(3),
+                category_id: None,
+            },
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let text = get_body_text(response).await;
+        assert!(text.contains("March 2024"));
+        assert!(!text.contains("Last month"));
+        let positions: Vec<_> = [
+            "2024-03-01",
+            "Salary",
+            "Groceries",
+            "$960.00",
+            "2024-03-05",
+            "Lunch",
+            "-$12.50",
+            "Month total",
+            "$947.50",
+        ]
+        .iter()
+        .map(|text_to_find| text.find(text_to_find).unwrap())
+        .collect();
+        assert!(
+            positions.is_sorted(),
+            "days should be in order with their subtotals, got positions {positions:?}"
+        );
+        assert!(text.contains(&format!("{}?year=2024&amp;month=2", endpoints::STATEMENT)));
+        assert!(text.contains(&format!("{}?year=2024&amp;month=4", endpoints::STATEMENT)));
+    }
+
+    #[tokio::test]
+    async fn statement_filters_by_category() {
+        let (mut state, user) = get_test_state_and_user();
+        let food = state
+            .category_store()
+            .create(CategoryName::new_unchecked("Food"), user.id())
+            .unwrap();
+        let builders = [
+            Transaction::build(-40.0, user.id())
+                .description("Groceries".to_string())
+                .category(Some(food.id()))
+                .date(date!(2023 - 12 - 24))
+                .unwrap(),
+            Transaction::build(-100.0, user.id())
+                .description("Power bill".to_string())
+                .date(date!(2023 - 12 - 24))
+                .unwrap(),
+        ];
+        for builder in builders {
+            state
+                .transaction_store()
+                .create_from_builder(builder)
+                .unwrap();
+        }
+
+        let response = get_statement(
+            state,
+            &user,
+            StatementQuery {
+                year: Some(2023),
+                month: Some(12),
+                category_id: Some(food.id()),
+            },
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let text = get_body_text(response).await;
+        assert!(text.contains("Groceries"));
+        assert!(!text.contains("Power bill"));
+        assert!(!text.contains("-$140.00"));
+        assert!(text.contains(&format!(
+            "{}?year=2024&amp;month=1&amp;category_id={}",
+            endpoints::STATEMENT,
+            food.id()
+        )));
+        assert!(text.contains(&format!(
+            "{}?year=2023&amp;month=12\"",
+            endpoints::STATEMENT
+        )));
+    }
+
+    #[tokio::test]
+    async fn statement_rejects_invalid_month() {
+        let (state, user) = get_test_state_and_user();
+
+        let response = get_statement(
+            state,
+            &user,
+            StatementQuery {
+                year: Some(2024),
+                month: Some(13),
+                category_id: None,
+            },
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,161 @@
+//! This file defines a plain-text summary of the user's finances for use in terminals and scripts.
+
+use crate::{
+    aggregation::{last_week, month_containing, month_to_date, totals, uncategorised, Totals},
+    models::UserID,
+    stores::{transaction::TransactionQuery, CategoryStore, TransactionStore, UserStore},
+    timezone::{get_preferences_today, get_user_preferences},
+    AppError, AppState,
+};
+use askama_axum::Template;
+use axum::{
+    extract::State,
+    response::{IntoResponse, Response},
+    Extension,
+};
+
+/// Renders the plain-text summary.
+///
+/// The summary has one `key: value` pair per line. The keys and their order are stable so that
+/// scripts can rely on them:
+///
+/// ```text
+/// week_net: 123.45
+/// month: 2024-08
+/// month_income: 1000.00
+/// month_expenses: -250.00
+/// month_net: 750.00
+/// month_uncategorised: 3
+/// ```
+///
+/// Amounts are formatted with two decimal places and ignored transactions are excluded.
+#[derive(Template)]
+#[template(path = "text/summary.txt")]
+struct SummaryTemplate {
+    /// The net amount for the last week, the same as the dashboard balance.
+    week_net: f64,
+    /// The current month in the format YYYY-MM, where months start on the user's chosen month
+    /// start day.
+    month: String,
+    /// The income, expenses and net amount for the month to date.
+    month_totals: Totals,
+    /// The number of transactions in the month to date without a category.
+    month_uncategorised: usize,
+}
+
+/// Get a plain-text summary of the user's finances, e.g. for `curl` or a status bar.
+pub async fn get_summary_text<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let preferences = get_user_preferences(state.user_store(), user_id);
+    let today = get_preferences_today(&preferences);
+    let (year, month) = month_containing(today, preferences.month_start_day);
+
+    let week_transactions = match state.transaction_store().get_query(TransactionQuery {
+        user_id: Some(user_id),
+        date_range: Some(last_week(today)),
+        ..Default::default()
+    }) {
+        Ok(transactions) => transactions,
+        Err(error) => return AppError::TransactionError(error).into_response(),
+    };
+
+    let month_transactions = match state.transaction_store().get_query(TransactionQuery {
+        user_id: Some(user_id),
+        date_range: Some(month_to_date(today, preferences.month_start_day)),
+        ..Default::default()
+    }) {
+        Ok(transactions) => transactions,
+        Err(error) => return AppError::TransactionError(error).into_response(),
+    };
+
+    SummaryTemplate {
+        week_net: totals(&week_transactions).net,
+        month: format!("{year}-{:02}", month as u8),
+        month_totals: totals(&month_transactions),
+        month_uncategorised: uncategorised(&month_transactions).count,
+    }
+    .into_response()
+}
+
+#[cfg(test)]
+mod summary_route_tests {
+    use axum::{body::to_bytes, extract::State, http::StatusCode, Extension};
+    use rusqlite::Connection;
+    use time::OffsetDateTime;
+
+    use crate::{
+        models::{CategoryName, PasswordHash, Transaction, ValidatedPassword},
+        stores::{sql_store::create_app_state, CategoryStore, TransactionStore, UserStore},
+    };
+
+    use super::get_summary_text;
+
+    #[tokio::test]
+    async fn summary_has_stable_format() {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        let user = state
+            .user_store()
+            .create(
+                "test@test.com".parse().unwrap(),
+                PasswordHash::new(ValidatedPassword::new_unchecked("test"), 4).unwrap(),
+            )
+            .unwrap();
+        let other_user = state
+            .user_store()
+            .create(
+                "other@test.com".parse().unwrap(),
+                PasswordHash::new(ValidatedPassword::new_unchecked("test"), 4).unwrap(),
+            )
+            .unwrap();
+        let category = state
+            .category_store()
+            .create(CategoryName::new_unchecked("Food"), user.id())
+            .unwrap();
+
+        for builder in [
+            Transaction::build(100.0, user.id()),
+            Transaction::build(-30.5, user.id()),
+            Transaction::build(-10.0, user.id()).category(Some(category.id())),
+            Transaction::build(999.0, user.id()).ignored(true),
+            Transaction::build(42.0, other_user.id()),
+        ] {
+            state
+                .transaction_store()
+                .create_from_builder(builder)
+                .unwrap();
+        }
+
+        let response = get_summary_text(State(state), Extension(user.id())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response
+            .headers()
+            .get("content-type")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain"));
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let today = OffsetDateTime::now_utc().date();
+        let want = format!(
+            "week_net: 59.50\n\
+             month: {}-{:02}\n\
+             month_income: 100.00\n\
+             month_expenses: -40.50\n\
+             month_net: 59.50\n\
+             month_uncategorised: 2",
+            today.year(),
+            today.month() as u8
+        );
+
+        assert_eq!(String::from_utf8_lossy(&body), want);
+    }
+}
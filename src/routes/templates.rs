@@ -1,8 +1,44 @@
 /*! Askama HTML templates that are shared between views. */
 
 use askama::Template;
+use axum::{
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+};
+use time::Date;
 
-use crate::models::Transaction;
+use crate::{
+    filters,
+    models::{DatabaseID, Transaction, UserID},
+    routes::endpoints,
+};
+
+use super::errors::get_error_response;
+
+/// Render `template` as an HTML response with the status code `status`.
+///
+/// Prefer this to the `IntoResponse` impl that Askama derives, which responds to a rendering
+/// error, e.g. a failing filter, with an empty 500 response and logs nothing. Here the error is
+/// logged with a short random ID, and the client gets the 500 error page, or an alert for HTMX
+/// requests, showing the same ID so that users can quote it when reporting the problem.
+pub(crate) fn render(status: StatusCode, template: &impl Template) -> Response {
+    match template.render() {
+        Ok(html) => (status, Html(html)).into_response(),
+        Err(error) => {
+            let error_id = format!("{:08x}", rand::random::<u32>());
+            tracing::error!(error_id, "Could not render template: {error}");
+
+            get_error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "template_error",
+                &format!(
+                    "Sorry, something went wrong while showing this page. \
+                    Please quote the error ID {error_id} when reporting this problem."
+                ),
+            )
+        }
+    }
+}
 
 #[derive(Template, Default)]
 #[template(path = "partials/register/inputs/email.html")]
@@ -19,9 +55,264 @@ pub struct PasswordInputTemplate<'a> {
     pub error_message: &'a str,
 }
 
-/// Renders a transaction as a 5 column table row.
+/// Renders a transaction as a 6 column table row, which is hidden on small screens.
 #[derive(Template)]
 #[template(path = "partials/dashboard/transaction.html")]
 pub struct TransactionRow {
     pub transaction: Transaction,
 }
+
+impl TransactionRow {
+    /// The transaction as a card for small screens.
+    pub fn card(&self) -> TransactionCard<'_> {
+        TransactionCard {
+            transaction: &self.transaction,
+        }
+    }
+
+    /// The route for setting whether the transaction is ignored.
+    fn ignored_route(&self) -> String {
+        endpoints::format_endpoint(endpoints::TRANSACTION_IGNORED, self.transaction.id())
+    }
+
+    /// The route for deleting the transaction.
+    fn delete_route(&self) -> String {
+        endpoints::format_endpoint(endpoints::TRANSACTION, self.transaction.id())
+    }
+
+    /// The route for the page showing everything about the transaction.
+    fn detail_route(&self) -> String {
+        endpoints::format_endpoint(endpoints::TRANSACTION, self.transaction.id())
+    }
+}
+
+/// Renders a transaction as a stacked card for small screens, in place of [TransactionRow].
+#[derive(Template)]
+#[template(path = "partials/transactions/card.html")]
+pub struct TransactionCard<'a> {
+    pub transaction: &'a Transaction,
+}
+
+impl TransactionCard<'_> {
+    /// The route for setting whether the transaction is ignored.
+    fn ignored_route(&self) -> String {
+        endpoints::format_endpoint(endpoints::TRANSACTION_IGNORED, self.transaction.id())
+    }
+
+    /// The route for deleting the transaction.
+    fn delete_route(&self) -> String {
+        endpoints::format_endpoint(endpoints::TRANSACTION, self.transaction.id())
+    }
+
+    /// The route for the page showing everything about the transaction.
+    fn detail_route(&self) -> String {
+        endpoints::format_endpoint(endpoints::TRANSACTION, self.transaction.id())
+    }
+}
+
+/// Renders the inputs of the new transaction form as a table row.
+///
+/// When the form is rejected, the row is rendered again with the submitted values and an error
+/// message under each invalid input.
+#[derive(Template)]
+#[template(path = "partials/transactions/new_transaction_row.html")]
+pub struct NewTransactionRowTemplate {
+    /// Today's date in the user's timezone, the latest date that can be entered.
+    pub today: Date,
+    /// The route for searching the current user's categories.
+    pub category_search_route: &'static str,
+    /// The route for creating a category from the category search.
+    pub create_category_route: String,
+    /// The route for suggesting categories from the description.
+    pub category_suggestions_route: &'static str,
+    pub amount: String,
+    pub date: String,
+    pub description: String,
+    /// The ID of the chosen category, or zero for none.
+    pub category_id: DatabaseID,
+    /// The text typed into the category search.
+    pub category_name: String,
+    pub amount_error: Option<String>,
+    pub date_error: Option<String>,
+}
+
+impl NewTransactionRowTemplate {
+    /// An empty form for `user_id` dated `today`.
+    pub fn new(user_id: UserID, today: Date) -> Self {
+        Self {
+            today,
+            category_search_route: endpoints::CATEGORY_SEARCH,
+            create_category_route: endpoints::format_endpoint(
+                endpoints::USER_CATEGORIES,
+                user_id.as_i64(),
+            ),
+            category_suggestions_route: endpoints::CATEGORY_SUGGESTIONS,
+            amount: String::new(),
+            date: today.to_string(),
+            description: String::new(),
+            category_id: 0,
+            category_name: String::new(),
+            amount_error: None,
+            date_error: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod transaction_templates_tests {
+    use askama::Template;
+    use time::macros::date;
+
+    use crate::models::{Transaction, UserID};
+
+    use super::TransactionRow;
+
+    fn get_row() -> TransactionRow {
+        TransactionRow {
+            transaction: Transaction::build(-12.5, UserID::new(1))
+                .date(date!(2024 - 01 - 02))
+                .unwrap()
+                .description("Coffee".to_owned())
+                .category(Some(7))
+                .finalise(42),
+        }
+    }
+
+    #[test]
+    fn row_and_card_contain_transaction_id() {
+        let row = get_row();
+
+        let row_html = row.render().unwrap();
+        let card_html = row.card().render().unwrap();
+
+        for html in [&row_html, &card_html] {
+            assert!(html.contains(r#"data-transaction-id="42""#), "got {html}");
+            assert!(html.contains("/transactions/42/ignored"), "got {html}");
+            assert!(html.contains("Coffee"), "got {html}");
+        }
+        assert!(row_html.contains("md:table-row"));
+    }
+
+    #[test]
+    fn card_asks_for_a_card_when_toggling_ignored() {
+        let card_html = get_row().card().render().unwrap();
+
+        assert!(card_html.contains(r#"hx-vals='{"card": true}'"#));
+        assert!(card_html.contains("Category 7"));
+        assert!(card_html.contains("text-red-600"));
+    }
+}
+
+#[cfg(test)]
+mod render_tests {
+    use askama::Template;
+    use axum::{http::StatusCode, middleware, routing::get, Router};
+    use axum_extra::extract::cookie::Key;
+    use axum_htmx::HX_REQUEST;
+    use axum_test::TestServer;
+    use regex::Regex;
+
+    use crate::routes::errors::render_errors;
+
+    use super::render;
+
+    mod filters {
+        /// A filter that always fails, to test how rendering errors are handled.
+        pub fn fail<T>(_value: T) -> askama::Result<String> {
+            Err(askama::Error::Fmt(std::fmt::Error))
+        }
+    }
+
+    #[derive(Template)]
+    #[template(source = "<p>{{ message }}</p>", ext = "html")]
+    struct WorkingTemplate {
+        message: &'static str,
+    }
+
+    #[derive(Template)]
+    #[template(source = "<p>{{ message|fail }}</p>", ext = "html")]
+    struct FailingTemplate {
+        message: &'static str,
+    }
+
+    fn get_test_server() -> TestServer {
+        let app = Router::new()
+            .route(
+                "/works",
+                get(|| async {
+                    render(StatusCode::CREATED, &WorkingTemplate { message: "hello" })
+                }),
+            )
+            .route(
+                "/fails",
+                get(|| async { render(StatusCode::OK, &FailingTemplate { message: "hello" }) }),
+            )
+            .layer(middleware::from_fn_with_state(
+                (Key::from(&[0; 64]), false),
+                render_errors,
+            ));
+
+        TestServer::new(app).unwrap()
+    }
+
+    fn get_error_id(text: &str) -> String {
+        Regex::new("error ID ([0-9a-f]{8})")
+            .unwrap()
+            .captures(text)
+            .unwrap_or_else(|| panic!("could not find an error ID in {text}"))[1]
+            .to_owned()
+    }
+
+    #[tokio::test]
+    async fn renders_template_with_status() {
+        let server = get_test_server();
+
+        let response = server.get("/works").await;
+
+        response.assert_status(StatusCode::CREATED);
+        response.assert_text("<p>hello</p>");
+        assert!(response
+            .header("content-type")
+            .to_str()
+            .unwrap()
+            .starts_with("text/html"));
+    }
+
+    #[tokio::test]
+    async fn failing_template_gets_error_page_with_error_id() {
+        let server = get_test_server();
+
+        let response = server.get("/fails").await;
+
+        response.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+        let body = response.text();
+        assert!(body.contains("<html"));
+        get_error_id(&body);
+    }
+
+    #[tokio::test]
+    async fn failing_template_gets_alert_for_htmx() {
+        let server = get_test_server();
+
+        let response = server
+            .get("/fails")
+            .add_header(HX_REQUEST, "true".parse().unwrap())
+            .await;
+
+        response.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+        let body = response.text();
+        assert!(!body.contains("<html"));
+        assert!(body.contains(r#"role="alert""#));
+        get_error_id(&body);
+    }
+
+    #[tokio::test]
+    async fn error_ids_are_unique() {
+        let server = get_test_server();
+
+        let first = get_error_id(&server.get("/fails").await.text());
+        let second = get_error_id(&server.get("/fails").await.text());
+
+        assert_ne!(first, second);
+    }
+}
@@ -0,0 +1,244 @@
+//! Showing pages in the logged in user's choice of light or dark colours.
+//!
+//! The theme is rendered into the page by the server so that pages do not flash the wrong colours
+//! while they load. The rest of the user's preferences are made available while rendering too,
+//! e.g. so that the navbar can mark the user's landing page.
+
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension, Form,
+};
+use axum_htmx::HxRefresh;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    models::{LandingPage, Theme, UserID, UserPreferences},
+    routes::get_internal_server_error_redirect,
+    stores::{CategoryStore, TransactionStore, UserStore},
+    AppState,
+};
+
+tokio::task_local! {
+    /// The preferences of the user whose request is being handled.
+    static CURRENT_PREFERENCES: UserPreferences;
+}
+
+/// The theme to render the current page with.
+///
+/// This is the logged in user's theme while handling a request that passed through
+/// [theme_middleware], or [Theme::System] otherwise, e.g. on the log-in page.
+pub fn get_current_theme() -> Theme {
+    CURRENT_PREFERENCES
+        .try_with(|preferences| preferences.theme)
+        .unwrap_or_default()
+}
+
+/// The logged in user's landing page while handling a request that passed through
+/// [theme_middleware], or [LandingPage::Dashboard] otherwise.
+pub fn get_current_landing_page() -> LandingPage {
+    CURRENT_PREFERENCES
+        .try_with(|preferences| preferences.landing_page)
+        .unwrap_or_default()
+}
+
+/// The class to set on the page's `html` element for the current theme.
+///
+/// The system theme is decided in the browser, since only it knows the device's colour scheme.
+pub fn get_theme_class() -> &'static str {
+    match get_current_theme() {
+        Theme::Dark => "dark",
+        Theme::Light | Theme::System => "",
+    }
+}
+
+/// Render the rest of the request with the logged in user's theme and other preferences.
+///
+/// This must run after [auth_guard](crate::auth::middleware::auth_guard), which adds the user's ID
+/// to the request.
+pub async fn theme_middleware<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    request: Request,
+    next: Next,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let preferences = match request.extensions().get::<UserID>() {
+        Some(&user_id) => match state.user_store().get_preferences(user_id) {
+            Ok(preferences) => preferences,
+            Err(error) => {
+                tracing::error!("Could not get the preferences for user {user_id}: {error}");
+                UserPreferences::default()
+            }
+        },
+        None => UserPreferences::default(),
+    };
+
+    CURRENT_PREFERENCES
+        .scope(preferences, next.run(request))
+        .await
+}
+
+/// The form data for changing a user's theme.
+#[derive(Serialize, Deserialize)]
+pub struct ThemeForm {
+    /// The theme to change to.
+    pub theme: Theme,
+}
+
+/// Change the logged in user's theme and tell HTMX to reload the page with it.
+pub async fn set_theme<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Form(form): Form<ThemeForm>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    match state.user_store().set_theme(user_id, form.theme) {
+        Ok(_) => (HxRefresh(true), StatusCode::OK).into_response(),
+        Err(error) => {
+            tracing::error!("Could not set the theme for user {user_id}: {error}");
+            get_internal_server_error_redirect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod theme_tests {
+    use axum::{
+        middleware,
+        routing::{get, post},
+        Router,
+    };
+    use axum_htmx::HX_REFRESH;
+    use axum_test::TestServer;
+    use email_address::EmailAddress;
+    use rusqlite::Connection;
+
+    use crate::{
+        auth::{log_in::LogInData, middleware::auth_guard},
+        models::{PasswordHash, Theme, User, ValidatedPassword},
+        routes::{
+            endpoints, log_in::get_log_in_page, log_in::post_log_in, settings::get_settings_page,
+        },
+        stores::{
+            sql_store::{create_app_state, SQLAppState},
+            UserStore,
+        },
+    };
+
+    use super::{set_theme, theme_middleware, ThemeForm};
+
+    const PASSWORD: &str = "averysafeandsecurepassword";
+
+    fn get_test_state_server_and_user() -> (SQLAppState, TestServer, User) {
+        let connection = Connection::open_in_memory().unwrap();
+        let mut state = create_app_state(connection, "42").unwrap();
+        let user = state
+            .user_store()
+            .create(
+                EmailAddress::new_unchecked("foo@bar.baz"),
+                PasswordHash::new(ValidatedPassword::new_unchecked(PASSWORD), 4).unwrap(),
+            )
+            .unwrap();
+
+        let app = Router::new()
+            .route(endpoints::SETTINGS, get(get_settings_page))
+            .route(endpoints::THEME, post(set_theme))
+            .layer(middleware::from_fn_with_state(
+                state.clone(),
+                theme_middleware,
+            ))
+            .layer(middleware::from_fn_with_state(state.clone(), auth_guard))
+            .route(endpoints::LOG_IN, get(get_log_in_page).post(post_log_in))
+            .with_state(state.clone());
+
+        (
+            state,
+            TestServer::new(app).expect("Could not create test server."),
+            user,
+        )
+    }
+
+    async fn log_in(server: &TestServer, user: &User) -> axum_test::TestResponse {
+        server
+            .post(endpoints::LOG_IN)
+            .form(&LogInData {
+                email: user.email().to_string(),
+                password: PASSWORD.to_string(),
+                remember_me: None,
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn pages_use_system_theme_by_default() {
+        let (_, server, user) = get_test_state_server_and_user();
+        let cookies = log_in(&server, &user).await.cookies();
+
+        let response = server.get(endpoints::SETTINGS).add_cookies(cookies).await;
+
+        response.assert_status_ok();
+        response.assert_text_contains(r#"<html lang="en" class="" data-theme="system">"#);
+        response.assert_text_contains(r#"<option value="system" selected>"#);
+    }
+
+    #[tokio::test]
+    async fn log_in_page_uses_system_theme() {
+        let (_, server, _) = get_test_state_server_and_user();
+
+        let response = server.get(endpoints::LOG_IN).await;
+
+        response.assert_status_ok();
+        response.assert_text_contains(r#"<html lang="en" class="" data-theme="system">"#);
+    }
+
+    #[tokio::test]
+    async fn set_theme_persists_and_refreshes_page() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let cookies = log_in(&server, &user).await.cookies();
+
+        let response = server
+            .post(endpoints::THEME)
+            .add_cookies(cookies.clone())
+            .form(&ThemeForm { theme: Theme::Dark })
+            .await;
+
+        response.assert_status_ok();
+        assert_eq!(response.header(HX_REFRESH), "true");
+        assert_eq!(
+            state.user_store().get_preferences(user.id()).unwrap().theme,
+            Theme::Dark
+        );
+
+        let response = server.get(endpoints::SETTINGS).add_cookies(cookies).await;
+        response.assert_text_contains(r#"<html lang="en" class="dark" data-theme="dark">"#);
+        response.assert_text_contains(r#"<option value="dark" selected>"#);
+    }
+
+    #[tokio::test]
+    async fn set_theme_rejects_unknown_theme() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let cookies = log_in(&server, &user).await.cookies();
+
+        let response = server
+            .post(endpoints::THEME)
+            .add_cookies(cookies)
+            .form(&[("theme", "sepia")])
+            .await;
+
+        response.assert_status_unprocessable_entity();
+        assert_eq!(
+            state.user_store().get_preferences(user.id()).unwrap().theme,
+            Theme::System
+        );
+    }
+}
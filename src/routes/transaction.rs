@@ -1,47 +1,144 @@
 //! This files defines the routes for the transaction type.
 
+use askama_axum::Template;
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::IntoResponse,
-    Form, Json,
+    extract::{Path, Query, State},
+    http::{StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Extension, Form,
 };
-use axum_extra::extract::PrivateCookieJar;
-use serde::Deserialize;
-use time::Date;
+use axum_htmx::{HxRedirect, HxResponseTrigger, HxReswap, HxRetarget, SwapOption};
+use serde::{Deserialize, Serialize};
+use time::{format_description::FormatItem, macros::format_description, Date, Duration};
 
 use crate::{
-    auth::cookie::get_user_id_from_auth_cookie,
-    models::{DatabaseID, Transaction, UserID},
+    filters,
+    models::{CategoryError, DatabaseID, Transaction, TransactionError, UserID, WebhookEvent},
     stores::{CategoryStore, TransactionStore, UserStore},
+    timezone::get_user_today,
     AppError, AppState,
 };
 
-use super::templates::TransactionRow;
+use super::{
+    endpoints::{self, format_endpoint},
+    navigation::{get_nav_bar, NavbarTemplate},
+    templates::{render, NewTransactionRowTemplate, TransactionCard, TransactionRow},
+    transactions::{TransactionsPageQuery, TRANSACTIONS_CHANGED},
+};
+
+/// The default age past which a new transaction's date is likely a typo, e.g. the wrong year.
+pub const DEFAULT_SUSPICIOUS_TRANSACTION_AGE: Duration = Duration::days(365);
 
 /// The form data for creating a transaction.
-#[derive(Debug, Deserialize)]
+///
+/// The amount and date are kept as they were typed so that they can be shown again with an error
+/// message if they are invalid.
+#[derive(Debug, Serialize, Deserialize)]
 pub struct TransactionForm {
     /// The value of the transaction in dollars.
-    pub amount: f64,
-    /// The date when the transaction ocurred.
-    pub date: Date,
+    pub amount: String,
+    /// The date when the transaction ocurred, formatted as YYYY-MM-DD.
+    pub date: String,
     /// Text detailing the transaction.
     pub description: String,
     /// The ID of the category to assign the transaction to.
     ///
     /// Zero should be interpreted as `None`.
     pub category_id: DatabaseID,
+    /// The text typed into the category search, which is only used to fill in the form again.
+    #[serde(default, rename = "q")]
+    pub category_name: String,
+    /// Whether the user has confirmed that they want to save a transaction dated long ago.
+    #[serde(default)]
+    pub confirmed: bool,
+}
+
+/// The format of the dates submitted by the new transaction form.
+const FORM_DATE_FORMAT: &[FormatItem<'static>] = format_description!("[year]-[month]-[day]");
+
+/// The error messages for the invalid fields of a [TransactionForm].
+#[derive(Debug, Default, PartialEq)]
+struct TransactionFormErrors {
+    amount: Option<String>,
+    date: Option<String>,
+}
+
+/// Parse the amount and date in `form`, checking that the date is not after `today`.
+///
+/// # Errors
+/// Returns an error message for each field that is invalid.
+fn parse_transaction_form(
+    form: &TransactionForm,
+    today: Date,
+) -> Result<(f64, Date), TransactionFormErrors> {
+    let amount = match form.amount.trim().parse::<f64>() {
+        Ok(amount) if amount.is_finite() => Ok(amount),
+        _ if form.amount.trim().is_empty() => Err("Enter an amount.".to_owned()),
+        _ => Err("The amount must be a number, e.g. 12.34.".to_owned()),
+    };
+
+    let date = match Date::parse(form.date.trim(), FORM_DATE_FORMAT) {
+        Ok(date) if date > today => Err("The date cannot be in the future.".to_owned()),
+        Ok(date) => Ok(date),
+        Err(_) => Err("Enter a date in the format YYYY-MM-DD.".to_owned()),
+    };
+
+    match (amount, date) {
+        (Ok(amount), Ok(date)) => Ok((amount, date)),
+        (amount, date) => Err(TransactionFormErrors {
+            amount: amount.err(),
+            date: date.err(),
+        }),
+    }
+}
+
+/// Asks the user to confirm a new transaction dated long ago, in case the date is a typo.
+#[derive(Template)]
+#[template(path = "partials/transactions/confirm_old_date.html")]
+struct ConfirmOldTransactionTemplate {
+    create_transaction_route: String,
+    /// How long ago the transaction is dated, e.g. "9 years".
+    age: String,
+    form: TransactionForm,
+}
+
+/// Describe how old a transaction dated `age` ago is, e.g. "9 years".
+fn format_transaction_age(age: Duration) -> String {
+    let (count, unit) = if age >= Duration::days(365) {
+        (age.whole_days() / 365, "year")
+    } else {
+        (age.whole_days(), "day")
+    };
+
+    if count == 1 {
+        format!("1 {unit}")
+    } else {
+        format!("{count} {unit}s")
+    }
 }
 
 /// A route handler for creating a new transaction, returns [TransactionRow] as a [Response] on success.
 ///
+/// The response also tells the transactions table to reload so that the new transaction is shown
+/// in its place among the filtered transactions.
+///
+/// If the amount or date is invalid, e.g. the date is in the future, the transaction is not
+/// created. Instead, the response replaces the new transaction form's inputs with the submitted
+/// values and an error message under each invalid input.
+///
+/// If the transaction is dated further in the past than [AppState::suspicious_transaction_age]
+/// and `confirmed` is not set, the transaction is not created. Instead, the response asks the
+/// user to confirm the date with a form that submits the transaction again with `confirmed` set.
+///
+/// This function will return the status code 404 if `user_id` is not the logged in user or the
+/// category belongs to another user.
+///
 /// # Panics
 ///
 /// Panics if the lock for the database connection is already held by the same thread.
 pub async fn create_transaction<C, T, U>(
     State(mut state): State<AppState<C, T, U>>,
-    _jar: PrivateCookieJar,
+    Extension(logged_in_user_id): Extension<UserID>,
     Path(user_id): Path<UserID>,
     Form(data): Form<TransactionForm>,
 ) -> impl IntoResponse
@@ -50,6 +147,11 @@ where
     T: TransactionStore + Send + Sync,
     U: UserStore + Send + Sync,
 {
+    // Respond with 404 not found so that unauthorized users cannot know whether another user's resource exists.
+    if user_id != logged_in_user_id {
+        return Err(AppError::NotFound);
+    }
+
     // HACK: Zero is used as a sentinel value for None. Currently, options do not work with empty
     // form values. For example, the URL encoded form "num=" will return an error.
     let category = match data.category_id {
@@ -57,48 +159,255 @@ where
         id => Some(id),
     };
 
-    let transaction = Transaction::build(data.amount, user_id)
-        .description(data.description)
+    if let Some(category_id) = category {
+        match state.category_store().get(category_id) {
+            Ok(category) if category.user_id() == user_id => {}
+            Ok(_) | Err(CategoryError::NotFound) => return Err(AppError::NotFound),
+            Err(error) => return Err(AppError::CategoryError(error)),
+        }
+    }
+
+    let today = get_user_today(state.user_store(), user_id);
+    let (amount, date) = match parse_transaction_form(&data, today) {
+        Ok(parsed) => parsed,
+        Err(errors) => {
+            return Ok((
+                HxRetarget("#new-transaction-row".to_owned()),
+                HxReswap(SwapOption::OuterHtml),
+                render(
+                    StatusCode::OK,
+                    &NewTransactionRowTemplate {
+                        amount: data.amount,
+                        date: data.date,
+                        description: data.description,
+                        category_id: data.category_id,
+                        category_name: data.category_name,
+                        amount_error: errors.amount,
+                        date_error: errors.date,
+                        ..NewTransactionRowTemplate::new(user_id, today)
+                    },
+                ),
+            )
+                .into_response())
+        }
+    };
+
+    let transaction = Transaction::build(amount, user_id)
+        .today(today)
+        .description(data.description.clone())
         .category(category)
-        .date(data.date)?;
+        .date(date)?;
+
+    let age = today - date;
+    if !data.confirmed
+        && state
+            .suspicious_transaction_age
+            .is_some_and(|suspicious_age| age > suspicious_age)
+    {
+        return Ok((
+            HxRetarget("#transaction-confirmation".to_owned()),
+            HxReswap(SwapOption::InnerHtml),
+            render(
+                StatusCode::OK,
+                &ConfirmOldTransactionTemplate {
+                    create_transaction_route: format_endpoint(
+                        endpoints::USER_TRANSACTIONS,
+                        user_id.as_i64(),
+                    ),
+                    age: format_transaction_age(age),
+                    form: data,
+                },
+            ),
+        )
+            .into_response());
+    }
 
-    state
+    let transaction = state
         .transaction_store()
         .create_from_builder(transaction)
-        .map(|transaction| (StatusCode::OK, TransactionRow { transaction }))
-        .map_err(AppError::TransactionError)
+        .map_err(AppError::TransactionError)?;
+
+    state.dispatch_webhooks(user_id, WebhookEvent::TransactionCreated, &transaction);
+
+    Ok((
+        HxResponseTrigger::normal([TRANSACTIONS_CHANGED]),
+        render(StatusCode::OK, &TransactionRow { transaction }),
+    )
+        .into_response())
+}
+
+/// The category of the transaction shown on its page.
+struct CategoryLink {
+    name: String,
+    /// The link to the transactions page filtered by the category.
+    href: String,
+}
+
+/// Renders the page for one transaction with everything about it.
+#[derive(Template)]
+#[template(path = "views/transaction.html")]
+struct TransactionTemplate<'a> {
+    navbar: NavbarTemplate<'a>,
+    transaction: Transaction,
+    /// The transaction's category, or `None` if it has no category.
+    category: Option<CategoryLink>,
+    transactions_route: &'a str,
+    /// The route for deleting the transaction and then going to the transactions page.
+    delete_route: String,
 }
 
-/// A route handler for getting a transaction by its database ID.
+/// A route handler for the page showing one of the logged in user's transactions.
 ///
-/// This function will return the status code 404 if the requested resource does not exist (e.g., not created yet).
+/// The transaction as JSON is at [endpoints::API_TRANSACTION].
+///
+/// This function will return the status code 404 if the requested resource does not exist or
+/// belongs to another user.
+pub async fn get_transaction<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Path(transaction_id): Path<DatabaseID>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let transaction = match state.transaction_store().get(transaction_id) {
+        Ok(transaction) if transaction.user_id() == user_id => transaction,
+        // Respond with 404 not found so that unauthorized users cannot know whether another user's resource exists.
+        Ok(_) | Err(TransactionError::NotFound) => return AppError::NotFound.into_response(),
+        Err(error) => return AppError::TransactionError(error).into_response(),
+    };
+
+    let category = match transaction
+        .category_id()
+        .map(|category_id| state.category_store().get(category_id))
+        .transpose()
+    {
+        Ok(category) => category,
+        Err(error) => return AppError::CategoryError(error).into_response(),
+    };
+
+    render(
+        StatusCode::OK,
+        &TransactionTemplate {
+            navbar: get_nav_bar(endpoints::TRANSACTIONS, state.demo_mode),
+            category: category.map(|category| CategoryLink {
+                name: category.name().as_ref().to_string(),
+                href: TransactionsPageQuery {
+                    category_id: Some(category.id()),
+                    ..Default::default()
+                }
+                .href(),
+            }),
+            transactions_route: endpoints::TRANSACTIONS,
+            delete_route: format!(
+                "{}?redirect=true",
+                format_endpoint(endpoints::TRANSACTION, transaction.id())
+            ),
+            transaction,
+        },
+    )
+}
+
+/// The form data for setting whether a transaction is ignored.
+#[derive(Debug, Deserialize)]
+pub struct IgnoreTransactionForm {
+    /// Whether the transaction should be left out of totals.
+    pub ignored: bool,
+    /// Whether to respond with a [TransactionCard] for small screens instead of a table row.
+    #[serde(default)]
+    pub card: bool,
+}
+
+/// A route handler for setting whether a transaction is ignored, returns [TransactionRow], or
+/// [TransactionCard] if `card` is set, as a [Response] on success.
+///
+/// This function will return the status code 404 if the requested resource does not exist or
+/// belongs to another user.
 ///
 /// # Panics
 ///
 /// Panics if the lock for the database connection is already held by the same thread.
-pub async fn get_transaction<C, T, U>(
+pub async fn set_transaction_ignored<C, T, U>(
     State(mut state): State<AppState<C, T, U>>,
-    jar: PrivateCookieJar,
+    Extension(user_id): Extension<UserID>,
     Path(transaction_id): Path<DatabaseID>,
-) -> impl IntoResponse
+    Form(data): Form<IgnoreTransactionForm>,
+) -> Response
 where
     C: CategoryStore + Send + Sync,
     T: TransactionStore + Send + Sync,
     U: UserStore + Send + Sync,
 {
-    state
+    match state.transaction_store().get(transaction_id) {
+        Ok(transaction) if transaction.user_id() == user_id => {}
+        // Respond with 404 not found so that unauthorized users cannot know whether another user's resource exists.
+        Ok(_) | Err(TransactionError::NotFound) => return AppError::NotFound.into_response(),
+        Err(error) => return AppError::TransactionError(error).into_response(),
+    }
+
+    match state
         .transaction_store()
-        .get(transaction_id)
-        .map_err(AppError::TransactionError)
-        .and_then(|transaction| {
-            if get_user_id_from_auth_cookie(&jar)? == transaction.user_id() {
-                Ok(transaction)
-            } else {
-                // Respond with 404 not found so that unauthorized users cannot know whether another user's resource exists.
-                Err(AppError::NotFound)
-            }
-        })
-        .map(|transaction| (StatusCode::OK, Json(transaction)))
+        .set_ignored(transaction_id, data.ignored)
+    {
+        Ok(transaction) if data.card => (
+            StatusCode::OK,
+            TransactionCard {
+                transaction: &transaction,
+            },
+        )
+            .into_response(),
+        Ok(transaction) => (StatusCode::OK, TransactionRow { transaction }).into_response(),
+        Err(error) => AppError::TransactionError(error).into_response(),
+    }
+}
+
+/// The query parameters for deleting a transaction.
+#[derive(Debug, Default, Deserialize)]
+pub struct DeleteTransactionQuery {
+    /// Whether to send the browser to the transactions page afterwards, e.g. when deleting from
+    /// the transaction's own page.
+    #[serde(default)]
+    pub redirect: bool,
+}
+
+/// A route handler for deleting a transaction, which tells the transactions table to reload, or
+/// redirects to the transactions page if `redirect` is set.
+///
+/// This function will return the status code 404 if the requested resource does not exist or
+/// belongs to another user.
+pub async fn delete_transaction<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Path(transaction_id): Path<DatabaseID>,
+    Query(query): Query<DeleteTransactionQuery>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    match state.transaction_store().get(transaction_id) {
+        Ok(transaction) if transaction.user_id() == user_id => {}
+        // Respond with 404 not found so that unauthorized users cannot know whether another user's resource exists.
+        Ok(_) | Err(TransactionError::NotFound) => return AppError::NotFound.into_response(),
+        Err(error) => return AppError::TransactionError(error).into_response(),
+    }
+
+    match state.transaction_store().delete(transaction_id) {
+        Ok(()) if query.redirect => (
+            HxRedirect(Uri::from_static(endpoints::TRANSACTIONS)),
+            StatusCode::OK,
+        )
+            .into_response(),
+        Ok(()) => (
+            HxResponseTrigger::normal([TRANSACTIONS_CHANGED]),
+            StatusCode::OK,
+        )
+            .into_response(),
+        Err(error) => AppError::TransactionError(error).into_response(),
+    }
 }
 
 #[cfg(test)]
@@ -109,21 +418,25 @@ mod transaction_tests {
     use axum::body::Body;
     use axum::extract::{Path, State};
     use axum::http::{Response, StatusCode};
-    use axum::Form;
-    use axum_extra::extract::PrivateCookieJar;
-    use time::OffsetDateTime;
+    use axum::{Extension, Form};
+    use time::{Duration, OffsetDateTime};
 
-    use crate::auth::cookie::set_auth_cookie;
     use crate::models::{
-        CategoryError, DatabaseID, PasswordHash, TransactionBuilder, TransactionError,
+        CategoryError, CategoryName, DatabaseID, PasswordHash, TransactionBuilder, TransactionError,
+    };
+    use crate::routes::endpoints::{self, format_endpoint};
+    use crate::routes::transaction::{
+        create_transaction, format_transaction_age, get_transaction, parse_transaction_form,
+        set_transaction_ignored, IgnoreTransactionForm, TransactionForm, TransactionFormErrors,
     };
-    use crate::routes::transaction::{create_transaction, get_transaction, TransactionForm};
-    use crate::stores::transaction::TransactionQuery;
+    use crate::routes::transactions::TRANSACTIONS_CHANGED;
+    use crate::stores::transaction::{BatchOutcome, NewTransaction, TransactionQuery};
     use crate::stores::{CategoryStore, TransactionStore, UserStore};
     use crate::{
         models::{Category, Transaction, UserID},
         AppState,
     };
+    use axum_htmx::{HX_RETARGET, HX_TRIGGER};
 
     #[derive(Clone)]
     struct DummyUserStore {}
@@ -137,6 +450,15 @@ mod transaction_tests {
             todo!()
         }
 
+        fn create_with_invite(
+            &mut self,
+            _email: email_address::EmailAddress,
+            _password_hash: PasswordHash,
+            _invite_code: &str,
+        ) -> Result<crate::models::User, crate::stores::UserError> {
+            todo!()
+        }
+
         fn get(&self, _id: UserID) -> Result<crate::models::User, crate::stores::UserError> {
             todo!()
         }
@@ -147,12 +469,89 @@ mod transaction_tests {
         ) -> Result<crate::models::User, crate::stores::UserError> {
             todo!()
         }
+
+        fn set_password(
+            &mut self,
+            _id: UserID,
+            _password_hash: PasswordHash,
+        ) -> Result<crate::models::User, crate::stores::UserError> {
+            todo!()
+        }
+
+        fn increment_session_generation(
+            &mut self,
+            _id: UserID,
+        ) -> Result<crate::models::User, crate::stores::UserError> {
+            todo!()
+        }
+
+        fn update_password_hash(
+            &mut self,
+            _id: UserID,
+            _password_hash: PasswordHash,
+        ) -> Result<(), crate::stores::UserError> {
+            todo!()
+        }
+
+        fn set_email(
+            &mut self,
+            _id: UserID,
+            _email: email_address::EmailAddress,
+        ) -> Result<crate::models::User, crate::stores::UserError> {
+            todo!()
+        }
+
+        fn delete(&mut self, _id: UserID) -> Result<(), crate::stores::UserError> {
+            todo!()
+        }
+
+        fn get_preferences(
+            &self,
+            _user_id: UserID,
+        ) -> Result<crate::models::UserPreferences, crate::stores::UserError> {
+            Ok(crate::models::UserPreferences::default())
+        }
+
+        fn set_timezone(
+            &mut self,
+            _user_id: UserID,
+            _timezone: &str,
+        ) -> Result<crate::models::UserPreferences, crate::stores::UserError> {
+            todo!()
+        }
+
+        fn set_theme(
+            &mut self,
+            _user_id: UserID,
+            _theme: crate::models::Theme,
+        ) -> Result<crate::models::UserPreferences, crate::stores::UserError> {
+            todo!()
+        }
+
+        fn set_landing_page(
+            &mut self,
+            _user_id: UserID,
+            _landing_page: crate::models::LandingPage,
+        ) -> Result<crate::models::UserPreferences, crate::stores::UserError> {
+            todo!()
+        }
+
+        fn set_date_periods(
+            &mut self,
+            _user_id: UserID,
+            _week_start: crate::models::WeekStart,
+            _month_start_day: u8,
+        ) -> Result<crate::models::UserPreferences, crate::stores::UserError> {
+            todo!()
+        }
     }
 
-    #[derive(Clone)]
-    struct DummyCategoryStore {}
+    #[derive(Clone, Default)]
+    struct StubCategoryStore {
+        categories: Vec<Category>,
+    }
 
-    impl CategoryStore for DummyCategoryStore {
+    impl CategoryStore for StubCategoryStore {
         fn create(
             &self,
             _name: crate::models::CategoryName,
@@ -161,13 +560,38 @@ mod transaction_tests {
             todo!()
         }
 
-        fn get(&self, _category_id: DatabaseID) -> Result<Category, CategoryError> {
-            todo!()
+        fn get(&self, category_id: DatabaseID) -> Result<Category, CategoryError> {
+            self.categories
+                .iter()
+                .find(|category| category.id() == category_id)
+                .cloned()
+                .ok_or(CategoryError::NotFound)
         }
 
         fn get_by_user(&self, _user_id: UserID) -> Result<Vec<Category>, CategoryError> {
             todo!()
         }
+
+        fn search(
+            &self,
+            _user_id: UserID,
+            _prefix: &str,
+            _limit: u64,
+        ) -> Result<Vec<Category>, CategoryError> {
+            todo!()
+        }
+
+        fn suggest(
+            &self,
+
+            _user_id: UserID,
+
+            _description: &str,
+
+            _limit: u64,
+        ) -> Result<Vec<Category>, CategoryError> {
+            todo!()
+        }
     }
 
     #[derive(Clone)]
@@ -211,6 +635,13 @@ mod transaction_tests {
             Ok(transaction)
         }
 
+        fn create_batch(
+            &mut self,
+            _transactions: Vec<NewTransaction>,
+        ) -> Result<Vec<BatchOutcome>, TransactionError> {
+            todo!()
+        }
+
         fn get(&self, id: DatabaseID) -> Result<Transaction, TransactionError> {
             self.transactions
                 .iter()
@@ -229,20 +660,104 @@ mod transaction_tests {
         ) -> Result<Vec<Transaction>, TransactionError> {
             todo!()
         }
+
+        fn count_query(&self, _filter: TransactionQuery) -> Result<u64, TransactionError> {
+            todo!()
+        }
+
+        fn sum_query(&self, _filter: TransactionQuery) -> Result<f64, TransactionError> {
+            todo!()
+        }
+
+        fn set_ignored(
+            &mut self,
+            id: DatabaseID,
+            ignored: bool,
+        ) -> Result<Transaction, TransactionError> {
+            let transaction = self
+                .transactions
+                .iter_mut()
+                .find(|transaction| transaction.id() == id)
+                .ok_or(TransactionError::NotFound)?;
+
+            *transaction = Transaction::new_unchecked(
+                transaction.id(),
+                transaction.amount(),
+                *transaction.date(),
+                transaction.description().to_string(),
+                transaction.category_id(),
+                transaction.user_id(),
+                ignored,
+                transaction.is_reconciled(),
+            );
+
+            Ok(transaction.to_owned())
+        }
+
+        fn set_reconciled(&mut self, _ids: &[DatabaseID]) -> Result<(), TransactionError> {
+            todo!()
+        }
+
+        fn update(
+            &mut self,
+            _id: DatabaseID,
+            _builder: TransactionBuilder,
+        ) -> Result<Transaction, TransactionError> {
+            todo!()
+        }
+
+        fn delete(&mut self, _id: DatabaseID) -> Result<(), TransactionError> {
+            todo!()
+        }
+
+        fn get_duplicates(
+            &self,
+            _user_id: UserID,
+        ) -> Result<Vec<Vec<Transaction>>, TransactionError> {
+            todo!()
+        }
+
+        fn merge_duplicates(
+            &mut self,
+            _keep_id: DatabaseID,
+            _duplicate_ids: &[DatabaseID],
+        ) -> Result<Transaction, TransactionError> {
+            todo!()
+        }
     }
 
-    #[tokio::test]
-    async fn can_create_transaction() {
-        let state = AppState::new(
+    fn get_create_transaction_state(
+        user_id: UserID,
+    ) -> AppState<StubCategoryStore, FakeTransactionStore, DummyUserStore> {
+        AppState::new(
             "42",
-            DummyCategoryStore {},
+            StubCategoryStore {
+                categories: vec![Category::new(
+                    1,
+                    CategoryName::new_unchecked("Foo"),
+                    user_id,
+                )],
+            },
             FakeTransactionStore::new(),
             DummyUserStore {},
-        );
+        )
+    }
 
-        let jar = PrivateCookieJar::new(state.cookie_key().to_owned());
+    fn get_transaction_form(category_id: DatabaseID) -> TransactionForm {
+        TransactionForm {
+            description: "aaaaaaaaaaaaa".to_string(),
+            amount: "12.3".to_owned(),
+            date: OffsetDateTime::now_utc().date().to_string(),
+            category_id,
+            category_name: String::new(),
+            confirmed: false,
+        }
+    }
 
+    #[tokio::test]
+    async fn can_create_transaction() {
         let user_id = UserID::new(123);
+        let state = get_create_transaction_state(user_id);
 
         let want = Transaction::build(12.3, user_id)
             .date(OffsetDateTime::now_utc().date())
@@ -251,28 +766,329 @@ mod transaction_tests {
             .category(Some(1))
             .finalise(0);
 
-        let form = TransactionForm {
-            description: want.description().to_string(),
-            amount: want.amount(),
-            date: want.date().to_owned(),
-            category_id: want.category_id().unwrap(),
+        let response = create_transaction(
+            State(state.clone()),
+            Extension(user_id),
+            Path(user_id),
+            Form(get_transaction_form(1)),
+        )
+        .await
+        .into_response();
+
+        assert_eq!(
+            response.headers().get(HX_TRIGGER).unwrap(),
+            TRANSACTIONS_CHANGED
+        );
+        assert_create_calls(state, want.clone());
+        assert_response_contains_transaction(response, want).await;
+    }
+
+    #[tokio::test]
+    async fn cannot_create_transaction_for_another_user() {
+        let user_id = UserID::new(123);
+        let unauthorized_user_id = UserID::new(1337);
+        let mut state = get_create_transaction_state(user_id);
+
+        let response = create_transaction(
+            State(state.clone()),
+            Extension(unauthorized_user_id),
+            Path(user_id),
+            Form(get_transaction_form(0)),
+        )
+        .await
+        .into_response();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(state
+            .transaction_store()
+            .create_calls
+            .lock()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn cannot_create_transaction_with_another_users_category() {
+        let user_id = UserID::new(123);
+        let unauthorized_user_id = UserID::new(1337);
+        let mut state = get_create_transaction_state(user_id);
+
+        let response = create_transaction(
+            State(state.clone()),
+            Extension(unauthorized_user_id),
+            Path(unauthorized_user_id),
+            Form(get_transaction_form(1)),
+        )
+        .await
+        .into_response();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(state
+            .transaction_store()
+            .create_calls
+            .lock()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn create_transaction_asks_to_confirm_old_date() {
+        let user_id = UserID::new(123);
+        let mut state = get_create_transaction_state(user_id);
+        let date = OffsetDateTime::now_utc().date() - Duration::days(9 * 365 + 3);
+
+        let response = create_transaction(
+            State(state.clone()),
+            Extension(user_id),
+            Path(user_id),
+            Form(TransactionForm {
+                date: date.to_string(),
+                ..get_transaction_form(1)
+            }),
+        )
+        .await
+        .into_response();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(HX_RETARGET).unwrap(),
+            "#transaction-confirmation"
+        );
+        assert!(response.headers().get(HX_TRIGGER).is_none());
+        let text = extract_text(response).await;
+        assert!(text.contains("dated 9 years ago"), "got {text}");
+        assert!(text.contains(&format!("name=\"date\" value=\"{date}\"")));
+        assert!(text.contains("name=\"confirmed\" value=\"true\""));
+        assert!(state
+            .transaction_store()
+            .create_calls
+            .lock()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn can_create_confirmed_transaction_with_old_date() {
+        let user_id = UserID::new(123);
+        let state = get_create_transaction_state(user_id);
+        let date = OffsetDateTime::now_utc().date() - Duration::days(9 * 365);
+
+        let want = Transaction::build(12.3, user_id)
+            .date(date)
+            .unwrap()
+            .description("aaaaaaaaaaaaa".to_string())
+            .category(Some(1))
+            .finalise(0);
+
+        let response = create_transaction(
+            State(state.clone()),
+            Extension(user_id),
+            Path(user_id),
+            Form(TransactionForm {
+                date: date.to_string(),
+                confirmed: true,
+                ..get_transaction_form(1)
+            }),
+        )
+        .await
+        .into_response();
+
+        assert_eq!(
+            response.headers().get(HX_TRIGGER).unwrap(),
+            TRANSACTIONS_CHANGED
+        );
+        assert_create_calls(state, want.clone());
+        assert_response_contains_transaction(response, want).await;
+    }
+
+    #[tokio::test]
+    async fn old_date_is_not_confirmed_when_turned_off() {
+        let user_id = UserID::new(123);
+        let mut state = get_create_transaction_state(user_id);
+        state.suspicious_transaction_age = None;
+        let date = OffsetDateTime::now_utc().date() - Duration::days(9 * 365);
+
+        let response = create_transaction(
+            State(state.clone()),
+            Extension(user_id),
+            Path(user_id),
+            Form(TransactionForm {
+                date: date.to_string(),
+                ..get_transaction_form(1)
+            }),
+        )
+        .await
+        .into_response();
+
+        assert_eq!(
+            response.headers().get(HX_TRIGGER).unwrap(),
+            TRANSACTIONS_CHANGED
+        );
+    }
+
+    #[tokio::test]
+    async fn future_date_keeps_typed_values() {
+        let user_id = UserID::new(123);
+        let mut state = get_create_transaction_state(user_id);
+        let date = OffsetDateTime::now_utc().date() + Duration::days(2);
+
+        let response = create_transaction(
+            State(state.clone()),
+            Extension(user_id),
+            Path(user_id),
+            Form(TransactionForm {
+                date: date.to_string(),
+                description: "Birthday present".to_owned(),
+                category_name: "Foo".to_owned(),
+                ..get_transaction_form(1)
+            }),
+        )
+        .await
+        .into_response();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(HX_RETARGET).unwrap(),
+            "#new-transaction-row"
+        );
+        assert!(response.headers().get(HX_TRIGGER).is_none());
+        let text = extract_text(response).await;
+        assert!(text.contains(r#"value="Birthday present""#), "got {text}");
+        assert!(text.contains(r#"value="12.3""#), "got {text}");
+        assert!(text.contains(&format!(r#"value="{date}""#)), "got {text}");
+        assert!(text.contains(r#"value="Foo""#), "got {text}");
+        assert!(text.contains("The date cannot be in the future."));
+        assert!(state
+            .transaction_store()
+            .create_calls
+            .lock()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn non_numeric_amount_keeps_typed_values() {
+        let user_id = UserID::new(123);
+        let mut state = get_create_transaction_state(user_id);
+
+        let response = create_transaction(
+            State(state.clone()),
+            Extension(user_id),
+            Path(user_id),
+            Form(TransactionForm {
+                amount: "twelve".to_owned(),
+                description: "Birthday present".to_owned(),
+                ..get_transaction_form(1)
+            }),
+        )
+        .await
+        .into_response();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(HX_RETARGET).unwrap(),
+            "#new-transaction-row"
+        );
+        let text = extract_text(response).await;
+        assert!(text.contains(r#"value="Birthday present""#), "got {text}");
+        assert!(text.contains(r#"value="twelve""#), "got {text}");
+        assert!(text.contains("The amount must be a number"), "got {text}");
+        assert!(!text.contains("date-error"), "got {text}");
+        assert!(state
+            .transaction_store()
+            .create_calls
+            .lock()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn parse_transaction_form_reports_each_invalid_field() {
+        let today = OffsetDateTime::now_utc().date();
+        let form = |amount: &str, date: &str| TransactionForm {
+            amount: amount.to_owned(),
+            date: date.to_owned(),
+            ..get_transaction_form(0)
         };
 
-        let response = create_transaction(State(state.clone()), jar, Path(user_id), Form(form))
+        assert_eq!(
+            parse_transaction_form(&form(" -12.5 ", &today.to_string()), today),
+            Ok((-12.5, today))
+        );
+        assert_eq!(
+            parse_transaction_form(&form("", "not a date"), today),
+            Err(TransactionFormErrors {
+                amount: Some("Enter an amount.".to_owned()),
+                date: Some("Enter a date in the format YYYY-MM-DD.".to_owned()),
+            })
+        );
+        assert_eq!(
+            parse_transaction_form(&form("NaN", &today.to_string()), today),
+            Err(TransactionFormErrors {
+                amount: Some("The amount must be a number, e.g. 12.34.".to_owned()),
+                date: None,
+            })
+        );
+    }
+
+    #[test]
+    fn formats_transaction_age() {
+        assert_eq!(format_transaction_age(Duration::days(1)), "1 day");
+        assert_eq!(format_transaction_age(Duration::days(364)), "364 days");
+        assert_eq!(format_transaction_age(Duration::days(365)), "1 year");
+        assert_eq!(
+            format_transaction_age(Duration::days(9 * 365 + 3)),
+            "9 years"
+        );
+    }
+
+    #[tokio::test]
+    async fn transaction_page_shows_transaction_without_optional_data() {
+        let user_id = UserID::new(42);
+
+        let mut state = AppState::new(
+            "42",
+            StubCategoryStore::default(),
+            FakeTransactionStore::new(),
+            DummyUserStore {},
+        );
+
+        let transaction = state
+            .transaction_store()
+            .create_from_builder(TransactionBuilder::new(13.34, user_id))
+            .unwrap();
+
+        let response = get_transaction(State(state), Extension(user_id), Path(transaction.id()))
             .await
             .into_response();
 
-        assert_create_calls(state, want.clone());
-        assert_response_contains_transaction(response, want).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let text = extract_text(response).await;
+        assert!(text.contains(&format!("Transaction #{}", transaction.id())));
+        assert!(text.contains(&transaction.date().to_string()));
+        assert!(text.contains("No description"));
+        assert!(text.contains("Uncategorised"));
+        assert!(!text.contains("left out of totals"));
+        assert!(!text.contains("aria-label=\"Reconciled\""));
+        assert!(text.contains(&format!(
+            "hx-delete=\"{}?redirect=true\"",
+            format_endpoint(endpoints::TRANSACTION, transaction.id())
+        )));
     }
 
     #[tokio::test]
-    async fn can_get_transaction() {
+    async fn transaction_page_shows_everything_about_transaction() {
         let user_id = UserID::new(42);
 
         let mut state = AppState::new(
             "42",
-            DummyCategoryStore {},
+            StubCategoryStore {
+                categories: vec![Category::new(
+                    24,
+                    CategoryName::new_unchecked("Groceries"),
+                    user_id,
+                )],
+            },
             FakeTransactionStore::new(),
             DummyUserStore {},
         );
@@ -280,24 +1096,30 @@ mod transaction_tests {
         let transaction = state
             .transaction_store()
             .create_from_builder(
-                TransactionBuilder::new(13.34, user_id)
+                TransactionBuilder::new(-13.34, user_id)
                     .category(Some(24))
-                    .description("foobar".to_string()),
+                    .description("PAK N SAVE PETONE".to_string())
+                    .ignored(true)
+                    .reconciled(true),
             )
             .unwrap();
 
-        let jar = PrivateCookieJar::new(state.cookie_key().to_owned());
-        let jar = set_auth_cookie(jar, user_id, state.cookie_duration).unwrap();
-
-        let response = get_transaction(State(state), jar, Path(transaction.id()))
+        let response = get_transaction(State(state), Extension(user_id), Path(transaction.id()))
             .await
             .into_response();
 
         assert_eq!(response.status(), StatusCode::OK);
-
-        let json_response = extract_from_json(response).await;
-
-        assert_eq!(json_response, transaction);
+        let text = extract_text(response).await;
+        assert!(text.contains("PAK N SAVE PETONE"));
+        assert!(!text.contains("No description"));
+        assert!(text.contains(&format!(
+            "href=\"{}?category_id=24\"",
+            endpoints::TRANSACTIONS
+        )));
+        assert!(text.contains("Groceries"));
+        assert!(!text.contains("Uncategorised"));
+        assert!(text.contains("left out of totals"));
+        assert!(text.contains("aria-label=\"Reconciled\""));
     }
 
     #[tokio::test]
@@ -307,7 +1129,7 @@ mod transaction_tests {
 
         let mut state = AppState::new(
             "42",
-            DummyCategoryStore {},
+            StubCategoryStore::default(),
             FakeTransactionStore::new(),
             DummyUserStore {},
         );
@@ -321,32 +1143,122 @@ mod transaction_tests {
             )
             .unwrap();
 
-        let jar = PrivateCookieJar::new(state.cookie_key().to_owned());
-        let jar = set_auth_cookie(jar, unauthorized_user_id, state.cookie_duration).unwrap();
-
-        let response = get_transaction(State(state), jar, Path(transaction.id()))
-            .await
-            .into_response();
+        let response = get_transaction(
+            State(state),
+            Extension(unauthorized_user_id),
+            Path(transaction.id()),
+        )
+        .await
+        .into_response();
 
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
     }
 
-    async fn extract_text(response: Response<Body>) -> String {
-        let body = response.into_body();
-        let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+    #[tokio::test]
+    async fn can_ignore_transaction() {
+        let user_id = UserID::new(42);
 
-        String::from_utf8_lossy(&body).to_string()
+        let mut state = AppState::new(
+            "42",
+            StubCategoryStore::default(),
+            FakeTransactionStore::new(),
+            DummyUserStore {},
+        );
+
+        let transaction = state
+            .transaction_store()
+            .create_from_builder(TransactionBuilder::new(0.0, user_id))
+            .unwrap();
+
+        let response = set_transaction_ignored(
+            State(state.clone()),
+            Extension(user_id),
+            Path(transaction.id()),
+            Form(IgnoreTransactionForm {
+                ignored: true,
+                card: false,
+            }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(extract_text(response).await.contains("line-through"));
+    }
+
+    #[tokio::test]
+    async fn ignoring_transaction_from_card_responds_with_card() {
+        let user_id = UserID::new(42);
+
+        let mut state = AppState::new(
+            "42",
+            StubCategoryStore::default(),
+            FakeTransactionStore::new(),
+            DummyUserStore {},
+        );
+
+        let transaction = state
+            .transaction_store()
+            .create_from_builder(TransactionBuilder::new(0.0, user_id))
+            .unwrap();
+
+        let response = set_transaction_ignored(
+            State(state.clone()),
+            Extension(user_id),
+            Path(transaction.id()),
+            Form(IgnoreTransactionForm {
+                ignored: true,
+                card: true,
+            }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let text = extract_text(response).await;
+        assert!(text.starts_with("<li"), "got {text}");
+        assert!(text.contains(&format!("data-transaction-id=\"{}\"", transaction.id())));
+        assert!(text.contains("Unignore"));
+    }
+
+    #[tokio::test]
+    async fn cannot_ignore_transaction_with_unauthorized_user() {
+        let user_id = UserID::new(42);
+        let unauthorized_user_id = UserID::new(1337);
+
+        let mut state = AppState::new(
+            "42",
+            StubCategoryStore::default(),
+            FakeTransactionStore::new(),
+            DummyUserStore {},
+        );
+
+        let transaction = state
+            .transaction_store()
+            .create_from_builder(TransactionBuilder::new(0.0, user_id))
+            .unwrap();
+
+        let response = set_transaction_ignored(
+            State(state),
+            Extension(unauthorized_user_id),
+            Path(transaction.id()),
+            Form(IgnoreTransactionForm {
+                ignored: true,
+                card: false,
+            }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
     }
 
-    async fn extract_from_json(response: Response<Body>) -> Transaction {
+    async fn extract_text(response: Response<Body>) -> String {
         let body = response.into_body();
         let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
 
-        serde_json::from_slice(&body).unwrap()
+        String::from_utf8_lossy(&body).to_string()
     }
 
     fn assert_create_calls(
-        mut state: AppState<DummyCategoryStore, FakeTransactionStore, DummyUserStore>,
+        mut state: AppState<StubCategoryStore, FakeTransactionStore, DummyUserStore>,
         want: Transaction,
     ) {
         let create_calls = state
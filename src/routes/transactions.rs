@@ -1,120 +1,544 @@
+use std::ops::RangeInclusive;
+
 use askama_axum::Template;
 use axum::{
     extract::State,
-    http::Uri,
+    http::{StatusCode, Uri},
     response::{IntoResponse, Response},
     Extension,
 };
-use time::{Date, OffsetDateTime};
+use axum_extra::extract::Query;
+use serde::Deserialize;
+use time::Date;
 
 use crate::{
-    models::UserID,
+    filters,
+    models::{Category, DatabaseID, Transaction, TransactionError, UserID},
+    pagination::{get_page, get_page_count, PAGE_SIZE_OPTIONS},
     routes::get_internal_server_error_redirect,
     stores::{
-        transaction::{SortOrder, TransactionQuery},
+        transaction::{IgnoredFilter, SortOrder, TransactionCursor, TransactionQuery},
         CategoryStore, TransactionStore, UserStore,
     },
+    timezone::get_user_today,
     AppError, AppState,
 };
 
 use super::{
     endpoints::{self, format_endpoint},
     navigation::{get_nav_bar, NavbarTemplate},
-    templates::TransactionRow,
+    templates::{render, NewTransactionRowTemplate, TransactionRow},
 };
 
-/// Renders the dashboard page.
+/// Renders the transactions page.
 #[derive(Template)]
 #[template(path = "views/transactions.html")]
 struct TransactionsTemplate<'a> {
     navbar: NavbarTemplate<'a>,
-    /// The user's transactions for this week, as Askama templates.
+    /// The link that toggles between showing all transactions and ignored transactions only.
+    toggle_ignored_href: String,
+    /// The route for reconciling transactions against a bank statement.
+    reconcile_route: &'a str,
+    duplicates_route: &'a str,
+    /// The link to the monthly statement, filtered by the same category if one is selected.
+    statement_href: String,
+    /// The link that clears the date and category filters, if any are set.
+    clear_filters_href: Option<String>,
+    /// The filters the transactions were selected with.
+    query: TransactionsPageQuery,
+    /// The user's categories, for choosing which to exclude.
+    categories: Vec<Category>,
+    transactions_route: &'a str,
+    page_sizes: [u64; 4],
+    table: TransactionsTableTemplate,
+}
+
+impl TransactionsTemplate<'_> {
+    fn is_selected(&self, per_page: &u64) -> bool {
+        self.table.per_page == *per_page
+    }
+
+    fn is_excluded(&self, category: &Category) -> bool {
+        self.query.exclude_category_ids.contains(&category.id())
+    }
+}
+
+/// Renders a page of the transactions table and the links to the other pages.
+///
+/// The table reloads itself when a response has the HX-Trigger header [TRANSACTIONS_CHANGED], so
+/// that changes to transactions are shown without losing the filters or page.
+#[derive(Template)]
+#[template(path = "partials/transactions/table.html")]
+struct TransactionsTableTemplate {
+    /// The user's transactions on this page, as Askama templates.
     transactions: Vec<TransactionRow>,
-    /// Today's date, i.e. the date the template was rendered.
-    today: Date,
     /// The route for creating a new transaction for the current user.
     create_transaction_route: Uri,
+    /// The inputs for a new transaction.
+    new_transaction_row: NewTransactionRowTemplate,
+    /// The link that renders this table again with the same filters and page.
+    table_href: String,
+    changed_event: &'static str,
+    /// The current page, starting from one.
+    page: u64,
+    page_count: u64,
+    per_page: u64,
+    previous_page_href: Option<String>,
+    next_page_href: Option<String>,
+    /// The link that loads the transactions after the last one on this page, if there are any.
+    load_more_href: Option<String>,
+    /// The totals of every transaction matching the filters, not just this page. Only set when
+    /// the transactions are filtered, so that the unfiltered page does not sum every transaction.
+    summary: Option<FilteredSummary>,
+}
+
+/// The number, total and average amount of the transactions matching the filters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FilteredSummary {
+    count: u64,
+    total: f64,
+    average: f64,
+}
+
+/// Renders the transactions after a cursor, for appending to the transactions table.
+#[derive(Template)]
+#[template(path = "partials/transactions/more.html")]
+struct MoreTransactionsTemplate {
+    transactions: Vec<TransactionRow>,
+    /// The link that loads the transactions after these ones, if there are any.
+    load_more_href: Option<String>,
+}
+
+/// The HTMX event that tells the transactions table to reload, e.g. after a transaction is
+/// created or deleted.
+pub const TRANSACTIONS_CHANGED: &str = "transactions-changed";
+
+/// The query parameters for filtering the transactions page.
+///
+/// Use [TransactionsPageQuery::href] to build links to a filtered transactions page.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+pub struct TransactionsPageQuery {
+    /// Display only ignored transactions.
+    #[serde(default)]
+    pub ignored_only: bool,
+    /// Display transactions on or after this date.
+    pub from: Option<Date>,
+    /// Display transactions on or before this date.
+    pub to: Option<Date>,
+    /// Display transactions assigned to the category with this ID.
+    pub category_id: Option<DatabaseID>,
+    /// Display only transactions without a category.
+    #[serde(default)]
+    pub uncategorised_only: bool,
+    /// Hide transactions assigned to any of these categories, e.g. transfers between accounts.
+    ///
+    /// Given as a repeated query parameter, e.g. `exclude_category_ids=1&exclude_category_ids=2`.
+    #[serde(default)]
+    pub exclude_category_ids: Vec<DatabaseID>,
+    /// The page to display, starting from one. Defaults to the first page.
+    pub page: Option<u64>,
+    /// The number of transactions to display on each page.
+    pub per_page: Option<u64>,
+}
+
+impl TransactionsPageQuery {
+    /// Build the link to the transactions page filtered by this query.
+    pub fn href(&self) -> String {
+        self.href_with_path(endpoints::TRANSACTIONS)
+    }
+
+    /// Build the link to `path` with this query.
+    fn href_with_path(&self, path: &str) -> String {
+        let parameters = self.query_parameters();
+
+        if parameters.is_empty() {
+            path.to_string()
+        } else {
+            format!("{path}?{}", parameters.join("&"))
+        }
+    }
+
+    /// Build the link that loads `per_page` transactions after `last` filtered by this query.
+    fn load_more_href(&self, per_page: u64, last: &Transaction) -> String {
+        let mut parameters = TransactionsPageQuery {
+            page: None,
+            per_page: Some(per_page),
+            ..self.clone()
+        }
+        .query_parameters();
+        parameters.push(format!("after={}", TransactionCursor::of(last).encode()));
+
+        format!("{}?{}", endpoints::TRANSACTIONS_MORE, parameters.join("&"))
+    }
+
+    /// The `key=value` pairs for the parameters that are set.
+    fn query_parameters(&self) -> Vec<String> {
+        let mut parameters = vec![];
+
+        if self.ignored_only {
+            parameters.push("ignored_only=true".to_string());
+        }
+
+        if let Some(from) = self.from {
+            parameters.push(format!("from={from}"));
+        }
+
+        if let Some(to) = self.to {
+            parameters.push(format!("to={to}"));
+        }
+
+        if let Some(category_id) = self.category_id {
+            parameters.push(format!("category_id={category_id}"));
+        }
+
+        if self.uncategorised_only {
+            parameters.push("uncategorised_only=true".to_string());
+        }
+
+        for category_id in &self.exclude_category_ids {
+            parameters.push(format!("exclude_category_ids={category_id}"));
+        }
+
+        if let Some(page) = self.page {
+            parameters.push(format!("page={page}"));
+        }
+
+        if let Some(per_page) = self.per_page {
+            parameters.push(format!("per_page={per_page}"));
+        }
+
+        parameters
+    }
+
+    /// The query for `user_id`'s transactions matching these filters, most recent first.
+    pub(crate) fn transaction_query(&self, user_id: UserID) -> TransactionQuery {
+        TransactionQuery {
+            user_id: Some(user_id),
+            date_range: self.date_range(),
+            category_id: self.category_id,
+            uncategorised_only: self.uncategorised_only,
+            exclude_category_ids: self.exclude_category_ids.clone(),
+            sort_date: Some(SortOrder::Descending),
+            ignored: if self.ignored_only {
+                IgnoredFilter::Only
+            } else {
+                IgnoredFilter::Include
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Whether the date or category filters are set.
+    fn is_filtered(&self) -> bool {
+        self.from.is_some()
+            || self.to.is_some()
+            || self.category_id.is_some()
+            || self.uncategorised_only
+            || !self.exclude_category_ids.is_empty()
+    }
+
+    /// The date range selected by `from` and `to`, if either is set.
+    pub(crate) fn date_range(&self) -> Option<RangeInclusive<Date>> {
+        match (self.from, self.to) {
+            (None, None) => None,
+            (from, to) => Some(from.unwrap_or(Date::MIN)..=to.unwrap_or(Date::MAX)),
+        }
+    }
 }
 
+/// Display a page of the user's transactions, most recent first.
+///
+/// Ignored transactions are included so that they can be toggled back, unless
+/// `ignored_only` is set, in which case only ignored transactions are displayed.
 pub async fn get_transactions_page<C, T, U>(
     State(mut state): State<AppState<C, T, U>>,
     Extension(user_id): Extension<UserID>,
+    Query(query): Query<TransactionsPageQuery>,
 ) -> Response
 where
     C: CategoryStore + Send + Sync,
     T: TransactionStore + Send + Sync,
     U: UserStore + Send + Sync,
 {
-    let navbar = get_nav_bar(endpoints::TRANSACTIONS);
-
-    let transactions = state.transaction_store().get_query(TransactionQuery {
-        user_id: Some(user_id),
-        limit: Some(20),
-        sort_date: Some(SortOrder::Descending),
-        ..Default::default()
-    });
-    let transactions = match transactions {
-        Ok(transactions) => transactions,
-        Err(error) => return AppError::TransactionError(error).into_response(),
+    let table = match get_transactions_table(&mut state, user_id, &query) {
+        Ok(table) => table,
+        Err(response) => return response,
     };
+    let categories = match state.category_store().get_by_user(user_id) {
+        Ok(categories) => categories,
+        Err(error) => return AppError::CategoryError(error).into_response(),
+    };
+
+    render(
+        StatusCode::OK,
+        &TransactionsTemplate {
+            navbar: get_nav_bar(endpoints::TRANSACTIONS, state.demo_mode),
+            reconcile_route: endpoints::RECONCILE,
+            duplicates_route: endpoints::DUPLICATES,
+            statement_href: match query.category_id {
+                Some(category_id) => format!("{}?category_id={category_id}", endpoints::STATEMENT),
+                None => endpoints::STATEMENT.to_string(),
+            },
+            toggle_ignored_href: TransactionsPageQuery {
+                ignored_only: !query.ignored_only,
+                page: None,
+                ..query.clone()
+            }
+            .href(),
+            clear_filters_href: query.is_filtered().then(|| {
+                TransactionsPageQuery {
+                    ignored_only: query.ignored_only,
+                    per_page: query.per_page,
+                    ..Default::default()
+                }
+                .href()
+            }),
+            transactions_route: endpoints::TRANSACTIONS,
+            page_sizes: PAGE_SIZE_OPTIONS,
+            table,
+            query,
+            categories,
+        },
+    )
+}
+
+/// Render only the transactions table for the filters and page in `query`, e.g. to refresh the
+/// table after a transaction is deleted.
+pub async fn get_transactions_table_fragment<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Query(query): Query<TransactionsPageQuery>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    match get_transactions_table(&mut state, user_id, &query) {
+        Ok(table) => render(StatusCode::OK, &table),
+        Err(response) => response,
+    }
+}
+
+/// Get the page of `user_id`'s transactions selected by `query`.
+///
+/// The page size is clamped to the limits in [AppState::pagination], and pages past the last
+/// page give the last page.
+///
+/// # Errors
+/// Returns an error response if the transactions could not be retrieved.
+#[allow(clippy::result_large_err)]
+fn get_transactions_table<C, T, U>(
+    state: &mut AppState<C, T, U>,
+    user_id: UserID,
+    query: &TransactionsPageQuery,
+) -> Result<TransactionsTableTemplate, Response>
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let count = state
+        .transaction_store()
+        .count_query(query.transaction_query(user_id))
+        .map_err(|error| AppError::TransactionError(error).into_response())?;
+    let per_page = state.pagination.per_page(query.per_page);
+    let page_count = get_page_count(count, per_page);
+    let page = get_page(query.page, page_count);
 
-    let today = OffsetDateTime::now_utc().date();
+    let transactions = state
+        .transaction_store()
+        .get_query(TransactionQuery {
+            limit: Some(per_page),
+            offset: Some((page - 1) * per_page),
+            ..query.transaction_query(user_id)
+        })
+        .map_err(|error| AppError::TransactionError(error).into_response())?;
 
-    let create_transaction_route =
-        format_endpoint(endpoints::USER_TRANSACTIONS, user_id.as_i64()).parse();
+    let summary = if query.is_filtered() {
+        Some(
+            get_filtered_summary(state, user_id, query)
+                .map_err(|error| AppError::TransactionError(error).into_response())?,
+        )
+    } else {
+        None
+    };
 
-    let create_transaction_route = match create_transaction_route {
-        Ok(uri) => uri,
-        Err(error) => {
+    let today = get_user_today(state.user_store(), user_id);
+
+    let create_transaction_route = format_endpoint(endpoints::USER_TRANSACTIONS, user_id.as_i64())
+        .parse()
+        .map_err(|error| {
             tracing::error!(
                 "An error ocurred while creating route URI using the endpoint {}: {error}",
                 endpoints::USER_TRANSACTIONS
             );
-            return get_internal_server_error_redirect();
-        }
-    };
+            get_internal_server_error_redirect()
+        })?;
 
-    let transactions = transactions
-        .into_iter()
-        .map(|transaction| TransactionRow { transaction })
-        .collect();
+    let load_more_href = transactions
+        .last()
+        .filter(|_| page < page_count)
+        .map(|last| query.load_more_href(per_page, last));
+
+    let page_query = |page: u64| TransactionsPageQuery {
+        page: Some(page),
+        per_page: Some(per_page),
+        ..query.clone()
+    };
 
-    TransactionsTemplate {
-        navbar,
-        transactions,
-        today,
+    Ok(TransactionsTableTemplate {
+        transactions: transactions
+            .into_iter()
+            .map(|transaction| TransactionRow { transaction })
+            .collect(),
         create_transaction_route,
-    }
-    .into_response()
+        new_transaction_row: NewTransactionRowTemplate::new(user_id, today),
+        table_href: page_query(page).href_with_path(endpoints::TRANSACTIONS_TABLE),
+        changed_event: TRANSACTIONS_CHANGED,
+        page,
+        page_count,
+        per_page,
+        previous_page_href: (page > 1).then(|| page_query(page - 1).href()),
+        next_page_href: (page < page_count).then(|| page_query(page + 1).href()),
+        load_more_href,
+        summary,
+    })
+}
+
+/// Count, sum and average `user_id`'s transactions matching the filters in `query`.
+///
+/// Ignored transactions are left out of the totals unless only ignored transactions are shown.
+fn get_filtered_summary<C, T, U>(
+    state: &mut AppState<C, T, U>,
+    user_id: UserID,
+    query: &TransactionsPageQuery,
+) -> Result<FilteredSummary, TransactionError>
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let summary_query = || TransactionQuery {
+        ignored: if query.ignored_only {
+            IgnoredFilter::Only
+        } else {
+            IgnoredFilter::Exclude
+        },
+        ..query.transaction_query(user_id)
+    };
+
+    let count = state.transaction_store().count_query(summary_query())?;
+    let total = state.transaction_store().sum_query(summary_query())?;
+    let average = if count == 0 {
+        0.0
+    } else {
+        total / count as f64
+    };
+
+    Ok(FilteredSummary {
+        count,
+        total,
+        average,
+    })
+}
+
+/// The query parameters for loading more transactions.
+#[derive(Debug, Deserialize)]
+pub struct LoadMoreQuery {
+    /// Load the transactions after this one, encoded with [TransactionCursor::encode].
+    pub after: TransactionCursor,
+}
+
+/// Render the table rows for the transactions after the cursor `after`, followed by a button to
+/// load more if there are any.
+///
+/// This uses the cursor instead of a page number so that loading later transactions does not
+/// get slower the further the user scrolls.
+pub async fn get_more_transactions<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Query(query): Query<TransactionsPageQuery>,
+    Query(LoadMoreQuery { after }): Query<LoadMoreQuery>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let per_page = state.pagination.per_page(query.per_page);
+
+    // Get one extra transaction to find out whether there are more to load.
+    let transactions = state.transaction_store().get_query(TransactionQuery {
+        limit: Some(per_page + 1),
+        after: Some(after),
+        ..query.transaction_query(user_id)
+    });
+    let mut transactions = match transactions {
+        Ok(transactions) => transactions,
+        Err(error) => return AppError::TransactionError(error).into_response(),
+    };
+
+    let has_more = transactions.len() as u64 > per_page;
+    transactions.truncate(per_page as usize);
+
+    let load_more_href = transactions
+        .last()
+        .filter(|_| has_more)
+        .map(|last| query.load_more_href(per_page, last));
+
+    render(
+        StatusCode::OK,
+        &MoreTransactionsTemplate {
+            transactions: transactions
+                .into_iter()
+                .map(|transaction| TransactionRow { transaction })
+                .collect(),
+            load_more_href,
+        },
+    )
 }
 
 #[cfg(test)]
 mod transactions_route_tests {
     use axum::{
+        extract::FromRequestParts,
+        http::{Request, Uri},
         middleware,
-        routing::{get, post},
+        routing::{delete, get, post},
         Router,
     };
+    use axum_extra::extract::Query;
+    use axum_htmx::{HX_REDIRECT, HX_TRIGGER};
     use axum_test::TestServer;
     use rusqlite::Connection;
+    use time::{macros::date, Duration, OffsetDateTime};
 
     use crate::{
         auth::{log_in::LogInData, middleware::auth_guard},
-        models::{PasswordHash, Transaction, User, ValidatedPassword},
-        routes::{endpoints, log_in::post_log_in},
+        models::{CategoryName, PasswordHash, Transaction, User, ValidatedPassword},
+        routes::{
+            endpoints::{self, format_endpoint},
+            log_in::post_log_in,
+            transaction::delete_transaction,
+        },
         stores::{
             sql_store::{create_app_state, SQLAppState},
-            TransactionStore, UserStore,
+            CategoryStore, TransactionStore, UserStore,
         },
     };
 
-    use super::get_transactions_page;
+    use super::{
+        get_more_transactions, get_transactions_page, get_transactions_table_fragment,
+        TransactionsPageQuery, TRANSACTIONS_CHANGED,
+    };
 
     fn get_test_state_server_and_user() -> (SQLAppState, TestServer, User) {
         let db_connection =
             Connection::open_in_memory().expect("Could not open database in memory.");
 
         let mut state = create_app_state(db_connection, "42").unwrap();
+        state.password_hash_cost = 4;
 
         let user = state
             .user_store()
@@ -126,6 +550,12 @@ mod transactions_route_tests {
 
         let app = Router::new()
             .route(endpoints::TRANSACTIONS, get(get_transactions_page))
+            .route(endpoints::TRANSACTIONS_MORE, get(get_more_transactions))
+            .route(
+                endpoints::TRANSACTIONS_TABLE,
+                get(get_transactions_table_fragment),
+            )
+            .route(endpoints::TRANSACTION, delete(delete_transaction))
             .layer(middleware::from_fn_with_state(state.clone(), auth_guard))
             .route(endpoints::LOG_IN, post(post_log_in))
             .with_state(state.clone());
@@ -175,4 +605,547 @@ mod transactions_route_tests {
             assert!(transactions_page.contains(transaction.description()));
         }
     }
+
+    #[tokio::test]
+    async fn transactions_page_renders_table_rows_and_cards() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let transaction = state
+            .transaction_store()
+            .create_from_builder(Transaction::build(1.0, user.id()))
+            .unwrap();
+        let jar = server
+            .post(endpoints::LOG_IN)
+            .form(&LogInData {
+                email: "test@test.com".to_string(),
+                password: "test".to_string(),
+                remember_me: None,
+            })
+            .await
+            .cookies();
+
+        let text = server
+            .get(endpoints::TRANSACTIONS)
+            .add_cookies(jar)
+            .await
+            .text();
+
+        let id_attribute = format!("data-transaction-id=\"{}\"", transaction.id());
+        assert_eq!(text.matches(&id_attribute).count(), 2, "got {text}");
+        assert!(text.contains("id=\"transaction-cards\""));
+    }
+
+    #[tokio::test]
+    async fn transactions_page_filters_ignored_transactions() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+
+        state
+            .transaction_store()
+            .create_from_builder(Transaction::build(1.0, user.id()).description("foo".to_string()))
+            .unwrap();
+        state
+            .transaction_store()
+            .create_from_builder(
+                Transaction::build(0.0, user.id())
+                    .description("bar".to_string())
+                    .ignored(true),
+            )
+            .unwrap();
+
+        let jar = server
+            .post(endpoints::LOG_IN)
+            .form(&LogInData {
+                email: "test@test.com".to_string(),
+                password: "test".to_string(),
+                remember_me: None,
+            })
+            .await
+            .cookies();
+
+        let all_page = server
+            .get(endpoints::TRANSACTIONS)
+            .add_cookies(jar.clone())
+            .await
+            .text();
+        assert!(all_page.contains("foo"));
+        assert!(all_page.contains("bar"));
+
+        let ignored_page = server
+            .get(endpoints::TRANSACTIONS)
+            .add_query_param("ignored_only", true)
+            .add_cookies(jar)
+            .await
+            .text();
+        assert!(!ignored_page.contains("foo"));
+        assert!(ignored_page.contains("bar"));
+    }
+
+    #[tokio::test]
+    async fn transactions_page_filters_by_date_range() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let today = OffsetDateTime::now_utc().date();
+
+        state
+            .transaction_store()
+            .create_from_builder(
+                Transaction::build(1.0, user.id())
+                    .description("foo".to_string())
+                    .date(today - Duration::weeks(2))
+                    .unwrap(),
+            )
+            .unwrap();
+        state
+            .transaction_store()
+            .create_from_builder(Transaction::build(2.0, user.id()).description("bar".to_string()))
+            .unwrap();
+
+        let jar = server
+            .post(endpoints::LOG_IN)
+            .form(&LogInData {
+                email: "test@test.com".to_string(),
+                password: "test".to_string(),
+                remember_me: None,
+            })
+            .await
+            .cookies();
+
+        let href = TransactionsPageQuery {
+            from: Some(today - Duration::weeks(1)),
+            to: Some(today),
+            ..Default::default()
+        }
+        .href();
+
+        let page = server.get(&href).add_cookies(jar).await.text();
+
+        assert!(!page.contains("foo"));
+        assert!(page.contains("bar"));
+        assert!(page.contains("Clear filters"));
+    }
+
+    #[tokio::test]
+    async fn transactions_page_excludes_categories() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let transfers = state
+            .category_store()
+            .create(CategoryName::new_unchecked("Transfers"), user.id())
+            .unwrap();
+        let builders = [
+            Transaction::build(1.0, user.id()).description("groceries #1".to_string()),
+            Transaction::build(2.0, user.id())
+                .description("transfer to savings".to_string())
+                .category(Some(transfers.id())),
+            Transaction::build(3.0, user.id()).description("groceries #2".to_string()),
+        ];
+        for builder in builders {
+            state
+                .transaction_store()
+                .create_from_builder(builder)
+                .unwrap();
+        }
+
+        let jar = server
+            .post(endpoints::LOG_IN)
+            .form(&LogInData {
+                email: "test@test.com".to_string(),
+                password: "test".to_string(),
+                remember_me: None,
+            })
+            .await
+            .cookies();
+
+        let href = TransactionsPageQuery {
+            exclude_category_ids: vec![transfers.id()],
+            per_page: Some(1),
+            ..Default::default()
+        }
+        .href();
+
+        let page = server.get(&href).add_cookies(jar).await.text();
+
+        assert!(!page.contains("transfer to savings"));
+        assert!(page.contains("groceries #2<"));
+        // The excluded transaction is not counted, and pagination keeps the exclusion.
+        assert!(
+            page.contains("Page 1 of 2"),
+            "page count not found in {page}"
+        );
+        assert!(page.contains(&format!("exclude_category_ids={}", transfers.id())));
+        assert!(page.contains("excluding 1 category"));
+    }
+
+    #[tokio::test]
+    async fn transactions_page_summarises_all_filtered_transactions() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let today = OffsetDateTime::now_utc().date();
+        let builders = [
+            Transaction::build(100.0, user.id()),
+            Transaction::build(-40.5, user.id()),
+            Transaction::build(-20.0, user.id()),
+            // Ignored transactions are left out of totals.
+            Transaction::build(-1000.0, user.id()).ignored(true),
+            // Transactions outside of the date range do not match the filter.
+            Transaction::build(500.0, user.id())
+                .date(today - Duration::weeks(2))
+                .unwrap(),
+        ];
+        for builder in builders {
+            state
+                .transaction_store()
+                .create_from_builder(builder)
+                .unwrap();
+        }
+
+        let jar = server
+            .post(endpoints::LOG_IN)
+            .form(&LogInData {
+                email: "test@test.com".to_string(),
+                password: "test".to_string(),
+                remember_me: None,
+            })
+            .await
+            .cookies();
+
+        // The summary should cover every matching transaction, not just the first page.
+        let href = TransactionsPageQuery {
+            from: Some(today - Duration::weeks(1)),
+            per_page: Some(1),
+            ..Default::default()
+        }
+        .href();
+
+        let page = server.get(&href).add_cookies(jar).await.text();
+
+        assert!(
+            page.contains("3 transactions, total $39.50, avg $13.17"),
+            "summary not found in {page}"
+        );
+    }
+
+    #[tokio::test]
+    async fn unfiltered_transactions_page_has_no_summary() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let jar = create_transactions_and_log_in(&mut state, &server, &user, 3)
+            .await
+            .cookies();
+
+        let page = server
+            .get(endpoints::TRANSACTIONS)
+            .add_cookies(jar)
+            .await
+            .text();
+
+        assert!(!page.contains("filtered-summary"));
+    }
+
+    /// Create `count` transactions described as "transaction #1", "transaction #2", etc., and log
+    /// in as `user`.
+    async fn create_transactions_and_log_in(
+        state: &mut SQLAppState,
+        server: &TestServer,
+        user: &User,
+        count: usize,
+    ) -> axum_test::TestResponse {
+        for i in 1..=count {
+            state
+                .transaction_store()
+                .create_from_builder(
+                    Transaction::build(i as f64, user.id())
+                        .description(format!("transaction #{i}")),
+                )
+                .unwrap();
+        }
+
+        server
+            .post(endpoints::LOG_IN)
+            .form(&LogInData {
+                email: "test@test.com".to_string(),
+                password: "test".to_string(),
+                remember_me: None,
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn transactions_page_uses_default_page_size_for_zero() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let jar = create_transactions_and_log_in(&mut state, &server, &user, 30)
+            .await
+            .cookies();
+
+        let response = server
+            .get(endpoints::TRANSACTIONS)
+            .add_query_param("per_page", 0)
+            .add_cookies(jar)
+            .await;
+
+        response.assert_status_ok();
+        let page = response.text();
+        // The newest transactions come first, so the first page of 25 ends at #6.
+        assert!(page.contains("transaction #30<"));
+        assert!(page.contains("transaction #6<"));
+        assert!(!page.contains("transaction #5<"));
+        assert!(page.contains("Page 1 of 2"));
+    }
+
+    #[tokio::test]
+    async fn transactions_page_clamps_page_size() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let jar = create_transactions_and_log_in(&mut state, &server, &user, 120)
+            .await
+            .cookies();
+
+        let page = server
+            .get(endpoints::TRANSACTIONS)
+            .add_query_param("per_page", 1_000_000)
+            .add_cookies(jar)
+            .await
+            .text();
+
+        assert!(page.contains("transaction #21<"));
+        assert!(!page.contains("transaction #20<"));
+        assert!(page.contains("Page 1 of 2"));
+    }
+
+    #[tokio::test]
+    async fn transactions_page_clamps_page_to_last_page() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let jar = create_transactions_and_log_in(&mut state, &server, &user, 30)
+            .await
+            .cookies();
+
+        let response = server
+            .get(endpoints::TRANSACTIONS)
+            .add_query_param("page", 99999)
+            .add_query_param("per_page", 10)
+            .add_cookies(jar)
+            .await;
+
+        response.assert_status_ok();
+        let page = response.text();
+        assert!(page.contains("transaction #1<"));
+        assert!(page.contains("transaction #10<"));
+        assert!(!page.contains("transaction #11<"));
+        assert!(page.contains("Page 3 of 3"));
+        assert!(page.contains("page=2&amp;per_page=10"));
+    }
+
+    /// Get the link of the "Load more" button in `html`, if there is one.
+    fn get_load_more_href(html: &str) -> Option<String> {
+        let prefix = r#"hx-get=""#;
+        let start = html.find(&format!("{prefix}{}", endpoints::TRANSACTIONS_MORE))? + prefix.len();
+        let end = start + html[start..].find('"')?;
+
+        Some(html[start..end].replace("&amp;", "&"))
+    }
+
+    #[tokio::test]
+    async fn load_more_continues_from_last_transaction() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        // The transactions all have the same date, so only their IDs tell them apart.
+        let jar = create_transactions_and_log_in(&mut state, &server, &user, 25)
+            .await
+            .cookies();
+
+        let page = server
+            .get(endpoints::TRANSACTIONS)
+            .add_query_param("per_page", 10)
+            .add_cookies(jar.clone())
+            .await
+            .text();
+        let mut load_more_href = get_load_more_href(&page);
+        let mut loaded = vec![];
+
+        while let Some(href) = load_more_href {
+            let response = server.get(&href).add_cookies(jar.clone()).await;
+            response.assert_status_ok();
+            let fragment = response.text();
+
+            assert!(!fragment.contains("<html"));
+            loaded.push(fragment.clone());
+            load_more_href = get_load_more_href(&fragment);
+        }
+
+        assert_eq!(loaded.len(), 2);
+        for i in 11..=15 {
+            assert!(page.contains(&format!("transaction #{}<", i + 10)));
+            assert!(loaded[0].contains(&format!("transaction #{i}<")));
+            assert!(!loaded[1].contains(&format!("transaction #{i}<")));
+        }
+        for i in 1..=5 {
+            assert!(loaded[1].contains(&format!("transaction #{i}<")));
+            assert!(!loaded[0].contains(&format!("transaction #{i}<")));
+        }
+    }
+
+    #[tokio::test]
+    async fn load_more_rejects_invalid_cursor() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let jar = create_transactions_and_log_in(&mut state, &server, &user, 1)
+            .await
+            .cookies();
+
+        server
+            .get(endpoints::TRANSACTIONS_MORE)
+            .add_query_param("after", "garbage")
+            .add_cookies(jar)
+            .await
+            .assert_status_bad_request();
+    }
+
+    /// Get the link that the transactions table reloads itself from in `html`.
+    fn get_table_href(html: &str) -> String {
+        let prefix = r#"hx-get=""#;
+        let start = html
+            .find(&format!("{prefix}{}", endpoints::TRANSACTIONS_TABLE))
+            .expect("the transactions table should have a link to reload it")
+            + prefix.len();
+        let end = start + html[start..].find('"').unwrap();
+
+        html[start..end].replace("&amp;", "&")
+    }
+
+    #[tokio::test]
+    async fn delete_keeps_filters_and_page() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let jar = create_transactions_and_log_in(&mut state, &server, &user, 30)
+            .await
+            .cookies();
+        let today = OffsetDateTime::now_utc().date();
+        let href = TransactionsPageQuery {
+            from: Some(today - Duration::days(1)),
+            to: Some(today),
+            page: Some(3),
+            per_page: Some(10),
+            ..Default::default()
+        }
+        .href();
+
+        let page = server.get(&href).add_cookies(jar.clone()).await.text();
+        let table_href = get_table_href(&page);
+        assert!(page.contains("transaction #5<"));
+        assert!(!page.contains("transaction #11<"));
+        assert!(table_href.contains(&format!("from={}", today - Duration::days(1))));
+        assert!(table_href.contains("page=3&per_page=10"));
+
+        // Transaction #5 has the ID 5, since it was the fifth to be created.
+        let response = server
+            .delete(&format_endpoint(endpoints::TRANSACTION, 5))
+            .add_cookies(jar.clone())
+            .await;
+        response.assert_status_ok();
+        assert_eq!(response.header(HX_TRIGGER), TRANSACTIONS_CHANGED);
+
+        let table = server.get(&table_href).add_cookies(jar).await;
+        table.assert_status_ok();
+        let table = table.text();
+        assert!(!table.contains("<html"));
+        assert!(table.contains("Page 3 of 3"));
+        assert!(table.contains("transaction #10<"));
+        assert!(!table.contains("transaction #5<"));
+        assert_eq!(get_table_href(&table), table_href);
+    }
+
+    #[tokio::test]
+    async fn cannot_delete_another_users_transaction() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let jar = create_transactions_and_log_in(&mut state, &server, &user, 1)
+            .await
+            .cookies();
+        let other_user = state
+            .user_store()
+            .create(
+                "other@test.com".parse().unwrap(),
+                PasswordHash::new(ValidatedPassword::new_unchecked("test"), 4).unwrap(),
+            )
+            .unwrap();
+        let other_transaction = state
+            .transaction_store()
+            .create(1.0, other_user.id())
+            .unwrap();
+
+        server
+            .delete(&format_endpoint(
+                endpoints::TRANSACTION,
+                other_transaction.id(),
+            ))
+            .add_cookies(jar)
+            .await
+            .assert_status_not_found();
+
+        assert!(state
+            .transaction_store()
+            .get(other_transaction.id())
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn deleting_from_transaction_page_redirects_to_transactions_page() {
+        let (mut state, server, user) = get_test_state_server_and_user();
+        let jar = create_transactions_and_log_in(&mut state, &server, &user, 1)
+            .await
+            .cookies();
+        let transaction_id = state.transaction_store().get_by_user_id(user.id()).unwrap()[0].id();
+
+        let response = server
+            .delete(&format!(
+                "{}?redirect=true",
+                format_endpoint(endpoints::TRANSACTION, transaction_id)
+            ))
+            .add_cookies(jar)
+            .await;
+
+        response.assert_status_ok();
+        assert_eq!(response.header(HX_REDIRECT), endpoints::TRANSACTIONS);
+        assert!(state.transaction_store().get(transaction_id).is_err());
+    }
+
+    #[tokio::test]
+    async fn href_round_trips_through_query_parsing() {
+        let cases = [
+            TransactionsPageQuery::default(),
+            TransactionsPageQuery {
+                ignored_only: true,
+                ..Default::default()
+            },
+            TransactionsPageQuery {
+                from: Some(date!(2024 - 01 - 01)),
+                to: Some(date!(2024 - 01 - 31)),
+                ..Default::default()
+            },
+            TransactionsPageQuery {
+                ignored_only: true,
+                from: Some(date!(2024 - 01 - 01)),
+                to: None,
+                category_id: Some(42),
+                uncategorised_only: false,
+                exclude_category_ids: vec![7, 9],
+                page: Some(3),
+                per_page: Some(50),
+            },
+            TransactionsPageQuery {
+                from: Some(date!(2024 - 01 - 01)),
+                to: Some(date!(2024 - 01 - 07)),
+                uncategorised_only: true,
+                ..Default::default()
+            },
+            TransactionsPageQuery {
+                exclude_category_ids: vec![3],
+                ..Default::default()
+            },
+        ];
+
+        for want in cases {
+            let href = want.href();
+            let uri: Uri = href.parse().unwrap();
+
+            assert_eq!(uri.path(), endpoints::TRANSACTIONS);
+
+            let (mut parts, _) = Request::get(uri).body(()).unwrap().into_parts();
+            let Query(got) = Query::<TransactionsPageQuery>::from_request_parts(&mut parts, &())
+                .await
+                .unwrap();
+
+            assert_eq!(got, want, "got {got:?} from {href}, want {want:?}");
+        }
+    }
 }
@@ -0,0 +1,571 @@
+//! The page for managing the webhooks that are notified when something happens to a user's data.
+
+use askama_axum::Template;
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Redirect, Response},
+    Extension, Form,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    models::{DatabaseID, UserID, Webhook, WebhookEvent},
+    routes::get_internal_server_error_redirect,
+    stores::{sql_store::SQLAppState, UserError, WebhookStore},
+    webhooks::{build_payload, parse_webhook_url, WebhookError},
+};
+
+use super::{
+    endpoints::{self, format_endpoint},
+    navigation::{get_nav_bar, NavbarTemplate},
+};
+
+#[derive(Template)]
+#[template(path = "views/webhooks.html")]
+struct WebhooksPageTemplate<'a> {
+    navbar: NavbarTemplate<'a>,
+    webhooks: WebhooksTemplate<'a>,
+}
+
+/// Renders the form for adding webhooks and the list of the user's webhooks.
+///
+/// Empty strings mean that there is no message to display.
+#[derive(Template)]
+#[template(path = "partials/webhooks/section.html")]
+struct WebhooksTemplate<'a> {
+    webhooks_route: &'a str,
+    webhooks: Vec<Webhook>,
+    url: &'a str,
+    url_error: &'a str,
+    message: &'a str,
+    error_message: &'a str,
+}
+
+impl WebhooksTemplate<'_> {
+    fn new(webhooks: Vec<Webhook>) -> Self {
+        Self {
+            webhooks_route: endpoints::WEBHOOKS,
+            webhooks,
+            url: "",
+            url_error: "",
+            message: "",
+            error_message: "",
+        }
+    }
+
+    /// The route for deleting the webhook.
+    fn delete_route(&self, webhook: &Webhook) -> String {
+        format_endpoint(endpoints::WEBHOOK, webhook.id())
+    }
+
+    /// The route for enabling or disabling the webhook.
+    fn enabled_route(&self, webhook: &Webhook) -> String {
+        format_endpoint(endpoints::WEBHOOK_ENABLED, webhook.id())
+    }
+
+    /// The route for sending the webhook a test event.
+    fn test_route(&self, webhook: &Webhook) -> String {
+        format_endpoint(endpoints::WEBHOOK_TEST, webhook.id())
+    }
+
+    /// The names of the events the webhook is subscribed to.
+    fn describe_events(&self, webhook: &Webhook) -> String {
+        if webhook.events().is_empty() {
+            return "none".to_string();
+        }
+
+        webhook
+            .events()
+            .iter()
+            .map(WebhookEvent::as_str)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Display the webhooks page.
+pub async fn get_webhooks_page(
+    State(mut state): State<SQLAppState>,
+    Extension(user_id): Extension<UserID>,
+) -> Response {
+    let webhooks = match state.webhook_store().get_by_user_id(user_id) {
+        Ok(webhooks) => webhooks,
+        Err(error) => {
+            tracing::error!("Could not get webhooks for user {user_id}: {error}");
+            return Redirect::to(endpoints::INTERNAL_ERROR).into_response();
+        }
+    };
+
+    WebhooksPageTemplate {
+        navbar: get_nav_bar(endpoints::SETTINGS, state.demo_mode),
+        webhooks: WebhooksTemplate::new(webhooks),
+    }
+    .into_response()
+}
+
+/// The form data for adding a webhook.
+#[derive(Serialize, Deserialize)]
+pub struct WebhookForm {
+    /// The URL to send payloads to.
+    pub url: String,
+    /// The secret to sign payloads with, or an empty string to generate one.
+    #[serde(default)]
+    pub secret: String,
+    /// Whether to send the webhook [WebhookEvent::TransactionCreated] events.
+    ///
+    /// This is a checkbox, so any value means yes.
+    pub transaction_created: Option<String>,
+}
+
+/// Add a webhook for the logged in user and respond with the updated list of webhooks.
+///
+/// Responds with an inline error message if the URL is invalid.
+pub async fn create_webhook(
+    State(mut state): State<SQLAppState>,
+    Extension(user_id): Extension<UserID>,
+    Form(form): Form<WebhookForm>,
+) -> Response {
+    let mut webhook_store = state.webhook_store();
+    let url = form.url.trim();
+
+    if let Err(error) = parse_webhook_url(url) {
+        let url_error = match error {
+            WebhookError::InvalidUrl(reason) => format!("Invalid URL: {reason}"),
+            error => error.to_string(),
+        };
+
+        return get_webhooks_response(&webhook_store, user_id, |template| WebhooksTemplate {
+            url,
+            url_error: &url_error,
+            ..template
+        });
+    }
+
+    let secret = match form.secret.trim() {
+        "" => Webhook::generate_secret(),
+        secret => secret.to_string(),
+    };
+    let events = if form.transaction_created.is_some() {
+        vec![WebhookEvent::TransactionCreated]
+    } else {
+        vec![]
+    };
+
+    if let Err(error) = webhook_store.create(user_id, url.to_string(), secret, events) {
+        tracing::error!("Could not create webhook for user {user_id}: {error}");
+        return get_internal_server_error_redirect();
+    }
+
+    get_webhooks_response(&webhook_store, user_id, |template| template)
+}
+
+/// The form data for enabling or disabling a webhook.
+#[derive(Serialize, Deserialize)]
+pub struct WebhookEnabledForm {
+    /// Whether the webhook should be sent events.
+    pub enabled: bool,
+}
+
+/// Enable or disable the logged in user's webhook with the ID in the path and respond with the
+/// updated list of webhooks.
+pub async fn set_webhook_enabled(
+    State(mut state): State<SQLAppState>,
+    Extension(user_id): Extension<UserID>,
+    Path(webhook_id): Path<DatabaseID>,
+    Form(form): Form<WebhookEnabledForm>,
+) -> Response {
+    let mut webhook_store = state.webhook_store();
+
+    match webhook_store.set_enabled(user_id, webhook_id, form.enabled) {
+        // The webhook may have been deleted in the meantime, e.g. in another tab, which is fine.
+        Ok(()) | Err(UserError::NotFound) => {}
+        Err(error) => {
+            tracing::error!("Could not update webhook {webhook_id}: {error}");
+            return get_internal_server_error_redirect();
+        }
+    }
+
+    get_webhooks_response(&webhook_store, user_id, |template| template)
+}
+
+/// Delete the logged in user's webhook with the ID in the path and respond with the updated list
+/// of webhooks.
+pub async fn delete_webhook(
+    State(mut state): State<SQLAppState>,
+    Extension(user_id): Extension<UserID>,
+    Path(webhook_id): Path<DatabaseID>,
+) -> Response {
+    let mut webhook_store = state.webhook_store();
+
+    match webhook_store.delete(user_id, webhook_id) {
+        // The webhook may have already been deleted, e.g. in another tab, which is fine.
+        Ok(()) | Err(UserError::NotFound) => {}
+        Err(error) => {
+            tracing::error!("Could not delete webhook {webhook_id}: {error}");
+            return get_internal_server_error_redirect();
+        }
+    }
+
+    get_webhooks_response(&webhook_store, user_id, |template| template)
+}
+
+/// Send a test event to the logged in user's webhook with the ID in the path and respond with the
+/// list of webhooks and whether the test event was delivered.
+///
+/// The test event is sent once, without retries, so that the user gets a prompt answer.
+pub async fn send_test_webhook(
+    State(mut state): State<SQLAppState>,
+    Extension(user_id): Extension<UserID>,
+    Path(webhook_id): Path<DatabaseID>,
+) -> Response {
+    let webhook_store = state.webhook_store();
+    let webhook = match webhook_store.get_by_user_id(user_id) {
+        Ok(webhooks) => webhooks
+            .into_iter()
+            .find(|webhook| webhook.id() == webhook_id),
+        Err(error) => {
+            tracing::error!("Could not get webhooks for user {user_id}: {error}");
+            return get_internal_server_error_redirect();
+        }
+    };
+    let Some(webhook) = webhook else {
+        return get_webhooks_response(&webhook_store, user_id, |template| WebhooksTemplate {
+            error_message: "The webhook could not be found.",
+            ..template
+        });
+    };
+
+    let result = match build_payload(
+        WebhookEvent::Test,
+        &json!({ "message": "This is a test event from Budgeteur." }),
+    ) {
+        Ok(body) => {
+            state
+                .webhook_dispatcher
+                .send(&webhook, WebhookEvent::Test, body)
+                .await
+        }
+        Err(error) => Err(error),
+    };
+
+    match result {
+        Ok(()) => get_webhooks_response(&webhook_store, user_id, |template| WebhooksTemplate {
+            message: "The test event was delivered.",
+            ..template
+        }),
+        Err(error) => {
+            let error_message = format!("Could not deliver the test event: {error}.");
+
+            get_webhooks_response(&webhook_store, user_id, |template| WebhooksTemplate {
+                error_message: &error_message,
+                ..template
+            })
+        }
+    }
+}
+
+/// Respond with the user's webhooks rendered by the template that `customise` returns, or a
+/// redirect to the internal server error page.
+fn get_webhooks_response<'a, W>(
+    webhook_store: &W,
+    user_id: UserID,
+    customise: impl FnOnce(WebhooksTemplate<'a>) -> WebhooksTemplate<'a>,
+) -> Response
+where
+    W: WebhookStore,
+{
+    match webhook_store.get_by_user_id(user_id) {
+        Ok(webhooks) => customise(WebhooksTemplate::new(webhooks)).into_response(),
+        Err(error) => {
+            tracing::error!("Could not get webhooks for user {user_id}: {error}");
+            get_internal_server_error_redirect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod webhooks_route_tests {
+    use std::time::Duration;
+
+    use axum_test::TestServer;
+    use email_address::EmailAddress;
+    use rusqlite::Connection;
+    use serde_json::Value;
+    use time::OffsetDateTime;
+
+    use crate::{
+        auth::log_in::LogInData,
+        build_router,
+        models::{PasswordHash, User, ValidatedPassword, WebhookEvent},
+        routes::{
+            api::TransactionData,
+            endpoints::{self, format_endpoint},
+            transaction::TransactionForm,
+        },
+        stores::{
+            sql_store::{create_app_state, SQLAppState},
+            UserStore, WebhookStore,
+        },
+        webhooks::{mock_server::MockServer, sign_payload, EVENT_HEADER, SIGNATURE_HEADER},
+    };
+
+    use super::{WebhookEnabledForm, WebhookForm};
+
+    const PASSWORD: &str = "averysafeandsecurepassword";
+
+    async fn get_test_state_server_and_user() -> (SQLAppState, TestServer, User) {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        state.password_hash_cost = 4;
+        state.webhook_dispatcher.initial_backoff = Duration::from_millis(1);
+        state.webhook_dispatcher.allow_local_receivers();
+        let user = state
+            .user_store()
+            .create(
+                EmailAddress::new_unchecked("foo@bar.baz"),
+                PasswordHash::new(ValidatedPassword::new_unchecked(PASSWORD), 4).unwrap(),
+            )
+            .unwrap();
+
+        let mut server =
+            TestServer::new(build_router(state.clone())).expect("Could not create test server.");
+        server.do_save_cookies();
+        server
+            .post(endpoints::LOG_IN)
+            .form(&LogInData {
+                email: user.email().to_string(),
+                password: PASSWORD.to_string(),
+                remember_me: None,
+            })
+            .await
+            .assert_status_see_other();
+
+        (state, server, user)
+    }
+
+    fn get_webhook_form(url: &str) -> WebhookForm {
+        WebhookForm {
+            url: url.to_string(),
+            secret: "secret".to_string(),
+            transaction_created: Some("on".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn get_webhooks_page() {
+        let (_, server, _) = get_test_state_server_and_user().await;
+
+        let response = server.get(endpoints::WEBHOOKS).await;
+
+        response.assert_status_ok();
+        response.assert_text_contains("You have no webhooks.");
+    }
+
+    #[tokio::test]
+    async fn create_webhook() {
+        let (mut state, server, user) = get_test_state_server_and_user().await;
+
+        let response = server
+            .post(endpoints::WEBHOOKS)
+            .form(&get_webhook_form("https://ntfy.sh/budget"))
+            .await;
+
+        response.assert_status_ok();
+        response.assert_text_contains("https://ntfy.sh/budget");
+        let webhooks = state.webhook_store().get_by_user_id(user.id()).unwrap();
+        assert_eq!(webhooks.len(), 1);
+        assert_eq!(webhooks[0].secret(), "secret");
+        assert_eq!(webhooks[0].events(), &[WebhookEvent::TransactionCreated]);
+        assert!(webhooks[0].enabled());
+    }
+
+    #[tokio::test]
+    async fn create_webhook_generates_secret() {
+        let (mut state, server, user) = get_test_state_server_and_user().await;
+
+        server
+            .post(endpoints::WEBHOOKS)
+            .form(&WebhookForm {
+                secret: "".to_string(),
+                transaction_created: None,
+                ..get_webhook_form("https://ntfy.sh/budget")
+            })
+            .await
+            .assert_status_ok();
+
+        let webhooks = state.webhook_store().get_by_user_id(user.id()).unwrap();
+        assert_eq!(webhooks.len(), 1);
+        assert!(!webhooks[0].secret().is_empty());
+        assert_eq!(webhooks[0].events(), &[]);
+    }
+
+    #[tokio::test]
+    async fn create_webhook_fails_with_invalid_url() {
+        let (mut state, server, user) = get_test_state_server_and_user().await;
+
+        let response = server
+            .post(endpoints::WEBHOOKS)
+            .form(&get_webhook_form("http://localhost:8080/hook"))
+            .await;
+
+        response.assert_status_ok();
+        response.assert_text_contains(
+            "Invalid URL: webhooks cannot be sent to loopback or link-local addresses",
+        );
+        assert_eq!(
+            state.webhook_store().get_by_user_id(user.id()).unwrap(),
+            vec![]
+        );
+    }
+
+    #[tokio::test]
+    async fn disable_and_delete_webhook() {
+        let (mut state, server, user) = get_test_state_server_and_user().await;
+        let webhook = state
+            .webhook_store()
+            .create(
+                user.id(),
+                "http://localhost/hook".to_string(),
+                "secret".to_string(),
+                vec![WebhookEvent::TransactionCreated],
+            )
+            .unwrap();
+
+        server
+            .post(&format_endpoint(endpoints::WEBHOOK_ENABLED, webhook.id()))
+            .form(&WebhookEnabledForm { enabled: false })
+            .await
+            .assert_text_contains("Disabled");
+        assert!(!state.webhook_store().get_by_user_id(user.id()).unwrap()[0].enabled());
+
+        server
+            .delete(&format_endpoint(endpoints::WEBHOOK, webhook.id()))
+            .await
+            .assert_text_contains("You have no webhooks.");
+        assert_eq!(
+            state.webhook_store().get_by_user_id(user.id()).unwrap(),
+            vec![]
+        );
+    }
+
+    #[tokio::test]
+    async fn send_test_event() {
+        let (mut state, server, user) = get_test_state_server_and_user().await;
+        let receiver = MockServer::start(vec![]).await;
+        let webhook = state
+            .webhook_store()
+            .create(
+                user.id(),
+                receiver.url.clone(),
+                "secret".to_string(),
+                vec![],
+            )
+            .unwrap();
+
+        let response = server
+            .post(&format_endpoint(endpoints::WEBHOOK_TEST, webhook.id()))
+            .await;
+
+        response.assert_status_ok();
+        response.assert_text_contains("The test event was delivered.");
+        let received = receiver.received();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].headers[EVENT_HEADER], "test");
+        assert_eq!(
+            received[0].headers[SIGNATURE_HEADER],
+            sign_payload(b"secret", &received[0].body).as_str()
+        );
+    }
+
+    #[tokio::test]
+    async fn send_test_event_reports_failure() {
+        let (mut state, server, user) = get_test_state_server_and_user().await;
+        let webhook = state
+            .webhook_store()
+            .create(
+                user.id(),
+                "http://127.0.0.1:9/hook".to_string(),
+                "secret".to_string(),
+                vec![],
+            )
+            .unwrap();
+
+        let response = server
+            .post(&format_endpoint(endpoints::WEBHOOK_TEST, webhook.id()))
+            .await;
+
+        response.assert_status_ok();
+        response.assert_text_contains("Could not deliver the test event");
+    }
+
+    #[tokio::test]
+    async fn creating_transaction_notifies_subscribed_webhooks() {
+        let (mut state, server, user) = get_test_state_server_and_user().await;
+        let subscribed = MockServer::start(vec![]).await;
+        let unsubscribed = MockServer::start(vec![]).await;
+        let disabled = MockServer::start(vec![]).await;
+        for (receiver, events) in [
+            (&subscribed, vec![WebhookEvent::TransactionCreated]),
+            (&unsubscribed, vec![]),
+            (&disabled, vec![WebhookEvent::TransactionCreated]),
+        ] {
+            state
+                .webhook_store()
+                .create(
+                    user.id(),
+                    receiver.url.clone(),
+                    "secret".to_string(),
+                    events,
+                )
+                .unwrap();
+        }
+        let disabled_id = state.webhook_store().get_by_user_id(user.id()).unwrap()[2].id();
+        state
+            .webhook_store()
+            .set_enabled(user.id(), disabled_id, false)
+            .unwrap();
+        let date = OffsetDateTime::now_utc().date();
+
+        server
+            .post(&format_endpoint(
+                endpoints::USER_TRANSACTIONS,
+                user.id().as_i64(),
+            ))
+            .form(&TransactionForm {
+                amount: "12.3".to_owned(),
+                date: date.to_string(),
+                description: "From the form".to_string(),
+                category_id: 0,
+                category_name: String::new(),
+                confirmed: false,
+            })
+            .await
+            .assert_status_ok();
+        server
+            .post(endpoints::API_TRANSACTIONS)
+            .json(&TransactionData {
+                amount: 45.6,
+                date,
+                description: "From the API".to_string(),
+                category_id: None,
+            })
+            .await
+            .assert_status(axum::http::StatusCode::CREATED);
+
+        let received = subscribed.wait_for(2).await;
+        assert_eq!(received.len(), 2);
+        let mut descriptions = received
+            .iter()
+            .map(|request| {
+                assert_eq!(request.headers[EVENT_HEADER], "transaction.created");
+                let payload: Value = serde_json::from_slice(&request.body).unwrap();
+                assert_eq!(payload["event"], "transaction.created");
+                payload["data"]["description"].as_str().unwrap().to_string()
+            })
+            .collect::<Vec<_>>();
+        descriptions.sort();
+        assert_eq!(descriptions, vec!["From the API", "From the form"]);
+        assert_eq!(unsubscribed.received().len(), 0);
+        assert_eq!(disabled.received().len(), 0);
+    }
+}
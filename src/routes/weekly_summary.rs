@@ -0,0 +1,273 @@
+//! This file defines the printable weekly summary page.
+
+use std::collections::HashMap;
+
+use askama_axum::Template;
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use serde::Deserialize;
+use time::Date;
+
+use crate::{
+    aggregation::{largest, named_totals_by_category, totals, week_range, CategoryTotal, Totals},
+    filters,
+    models::{Transaction, UserID},
+    stores::{transaction::TransactionQuery, CategoryStore, TransactionStore, UserStore},
+    timezone::{get_preferences_today, get_user_preferences},
+    AppError, AppState,
+};
+
+/// The number of transactions to list in the weekly summary.
+const LARGEST_TRANSACTION_COUNT: usize = 5;
+
+/// Renders the weekly summary page without the navbar so that it prints cleanly.
+#[derive(Template)]
+#[template(path = "views/weekly_summary.html")]
+struct WeeklySummaryTemplate {
+    /// The first day of the summary (inclusive).
+    start: Date,
+    /// The last day of the summary (inclusive).
+    end: Date,
+    /// The money in and out over the week.
+    totals: Totals,
+    /// The totals for each category, starting with the category with the most spent.
+    category_totals: Vec<CategoryTotal>,
+    /// The transactions with the largest amounts.
+    largest_transactions: Vec<Transaction>,
+}
+
+/// The query parameters for the weekly summary page.
+#[derive(Debug, Default, Deserialize)]
+pub struct WeeklySummaryQuery {
+    /// A day in the week to summarise. Defaults to today.
+    pub date: Option<Date>,
+}
+
+/// Display a printable summary of the week containing `date`.
+///
+/// Weeks start on the user's chosen week start day.
+pub async fn get_weekly_summary_page<C, T, U>(
+    State(mut state): State<AppState<C, T, U>>,
+    Extension(user_id): Extension<UserID>,
+    Query(query): Query<WeeklySummaryQuery>,
+) -> Response
+where
+    C: CategoryStore + Send + Sync,
+    T: TransactionStore + Send + Sync,
+    U: UserStore + Send + Sync,
+{
+    let preferences = get_user_preferences(state.user_store(), user_id);
+    let date = query
+        .date
+        .unwrap_or_else(|| get_preferences_today(&preferences));
+    let date_range = week_range(date, preferences.week_start);
+
+    let category_names: HashMap<_, _> = match state.category_store().get_by_user(user_id) {
+        Ok(categories) => categories
+            .into_iter()
+            .map(|category| (category.id(), category.name().as_ref().to_string()))
+            .collect(),
+        Err(error) => return AppError::CategoryError(error).into_response(),
+    };
+
+    let transactions = match state.transaction_store().get_query(TransactionQuery {
+        user_id: Some(user_id),
+        date_range: Some(date_range.clone()),
+        ..Default::default()
+    }) {
+        Ok(transactions) => transactions,
+        Err(error) => return AppError::TransactionError(error).into_response(),
+    };
+
+    let category_totals = named_totals_by_category(&transactions, &category_names);
+
+    WeeklySummaryTemplate {
+        start: *date_range.start(),
+        end: *date_range.end(),
+        totals: totals(&transactions),
+        category_totals,
+        largest_transactions: largest(&transactions, LARGEST_TRANSACTION_COUNT),
+    }
+    .into_response()
+}
+
+#[cfg(test)]
+mod weekly_summary_route_tests {
+    use axum::{
+        body::to_bytes,
+        extract::{Query, State},
+        http::StatusCode,
+        response::Response,
+        Extension,
+    };
+    use rusqlite::Connection;
+    use time::{macros::date, OffsetDateTime};
+
+    use crate::{
+        aggregation::week_range,
+        models::{CategoryName, PasswordHash, Transaction, User, ValidatedPassword, WeekStart},
+        stores::{
+            sql_store::{create_app_state, SQLAppState},
+            CategoryStore, TransactionStore, UserStore,
+        },
+    };
+
+    use super::{get_weekly_summary_page, WeeklySummaryQuery};
+
+    fn get_test_state_and_user() -> (SQLAppState, User) {
+        let mut state = create_app_state(Connection::open_in_memory().unwrap(), "42").unwrap();
+        let user = state
+            .user_store()
+            .create(
+                "test@test.com".parse().unwrap(),
+                PasswordHash::new(ValidatedPassword::new_unchecked("test"), 4).unwrap(),
+            )
+            .unwrap();
+
+        (state, user)
+    }
+
+    async fn get_body_text(response: Response) -> String {
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        String::from_utf8_lossy(&body).to_string()
+    }
+
+    #[tokio::test]
+    async fn weekly_summary_displays_correct_numbers() {
+        let (mut state, user) = get_test_state_and_user();
+        let food = state
+            .category_store()
+            .create(CategoryName::new_unchecked("Food"), user.id())
+            .unwrap();
+
+        let builders = [
+            Transaction::build(1000.0, user.id())
+                .description("Salary".to_string())
+                .date(date!(2024 - 03 - 04))
+                .unwrap(),
+            Transaction::build(-50.0, user.id())
+                .description("Groceries".to_string())
+                .category(Some(food.id()))
+                .date(date!(2024 - 03 - 05))
+                .unwrap(),
+            Transaction::build(-20.0, user.id())
+                .description("Lunch".to_string())
+                .category(Some(food.id()))
+                .date(date!(2024 - 03 - 06))
+                .unwrap(),
+            Transaction::build(-100.0, user.id())
+                .description("Power bill".to_string())
+                .date(date!(2024 - 03 - 10))
+                .unwrap(),
+            // Transactions outside of the week should not be included.
+            Transaction::build(-500.0, user.id())
+                .description("Last week".to_string())
+                .date(date!(2024 - 03 - 03))
+                .unwrap(),
+            // Neither should ignored transactions.
+            Transaction::build(-999.0, user.id())
+                .description("Transfer".to_string())
+                .date(date!(2024 - 03 - 08))
+                .unwrap()
+                .ignored(true),
+        ];
+
+        for builder in builders {
+            state
+                .transaction_store()
+                .create_from_builder(builder)
+                .unwrap();
+        }
+
+        let response = get_weekly_summary_page(
+            State(state),
+            Extension(user.id()),
+            Query(WeeklySummaryQuery {
+                date: Some(date!(2024 - 03 - 10)),
+            }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let text = get_body_text(response).await;
+
+        assert!(text.contains("Summary for 2024-03-04 to 2024-03-10"));
+        assert!(text.contains("$1,000.00"));
+        assert!(text.contains("-$170.00"));
+        assert!(text.contains("$830.00"));
+        assert!(text.contains("-$70.00"));
+        assert!(text.contains("$900.00"));
+        assert!(!text.contains("Last week"));
+        assert!(!text.contains("Transfer"));
+
+        let food_position = text.find("Food").unwrap();
+        let uncategorised_position = text.find("Uncategorised").unwrap();
+        assert!(
+            food_position < uncategorised_position,
+            "categories should be sorted by most spent"
+        );
+
+        let positions: Vec<_> = ["Salary", "Power bill", "Groceries", "Lunch"]
+            .iter()
+            .map(|description| text.find(description).unwrap())
+            .collect();
+        assert!(
+            positions.is_sorted(),
+            "transactions should be sorted by size, got positions {positions:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn weekly_summary_defaults_to_today() {
+        let (state, user) = get_test_state_and_user();
+        let date_range = week_range(OffsetDateTime::now_utc().date(), WeekStart::Monday);
+
+        let response = get_weekly_summary_page(
+            State(state),
+            Extension(user.id()),
+            Query(WeeklySummaryQuery::default()),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let text = get_body_text(response).await;
+
+        assert!(text.contains(&format!(
+            "Summary for {} to {}",
+            date_range.start(),
+            date_range.end()
+        )));
+        assert!(text.contains("No transactions this week."));
+    }
+
+    #[tokio::test]
+    async fn weekly_summary_follows_week_start() {
+        let (mut state, user) = get_test_state_and_user();
+        state
+            .user_store()
+            .set_date_periods(user.id(), WeekStart::Sunday, 1)
+            .unwrap();
+
+        let response = get_weekly_summary_page(
+            State(state),
+            Extension(user.id()),
+            // A Wednesday.
+            Query(WeeklySummaryQuery {
+                date: Some(date!(2024 - 03 - 06)),
+            }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let text = get_body_text(response).await;
+
+        assert!(text.contains("Summary for 2024-03-03 to 2024-03-09"));
+    }
+}
@@ -0,0 +1,326 @@
+//! Backs up the database to a directory on a schedule, keeping only the most recent backups.
+//!
+//! Scheduled backups are off unless a [BackupSchedule] is given to [run_scheduled_backups]. A
+//! failed backup is logged and tried again at the next interval.
+
+use std::{
+    fs,
+    future::Future,
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use thiserror::Error;
+use time::OffsetDateTime;
+use tokio::time::MissedTickBehavior;
+
+use crate::{
+    db::{get_backup_file_name, DatabaseError},
+    stores::SQLiteUserStore,
+};
+
+/// The default number of scheduled backups to keep.
+pub const DEFAULT_BACKUP_RETENTION: usize = 7;
+
+/// The default time between scheduled backups.
+pub const DEFAULT_BACKUP_INTERVAL: std::time::Duration =
+    std::time::Duration::from_secs(24 * 60 * 60);
+
+/// The number of intervals without a successful backup after which the last backup is stale.
+const STALE_AFTER_INTERVALS: u32 = 2;
+
+/// When and where to write scheduled backups.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupSchedule {
+    /// The directory to write the backups to. It is created if it does not exist.
+    pub directory: PathBuf,
+    /// The time between backups.
+    pub interval: std::time::Duration,
+    /// The number of backups to keep. Older backups are deleted after each backup.
+    pub retention: usize,
+}
+
+/// Errors that can occur while writing a scheduled backup.
+#[derive(Debug, Error)]
+pub enum ScheduledBackupError {
+    /// The database could not be copied.
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+
+    /// The backup directory could not be created or old backups could not be deleted.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// The time of the last successful scheduled backup.
+///
+/// Clones share the same status, so the backup task can update it while the settings page reads
+/// it.
+#[derive(Debug, Clone, Default)]
+pub struct BackupStatus {
+    /// The time between backups, or `None` if scheduled backups are off.
+    interval: Option<std::time::Duration>,
+    last_success: Arc<Mutex<Option<OffsetDateTime>>>,
+}
+
+impl BackupStatus {
+    /// Create the status for backups taken every `interval`.
+    pub fn new(interval: std::time::Duration) -> Self {
+        Self {
+            interval: Some(interval),
+            last_success: Arc::default(),
+        }
+    }
+
+    /// Whether scheduled backups are on.
+    pub fn is_enabled(&self) -> bool {
+        self.interval.is_some()
+    }
+
+    /// The time of the last successful backup, or `None` if there has not been one yet.
+    pub fn last_success(&self) -> Option<OffsetDateTime> {
+        *self
+            .last_success
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Record that a backup finished successfully at `time`.
+    pub fn record_success(&self, time: OffsetDateTime) {
+        *self
+            .last_success
+            .lock()
+            .unwrap_or_else(|error| error.into_inner()) = Some(time);
+    }
+
+    /// Whether scheduled backups are on but none has succeeded for a couple of intervals, or at
+    /// all.
+    pub fn is_stale(&self, now: OffsetDateTime) -> bool {
+        let Some(interval) = self.interval else {
+            return false;
+        };
+
+        match self.last_success() {
+            Some(last_success) => now - last_success > interval * STALE_AFTER_INTERVALS,
+            None => true,
+        }
+    }
+}
+
+/// Back up the database every `schedule.interval` until `shutdown` completes, starting
+/// immediately.
+///
+/// Each successful backup is recorded in `status`. A backup that is in progress when `shutdown`
+/// completes is allowed to finish, so that no partial backups are left behind.
+pub async fn run_scheduled_backups(
+    user_store: SQLiteUserStore,
+    schedule: BackupSchedule,
+    status: BackupStatus,
+    shutdown: impl Future<Output = ()>,
+) {
+    let mut interval = tokio::time::interval(schedule.interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    tokio::pin!(shutdown);
+
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            _ = interval.tick() => {}
+        }
+
+        let now = OffsetDateTime::now_utc();
+        let user_store = user_store.clone();
+        let schedule = schedule.clone();
+        let result =
+            tokio::task::spawn_blocking(move || back_up(&user_store, &schedule, now)).await;
+
+        match result {
+            Ok(Ok(path)) => {
+                tracing::info!("Backed up the database to {}", path.display());
+                status.record_success(now);
+            }
+            Ok(Err(error)) => tracing::error!("Could not back up the database: {error}"),
+            Err(error) => tracing::error!("The database backup task failed: {error}"),
+        }
+    }
+
+    tracing::debug!("Stopped scheduled backups");
+}
+
+/// Write a backup taken at `now` to the schedule's directory and delete the oldest backups
+/// beyond the retention count.
+///
+/// Returns the path of the new backup.
+fn back_up(
+    user_store: &SQLiteUserStore,
+    schedule: &BackupSchedule,
+    now: OffsetDateTime,
+) -> Result<PathBuf, ScheduledBackupError> {
+    fs::create_dir_all(&schedule.directory)?;
+    let path = schedule.directory.join(get_backup_file_name(now));
+    user_store.backup(&path)?;
+    prune_backups(&schedule.directory, schedule.retention)?;
+
+    Ok(path)
+}
+
+/// Delete all but the newest `retention` backups in `directory`.
+///
+/// Only files named like [get_backup_file_name] are considered, so other files in the directory
+/// are left alone.
+fn prune_backups(directory: &Path, retention: usize) -> Result<(), io::Error> {
+    let mut file_names = Vec::new();
+
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+
+        if let Some(file_name) = entry.file_name().to_str() {
+            file_names.push(file_name.to_owned());
+        }
+    }
+
+    for file_name in get_backups_to_prune(file_names, retention) {
+        fs::remove_file(directory.join(&file_name))?;
+        tracing::debug!("Deleted the old backup {file_name}");
+    }
+
+    Ok(())
+}
+
+/// Pick the backups in `file_names` that are older than the newest `retention` backups.
+///
+/// Backup file names contain the UTC time they were taken in a fixed-width format, so sorting
+/// them by name sorts them by age.
+fn get_backups_to_prune(file_names: Vec<String>, retention: usize) -> Vec<String> {
+    let mut backups: Vec<String> = file_names
+        .into_iter()
+        .filter(|file_name| file_name.starts_with("budgeteur-") && file_name.ends_with(".sqlite"))
+        .collect();
+    backups.sort();
+
+    let prune_count = backups.len().saturating_sub(retention);
+    backups.truncate(prune_count);
+
+    backups
+}
+
+/// Describe how long ago something happened, e.g. "3 hours ago".
+pub fn format_time_ago(elapsed: time::Duration) -> String {
+    let (count, unit) = if elapsed < time::Duration::minutes(1) {
+        return "just now".to_owned();
+    } else if elapsed < time::Duration::hours(1) {
+        (elapsed.whole_minutes(), "minute")
+    } else if elapsed < time::Duration::days(1) {
+        (elapsed.whole_hours(), "hour")
+    } else {
+        (elapsed.whole_days(), "day")
+    };
+
+    if count == 1 {
+        format!("1 {unit} ago")
+    } else {
+        format!("{count} {unit}s ago")
+    }
+}
+
+#[cfg(test)]
+mod scheduled_backups_tests {
+    use std::{
+        env, fs,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use rusqlite::Connection;
+    use time::{macros::datetime, OffsetDateTime};
+    use tokio::sync::oneshot;
+
+    use crate::{db::initialize, stores::SQLiteUserStore};
+
+    use super::{
+        format_time_ago, get_backups_to_prune, run_scheduled_backups, BackupSchedule, BackupStatus,
+    };
+
+    #[test]
+    fn prunes_oldest_backups_beyond_retention() {
+        let file_names = vec![
+            "budgeteur-2024-01-03T000000Z.sqlite".to_owned(),
+            "notes.txt".to_owned(),
+            "budgeteur-2024-01-01T000000Z.sqlite".to_owned(),
+            "budgeteur-2024-01-02T000000Z.sqlite".to_owned(),
+        ];
+
+        assert_eq!(
+            get_backups_to_prune(file_names.clone(), 2),
+            vec!["budgeteur-2024-01-01T000000Z.sqlite".to_owned()]
+        );
+        assert!(get_backups_to_prune(file_names, 3).is_empty());
+    }
+
+    #[test]
+    fn backup_status_is_stale_after_missed_backups() {
+        let now = datetime!(2024-01-31 12:00 UTC);
+        let status = BackupStatus::new(Duration::from_secs(60 * 60));
+        assert!(status.is_stale(now));
+
+        status.record_success(now - time::Duration::hours(2));
+        assert!(!status.is_stale(now));
+
+        status.record_success(now - time::Duration::hours(3));
+        assert!(status.is_stale(now));
+        assert!(!BackupStatus::default().is_stale(now));
+    }
+
+    #[test]
+    fn formats_time_ago() {
+        assert_eq!(format_time_ago(time::Duration::seconds(30)), "just now");
+        assert_eq!(format_time_ago(time::Duration::minutes(1)), "1 minute ago");
+        assert_eq!(
+            format_time_ago(time::Duration::minutes(59)),
+            "59 minutes ago"
+        );
+        assert_eq!(format_time_ago(time::Duration::hours(3)), "3 hours ago");
+        assert_eq!(format_time_ago(time::Duration::days(2)), "2 days ago");
+    }
+
+    #[tokio::test]
+    async fn writes_backups_until_shut_down() {
+        let connection = Connection::open_in_memory().unwrap();
+        initialize(&connection).unwrap();
+        let user_store = SQLiteUserStore::new(Arc::new(Mutex::new(connection)));
+        let directory = env::temp_dir().join(format!(
+            "budgeteur_scheduled_backup_test_{}",
+            rand::random::<u64>()
+        ));
+        let schedule = BackupSchedule {
+            directory: directory.clone(),
+            interval: Duration::from_millis(10),
+            retention: 1,
+        };
+        let status = BackupStatus::new(schedule.interval);
+        let (stop, stopped) = oneshot::channel::<()>();
+
+        let task = tokio::spawn(run_scheduled_backups(
+            user_store,
+            schedule,
+            status.clone(),
+            async {
+                let _ = stopped.await;
+            },
+        ));
+        while status.last_success().is_none() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        stop.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), task)
+            .await
+            .expect("the backup task should stop when shut down")
+            .unwrap();
+
+        let backups: Vec<_> = fs::read_dir(&directory).unwrap().collect();
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(backups.len(), 1);
+        assert!(status.last_success() <= Some(OffsetDateTime::now_utc()));
+    }
+}
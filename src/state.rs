@@ -1,6 +1,10 @@
 //! Implements a struct that holds the state of the REST server.
 
-use std::marker::{Send, Sync};
+use std::{
+    fmt::Display,
+    marker::{Send, Sync},
+    str::FromStr,
+};
 
 use axum::{
     async_trait,
@@ -8,14 +12,67 @@ use axum::{
     http::request::Parts,
 };
 use axum_extra::extract::cookie::Key;
+use serde::Serialize;
 use sha2::{Digest, Sha512};
 use time::Duration;
 
 use crate::{
-    auth::{cookie::COOKIE_DURATION, AuthError},
+    auth::{
+        cookie::{COOKIE_DURATION, REMEMBER_ME_COOKIE_DURATION},
+        rate_limit::LogInRateLimiter,
+        AuthError,
+    },
+    models::{PasswordHash, UserID, WebhookEvent},
+    operations::OperationTracker,
+    pagination::PaginationConfig,
+    proxy::ProxySettings,
+    routes::{
+        DEFAULT_MAX_REQUEST_BODY_SIZE, DEFAULT_REQUEST_TIMEOUT, DEFAULT_SUSPICIOUS_TRANSACTION_AGE,
+    },
+    scheduled_backups::BackupStatus,
     stores::{CategoryStore, TransactionStore, UserStore},
+    webhooks::WebhookDispatcher,
 };
 
+/// Controls who can create an account.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RegistrationMode {
+    /// Anyone can register.
+    #[default]
+    Open,
+    /// Nobody can register.
+    Closed,
+    /// Only people with an unused invite code can register.
+    InviteOnly,
+}
+
+impl FromStr for RegistrationMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(Self::Open),
+            "closed" => Ok(Self::Closed),
+            "invite-only" => Ok(Self::InviteOnly),
+            _ => Err(format!(
+                "invalid registration mode '{s}', expected one of 'open', 'closed' or 'invite-only'"
+            )),
+        }
+    }
+}
+
+impl Display for RegistrationMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mode = match self {
+            Self::Open => "open",
+            Self::Closed => "closed",
+            Self::InviteOnly => "invite-only",
+        };
+
+        write!(f, "{mode}")
+    }
+}
+
 /// The state of the REST server.
 #[derive(Debug, Clone)]
 pub struct AppState<C, T, U>
@@ -27,7 +84,40 @@ where
     /// The secret used to encrypt auth cookies.
     cookie_key: Key,
     /// The duration for which cookies used for authentication are valid.
+    ///
+    /// Each authenticated request extends the cookie so that it is valid for at least this long.
     pub cookie_duration: Duration,
+    /// The duration for which cookies used for authentication are valid when the user selects
+    /// "remember me" at log-in.
+    pub remember_me_cookie_duration: Duration,
+    /// Blocks log-in attempts after too many failures.
+    pub log_in_rate_limiter: LogInRateLimiter,
+    /// Controls who can create an account.
+    pub registration_mode: RegistrationMode,
+    /// The bcrypt cost used when hashing passwords.
+    ///
+    /// Passwords hashed with a lower cost are rehashed with this cost when the user next logs in.
+    pub password_hash_cost: u32,
+    /// Delivers events to users' webhooks.
+    pub webhook_dispatcher: WebhookDispatcher,
+    /// How long the server spends on a request before responding with a 503 error.
+    pub request_timeout: std::time::Duration,
+    /// The maximum size of a request body in bytes. Larger requests get a 413 error.
+    pub max_request_body_size: usize,
+    /// The long-running operations in progress, which shutdown waits for.
+    pub operations: OperationTracker,
+    /// How clients reach the server, e.g. through a reverse proxy.
+    pub proxy_settings: ProxySettings,
+    /// The limits on how many items a page of, e.g., transactions can show.
+    pub pagination: PaginationConfig,
+    /// The time of the last scheduled backup, shown on the settings page.
+    pub backup_status: BackupStatus,
+    /// New transactions dated further in the past than this must be confirmed before they are
+    /// created, in case the date is a typo. `None` turns off the confirmation.
+    pub suspicious_transaction_age: Option<Duration>,
+    /// Whether the server is running the demo, which shows a banner on every page and stops
+    /// visitors from changing the demo user's password or deleting their account.
+    pub demo_mode: bool,
     category_store: C,
     transaction_store: T,
     user_store: U,
@@ -51,6 +141,19 @@ where
         Self {
             cookie_key: Key::from(&hash),
             cookie_duration: COOKIE_DURATION,
+            remember_me_cookie_duration: REMEMBER_ME_COOKIE_DURATION,
+            log_in_rate_limiter: LogInRateLimiter::default(),
+            registration_mode: RegistrationMode::default(),
+            password_hash_cost: PasswordHash::DEFAULT_COST,
+            webhook_dispatcher: WebhookDispatcher::default(),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            max_request_body_size: DEFAULT_MAX_REQUEST_BODY_SIZE,
+            operations: OperationTracker::default(),
+            proxy_settings: ProxySettings::default(),
+            pagination: PaginationConfig::default(),
+            backup_status: BackupStatus::default(),
+            suspicious_transaction_age: Some(DEFAULT_SUSPICIOUS_TRANSACTION_AGE),
+            demo_mode: false,
             category_store,
             transaction_store,
             user_store,
@@ -76,6 +179,11 @@ where
     pub fn user_store(&mut self) -> &mut U {
         &mut self.user_store
     }
+
+    /// Send `event` with `data` to the webhooks of the user `user_id` in the background.
+    pub fn dispatch_webhooks(&self, user_id: UserID, event: WebhookEvent, data: &impl Serialize) {
+        self.webhook_dispatcher.dispatch(user_id, event, data);
+    }
 }
 
 // this impl tells `PrivateCookieJar` how to access the key from our state
@@ -105,3 +213,24 @@ where
         Ok(Self::from_ref(state))
     }
 }
+
+#[cfg(test)]
+mod registration_mode_tests {
+    use super::RegistrationMode;
+
+    #[test]
+    fn parses_display_output() {
+        for mode in [
+            RegistrationMode::Open,
+            RegistrationMode::Closed,
+            RegistrationMode::InviteOnly,
+        ] {
+            assert_eq!(mode.to_string().parse(), Ok(mode));
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_mode() {
+        assert!("invite".parse::<RegistrationMode>().is_err());
+    }
+}
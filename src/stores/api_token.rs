@@ -0,0 +1,332 @@
+//! Defines the API token store trait and an implementation for the SQLite backend.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use rusqlite::{Connection, Row};
+use time::OffsetDateTime;
+
+use crate::models::{ApiToken, DatabaseID, UserID};
+
+use super::UserError;
+
+/// Handles the creation, retrieval and revocation of the [ApiToken]s that scripts use to access
+/// the JSON API.
+pub trait ApiTokenStore {
+    /// Create an API token for the user `user_id` from the hash of its plaintext token.
+    fn create(
+        &mut self,
+        user_id: UserID,
+        name: String,
+        token_hash: String,
+        expires_at: Option<OffsetDateTime>,
+    ) -> Result<ApiToken, UserError>;
+
+    /// Get all of the API tokens for the user `user_id`, oldest first.
+    fn get_by_user_id(&self, user_id: UserID) -> Result<Vec<ApiToken>, UserError>;
+
+    /// Get the API token with the hash `token_hash`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::NotFound] if there is no API token with the specified hash, e.g.
+    /// because it was revoked.
+    fn get_by_hash(&self, token_hash: &str) -> Result<ApiToken, UserError>;
+
+    /// Record that the API token `id` was used to authenticate a request at `last_used_at`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::NotFound] if there is no API token with the specified `id`.
+    fn set_last_used(
+        &mut self,
+        id: DatabaseID,
+        last_used_at: OffsetDateTime,
+    ) -> Result<(), UserError>;
+
+    /// Revoke the API token `id` belonging to the user `user_id`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::NotFound] if the user has no API token with the specified `id`.
+    fn delete(&mut self, user_id: UserID, id: DatabaseID) -> Result<(), UserError>;
+}
+
+/// Handles the creation, retrieval and revocation of API tokens in a SQLite database.
+#[derive(Debug, Clone)]
+pub struct SQLiteApiTokenStore {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SQLiteApiTokenStore {
+    /// Create a new API token store.
+    pub fn new(connection: Arc<Mutex<Connection>>) -> Self {
+        Self { connection }
+    }
+
+    /// Get exclusive access to the database connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::DatabaseLockError] if another thread panicked while holding the
+    /// database lock.
+    fn connection(&self) -> Result<MutexGuard<'_, Connection>, UserError> {
+        self.connection
+            .lock()
+            .map_err(|_| UserError::DatabaseLockError)
+    }
+}
+
+impl ApiTokenStore for SQLiteApiTokenStore {
+    /// Create and insert a new API token into the database.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::SqlError] if an SQL related error occurred, e.g. `user_id` does not
+    /// refer to a valid user.
+    fn create(
+        &mut self,
+        user_id: UserID,
+        name: String,
+        token_hash: String,
+        expires_at: Option<OffsetDateTime>,
+    ) -> Result<ApiToken, UserError> {
+        let created_at = OffsetDateTime::now_utc();
+        let connection = self.connection()?;
+
+        connection.execute(
+            "INSERT INTO api_token (user_id, name, token_hash, created_at, expires_at) \
+            VALUES (?1, ?2, ?3, ?4, ?5)",
+            (user_id.as_i64(), &name, &token_hash, created_at, expires_at),
+        )?;
+
+        Ok(ApiToken::new(
+            connection.last_insert_rowid(),
+            user_id,
+            name,
+            created_at,
+            None,
+            expires_at,
+        ))
+    }
+
+    /// Get all of the API tokens for the user `user_id`, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::SqlError] if an SQL related error occurred.
+    fn get_by_user_id(&self, user_id: UserID) -> Result<Vec<ApiToken>, UserError> {
+        self.connection()?
+            .prepare(
+                "SELECT id, user_id, name, created_at, last_used_at, expires_at FROM api_token \
+                WHERE user_id = ?1 ORDER BY created_at ASC, id ASC",
+            )?
+            .query_map((user_id.as_i64(),), map_api_token_row)?
+            .map(|maybe_token| maybe_token.map_err(UserError::from))
+            .collect()
+    }
+
+    /// Get the API token with the hash `token_hash`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::NotFound] error if there is no API token with the specified hash or [UserError::SqlError] if there are SQL related errors.
+    fn get_by_hash(&self, token_hash: &str) -> Result<ApiToken, UserError> {
+        self.connection()?
+            .prepare(
+                "SELECT id, user_id, name, created_at, last_used_at, expires_at FROM api_token \
+                WHERE token_hash = ?1",
+            )?
+            .query_row((token_hash,), map_api_token_row)
+            .map_err(UserError::from)
+    }
+
+    /// Set when the API token `id` was last used.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::NotFound] error if there is no API token with the specified `id` or [UserError::SqlError] if there are SQL related errors.
+    fn set_last_used(
+        &mut self,
+        id: DatabaseID,
+        last_used_at: OffsetDateTime,
+    ) -> Result<(), UserError> {
+        let rows_affected = self.connection()?.execute(
+            "UPDATE api_token SET last_used_at = ?1 WHERE id = ?2",
+            (last_used_at, id),
+        )?;
+
+        if rows_affected == 0 {
+            return Err(UserError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    /// Delete the API token `id` belonging to the user `user_id` from the database.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::NotFound] error if the user has no API token with the specified `id` or [UserError::SqlError] if there are SQL related errors.
+    fn delete(&mut self, user_id: UserID, id: DatabaseID) -> Result<(), UserError> {
+        let rows_affected = self.connection()?.execute(
+            "DELETE FROM api_token WHERE id = ?1 AND user_id = ?2",
+            (id, user_id.as_i64()),
+        )?;
+
+        if rows_affected == 0 {
+            return Err(UserError::NotFound);
+        }
+
+        Ok(())
+    }
+}
+
+/// Map a row of the columns `id, user_id, name, created_at, last_used_at, expires_at` from the
+/// `api_token` table to an [ApiToken].
+fn map_api_token_row(row: &Row) -> Result<ApiToken, rusqlite::Error> {
+    Ok(ApiToken::new(
+        row.get(0)?,
+        UserID::new(row.get(1)?),
+        row.get(2)?,
+        row.get(3)?,
+        row.get(4)?,
+        row.get(5)?,
+    ))
+}
+
+#[cfg(test)]
+mod api_token_tests {
+    use std::{
+        str::FromStr,
+        sync::{Arc, Mutex},
+    };
+
+    use email_address::EmailAddress;
+    use rusqlite::Connection;
+    use time::{Duration, OffsetDateTime};
+
+    use crate::{
+        db::initialize,
+        models::{PasswordHash, User, UserID},
+        stores::{SQLiteUserStore, UserError, UserStore},
+    };
+
+    use super::{ApiTokenStore, SQLiteApiTokenStore};
+
+    fn get_store_and_user() -> (SQLiteApiTokenStore, SQLiteUserStore, User) {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize(&conn).unwrap();
+        let connection = Arc::new(Mutex::new(conn));
+        let mut user_store = SQLiteUserStore::new(connection.clone());
+        let user = user_store
+            .create(
+                EmailAddress::from_str("foo@bar.baz").unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+
+        (SQLiteApiTokenStore::new(connection), user_store, user)
+    }
+
+    #[test]
+    fn create_and_get_api_tokens() {
+        let (mut store, _, user) = get_store_and_user();
+        let expires_at = Some(OffsetDateTime::now_utc() + Duration::days(30));
+
+        let first = store
+            .create(user.id(), "first".to_string(), "hash1".to_string(), None)
+            .unwrap();
+        let second = store
+            .create(
+                user.id(),
+                "second".to_string(),
+                "hash2".to_string(),
+                expires_at,
+            )
+            .unwrap();
+
+        assert_eq!(first.name(), "first");
+        assert_eq!(first.user_id(), user.id());
+        assert_eq!(first.last_used_at(), None);
+        assert_eq!(second.expires_at(), expires_at);
+        assert_eq!(
+            store.get_by_user_id(user.id()).unwrap(),
+            vec![first, second]
+        );
+    }
+
+    #[test]
+    fn get_api_tokens_only_returns_users_tokens() {
+        let (mut store, mut user_store, user) = get_store_and_user();
+        let other_user = user_store
+            .create(
+                EmailAddress::from_str("qux@bar.baz").unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+        store
+            .create(other_user.id(), "foo".to_string(), "hash".to_string(), None)
+            .unwrap();
+
+        assert_eq!(store.get_by_user_id(user.id()).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn get_api_token_by_hash() {
+        let (mut store, _, user) = get_store_and_user();
+        let token = store
+            .create(user.id(), "foo".to_string(), "hash".to_string(), None)
+            .unwrap();
+
+        assert_eq!(store.get_by_hash("hash"), Ok(token));
+        assert_eq!(store.get_by_hash("not a hash"), Err(UserError::NotFound));
+    }
+
+    #[test]
+    fn set_api_token_last_used() {
+        let (mut store, _, user) = get_store_and_user();
+        let token = store
+            .create(user.id(), "foo".to_string(), "hash".to_string(), None)
+            .unwrap();
+        let last_used_at = OffsetDateTime::now_utc();
+
+        store.set_last_used(token.id(), last_used_at).unwrap();
+
+        assert_eq!(
+            store.get_by_hash("hash").unwrap().last_used_at(),
+            Some(last_used_at)
+        );
+        assert_eq!(
+            store.set_last_used(token.id() + 1, last_used_at),
+            Err(UserError::NotFound)
+        );
+    }
+
+    #[test]
+    fn delete_api_token() {
+        let (mut store, _, user) = get_store_and_user();
+        let token = store
+            .create(user.id(), "foo".to_string(), "hash".to_string(), None)
+            .unwrap();
+
+        store.delete(user.id(), token.id()).unwrap();
+
+        assert_eq!(store.get_by_user_id(user.id()).unwrap(), vec![]);
+        assert_eq!(store.get_by_hash("hash"), Err(UserError::NotFound));
+    }
+
+    #[test]
+    fn delete_api_token_fails_for_other_users_token() {
+        let (mut store, _, user) = get_store_and_user();
+        let token = store
+            .create(user.id(), "foo".to_string(), "hash".to_string(), None)
+            .unwrap();
+        let other_user_id = UserID::new(user.id().as_i64() + 1);
+
+        assert_eq!(
+            store.delete(other_user_id, token.id()),
+            Err(UserError::NotFound)
+        );
+        assert_eq!(store.get_by_user_id(user.id()).unwrap(), vec![token]);
+    }
+}
@@ -0,0 +1,214 @@
+//! Writing and reading the audit log of changes to users' data.
+//!
+//! Stores write audit log entries with [audit] using the same SQL transaction as the change that
+//! they describe, so that the audit log cannot disagree with the data.
+
+use rusqlite::{params_from_iter, types::Value, Connection, Row};
+use time::OffsetDateTime;
+
+use crate::models::{AuditAction, AuditEntityType, AuditLogEntry, DatabaseID, UserID};
+
+/// Add an entry to the audit log.
+///
+/// Call this with the SQL transaction that makes the change being audited.
+///
+/// # Errors
+/// Returns an error if there is an SQL error.
+pub fn audit(
+    connection: &Connection,
+    user_id: UserID,
+    action: AuditAction,
+    entity_type: AuditEntityType,
+    entity_id: DatabaseID,
+    summary: &str,
+) -> Result<(), rusqlite::Error> {
+    connection.execute(
+        "INSERT INTO audit_log (timestamp, user_id, action, entity_type, entity_id, summary) \
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        (
+            OffsetDateTime::now_utc(),
+            user_id.as_i64(),
+            action.as_str(),
+            entity_type.as_str(),
+            entity_id,
+            summary,
+        ),
+    )?;
+
+    Ok(())
+}
+
+/// Which audit log entries to get.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuditLogQuery {
+    /// Only get entries for changes made by this user.
+    pub user_id: UserID,
+    /// Only get entries for this kind of entity, or all kinds if `None`.
+    pub entity_type: Option<AuditEntityType>,
+    /// The maximum number of entries to get.
+    pub limit: u64,
+    /// The number of entries to skip, newest first.
+    pub offset: u64,
+}
+
+/// Build the WHERE clause and its parameters for `query`.
+fn get_filter(query: &AuditLogQuery) -> (String, Vec<Value>) {
+    let mut clause = "WHERE user_id = ?".to_string();
+    let mut params = vec![Value::Integer(query.user_id.as_i64())];
+
+    if let Some(entity_type) = query.entity_type {
+        clause.push_str(" AND entity_type = ?");
+        params.push(Value::Text(entity_type.as_str().to_string()));
+    }
+
+    (clause, params)
+}
+
+/// Get the audit log entries matching `query`, newest first.
+///
+/// # Errors
+/// Returns an error if there is an SQL error.
+pub fn get_audit_log(
+    connection: &Connection,
+    query: AuditLogQuery,
+) -> Result<Vec<AuditLogEntry>, rusqlite::Error> {
+    let (clause, mut params) = get_filter(&query);
+    params.push(Value::Integer(query.limit as i64));
+    params.push(Value::Integer(query.offset as i64));
+
+    connection
+        .prepare(&format!(
+            "SELECT id, timestamp, user_id, action, entity_type, entity_id, summary \
+            FROM audit_log {clause} ORDER BY id DESC LIMIT ? OFFSET ?"
+        ))?
+        .query_map(params_from_iter(params), map_audit_log_row)?
+        .collect()
+}
+
+/// Count the audit log entries matching `query`, ignoring its limit and offset.
+///
+/// # Errors
+/// Returns an error if there is an SQL error.
+pub fn count_audit_log(
+    connection: &Connection,
+    query: AuditLogQuery,
+) -> Result<u64, rusqlite::Error> {
+    let (clause, params) = get_filter(&query);
+
+    connection.query_row(
+        &format!("SELECT COUNT(*) FROM audit_log {clause}"),
+        params_from_iter(params),
+        |row| row.get(0),
+    )
+}
+
+fn map_audit_log_row(row: &Row) -> Result<AuditLogEntry, rusqlite::Error> {
+    let parse_error = |index, error: String| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, error.into())
+    };
+
+    let action: String = row.get(3)?;
+    let entity_type: String = row.get(4)?;
+
+    Ok(AuditLogEntry::new(
+        row.get(0)?,
+        row.get(1)?,
+        UserID::new(row.get(2)?),
+        action.parse().map_err(|error| parse_error(3, error))?,
+        entity_type.parse().map_err(|error| parse_error(4, error))?,
+        row.get(5)?,
+        row.get(6)?,
+    ))
+}
+
+#[cfg(test)]
+mod audit_store_tests {
+    use rusqlite::Connection;
+
+    use crate::{
+        db::initialize,
+        models::{AuditAction, AuditEntityType, UserID},
+    };
+
+    use super::{audit, count_audit_log, get_audit_log, AuditLogQuery};
+
+    fn get_connection_and_user() -> (Connection, UserID) {
+        let connection = Connection::open_in_memory().unwrap();
+        initialize(&connection).unwrap();
+        connection
+            .execute(
+                "INSERT INTO user (email, password) VALUES ('foo@bar.baz', 'hunter2'), ('qux@bar.baz', 'hunter3')",
+                (),
+            )
+            .unwrap();
+
+        (connection, UserID::new(1))
+    }
+
+    #[test]
+    fn get_audit_log_filters_and_pages_newest_first() {
+        let (connection, user_id) = get_connection_and_user();
+        let other_user_id = UserID::new(2);
+        for id in 1..=3 {
+            audit(
+                &connection,
+                user_id,
+                AuditAction::Create,
+                AuditEntityType::Transaction,
+                id,
+                &format!("transaction {id}"),
+            )
+            .unwrap();
+        }
+        audit(
+            &connection,
+            user_id,
+            AuditAction::Create,
+            AuditEntityType::Category,
+            1,
+            "category",
+        )
+        .unwrap();
+        audit(
+            &connection,
+            other_user_id,
+            AuditAction::Delete,
+            AuditEntityType::Transaction,
+            4,
+            "someone else's transaction",
+        )
+        .unwrap();
+
+        let query = AuditLogQuery {
+            user_id,
+            entity_type: Some(AuditEntityType::Transaction),
+            limit: 2,
+            offset: 0,
+        };
+        let first_page = get_audit_log(&connection, query).unwrap();
+        let second_page = get_audit_log(&connection, AuditLogQuery { offset: 2, ..query }).unwrap();
+
+        let summaries = |entries: &[crate::models::AuditLogEntry]| {
+            entries
+                .iter()
+                .map(|entry| entry.summary().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            summaries(&first_page),
+            vec!["transaction 3", "transaction 2"]
+        );
+        assert_eq!(summaries(&second_page), vec!["transaction 1"]);
+        assert_eq!(count_audit_log(&connection, query), Ok(3));
+        assert_eq!(
+            count_audit_log(
+                &connection,
+                AuditLogQuery {
+                    entity_type: None,
+                    ..query
+                }
+            ),
+            Ok(4)
+        );
+    }
+}
@@ -1,13 +1,20 @@
 //! Defines the category store trait and an implementation for the SQLite backend.
-use std::sync::{Arc, Mutex};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, MutexGuard},
+};
 
 use rusqlite::{Connection, Row};
 
 use crate::{
-    db::{CreateTable, MapRow},
-    models::{Category, CategoryError, CategoryName, DatabaseID, UserID},
+    db::{escape_like, MapRow},
+    models::{
+        AuditAction, AuditEntityType, Category, CategoryError, CategoryName, DatabaseID, UserID,
+    },
 };
 
+use super::audit::audit;
+
 /// Creates and retrieves transaction categories for transactions.
 pub trait CategoryStore {
     /// Create a new category and add it the store.
@@ -18,6 +25,40 @@ pub trait CategoryStore {
 
     /// Get all categories for a given user.
     fn get_by_user(&self, user_id: UserID) -> Result<Vec<Category>, CategoryError>;
+
+    /// Get up to `limit` of the user's categories whose names start with `prefix`, ignoring case,
+    /// sorted by name.
+    fn search(
+        &self,
+        user_id: UserID,
+        prefix: &str,
+        limit: u64,
+    ) -> Result<Vec<Category>, CategoryError>;
+
+    /// Get up to `limit` of the user's categories that were assigned to recent transactions with
+    /// descriptions similar to `description`, most likely first.
+    fn suggest(
+        &self,
+        user_id: UserID,
+        description: &str,
+        limit: u64,
+    ) -> Result<Vec<Category>, CategoryError>;
+}
+
+/// The number of the user's most recent categorised transactions that category suggestions are
+/// drawn from, so that suggesting does not scan every transaction.
+const SUGGESTION_HISTORY_LIMIT: i64 = 1000;
+
+/// Split `description` into lowercase words for comparing descriptions.
+///
+/// Words shorter than three characters and numbers, e.g. card numbers and dates, are left out
+/// since they rarely say anything about what a transaction was for.
+fn description_words(description: &str) -> HashSet<String> {
+    description
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 3 && !word.chars().all(|c| c.is_numeric()))
+        .map(str::to_lowercase)
+        .collect()
 }
 
 /// Creates and retrieves transaction categories to/from a SQLite database.
@@ -31,21 +72,55 @@ impl SQLiteCategoryStore {
     pub fn new(connection: Arc<Mutex<Connection>>) -> Self {
         Self { connection }
     }
+
+    /// Get exclusive access to the database connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [CategoryError::DatabaseLockError] if another thread panicked while holding the
+    /// database lock.
+    fn connection(&self) -> Result<MutexGuard<'_, Connection>, CategoryError> {
+        self.connection
+            .lock()
+            .map_err(|_| CategoryError::DatabaseLockError)
+    }
 }
 
 impl CategoryStore for SQLiteCategoryStore {
     /// Create a category in the database for the user `user_id`.
     ///
     /// # Errors
-    /// This function will return an error if there is an SQL error.
+    /// This function will return a:
+    /// - [CategoryError::DuplicateName] if the user already has a category with the same name,
+    ///   ignoring case,
+    /// - or [CategoryError::SqlError] if there is some other SQL error.
     fn create(&self, name: CategoryName, user_id: UserID) -> Result<Category, CategoryError> {
-        let connection = self.connection.lock().unwrap();
+        let mut connection = self.connection()?;
+        let connection = connection.transaction()?;
+
+        let name_exists: bool = connection.query_row(
+            "SELECT EXISTS(SELECT 1 FROM category WHERE user_id = ?1 AND name = ?2 COLLATE NOCASE)",
+            (user_id.as_i64(), name.as_ref()),
+            |row| row.get(0),
+        )?;
+        if name_exists {
+            return Err(CategoryError::DuplicateName);
+        }
         connection.execute(
             "INSERT INTO category (name, user_id) VALUES (?1, ?2)",
             (name.as_ref(), user_id.as_i64()),
         )?;
 
         let id = connection.last_insert_rowid();
+        audit(
+            &connection,
+            user_id,
+            AuditAction::Create,
+            AuditEntityType::Category,
+            id,
+            name.as_ref(),
+        )?;
+        connection.commit()?;
 
         Ok(Category::new(id, name, user_id))
     }
@@ -55,9 +130,7 @@ impl CategoryStore for SQLiteCategoryStore {
     /// # Errors
     /// This function will return an error if there is an SQL error.
     fn get(&self, category_id: DatabaseID) -> Result<Category, CategoryError> {
-        self.connection
-            .lock()
-            .unwrap()
+        self.connection()?
             .prepare("SELECT id, name, user_id FROM category WHERE id = :id")?
             .query_row(&[(":id", &category_id)], SQLiteCategoryStore::map_row)
             .map_err(|error| error.into())
@@ -68,9 +141,7 @@ impl CategoryStore for SQLiteCategoryStore {
     /// # Errors
     /// This function will return an error if there is an SQL error.
     fn get_by_user(&self, user_id: UserID) -> Result<Vec<Category>, CategoryError> {
-        self.connection
-            .lock()
-            .unwrap()
+        self.connection()?
             .prepare("SELECT id, name, user_id FROM category WHERE user_id = :user_id")?
             .query_map(
                 &[(":user_id", &user_id.as_i64())],
@@ -79,22 +150,100 @@ impl CategoryStore for SQLiteCategoryStore {
             .map(|maybe_category| maybe_category.map_err(CategoryError::SqlError))
             .collect()
     }
-}
 
-impl CreateTable for SQLiteCategoryStore {
-    fn create_table(connection: &Connection) -> Result<(), rusqlite::Error> {
-        connection.execute(
-            "CREATE TABLE category (
-                id INTEGER PRIMARY KEY,
-                name TEXT NOT NULL,
-                user_id INTEGER NOT NULL,
-                FOREIGN KEY(user_id) REFERENCES user(id) ON UPDATE CASCADE ON DELETE CASCADE,
-                UNIQUE(user_id, name) ON CONFLICT ROLLBACK
-                )",
-            (),
-        )?;
+    /// Retrieve up to `limit` of the categories of the user `user_id` whose names start with
+    /// `prefix`, ignoring case.
+    ///
+    /// The wildcards `%` and `_` in `prefix` match themselves.
+    ///
+    /// # Errors
+    /// This function will return an error if there is an SQL error.
+    fn search(
+        &self,
+        user_id: UserID,
+        prefix: &str,
+        limit: u64,
+    ) -> Result<Vec<Category>, CategoryError> {
+        let pattern = format!("{}%", escape_like(prefix));
+
+        self.connection()?
+            .prepare(
+                "SELECT id, name, user_id FROM category
+                WHERE user_id = ?1 AND name LIKE ?2 ESCAPE '\\'
+                ORDER BY name COLLATE NOCASE
+                LIMIT ?3",
+            )?
+            .query_map(
+                (user_id.as_i64(), pattern, limit as i64),
+                SQLiteCategoryStore::map_row,
+            )?
+            .map(|maybe_category| maybe_category.map_err(CategoryError::SqlError))
+            .collect()
+    }
 
-        Ok(())
+    /// Suggest categories for `description` from the user's most recent categorised transactions.
+    ///
+    /// Each past transaction that shares words with `description` counts towards its category
+    /// once for each shared word. Categories are ranked by their counts, then by name.
+    fn suggest(
+        &self,
+        user_id: UserID,
+        description: &str,
+        limit: u64,
+    ) -> Result<Vec<Category>, CategoryError> {
+        let words = description_words(description);
+
+        if words.is_empty() || limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let connection = self.connection()?;
+        let history = connection
+            .prepare(
+                "SELECT category.id, category.name, category.user_id, recent.description
+                FROM (
+                    SELECT category_id, description FROM \"transaction\"
+                    WHERE user_id = ?1 AND category_id IS NOT NULL
+                    ORDER BY date DESC, id DESC
+                    LIMIT ?2
+                ) AS recent
+                INNER JOIN category ON category.id = recent.category_id",
+            )?
+            .query_map((user_id.as_i64(), SUGGESTION_HISTORY_LIMIT), |row| {
+                Ok((SQLiteCategoryStore::map_row(row)?, row.get::<_, String>(3)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut scores: HashMap<DatabaseID, (usize, Category)> = HashMap::new();
+
+        for (category, past_description) in history {
+            let shared_words = description_words(&past_description)
+                .intersection(&words)
+                .count();
+
+            if shared_words > 0 {
+                scores
+                    .entry(category.id())
+                    .or_insert_with(|| (0, category))
+                    .0 += shared_words;
+            }
+        }
+
+        let mut scores: Vec<_> = scores.into_values().collect();
+        scores.sort_by(|(lhs_score, lhs), (rhs_score, rhs)| {
+            rhs_score.cmp(lhs_score).then_with(|| {
+                lhs.name()
+                    .as_ref()
+                    .to_lowercase()
+                    .cmp(&rhs.name().as_ref().to_lowercase())
+            })
+        });
+
+        Ok(scores
+            .into_iter()
+            .take(limit as usize)
+            .map(|(_, category)| category)
+            .collect())
     }
 }
 
@@ -123,11 +272,11 @@ mod category_tests {
 
     use crate::{
         db::initialize,
-        models::{CategoryError, CategoryName, PasswordHash, User, UserID},
-        stores::{SQLiteUserStore, UserStore},
+        models::{Category, CategoryError, CategoryName, PasswordHash, Transaction, User, UserID},
+        stores::{SQLiteTransactionStore, SQLiteUserStore, TransactionStore, UserStore},
     };
 
-    use super::{CategoryStore, SQLiteCategoryStore};
+    use super::{description_words, CategoryStore, SQLiteCategoryStore};
 
     fn get_store_and_user() -> (SQLiteCategoryStore, User) {
         let connection = Connection::open_in_memory().unwrap();
@@ -158,6 +307,36 @@ mod category_tests {
         assert_eq!(category.user_id(), user.id());
     }
 
+    #[test]
+    fn create_category_fails_on_duplicate_name_ignoring_case() {
+        let (store, user) = get_store_and_user();
+        store
+            .create(CategoryName::new_unchecked("Groceries"), user.id())
+            .unwrap();
+
+        let result = store.create(CategoryName::new_unchecked("GROCERIES"), user.id());
+
+        assert_eq!(result, Err(CategoryError::DuplicateName));
+    }
+
+    #[test]
+    fn create_category_allows_same_name_for_other_users() {
+        let (store, user) = get_store_and_user();
+        let other_user = SQLiteUserStore::new(store.connection.clone())
+            .create(
+                "qux@bar.baz".parse().unwrap(),
+                PasswordHash::from_raw_password("naetoafntseoafunts", 4).unwrap(),
+            )
+            .unwrap();
+        store
+            .create(CategoryName::new_unchecked("Groceries"), user.id())
+            .unwrap();
+
+        let result = store.create(CategoryName::new_unchecked("Groceries"), other_user.id());
+
+        assert!(result.is_ok());
+    }
+
     #[test]
     fn get_category_succeeds() {
         let (store, user) = get_store_and_user();
@@ -216,4 +395,185 @@ mod category_tests {
 
         assert_eq!(selected_categories, Ok(vec![]));
     }
+
+    fn get_names(categories: Vec<Category>) -> Vec<String> {
+        categories
+            .iter()
+            .map(|category| category.name().as_ref().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn search_matches_prefix_ignoring_case() {
+        let (store, user) = get_store_and_user();
+        for name in ["Vet bills", "Groceries", "vacation", "Travel"] {
+            store
+                .create(CategoryName::new_unchecked(name), user.id())
+                .unwrap();
+        }
+
+        let got = store.search(user.id(), "V", 10).unwrap();
+
+        assert_eq!(get_names(got), ["vacation", "Vet bills"]);
+    }
+
+    #[test]
+    fn search_returns_at_most_limit() {
+        let (store, user) = get_store_and_user();
+        for index in 0..15 {
+            store
+                .create(
+                    CategoryName::new_unchecked(&format!("Category {index:02}")),
+                    user.id(),
+                )
+                .unwrap();
+        }
+
+        let got = store.search(user.id(), "cat", 10).unwrap();
+
+        assert_eq!(got.len(), 10);
+        assert_eq!(get_names(got)[0], "Category 00");
+    }
+
+    #[test]
+    fn search_treats_wildcards_literally() {
+        let (store, user) = get_store_and_user();
+        for name in ["50% off", "500 club", "a_b", "abc", "back\\slash"] {
+            store
+                .create(CategoryName::new_unchecked(name), user.id())
+                .unwrap();
+        }
+
+        assert_eq!(
+            get_names(store.search(user.id(), "50%", 10).unwrap()),
+            ["50% off"]
+        );
+        assert_eq!(
+            get_names(store.search(user.id(), "a_", 10).unwrap()),
+            ["a_b"]
+        );
+        assert_eq!(
+            get_names(store.search(user.id(), "%", 10).unwrap()),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            get_names(store.search(user.id(), "back\\", 10).unwrap()),
+            ["back\\slash"]
+        );
+    }
+
+    #[test]
+    fn search_excludes_other_users_categories() {
+        let (store, user) = get_store_and_user();
+        let other_user = SQLiteUserStore::new(store.connection.clone())
+            .create(
+                "qux@bar.baz".parse().unwrap(),
+                PasswordHash::from_raw_password("naetoafntseoafunts", 4).unwrap(),
+            )
+            .unwrap();
+        store
+            .create(CategoryName::new_unchecked("Groceries"), other_user.id())
+            .unwrap();
+
+        let got = store.search(user.id(), "", 10).unwrap();
+
+        assert_eq!(got, vec![]);
+    }
+
+    #[test]
+    fn description_words_skips_short_words_and_numbers() {
+        let got = description_words("POS W/D BUCKSTARS-1234 12/03 Café ☕ 東京駅");
+
+        assert_eq!(
+            got,
+            HashSet::from([
+                "pos".to_string(),
+                "buckstars".to_string(),
+                "café".to_string(),
+                "東京駅".to_string()
+            ])
+        );
+        assert!(description_words("").is_empty());
+        assert!(description_words("🎉🎉🎉 -- 42").is_empty());
+    }
+
+    /// Categorise past transactions for `user` with the (description, category name) pairs in
+    /// `history`, creating the categories as needed.
+    fn seed_history(store: &SQLiteCategoryStore, user: &User, history: &[(&str, &str)]) {
+        let mut transaction_store = SQLiteTransactionStore::new(store.connection.clone());
+        let mut categories: Vec<Category> = Vec::new();
+
+        for (description, name) in history {
+            let category = match categories
+                .iter()
+                .find(|category| category.name().as_ref() == *name)
+            {
+                Some(category) => category.clone(),
+                None => {
+                    let category = store
+                        .create(CategoryName::new_unchecked(name), user.id())
+                        .unwrap();
+                    categories.push(category.clone());
+                    category
+                }
+            };
+
+            transaction_store
+                .create_from_builder(
+                    Transaction::build(-5.0, user.id())
+                        .description(description.to_string())
+                        .category(Some(category.id())),
+                )
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn suggest_ranks_categories_by_shared_words() {
+        let (store, user) = get_store_and_user();
+        seed_history(
+            &store,
+            &user,
+            &[
+                ("BUCKSTARS Wellington 1234", "Coffee"),
+                ("BUCKSTARS Auckland", "Coffee"),
+                ("BUCKSTARS Airport", "Travel"),
+                ("Countdown Wellington", "Groceries"),
+                ("Power bill", "Utilities"),
+            ],
+        );
+
+        let got = store.suggest(user.id(), "buckstars 5678", 3).unwrap();
+        assert_eq!(get_names(got), ["Coffee", "Travel"]);
+
+        let got = store.suggest(user.id(), "BUCKSTARS Wellington", 3).unwrap();
+        assert_eq!(get_names(got), ["Coffee", "Groceries", "Travel"]);
+
+        let got = store.suggest(user.id(), "BUCKSTARS Wellington", 1).unwrap();
+        assert_eq!(get_names(got), ["Coffee"]);
+    }
+
+    #[test]
+    fn suggest_returns_nothing_without_shared_words() {
+        let (store, user) = get_store_and_user();
+        seed_history(&store, &user, &[("BUCKSTARS", "Coffee")]);
+
+        assert_eq!(store.suggest(user.id(), "Rent", 3).unwrap(), vec![]);
+        assert_eq!(store.suggest(user.id(), "", 3).unwrap(), vec![]);
+        assert_eq!(store.suggest(user.id(), "12 34", 3).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn suggest_excludes_other_users_history() {
+        let (store, user) = get_store_and_user();
+        let other_user = SQLiteUserStore::new(store.connection.clone())
+            .create(
+                "qux@bar.baz".parse().unwrap(),
+                PasswordHash::from_raw_password("naetoafntseoafunts", 4).unwrap(),
+            )
+            .unwrap();
+        seed_history(&store, &other_user, &[("BUCKSTARS", "Coffee")]);
+
+        assert_eq!(store.suggest(user.id(), "BUCKSTARS", 3).unwrap(), vec![]);
+    }
 }
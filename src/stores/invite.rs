@@ -0,0 +1,157 @@
+//! Defines the invite store trait and an implementation for the SQLite backend.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use rusqlite::Connection;
+use time::OffsetDateTime;
+
+use crate::models::Invite;
+
+use super::UserError;
+
+/// Handles the creation, retrieval and deletion of the [Invite]s that allow people to register
+/// while registration is invite-only.
+///
+/// Invites are used up by [UserStore::create_with_invite](super::UserStore::create_with_invite)
+/// so that the user and the invite change in the same database transaction.
+pub trait InviteStore {
+    /// Create an invite with the specified `code`.
+    fn create(&mut self, code: String) -> Result<Invite, UserError>;
+
+    /// Get all of the unused invites, oldest first.
+    fn get_all(&self) -> Result<Vec<Invite>, UserError>;
+
+    /// Delete the invite with the specified `code`, e.g. to revoke it.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::NotFound] if there is no invite with the specified `code`.
+    fn delete(&mut self, code: &str) -> Result<(), UserError>;
+}
+
+/// Handles the creation, retrieval and deletion of invites in a SQLite database.
+#[derive(Debug, Clone)]
+pub struct SQLiteInviteStore {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SQLiteInviteStore {
+    /// Create a new invite store.
+    pub fn new(connection: Arc<Mutex<Connection>>) -> Self {
+        Self { connection }
+    }
+
+    /// Get exclusive access to the database connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::DatabaseLockError] if another thread panicked while holding the
+    /// database lock.
+    fn connection(&self) -> Result<MutexGuard<'_, Connection>, UserError> {
+        self.connection
+            .lock()
+            .map_err(|_| UserError::DatabaseLockError)
+    }
+}
+
+impl InviteStore for SQLiteInviteStore {
+    /// Create and insert a new invite into the database.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::SqlError] if an SQL related error occurred, e.g. the code is already in use.
+    fn create(&mut self, code: String) -> Result<Invite, UserError> {
+        let created_at = OffsetDateTime::now_utc();
+
+        self.connection()?.execute(
+            "INSERT INTO invite (code, created_at) VALUES (?1, ?2)",
+            (&code, created_at),
+        )?;
+
+        Ok(Invite::new(code, created_at))
+    }
+
+    /// Get all of the invites in the database, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::SqlError] if an SQL related error occurred.
+    fn get_all(&self) -> Result<Vec<Invite>, UserError> {
+        self.connection()?
+            .prepare("SELECT code, created_at FROM invite ORDER BY created_at ASC, code ASC")?
+            .query_map((), |row| Ok(Invite::new(row.get(0)?, row.get(1)?)))?
+            .map(|maybe_invite| maybe_invite.map_err(UserError::from))
+            .collect()
+    }
+
+    /// Delete the invite with the specified `code` from the database.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::NotFound] error if there is no invite with the specified `code` or [UserError::SqlError] if there are SQL related errors.
+    fn delete(&mut self, code: &str) -> Result<(), UserError> {
+        let rows_affected = self
+            .connection()?
+            .execute("DELETE FROM invite WHERE code = ?1", (code,))?;
+
+        if rows_affected == 0 {
+            return Err(UserError::NotFound);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod invite_tests {
+    use std::sync::{Arc, Mutex};
+
+    use rusqlite::Connection;
+
+    use crate::{db::initialize, stores::UserError};
+
+    use super::{InviteStore, SQLiteInviteStore};
+
+    fn get_store() -> SQLiteInviteStore {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize(&conn).unwrap();
+
+        SQLiteInviteStore::new(Arc::new(Mutex::new(conn)))
+    }
+
+    #[test]
+    fn create_and_get_invites() {
+        let mut store = get_store();
+
+        let first = store.create("first".to_string()).unwrap();
+        let second = store.create("second".to_string()).unwrap();
+
+        assert_eq!(store.get_all().unwrap(), vec![first, second]);
+    }
+
+    #[test]
+    fn create_invite_fails_on_duplicate_code() {
+        let mut store = get_store();
+
+        store.create("code".to_string()).unwrap();
+
+        assert!(store.create("code".to_string()).is_err());
+    }
+
+    #[test]
+    fn delete_invite_succeeds() {
+        let mut store = get_store();
+        store.create("code".to_string()).unwrap();
+
+        store.delete("code").unwrap();
+
+        assert_eq!(store.get_all().unwrap(), vec![]);
+    }
+
+    #[test]
+    fn delete_invite_fails_with_non_existent_code() {
+        let mut store = get_store();
+
+        assert_eq!(store.delete("code"), Err(UserError::NotFound));
+    }
+}
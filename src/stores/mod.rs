@@ -1,10 +1,17 @@
 //! Contains traits and implementations for objects that store the domain [models](crate::models).
 
+pub mod api_token;
+pub mod audit;
 pub mod category;
+pub mod invite;
 pub mod sql_store;
 pub mod transaction;
 pub mod user;
+pub mod webhook;
 
+pub use api_token::{ApiTokenStore, SQLiteApiTokenStore};
 pub use category::{CategoryStore, SQLiteCategoryStore};
+pub use invite::{InviteStore, SQLiteInviteStore};
 pub use transaction::{SQLiteTransactionStore, TransactionStore};
 pub use user::{SQLiteUserStore, UserError, UserStore};
+pub use webhook::{SQLiteWebhookStore, WebhookStore};
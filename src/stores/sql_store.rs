@@ -3,15 +3,40 @@
 
 use std::sync::{Arc, Mutex};
 
-use rusqlite::{Connection, Error};
+use rusqlite::Connection;
 
-use crate::{db::initialize, AppState};
+use crate::{
+    db::{initialize, DatabaseError},
+    AppState,
+};
 
-use super::{SQLiteCategoryStore, SQLiteTransactionStore, SQLiteUserStore};
+use super::{
+    SQLiteApiTokenStore, SQLiteCategoryStore, SQLiteInviteStore, SQLiteTransactionStore,
+    SQLiteUserStore, SQLiteWebhookStore,
+};
 
 /// An alias for an [AppState] that usXs SQLite for the backend.
 pub type SQLAppState = AppState<SQLiteCategoryStore, SQLiteTransactionStore, SQLiteUserStore>;
 
+impl SQLAppState {
+    /// The store for the invites that allow people to register while registration is invite-only.
+    ///
+    /// The store shares the database connection with the other stores in the app state.
+    pub fn invite_store(&mut self) -> SQLiteInviteStore {
+        SQLiteInviteStore::new(self.user_store().shared_connection())
+    }
+
+    /// The store for the API tokens that scripts use to access the JSON API.
+    pub fn api_token_store(&mut self) -> SQLiteApiTokenStore {
+        SQLiteApiTokenStore::new(self.user_store().shared_connection())
+    }
+
+    /// The store for the webhooks that are notified when something happens to a user's data.
+    pub fn webhook_store(&mut self) -> SQLiteWebhookStore {
+        SQLiteWebhookStore::new(self.user_store().shared_connection())
+    }
+}
+
 /// Creates an [AppState] instance that uses SQLite for the backend.
 ///
 /// This function will modify the database by adding the tables for the domain
@@ -19,18 +44,79 @@ pub type SQLAppState = AppState<SQLiteCategoryStore, SQLiteTransactionStore, SQL
 pub fn create_app_state(
     db_connection: Connection,
     cookie_secret: &str,
-) -> Result<SQLAppState, Error> {
+) -> Result<SQLAppState, DatabaseError> {
     initialize(&db_connection)?;
 
-    let connection = Arc::new(Mutex::new(db_connection));
+    Ok(build_app_state(
+        Arc::new(Mutex::new(db_connection)),
+        cookie_secret,
+    ))
+}
+
+/// Creates an [AppState] instance whose stores, including the webhook store used to send
+/// webhooks, share `connection`.
+///
+/// Unlike [create_app_state], this function expects the database to already be initialized.
+pub fn build_app_state(connection: Arc<Mutex<Connection>>, cookie_secret: &str) -> SQLAppState {
     let category_store = SQLiteCategoryStore::new(connection.clone());
     let transaction_store = SQLiteTransactionStore::new(connection.clone());
     let user_store = SQLiteUserStore::new(connection.clone());
+    let mut state = AppState::new(cookie_secret, category_store, transaction_store, user_store);
+    state.webhook_dispatcher.webhook_store = Some(SQLiteWebhookStore::new(connection));
 
-    Ok(AppState::new(
-        cookie_secret,
-        category_store,
-        transaction_store,
-        user_store,
-    ))
+    state
+}
+
+#[cfg(test)]
+mod sql_store_tests {
+    use std::sync::{Arc, Mutex};
+
+    use rusqlite::Connection;
+    use serde_json::{json, Value};
+
+    use crate::{
+        db::initialize,
+        models::{PasswordHash, WebhookEvent},
+        stores::{UserStore, WebhookStore},
+        webhooks::{mock_server::MockServer, EVENT_HEADER},
+    };
+
+    use super::build_app_state;
+
+    #[tokio::test]
+    async fn built_state_sends_webhooks() {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize(&conn).unwrap();
+        let mut state = build_app_state(Arc::new(Mutex::new(conn)), "42");
+        state.webhook_dispatcher.allow_local_receivers();
+        let user = state
+            .user_store()
+            .create(
+                "foo@bar.baz".parse().unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+        let server = MockServer::start(vec![]).await;
+        state
+            .webhook_store()
+            .create(
+                user.id(),
+                server.url.clone(),
+                "secret".to_string(),
+                vec![WebhookEvent::TransactionCreated],
+            )
+            .unwrap();
+
+        state.dispatch_webhooks(
+            user.id(),
+            WebhookEvent::TransactionCreated,
+            &json!({ "amount": 12.3 }),
+        );
+
+        let received = server.wait_for(1).await;
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].headers[EVENT_HEADER], "transaction.created");
+        let payload: Value = serde_json::from_slice(&received[0].body).unwrap();
+        assert_eq!(payload["data"]["amount"], 12.3);
+    }
 }
@@ -2,18 +2,23 @@
 
 use std::{
     ops::RangeInclusive,
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, MutexGuard},
 };
 
-use rusqlite::{params_from_iter, types::Value, Connection, Row};
-use time::Date;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rusqlite::{params_from_iter, types::Value, Connection, OptionalExtension, Row};
+use serde::Deserialize;
+use time::{format_description::FormatItem, macros::format_description, Date, OffsetDateTime};
 
 use crate::{
-    db::{CreateTable, MapRow},
-    models::{DatabaseID, Transaction, TransactionBuilder, TransactionError, UserID},
+    db::{escape_like, MapRow},
+    models::{
+        AuditAction, AuditEntityType, DatabaseID, Transaction, TransactionBuilder,
+        TransactionError, UserID,
+    },
 };
 
-use super::SQLiteCategoryStore;
+use super::{audit::audit, SQLiteCategoryStore};
 
 /// Handles the creation and retrieval of transactions.
 pub trait TransactionStore {
@@ -26,6 +31,16 @@ pub trait TransactionStore {
         builder: TransactionBuilder,
     ) -> Result<Transaction, TransactionError>;
 
+    /// Create `transactions` together, e.g. for a sync script that sends many at once.
+    ///
+    /// Returns the outcome of each transaction in the same order as `transactions`. A
+    /// transaction whose external ID matches one of the user's existing transactions is skipped
+    /// as a duplicate.
+    fn create_batch(
+        &mut self,
+        transactions: Vec<NewTransaction>,
+    ) -> Result<Vec<BatchOutcome>, TransactionError>;
+
     /// Retrieve a transaction from the store.
     fn get(&self, id: DatabaseID) -> Result<Transaction, TransactionError>;
 
@@ -34,6 +49,75 @@ pub trait TransactionStore {
 
     /// Retrieve transactions from the store in the way defined by `query`.
     fn get_query(&self, query: TransactionQuery) -> Result<Vec<Transaction>, TransactionError>;
+
+    /// Count the transactions matching `query`, ignoring its `limit`, `offset` and `after`.
+    fn count_query(&self, query: TransactionQuery) -> Result<u64, TransactionError>;
+
+    /// Sum the amounts of the transactions matching `query`, ignoring its `limit`, `offset` and
+    /// `after`. The sum of no transactions is zero.
+    fn sum_query(&self, query: TransactionQuery) -> Result<f64, TransactionError>;
+
+    /// Set whether the transaction `id` should be left out of totals.
+    fn set_ignored(
+        &mut self,
+        id: DatabaseID,
+        ignored: bool,
+    ) -> Result<Transaction, TransactionError>;
+
+    /// Mark the transactions `ids` as reconciled.
+    ///
+    /// Either all of the transactions are marked as reconciled, or none of them are.
+    fn set_reconciled(&mut self, ids: &[DatabaseID]) -> Result<(), TransactionError>;
+
+    /// Replace the details of the transaction `id` with those in `builder`.
+    ///
+    /// The transaction keeps its ID and owner.
+    fn update(
+        &mut self,
+        id: DatabaseID,
+        builder: TransactionBuilder,
+    ) -> Result<Transaction, TransactionError>;
+
+    /// Delete the transaction `id`.
+    fn delete(&mut self, id: DatabaseID) -> Result<(), TransactionError>;
+
+    /// Get the groups of `user_id`'s transactions that may be duplicates of each other, i.e.
+    /// transactions with the same date, amount and description.
+    ///
+    /// Groups are ordered by date, newest first, and the transactions in each group are ordered
+    /// by ID, so the first transaction in a group is the one that was created first.
+    fn get_duplicates(&self, user_id: UserID) -> Result<Vec<Vec<Transaction>>, TransactionError>;
+
+    /// Merge the transactions `duplicate_ids` into the transaction `keep_id` by deleting them.
+    ///
+    /// If `keep_id` has no category, it takes the category of the first duplicate that has one.
+    /// Either all of the duplicates are merged, or none of them are.
+    fn merge_duplicates(
+        &mut self,
+        keep_id: DatabaseID,
+        duplicate_ids: &[DatabaseID],
+    ) -> Result<Transaction, TransactionError>;
+}
+
+/// A transaction to create with [TransactionStore::create_batch].
+pub struct NewTransaction {
+    /// The details of the transaction.
+    pub builder: TransactionBuilder,
+    /// The ID of the transaction in the system it came from, e.g. a bank's API.
+    ///
+    /// Sending a transaction with the same external ID again does not create a second copy.
+    pub external_id: Option<String>,
+}
+
+/// The outcome of creating one transaction with [TransactionStore::create_batch].
+#[derive(Debug, PartialEq)]
+pub enum BatchOutcome {
+    /// The transaction was created.
+    Created(Transaction),
+    /// The user already has the transaction with this ID and the same external ID.
+    Duplicate(DatabaseID),
+    /// The transaction could not be created, e.g. because its category is invalid.
+    Failed(TransactionError),
 }
 
 /// Defines how transactions should be fetched from [TransactionStore::get_query].
@@ -43,14 +127,109 @@ pub struct TransactionQuery {
     pub user_id: Option<UserID>,
     /// Include transactions within `date_range` (inclusive).
     pub date_range: Option<RangeInclusive<Date>>,
+    /// Matches transactions assigned to the category with the ID `category_id`.
+    pub category_id: Option<DatabaseID>,
+    /// Matches only transactions that have not been assigned a category.
+    pub uncategorised_only: bool,
+    /// Leaves out transactions assigned to any of these categories. Transactions without a
+    /// category are kept.
+    pub exclude_category_ids: Vec<DatabaseID>,
+    /// Matches transactions whose description contains this text, ignoring case.
+    ///
+    /// The wildcards `%` and `_` match themselves.
+    pub description_contains: Option<String>,
     /// Selects up to the first N (`limit`) transactions.
     pub limit: Option<u64>,
+    /// Skips the first N (`offset`) transactions, e.g. to select a later page.
+    pub offset: Option<u64>,
+    /// Selects only the transactions that come after `after` in the order `sort_date`.
+    ///
+    /// Unlike `offset`, this does not need to step over the preceding transactions, so later
+    /// pages are as fast as the first. It has no effect unless `sort_date` is set.
+    pub after: Option<TransactionCursor>,
     /// Orders transactions by date in the order `sort_date`. None returns transactions in the
     /// order they are stored.
     pub sort_date: Option<SortOrder>,
+    /// Whether to select [ignored](Transaction::is_ignored) transactions. By default, ignored
+    /// transactions are left out so that they do not affect totals.
+    pub ignored: IgnoredFilter,
+    /// Matches transactions that have (`Some(true)`) or have not (`Some(false)`) been
+    /// [reconciled](Transaction::is_reconciled). None selects transactions regardless.
+    pub reconciled: Option<bool>,
+}
+
+/// The format of dates in a [TransactionCursor], e.g. 2024-01-31.
+const DATE_FORMAT: &[FormatItem<'static>] = format_description!("[year]-[month]-[day]");
+
+/// The position of a transaction in a list of transactions sorted by date.
+///
+/// Transactions on the same date are ordered by their ID, so the date and ID together give a
+/// unique position even when many transactions share a date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct TransactionCursor {
+    /// The date of the transaction.
+    pub date: Date,
+    /// The ID of the transaction.
+    pub id: DatabaseID,
+}
+
+impl TransactionCursor {
+    /// The position of `transaction`.
+    pub fn of(transaction: &Transaction) -> Self {
+        Self {
+            date: *transaction.date(),
+            id: transaction.id(),
+        }
+    }
+
+    /// Encode the cursor as an opaque string that is safe to use in a URL.
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!("{}:{}", self.date, self.id))
+    }
+
+    /// Decode a cursor from a string created by [TransactionCursor::encode].
+    ///
+    /// # Errors
+    /// Returns an error message if `encoded` is not a valid cursor.
+    pub fn decode(encoded: &str) -> Result<Self, String> {
+        let invalid_cursor = || format!("invalid cursor '{encoded}'");
+
+        let decoded = URL_SAFE_NO_PAD
+            .decode(encoded)
+            .map_err(|_| invalid_cursor())?;
+        let decoded = String::from_utf8(decoded).map_err(|_| invalid_cursor())?;
+        let (date, id) = decoded.split_once(':').ok_or_else(invalid_cursor)?;
+
+        Ok(Self {
+            date: Date::parse(date, DATE_FORMAT).map_err(|_| invalid_cursor())?,
+            id: id.parse().map_err(|_| invalid_cursor())?,
+        })
+    }
+}
+
+impl TryFrom<String> for TransactionCursor {
+    type Error = String;
+
+    fn try_from(encoded: String) -> Result<Self, Self::Error> {
+        Self::decode(&encoded)
+    }
+}
+
+/// How to treat [ignored](Transaction::is_ignored) transactions in a [TransactionQuery].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum IgnoredFilter {
+    /// Leave out ignored transactions.
+    #[default]
+    Exclude,
+    /// Select transactions regardless of whether they are ignored.
+    Include,
+    /// Select only ignored transactions.
+    Only,
 }
 
 /// The order to sort transactions in a [TransactionQuery].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SortOrder {
     /// Sort in order of increasing value.
     Ascending,
@@ -74,6 +253,104 @@ impl SQLiteTransactionStore {
     pub fn new(connection: Arc<Mutex<Connection>>) -> Self {
         Self { connection }
     }
+
+    /// Get exclusive access to the database connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [TransactionError::DatabaseLockError] if another thread panicked while holding
+    /// the database lock.
+    fn connection(&self) -> Result<MutexGuard<'_, Connection>, TransactionError> {
+        self.connection
+            .lock()
+            .map_err(|_| TransactionError::DatabaseLockError)
+    }
+
+    /// Build the WHERE clause that selects the transactions matching `filter`, and the parameters
+    /// it refers to. The clause is empty if `filter` matches every transaction.
+    fn build_where_clause(filter: &TransactionQuery) -> (Option<String>, Vec<Value>) {
+        let mut where_clause_parts = vec![];
+        let mut query_parameters = vec![];
+
+        if let Some(user_id) = filter.user_id {
+            where_clause_parts.push(format!("user_id = ?{}", query_parameters.len() + 1));
+            query_parameters.push(Value::Integer(user_id.as_i64()));
+        }
+
+        if let Some(date_range) = &filter.date_range {
+            where_clause_parts.push(format!(
+                "date BETWEEN ?{} AND ?{}",
+                query_parameters.len() + 1,
+                query_parameters.len() + 2,
+            ));
+            query_parameters.push(Value::Text(date_range.start().to_string()));
+            query_parameters.push(Value::Text(date_range.end().to_string()));
+        }
+
+        if let Some(category_id) = filter.category_id {
+            where_clause_parts.push(format!("category_id = ?{}", query_parameters.len() + 1));
+            query_parameters.push(Value::Integer(category_id));
+        }
+
+        if filter.uncategorised_only {
+            where_clause_parts.push("category_id IS NULL".to_string());
+        }
+
+        if !filter.exclude_category_ids.is_empty() {
+            let placeholders = (1..=filter.exclude_category_ids.len())
+                .map(|i| format!("?{}", query_parameters.len() + i))
+                .collect::<Vec<_>>()
+                .join(", ");
+            // Without the NULL check, NOT IN would also leave out uncategorised transactions.
+            where_clause_parts.push(format!(
+                "(category_id IS NULL OR category_id NOT IN ({placeholders}))"
+            ));
+            query_parameters.extend(
+                filter
+                    .exclude_category_ids
+                    .iter()
+                    .map(|&category_id| Value::Integer(category_id)),
+            );
+        }
+
+        if let Some(text) = &filter.description_contains {
+            where_clause_parts.push(format!(
+                "description LIKE ?{} ESCAPE '\\'",
+                query_parameters.len() + 1
+            ));
+            query_parameters.push(Value::Text(format!("%{}%", escape_like(text))));
+        }
+
+        match filter.ignored {
+            IgnoredFilter::Exclude => where_clause_parts.push("ignored = 0".to_string()),
+            IgnoredFilter::Include => {}
+            IgnoredFilter::Only => where_clause_parts.push("ignored = 1".to_string()),
+        }
+
+        if let Some(reconciled) = filter.reconciled {
+            where_clause_parts.push(format!("reconciled = ?{}", query_parameters.len() + 1));
+            query_parameters.push(Value::Integer(reconciled as i64));
+        }
+
+        if let (Some(after), Some(sort_date)) = (filter.after, &filter.sort_date) {
+            let operator = match sort_date {
+                SortOrder::Ascending => ">",
+                SortOrder::Descending => "<",
+            };
+            where_clause_parts.push(format!(
+                "(date, id) {operator} (?{}, ?{})",
+                query_parameters.len() + 1,
+                query_parameters.len() + 2,
+            ));
+            query_parameters.push(Value::Text(after.date.to_string()));
+            query_parameters.push(Value::Integer(after.id));
+        }
+
+        let where_clause = (!where_clause_parts.is_empty())
+            .then(|| String::from("WHERE ") + &where_clause_parts.join(" AND "));
+
+        (where_clause, query_parameters)
+    }
 }
 
 impl TransactionStore for SQLiteTransactionStore {
@@ -107,56 +384,67 @@ impl TransactionStore for SQLiteTransactionStore {
         &mut self,
         builder: TransactionBuilder,
     ) -> Result<Transaction, TransactionError> {
-        let connection = self.connection.lock().unwrap();
+        let mut connection = self.connection()?;
+        let connection = connection.transaction()?;
 
-        let next_id: i64 = connection.query_row(
-            "SELECT COALESCE(MAX(id), 0) FROM \"transaction\"",
-            [],
-            |row| row.get(0),
-        )?;
-        let next_id = next_id + 1;
+        let transaction = insert_transaction(&connection, builder, None)?;
+        connection.commit()?;
 
-        let transaction = builder.finalise(next_id);
+        Ok(transaction)
+    }
 
-        if let Some(category_id) = transaction.category_id() {
-            let category = connection
-                .query_row(
-                    "SELECT id, name, user_id FROM category WHERE id = ?1",
-                    (category_id,),
-                    SQLiteCategoryStore::map_row,
-                )
-                .map_err(|error| match error {
-                    // We enforce the foreign key constraint (the ID refers to a valid, existing
-                    // record) here so that we know later that if a foreign key constraint is
-                    // violated, it is for the user ID. Otherwise, it would difficult to know
-                    // which foreign key constraint was violated since the SQL error does not
-                    // provide any useful information.
-                    rusqlite::Error::QueryReturnedNoRows => TransactionError::InvalidCategory,
-                    error => TransactionError::SqlError(error),
-                })?;
-
-            if category.user_id() != transaction.user_id() {
-                // Use same error as if the category doesn't exist so that unauthorized users can't
-                // poke around to find out what data exists.
-                return Err(TransactionError::InvalidCategory);
+    /// Create `transactions` in a single database transaction.
+    ///
+    /// Transactions with an invalid category are reported as [BatchOutcome::Failed] and do not
+    /// stop the others from being created. A transaction is a duplicate if the user already has
+    /// a transaction with its external ID, including one created earlier in the same batch.
+    ///
+    /// # Errors
+    /// This function will return a:
+    /// - [TransactionError::InvalidUser] if a transaction's user does not exist,
+    /// - or [TransactionError::SqlError] if there is some other SQL error.
+    ///
+    /// In either case, none of the transactions are created.
+    fn create_batch(
+        &mut self,
+        transactions: Vec<NewTransaction>,
+    ) -> Result<Vec<BatchOutcome>, TransactionError> {
+        let mut connection = self.connection()?;
+        let connection = connection.transaction()?;
+        let mut outcomes = Vec::with_capacity(transactions.len());
+
+        for NewTransaction {
+            builder,
+            external_id,
+        } in transactions
+        {
+            if let Some(external_id) = &external_id {
+                let existing_id: Option<DatabaseID> = connection
+                    .query_row(
+                        "SELECT id FROM \"transaction\" WHERE user_id = ?1 AND external_id = ?2",
+                        (builder.user_id().as_i64(), external_id),
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+
+                if let Some(existing_id) = existing_id {
+                    outcomes.push(BatchOutcome::Duplicate(existing_id));
+                    continue;
+                }
             }
+
+            let outcome = match insert_transaction(&connection, builder, external_id) {
+                Ok(transaction) => BatchOutcome::Created(transaction),
+                Err(error @ TransactionError::InvalidCategory) => BatchOutcome::Failed(error),
+                // Dropping the transaction without committing rolls back the earlier inserts.
+                Err(error) => return Err(error),
+            };
+            outcomes.push(outcome);
         }
 
-        connection
-                .execute(
-                    "INSERT INTO \"transaction\" (id, amount, date, description, category_id, user_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-                    (transaction.id(), transaction.amount(), transaction.date(), transaction.description(), transaction.category_id(), transaction.user_id().as_i64()),
-                ).map_err(|error| match error
-                {
-                    // Code 787 occurs when a FOREIGN KEY constraint failed.
-                    // The client tried to add a transaction for a nonexistent user.
-                    rusqlite::Error::SqliteFailure(error, Some(_)) if error.extended_code == 787 => {
-                        TransactionError::InvalidUser
-                    }
-                    error => TransactionError::SqlError(error)
-                })?;
+        connection.commit()?;
 
-        Ok(transaction)
+        Ok(outcomes)
     }
 
     /// Retrieve a transaction in the database by its `id`.
@@ -166,11 +454,9 @@ impl TransactionStore for SQLiteTransactionStore {
     /// - [TransactionError::NotFound] if `id` does not refer to a valid transaction,
     /// - or [TransactionError::SqlError] there is some other SQL error.
     fn get(&self, id: DatabaseID) -> Result<Transaction, TransactionError> {
-        let transaction = self.connection.lock().unwrap()
-                .prepare("SELECT id, amount, date, description, category_id, user_id FROM \"transaction\" WHERE id = :id")?
-                .query_row(&[(":id", &id)], Self::map_row)?;
+        let connection = self.connection()?;
 
-        Ok(transaction)
+        get_transaction(&connection, id)
     }
 
     /// Retrieve the transactions in the database that have `user_id`.
@@ -180,8 +466,8 @@ impl TransactionStore for SQLiteTransactionStore {
     /// # Errors
     /// This function will return a [TransactionError::SqlError] if there is an SQL error.
     fn get_by_user_id(&self, user_id: UserID) -> Result<Vec<Transaction>, TransactionError> {
-        self.connection.lock().unwrap()
-                .prepare("SELECT id, amount, date, description, category_id, user_id FROM \"transaction\" WHERE user_id = :user_id")?
+        self.connection()?
+                .prepare("SELECT id, amount, date, description, category_id, user_id, ignored, reconciled FROM \"transaction\" WHERE user_id = :user_id")?
                 .query_map(&[(":user_id", &user_id.as_i64())], Self::map_row)?
                 .map(|maybe_category| maybe_category.map_err(TransactionError::SqlError))
                 .collect()
@@ -189,75 +475,402 @@ impl TransactionStore for SQLiteTransactionStore {
 
     fn get_query(&self, filter: TransactionQuery) -> Result<Vec<Transaction>, TransactionError> {
         let mut query_string_parts = vec![
-            "SELECT id, amount, date, description, category_id, user_id FROM \"transaction\""
+            "SELECT id, amount, date, description, category_id, user_id, ignored, reconciled FROM \"transaction\""
                 .to_string(),
         ];
-        let mut where_clause_parts = vec![];
-        let mut query_parameters = vec![];
-
-        if let Some(user_id) = filter.user_id {
-            where_clause_parts.push(format!("user_id = ?{}", query_parameters.len() + 1));
-            query_parameters.push(Value::Integer(user_id.as_i64()));
-        }
-
-        if let Some(date_range) = filter.date_range {
-            where_clause_parts.push(format!(
-                "date BETWEEN ?{} AND ?{}",
-                query_parameters.len() + 1,
-                query_parameters.len() + 2,
-            ));
-            query_parameters.push(Value::Text(date_range.start().to_string()));
-            query_parameters.push(Value::Text(date_range.end().to_string()));
-        }
-
-        if !where_clause_parts.is_empty() {
-            query_string_parts.push(String::from("WHERE ") + &where_clause_parts.join(" AND "));
-        }
+        let (where_clause, query_parameters) = Self::build_where_clause(&filter);
+        query_string_parts.extend(where_clause);
 
         match filter.sort_date {
-            Some(SortOrder::Ascending) => query_string_parts.push("ORDER BY date ASC".to_string()),
+            // Sort transactions on the same date by ID so that pages do not overlap.
+            Some(SortOrder::Ascending) => {
+                query_string_parts.push("ORDER BY date ASC, id ASC".to_string())
+            }
             Some(SortOrder::Descending) => {
-                query_string_parts.push("ORDER BY date DESC".to_string())
+                query_string_parts.push("ORDER BY date DESC, id DESC".to_string())
             }
             None => {}
         }
 
-        if let Some(limit) = filter.limit {
-            query_string_parts.push(format!("LIMIT {}", limit));
+        match (filter.limit, filter.offset) {
+            (Some(limit), Some(offset)) => {
+                query_string_parts.push(format!("LIMIT {limit} OFFSET {offset}"))
+            }
+            (Some(limit), None) => query_string_parts.push(format!("LIMIT {limit}")),
+            // SQLite only allows OFFSET after LIMIT, where a negative limit means no limit.
+            (None, Some(offset)) => query_string_parts.push(format!("LIMIT -1 OFFSET {offset}")),
+            (None, None) => {}
         }
 
         let query_string = query_string_parts.join(" ");
         let params = params_from_iter(query_parameters.iter());
 
-        self.connection
-            .lock()
-            .unwrap()
+        self.connection()?
             .prepare(&query_string)?
             .query_map(params, Self::map_row)?
             .map(|maybe_category| maybe_category.map_err(TransactionError::SqlError))
             .collect()
     }
-}
 
-impl CreateTable for SQLiteTransactionStore {
-    fn create_table(connection: &Connection) -> Result<(), rusqlite::Error> {
-        connection
-                .execute(
-                    "CREATE TABLE \"transaction\" (
-                            id INTEGER PRIMARY KEY,
-                            amount REAL NOT NULL,
-                            date TEXT NOT NULL,
-                            description TEXT NOT NULL,
-                            category_id INTEGER,
-                            user_id INTEGER NOT NULL,
-                            FOREIGN KEY(category_id) REFERENCES category(id) ON UPDATE CASCADE ON DELETE CASCADE,
-                            FOREIGN KEY(user_id) REFERENCES user(id) ON UPDATE CASCADE ON DELETE CASCADE
-                            )",
-                    (),
+    fn count_query(&self, filter: TransactionQuery) -> Result<u64, TransactionError> {
+        let mut query_string_parts = vec!["SELECT COUNT(*) FROM \"transaction\"".to_string()];
+        let (where_clause, query_parameters) = Self::build_where_clause(&TransactionQuery {
+            after: None,
+            ..filter
+        });
+        query_string_parts.extend(where_clause);
+
+        let query_string = query_string_parts.join(" ");
+        let params = params_from_iter(query_parameters.iter());
+
+        let count: i64 = self
+            .connection()?
+            .query_row(&query_string, params, |row| row.get(0))?;
+
+        Ok(count as u64)
+    }
+
+    fn sum_query(&self, filter: TransactionQuery) -> Result<f64, TransactionError> {
+        let mut query_string_parts =
+            vec!["SELECT COALESCE(SUM(amount), 0.0) FROM \"transaction\"".to_string()];
+        let (where_clause, query_parameters) = Self::build_where_clause(&TransactionQuery {
+            after: None,
+            ..filter
+        });
+        query_string_parts.extend(where_clause);
+
+        let query_string = query_string_parts.join(" ");
+        let params = params_from_iter(query_parameters.iter());
+
+        let sum = self
+            .connection()?
+            .query_row(&query_string, params, |row| row.get(0))?;
+
+        Ok(sum)
+    }
+
+    /// Set whether the transaction `id` should be left out of totals.
+    ///
+    /// # Errors
+    /// This function will return a:
+    /// - [TransactionError::NotFound] if `id` does not refer to a valid transaction,
+    /// - or [TransactionError::SqlError] there is some other SQL error.
+    fn set_ignored(
+        &mut self,
+        id: DatabaseID,
+        ignored: bool,
+    ) -> Result<Transaction, TransactionError> {
+        let mut connection = self.connection()?;
+        let connection = connection.transaction()?;
+
+        connection.execute(
+            "UPDATE \"transaction\" SET ignored = ?1 WHERE id = ?2",
+            (ignored, id),
+        )?;
+        let transaction = get_transaction(&connection, id)?;
+
+        audit_transaction(&connection, AuditAction::Update, &transaction)?;
+        connection.commit()?;
+
+        Ok(transaction)
+    }
+
+    /// Mark the transactions `ids` as reconciled in a single database transaction.
+    ///
+    /// # Errors
+    /// This function will return a:
+    /// - [TransactionError::NotFound] if any of `ids` do not refer to a valid transaction, in
+    ///   which case no transactions are changed,
+    /// - or [TransactionError::SqlError] there is some other SQL error.
+    fn set_reconciled(&mut self, ids: &[DatabaseID]) -> Result<(), TransactionError> {
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction()?;
+
+        {
+            let mut statement =
+                transaction.prepare("UPDATE \"transaction\" SET reconciled = 1 WHERE id = ?1")?;
+
+            for id in ids {
+                if statement.execute([id])? == 0 {
+                    // Dropping the transaction without committing rolls back the earlier updates.
+                    return Err(TransactionError::NotFound);
+                }
+
+                audit_transaction(
+                    &transaction,
+                    AuditAction::Update,
+                    &get_transaction(&transaction, *id)?,
                 )?;
+            }
+        }
+
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    /// Replace the amount, date, description, category and flags of the transaction `id`.
+    ///
+    /// # Errors
+    /// This function will return a:
+    /// - [TransactionError::NotFound] if `id` does not refer to a valid transaction,
+    /// - [TransactionError::InvalidCategory] if the category does not exist or belongs to
+    ///   another user,
+    /// - or [TransactionError::SqlError] there is some other SQL error.
+    fn update(
+        &mut self,
+        id: DatabaseID,
+        builder: TransactionBuilder,
+    ) -> Result<Transaction, TransactionError> {
+        let raw_description = builder.raw_description().to_owned();
+        let transaction = builder.finalise(id);
+
+        let mut connection = self.connection()?;
+        let connection = connection.transaction()?;
+        check_category(&connection, &transaction)?;
+
+        let rows_affected = connection.execute(
+            "UPDATE \"transaction\" SET amount = ?1, date = ?2, description = ?3, raw_description = ?4, category_id = ?5, ignored = ?6, reconciled = ?7 WHERE id = ?8 AND user_id = ?9",
+            (
+                transaction.amount(),
+                transaction.date(),
+                transaction.description(),
+                raw_description,
+                transaction.category_id(),
+                transaction.is_ignored(),
+                transaction.is_reconciled(),
+                id,
+                transaction.user_id().as_i64(),
+            ),
+        )?;
+
+        if rows_affected == 0 {
+            return Err(TransactionError::NotFound);
+        }
+
+        let transaction = get_transaction(&connection, id)?;
+        audit_transaction(&connection, AuditAction::Update, &transaction)?;
+        connection.commit()?;
+
+        Ok(transaction)
+    }
+
+    /// Delete the transaction `id`.
+    ///
+    /// # Errors
+    /// This function will return a:
+    /// - [TransactionError::NotFound] if `id` does not refer to a valid transaction,
+    /// - or [TransactionError::SqlError] there is some other SQL error.
+    fn delete(&mut self, id: DatabaseID) -> Result<(), TransactionError> {
+        let mut connection = self.connection()?;
+        let connection = connection.transaction()?;
+
+        let transaction = get_transaction(&connection, id)?;
+        connection.execute("DELETE FROM \"transaction\" WHERE id = ?1", (id,))?;
+
+        audit_transaction(&connection, AuditAction::Delete, &transaction)?;
+        connection.commit()?;
 
         Ok(())
     }
+
+    fn get_duplicates(&self, user_id: UserID) -> Result<Vec<Vec<Transaction>>, TransactionError> {
+        let transactions = self
+            .connection()?
+            .prepare(
+                "SELECT id, amount, date, description, category_id, user_id, ignored, reconciled FROM (\
+                    SELECT *, COUNT(*) OVER (PARTITION BY date, amount, description) AS copies \
+                    FROM \"transaction\" WHERE user_id = ?1\
+                ) WHERE copies > 1 ORDER BY date DESC, amount, description, id",
+            )?
+            .query_map((user_id.as_i64(),), Self::map_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut groups: Vec<Vec<Transaction>> = Vec::new();
+
+        for transaction in transactions {
+            match groups.last_mut() {
+                Some(group) if is_duplicate(&group[0], &transaction) => group.push(transaction),
+                _ => groups.push(vec![transaction]),
+            }
+        }
+
+        Ok(groups)
+    }
+
+    /// Merge the transactions `duplicate_ids` into the transaction `keep_id` by deleting them.
+    ///
+    /// `keep_id` is skipped if it is in `duplicate_ids`.
+    ///
+    /// # Errors
+    /// This function will return a:
+    /// - [TransactionError::NotFound] if `keep_id` does not refer to a valid transaction, or any
+    ///   of `duplicate_ids` do not refer to a duplicate of it, in which case no transactions are
+    ///   changed,
+    /// - or [TransactionError::SqlError] there is some other SQL error.
+    fn merge_duplicates(
+        &mut self,
+        keep_id: DatabaseID,
+        duplicate_ids: &[DatabaseID],
+    ) -> Result<Transaction, TransactionError> {
+        let mut connection = self.connection()?;
+        let connection = connection.transaction()?;
+
+        let kept = get_transaction(&connection, keep_id)?;
+        let mut category_id = kept.category_id();
+
+        for &id in duplicate_ids.iter().filter(|&&id| id != keep_id) {
+            let duplicate = get_transaction(&connection, id)?;
+
+            if !is_duplicate(&kept, &duplicate) {
+                return Err(TransactionError::NotFound);
+            }
+
+            category_id = category_id.or(duplicate.category_id());
+            connection.execute("DELETE FROM \"transaction\" WHERE id = ?1", (id,))?;
+            audit_transaction(&connection, AuditAction::Delete, &duplicate)?;
+        }
+
+        let kept = if category_id == kept.category_id() {
+            kept
+        } else {
+            connection.execute(
+                "UPDATE \"transaction\" SET category_id = ?1 WHERE id = ?2",
+                (category_id, keep_id),
+            )?;
+            let kept = get_transaction(&connection, keep_id)?;
+            audit_transaction(&connection, AuditAction::Update, &kept)?;
+            kept
+        };
+
+        connection.commit()?;
+
+        Ok(kept)
+    }
+}
+
+/// Whether `a` and `b` belong to the same user and have the same date, amount and description.
+fn is_duplicate(a: &Transaction, b: &Transaction) -> bool {
+    a.user_id() == b.user_id()
+        && a.date() == b.date()
+        && a.amount() == b.amount()
+        && a.description() == b.description()
+}
+
+/// Insert the transaction described by `builder` using `connection`, which should be part of an
+/// SQL transaction, and record it in the audit log.
+///
+/// # Errors
+/// This function will return a:
+/// - [TransactionError::InvalidCategory] if the category does not exist or belongs to another
+///   user,
+/// - [TransactionError::InvalidUser] if the user does not exist,
+/// - or [TransactionError::SqlError] if there is some other SQL error.
+fn insert_transaction(
+    connection: &Connection,
+    builder: TransactionBuilder,
+    external_id: Option<String>,
+) -> Result<Transaction, TransactionError> {
+    let next_id: i64 = connection.query_row(
+        "SELECT COALESCE(MAX(id), 0) FROM \"transaction\"",
+        [],
+        |row| row.get(0),
+    )?;
+    let next_id = next_id + 1;
+
+    let raw_description = builder.raw_description().to_owned();
+    let transaction = builder.finalise(next_id);
+
+    check_category(connection, &transaction)?;
+
+    connection
+            .execute(
+                "INSERT INTO \"transaction\" (id, amount, date, description, raw_description, category_id, user_id, ignored, reconciled, created_at, external_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                (transaction.id(), transaction.amount(), transaction.date(), transaction.description(), raw_description, transaction.category_id(), transaction.user_id().as_i64(), transaction.is_ignored(), transaction.is_reconciled(), OffsetDateTime::now_utc(), external_id),
+            ).map_err(|error| match error
+            {
+                // Code 787 occurs when a FOREIGN KEY constraint failed.
+                // The client tried to add a transaction for a nonexistent user.
+                rusqlite::Error::SqliteFailure(error, Some(_)) if error.extended_code == 787 => {
+                    TransactionError::InvalidUser
+                }
+                error => TransactionError::SqlError(error)
+            })?;
+
+    audit_transaction(connection, AuditAction::Create, &transaction)?;
+
+    Ok(transaction)
+}
+
+/// Get the transaction `id` using `connection`, which may be part of an SQL transaction.
+fn get_transaction(
+    connection: &Connection,
+    id: DatabaseID,
+) -> Result<Transaction, TransactionError> {
+    let transaction = connection
+        .prepare("SELECT id, amount, date, description, category_id, user_id, ignored, reconciled FROM \"transaction\" WHERE id = :id")?
+        .query_row(&[(":id", &id)], SQLiteTransactionStore::map_row)?;
+
+    Ok(transaction)
+}
+
+/// Record `action` on `transaction` in the audit log.
+fn audit_transaction(
+    connection: &Connection,
+    action: AuditAction,
+    transaction: &Transaction,
+) -> Result<(), rusqlite::Error> {
+    let mut summary = format!("{:.2} on {}", transaction.amount(), transaction.date());
+
+    if !transaction.description().is_empty() {
+        summary.push_str(&format!(" ({})", transaction.description()));
+    }
+
+    audit(
+        connection,
+        transaction.user_id(),
+        action,
+        AuditEntityType::Transaction,
+        transaction.id(),
+        &summary,
+    )
+}
+
+/// Check that the category of `transaction`, if any, exists and belongs to the same user.
+///
+/// # Errors
+/// This function will return a [TransactionError::InvalidCategory] if the category does not
+/// exist or belongs to another user, or [TransactionError::SqlError] if there is some other SQL
+/// error.
+fn check_category(
+    connection: &Connection,
+    transaction: &Transaction,
+) -> Result<(), TransactionError> {
+    if let Some(category_id) = transaction.category_id() {
+        let category = connection
+            .query_row(
+                "SELECT id, name, user_id FROM category WHERE id = ?1",
+                (category_id,),
+                SQLiteCategoryStore::map_row,
+            )
+            .map_err(|error| match error {
+                // We enforce the foreign key constraint (the ID refers to a valid, existing
+                // record) here so that we know later that if a foreign key constraint is
+                // violated, it is for the user ID. Otherwise, it would difficult to know
+                // which foreign key constraint was violated since the SQL error does not
+                // provide any useful information.
+                rusqlite::Error::QueryReturnedNoRows => TransactionError::InvalidCategory,
+                error => TransactionError::SqlError(error),
+            })?;
+
+        if category.user_id() != transaction.user_id() {
+            // Use same error as if the category doesn't exist so that unauthorized users can't
+            // poke around to find out what data exists.
+            return Err(TransactionError::InvalidCategory);
+        }
+    }
+
+    Ok(())
 }
 
 impl MapRow for SQLiteTransactionStore {
@@ -270,9 +883,19 @@ impl MapRow for SQLiteTransactionStore {
         let description = row.get(offset + 3)?;
         let category_id = row.get(offset + 4)?;
         let user_id = UserID::new(row.get(offset + 5)?);
-
-        let transaction =
-            Transaction::new_unchecked(id, amount, date, description, category_id, user_id);
+        let ignored = row.get(offset + 6)?;
+        let reconciled = row.get(offset + 7)?;
+
+        let transaction = Transaction::new_unchecked(
+            id,
+            amount,
+            date,
+            description,
+            category_id,
+            user_id,
+            ignored,
+            reconciled,
+        );
 
         Ok(transaction)
     }
@@ -289,12 +912,14 @@ mod sqlite_transaction_store_tests {
         models::{CategoryName, PasswordHash, Transaction, TransactionBuilder, User, UserID},
         stores::{
             sql_store::{create_app_state, SQLAppState},
-            transaction::{SortOrder, TransactionQuery},
+            transaction::{IgnoredFilter, SortOrder, TransactionQuery},
             CategoryStore, UserStore,
         },
     };
 
-    use super::{TransactionError, TransactionStore};
+    use super::{
+        BatchOutcome, NewTransaction, TransactionCursor, TransactionError, TransactionStore,
+    };
 
     fn get_app_state_and_test_user() -> (SQLAppState, User) {
         let conn = Connection::open_in_memory().unwrap();
@@ -327,18 +952,64 @@ mod sqlite_transaction_store_tests {
     }
 
     #[test]
-    fn create_fails_on_invalid_user_id() {
+    fn create_records_creation_time() {
         let (mut state, user) = get_app_state_and_test_user();
 
-        let transaction = state
-            .transaction_store()
-            .create(PI, UserID::new(user.id().as_i64() + 42));
+        let transaction = state.transaction_store().create(PI, user.id()).unwrap();
 
-        assert_eq!(transaction, Err(TransactionError::InvalidUser));
+        let created_at: Option<OffsetDateTime> = state
+            .transaction_store()
+            .connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT created_at FROM \"transaction\" WHERE id = ?1",
+                [transaction.id()],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(created_at.is_some());
     }
 
     #[test]
-    fn create_fails_on_invalid_category_id() {
+    fn create_keeps_raw_description() {
+        let (mut state, user) = get_app_state_and_test_user();
+
+        let transaction = state
+            .transaction_store()
+            .create_from_builder(
+                Transaction::build(PI, user.id()).description("WARBURTON  ST  ;".to_string()),
+            )
+            .unwrap();
+
+        assert_eq!(transaction.description(), "WARBURTON ST");
+        let raw_description: Option<String> = state
+            .transaction_store()
+            .connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT raw_description FROM \"transaction\" WHERE id = ?1",
+                [transaction.id()],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(raw_description.as_deref(), Some("WARBURTON  ST  ;"));
+    }
+
+    #[test]
+    fn create_fails_on_invalid_user_id() {
+        let (mut state, user) = get_app_state_and_test_user();
+
+        let transaction = state
+            .transaction_store()
+            .create(PI, UserID::new(user.id().as_i64() + 42));
+
+        assert_eq!(transaction, Err(TransactionError::InvalidUser));
+    }
+
+    #[test]
+    fn create_fails_on_invalid_category_id() {
         let (mut state, user) = get_app_state_and_test_user();
 
         let transaction = state
@@ -348,6 +1019,79 @@ mod sqlite_transaction_store_tests {
         assert_eq!(transaction, Err(TransactionError::InvalidCategory));
     }
 
+    #[test]
+    fn create_batch_reports_each_outcome() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let new_transaction =
+            |amount: f64, category_id: Option<i64>, external_id: &str| NewTransaction {
+                builder: Transaction::build(amount, user.id()).category(category_id),
+                external_id: Some(external_id.to_string()),
+            };
+        let existing = state
+            .transaction_store()
+            .create_batch(vec![new_transaction(1.0, None, "a")])
+            .unwrap();
+        let existing_id = match existing.as_slice() {
+            [BatchOutcome::Created(transaction)] => transaction.id(),
+            outcomes => panic!("expected one created transaction, got {outcomes:?}"),
+        };
+
+        let outcomes = state
+            .transaction_store()
+            .create_batch(vec![
+                new_transaction(2.0, None, "a"),
+                new_transaction(3.0, Some(999), "b"),
+                new_transaction(4.0, None, "c"),
+                new_transaction(5.0, None, "c"),
+            ])
+            .unwrap();
+
+        assert_eq!(outcomes.len(), 4);
+        assert_eq!(outcomes[0], BatchOutcome::Duplicate(existing_id));
+        assert_eq!(
+            outcomes[1],
+            BatchOutcome::Failed(TransactionError::InvalidCategory)
+        );
+        let created_id = match &outcomes[2] {
+            BatchOutcome::Created(transaction) => {
+                assert_eq!(transaction.amount(), 4.0);
+                transaction.id()
+            }
+            outcome => panic!("expected a created transaction, got {outcome:?}"),
+        };
+        assert_eq!(outcomes[3], BatchOutcome::Duplicate(created_id));
+        assert_eq!(
+            state
+                .transaction_store()
+                .get_by_user_id(user.id())
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn create_batch_creates_nothing_on_invalid_user() {
+        let (mut state, user) = get_app_state_and_test_user();
+
+        let result = state.transaction_store().create_batch(vec![
+            NewTransaction {
+                builder: Transaction::build(1.0, user.id()),
+                external_id: None,
+            },
+            NewTransaction {
+                builder: Transaction::build(2.0, UserID::new(user.id().as_i64() + 1)),
+                external_id: None,
+            },
+        ]);
+
+        assert_eq!(result, Err(TransactionError::InvalidUser));
+        assert_eq!(
+            state.transaction_store().get_by_user_id(user.id()).unwrap(),
+            vec![]
+        );
+    }
+
     #[test]
     fn create_fails_on_user_id_mismatch() {
         // `user` is the owner of `someone_elses_category`.
@@ -536,6 +1280,164 @@ mod sqlite_transaction_store_tests {
         assert_eq!(got.len(), 5, "got {} transactions, want 5", got.len());
     }
 
+    #[test]
+    fn cursor_pages_do_not_skip_or_repeat_transactions_on_same_date() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let today = OffsetDateTime::now_utc().date();
+        let mut want = vec![];
+
+        // Several transactions share each date, so page boundaries fall between them.
+        for i in 0..10 {
+            let transaction = state
+                .transaction_store()
+                .create_from_builder(
+                    TransactionBuilder::new(i as f64, user.id())
+                        .date(today - Duration::days(i % 3))
+                        .unwrap(),
+                )
+                .unwrap();
+            want.push(transaction);
+        }
+
+        want.sort_by_key(|transaction| (*transaction.date(), transaction.id()));
+
+        for (sort_date, want) in [
+            (SortOrder::Ascending, want.clone()),
+            (SortOrder::Descending, want.into_iter().rev().collect()),
+        ] {
+            let mut got = vec![];
+            let mut after = None;
+
+            loop {
+                let page = state
+                    .transaction_store()
+                    .get_query(TransactionQuery {
+                        user_id: Some(user.id()),
+                        sort_date: Some(sort_date),
+                        limit: Some(3),
+                        after,
+                        ..Default::default()
+                    })
+                    .unwrap();
+
+                let Some(last) = page.last() else {
+                    break;
+                };
+                after = Some(TransactionCursor::of(last));
+                got.extend(page);
+            }
+
+            assert_eq!(got, want);
+        }
+    }
+
+    #[test]
+    fn count_ignores_cursor() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let first = state.transaction_store().create(1.0, user.id()).unwrap();
+        state.transaction_store().create(2.0, user.id()).unwrap();
+
+        let count = state
+            .transaction_store()
+            .count_query(TransactionQuery {
+                sort_date: Some(SortOrder::Ascending),
+                after: Some(TransactionCursor::of(&first)),
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn sum_adds_mixed_sign_amounts_matching_filter() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let other_user = state
+            .user_store()
+            .create(
+                "other@test.com".parse().unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+        let store = state.transaction_store();
+        for amount in [100.25, -40.5, -9.75] {
+            store.create(amount, user.id()).unwrap();
+        }
+        store.create(1000.0, other_user.id()).unwrap();
+
+        let sum = store
+            .sum_query(TransactionQuery {
+                user_id: Some(user.id()),
+                limit: Some(1),
+                offset: Some(1),
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert_eq!(sum, 50.0);
+    }
+
+    #[test]
+    fn sum_of_no_transactions_is_zero() {
+        let (mut state, user) = get_app_state_and_test_user();
+
+        let sum = state
+            .transaction_store()
+            .sum_query(TransactionQuery {
+                user_id: Some(user.id()),
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert_eq!(sum, 0.0);
+    }
+
+    #[test]
+    fn cursor_round_trips_through_encoding() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let transaction = state.transaction_store().create(1.0, user.id()).unwrap();
+        let cursor = TransactionCursor::of(&transaction);
+
+        let encoded = cursor.encode();
+
+        assert!(encoded
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        assert_eq!(TransactionCursor::decode(&encoded), Ok(cursor));
+        assert!(TransactionCursor::decode("not a cursor").is_err());
+        assert!(TransactionCursor::decode("bm90IGEgY3Vyc29y").is_err());
+    }
+
+    #[test]
+    fn get_page_of_transactions_and_count() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let today = OffsetDateTime::now_utc().date();
+        let mut want = vec![];
+
+        for i in 1..=5 {
+            let transaction = state
+                .transaction_store()
+                .create_from_builder(TransactionBuilder::new(i as f64, user.id()).today(today))
+                .unwrap();
+            want.push(transaction);
+        }
+
+        want.reverse();
+        let query = || TransactionQuery {
+            user_id: Some(user.id()),
+            sort_date: Some(SortOrder::Descending),
+            limit: Some(2),
+            offset: Some(2),
+            ..Default::default()
+        };
+
+        let got = state.transaction_store().get_query(query()).unwrap();
+        let count = state.transaction_store().count_query(query()).unwrap();
+
+        assert_eq!(got, want[2..4]);
+        assert_eq!(count, 5);
+    }
+
     #[test]
     fn get_transactions_descending_date() {
         let (mut state, user) = get_app_state_and_test_user();
@@ -575,4 +1477,477 @@ mod sqlite_transaction_store_tests {
             "got transactions that were not sorted in descending order."
         );
     }
+
+    #[test]
+    fn set_ignored_succeeds() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let store = state.transaction_store();
+        let transaction = store.create(PI, user.id()).unwrap();
+
+        let got = store.set_ignored(transaction.id(), true).unwrap();
+
+        assert!(got.is_ignored());
+        assert_eq!(store.get(transaction.id()), Ok(got));
+    }
+
+    #[test]
+    fn set_ignored_fails_on_invalid_id() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let store = state.transaction_store();
+        let transaction = store.create(PI, user.id()).unwrap();
+
+        let result = store.set_ignored(transaction.id() + 1, true);
+
+        assert_eq!(result, Err(TransactionError::NotFound));
+    }
+
+    #[test]
+    fn get_query_filters_ignored_transactions() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let store = state.transaction_store();
+
+        let visible = store
+            .create_from_builder(TransactionBuilder::new(1.0, user.id()))
+            .unwrap();
+        let ignored = store
+            .create_from_builder(TransactionBuilder::new(0.0, user.id()).ignored(true))
+            .unwrap();
+
+        let cases = [
+            (IgnoredFilter::Exclude, vec![visible.clone()]),
+            (IgnoredFilter::Include, vec![visible, ignored.clone()]),
+            (IgnoredFilter::Only, vec![ignored]),
+        ];
+
+        for (filter, want) in cases {
+            let got = store
+                .get_query(TransactionQuery {
+                    user_id: Some(user.id()),
+                    ignored: filter,
+                    ..Default::default()
+                })
+                .unwrap();
+
+            assert_eq!(got, want, "got {got:?} for {filter:?}, want {want:?}");
+        }
+    }
+
+    #[test]
+    fn get_query_filters_by_category() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let category = state
+            .category_store()
+            .create(CategoryName::new_unchecked("Food"), user.id())
+            .unwrap();
+        let store = state.transaction_store();
+
+        let want = store
+            .create_from_builder(
+                TransactionBuilder::new(1.0, user.id()).category(Some(category.id())),
+            )
+            .unwrap();
+        store
+            .create_from_builder(TransactionBuilder::new(2.0, user.id()))
+            .unwrap();
+
+        let got = store
+            .get_query(TransactionQuery {
+                category_id: Some(category.id()),
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert_eq!(got, vec![want]);
+    }
+
+    #[test]
+    fn get_query_filters_uncategorised_transactions() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let category = state
+            .category_store()
+            .create(CategoryName::new_unchecked("Food"), user.id())
+            .unwrap();
+        let store = state.transaction_store();
+
+        store
+            .create_from_builder(
+                TransactionBuilder::new(1.0, user.id()).category(Some(category.id())),
+            )
+            .unwrap();
+        let want = store
+            .create_from_builder(TransactionBuilder::new(2.0, user.id()))
+            .unwrap();
+
+        let got = store
+            .get_query(TransactionQuery {
+                uncategorised_only: true,
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert_eq!(got, vec![want]);
+    }
+
+    #[test]
+    fn get_query_matches_description_ignoring_case_and_wildcards() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let store = state.transaction_store();
+        let coffee = store
+            .create_from_builder(
+                TransactionBuilder::new(-5.0, user.id())
+                    .description("BUCKSTARS COFFEE".to_string()),
+            )
+            .unwrap();
+        let discount = store
+            .create_from_builder(
+                TransactionBuilder::new(-10.0, user.id()).description("50% off sale".to_string()),
+            )
+            .unwrap();
+        store
+            .create_from_builder(
+                TransactionBuilder::new(-20.0, user.id()).description("500 bolts".to_string()),
+            )
+            .unwrap();
+
+        let search = |text: &str| {
+            store
+                .get_query(TransactionQuery {
+                    description_contains: Some(text.to_string()),
+                    ..Default::default()
+                })
+                .unwrap()
+        };
+
+        assert_eq!(search("buckstars"), vec![coffee]);
+        assert_eq!(search("50%"), vec![discount]);
+        assert!(search("bolt_").is_empty());
+    }
+
+    #[test]
+    fn get_query_excludes_categories() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let transfers = state
+            .category_store()
+            .create(CategoryName::new_unchecked("Transfers"), user.id())
+            .unwrap();
+        let food = state
+            .category_store()
+            .create(CategoryName::new_unchecked("Food"), user.id())
+            .unwrap();
+        let store = state.transaction_store();
+
+        store
+            .create_from_builder(
+                TransactionBuilder::new(1.0, user.id()).category(Some(transfers.id())),
+            )
+            .unwrap();
+        let food_transaction = store
+            .create_from_builder(TransactionBuilder::new(2.0, user.id()).category(Some(food.id())))
+            .unwrap();
+        // Uncategorised transactions are not in an excluded category, so they are kept.
+        let uncategorised = store
+            .create_from_builder(TransactionBuilder::new(3.0, user.id()))
+            .unwrap();
+
+        let got = store
+            .get_query(TransactionQuery {
+                exclude_category_ids: vec![transfers.id()],
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert_eq!(got, vec![food_transaction, uncategorised]);
+        assert_eq!(
+            store
+                .count_query(TransactionQuery {
+                    exclude_category_ids: vec![transfers.id(), food.id()],
+                    ..Default::default()
+                })
+                .unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn set_reconciled_succeeds() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let store = state.transaction_store();
+        let first = store.create(1.0, user.id()).unwrap();
+        let second = store.create(2.0, user.id()).unwrap();
+
+        store.set_reconciled(&[first.id(), second.id()]).unwrap();
+
+        assert!(store.get(first.id()).unwrap().is_reconciled());
+        assert!(store.get(second.id()).unwrap().is_reconciled());
+    }
+
+    #[test]
+    fn set_reconciled_rolls_back_on_invalid_id() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let store = state.transaction_store();
+        let transaction = store.create(1.0, user.id()).unwrap();
+
+        let result = store.set_reconciled(&[transaction.id(), transaction.id() + 1]);
+
+        assert_eq!(result, Err(TransactionError::NotFound));
+        assert!(!store.get(transaction.id()).unwrap().is_reconciled());
+    }
+
+    #[test]
+    fn get_query_filters_reconciled_transactions() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let store = state.transaction_store();
+
+        let unreconciled = store
+            .create_from_builder(TransactionBuilder::new(1.0, user.id()))
+            .unwrap();
+        let reconciled = store
+            .create_from_builder(TransactionBuilder::new(2.0, user.id()).reconciled(true))
+            .unwrap();
+
+        let cases = [
+            (None, vec![unreconciled.clone(), reconciled.clone()]),
+            (Some(false), vec![unreconciled]),
+            (Some(true), vec![reconciled]),
+        ];
+
+        for (filter, want) in cases {
+            let got = store
+                .get_query(TransactionQuery {
+                    reconciled: filter,
+                    ..Default::default()
+                })
+                .unwrap();
+
+            assert_eq!(got, want, "got {got:?} for {filter:?}, want {want:?}");
+        }
+    }
+
+    #[test]
+    fn update_succeeds() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let category = state
+            .category_store()
+            .create(CategoryName::new_unchecked("Food"), user.id())
+            .unwrap();
+        let store = state.transaction_store();
+        let transaction = store.create(PI, user.id()).unwrap();
+        let date = OffsetDateTime::now_utc().date() - Duration::days(1);
+
+        let updated_transaction = store
+            .update(
+                transaction.id(),
+                TransactionBuilder::new(1.23, user.id())
+                    .date(date)
+                    .unwrap()
+                    .description("foo".to_string())
+                    .category(Some(category.id())),
+            )
+            .unwrap();
+
+        assert_eq!(updated_transaction.id(), transaction.id());
+        assert_eq!(updated_transaction.amount(), 1.23);
+        assert_eq!(updated_transaction.date(), &date);
+        assert_eq!(updated_transaction.description(), "foo");
+        assert_eq!(updated_transaction.category_id(), Some(category.id()));
+        assert_eq!(store.get(transaction.id()), Ok(updated_transaction));
+    }
+
+    #[test]
+    fn update_fails_on_invalid_id() {
+        let (mut state, user) = get_app_state_and_test_user();
+
+        let result = state
+            .transaction_store()
+            .update(42, TransactionBuilder::new(1.23, user.id()));
+
+        assert_eq!(result, Err(TransactionError::NotFound));
+    }
+
+    #[test]
+    fn update_fails_on_invalid_category_id() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let store = state.transaction_store();
+        let transaction = store.create(PI, user.id()).unwrap();
+
+        let result = store.update(
+            transaction.id(),
+            TransactionBuilder::new(1.23, user.id()).category(Some(999)),
+        );
+
+        assert_eq!(result, Err(TransactionError::InvalidCategory));
+        assert_eq!(store.get(transaction.id()), Ok(transaction));
+    }
+
+    #[test]
+    fn delete_succeeds() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let store = state.transaction_store();
+        let transaction = store.create(PI, user.id()).unwrap();
+
+        store.delete(transaction.id()).unwrap();
+
+        assert_eq!(store.get(transaction.id()), Err(TransactionError::NotFound));
+    }
+
+    #[test]
+    fn delete_fails_on_invalid_id() {
+        let (mut state, _) = get_app_state_and_test_user();
+
+        assert_eq!(
+            state.transaction_store().delete(42),
+            Err(TransactionError::NotFound)
+        );
+    }
+
+    fn get_audit_actions(state: &mut SQLAppState) -> Vec<(String, i64)> {
+        state
+            .transaction_store()
+            .connection
+            .lock()
+            .unwrap()
+            .prepare("SELECT action, entity_id FROM audit_log WHERE entity_type = 'transaction' ORDER BY id")
+            .unwrap()
+            .query_map((), |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn changes_are_audited() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let store = state.transaction_store();
+        let transaction = store.create(PI, user.id()).unwrap();
+        let id = transaction.id();
+
+        store
+            .update(id, TransactionBuilder::new(1.23, user.id()))
+            .unwrap();
+        store.set_ignored(id, true).unwrap();
+        store.set_reconciled(&[id]).unwrap();
+        store.delete(id).unwrap();
+
+        assert_eq!(
+            get_audit_actions(&mut state),
+            vec![
+                ("create".to_string(), id),
+                ("update".to_string(), id),
+                ("update".to_string(), id),
+                ("update".to_string(), id),
+                ("delete".to_string(), id),
+            ]
+        );
+    }
+
+    #[test]
+    fn failed_changes_are_not_audited() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let store = state.transaction_store();
+        let transaction = store.create(PI, user.id()).unwrap();
+
+        assert!(store
+            .update(
+                transaction.id(),
+                TransactionBuilder::new(1.23, user.id()).category(Some(999)),
+            )
+            .is_err());
+        assert!(store.set_reconciled(&[transaction.id(), 42]).is_err());
+        assert!(store.delete(42).is_err());
+
+        assert_eq!(
+            get_audit_actions(&mut state),
+            vec![("create".to_string(), transaction.id())]
+        );
+    }
+
+    /// Create a transaction for `user` with the given details, dated yesterday.
+    fn create_transaction(
+        state: &mut SQLAppState,
+        user: &User,
+        amount: f64,
+        description: &str,
+        category_id: Option<i64>,
+    ) -> Transaction {
+        let yesterday = OffsetDateTime::now_utc().date() - Duration::days(1);
+
+        state
+            .transaction_store()
+            .create_from_builder(
+                Transaction::build(amount, user.id())
+                    .date(yesterday)
+                    .unwrap()
+                    .description(description.to_string())
+                    .category(category_id),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn get_duplicates_groups_matching_transactions() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let other_user = state
+            .user_store()
+            .create(
+                "other@test.com".parse().unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+        let first = create_transaction(&mut state, &user, -5.0, "COFFEE", None);
+        let second = create_transaction(&mut state, &user, -5.0, "COFFEE  ;", None);
+        create_transaction(&mut state, &user, -5.5, "COFFEE", None);
+        create_transaction(&mut state, &user, -5.0, "TEA", None);
+        create_transaction(&mut state, &other_user, -5.0, "COFFEE", None);
+
+        let groups = state.transaction_store().get_duplicates(user.id()).unwrap();
+
+        assert_eq!(groups, vec![vec![first, second]]);
+    }
+
+    #[test]
+    fn merge_duplicates_deletes_duplicates_and_keeps_category() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let category = state
+            .category_store()
+            .create(CategoryName::new_unchecked("Food"), user.id())
+            .unwrap();
+        let kept = create_transaction(&mut state, &user, -5.0, "COFFEE", None);
+        let duplicate = create_transaction(&mut state, &user, -5.0, "COFFEE", Some(category.id()));
+
+        let merged = state
+            .transaction_store()
+            .merge_duplicates(kept.id(), &[duplicate.id()])
+            .unwrap();
+
+        assert_eq!(merged.id(), kept.id());
+        assert_eq!(merged.category_id(), Some(category.id()));
+        assert_eq!(
+            state.transaction_store().get(duplicate.id()),
+            Err(TransactionError::NotFound)
+        );
+        assert!(state
+            .transaction_store()
+            .get_duplicates(user.id())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn merge_duplicates_changes_nothing_if_any_are_not_duplicates() {
+        let (mut state, user) = get_app_state_and_test_user();
+        let kept = create_transaction(&mut state, &user, -5.0, "COFFEE", None);
+        let duplicate = create_transaction(&mut state, &user, -5.0, "COFFEE", None);
+        let not_duplicate = create_transaction(&mut state, &user, -5.0, "TEA", None);
+
+        let result = state
+            .transaction_store()
+            .merge_duplicates(kept.id(), &[duplicate.id(), not_duplicate.id()]);
+
+        assert_eq!(result, Err(TransactionError::NotFound));
+        assert_eq!(state.transaction_store().get(duplicate.id()), Ok(duplicate));
+        assert_eq!(
+            state.transaction_store().get(not_duplicate.id()),
+            Ok(not_duplicate)
+        );
+    }
 }
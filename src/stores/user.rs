@@ -1,15 +1,27 @@
 //! Defines the user store trait and an implentation for the SQLite backend.
-use std::sync::{Arc, Mutex};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+};
 
 use email_address::EmailAddress;
-use rusqlite::{Connection, Row};
+use rusqlite::{types::Type, Connection, DatabaseName, OpenFlags, OptionalExtension, Row};
 use thiserror::Error;
 
 use crate::{
-    db::{CreateTable, MapRow},
-    models::{PasswordHash, User, UserID},
+    db::{
+        backup_database,
+        maintenance::{run_maintenance, MaintenanceReport, MaintenanceTask},
+        DatabaseError, MapRow, BUSY_TIMEOUT_MS,
+    },
+    models::{
+        clamp_month_start_day, AuditLogEntry, LandingPage, PasswordHash, Theme, User, UserID,
+        UserPreferences, WeekStart, DEFAULT_TIMEZONE,
+    },
 };
 
+use super::audit::{count_audit_log, get_audit_log, AuditLogQuery};
+
 /// Handles the creation and retrieval of User objects.
 pub trait UserStore {
     /// Create a new user.
@@ -19,6 +31,20 @@ pub trait UserStore {
         password_hash: PasswordHash,
     ) -> Result<User, UserError>;
 
+    /// Create a new user and use up the invite with the specified `invite_code` at the same
+    /// time, so that an invite cannot be used twice.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::InvalidInvite] if there is no invite with the specified
+    /// `invite_code`, in which case the user is not created.
+    fn create_with_invite(
+        &mut self,
+        email: EmailAddress,
+        password_hash: PasswordHash,
+        invite_code: &str,
+    ) -> Result<User, UserError>;
+
     /// Get a user by their ID.
     fn get(&self, id: UserID) -> Result<User, UserError>;
 
@@ -26,6 +52,85 @@ pub trait UserStore {
     ///
     /// Returns [UserError::NotFound] if no user with the given email exists.
     fn get_by_email(&self, email: &EmailAddress) -> Result<User, UserError>;
+
+    /// Replace the password hash of the user with the specified `id`.
+    ///
+    /// This also increments the user's session generation so that existing sessions are logged out.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::NotFound] if there is no user with the specified `id`.
+    fn set_password(&mut self, id: UserID, password_hash: PasswordHash) -> Result<User, UserError>;
+
+    /// Increment the session generation of the user with the specified `id`, which invalidates
+    /// all of the user's existing auth cookies.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::NotFound] if there is no user with the specified `id`.
+    fn increment_session_generation(&mut self, id: UserID) -> Result<User, UserError>;
+
+    /// Replace the password hash of the user with the specified `id` without logging out their
+    /// existing sessions, e.g. to rehash the same password with a higher cost.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::NotFound] if there is no user with the specified `id`.
+    fn update_password_hash(
+        &mut self,
+        id: UserID,
+        password_hash: PasswordHash,
+    ) -> Result<(), UserError>;
+
+    /// Replace the email address of the user with the specified `id`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::NotFound] if there is no user with the specified `id` or
+    /// [UserError::DuplicateEmail] if another user already has the email address.
+    fn set_email(&mut self, id: UserID, email: EmailAddress) -> Result<User, UserError>;
+
+    /// Delete the user with the specified `id` along with all of their categories and
+    /// transactions.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::NotFound] if there is no user with the specified `id` or
+    /// [UserError::OwnerCannotBeDeleted] if the user owns the site.
+    fn delete(&mut self, id: UserID) -> Result<(), UserError>;
+
+    /// Get the preferences of the user `user_id`, or the defaults if they have not changed any.
+    fn get_preferences(&self, user_id: UserID) -> Result<UserPreferences, UserError>;
+
+    /// Set the timezone of the user `user_id` to the IANA timezone name `timezone`.
+    ///
+    /// The caller is responsible for checking that `timezone` is a known timezone.
+    fn set_timezone(
+        &mut self,
+        user_id: UserID,
+        timezone: &str,
+    ) -> Result<UserPreferences, UserError>;
+
+    /// Set whether the user `user_id` sees light or dark pages.
+    fn set_theme(&mut self, user_id: UserID, theme: Theme) -> Result<UserPreferences, UserError>;
+
+    /// Set the page that the user `user_id` is sent to after logging in.
+    fn set_landing_page(
+        &mut self,
+        user_id: UserID,
+        landing_page: LandingPage,
+    ) -> Result<UserPreferences, UserError>;
+
+    /// Set the day that the user `user_id`'s weeks start on and the day of the month that their
+    /// months start on.
+    ///
+    /// `month_start_day` is clamped with [clamp_month_start_day].
+    fn set_date_periods(
+        &mut self,
+        user_id: UserID,
+        week_start: WeekStart,
+        month_start_day: u8,
+    ) -> Result<UserPreferences, UserError>;
 }
 
 /// Errors that can occur during the creation or retrieval of a user.
@@ -41,9 +146,22 @@ pub enum UserError {
     #[error("no user found with the given details")]
     NotFound,
 
+    /// There is no unused invite with the given code. The client should try again with a
+    /// different invite code.
+    #[error("the invite code is invalid or has already been used")]
+    InvalidInvite,
+
+    /// The user owns the site, so deleting their account would leave nobody who can manage it.
+    #[error("the owner of the site cannot be deleted")]
+    OwnerCannotBeDeleted,
+
     /// An unhandled/unexpected SQL error.
     #[error("an error occurred while creating the user: {0}")]
     SqlError(rusqlite::Error),
+
+    /// The database lock was poisoned by a thread that panicked while holding it.
+    #[error("the database lock is poisoned")]
+    DatabaseLockError,
 }
 
 impl From<rusqlite::Error> for UserError {
@@ -72,15 +190,138 @@ impl SQLiteUserStore {
     pub fn new(connection: Arc<Mutex<Connection>>) -> Self {
         Self { connection }
     }
+
+    /// Get exclusive access to the database connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::DatabaseLockError] if another thread panicked while holding the
+    /// database lock.
+    fn connection(&self) -> Result<MutexGuard<'_, Connection>, UserError> {
+        self.connection
+            .lock()
+            .map_err(|_| UserError::DatabaseLockError)
+    }
+
+    /// Get the database connection that this store shares with the other SQLite stores, e.g. to
+    /// create a store for the data that belongs to users.
+    pub(crate) fn shared_connection(&self) -> Arc<Mutex<Connection>> {
+        self.connection.clone()
+    }
+
+    /// Check that the database can be queried by acquiring the database lock and running a
+    /// trivial query, and that it can be written to.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem if the lock is poisoned, the query fails or the
+    /// database was opened read-only, e.g. because the file is not writable.
+    pub fn check_connection(&self) -> Result<(), String> {
+        let connection = self
+            .connection
+            .lock()
+            .map_err(|error| format!("the database lock is poisoned: {error}"))?;
+
+        connection
+            .query_row("SELECT 1", (), |_| Ok(()))
+            .map_err(|error| error.to_string())?;
+
+        match connection.is_readonly(DatabaseName::Main) {
+            Ok(false) => Ok(()),
+            Ok(true) => Err("the database is read-only".to_owned()),
+            Err(error) => Err(error.to_string()),
+        }
+    }
+
+    /// Get the ID of the user that owns the site, i.e. the first user to register, or `None` if
+    /// there are no users.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::SqlError] if there is an SQL error.
+    pub fn get_owner_id(&self) -> Result<Option<UserID>, UserError> {
+        let owner_id: Option<i64> = self
+            .connection()?
+            .query_row("SELECT id FROM user WHERE is_owner", (), |row| row.get(0))
+            .optional()?;
+
+        Ok(owner_id.map(UserID::new))
+    }
+
+    /// Back up the whole database to a new database file at `destination`.
+    ///
+    /// If the database is stored in a file, the backup is copied from a separate read-only
+    /// connection so that the database lock is only held for long enough to find the file.
+    /// In-memory databases cannot be shared between connections, so their backup is copied with
+    /// the lock held.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [DatabaseError] if the backup could not be created.
+    pub fn backup(&self, destination: &Path) -> Result<(), DatabaseError> {
+        let connection = self
+            .connection
+            .lock()
+            .map_err(|_| DatabaseError::DatabaseLockError)?;
+
+        match connection.path() {
+            Some(path) if !path.is_empty() => {
+                let path = path.to_string();
+                drop(connection);
+
+                let source = Connection::open_with_flags(
+                    path,
+                    OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+                )?;
+                source.pragma_update(None, "busy_timeout", BUSY_TIMEOUT_MS)?;
+                backup_database(&source, destination)
+            }
+            _ => backup_database(&connection, destination),
+        }
+    }
+
+    /// Run a maintenance `task` such as VACUUM on the database.
+    ///
+    /// The database lock is held until the task finishes, which may take several seconds on large
+    /// databases.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [DatabaseError] if the task failed.
+    pub fn run_maintenance(
+        &self,
+        task: MaintenanceTask,
+    ) -> Result<MaintenanceReport, DatabaseError> {
+        let connection = self
+            .connection
+            .lock()
+            .map_err(|_| DatabaseError::DatabaseLockError)?;
+
+        run_maintenance(&connection, task)
+    }
+
+    /// Get a page of the audit log entries matching `query`, newest first, and the total number
+    /// of matching entries.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::SqlError] if there is an SQL error.
+    pub fn get_audit_log(
+        &self,
+        query: AuditLogQuery,
+    ) -> Result<(Vec<AuditLogEntry>, u64), UserError> {
+        let connection = self.connection()?;
+
+        let entries = get_audit_log(&connection, query)?;
+        let count = count_audit_log(&connection, query)?;
+
+        Ok((entries, count))
+    }
 }
 
 impl UserStore for SQLiteUserStore {
     /// Create and insert a new user into the database.
     ///
-    /// # Panics
-    ///
-    /// Panics if the database lock is already acquired by the same thread or is poisoned.
-    ///
     /// # Errors
     ///
     /// Returns a [UserError::SqlError] if an SQL related error occurred.
@@ -89,10 +330,11 @@ impl UserStore for SQLiteUserStore {
         email: EmailAddress,
         password_hash: PasswordHash,
     ) -> Result<User, UserError> {
-        let connection = self.connection.lock().unwrap();
+        let connection = self.connection()?;
 
         connection.execute(
-            "INSERT INTO user (email, password) VALUES (?1, ?2)",
+            "INSERT INTO user (email, password, is_owner) \
+            VALUES (?1, ?2, NOT EXISTS (SELECT 1 FROM user WHERE is_owner))",
             (&email.to_string(), password_hash.to_string()),
         )?;
 
@@ -101,56 +343,278 @@ impl UserStore for SQLiteUserStore {
         Ok(User::new(id, email, password_hash))
     }
 
-    /// Get the user from the database that has the specified `id`, or return [UserError::NotFound] if such user does not exist.
+    /// Delete the invite with the specified `invite_code` and insert a new user in a single
+    /// database transaction.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// Panics if the database lock is already acquired by the same thread or is poisoned.
+    /// Returns a [UserError::InvalidInvite] if there is no invite with the specified
+    /// `invite_code`, [UserError::DuplicateEmail] if the email is already in use or
+    /// [UserError::SqlError] if an SQL related error occurred. The invite is kept if there is an
+    /// error.
+    fn create_with_invite(
+        &mut self,
+        email: EmailAddress,
+        password_hash: PasswordHash,
+        invite_code: &str,
+    ) -> Result<User, UserError> {
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction()?;
+
+        let rows_affected =
+            transaction.execute("DELETE FROM invite WHERE code = ?1", (invite_code,))?;
+
+        if rows_affected != 1 {
+            // Dropping the transaction rolls it back.
+            return Err(UserError::InvalidInvite);
+        }
+
+        transaction.execute(
+            "INSERT INTO user (email, password, is_owner) \
+            VALUES (?1, ?2, NOT EXISTS (SELECT 1 FROM user WHERE is_owner))",
+            (&email.to_string(), password_hash.to_string()),
+        )?;
+
+        let id = UserID::new(transaction.last_insert_rowid());
+        transaction.commit()?;
+
+        Ok(User::new(id, email, password_hash))
+    }
+
+    /// Get the user from the database that has the specified `id`, or return [UserError::NotFound] if such user does not exist.
     ///
     /// # Errors
     ///
     /// Returns a [UserError::NotFound] error if there is no user with the specified email or [UserError::SqlError] if there are SQL related errors.
     fn get(&self, id: UserID) -> Result<User, UserError> {
-        self.connection
-            .lock()
-            .unwrap()
-            .prepare("SELECT id, email, password FROM user WHERE id = :id")?
+        self.connection()?
+            .prepare("SELECT id, email, password, session_generation FROM user WHERE id = :id")?
             .query_row(&[(":id", &id.as_i64())], SQLiteUserStore::map_row)
             .map_err(|e| e.into())
     }
 
     /// Get the user from the database that has the specified `email` address, or return [UserError::NotFound] if such user does not exist.
     ///
-    /// # Panics
-    ///
-    /// Panics if the database lock is already acquired by the same thread or is poisoned.
-    ///
     /// # Errors
     ///
     /// Returns a [UserError::NotFound] error if there is no user with the specified email or [UserError::SqlError] there are SQL related errors.
     fn get_by_email(&self, email: &EmailAddress) -> Result<User, UserError> {
-        self.connection
-            .lock()
-            .unwrap()
-            .prepare("SELECT id, email, password FROM user WHERE email = :email")?
+        self.connection()?
+            .prepare(
+                "SELECT id, email, password, session_generation FROM user WHERE email = :email",
+            )?
             .query_row(&[(":email", &email.to_string())], SQLiteUserStore::map_row)
             .map_err(|e| e.into())
     }
-}
 
-impl CreateTable for SQLiteUserStore {
-    fn create_table(connection: &Connection) -> Result<(), rusqlite::Error> {
-        connection.execute(
-            "CREATE TABLE user (
-                    id INTEGER PRIMARY KEY,
-                    email TEXT UNIQUE NOT NULL,
-                    password TEXT NOT NULL
-                    )",
-            (),
+    /// Replace the password hash of the user with the specified `id` and increment their session generation.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::NotFound] error if there is no user with the specified `id` or [UserError::SqlError] if there are SQL related errors.
+    fn set_password(&mut self, id: UserID, password_hash: PasswordHash) -> Result<User, UserError> {
+        let rows_affected = self.connection()?.execute(
+            "UPDATE user SET password = ?1, session_generation = session_generation + 1 \
+            WHERE id = ?2",
+            (password_hash.to_string(), id.as_i64()),
+        )?;
+
+        if rows_affected == 0 {
+            return Err(UserError::NotFound);
+        }
+
+        self.get(id)
+    }
+
+    /// Increment the session generation of the user with the specified `id`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::NotFound] error if there is no user with the specified `id` or [UserError::SqlError] if there are SQL related errors.
+    fn increment_session_generation(&mut self, id: UserID) -> Result<User, UserError> {
+        let rows_affected = self.connection()?.execute(
+            "UPDATE user SET session_generation = session_generation + 1 WHERE id = ?1",
+            (id.as_i64(),),
+        )?;
+
+        if rows_affected == 0 {
+            return Err(UserError::NotFound);
+        }
+
+        self.get(id)
+    }
+
+    /// Replace the password hash of the user with the specified `id`, keeping their session generation.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::NotFound] error if there is no user with the specified `id` or [UserError::SqlError] if there are SQL related errors.
+    fn update_password_hash(
+        &mut self,
+        id: UserID,
+        password_hash: PasswordHash,
+    ) -> Result<(), UserError> {
+        let rows_affected = self.connection()?.execute(
+            "UPDATE user SET password = ?1 WHERE id = ?2",
+            (password_hash.to_string(), id.as_i64()),
+        )?;
+
+        if rows_affected == 0 {
+            return Err(UserError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    /// Replace the email address of the user with the specified `id`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::NotFound] error if there is no user with the specified `id`, [UserError::DuplicateEmail] if the email is already in use or [UserError::SqlError] if there are SQL related errors.
+    fn set_email(&mut self, id: UserID, email: EmailAddress) -> Result<User, UserError> {
+        let rows_affected = self.connection()?.execute(
+            "UPDATE user SET email = ?1 WHERE id = ?2",
+            (email.to_string(), id.as_i64()),
+        )?;
+
+        if rows_affected == 0 {
+            return Err(UserError::NotFound);
+        }
+
+        self.get(id)
+    }
+
+    /// Delete the user with the specified `id` and the rows in the category and transaction
+    /// tables that belong to them in a single database transaction.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::NotFound] error if there is no user with the specified `id` or [UserError::SqlError] if there are SQL related errors.
+    fn delete(&mut self, id: UserID) -> Result<(), UserError> {
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction()?;
+
+        let is_owner: bool = transaction.query_row(
+            "SELECT is_owner FROM user WHERE id = ?1",
+            (id.as_i64(),),
+            |row| row.get(0),
+        )?;
+        if is_owner {
+            return Err(UserError::OwnerCannotBeDeleted);
+        }
+
+        transaction.execute(
+            "DELETE FROM \"transaction\" WHERE user_id = ?1",
+            (id.as_i64(),),
         )?;
+        transaction.execute("DELETE FROM category WHERE user_id = ?1", (id.as_i64(),))?;
+        transaction.execute("DELETE FROM api_token WHERE user_id = ?1", (id.as_i64(),))?;
+        transaction.execute("DELETE FROM webhook WHERE user_id = ?1", (id.as_i64(),))?;
+        transaction.execute("DELETE FROM user WHERE id = ?1", (id.as_i64(),))?;
+        transaction.commit()?;
 
         Ok(())
     }
+
+    fn get_preferences(&self, user_id: UserID) -> Result<UserPreferences, UserError> {
+        let preferences = self
+            .connection()?
+            .query_row(
+                "SELECT timezone, theme, landing_page, week_start, month_start_day
+                FROM user_preferences WHERE user_id = ?1",
+                [user_id.as_i64()],
+                map_preferences_row,
+            )
+            .optional()?;
+
+        Ok(preferences.unwrap_or_default())
+    }
+
+    fn set_timezone(
+        &mut self,
+        user_id: UserID,
+        timezone: &str,
+    ) -> Result<UserPreferences, UserError> {
+        self.connection()?.execute(
+            "INSERT INTO user_preferences (user_id, timezone) VALUES (?1, ?2)
+            ON CONFLICT(user_id) DO UPDATE SET timezone = excluded.timezone",
+            (user_id.as_i64(), timezone),
+        )?;
+
+        self.get_preferences(user_id)
+    }
+
+    fn set_theme(&mut self, user_id: UserID, theme: Theme) -> Result<UserPreferences, UserError> {
+        self.connection()?.execute(
+            "INSERT INTO user_preferences (user_id, timezone, theme) VALUES (?1, ?2, ?3)
+            ON CONFLICT(user_id) DO UPDATE SET theme = excluded.theme",
+            (user_id.as_i64(), DEFAULT_TIMEZONE, theme.as_str()),
+        )?;
+
+        self.get_preferences(user_id)
+    }
+
+    fn set_landing_page(
+        &mut self,
+        user_id: UserID,
+        landing_page: LandingPage,
+    ) -> Result<UserPreferences, UserError> {
+        self.connection()?.execute(
+            "INSERT INTO user_preferences (user_id, timezone, landing_page) VALUES (?1, ?2, ?3)
+            ON CONFLICT(user_id) DO UPDATE SET landing_page = excluded.landing_page",
+            (user_id.as_i64(), DEFAULT_TIMEZONE, landing_page.as_str()),
+        )?;
+
+        self.get_preferences(user_id)
+    }
+
+    fn set_date_periods(
+        &mut self,
+        user_id: UserID,
+        week_start: WeekStart,
+        month_start_day: u8,
+    ) -> Result<UserPreferences, UserError> {
+        self.connection()?.execute(
+            "INSERT INTO user_preferences (user_id, timezone, week_start, month_start_day)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT(user_id) DO UPDATE SET
+                week_start = excluded.week_start,
+                month_start_day = excluded.month_start_day",
+            (
+                user_id.as_i64(),
+                DEFAULT_TIMEZONE,
+                week_start.as_str(),
+                clamp_month_start_day(month_start_day),
+            ),
+        )?;
+
+        self.get_preferences(user_id)
+    }
+}
+
+/// Map a row of the columns `timezone, theme, landing_page, week_start, month_start_day` from the
+/// `user_preferences` table to [UserPreferences].
+fn map_preferences_row(row: &Row) -> Result<UserPreferences, rusqlite::Error> {
+    let raw_theme: String = row.get(1)?;
+    let theme = raw_theme.parse().map_err(|error: String| {
+        rusqlite::Error::FromSqlConversionFailure(1, Type::Text, error.into())
+    })?;
+    let raw_landing_page: String = row.get(2)?;
+    let landing_page = raw_landing_page.parse().map_err(|error: String| {
+        rusqlite::Error::FromSqlConversionFailure(2, Type::Text, error.into())
+    })?;
+    let raw_week_start: String = row.get(3)?;
+    let week_start = raw_week_start.parse().map_err(|error: String| {
+        rusqlite::Error::FromSqlConversionFailure(3, Type::Text, error.into())
+    })?;
+
+    Ok(UserPreferences {
+        timezone: row.get(0)?,
+        theme,
+        landing_page,
+        week_start,
+        month_start_day: row.get(4)?,
+    })
 }
 
 impl MapRow for SQLiteUserStore {
@@ -160,12 +624,14 @@ impl MapRow for SQLiteUserStore {
         let raw_id = row.get(offset)?;
         let raw_email: String = row.get(offset + 1)?;
         let raw_password_hash: String = row.get(offset + 2)?;
+        let session_generation = row.get(offset + 3)?;
 
         let id = UserID::new(raw_id);
         let email = EmailAddress::new_unchecked(raw_email);
         let password_hash = PasswordHash::new_unchecked(&raw_password_hash);
 
-        Ok(Self::ReturnType::new(id, email, password_hash))
+        Ok(Self::ReturnType::new(id, email, password_hash)
+            .with_session_generation(session_generation))
     }
 }
 
@@ -174,25 +640,39 @@ mod user_tests {
     use std::{
         str::FromStr,
         sync::{Arc, Mutex},
+        thread,
     };
 
     use email_address::EmailAddress;
     use rusqlite::Connection;
 
     use crate::{
-        db::CreateTable,
-        models::{PasswordHash, UserID},
+        db::initialize,
+        models::{LandingPage, PasswordHash, Theme, UserID, UserPreferences, WeekStart},
+        stores::{
+            ApiTokenStore, InviteStore, SQLiteApiTokenStore, SQLiteInviteStore, SQLiteWebhookStore,
+            WebhookStore,
+        },
     };
 
     use super::{SQLiteUserStore, UserError, UserStore};
 
     fn get_store() -> SQLiteUserStore {
         let conn = Connection::open_in_memory().unwrap();
-        SQLiteUserStore::create_table(&conn).unwrap();
+        initialize(&conn).unwrap();
 
         SQLiteUserStore::new(Arc::new(Mutex::new(conn)))
     }
 
+    /// Get a user store and an invite store with the invite `code`.
+    fn get_store_with_invite(code: &str) -> (SQLiteUserStore, SQLiteInviteStore) {
+        let store = get_store();
+        let mut invite_store = SQLiteInviteStore::new(store.shared_connection());
+        invite_store.create(code.to_string()).unwrap();
+
+        (store, invite_store)
+    }
+
     #[test]
     fn insert_user_succeeds() {
         let mut store = get_store();
@@ -273,4 +753,396 @@ mod user_tests {
 
         assert_eq!(retrieved_user, test_user);
     }
+
+    #[test]
+    fn set_password_succeeds() {
+        let mut store = get_store();
+
+        let test_user = store
+            .create(
+                EmailAddress::from_str("foo@bar.baz").unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+
+        let updated_user = store
+            .set_password(test_user.id(), PasswordHash::new_unchecked("hunter3"))
+            .unwrap();
+
+        assert_eq!(
+            updated_user.password_hash(),
+            &PasswordHash::new_unchecked("hunter3")
+        );
+        assert_eq!(store.get(test_user.id()).unwrap(), updated_user);
+    }
+
+    #[test]
+    fn set_password_increments_session_generation() {
+        let mut store = get_store();
+
+        let test_user = store
+            .create(
+                EmailAddress::from_str("foo@bar.baz").unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+
+        let updated_user = store
+            .set_password(test_user.id(), PasswordHash::new_unchecked("hunter3"))
+            .unwrap();
+
+        assert_eq!(
+            updated_user.session_generation(),
+            test_user.session_generation() + 1
+        );
+    }
+
+    #[test]
+    fn increment_session_generation_succeeds() {
+        let mut store = get_store();
+
+        let test_user = store
+            .create(
+                EmailAddress::from_str("foo@bar.baz").unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+
+        let updated_user = store.increment_session_generation(test_user.id()).unwrap();
+
+        assert_eq!(test_user.session_generation(), 0);
+        assert_eq!(updated_user.session_generation(), 1);
+        assert_eq!(store.get(test_user.id()).unwrap(), updated_user);
+    }
+
+    #[test]
+    fn increment_session_generation_fails_with_non_existent_id() {
+        let mut store = get_store();
+
+        assert_eq!(
+            store.increment_session_generation(UserID::new(42)),
+            Err(UserError::NotFound)
+        );
+    }
+
+    #[test]
+    fn create_with_invite_uses_up_invite() {
+        let (mut store, invite_store) = get_store_with_invite("code");
+        let email = EmailAddress::from_str("hello@world.com").unwrap();
+
+        let user = store
+            .create_with_invite(
+                email.clone(),
+                PasswordHash::new_unchecked("hunter2"),
+                "code",
+            )
+            .unwrap();
+
+        assert_eq!(store.get_by_email(&email), Ok(user));
+        assert_eq!(invite_store.get_all().unwrap(), vec![]);
+        assert_eq!(
+            store.create_with_invite(
+                EmailAddress::from_str("other@world.com").unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+                "code"
+            ),
+            Err(UserError::InvalidInvite)
+        );
+    }
+
+    #[test]
+    fn create_with_invite_fails_with_invalid_code() {
+        let (mut store, invite_store) = get_store_with_invite("code");
+        let email = EmailAddress::from_str("hello@world.com").unwrap();
+
+        let result =
+            store.create_with_invite(email.clone(), PasswordHash::new_unchecked("hunter2"), "foo");
+
+        assert_eq!(result, Err(UserError::InvalidInvite));
+        assert_eq!(store.get_by_email(&email), Err(UserError::NotFound));
+        assert_eq!(invite_store.get_all().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn create_with_invite_keeps_invite_when_email_is_taken() {
+        let (mut store, invite_store) = get_store_with_invite("code");
+        let email = EmailAddress::from_str("hello@world.com").unwrap();
+        store
+            .create(email.clone(), PasswordHash::new_unchecked("hunter2"))
+            .unwrap();
+
+        let result =
+            store.create_with_invite(email, PasswordHash::new_unchecked("hunter2"), "code");
+
+        assert_eq!(result, Err(UserError::DuplicateEmail));
+        assert_eq!(invite_store.get_all().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn set_password_fails_with_non_existent_id() {
+        let mut store = get_store();
+
+        assert_eq!(
+            store.set_password(UserID::new(42), PasswordHash::new_unchecked("hunter3")),
+            Err(UserError::NotFound)
+        );
+    }
+
+    #[test]
+    fn set_email_succeeds() {
+        let mut store = get_store();
+        let user = store
+            .create(
+                EmailAddress::from_str("foo@bar.baz").unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+        let new_email = EmailAddress::from_str("qux@bar.baz").unwrap();
+
+        let updated_user = store.set_email(user.id(), new_email.clone()).unwrap();
+
+        assert_eq!(updated_user.email(), &new_email);
+        assert_eq!(store.get_by_email(&new_email).unwrap().id(), user.id());
+    }
+
+    #[test]
+    fn set_email_fails_on_duplicate_email() {
+        let mut store = get_store();
+        let taken_email = EmailAddress::from_str("foo@bar.baz").unwrap();
+        store
+            .create(taken_email.clone(), PasswordHash::new_unchecked("hunter2"))
+            .unwrap();
+        let user = store
+            .create(
+                EmailAddress::from_str("qux@bar.baz").unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+
+        assert_eq!(
+            store.set_email(user.id(), taken_email),
+            Err(UserError::DuplicateEmail)
+        );
+    }
+
+    #[test]
+    fn set_email_fails_with_non_existent_id() {
+        let mut store = get_store();
+
+        assert_eq!(
+            store.set_email(
+                UserID::new(42),
+                EmailAddress::from_str("foo@bar.baz").unwrap()
+            ),
+            Err(UserError::NotFound)
+        );
+    }
+
+    #[test]
+    fn delete_removes_user_and_their_data() {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize(&conn).unwrap();
+        let conn = Arc::new(Mutex::new(conn));
+        let mut store = SQLiteUserStore::new(conn.clone());
+        // The first user owns the site and cannot be deleted.
+        let other_user = store
+            .create(
+                EmailAddress::from_str("qux@bar.baz").unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+        let user = store
+            .create(
+                EmailAddress::from_str("foo@bar.baz").unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+        for user_id in [user.id(), other_user.id()] {
+            let conn = conn.lock().unwrap();
+            conn.execute(
+                "INSERT INTO category (name, user_id) VALUES ('Food', ?1)",
+                (user_id.as_i64(),),
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO \"transaction\" (amount, date, description, user_id) \
+                VALUES (1.0, '2024-01-01', '', ?1)",
+                (user_id.as_i64(),),
+            )
+            .unwrap();
+        }
+        let mut api_token_store = SQLiteApiTokenStore::new(conn.clone());
+        let mut webhook_store = SQLiteWebhookStore::new(conn.clone());
+        for (user_id, name) in [(user.id(), "foo"), (other_user.id(), "qux")] {
+            api_token_store
+                .create(user_id, name.to_string(), name.to_string(), None)
+                .unwrap();
+            webhook_store
+                .create(user_id, name.to_string(), name.to_string(), vec![])
+                .unwrap();
+        }
+
+        store.delete(user.id()).unwrap();
+
+        assert_eq!(store.get(user.id()), Err(UserError::NotFound));
+        assert_eq!(store.get(other_user.id()).as_ref(), Ok(&other_user));
+        let conn = conn.lock().unwrap();
+        for table in ["category", "\"transaction\"", "api_token", "webhook"] {
+            let user_ids: Vec<i64> = conn
+                .prepare(&format!("SELECT user_id FROM {table}"))
+                .unwrap()
+                .query_map((), |row| row.get(0))
+                .unwrap()
+                .map(|user_id| user_id.unwrap())
+                .collect();
+            assert_eq!(user_ids, vec![other_user.id().as_i64()], "{table}");
+        }
+    }
+
+    #[test]
+    fn delete_fails_with_non_existent_id() {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize(&conn).unwrap();
+        let mut store = SQLiteUserStore::new(Arc::new(Mutex::new(conn)));
+
+        assert_eq!(store.delete(UserID::new(42)), Err(UserError::NotFound));
+    }
+
+    #[test]
+    fn first_user_owns_the_site() {
+        let mut store = get_store();
+        assert_eq!(store.get_owner_id(), Ok(None));
+
+        let owner = store
+            .create(
+                EmailAddress::from_str("foo@bar.baz").unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+        store
+            .create(
+                EmailAddress::from_str("qux@bar.baz").unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+
+        assert_eq!(store.get_owner_id(), Ok(Some(owner.id())));
+    }
+
+    #[test]
+    fn delete_fails_for_owner() {
+        let mut store = get_store();
+        let owner = store
+            .create(
+                EmailAddress::from_str("foo@bar.baz").unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+
+        assert_eq!(
+            store.delete(owner.id()),
+            Err(UserError::OwnerCannotBeDeleted)
+        );
+        assert_eq!(store.get(owner.id()), Ok(owner));
+    }
+
+    #[test]
+    fn update_password_hash_keeps_session_generation() {
+        let mut store = get_store();
+        let user = store
+            .create(
+                EmailAddress::from_str("foo@bar.baz").unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+        let new_password_hash = PasswordHash::new_unchecked("hunter3");
+
+        store
+            .update_password_hash(user.id(), new_password_hash.clone())
+            .unwrap();
+
+        let updated_user = store.get(user.id()).unwrap();
+        assert_eq!(updated_user.password_hash(), &new_password_hash);
+        assert_eq!(updated_user.session_generation(), user.session_generation());
+    }
+
+    #[test]
+    fn update_password_hash_fails_with_non_existent_id() {
+        let mut store = get_store();
+
+        assert_eq!(
+            store.update_password_hash(UserID::new(42), PasswordHash::new_unchecked("hunter3")),
+            Err(UserError::NotFound)
+        );
+    }
+
+    #[test]
+    fn get_and_set_preferences() {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize(&conn).unwrap();
+        let mut store = SQLiteUserStore::new(Arc::new(Mutex::new(conn)));
+        let user = store
+            .create(
+                EmailAddress::from_str("foo@bar.baz").unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+
+        assert_eq!(
+            store.get_preferences(user.id()).unwrap(),
+            UserPreferences::default()
+        );
+
+        store.set_timezone(user.id(), "Europe/London").unwrap();
+        let preferences = store.set_timezone(user.id(), "Pacific/Auckland").unwrap();
+
+        assert_eq!(preferences.timezone, "Pacific/Auckland");
+        assert_eq!(store.get_preferences(user.id()).unwrap(), preferences);
+
+        let preferences = store.set_theme(user.id(), Theme::Dark).unwrap();
+
+        assert_eq!(preferences.theme, Theme::Dark);
+        assert_eq!(preferences.timezone, "Pacific/Auckland");
+
+        let preferences = store
+            .set_landing_page(user.id(), LandingPage::Transactions)
+            .unwrap();
+
+        assert_eq!(preferences.landing_page, LandingPage::Transactions);
+        assert_eq!(preferences.theme, Theme::Dark);
+        assert_eq!(store.get_preferences(user.id()).unwrap(), preferences);
+
+        let preferences = store
+            .set_date_periods(user.id(), WeekStart::Sunday, 31)
+            .unwrap();
+
+        assert_eq!(preferences.week_start, WeekStart::Sunday);
+        assert_eq!(preferences.month_start_day, 28);
+        assert_eq!(preferences.landing_page, LandingPage::Transactions);
+        assert_eq!(store.get_preferences(user.id()).unwrap(), preferences);
+    }
+
+    #[test]
+    fn get_preferences_fails_with_poisoned_lock() {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize(&conn).unwrap();
+        let conn = Arc::new(Mutex::new(conn));
+        let mut store = SQLiteUserStore::new(conn.clone());
+        let user = store
+            .create(
+                EmailAddress::from_str("foo@bar.baz").unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+        let _ = thread::spawn(move || {
+            let _guard = conn.lock().unwrap();
+            panic!("poison the database lock");
+        })
+        .join();
+
+        assert_eq!(
+            store.get_preferences(user.id()),
+            Err(UserError::DatabaseLockError)
+        );
+    }
 }
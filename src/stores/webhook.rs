@@ -0,0 +1,307 @@
+//! Defines the webhook store trait and an implementation for the SQLite backend.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use rusqlite::{types::Type, Connection, Row};
+
+use crate::models::{DatabaseID, UserID, Webhook, WebhookEvent};
+
+use super::UserError;
+
+/// Handles the creation, retrieval and deletion of the [Webhook]s that are notified when
+/// something happens to a user's data.
+pub trait WebhookStore {
+    /// Create an enabled webhook for the user `user_id` that is sent the specified `events`.
+    fn create(
+        &mut self,
+        user_id: UserID,
+        url: String,
+        secret: String,
+        events: Vec<WebhookEvent>,
+    ) -> Result<Webhook, UserError>;
+
+    /// Get all of the webhooks for the user `user_id`, oldest first.
+    fn get_by_user_id(&self, user_id: UserID) -> Result<Vec<Webhook>, UserError>;
+
+    /// Set whether the webhook `id` belonging to the user `user_id` is sent events.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::NotFound] if the user has no webhook with the specified `id`.
+    fn set_enabled(
+        &mut self,
+        user_id: UserID,
+        id: DatabaseID,
+        enabled: bool,
+    ) -> Result<(), UserError>;
+
+    /// Delete the webhook `id` belonging to the user `user_id`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::NotFound] if the user has no webhook with the specified `id`.
+    fn delete(&mut self, user_id: UserID, id: DatabaseID) -> Result<(), UserError>;
+}
+
+/// Handles the creation, retrieval and deletion of webhooks in a SQLite database.
+#[derive(Debug, Clone)]
+pub struct SQLiteWebhookStore {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SQLiteWebhookStore {
+    /// Create a new webhook store.
+    pub fn new(connection: Arc<Mutex<Connection>>) -> Self {
+        Self { connection }
+    }
+
+    /// Get exclusive access to the database connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::DatabaseLockError] if another thread panicked while holding the
+    /// database lock.
+    fn connection(&self) -> Result<MutexGuard<'_, Connection>, UserError> {
+        self.connection
+            .lock()
+            .map_err(|_| UserError::DatabaseLockError)
+    }
+}
+
+impl WebhookStore for SQLiteWebhookStore {
+    /// Create and insert a new webhook into the database.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::SqlError] if an SQL related error occurred, e.g. `user_id` does not
+    /// refer to a valid user.
+    fn create(
+        &mut self,
+        user_id: UserID,
+        url: String,
+        secret: String,
+        events: Vec<WebhookEvent>,
+    ) -> Result<Webhook, UserError> {
+        let connection = self.connection()?;
+
+        connection.execute(
+            "INSERT INTO webhook (user_id, url, secret, events, enabled) \
+            VALUES (?1, ?2, ?3, ?4, TRUE)",
+            (
+                user_id.as_i64(),
+                &url,
+                &secret,
+                join_webhook_events(&events),
+            ),
+        )?;
+
+        Ok(Webhook::new(
+            connection.last_insert_rowid(),
+            user_id,
+            url,
+            secret,
+            events,
+            true,
+        ))
+    }
+
+    /// Get all of the webhooks for the user `user_id`, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::SqlError] if an SQL related error occurred.
+    fn get_by_user_id(&self, user_id: UserID) -> Result<Vec<Webhook>, UserError> {
+        self.connection()?
+            .prepare(
+                "SELECT id, user_id, url, secret, events, enabled FROM webhook \
+                WHERE user_id = ?1 ORDER BY id ASC",
+            )?
+            .query_map((user_id.as_i64(),), map_webhook_row)?
+            .map(|maybe_webhook| maybe_webhook.map_err(UserError::from))
+            .collect()
+    }
+
+    /// Set whether the webhook `id` belonging to the user `user_id` is enabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::NotFound] error if the user has no webhook with the specified `id` or [UserError::SqlError] if there are SQL related errors.
+    fn set_enabled(
+        &mut self,
+        user_id: UserID,
+        id: DatabaseID,
+        enabled: bool,
+    ) -> Result<(), UserError> {
+        let rows_affected = self.connection()?.execute(
+            "UPDATE webhook SET enabled = ?1 WHERE id = ?2 AND user_id = ?3",
+            (enabled, id, user_id.as_i64()),
+        )?;
+
+        if rows_affected == 0 {
+            return Err(UserError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    /// Delete the webhook `id` belonging to the user `user_id` from the database.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [UserError::NotFound] error if the user has no webhook with the specified `id` or [UserError::SqlError] if there are SQL related errors.
+    fn delete(&mut self, user_id: UserID, id: DatabaseID) -> Result<(), UserError> {
+        let rows_affected = self.connection()?.execute(
+            "DELETE FROM webhook WHERE id = ?1 AND user_id = ?2",
+            (id, user_id.as_i64()),
+        )?;
+
+        if rows_affected == 0 {
+            return Err(UserError::NotFound);
+        }
+
+        Ok(())
+    }
+}
+
+/// Join webhook events into the comma separated list stored in the database.
+fn join_webhook_events(events: &[WebhookEvent]) -> String {
+    events
+        .iter()
+        .map(WebhookEvent::as_str)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Map a row of the columns `id, user_id, url, secret, events, enabled` from the `webhook` table
+/// to a [Webhook].
+fn map_webhook_row(row: &Row) -> Result<Webhook, rusqlite::Error> {
+    let raw_events: String = row.get(4)?;
+    let events = raw_events
+        .split(',')
+        .filter(|event| !event.is_empty())
+        .map(|event| {
+            event.parse().map_err(|error: String| {
+                rusqlite::Error::FromSqlConversionFailure(4, Type::Text, error.into())
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(Webhook::new(
+        row.get(0)?,
+        UserID::new(row.get(1)?),
+        row.get(2)?,
+        row.get(3)?,
+        events,
+        row.get(5)?,
+    ))
+}
+
+#[cfg(test)]
+mod webhook_tests {
+    use std::{
+        str::FromStr,
+        sync::{Arc, Mutex},
+    };
+
+    use email_address::EmailAddress;
+    use rusqlite::Connection;
+
+    use crate::{
+        db::initialize,
+        models::{PasswordHash, User, UserID, WebhookEvent},
+        stores::{SQLiteUserStore, UserError, UserStore},
+    };
+
+    use super::{SQLiteWebhookStore, WebhookStore};
+
+    fn get_store_and_user() -> (SQLiteWebhookStore, User) {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize(&conn).unwrap();
+        let connection = Arc::new(Mutex::new(conn));
+        let user = SQLiteUserStore::new(connection.clone())
+            .create(
+                EmailAddress::from_str("foo@bar.baz").unwrap(),
+                PasswordHash::new_unchecked("hunter2"),
+            )
+            .unwrap();
+
+        (SQLiteWebhookStore::new(connection), user)
+    }
+
+    #[test]
+    fn create_and_get_webhooks() {
+        let (mut store, user) = get_store_and_user();
+
+        let first = store
+            .create(
+                user.id(),
+                "http://localhost/first".to_string(),
+                "secret1".to_string(),
+                vec![WebhookEvent::TransactionCreated],
+            )
+            .unwrap();
+        let second = store
+            .create(
+                user.id(),
+                "http://localhost/second".to_string(),
+                "secret2".to_string(),
+                vec![],
+            )
+            .unwrap();
+
+        assert!(first.enabled());
+        assert_eq!(first.events(), &[WebhookEvent::TransactionCreated]);
+        assert_eq!(
+            store.get_by_user_id(user.id()).unwrap(),
+            vec![first, second]
+        );
+        assert_eq!(
+            store
+                .get_by_user_id(UserID::new(user.id().as_i64() + 1))
+                .unwrap(),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn set_webhook_enabled() {
+        let (mut store, user) = get_store_and_user();
+        let webhook = store
+            .create(
+                user.id(),
+                "http://localhost".to_string(),
+                "secret".to_string(),
+                vec![WebhookEvent::TransactionCreated],
+            )
+            .unwrap();
+
+        store.set_enabled(user.id(), webhook.id(), false).unwrap();
+
+        assert!(!store.get_by_user_id(user.id()).unwrap()[0].enabled());
+        assert_eq!(
+            store.set_enabled(UserID::new(user.id().as_i64() + 1), webhook.id(), true),
+            Err(UserError::NotFound)
+        );
+    }
+
+    #[test]
+    fn delete_webhook() {
+        let (mut store, user) = get_store_and_user();
+        let webhook = store
+            .create(
+                user.id(),
+                "http://localhost".to_string(),
+                "secret".to_string(),
+                vec![],
+            )
+            .unwrap();
+
+        assert_eq!(
+            store.delete(UserID::new(user.id().as_i64() + 1), webhook.id()),
+            Err(UserError::NotFound)
+        );
+        store.delete(user.id(), webhook.id()).unwrap();
+
+        assert_eq!(store.get_by_user_id(user.id()).unwrap(), vec![]);
+    }
+}
@@ -0,0 +1,126 @@
+//! Working out the date for a user from their timezone.
+//!
+//! Timezones are stored as IANA names, e.g. "Pacific/Auckland", rather than fixed offsets so that
+//! daylight saving time is taken into account.
+
+use time::{Date, OffsetDateTime};
+use time_tz::{timezones, OffsetDateTimeExt, TimeZone, Tz};
+
+use crate::{
+    models::{UserID, UserPreferences, DEFAULT_TIMEZONE},
+    stores::UserStore,
+};
+
+/// Get the timezone with the IANA name `name`, or `None` if there is no such timezone.
+pub fn get_timezone(name: &str) -> Option<&'static Tz> {
+    timezones::get_by_name(name)
+}
+
+/// The IANA names of every known timezone, sorted alphabetically.
+pub fn get_timezone_names() -> Vec<&'static str> {
+    let mut names: Vec<_> = timezones::iter().map(|timezone| timezone.name()).collect();
+    names.sort_unstable();
+    names.dedup();
+    names
+}
+
+/// The date at the instant `now` in the timezone named `timezone`.
+///
+/// Falls back to the date in UTC if `timezone` is not a known timezone.
+pub fn get_local_date(timezone: &str, now: OffsetDateTime) -> Date {
+    match get_timezone(timezone) {
+        Some(timezone) => now.to_timezone(timezone).date(),
+        None => {
+            tracing::warn!("Unknown timezone {timezone}, using {DEFAULT_TIMEZONE}");
+            now.date()
+        }
+    }
+}
+
+/// Today's date for the user `user_id` according to their timezone preference.
+///
+/// Falls back to the date in UTC if the user's preferences could not be loaded.
+pub fn get_user_today<U>(user_store: &U, user_id: UserID) -> Date
+where
+    U: UserStore,
+{
+    get_preferences_today(&get_user_preferences(user_store, user_id))
+}
+
+/// The preferences of the user `user_id`, e.g. for their week and month start days.
+///
+/// Falls back to the default preferences if the user's preferences could not be loaded.
+pub fn get_user_preferences<U>(user_store: &U, user_id: UserID) -> UserPreferences
+where
+    U: UserStore,
+{
+    user_store.get_preferences(user_id).unwrap_or_else(|error| {
+        tracing::error!("Could not get the preferences for user {user_id}: {error}");
+        UserPreferences::default()
+    })
+}
+
+/// Today's date in the timezone from `preferences`.
+pub fn get_preferences_today(preferences: &UserPreferences) -> Date {
+    get_local_date(&preferences.timezone, OffsetDateTime::now_utc())
+}
+
+#[cfg(test)]
+mod timezone_tests {
+    use time::macros::{date, datetime};
+
+    use super::{get_local_date, get_timezone, get_timezone_names};
+
+    #[test]
+    fn local_date_follows_daylight_saving() {
+        // New Zealand moved its clocks forward from UTC+12 to UTC+13 at 2024-09-29 02:00 local
+        // time, which was 2024-09-28 14:00 UTC.
+        let before = datetime!(2024-09-28 11:30 UTC);
+        let after = datetime!(2024-09-28 11:30 UTC) + time::Duration::days(1);
+
+        // 23:30 on the 28th in UTC+12.
+        assert_eq!(
+            get_local_date("Pacific/Auckland", before),
+            date!(2024 - 09 - 28)
+        );
+        // 00:30 on the 30th in UTC+13. A fixed offset of +12 would give the 29th.
+        assert_eq!(
+            get_local_date("Pacific/Auckland", after),
+            date!(2024 - 09 - 30)
+        );
+    }
+
+    #[test]
+    fn local_date_differs_between_timezones() {
+        let now = datetime!(2024-01-15 20:00 UTC);
+
+        assert_eq!(
+            get_local_date("Pacific/Auckland", now),
+            date!(2024 - 01 - 16)
+        );
+        assert_eq!(
+            get_local_date("America/New_York", now),
+            date!(2024 - 01 - 15)
+        );
+    }
+
+    #[test]
+    fn unknown_timezone_uses_utc() {
+        let now = datetime!(2024-01-15 20:00 UTC);
+
+        assert!(get_timezone("Mars/Olympus_Mons").is_none());
+        assert_eq!(
+            get_local_date("Mars/Olympus_Mons", now),
+            date!(2024 - 01 - 15)
+        );
+    }
+
+    #[test]
+    fn timezone_names_are_sorted_and_include_default() {
+        let names = get_timezone_names();
+
+        assert!(names.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(names.contains(&"Pacific/Auckland"));
+        assert!(names.contains(&super::DEFAULT_TIMEZONE));
+    }
+}
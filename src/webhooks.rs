@@ -0,0 +1,704 @@
+//! Sends signed JSON payloads to users' webhooks when events happen.
+//!
+//! Each payload has the shape `{ "event": "<event>", "sent_at": "<RFC 3339 time>", "data": ... }`.
+//! The request has the header `X-Budgeteur-Event` with the event name and the header
+//! `X-Budgeteur-Signature` with `sha256=<hex HMAC-SHA256 of the body keyed with the webhook secret>`
+//! so that receivers can check that the payload came from this server.
+//!
+//! Payloads are delivered in the background and retried with exponential backoff, so that slow or
+//! unavailable receivers never hold up the response to the user.
+//!
+//! Webhooks are never sent to loopback or link-local addresses, so that users cannot use them to
+//! reach services that are only meant to be reachable from the server itself, e.g. a cloud
+//! provider's metadata service.
+
+use std::{
+    future::Future,
+    io,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use axum::{
+    body::Bytes,
+    http::{
+        header::{CONTENT_TYPE, USER_AGENT},
+        Request, StatusCode, Uri,
+    },
+};
+use hmac::{Hmac, Mac};
+use http_body_util::Full;
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::{
+    client::legacy::{
+        connect::{dns::Name, HttpConnector},
+        Client,
+    },
+    rt::TokioExecutor,
+};
+use serde::Serialize;
+use serde_json::json;
+use sha2::Sha256;
+use thiserror::Error;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tower_service::Service;
+
+use crate::{
+    models::{UserID, Webhook, WebhookEvent},
+    stores::{SQLiteWebhookStore, WebhookStore},
+};
+
+/// The header containing the name of the event.
+pub const EVENT_HEADER: &str = "X-Budgeteur-Event";
+
+/// The header containing the signature of the payload.
+pub const SIGNATURE_HEADER: &str = "X-Budgeteur-Signature";
+
+/// The default number of times to try delivering a payload.
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 4;
+
+/// The default time to wait before retrying a failed delivery for the first time.
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_secs(2);
+
+/// The default time to wait for a receiver to respond.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Errors that can occur when delivering a payload to a webhook.
+#[derive(Debug, Error)]
+pub enum WebhookError {
+    /// The webhook URL is not a valid `http://` or `https://` URL, or points at this machine.
+    #[error("invalid webhook URL: {0}")]
+    InvalidUrl(String),
+
+    /// The request could not be sent, e.g. the receiver is not running.
+    #[error("could not send the request: {0}")]
+    Request(String),
+
+    /// The receiver did not respond in time.
+    #[error("the receiver did not respond within {0:?}")]
+    Timeout(Duration),
+
+    /// The receiver responded with a non-success status code.
+    #[error("the receiver responded with {0}")]
+    Status(StatusCode),
+
+    /// The payload could not be serialized.
+    #[error("could not serialize the payload: {0}")]
+    Payload(String),
+}
+
+/// Check that `url` is something webhooks can be sent to.
+///
+/// Both `http://` and `https://` URLs are supported, but not URLs for `localhost` or loopback and
+/// link-local addresses. Host names are also checked when they are resolved, see
+/// [ReceiverResolver].
+///
+/// # Errors
+///
+/// Returns a [WebhookError::InvalidUrl] with a message for the user if the URL cannot be used.
+pub fn parse_webhook_url(url: &str) -> Result<Uri, WebhookError> {
+    parse_receiver_url(url, false)
+}
+
+/// Check that `url` is something webhooks can be sent to, allowing URLs for this machine if
+/// `allow_local_receivers` is true.
+fn parse_receiver_url(url: &str, allow_local_receivers: bool) -> Result<Uri, WebhookError> {
+    let uri = url
+        .parse::<Uri>()
+        .map_err(|error| WebhookError::InvalidUrl(error.to_string()))?;
+
+    if !matches!(uri.scheme_str(), Some("http" | "https")) {
+        return Err(WebhookError::InvalidUrl(
+            "the URL must start with http:// or https://".to_string(),
+        ));
+    }
+
+    let Some(host) = uri.host().filter(|host| !host.is_empty()) else {
+        return Err(WebhookError::InvalidUrl(
+            "the URL must have a host".to_string(),
+        ));
+    };
+
+    if !allow_local_receivers && is_blocked_host(host) {
+        return Err(WebhookError::InvalidUrl(
+            "webhooks cannot be sent to loopback or link-local addresses".to_string(),
+        ));
+    }
+
+    Ok(uri)
+}
+
+/// Whether `host`, as it appears in a URL, is `localhost` or a loopback or link-local address.
+///
+/// The HTTP client connects to IP addresses directly instead of resolving them, so this is the
+/// only check that IP addresses get.
+fn is_blocked_host(host: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+
+    if host == "localhost" || host.ends_with(".localhost") {
+        return true;
+    }
+
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+        .is_ok_and(is_blocked_ip)
+}
+
+/// Whether `ip` is a loopback or link-local address, or an address that means this machine.
+fn is_blocked_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_loopback() || ip.is_link_local() || ip.is_unspecified(),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_blocked_ip(IpAddr::V4(ip)),
+            // fe80::/10 is the link-local range.
+            None => ip.is_loopback() || ip.is_unspecified() || ip.segments()[0] & 0xffc0 == 0xfe80,
+        },
+    }
+}
+
+/// Resolves the host names of webhook receivers, leaving out loopback and link-local addresses so
+/// that names like `localhost` cannot be used to reach this machine.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReceiverResolver {
+    allow_local_receivers: bool,
+}
+
+impl Service<Name> for ReceiverResolver {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _context: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let allow_local_receivers = self.allow_local_receivers;
+
+        Box::pin(async move {
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|address| allow_local_receivers || !is_blocked_ip(address.ip()))
+                .collect();
+
+            if addresses.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("{name} does not resolve to an address that webhooks can be sent to"),
+                ));
+            }
+
+            Ok(addresses.into_iter())
+        })
+    }
+}
+
+/// The connector for webhook receivers, which supports both HTTP and HTTPS.
+type ReceiverConnector = HttpsConnector<HttpConnector<ReceiverResolver>>;
+
+/// Build a client for sending webhooks, which may only send them to this machine if
+/// `allow_local_receivers` is true.
+fn build_client(allow_local_receivers: bool) -> Client<ReceiverConnector, Full<Bytes>> {
+    let mut http = HttpConnector::new_with_resolver(ReceiverResolver {
+        allow_local_receivers,
+    });
+    // Let the HTTPS connector handle `https://` URLs.
+    http.enforce_http(false);
+
+    let https = HttpsConnectorBuilder::new()
+        .with_webpki_roots()
+        .https_or_http()
+        .enable_http1()
+        .wrap_connector(http);
+
+    Client::builder(TokioExecutor::new()).build(https)
+}
+
+/// Sign `body` with `secret`, giving the value of the [SIGNATURE_HEADER] header.
+pub fn sign_payload(secret: &[u8], body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret).expect("HMAC should accept keys of any length");
+    mac.update(body);
+
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+/// Serialize the payload for `event` with `data`.
+///
+/// # Errors
+///
+/// Returns a [WebhookError::Payload] if `data` could not be serialized.
+pub fn build_payload(event: WebhookEvent, data: &impl Serialize) -> Result<Bytes, WebhookError> {
+    let sent_at = OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .map_err(|error| WebhookError::Payload(error.to_string()))?;
+    let payload = json!({
+        "event": event,
+        "sent_at": sent_at,
+        "data": data,
+    });
+
+    serde_json::to_vec(&payload)
+        .map(Bytes::from)
+        .map_err(|error| WebhookError::Payload(error.to_string()))
+}
+
+/// Delivers payloads to webhooks.
+#[derive(Debug, Clone)]
+pub struct WebhookDispatcher {
+    client: Client<ReceiverConnector, Full<Bytes>>,
+    /// Whether webhooks may be sent to this machine, which only tests need.
+    allow_local_receivers: bool,
+    /// The store of the webhooks that events are sent to.
+    ///
+    /// Events are not sent anywhere if there is no store, e.g. in tests with fake stores.
+    pub webhook_store: Option<SQLiteWebhookStore>,
+    /// The number of times to try delivering a payload before giving up.
+    pub max_attempts: u32,
+    /// The time to wait before retrying a failed delivery for the first time.
+    ///
+    /// The wait doubles after each failed attempt.
+    pub initial_backoff: Duration,
+    /// The time to wait for a receiver to respond to each attempt.
+    pub timeout: Duration,
+}
+
+impl Default for WebhookDispatcher {
+    fn default() -> Self {
+        Self {
+            client: build_client(false),
+            allow_local_receivers: false,
+            webhook_store: None,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+impl WebhookDispatcher {
+    /// Allow webhooks to be sent to this machine, so that tests can use a local receiver.
+    #[cfg(test)]
+    pub(crate) fn allow_local_receivers(&mut self) {
+        self.client = build_client(true);
+        self.allow_local_receivers = true;
+    }
+
+    /// Send `event` with `data` to the user's enabled webhooks that are subscribed to it.
+    ///
+    /// Deliveries happen in background tasks, so this function returns immediately. Failures are
+    /// logged rather than returned.
+    pub fn dispatch(&self, user_id: UserID, event: WebhookEvent, data: &impl Serialize) {
+        let Some(webhook_store) = &self.webhook_store else {
+            return;
+        };
+
+        let webhooks: Vec<Webhook> = match webhook_store.get_by_user_id(user_id) {
+            Ok(webhooks) => webhooks
+                .into_iter()
+                .filter(|webhook| webhook.wants(event))
+                .collect(),
+            Err(error) => {
+                tracing::error!("Could not get webhooks for user {user_id}: {error}");
+                return;
+            }
+        };
+
+        if webhooks.is_empty() {
+            return;
+        }
+
+        let body = match build_payload(event, data) {
+            Ok(body) => body,
+            Err(error) => {
+                tracing::error!("Could not build the payload for {event}: {error}");
+                return;
+            }
+        };
+
+        for webhook in webhooks {
+            let dispatcher = self.clone();
+            let body = body.clone();
+
+            tokio::spawn(async move {
+                if let Err(error) = dispatcher.deliver(&webhook, event, body).await {
+                    tracing::error!(
+                        "Giving up delivering {event} to webhook {}: {error}",
+                        webhook.id()
+                    );
+                }
+            });
+        }
+    }
+
+    /// Deliver `body` to `webhook`, retrying with exponential backoff if it fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error from the last attempt if every attempt fails.
+    pub async fn deliver(
+        &self,
+        webhook: &Webhook,
+        event: WebhookEvent,
+        body: Bytes,
+    ) -> Result<(), WebhookError> {
+        let mut backoff = self.initial_backoff;
+        let mut attempt = 1;
+
+        loop {
+            match self.send(webhook, event, body.clone()).await {
+                Ok(()) => return Ok(()),
+                Err(error) if attempt >= self.max_attempts => return Err(error),
+                Err(error) => {
+                    tracing::warn!(
+                        "Attempt {attempt} of {} to deliver {event} to webhook {} failed: {error}. \
+                        Retrying in {backoff:?}.",
+                        self.max_attempts,
+                        webhook.id()
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Make a single attempt at delivering `body` to `webhook`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request could not be sent, timed out, or the receiver responded
+    /// with a non-success status code.
+    pub async fn send(
+        &self,
+        webhook: &Webhook,
+        event: WebhookEvent,
+        body: Bytes,
+    ) -> Result<(), WebhookError> {
+        let uri = parse_receiver_url(webhook.url(), self.allow_local_receivers)?;
+        let request = Request::post(uri)
+            .header(CONTENT_TYPE, "application/json")
+            .header(USER_AGENT, "budgeteur")
+            .header(EVENT_HEADER, event.as_str())
+            .header(
+                SIGNATURE_HEADER,
+                sign_payload(webhook.secret().as_bytes(), &body),
+            )
+            .body(Full::new(body))
+            .map_err(|error| WebhookError::Request(error.to_string()))?;
+
+        let response = tokio::time::timeout(self.timeout, self.client.request(request))
+            .await
+            .map_err(|_| WebhookError::Timeout(self.timeout))?
+            .map_err(|error| WebhookError::Request(error.to_string()))?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(WebhookError::Status(response.status()))
+        }
+    }
+}
+
+/// A local server that records the webhook payloads it receives.
+#[cfg(test)]
+pub(crate) mod mock_server {
+    use std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use axum::{
+        body::Bytes,
+        extract::State,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Router,
+    };
+    use tokio::net::TcpListener;
+
+    /// A request received by the mock server.
+    #[derive(Debug, Clone)]
+    pub(crate) struct ReceivedRequest {
+        pub(crate) headers: HeaderMap,
+        pub(crate) body: Bytes,
+    }
+
+    /// Handle for inspecting the requests received by a mock server.
+    #[derive(Debug, Clone)]
+    pub(crate) struct MockServer {
+        /// The URL to send webhooks to.
+        pub(crate) url: String,
+        received: Arc<Mutex<Vec<ReceivedRequest>>>,
+    }
+
+    impl MockServer {
+        /// Start a server that responds with `statuses` in order, then with 200 OK.
+        pub(crate) async fn start(statuses: Vec<StatusCode>) -> Self {
+            let received = Arc::new(Mutex::new(Vec::new()));
+            let statuses = Arc::new(Mutex::new(VecDeque::from(statuses)));
+            let app = Router::new()
+                .route("/hook", post(receive))
+                .with_state((received.clone(), statuses));
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/hook", listener.local_addr().unwrap());
+
+            tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+            Self { url, received }
+        }
+
+        /// The requests received so far.
+        pub(crate) fn received(&self) -> Vec<ReceivedRequest> {
+            self.received.lock().unwrap().clone()
+        }
+
+        /// Wait up to a few seconds for the server to receive `count` requests and return them.
+        pub(crate) async fn wait_for(&self, count: usize) -> Vec<ReceivedRequest> {
+            for _ in 0..100 {
+                let received = self.received();
+                if received.len() >= count {
+                    return received;
+                }
+
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+
+            self.received()
+        }
+    }
+
+    type MockState = (
+        Arc<Mutex<Vec<ReceivedRequest>>>,
+        Arc<Mutex<VecDeque<StatusCode>>>,
+    );
+
+    async fn receive(
+        State((received, statuses)): State<MockState>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> StatusCode {
+        received
+            .lock()
+            .unwrap()
+            .push(ReceivedRequest { headers, body });
+
+        statuses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or(StatusCode::OK)
+    }
+}
+
+#[cfg(test)]
+mod webhooks_tests {
+    use std::time::Duration;
+
+    use std::str::FromStr;
+
+    use axum::http::StatusCode;
+    use hyper_util::client::legacy::connect::dns::Name;
+    use serde_json::{json, Value};
+    use tower_service::Service;
+
+    use crate::models::{UserID, Webhook, WebhookEvent};
+
+    use super::{
+        build_payload, mock_server::MockServer, parse_webhook_url, sign_payload, ReceiverResolver,
+        WebhookDispatcher, WebhookError, EVENT_HEADER, SIGNATURE_HEADER,
+    };
+
+    fn get_dispatcher() -> WebhookDispatcher {
+        let mut dispatcher = WebhookDispatcher {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            timeout: Duration::from_secs(1),
+            ..Default::default()
+        };
+        dispatcher.allow_local_receivers();
+
+        dispatcher
+    }
+
+    fn get_webhook(url: &str) -> Webhook {
+        Webhook::new(
+            1,
+            UserID::new(1),
+            url.to_string(),
+            "secret".to_string(),
+            vec![WebhookEvent::TransactionCreated],
+            true,
+        )
+    }
+
+    #[test]
+    fn sign_payload_matches_rfc_4231_test_vectors() {
+        assert_eq!(
+            sign_payload(b"Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+
+        // Keys longer than the block size are hashed first.
+        assert_eq!(
+            sign_payload(
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            ),
+            "sha256=60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    #[test]
+    fn parse_webhook_url_accepts_http_and_https_urls() {
+        for url in [
+            "https://ntfy.sh/budget",
+            "http://10.0.0.2/ntfy/budget",
+            "http://ntfy.local:8080/budget",
+            "https://[2001:db8::1]/hook",
+        ] {
+            assert!(parse_webhook_url(url).is_ok(), "{url}");
+        }
+
+        for url in ["ftp://ntfy.sh", "ntfy.sh", "", "not a url"] {
+            assert!(
+                matches!(parse_webhook_url(url), Err(WebhookError::InvalidUrl(_))),
+                "{url}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_webhook_url_rejects_local_targets() {
+        for url in [
+            "http://localhost:8080/hook",
+            "https://LOCALHOST./hook",
+            "http://ntfy.localhost/hook",
+            "http://127.0.0.1/hook",
+            "http://127.1.2.3/hook",
+            "http://0.0.0.0/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[::1]/hook",
+            "http://[::]/hook",
+            "http://[fe80::1]/hook",
+            "http://[::ffff:127.0.0.1]/hook",
+        ] {
+            assert!(
+                matches!(parse_webhook_url(url), Err(WebhookError::InvalidUrl(_))),
+                "{url}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn resolver_leaves_out_local_addresses() {
+        let name = Name::from_str("localhost").unwrap();
+
+        assert!(ReceiverResolver::default()
+            .call(name.clone())
+            .await
+            .is_err());
+
+        let addresses: Vec<_> = ReceiverResolver {
+            allow_local_receivers: true,
+        }
+        .call(name)
+        .await
+        .unwrap()
+        .collect();
+        assert!(addresses.iter().all(|address| address.ip().is_loopback()));
+    }
+
+    #[tokio::test]
+    async fn dispatcher_does_not_send_to_local_receivers_by_default() {
+        let server = MockServer::start(vec![]).await;
+
+        let result = WebhookDispatcher::default()
+            .send(&get_webhook(&server.url), WebhookEvent::Test, "{}".into())
+            .await;
+
+        assert!(matches!(result, Err(WebhookError::InvalidUrl(_))));
+        assert!(server.received().is_empty());
+    }
+
+    #[test]
+    fn build_payload_wraps_data() {
+        let body = build_payload(WebhookEvent::TransactionCreated, &json!({"id": 1})).unwrap();
+        let payload: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(payload["event"], "transaction.created");
+        assert_eq!(payload["data"], json!({"id": 1}));
+        assert!(payload["sent_at"].is_string());
+    }
+
+    #[tokio::test]
+    async fn deliver_sends_signed_payload() {
+        let server = MockServer::start(vec![]).await;
+        let body = build_payload(WebhookEvent::Test, &json!({})).unwrap();
+
+        get_dispatcher()
+            .deliver(&get_webhook(&server.url), WebhookEvent::Test, body.clone())
+            .await
+            .unwrap();
+
+        let received = server.received();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].body, body);
+        assert_eq!(received[0].headers[EVENT_HEADER], "test");
+        assert_eq!(
+            received[0].headers[SIGNATURE_HEADER],
+            sign_payload(b"secret", &body).as_str()
+        );
+        assert_eq!(received[0].headers["content-type"], "application/json");
+    }
+
+    #[tokio::test]
+    async fn deliver_retries_failed_attempts() {
+        let server = MockServer::start(vec![
+            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::SERVICE_UNAVAILABLE,
+        ])
+        .await;
+
+        get_dispatcher()
+            .deliver(&get_webhook(&server.url), WebhookEvent::Test, "{}".into())
+            .await
+            .unwrap();
+
+        assert_eq!(server.received().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn deliver_gives_up_after_max_attempts() {
+        let server = MockServer::start(vec![StatusCode::INTERNAL_SERVER_ERROR; 5]).await;
+
+        let result = get_dispatcher()
+            .deliver(&get_webhook(&server.url), WebhookEvent::Test, "{}".into())
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(WebhookError::Status(StatusCode::INTERNAL_SERVER_ERROR))
+        ));
+        assert_eq!(server.received().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn deliver_fails_when_receiver_is_down() {
+        // Nothing should be listening on the discard port.
+        let result = get_dispatcher()
+            .deliver(
+                &get_webhook("http://127.0.0.1:9/hook"),
+                WebhookEvent::Test,
+                "{}".into(),
+            )
+            .await;
+
+        assert!(matches!(result, Err(WebhookError::Request(_))));
+    }
+}